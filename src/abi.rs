@@ -14,6 +14,7 @@
 use crate::cpu::Cpu;
 use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr, SafeRead};
 use crate::Environment;
+use std::ops::RangeInclusive;
 
 /// The register number of the frame pointer in Apple's ABI.
 pub const FRAME_POINTER: usize = 7;
@@ -113,6 +114,11 @@ impl GuestFunction {
 /// [GuestFunction::call_without_pushing_stack_frame].
 pub trait CallFromGuest {
     fn call_from_guest(&self, env: &mut Environment);
+
+    /// How many words (registers or stack slots) of arguments this function
+    /// reads, not counting variadic arguments. This includes the hidden
+    /// pointer used for returning a large value.
+    fn arg_words(&self) -> usize;
 }
 
 macro_rules! impl_CallFromGuest {
@@ -139,6 +145,9 @@ macro_rules! impl_CallFromGuest {
                     retval.to_regs(env.cpu.regs_mut());
                 }
             }
+            fn arg_words(&self) -> usize {
+                usize::from(R::SIZE_IN_MEM.is_some()) $(+ <$P as GuestArg>::REG_COUNT)*
+            }
         }
         impl<R, $($P),*> CallFromGuest for fn(&mut Environment, $($P,)* DotDotDot) -> R
             where R: GuestRet, $($P: GuestArg,)* {
@@ -166,6 +175,9 @@ macro_rules! impl_CallFromGuest {
                     retval.to_regs(env.cpu.regs_mut());
                 }
             }
+            fn arg_words(&self) -> usize {
+                usize::from(R::SIZE_IN_MEM.is_some()) $(+ <$P as GuestArg>::REG_COUNT)*
+            }
         }
     }
 }
@@ -181,6 +193,70 @@ impl_CallFromGuest!(0 => P0, 1 => P1, 2 => P2, 3 => P3, 4 => P4, 5 => P5, 6 => P
 impl_CallFromGuest!(0 => P0, 1 => P1, 2 => P2, 3 => P3, 4 => P4, 5 => P5, 6 => P6, 7 => P7);
 impl_CallFromGuest!(0 => P0, 1 => P1, 2 => P2, 3 => P3, 4 => P4, 5 => P5, 6 => P6, 7 => P7, 8 => P8);
 
+/// Wrapper for a host function that also declares how many words of arguments
+/// the guest passes to it, according to the C prototype (e.g. a `double`
+/// counts as two words). Use the `arg_words = N` form of
+/// [crate::dyld::export_c_func] rather than constructing this directly.
+///
+/// In debug builds, every call checks that the Rust signature reads the
+/// declared number of words, and that any words passed on the stack are
+/// actually within the current thread's stack. A mismatched signature would
+/// otherwise silently read garbage registers.
+pub struct DeclaredArgWords<F> {
+    pub name: &'static str,
+    pub function: F,
+    pub arg_words: usize,
+}
+impl<F: CallFromGuest> CallFromGuest for DeclaredArgWords<F> {
+    fn call_from_guest(&self, env: &mut Environment) {
+        if cfg!(debug_assertions) {
+            let sp = env.cpu.regs()[Cpu::SP];
+            let stack = env.current_thread_stack();
+            if let Err(e) = check_arg_words(self.function.arg_words(), self.arg_words, sp, stack) {
+                panic!("Bad call to host function {}: {}", self.name, e);
+            }
+        }
+        self.function.call_from_guest(env)
+    }
+    fn arg_words(&self) -> usize {
+        self.function.arg_words()
+    }
+}
+
+/// Check a host function's arguments for [DeclaredArgWords]. `stack` is the
+/// current thread's stack, if known.
+fn check_arg_words(
+    signature_words: usize,
+    declared_words: usize,
+    sp: u32,
+    stack: Option<RangeInclusive<u32>>,
+) -> Result<(), String> {
+    if signature_words != declared_words {
+        return Err(format!(
+            "the Rust signature reads {} words of arguments, but {} were declared",
+            signature_words, declared_words
+        ));
+    }
+    if sp % 4 != 0 {
+        return Err(format!("the stack pointer ({:#x}) is misaligned", sp));
+    }
+    // After the fourth register is used, the arguments go on the stack.
+    let stack_words = declared_words.saturating_sub(4);
+    match stack {
+        Some(stack) if stack_words > 0 => {
+            let args_end = u64::from(sp) + 4 * stack_words as u64;
+            if !stack.contains(&sp) || args_end > u64::from(*stack.end()) + 1 {
+                return Err(format!(
+                    "{} words of arguments at {:#x} would extend beyond the stack",
+                    stack_words, sp
+                ));
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
 /// This trait represents a guest or host function that can be called from host
 /// code, but using the guest ABI. See [CallFromGuest], which this is the
 /// inverse of.
@@ -708,3 +784,42 @@ impl GuestRet for f64 {
         <u64 as GuestRet>::to_regs(self.to_bits(), regs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_words(_env: &mut Environment, _a: u32, _b: f32) {}
+    // The C prototype has a float argument, but it was declared as a double.
+    fn mismatched(_env: &mut Environment, _a: u32, _b: f64) -> f64 {
+        0.0
+    }
+
+    #[test]
+    fn test_declared_arg_words() {
+        let correct = DeclaredArgWords {
+            name: "_two_words",
+            function: two_words as fn(&mut Environment, _, _),
+            arg_words: 2,
+        };
+        let check = |f: &dyn CallFromGuest, declared, sp, stack| {
+            check_arg_words(f.arg_words(), declared, sp, stack)
+        };
+        assert_eq!(correct.arg_words(), 2);
+        assert!(check(&correct, 2, 0x1000, None).is_ok());
+
+        let mismatched = DeclaredArgWords {
+            name: "_mismatched",
+            function: mismatched as fn(&mut Environment, _, _) -> _,
+            arg_words: 2,
+        };
+        assert_eq!(mismatched.arg_words(), 3);
+        assert!(check(&mismatched, 2, 0x1000, None).is_err());
+
+        // Stack arguments must be within the stack.
+        assert!(check_arg_words(6, 6, 0xff8, Some(0x0..=0xfff)).is_ok());
+        assert!(check_arg_words(6, 6, 0xffc, Some(0x0..=0xfff)).is_err());
+        assert!(check_arg_words(6, 6, 0xfffffff8, Some(0x0..=0xffffffff)).is_ok());
+        assert!(check_arg_words(2, 2, 0x1002, None).is_err());
+    }
+}
@@ -106,7 +106,6 @@ fn enumerate_apps(apps_dir: &Path) -> Result<Vec<AppInfo>, std::io::Error> {
             }
         };
 
-        // TODO: what if this crashes?
         let display_name = bundle.display_name().to_owned();
 
         let icon = match bundle.load_icon(&fs) {
@@ -17,6 +17,25 @@ use plist::dictionary::Dictionary;
 use plist::Value;
 use std::io::Cursor;
 
+/// Parse the contents of an `Info.plist` file, checking for the keys that no
+/// app can do without.
+fn parse_info_plist(bytes: &[u8]) -> Result<Dictionary, String> {
+    let plist = Value::from_reader(Cursor::new(bytes))
+        .map_err(|e| format!("Could not deserialize Info.plist: {e}"))?;
+
+    let plist = plist
+        .into_dictionary()
+        .ok_or_else(|| "Info.plist root value is not a dictionary".to_string())?;
+
+    for key in ["CFBundleIdentifier", "CFBundleExecutable"] {
+        if plist.get(key).and_then(Value::as_string).is_none() {
+            return Err(format!("Info.plist has no {key} string"));
+        }
+    }
+
+    Ok(plist)
+}
+
 #[derive(Debug)]
 pub struct Bundle {
     path: GuestPathBuf,
@@ -30,18 +49,12 @@ impl Bundle {
         read_only_mode: bool,
     ) -> Result<(Bundle, Fs), String> {
         let plist_bytes = bundle_data.read_plist()?;
-
-        let plist = Value::from_reader(Cursor::new(plist_bytes))
-            .map_err(|_| "Could not deserialize plist data".to_string())?;
-
-        let plist = plist
-            .into_dictionary()
-            .ok_or_else(|| "plist root value is not a dictionary".to_string())?;
+        let plist = parse_info_plist(&plist_bytes)?;
 
         let bundle_name = format!(
             "{}.app",
-            if let Some(canonical) = plist.get("CFBundleName") {
-                canonical.as_string().unwrap()
+            if let Some(canonical) = plist.get("CFBundleName").and_then(Value::as_string) {
+                canonical
             } else {
                 bundle_data.bundle_name()
             }
@@ -74,8 +87,14 @@ impl Bundle {
         self.plist["CFBundleIdentifier"].as_string().unwrap()
     }
 
-    pub fn bundle_version(&self) -> &str {
-        self.plist["CFBundleVersion"].as_string().unwrap()
+    /// The parsed `Info.plist`. This is also what the main bundle's
+    /// `NSBundle` and `CFBundle` info dictionary is created from.
+    pub fn info_plist(&self) -> &Dictionary {
+        &self.plist
+    }
+
+    pub fn bundle_version(&self) -> Option<&str> {
+        self.plist.get("CFBundleVersion").and_then(Value::as_string)
     }
 
     pub fn bundle_localizations(&self) -> &[Value] {
@@ -88,9 +107,7 @@ impl Bundle {
 
     /// Canonical name for the bundle according to Info.plist
     pub fn canonical_bundle_name(&self) -> Option<&str> {
-        self.plist
-            .get("CFBundleName")
-            .map(|name| name.as_string().unwrap())
+        self.plist.get("CFBundleName").and_then(Value::as_string)
     }
 
     /// Name for the bundle, either the canonical name or, if there isn't one,
@@ -99,18 +116,39 @@ impl Bundle {
         self.path.file_name().unwrap().strip_suffix(".app").unwrap()
     }
 
+    /// Name shown on the home screen, falling back to the bundle name if
+    /// Info.plist doesn't specify one.
     pub fn display_name(&self) -> &str {
-        self.plist["CFBundleDisplayName"].as_string().unwrap()
+        self.plist
+            .get("CFBundleDisplayName")
+            .and_then(Value::as_string)
+            .unwrap_or_else(|| self.bundle_name())
     }
 
     pub fn minimum_os_version(&self) -> Option<&str> {
         self.plist
             .get("MinimumOSVersion")
-            .map(|v| v.as_string().unwrap())
+            .and_then(Value::as_string)
+    }
+
+    /// Values of `UIDeviceFamily`: 1 is iPhone and iPod touch, 2 is iPad.
+    /// Apps from before the iPad don't have this key and are for iPhone only.
+    pub fn device_families(&self) -> Vec<u64> {
+        let family = |value: &Value| match value {
+            Value::Integer(int) => int.as_unsigned(),
+            // Some apps use strings
+            Value::String(string) => string.parse().ok(),
+            _ => None,
+        };
+        match self.plist.get("UIDeviceFamily") {
+            None => vec![1],
+            Some(Value::Array(values)) => values.iter().filter_map(family).collect(),
+            Some(value) => family(value).into_iter().collect(),
+        }
     }
 
     pub fn executable_path(&self) -> GuestPathBuf {
-        // FIXME: Is this key optional? All iPhone apps seem to have it.
+        // Checked by parse_info_plist.
         self.path
             .join(self.plist["CFBundleExecutable"].as_string().unwrap())
     }
@@ -164,3 +202,35 @@ impl Bundle {
             .map(|v| v.as_string().unwrap())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_info_plist() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/TestApp.app");
+        let mut bundle_data = BundleData::open_host_dir(&path).unwrap();
+        let plist = parse_info_plist(&bundle_data.read_plist().unwrap()).unwrap();
+        assert_eq!(
+            plist["CFBundleIdentifier"].as_string(),
+            Some("com.yourcompany.TestApp")
+        );
+        assert_eq!(plist["CFBundleExecutable"].as_string(), Some("TestApp"));
+    }
+
+    #[test]
+    fn test_parse_bad_info_plist() {
+        assert!(parse_info_plist(b"not a plist").is_err());
+
+        let mut plist = Dictionary::new();
+        plist.insert("CFBundleExecutable".to_string(), "Foo".into());
+        let mut bytes = Vec::new();
+        Value::Dictionary(plist).to_writer_xml(&mut bytes).unwrap();
+        assert_eq!(
+            parse_info_plist(&bytes).unwrap_err(),
+            "Info.plist has no CFBundleIdentifier string"
+        );
+    }
+}
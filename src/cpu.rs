@@ -9,15 +9,37 @@
 //!
 //! iPhone OS apps used either ARMv6 or ARMv7-A, which are both 32-bit ISAs.
 //! For the moment, only ARMv6 has been tested.
+//!
+//! Memory model: all guest threads are run on a single host thread, one at a
+//! time, and a thread switch only happens between instructions. Every guest
+//! memory access is therefore seen by every other guest thread immediately and
+//! in program order, i.e. the guest gets sequential consistency for free.
+//! This is stronger than what real hardware provides, so memory barriers
+//! (`DMB`, `DSB`, `ISB` and their ARMv6 CP15 equivalents, and
+//! `OSMemoryBarrier()`) are accepted but don't need to do anything.
 
 use crate::abi::GuestFunction;
-use crate::mem::{ConstPtr, GuestUSize, Mem, MutPtr, Ptr, SafeRead, SafeWrite};
+use crate::mem::{guest_size_of, ConstPtr, GuestUSize, Mem, MutPtr, Ptr, SafeRead, SafeWrite};
 
 // Import functions from C++
 use touchHLE_dynarmic_wrapper::*;
 
 type VAddr = u32;
 
+thread_local! {
+    /// Address of the most recent memory access that failed, and whether it
+    /// failed only because it was misaligned, picked up by [Cpu::run_or_step].
+    static FAULT: std::cell::Cell<Option<(VAddr, bool)>> = std::cell::Cell::new(None);
+}
+
+/// Guard pages are only inaccessible to guest code, so unlike the null page,
+/// [Mem] doesn't check for them itself.
+fn check_guard_pages<T>(mem: &Mem, addr: VAddr) {
+    if mem.is_guard_page_access(addr, guest_size_of::<T>()) {
+        panic!("Attempted guard page access at {:#x}", addr);
+    }
+}
+
 fn touchHLE_cpu_read_impl<T: SafeRead + Default>(
     mem: *mut touchHLE_Mem,
     addr: VAddr,
@@ -36,11 +58,17 @@ fn touchHLE_cpu_read_impl<T: SafeRead + Default>(
     //
     // I'm not sure if this actually is unwind-safe, but considering
     // the emulator will crash anyway, maybe this is okay.
+    let mut misaligned = false;
     let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
         let mem = unsafe { &mut *mem.cast::<Mem>() };
+        check_guard_pages::<T>(mem, addr);
+        misaligned = mem.is_misaligned_access::<T>(addr);
         let ptr: ConstPtr<T> = Ptr::from_bits(addr);
         mem.read(ptr)
     }));
+    if res.is_err() {
+        FAULT.with(|fault| fault.set(Some((addr, misaligned))));
+    }
     unsafe {
         error.write(res.is_err());
     }
@@ -49,11 +77,17 @@ fn touchHLE_cpu_read_impl<T: SafeRead + Default>(
 
 fn touchHLE_cpu_write_impl<T: SafeWrite>(mem: *mut touchHLE_Mem, addr: VAddr, value: T) -> bool {
     // See comments above about catch_unwind
+    let mut misaligned = false;
     let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
         let mem = unsafe { &mut *mem.cast::<Mem>() };
+        check_guard_pages::<T>(mem, addr);
+        misaligned = mem.is_misaligned_access::<T>(addr);
         let ptr: MutPtr<T> = Ptr::from_bits(addr);
         mem.write(ptr, value)
     }));
+    if res.is_err() {
+        FAULT.with(|fault| fault.set(Some((addr, misaligned))));
+    }
     res.is_err()
 }
 
@@ -96,6 +130,10 @@ pub struct Cpu {
     /// Copy of the direct memory access pointer used to check it has not
     /// changed. If this is null, direct memory access is not in use.
     direct_memory_access_ptr: *const std::ffi::c_void,
+    /// See [Self::fault_address].
+    fault_address: Option<VAddr>,
+    /// See [Self::undefined_instruction].
+    undefined_instruction: Option<(VAddr, u32)>,
 }
 
 impl Drop for Cpu {
@@ -138,10 +176,90 @@ pub enum CpuState {
 pub enum CpuError {
     /// Memory error during execution (probably a null page access).
     MemoryError,
-    /// Undefined instruction (perhaps from a GDB software breakpoint).
+    /// Access to a stack guard page, presumably because a thread's stack
+    /// overflowed.
+    StackOverflow,
+    /// Misaligned memory access, which is only an error if
+    /// [Mem::enable_strict_alignment] was used.
+    MisalignedAccess,
+    /// Undefined instruction (perhaps from a GDB software breakpoint). See
+    /// [Cpu::undefined_instruction].
     UndefinedInstruction,
     /// Breakpoint (`bkpt` instruction).
     Breakpoint,
+    /// SVC instruction that wasn't written by the dynamic linker, e.g. a
+    /// system call made directly by the app. See [crate::dyld::Dyld].
+    UnexpectedSvc(u32),
+    /// Privileged instruction executed in user mode, which apps always run in.
+    /// The name of the instruction is included. See
+    /// [Cpu::undefined_instruction] for its address and encoding.
+    PrivilegedInstruction(&'static str),
+}
+
+/// Encodings of the privileged instructions, as `(mask, value, name)`, for
+/// [privileged_instruction_name]. These are only checked for once dynarmic has
+/// refused to execute an instruction. Writes to the privileged bits of the CPSR
+/// with `MSR` aren't included because, like real hardware in user mode,
+/// dynarmic ignores them, and it may treat some others the same way.
+const PRIVILEGED_ARM_INSTRUCTIONS: &[(u32, u32, &str)] = &[
+    (0xfff1fe20, 0xf1000000, "CPS"),
+    (0xfe5fffe0, 0xf84d0500, "SRS"),
+    (0xfe50ffff, 0xf8100a00, "RFE"),
+    (0x0fff0fff, 0x014f0000, "MRS from SPSR"),
+    (0x0ff0fff0, 0x0160f000, "MSR to SPSR"),
+    (0x0ff0f000, 0x0360f000, "MSR to SPSR"),
+    (
+        0x0e500000,
+        0x08500000,
+        "LDM with user registers or exception return",
+    ),
+    (0x0e500000, 0x08400000, "STM with user registers"),
+];
+/// Like [PRIVILEGED_ARM_INSTRUCTIONS], for Thumb. A 32-bit instruction's first
+/// halfword is in the top 16 bits, as in [Cpu::undefined_instruction].
+const PRIVILEGED_THUMB_INSTRUCTIONS: &[(u32, u32, &str)] = &[
+    (0xffffffe8, 0x0000b660, "CPS"),
+    (0xfffff800, 0xf3af8000, "CPS"), // unless it's a hint, see below
+    (0xffdfffe0, 0xe80dc000, "SRS"),
+    (0xffdfffe0, 0xe98dc000, "SRS"),
+    (0xffd0ffff, 0xe810c000, "RFE"),
+    (0xffd0ffff, 0xe990c000, "RFE"),
+    (0xfffff0ff, 0xf3ff8000, "MRS from SPSR"),
+    (0xfff0f0ff, 0xf3908000, "MSR to SPSR"),
+    (0xffffff00, 0xf3de8f00, "SUBS PC, LR"),
+];
+
+/// If an instruction is one that can only be used in privileged modes, get its
+/// name.
+fn privileged_instruction_name(thumb: bool, encoding: u32) -> Option<&'static str> {
+    if thumb {
+        // The 32-bit CPS shares its encoding with the hint instructions (NOP,
+        // YIELD, etc), which are the cases where imod and M are both zero.
+        if encoding & 0xfffff800 == 0xf3af8000 && encoding & 0x700 == 0 {
+            return None;
+        }
+        return PRIVILEGED_THUMB_INSTRUCTIONS
+            .iter()
+            .find(|&&(mask, value, _)| encoding & mask == value)
+            .map(|&(_, _, name)| name);
+    }
+
+    let unconditional = encoding >> 28 == 0b1111;
+    if let Some(&(_, _, name)) = PRIVILEGED_ARM_INSTRUCTIONS
+        .iter()
+        .filter(|&&(mask, _, _)| unconditional == (mask >> 28 == 0b1111))
+        .find(|&&(mask, value, _)| encoding & mask == value)
+    {
+        return Some(name);
+    }
+    // A data-processing instruction that sets the flags and writes to the PC,
+    // e.g. `SUBS PC, LR, #4`, copies the SPSR to the CPSR. Multiplies and
+    // extra loads and stores (bits 25, 7 and 4 being 0, 1 and 1) are excluded.
+    let is_data_processing = encoding & 0x0c000000 == 0 && encoding & 0x02000090 != 0x90;
+    if !unconditional && is_data_processing && encoding & 0x0010f000 == 0x0010f000 {
+        return Some("data-processing exception return (e.g. SUBS PC, LR)");
+    }
+    None
 }
 
 impl Cpu {
@@ -168,9 +286,12 @@ impl Cpu {
         // page aligned.
         let null_page_count: usize = direct_memory_access
             .as_ref()
-            .map_or(0, |mem| mem.null_segment_size() / 0x1000)
+            .map_or(0, |mem| mem.null_segment_size() / Mem::PAGE_SIZE)
             .try_into()
             .unwrap();
+        let guard_pages: Vec<VAddr> = direct_memory_access
+            .as_ref()
+            .map_or(Vec::new(), |mem| mem.guard_pages().collect());
         // Safety: the direct memory access pointer will be retained directly by
         // the dynarmic wrapper and indirectly by cached JIT code, so we must
         // ensure we only execute the CPU while holding a &mut on the Mem object
@@ -181,9 +302,57 @@ impl Cpu {
             });
         let dynarmic_wrapper =
             unsafe { touchHLE_DynarmicWrapper_new(direct_memory_access_ptr, null_page_count) };
-        Cpu {
+        let mut cpu = Cpu {
             dynarmic_wrapper,
             direct_memory_access_ptr,
+            fault_address: None,
+            undefined_instruction: None,
+        };
+        for page in guard_pages {
+            cpu.set_page_trapped(page, true);
+        }
+        cpu
+    }
+
+    /// Make guest accesses to a page go through [Mem], so that a guard page
+    /// (see [Mem::add_guard_page]) is respected, or undo that.
+    pub fn set_page_trapped(&mut self, page: VAddr, trapped: bool) {
+        assert!(page % Mem::PAGE_SIZE == 0);
+        unsafe {
+            touchHLE_DynarmicWrapper_set_page_trapped(
+                self.dynarmic_wrapper,
+                page / Mem::PAGE_SIZE,
+                trapped,
+            )
+        }
+    }
+
+    /// The address whose access caused the most recent
+    /// [CpuError::MemoryError], [CpuError::StackOverflow] or
+    /// [CpuError::MisalignedAccess], if known.
+    pub fn fault_address(&self) -> Option<VAddr> {
+        self.fault_address
+    }
+
+    /// The address and encoding of the most recent undefined instruction. For
+    /// a 32-bit Thumb instruction, the first halfword is in the top 16 bits.
+    pub fn undefined_instruction(&self) -> Option<(VAddr, u32)> {
+        self.undefined_instruction
+    }
+
+    fn read_instruction(&self, mem: &Mem, addr: VAddr) -> u32 {
+        if (self.cpsr() & Self::CPSR_THUMB) == 0 {
+            let ptr: ConstPtr<u32> = Ptr::from_bits(addr);
+            return mem.read(ptr);
+        }
+        let ptr: ConstPtr<u16> = Ptr::from_bits(addr);
+        let first = mem.read(ptr);
+        // 32-bit Thumb instructions start with 0b11101, 0b11110 or 0b11111.
+        if (first >> 11) >= 0b11101 {
+            let second = mem.read(ptr + 1);
+            ((first as u32) << 16) | (second as u32)
+        } else {
+            first as u32
         }
     }
 
@@ -298,11 +467,214 @@ impl Cpu {
         };
         match res {
             -1 => CpuState::Normal,
-            -2 => CpuState::Error(CpuError::MemoryError),
-            -3 => CpuState::Error(CpuError::UndefinedInstruction),
+            -2 => {
+                let fault = FAULT.with(|fault| fault.take());
+                self.fault_address = fault.map(|(addr, _)| addr);
+                match fault {
+                    Some((addr, _)) if mem.is_guard_page_access(addr, 1) => {
+                        CpuState::Error(CpuError::StackOverflow)
+                    }
+                    Some((_, true)) => CpuState::Error(CpuError::MisalignedAccess),
+                    _ => CpuState::Error(CpuError::MemoryError),
+                }
+            }
+            // -3 is an undefined instruction, -5 is an instruction dynarmic
+            // wanted an interpreter for, which are handled the same way.
+            -3 | -5 => {
+                let pc = unsafe { touchHLE_DynarmicWrapper_exception_pc(self.dynarmic_wrapper) };
+                let encoding = self.read_instruction(mem, pc);
+                self.undefined_instruction = Some((pc, encoding));
+                let thumb = (self.cpsr() & Self::CPSR_THUMB) != 0;
+                if res == -5 {
+                    // Unlike for an undefined instruction, the PC is still
+                    // pointing at the instruction.
+                    let len = if !thumb || encoding > 0xffff { 4 } else { 2 };
+                    self.regs_mut()[Self::PC] = pc + len;
+                }
+                if let Some(name) = privileged_instruction_name(thumb, encoding)
+                    // 0x1f is the mode field
+                    .filter(|_| self.cpsr() & 0x1f == Self::CPSR_USER_MODE)
+                {
+                    CpuState::Error(CpuError::PrivilegedInstruction(name))
+                } else {
+                    CpuState::Error(CpuError::UndefinedInstruction)
+                }
+            }
             -4 => CpuState::Error(CpuError::Breakpoint),
-            _ if res < -4 => panic!("Unexpected CPU execution result"),
+            _ if res < -5 => panic!("Unexpected CPU execution result"),
             svc => CpuState::Svc(svc as u32),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undefined_instruction() {
+        // `udf #1`
+        const UDF_1: u32 = 0xe7f000f1;
+
+        let mut mem = Mem::new();
+        let code: MutPtr<u32> = mem.alloc(4).cast();
+        mem.write(code, UDF_1);
+        let code_addr = code.to_bits();
+
+        let mut cpu = Cpu::new(Some(&mut mem));
+        cpu.branch(GuestFunction::from_addr_with_thumb_bit(code_addr));
+
+        let state = cpu.run_or_step(&mut mem, None);
+        assert!(matches!(
+            state,
+            CpuState::Error(CpuError::UndefinedInstruction)
+        ));
+        assert_eq!(cpu.undefined_instruction(), Some((code_addr, UDF_1)));
+    }
+
+    #[test]
+    fn test_memory_barriers() {
+        const BARRIERS: &[u32] = &[
+            0xf57ff05f, // dmb sy
+            0xf57ff04f, // dsb sy
+            0xf57ff06f, // isb sy
+            0xee070fba, // mcr p15, 0, r0, c7, c10, 5 (ARMv6 DMB)
+            0xee070f9a, // mcr p15, 0, r0, c7, c10, 4 (ARMv6 DSB)
+            0xee070f95, // mcr p15, 0, r0, c7, c5, 4 (ARMv6 ISB)
+        ];
+
+        let mut mem = Mem::new();
+        let code: MutPtr<u32> = mem
+            .alloc(guest_size_of::<u32>() * BARRIERS.len() as u32)
+            .cast();
+        for (i, &barrier) in BARRIERS.iter().enumerate() {
+            mem.write(code + i as GuestUSize, barrier);
+        }
+        let code_addr = code.to_bits();
+
+        let mut cpu = Cpu::new(Some(&mut mem));
+        cpu.branch(GuestFunction::from_addr_with_thumb_bit(code_addr));
+
+        for i in 0..BARRIERS.len() {
+            let state = cpu.run_or_step(&mut mem, None);
+            assert!(matches!(state, CpuState::Normal), "{:?}", state);
+            assert_eq!(cpu.regs()[Cpu::PC], code_addr + 4 * (i as u32 + 1));
+        }
+    }
+
+    #[test]
+    fn test_guard_page_access() {
+        // `ldr r0, [r1]`
+        const LDR: u32 = 0xe5910000;
+
+        let mut mem = Mem::new();
+        let code: MutPtr<u32> = mem.alloc(4).cast();
+        mem.write(code, LDR);
+        let stack = mem.alloc(Mem::PAGE_SIZE * 3);
+        let guard_page = (stack.to_bits() + Mem::PAGE_SIZE - 1) & !(Mem::PAGE_SIZE - 1);
+        mem.add_guard_page(guard_page);
+
+        let mut cpu = Cpu::new(Some(&mut mem));
+        cpu.branch(GuestFunction::from_addr_with_thumb_bit(code.to_bits()));
+        cpu.regs_mut()[1] = guard_page + 0x100;
+        let state = cpu.run_or_step(&mut mem, None);
+        assert!(
+            matches!(state, CpuState::Error(CpuError::StackOverflow)),
+            "{:?}",
+            state
+        );
+        assert_eq!(cpu.fault_address(), Some(guard_page + 0x100));
+    }
+
+    #[test]
+    fn test_strict_alignment() {
+        // `ldr r0, [r1]`
+        const LDR: u32 = 0xe5910000;
+
+        let run = |strict_alignment: bool| {
+            let mut mem = Mem::new();
+            if strict_alignment {
+                mem.enable_strict_alignment();
+            }
+            let code: MutPtr<u32> = mem.alloc(4).cast();
+            mem.write(code, LDR);
+            let data: MutPtr<u32> = mem.alloc(8).cast();
+            mem.write(data, 0x33221100);
+            mem.write(data + 1, 0x77665544);
+            let misaligned_addr = data.to_bits() + 1;
+
+            // Strict alignment only applies with the memory access callbacks.
+            let mut cpu = Cpu::new(None);
+            cpu.branch(GuestFunction::from_addr_with_thumb_bit(code.to_bits()));
+            cpu.regs_mut()[1] = misaligned_addr;
+            let state = cpu.run_or_step(&mut mem, None);
+            (state, cpu.regs()[0], cpu.fault_address(), misaligned_addr)
+        };
+
+        let (state, r0, _, _) = run(false);
+        assert!(matches!(state, CpuState::Normal), "{:?}", state);
+        assert_eq!(r0, 0x44332211);
+
+        let (state, _, fault_address, misaligned_addr) = run(true);
+        assert!(matches!(state, CpuState::Error(CpuError::MisalignedAccess)));
+        assert_eq!(fault_address, Some(misaligned_addr));
+    }
+
+    #[test]
+    fn test_privileged_instruction_name() {
+        let arm = |encoding| privileged_instruction_name(false, encoding);
+        let thumb = |encoding| privileged_instruction_name(true, encoding);
+
+        assert_eq!(arm(0xf10c0080), Some("CPS")); // cpsid i
+        assert_eq!(arm(0xf8ed0513), Some("SRS")); // srsia sp!, #0x13
+        assert_eq!(arm(0xf8bd0a00), Some("RFE")); // rfeia sp!
+        assert_eq!(arm(0xe14f0000), Some("MRS from SPSR")); // mrs r0, spsr
+        assert_eq!(arm(0xe169f000), Some("MSR to SPSR")); // msr spsr_fc, r0
+        assert_eq!(
+            arm(0xe8fd8000), // ldm sp!, {pc}^
+            Some("LDM with user registers or exception return")
+        );
+        assert!(arm(0xe25ef004).is_some()); // subs pc, lr, #4
+        assert_eq!(arm(0xe10f0000), None); // mrs r0, cpsr
+        assert_eq!(arm(0xe129f000), None); // msr cpsr_fc, r0
+        assert_eq!(arm(0xe8bd8000), None); // pop {pc}
+        assert!(arm(0xe1b0f00e).is_some()); // movs pc, lr
+        assert_eq!(arm(0xe1a0f00e), None); // mov pc, lr
+        assert_eq!(arm(0xe1dff0b0), None); // ldrh pc, [pc]
+
+        assert_eq!(thumb(0xb672), Some("CPS")); // cpsid i
+        assert_eq!(thumb(0xf3af8620), Some("CPS")); // cpsid.w i
+        assert_eq!(thumb(0xf3de8f04), Some("SUBS PC, LR")); // subs pc, lr, #4
+        assert_eq!(thumb(0xf3ff8000), Some("MRS from SPSR")); // mrs r0, spsr
+        assert_eq!(thumb(0xf3af8000), None); // nop.w
+        assert_eq!(thumb(0xbf00), None); // nop
+        assert_eq!(thumb(0xb500), None); // push {lr}
+    }
+
+    #[test]
+    fn test_privileged_instruction_in_user_mode() {
+        const CPSID_I: u32 = 0xf10c0080;
+
+        let mut mem = Mem::new();
+        let code: MutPtr<u32> = mem.alloc(4).cast();
+        mem.write(code, CPSID_I);
+        let code_addr = code.to_bits();
+
+        let mut cpu = Cpu::new(Some(&mut mem));
+        cpu.set_cpsr(Cpu::CPSR_USER_MODE);
+        cpu.branch(GuestFunction::from_addr_with_thumb_bit(code_addr));
+
+        let state = cpu.run_or_step(&mut mem, None);
+        assert!(
+            matches!(
+                state,
+                CpuState::Error(CpuError::PrivilegedInstruction("CPS"))
+            ),
+            "{:?}",
+            state
+        );
+        assert_eq!(cpu.undefined_instruction(), Some((code_addr, CPSID_I)));
+        // Like for an undefined instruction, the PC is after the instruction.
+        assert_eq!(cpu.regs()[Cpu::PC], code_addr + 4);
+    }
+}
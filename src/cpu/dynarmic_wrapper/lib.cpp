@@ -32,6 +32,7 @@ bool touchHLE_cpu_write_u64(touchHLE_Mem *mem, VAddr addr, std::uint64_t value);
 const auto HaltReasonSvc = Dynarmic::HaltReason::UserDefined1;
 const auto HaltReasonUndefinedInstruction = Dynarmic::HaltReason::UserDefined2;
 const auto HaltReasonBreakpoint = Dynarmic::HaltReason::UserDefined3;
+const auto HaltReasonInterpreterFallback = Dynarmic::HaltReason::UserDefined4;
 
 class Environment final : public Dynarmic::A32::UserCallbacks {
 public:
@@ -39,6 +40,7 @@ public:
   touchHLE_Mem *mem = nullptr;
   std::uint64_t ticks_remaining;
   uint32_t halting_svc;
+  VAddr exception_pc;
 
 private:
   std::uint8_t MemoryRead8(VAddr vaddr) override {
@@ -133,14 +135,20 @@ private:
     abort();
   }
 
-  void InterpreterFallback(std::uint32_t, size_t) override {
-    abort(); // TODO
+  // dynarmic asks for an interpreter to execute the instructions it doesn't
+  // implement, which are mostly system instructions that apps can't use in
+  // user mode anyway. There is no interpreter, so this is reported to the
+  // Rust side, which decides what went wrong (see src/cpu.rs).
+  void InterpreterFallback(VAddr pc, size_t) override {
+    exception_pc = pc;
+    cpu->HaltExecution(HaltReasonInterpreterFallback);
   }
   void CallSVC(std::uint32_t svc) override {
     halting_svc = svc;
     cpu->HaltExecution(HaltReasonSvc);
   }
   void ExceptionRaised(VAddr pc, Dynarmic::A32::Exception exception) override {
+    exception_pc = pc;
     // MemoryReadCode returned nullopt
     if (exception == Dynarmic::A32::Exception::NoExecuteFault) {
       cpu->HaltExecution(Dynarmic::HaltReason::MemoryAbort);
@@ -173,10 +181,17 @@ public:
   CallbackOrAccessOneWord CompileSendOneWord(bool two, unsigned opc1,
                                              CoprocReg CRn, CoprocReg CRm,
                                              unsigned opc2) override {
-    // Corresponds to `coproc_moveto_Data_Memory_Barrier(0)` according
-    // to the Ghidra or `mcr p15,0x0,lr,cr7,cr10,0x5` in the assembly
-    if (!two && CRn == CoprocReg::C7 && opc1 == 0 && CRm == CoprocReg::C10 &&
-        opc2 == 5) {
+    // These are the ARMv6 barrier operations, which were replaced by the
+    // DMB, DSB and ISB instructions in ARMv7:
+    // - `mcr p15,0x0,rX,cr7,cr10,0x5`: Data Memory Barrier
+    //   (`coproc_moveto_Data_Memory_Barrier(0)` in Ghidra)
+    // - `mcr p15,0x0,rX,cr7,cr10,0x4`: Data Synchronization Barrier
+    // - `mcr p15,0x0,rX,cr7,cr5,0x4`: Instruction Synchronization Barrier
+    // All guest threads run on a single host thread, so these don't need to
+    // do anything (see src/cpu.rs).
+    if (!two && CRn == CoprocReg::C7 && opc1 == 0 &&
+        ((CRm == CoprocReg::C10 && (opc2 == 5 || opc2 == 4)) ||
+         (CRm == CoprocReg::C5 && opc2 == 4))) {
       // just return the address to be used as storage
       return &addr;
     }
@@ -219,9 +234,11 @@ class DynarmicWrapper {
   std::unique_ptr<Dynarmic::ExclusiveMonitor> mon;
   std::array<std::uint8_t *, Dynarmic::A32::UserConfig::NUM_PAGE_TABLE_ENTRIES>
       page_table;
+  std::uint8_t *direct_memory_access_ptr;
 
 public:
-  DynarmicWrapper(void *direct_memory_access_ptr, size_t null_page_count) {
+  DynarmicWrapper(void *direct_memory_access_ptr, size_t null_page_count)
+      : direct_memory_access_ptr((std::uint8_t *)direct_memory_access_ptr) {
     Dynarmic::A32::UserConfig user_config;
     user_config.callbacks = &env;
     user_config.coprocessors[15] = std::make_shared<ArmDynarmicCP15>();
@@ -254,6 +271,8 @@ public:
     env.cpu = cpu.get();
   }
 
+  VAddr exception_pc() const { return env.exception_pc; }
+
   const std::uint32_t *regs() const { return &cpu->Regs().front(); }
   std::uint32_t *regs() { return &cpu->Regs().front(); }
 
@@ -264,6 +283,16 @@ public:
     cpu->InvalidateCacheRange(start, size);
   }
 
+  // Make accesses to a page fall back to a memory callback (like the null
+  // page), or stop doing so. This is used for stack guard pages.
+  void set_page_trapped(std::uint32_t page, bool trapped) {
+    if (!direct_memory_access_ptr) {
+      // Every access uses a memory callback already.
+      return;
+    }
+    page_table.at(page) = trapped ? nullptr : direct_memory_access_ptr;
+  }
+
   void swap_context(void *context) {
     Dynarmic::A32::Context tmp = cpu->SaveContext();
     cpu->LoadContext(*(Dynarmic::A32::Context *)context);
@@ -288,6 +317,8 @@ public:
       res = -3;
     } else if (Dynarmic::Has(hr, HaltReasonBreakpoint)) {
       res = -4;
+    } else if (Dynarmic::Has(hr, HaltReasonInterpreterFallback)) {
+      res = -5;
     } else if (Dynarmic::Has(hr, HaltReasonSvc)) {
       res = std::int32_t(env.halting_svc);
     } else {
@@ -326,6 +357,16 @@ void touchHLE_DynarmicWrapper_set_cpsr(DynarmicWrapper *cpu,
   cpu->set_cpsr(cpsr);
 }
 
+VAddr touchHLE_DynarmicWrapper_exception_pc(const DynarmicWrapper *cpu) {
+  return cpu->exception_pc();
+}
+
+void touchHLE_DynarmicWrapper_set_page_trapped(DynarmicWrapper *cpu,
+                                               std::uint32_t page,
+                                               bool trapped) {
+  cpu->set_page_trapped(page, trapped);
+}
+
 void touchHLE_DynarmicWrapper_swap_context(DynarmicWrapper *cpu,
                                            void *context) {
   cpu->swap_context(context);
@@ -35,6 +35,12 @@ extern "C" {
     pub fn touchHLE_DynarmicWrapper_regs_mut(cpu: *mut touchHLE_DynarmicWrapper) -> *mut u32;
     pub fn touchHLE_DynarmicWrapper_cpsr(cpu: *const touchHLE_DynarmicWrapper) -> u32;
     pub fn touchHLE_DynarmicWrapper_set_cpsr(cpu: *mut touchHLE_DynarmicWrapper, cpsr: u32);
+    pub fn touchHLE_DynarmicWrapper_exception_pc(cpu: *const touchHLE_DynarmicWrapper) -> VAddr;
+    pub fn touchHLE_DynarmicWrapper_set_page_trapped(
+        cpu: *mut touchHLE_DynarmicWrapper,
+        page: u32,
+        trapped: bool,
+    );
     pub fn touchHLE_DynarmicWrapper_swap_context(
         cpu: *mut touchHLE_DynarmicWrapper,
         context: *mut Dynarmic_A32_Context,
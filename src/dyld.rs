@@ -26,11 +26,11 @@ mod function_lists;
 use crate::abi::{CallFromGuest, GuestFunction};
 use crate::cpu::Cpu;
 use crate::frameworks::foundation::ns_string;
-use crate::mach_o::{MachO, SectionType};
-use crate::mem::{ConstVoidPtr, GuestUSize, Mem, MutPtr, Ptr};
+use crate::mach_o::{MachO, Section, SectionType};
+use crate::mem::{ConstVoidPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr};
 use crate::objc::{nil, ObjC};
 use crate::Environment;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub type HostFunction = &'static dyn CallFromGuest;
 
@@ -79,6 +79,14 @@ pub type FunctionExports = &'static [(&'static str, HostFunction)];
 /// defaults to a different type than a pure fn pointer, which is the type that
 /// [CallFromGuest] is implemented on. This macro will do the casting for you,
 /// but you will need to supply an underscore for each parameter.
+///
+/// Optionally, the number of words of arguments in the C prototype can be
+/// declared too, so that debug builds can check the Rust signature matches it
+/// (see [crate::abi::DeclaredArgWords]):
+///
+/// ```ignore
+/// export_c_func!(pow(_, _), arg_words = 4)
+/// ```
 #[macro_export]
 macro_rules! export_c_func {
     ($name:ident ($($_:ty),*)) => {
@@ -87,6 +95,16 @@ macro_rules! export_c_func {
             &($name as fn(&mut $crate::Environment, $($_),*) -> _)
         )
     };
+    ($name:ident ($($_:ty),*), arg_words = $arg_words:literal) => {
+        (
+            concat!("_", stringify!($name)),
+            &$crate::abi::DeclaredArgWords {
+                name: concat!("_", stringify!($name)),
+                function: $name as fn(&mut $crate::Environment, $($_),*) -> _,
+                arg_words: $arg_words,
+            }
+        )
+    };
 }
 pub use crate::export_c_func; // #[macro_export] is weird...
 
@@ -141,6 +159,27 @@ pub fn search_lists<T>(
         .find(|&(sym, _)| *sym == symbol)
 }
 
+/// Where an imported symbol would be linked from. See [Dyld::import_report].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportSource<'a> {
+    /// touchHLE's own implementation: a host function, a constant, an
+    /// Objective-C class, or something the linker handles specially.
+    Host,
+    /// A symbol exported by one of the loaded binaries (named here).
+    Binary(&'a str),
+    /// Nothing provides this symbol.
+    Unsatisfied,
+}
+
+/// The imports of one binary and where they would be linked from. See
+/// [Dyld::import_report].
+#[derive(Debug)]
+pub struct ImportReport<'a> {
+    pub bin_name: &'a str,
+    /// Sorted by symbol name.
+    pub imports: Vec<(&'a str, ImportSource<'a>)>,
+}
+
 fn encode_a32_svc(imm: u32) -> u32 {
     assert!(imm & 0xff000000 == 0);
     imm | 0xef000000
@@ -167,6 +206,18 @@ fn write_return_to_host_routine(mem: &mut Mem, svc: u32) -> GuestFunction {
     assert!(!ptr.is_thumb());
     ptr
 }
+/// What to do about an SVC instruction, as determined by
+/// [Dyld::get_svc_handler].
+pub enum SvcHandler {
+    /// Call this host function, then continue after the SVC.
+    HostFunction(HostFunction),
+    /// The SVC has been overwritten, so execution needs to resume at its
+    /// address.
+    Restart,
+    /// The SVC is not one the linker handles. This is a bug in the emulator.
+    Unexpected,
+}
+
 pub struct Dyld {
     /// List of host functions that have been "linked" and had SVCs assigned.
     ///
@@ -175,16 +226,28 @@ pub struct Dyld {
     linked_host_functions: Vec<(&'static str, HostFunction)>,
     return_to_host_routine: Option<GuestFunction>,
     thread_exit_routine: Option<GuestFunction>,
-    constants_to_link_later: Vec<(MutPtr<ConstVoidPtr>, &'static HostConstant)>,
+    /// Pointers to host constants to write once there's an [Environment] (see
+    /// [Self::do_late_linking]), with the offset to add to each.
+    constants_to_link_later: Vec<(MutPtr<ConstVoidPtr>, u32, &'static HostConstant)>,
     non_lazy_host_functions: HashMap<&'static str, GuestFunction>,
+    /// Addresses of the SVC instructions written by the linker. The app might
+    /// contain SVC instructions of its own (e.g. system calls), which must not
+    /// be mistaken for ours.
+    svc_addrs: HashSet<u32>,
+    /// Handles returned by `dlopen()` for the libraries in
+    /// [function_lists::LIBRARIES], keyed by install path. Each handle points
+    /// to a copy of the path, which is handy when debugging.
+    library_handles: HashMap<&'static str, MutVoidPtr>,
 }
 
 impl Dyld {
     /// We reserve this SVC ID for invoking the lazy linker.
     pub const SVC_LAZY_LINK: u32 = 0;
-    /// We reserve this SVC ID for the exit routine for spawned threads.
+    /// We reserve this SVC ID for the exit routine for spawned threads. Like
+    /// [Self::SVC_RETURN_TO_HOST], it's handled by [crate::Environment].
     pub const SVC_THREAD_EXIT: u32 = 1;
-    /// We reserve this SVC ID for the special return-to-host routine.
+    /// We reserve this SVC ID for the special return-to-host routine. It's
+    /// handled by [crate::Environment], not [Self::get_svc_handler].
     pub const SVC_RETURN_TO_HOST: u32 = 2;
     /// The range of SVC IDs `SVC_LINKED_FUNCTIONS_BASE..` is used to reference
     /// [Self::linked_host_functions] entries.
@@ -193,6 +256,21 @@ impl Dyld {
     const SYMBOL_STUB_INSTRUCTIONS: [u32; 2] = [0xe59fc000, 0xe59cf000];
     const PIC_SYMBOL_STUB_INSTRUCTIONS: [u32; 3] = [0xe59fc004, 0xe08fc00c, 0xe59cf000];
 
+    /// Get the original instructions of each stub in a `__symbol_stub`
+    /// section, which depend on its entry size. Panics with a diagnostic if
+    /// the entry size is not one we know about.
+    fn symbol_stub_instructions(bin: &MachO, stubs: &Section) -> &'static [u32] {
+        let entry_size = stubs.dyld_indirect_symbol_info.as_ref().unwrap().entry_size;
+        match entry_size {
+            12 => Self::SYMBOL_STUB_INSTRUCTIONS.as_slice(),
+            16 => Self::PIC_SYMBOL_STUB_INSTRUCTIONS.as_slice(),
+            _ => panic!(
+                "Unsupported symbol stub entry size {} (expected 12 or 16) in section {} of {}",
+                entry_size, stubs.name, bin.name
+            ),
+        }
+    }
+
     pub fn new() -> Dyld {
         Dyld {
             linked_host_functions: Vec::new(),
@@ -200,6 +278,8 @@ impl Dyld {
             thread_exit_routine: None,
             constants_to_link_later: Vec::new(),
             non_lazy_host_functions: HashMap::new(),
+            svc_addrs: HashSet::new(),
+            library_handles: HashMap::new(),
         }
     }
 
@@ -211,14 +291,33 @@ impl Dyld {
         self.thread_exit_routine.unwrap()
     }
 
+    /// Whether the SVC instruction at `svc_pc` was written by the linker, as
+    /// opposed to being part of the app. Only the former should be passed to
+    /// [Self::get_svc_handler].
+    pub fn is_linker_svc(&self, svc_pc: u32) -> bool {
+        self.svc_addrs.contains(&svc_pc)
+    }
+
+    fn write_svc(&mut self, mem: &mut Mem, ptr: MutPtr<u32>, svc: u32) {
+        mem.write(ptr, encode_a32_svc(svc));
+        self.svc_addrs.insert(ptr.to_bits());
+    }
+
+    fn write_return_to_host_routine(&mut self, mem: &mut Mem, svc: u32) -> GuestFunction {
+        let routine = write_return_to_host_routine(mem, svc);
+        self.svc_addrs.insert(routine.addr_without_thumb_bit());
+        routine
+    }
+
     /// Do linking-related tasks that need doing right after loading the
     /// binaries.
     pub fn do_initial_linking(&mut self, bins: &[MachO], mem: &mut Mem, objc: &mut ObjC) {
         assert!(self.return_to_host_routine.is_none());
         assert!(self.thread_exit_routine.is_none());
         self.return_to_host_routine =
-            Some(write_return_to_host_routine(mem, Self::SVC_RETURN_TO_HOST));
-        self.thread_exit_routine = Some(write_return_to_host_routine(mem, Self::SVC_THREAD_EXIT));
+            Some(self.write_return_to_host_routine(mem, Self::SVC_RETURN_TO_HOST));
+        self.thread_exit_routine =
+            Some(self.write_return_to_host_routine(mem, Self::SVC_THREAD_EXIT));
 
         // Currently assuming only the app binary contains Objective-C things.
 
@@ -232,6 +331,7 @@ impl Dyld {
             self.do_non_lazy_linking(bin, bins, mem, objc);
         }
 
+        objc.register_bin_protocols(&bins[0], mem);
         objc.register_bin_classes(&bins[0], mem);
         objc.register_bin_categories(&bins[0], mem);
 
@@ -244,8 +344,9 @@ impl Dyld {
         assert!(self.return_to_host_routine.is_none());
         assert!(self.thread_exit_routine.is_none());
         self.return_to_host_routine =
-            Some(write_return_to_host_routine(mem, Self::SVC_RETURN_TO_HOST));
-        self.thread_exit_routine = Some(write_return_to_host_routine(mem, Self::SVC_THREAD_EXIT));
+            Some(self.write_return_to_host_routine(mem, Self::SVC_RETURN_TO_HOST));
+        self.thread_exit_routine =
+            Some(self.write_return_to_host_routine(mem, Self::SVC_THREAD_EXIT));
 
         objc.register_host_selectors(mem);
     }
@@ -262,20 +363,21 @@ impl Dyld {
     ///
     /// These stubs already exist in the binary, but they need to be rewritten
     /// so that they will invoke our dynamic linker.
-    fn setup_lazy_linking(&self, bin: &MachO, mem: &mut Mem) {
-        let Some(stubs) = bin.get_section(SectionType::SymbolStubs) else {
-            return;
-        };
+    ///
+    /// Stub sections are found by their type rather than their name, since
+    /// the name depends on the toolchain, and there may be several of them.
+    fn setup_lazy_linking(&mut self, bin: &MachO, mem: &mut Mem) {
+        for stubs in bin.get_sections(SectionType::SymbolStubs) {
+            self.setup_lazy_linking_for_section(bin, stubs, mem);
+        }
+    }
 
+    fn setup_lazy_linking_for_section(&mut self, bin: &MachO, stubs: &Section, mem: &mut Mem) {
         let entry_size = stubs.dyld_indirect_symbol_info.as_ref().unwrap().entry_size;
 
         // two or three A32 instructions (PIC stub needs one more) followed by
         // the address or offset of the corresponding __la_symbol_ptr
-        let expected_instructions = match entry_size {
-            12 => Self::SYMBOL_STUB_INSTRUCTIONS.as_slice(),
-            16 => Self::PIC_SYMBOL_STUB_INSTRUCTIONS.as_slice(),
-            _ => unimplemented!(),
-        };
+        let expected_instructions = Self::symbol_stub_instructions(bin, stubs);
 
         assert!(stubs.size % entry_size == 0);
         let stub_count = stubs.size / entry_size;
@@ -286,7 +388,7 @@ impl Dyld {
                 assert!(mem.read(ptr + j.try_into().unwrap()) == instr);
             }
 
-            mem.write(ptr + 0, encode_a32_svc(Self::SVC_LAZY_LINK));
+            self.write_svc(mem, ptr + 0, Self::SVC_LAZY_LINK);
             // For convenience, make the stub return once the SVC is done
             // (Otherwise we'd have to manually update the PC)
             mem.write(ptr + 1, encode_a32_ret());
@@ -352,6 +454,11 @@ impl Dyld {
                     trampoline_ptr
                 );
                 trampoline_ptr
+            } else if let Some((_, template)) = search_lists(constant_lists::CONSTANT_LISTS, name) {
+                // See link_non_lazy_symbol_pointers
+                self.constants_to_link_later
+                    .push((ptr_ptr, offset, template));
+                continue;
             } else {
                 unhandled_relocations
                     .entry(name)
@@ -381,9 +488,26 @@ impl Dyld {
             );
         }
 
-        let Some(ptrs) = bin.get_section(SectionType::NonLazySymbolPointers) else {
-            return;
-        };
+        // Older toolchains put these in `__nl_symbol_ptr`, newer ones in
+        // `__got`, and there can be both.
+        for ptrs in bin.get_sections(SectionType::NonLazySymbolPointers) {
+            self.link_non_lazy_symbol_pointers(bin, ptrs, bins, mem);
+        }
+
+        // FIXME: check for internal relocations?
+    }
+
+    /// Link the entries in a non-lazy symbol pointer section (see
+    /// [Self::do_non_lazy_linking]). Functions are linked to a function in
+    /// another binary or a host function, and data to the address of a symbol
+    /// in another binary or a host constant.
+    fn link_non_lazy_symbol_pointers(
+        &mut self,
+        bin: &MachO,
+        ptrs: &Section,
+        bins: &[MachO],
+        mem: &mut Mem,
+    ) {
         let info = ptrs.dyld_indirect_symbol_info.as_ref().unwrap();
 
         let entry_size = info.entry_size;
@@ -425,7 +549,7 @@ impl Dyld {
             if let Some((_, template)) = search_lists(constant_lists::CONSTANT_LISTS, symbol) {
                 // Delay linking of constant until we have a `&mut Environment`,
                 // that makes it much easier to build NSString objects etc.
-                self.constants_to_link_later.push((ptr_ptr, template));
+                self.constants_to_link_later.push((ptr_ptr, 0, template));
                 continue;
             }
 
@@ -436,8 +560,6 @@ impl Dyld {
                 bin.name
             );
         }
-
-        // FIXME: check for internal relocations?
     }
 
     /// Do linking that can only be done once there is a full [Environment].
@@ -446,7 +568,7 @@ impl Dyld {
         // TODO: do symbols ever appear in __nl_symbol_ptr multiple times?
 
         let to_link = std::mem::take(&mut env.dyld.constants_to_link_later);
-        for (symbol_ptr_ptr, template) in to_link {
+        for (symbol_ptr_ptr, offset, template) in to_link {
             let symbol_ptr: ConstVoidPtr = match template {
                 HostConstant::NSString(static_str) => {
                     let string_ptr = ns_string::get_static_str(env, static_str);
@@ -460,13 +582,19 @@ impl Dyld {
                 }
                 HostConstant::Custom(f) => f(&mut env.mem, &mut env.dyld),
             };
-            env.mem.write(symbol_ptr_ptr, symbol_ptr.cast());
+            // See do_non_lazy_linking about the offset.
+            let symbol_ptr = Ptr::from_bits(symbol_ptr.to_bits().wrapping_add(offset));
+            env.mem.write(symbol_ptr_ptr, symbol_ptr);
         }
     }
 
-    /// Return a host function that can be called to handle an SVC instruction
-    /// encountered during CPU emulation. If `None` is returned, the execution
-    /// needs to resume at `svc_pc`.
+    /// Find out how to handle an SVC instruction written by the linker that
+    /// was encountered during CPU emulation.
+    ///
+    /// [Self::SVC_THREAD_EXIT] and [Self::SVC_RETURN_TO_HOST] are not handled
+    /// here, because they affect the [crate::Environment]'s threads rather than
+    /// linking. If they are passed anyway, the PC is logged and
+    /// [SvcHandler::Unexpected] is returned.
     pub fn get_svc_handler(
         &mut self,
         bins: &[MachO],
@@ -474,10 +602,22 @@ impl Dyld {
         cpu: &mut Cpu,
         svc_pc: u32,
         svc: u32,
-    ) -> Option<HostFunction> {
+    ) -> SvcHandler {
         match svc {
-            Self::SVC_LAZY_LINK => self.do_lazy_link(bins, mem, cpu, svc_pc),
-            Self::SVC_THREAD_EXIT | Self::SVC_RETURN_TO_HOST => unreachable!(), // don't handle here
+            Self::SVC_LAZY_LINK => match self.do_lazy_link(bins, mem, cpu, svc_pc) {
+                Ok(Some(f)) => SvcHandler::HostFunction(f),
+                Ok(None) => SvcHandler::Restart,
+                Err(symbol) => panic!("Call to unimplemented function {}", symbol),
+            },
+            Self::SVC_THREAD_EXIT | Self::SVC_RETURN_TO_HOST => {
+                log!(
+                    "Unexpected SVC #{} at {:#x}: the thread exit and return-to-host \
+                     routines must be handled by the environment, not the linker.",
+                    svc,
+                    svc_pc
+                );
+                SvcHandler::Unexpected
+            }
             Self::SVC_LINKED_FUNCTIONS_BASE.. => {
                 let f = self
                     .linked_host_functions
@@ -486,36 +626,37 @@ impl Dyld {
                     panic!("Unexpected SVC #{} at {:#x}", svc, svc_pc);
                 };
                 log_dbg!("Call to host function, already linked: {}", symbol);
-                Some(f)
+                SvcHandler::HostFunction(f)
             }
         }
     }
 
-    fn do_lazy_link(
+    /// Link the stub at `svc_pc`. If it was linked to a host function, that
+    /// function is returned so it can be called right away. Otherwise, the
+    /// stub has been restored and execution needs to restart at `svc_pc`.
+    /// Returns the symbol name as an [Err] if it has no implementation.
+    fn do_lazy_link<'a>(
         &mut self,
-        bins: &[MachO],
+        bins: &'a [MachO],
         mem: &mut Mem,
         cpu: &mut Cpu,
         svc_pc: u32,
-    ) -> Option<HostFunction> {
+    ) -> Result<Option<HostFunction>, &'a str> {
         // Links by restoring the original stub function, then updating
         // __la_symbol_ptr to the appropriate function.
         fn link_by_restoring_stub(
+            svc_addrs: &mut HashSet<u32>,
             mem: &mut Mem,
             cpu: &mut Cpu,
             linked_function: u32,
             svc_pc: u32,
-            entry_size: u32,
+            original_instructions: &[u32],
         ) -> (MutPtr<u32>, MutPtr<u32>) {
-            let original_instructions = match entry_size {
-                12 => Dyld::SYMBOL_STUB_INSTRUCTIONS.as_slice(),
-                16 => Dyld::PIC_SYMBOL_STUB_INSTRUCTIONS.as_slice(),
-                _ => unreachable!(),
-            };
             let instruction_count: GuestUSize = original_instructions.len().try_into().unwrap();
 
             // Restore the original stub, which calls the __la_symbol_ptr
             let stub_function_ptr: MutPtr<u32> = Ptr::from_bits(svc_pc);
+            svc_addrs.remove(&svc_pc);
             for (i, &instr) in original_instructions.iter().enumerate() {
                 mem.write(stub_function_ptr + i.try_into().unwrap(), instr)
             }
@@ -523,27 +664,32 @@ impl Dyld {
             cpu.invalidate_cache_range(stub_function_ptr.to_bits(), instruction_count * 4);
 
             // Update the __la_symbol_ptr
-            let la_symbol_ptr: MutPtr<u32> = if entry_size == 12 {
-                // Normal stub: absolute address
-                let addr = mem.read(stub_function_ptr + instruction_count);
-                Ptr::from_bits(addr)
-            } else {
-                // The PIC (position-independent code) stub uses a
-                // PC-relative offset rather than an absolute address.
-                let offset = mem.read(stub_function_ptr + instruction_count);
-                Ptr::from_bits(stub_function_ptr.to_bits() + offset + 12)
-            };
+            let la_symbol_ptr: MutPtr<u32> =
+                if original_instructions == Dyld::SYMBOL_STUB_INSTRUCTIONS {
+                    // Normal stub: absolute address
+                    let addr = mem.read(stub_function_ptr + instruction_count);
+                    Ptr::from_bits(addr)
+                } else {
+                    // The PIC (position-independent code) stub uses a
+                    // PC-relative offset rather than an absolute address.
+                    let offset = mem.read(stub_function_ptr + instruction_count);
+                    Ptr::from_bits(stub_function_ptr.to_bits() + offset + 12)
+                };
             mem.write(la_symbol_ptr, linked_function);
             (stub_function_ptr, la_symbol_ptr)
         }
 
-        let stubs = bins
+        let (bin, stubs) = bins
             .iter()
-            .flat_map(|bin| bin.get_section(SectionType::SymbolStubs))
-            .find(|stubs| (stubs.addr..(stubs.addr + stubs.size)).contains(&svc_pc))
+            .flat_map(|bin| {
+                bin.get_sections(SectionType::SymbolStubs)
+                    .map(move |stubs| (bin, stubs))
+            })
+            .find(|(_, stubs)| (stubs.addr..(stubs.addr + stubs.size)).contains(&svc_pc))
             .unwrap();
 
         let info = stubs.dyld_indirect_symbol_info.as_ref().unwrap();
+        let original_instructions = Self::symbol_stub_instructions(bin, stubs);
 
         let offset = svc_pc - stubs.addr;
         assert!(offset % info.entry_size == 0);
@@ -555,11 +701,12 @@ impl Dyld {
             // The host function was already linked non-lazily, point the
             // stub and __la_symbol_ptr to the function.
             let (stub_function_ptr, la_symbol_ptr) = link_by_restoring_stub(
+                &mut self.svc_addrs,
                 mem,
                 cpu,
                 addr.addr_with_thumb_bit(),
                 svc_pc,
-                info.entry_size,
+                original_instructions,
             );
             log_dbg!(
                 "Linked host function {} at {:?}/{:?} to existing stub ({:?}).",
@@ -570,7 +717,7 @@ impl Dyld {
             );
             // The stub jumps to the non-lazy function, which calls the
             // host function.
-            return None;
+            return Ok(None);
         }
 
         if let Some(&(symbol, f)) = search_lists(function_lists::FUNCTION_LISTS, symbol) {
@@ -581,7 +728,7 @@ impl Dyld {
 
             // Rewrite stub function to call this host function
             let stub_function_ptr: MutPtr<u32> = Ptr::from_bits(svc_pc);
-            mem.write(stub_function_ptr, encode_a32_svc(svc));
+            self.write_svc(mem, stub_function_ptr, svc);
             assert!(mem.read(stub_function_ptr + 1) == encode_a32_ret());
 
             cpu.invalidate_cache_range(stub_function_ptr.to_bits(), 4);
@@ -594,13 +741,19 @@ impl Dyld {
 
             // Return the host function so that we can call it now that we're
             // done.
-            return Some(f);
+            return Ok(Some(f));
         }
 
         for dylib in bins.iter() {
             if let Some(&addr) = dylib.exported_symbols.get(symbol) {
-                let (stub_function_ptr, la_symbol_ptr) =
-                    link_by_restoring_stub(mem, cpu, addr, svc_pc, info.entry_size);
+                let (stub_function_ptr, la_symbol_ptr) = link_by_restoring_stub(
+                    &mut self.svc_addrs,
+                    mem,
+                    cpu,
+                    addr,
+                    svc_pc,
+                    original_instructions,
+                );
                 log_dbg!(
                     "Linked {} at {:?}/{:?} to {:#x} from {}",
                     symbol,
@@ -610,11 +763,49 @@ impl Dyld {
                     dylib.name
                 );
                 // Tell the caller it needs to restart execution at svc_pc.
-                return None;
+                return Ok(None);
             }
         }
 
-        panic!("Call to unimplemented function {}", symbol);
+        Err(symbol)
+    }
+
+    /// Link every stub in the binaries now, rather than lazily when each is
+    /// first called (see [Self::setup_lazy_linking]). This takes the linker out
+    /// of the picture when measuring performance, and makes it obvious up
+    /// front which functions are missing: they're all logged at once. Their
+    /// stubs are left as they are, so calling one still panics.
+    pub fn eager_link_all(&mut self, bins: &[MachO], mem: &mut Mem, cpu: &mut Cpu) {
+        let lazy_link_svc = encode_a32_svc(Self::SVC_LAZY_LINK);
+        let mut linked_count = 0;
+        let mut missing = Vec::new();
+        for bin in bins {
+            for stubs in bin.get_sections(SectionType::SymbolStubs) {
+                let entry_size = stubs.dyld_indirect_symbol_info.as_ref().unwrap().entry_size;
+                for i in 0..(stubs.size / entry_size) {
+                    let stub: MutPtr<u32> = Ptr::from_bits(stubs.addr + i * entry_size);
+                    // Skip stubs that were already linked.
+                    if !self.is_linker_svc(stub.to_bits()) || mem.read(stub) != lazy_link_svc {
+                        continue;
+                    }
+                    match self.do_lazy_link(bins, mem, cpu, stub.to_bits()) {
+                        Ok(_) => linked_count += 1,
+                        Err(symbol) => missing.push(symbol),
+                    }
+                }
+            }
+        }
+        log!(
+            "Eagerly linked {} stubs, {} have no implementation",
+            linked_count,
+            missing.len()
+        );
+        for symbol in missing {
+            log!(
+                "Warning: no implementation for {}, calling it will panic",
+                symbol
+            );
+        }
     }
 
     /// Creates a guest function that will call a host function with the name
@@ -637,6 +828,47 @@ impl Dyld {
         Ok(function_ptr)
     }
 
+    /// Like [Self::create_proc_address], but only finds functions provided by
+    /// the library with the install path `path` (see [Self::library_handle]).
+    pub fn create_proc_address_in_library(
+        &mut self,
+        mem: &mut Mem,
+        cpu: &mut Cpu,
+        path: &str,
+        symbol: &str,
+    ) -> Result<GuestFunction, ()> {
+        let &(_, lists) = function_lists::LIBRARIES
+            .iter()
+            .find(|&&(library_path, _)| library_path == path)
+            .ok_or(())?;
+        search_lists(lists, symbol).ok_or(())?;
+        self.create_proc_address(mem, cpu, symbol)
+    }
+
+    /// Get the handle `dlopen()` returns for one of the libraries in
+    /// [function_lists::LIBRARIES]. The same path always gets the same handle.
+    /// Returns [None] if touchHLE doesn't have a library with that path.
+    pub fn library_handle(&mut self, mem: &mut Mem, path: &str) -> Option<MutVoidPtr> {
+        let &(path, _) = function_lists::LIBRARIES
+            .iter()
+            .find(|&&(library_path, _)| library_path == path)?;
+        Some(
+            *self
+                .library_handles
+                .entry(path)
+                .or_insert_with(|| mem.alloc_and_write_cstr(path.as_bytes()).cast()),
+        )
+    }
+
+    /// The install path of the library a handle from [Self::library_handle]
+    /// is for, or [None] if it isn't such a handle.
+    pub fn library_for_handle(&self, handle: MutVoidPtr) -> Option<&'static str> {
+        self.library_handles
+            .iter()
+            .find(|&(_, &library_handle)| library_handle == handle)
+            .map(|(&path, _)| path)
+    }
+
     /// Internal [Self::create_proc_address] that doesn't invalidate the cache.
     /// For use before a [Cpu] is available.
     fn create_proc_address_no_inval(
@@ -653,6 +885,74 @@ impl Dyld {
         Ok(function_ptr)
     }
 
+    /// For each binary, list the symbols it imports and where they would be
+    /// linked from, without actually linking anything. This is meant for
+    /// getting an overview of what an app needs that touchHLE doesn't provide
+    /// (see `--dump-imports`).
+    ///
+    /// Host implementations are checked before the other binaries, as the lazy
+    /// linker does. The non-lazy linker checks the binaries first, but it is
+    /// rare for a symbol to be provided by both.
+    pub fn import_report(bins: &[MachO]) -> Vec<ImportReport<'_>> {
+        bins.iter()
+            .map(|bin| ImportReport {
+                bin_name: &bin.name,
+                imports: bin
+                    .imported_symbols()
+                    .into_iter()
+                    .map(|symbol| (symbol, Self::find_import_source(bins, symbol)))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn find_import_source<'a>(bins: &'a [MachO], symbol: &str) -> ImportSource<'a> {
+        let is_host = if let Some(class_name) = symbol
+            .strip_prefix("_OBJC_CLASS_$_")
+            .or_else(|| symbol.strip_prefix("_OBJC_METACLASS_$_"))
+        {
+            ObjC::is_host_class(class_name)
+        } else {
+            // See do_non_lazy_linking
+            matches!(
+                symbol,
+                "___CFConstantStringClassReference" | "__objc_empty_vtable" | "__objc_empty_cache"
+            ) || search_lists(function_lists::FUNCTION_LISTS, symbol).is_some()
+                || search_lists(constant_lists::CONSTANT_LISTS, symbol).is_some()
+        };
+        if is_host {
+            return ImportSource::Host;
+        }
+        bins.iter()
+            .find(|bin| bin.exported_symbols.contains_key(symbol))
+            .map_or(ImportSource::Unsatisfied, |bin| {
+                ImportSource::Binary(&bin.name)
+            })
+    }
+
+    /// Print the result of [Self::import_report].
+    pub fn dump_import_report(bins: &[MachO]) {
+        for ImportReport { bin_name, imports } in Self::import_report(bins) {
+            let count = |source: fn(&ImportSource) -> bool| {
+                imports.iter().filter(|(_, s)| source(s)).count()
+            };
+            echo!(
+                "Imports of {:?}: {} from touchHLE, {} from binaries, {} unsatisfied",
+                bin_name,
+                count(|s| matches!(s, ImportSource::Host)),
+                count(|s| matches!(s, ImportSource::Binary(_))),
+                count(|s| matches!(s, ImportSource::Unsatisfied)),
+            );
+            for (symbol, source) in imports {
+                match source {
+                    ImportSource::Host => echo!("  {} (touchHLE)", symbol),
+                    ImportSource::Binary(name) => echo!("  {} ({})", symbol, name),
+                    ImportSource::Unsatisfied => echo!("  {} (UNSATISFIED)", symbol),
+                }
+            }
+        }
+    }
+
     pub fn create_guest_function(
         &mut self,
         mem: &mut Mem,
@@ -667,9 +967,306 @@ impl Dyld {
         // Create guest function to call this host function
         let function_ptr = mem.alloc(8);
         let function_ptr: MutPtr<u32> = function_ptr.cast();
-        mem.write(function_ptr + 0, encode_a32_svc(svc));
+        self.write_svc(mem, function_ptr + 0, svc);
         mem.write(function_ptr + 1, encode_a32_ret());
 
         GuestFunction::from_addr_with_thumb_bit(function_ptr.to_bits())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mach_o::DyldIndirectSymbolInfo;
+    use crate::mem::ConstPtr;
+
+    fn fixture_bin(name: &str, exports: &[&str]) -> MachO {
+        MachO {
+            name: name.to_string(),
+            dynamic_libraries: Vec::new(),
+            sections: Vec::new(),
+            exported_symbols: exports.iter().map(|&s| (s.to_string(), 0x1000)).collect(),
+            undefined_symbols: Vec::new(),
+            external_relocations: Vec::new(),
+            entry_point_pc: None,
+        }
+    }
+
+    #[test]
+    fn test_import_report() {
+        let mut app = fixture_bin("App", &["_appFunction"]);
+        app.undefined_symbols = vec![
+            "_strlen".to_string(),
+            "_dylibFunction".to_string(),
+            "_missingFunction".to_string(),
+        ];
+        app.external_relocations = vec![
+            (0x2000, "_OBJC_CLASS_$_NSObject".to_string()),
+            (0x2004, "_OBJC_CLASS_$_NSMissingClass".to_string()),
+            (0x2008, "___CFConstantStringClassReference".to_string()),
+        ];
+        app.sections.push(Section {
+            name: "__nl_symbol_ptr".to_string(),
+            addr: 0x3000,
+            size: 12,
+            type_: SectionType::NonLazySymbolPointers,
+            dyld_indirect_symbol_info: Some(DyldIndirectSymbolInfo {
+                entry_size: 4,
+                indirect_undef_symbols: vec![
+                    Some("_kCFTypeArrayCallBacks".to_string()),
+                    Some("_appFunction".to_string()),
+                    None,
+                ],
+            }),
+        });
+        let mut dylib = fixture_bin("libfixture.dylib", &["_dylibFunction"]);
+        dylib.undefined_symbols = vec!["_missingFunction".to_string()];
+        let bins = [app, dylib];
+
+        let report = Dyld::import_report(&bins);
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].bin_name, "App");
+        assert_eq!(
+            report[0].imports,
+            [
+                ("_OBJC_CLASS_$_NSMissingClass", ImportSource::Unsatisfied),
+                ("_OBJC_CLASS_$_NSObject", ImportSource::Host),
+                ("___CFConstantStringClassReference", ImportSource::Host),
+                ("_appFunction", ImportSource::Binary("App")),
+                ("_dylibFunction", ImportSource::Binary("libfixture.dylib")),
+                ("_kCFTypeArrayCallBacks", ImportSource::Host),
+                ("_missingFunction", ImportSource::Unsatisfied),
+                ("_strlen", ImportSource::Host),
+            ]
+        );
+        assert_eq!(report[1].bin_name, "libfixture.dylib");
+        assert_eq!(
+            report[1].imports,
+            [("_missingFunction", ImportSource::Unsatisfied)]
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Unsupported symbol stub entry size 8 (expected 12 or 16) in section __symbol_stub4 of App"
+    )]
+    fn test_unsupported_symbol_stub_entry_size() {
+        let mut app = fixture_bin("App", &[]);
+        app.sections.push(Section {
+            name: "__symbol_stub4".to_string(),
+            addr: 0x3000,
+            size: 16,
+            type_: SectionType::SymbolStubs,
+            dyld_indirect_symbol_info: Some(DyldIndirectSymbolInfo {
+                entry_size: 8,
+                indirect_undef_symbols: vec![Some("_strlen".to_string()); 2],
+            }),
+        });
+        let mut mem = Mem::new();
+        Dyld::new().setup_lazy_linking(&app, &mut mem);
+    }
+
+    #[test]
+    fn test_setup_lazy_linking() {
+        let mut mem = Mem::new();
+        // One stub in a non-PIC section, two in a PIC section with the name
+        // newer toolchains use.
+        let stubs: MutPtr<u32> = mem.alloc(12 + 32).cast();
+        let pic_stubs = stubs + 3;
+        mem.write(stubs, Dyld::SYMBOL_STUB_INSTRUCTIONS[0]);
+        mem.write(stubs + 1, Dyld::SYMBOL_STUB_INSTRUCTIONS[1]);
+        for stub in [pic_stubs, pic_stubs + 4] {
+            for (i, &instr) in Dyld::PIC_SYMBOL_STUB_INSTRUCTIONS.iter().enumerate() {
+                mem.write(stub + i as GuestUSize, instr);
+            }
+        }
+
+        let mut app = fixture_bin("App", &[]);
+        for (name, addr, size, entry_size) in [
+            ("__symbol_stub4", stubs.to_bits(), 12, 12),
+            ("__stubs", pic_stubs.to_bits(), 32, 16),
+        ] {
+            app.sections.push(Section {
+                name: name.to_string(),
+                addr,
+                size,
+                type_: SectionType::SymbolStubs,
+                dyld_indirect_symbol_info: Some(DyldIndirectSymbolInfo {
+                    entry_size,
+                    indirect_undef_symbols: vec![
+                        Some("_strlen".to_string());
+                        (size / entry_size) as usize
+                    ],
+                }),
+            });
+        }
+        let mut dyld = Dyld::new();
+        dyld.setup_lazy_linking(&app, &mut mem);
+
+        for stub in [stubs, pic_stubs, pic_stubs + 4] {
+            assert!(dyld.is_linker_svc(stub.to_bits()));
+            assert_eq!(mem.read(stub + 1), encode_a32_ret());
+        }
+        assert_eq!(mem.read(pic_stubs + 2), encode_a32_trap());
+    }
+
+    #[test]
+    fn test_eager_link_all() {
+        let mut mem = Mem::new();
+        let symbols = ["_strlen", "_dylibFunction", "_missingFunction"];
+        let stubs: MutPtr<u32> = mem.alloc(12 * 3).cast();
+        let la_symbol_ptrs: MutPtr<u32> = mem.alloc(4 * 3).cast();
+        for i in 0..3 {
+            let stub = stubs + i * 3;
+            mem.write(stub, Dyld::SYMBOL_STUB_INSTRUCTIONS[0]);
+            mem.write(stub + 1, Dyld::SYMBOL_STUB_INSTRUCTIONS[1]);
+            mem.write(stub + 2, (la_symbol_ptrs + i).to_bits());
+        }
+
+        let mut app = fixture_bin("App", &[]);
+        app.sections.push(Section {
+            name: "__symbol_stub4".to_string(),
+            addr: stubs.to_bits(),
+            size: 12 * 3,
+            type_: SectionType::SymbolStubs,
+            dyld_indirect_symbol_info: Some(DyldIndirectSymbolInfo {
+                entry_size: 12,
+                indirect_undef_symbols: symbols.iter().map(|s| Some(s.to_string())).collect(),
+            }),
+        });
+        let dylib = fixture_bin("libfixture.dylib", &["_dylibFunction"]);
+        let bins = [app, dylib];
+
+        let mut dyld = Dyld::new();
+        dyld.setup_lazy_linking(&bins[0], &mut mem);
+        let mut cpu = Cpu::new(Some(&mut mem));
+        dyld.eager_link_all(&bins, &mut mem, &mut cpu);
+
+        // The host function's stub calls it directly.
+        let lazy_link_svc = encode_a32_svc(Dyld::SVC_LAZY_LINK);
+        let svc = mem.read(stubs);
+        assert_ne!(svc, lazy_link_svc);
+        assert_eq!(svc, encode_a32_svc(Dyld::SVC_LINKED_FUNCTIONS_BASE));
+        // The dylib function's stub is restored and jumps to it.
+        let stub = stubs + 3;
+        assert_eq!(mem.read(stub), Dyld::SYMBOL_STUB_INSTRUCTIONS[0]);
+        assert!(!dyld.is_linker_svc(stub.to_bits()));
+        assert_eq!(mem.read(la_symbol_ptrs + 1), 0x1000);
+        // Only the missing function is still linked lazily.
+        assert_eq!(mem.read(stubs + 6), lazy_link_svc);
+
+        // Doing it again changes nothing.
+        dyld.eager_link_all(&bins, &mut mem, &mut cpu);
+        assert_eq!(mem.read(stubs), svc);
+        assert_eq!(dyld.linked_host_functions.len(), 1);
+    }
+
+    #[test]
+    fn test_non_lazy_linking_got() {
+        let mut mem = Mem::new();
+        let nl_symbol_ptr: MutPtr<ConstVoidPtr> = mem.alloc(4).cast();
+        let got: MutPtr<ConstVoidPtr> = mem.alloc(8).cast();
+
+        let mut app = fixture_bin("App", &[]);
+        for (name, ptrs, symbols) in [
+            ("__nl_symbol_ptr", nl_symbol_ptr, &["_strlen"][..]),
+            ("__got", got, &["_strlen", "_dylibData"][..]),
+        ] {
+            app.sections.push(Section {
+                name: name.to_string(),
+                addr: ptrs.to_bits(),
+                size: 4 * symbols.len() as GuestUSize,
+                type_: SectionType::NonLazySymbolPointers,
+                dyld_indirect_symbol_info: Some(DyldIndirectSymbolInfo {
+                    entry_size: 4,
+                    indirect_undef_symbols: symbols.iter().map(|s| Some(s.to_string())).collect(),
+                }),
+            });
+        }
+        let dylib = fixture_bin("libfixture.dylib", &["_dylibData"]);
+        let bins = [app, dylib];
+
+        let mut dyld = Dyld::new();
+        let mut objc = ObjC::new();
+        dyld.do_non_lazy_linking(&bins[0], &bins, &mut mem, &mut objc);
+
+        // Both entries for the host function point to the same guest function,
+        // which calls it via an SVC when the guest calls it indirectly.
+        let function: ConstPtr<u32> = mem.read(got).cast();
+        assert_eq!(mem.read(nl_symbol_ptr).cast::<u32>(), function);
+        let idx = dyld
+            .linked_host_functions
+            .iter()
+            .position(|&(symbol, _)| symbol == "_strlen")
+            .unwrap();
+        let svc = Dyld::SVC_LINKED_FUNCTIONS_BASE + idx as u32;
+        assert_eq!(mem.read(function), encode_a32_svc(svc));
+        assert_eq!(mem.read(function + 1), encode_a32_ret());
+        // Data is linked to the address of the symbol.
+        assert_eq!(mem.read(got + 1).to_bits(), 0x1000);
+    }
+
+    #[test]
+    fn test_external_relocations_to_host_symbols() {
+        let mut mem = Mem::new();
+        let ptrs: MutPtr<ConstVoidPtr> = mem.alloc(12).cast();
+        // The existing values are offsets to add to the symbol's address.
+        mem.write(ptrs, Ptr::null());
+        mem.write(ptrs + 1, Ptr::from_bits(4));
+        mem.write(ptrs + 2, Ptr::from_bits(8));
+
+        let mut app = fixture_bin("App", &[]);
+        app.external_relocations = vec![
+            (ptrs.to_bits(), "_strlen".to_string()),
+            ((ptrs + 1).to_bits(), "_kCFTypeArrayCallBacks".to_string()),
+            ((ptrs + 2).to_bits(), "_missingSymbol".to_string()),
+        ];
+        let bins = [app];
+
+        let mut dyld = Dyld::new();
+        let mut objc = ObjC::new();
+        dyld.do_non_lazy_linking(&bins[0], &bins, &mut mem, &mut objc);
+
+        // Host functions get the same guest function as any other reference,
+        // so the guest can call them through the pointer.
+        let function = dyld.create_proc_address_no_inval(&mut mem, "_strlen");
+        assert_eq!(
+            mem.read(ptrs).to_bits(),
+            function.unwrap().addr_with_thumb_bit()
+        );
+        // Host constants are linked later, with the offset.
+        assert_eq!(dyld.constants_to_link_later.len(), 1);
+        let (ptr_ptr, offset, _) = dyld.constants_to_link_later[0];
+        assert_eq!((ptr_ptr, offset), (ptrs + 1, 4));
+        // Unknown symbols are left alone.
+        assert_eq!(mem.read(ptrs + 2).to_bits(), 8);
+    }
+
+    #[test]
+    fn test_return_to_host_routine() {
+        let mut mem = Mem::new();
+        let mut objc = ObjC::new();
+        let mut dyld = Dyld::new();
+        dyld.do_initial_linking_with_no_bins(&mut mem, &mut objc);
+
+        let routine = dyld.return_to_host_routine();
+        assert!(!routine.is_thumb());
+        let addr = routine.addr_without_thumb_bit();
+        let instructions: ConstPtr<u32> = Ptr::from_bits(addr);
+        assert_eq!(
+            mem.read(instructions),
+            encode_a32_svc(Dyld::SVC_RETURN_TO_HOST)
+        );
+        assert_eq!(mem.read(instructions + 1), encode_a32_trap());
+        assert!(dyld.is_linker_svc(addr));
+
+        // The environment handles this SVC itself, so the linker shouldn't be
+        // asked to, but it mustn't crash if it is.
+        let mut cpu = Cpu::new(Some(&mut mem));
+        let handler = dyld.get_svc_handler(&[], &mut mem, &mut cpu, addr, Dyld::SVC_RETURN_TO_HOST);
+        assert!(matches!(handler, SvcHandler::Unexpected));
+        let exit = dyld.thread_exit_routine().addr_without_thumb_bit();
+        let handler = dyld.get_svc_handler(&[], &mut mem, &mut cpu, exit, Dyld::SVC_THREAD_EXIT);
+        assert!(matches!(handler, SvcHandler::Unexpected));
+    }
+}
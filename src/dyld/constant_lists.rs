@@ -14,6 +14,7 @@ use crate::libc;
 
 /// All the lists of constants that the linker should search through.
 pub const CONSTANT_LISTS: &[super::ConstantExports] = &[
+    libc::blocks::CONSTANTS,
     libc::ctype::CONSTANTS,
     libc::stdio::CONSTANTS,
     libc::mach_init::CONSTANTS,
@@ -21,6 +22,7 @@ pub const CONSTANT_LISTS: &[super::ConstantExports] = &[
     core_animation::ca_layer::CONSTANTS,
     core_animation::ca_media_timing_function::CONSTANTS,
     core_foundation::cf_allocator::CONSTANTS,
+    core_foundation::cf_array::CONSTANTS,
     core_foundation::cf_bundle::CONSTANTS,
     core_foundation::cf_dictionary::CONSTANTS,
     core_foundation::cf_locale::CONSTANTS,
@@ -29,12 +31,14 @@ pub const CONSTANT_LISTS: &[super::ConstantExports] = &[
     core_graphics::cg_color_space::CONSTANTS,
     core_graphics::cg_geometry::CONSTANTS,
     game_kit::gk_local_player::CONSTANTS,
+    foundation::ns_calendar::CONSTANTS,
     foundation::ns_error::CONSTANTS,
     foundation::ns_exception::CONSTANTS,
     foundation::ns_file_manager::CONSTANTS,
     foundation::ns_keyed_unarchiver::CONSTANTS,
     foundation::ns_locale::CONSTANTS,
     foundation::ns_run_loop::CONSTANTS,
+    foundation::ns_thread::CONSTANTS,
     media_player::movie_player::CONSTANTS,
     media_player::music_player::CONSTANTS,
     opengles::eagl::CONSTANTS,
@@ -14,23 +14,33 @@ use crate::libc;
 
 /// All the lists of functions that the linker should search through.
 pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
+    libc::aeabi::FUNCTIONS,
+    libc::blocks::FUNCTIONS,
     libc::clocale::FUNCTIONS,
     libc::ctype::FUNCTIONS,
+    libc::cxx_new::FUNCTIONS,
     libc::cxxabi::FUNCTIONS,
     libc::crypto::FUNCTIONS,
     libc::dirent::FUNCTIONS,
+    libc::dispatch::FUNCTIONS,
     libc::dlfcn::FUNCTIONS,
     libc::errno::FUNCTIONS,
+    libc::execinfo::FUNCTIONS,
     libc::ifaddrs::FUNCTIONS,
     libc::keymgr::FUNCTIONS,
     libc::mach_host::FUNCTIONS,
+    libc::mach_init::FUNCTIONS,
     libc::mach_semaphore::FUNCTIONS,
+    libc::mach_task_info::FUNCTIONS,
     libc::mach_thread_info::FUNCTIONS,
     libc::mach_time::FUNCTIONS,
+    libc::mach_vm::FUNCTIONS,
+    libc::malloc::FUNCTIONS,
     libc::math::FUNCTIONS,
     libc::mmap::FUNCTIONS,
     libc::net::if_::FUNCTIONS,
     libc::netdb::FUNCTIONS,
+    libc::os_atomic::FUNCTIONS,
     libc::posix_io::FUNCTIONS,
     libc::posix_io::stat::FUNCTIONS,
     libc::pthread::cond::FUNCTIONS,
@@ -62,6 +72,7 @@ pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
     audio_toolbox::audio_services::FUNCTIONS,
     audio_toolbox::audio_session::FUNCTIONS,
     audio_toolbox::audio_unit::FUNCTIONS,
+    core_foundation::cf_allocator::FUNCTIONS,
     core_foundation::cf_array::FUNCTIONS,
     core_foundation::cf_dictionary::FUNCTIONS,
     core_foundation::cf_bundle::FUNCTIONS,
@@ -94,3 +105,166 @@ pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
     uikit::ui_geometry::FUNCTIONS,
     uikit::ui_graphics::FUNCTIONS,
 ];
+
+/// The "dylibs" that `dlopen()` can open, by install path, and which of the
+/// [FUNCTION_LISTS] each of them provides. Every list belongs to exactly one.
+pub const LIBRARIES: &[(&str, &[super::FunctionExports])] = &[
+    (
+        "/usr/lib/libSystem.B.dylib",
+        &[
+            libc::aeabi::FUNCTIONS,
+            libc::blocks::FUNCTIONS,
+            libc::clocale::FUNCTIONS,
+            libc::ctype::FUNCTIONS,
+            libc::cxx_new::FUNCTIONS,
+            libc::cxxabi::FUNCTIONS,
+            libc::crypto::FUNCTIONS,
+            libc::dirent::FUNCTIONS,
+            libc::dispatch::FUNCTIONS,
+            libc::dlfcn::FUNCTIONS,
+            libc::errno::FUNCTIONS,
+            libc::execinfo::FUNCTIONS,
+            libc::ifaddrs::FUNCTIONS,
+            libc::keymgr::FUNCTIONS,
+            libc::mach_host::FUNCTIONS,
+            libc::mach_init::FUNCTIONS,
+            libc::mach_semaphore::FUNCTIONS,
+            libc::mach_task_info::FUNCTIONS,
+            libc::mach_thread_info::FUNCTIONS,
+            libc::mach_time::FUNCTIONS,
+            libc::mach_vm::FUNCTIONS,
+            libc::malloc::FUNCTIONS,
+            libc::math::FUNCTIONS,
+            libc::mmap::FUNCTIONS,
+            libc::net::if_::FUNCTIONS,
+            libc::netdb::FUNCTIONS,
+            libc::os_atomic::FUNCTIONS,
+            libc::posix_io::FUNCTIONS,
+            libc::posix_io::stat::FUNCTIONS,
+            libc::pthread::cond::FUNCTIONS,
+            libc::pthread::key::FUNCTIONS,
+            libc::pthread::mutex::FUNCTIONS,
+            libc::pthread::once::FUNCTIONS,
+            libc::pthread::thread::FUNCTIONS,
+            libc::sched::FUNCTIONS,
+            libc::semaphore::FUNCTIONS,
+            libc::setjmp::FUNCTIONS,
+            libc::signal::FUNCTIONS,
+            libc::stdio::FUNCTIONS,
+            libc::stdio::printf::FUNCTIONS,
+            libc::stdlib::FUNCTIONS,
+            libc::stdlib::qsort::FUNCTIONS,
+            libc::string::FUNCTIONS,
+            libc::sys::mount::FUNCTIONS,
+            libc::sys::ptrace::FUNCTIONS,
+            libc::sys::timeb::FUNCTIONS,
+            libc::sys::utsname::FUNCTIONS,
+            libc::sysctl::FUNCTIONS,
+            libc::time::FUNCTIONS,
+            libc::unistd::FUNCTIONS,
+            libc::wchar::FUNCTIONS,
+            // DNS Service Discovery is part of libSystem on iPhone OS.
+            dnssd::FUNCTIONS,
+        ],
+    ),
+    ("/usr/lib/libobjc.A.dylib", &[crate::objc::FUNCTIONS]),
+    (
+        "/System/Library/Frameworks/AudioToolbox.framework/AudioToolbox",
+        &[
+            audio_toolbox::audio_components::FUNCTIONS,
+            audio_toolbox::audio_file::FUNCTIONS,
+            audio_toolbox::audio_queue::FUNCTIONS,
+            audio_toolbox::audio_services::FUNCTIONS,
+            audio_toolbox::audio_session::FUNCTIONS,
+            audio_toolbox::audio_unit::FUNCTIONS,
+        ],
+    ),
+    (
+        "/System/Library/Frameworks/CoreFoundation.framework/CoreFoundation",
+        &[
+            core_foundation::cf_allocator::FUNCTIONS,
+            core_foundation::cf_array::FUNCTIONS,
+            core_foundation::cf_dictionary::FUNCTIONS,
+            core_foundation::cf_bundle::FUNCTIONS,
+            core_foundation::cf_data::FUNCTIONS,
+            core_foundation::cf_locale::FUNCTIONS,
+            core_foundation::cf_run_loop::FUNCTIONS,
+            core_foundation::cf_run_loop_timer::FUNCTIONS,
+            core_foundation::cf_string::FUNCTIONS,
+            core_foundation::cf_type::FUNCTIONS,
+            core_foundation::cf_url::FUNCTIONS,
+            core_foundation::time::FUNCTIONS,
+        ],
+    ),
+    (
+        "/System/Library/Frameworks/CoreGraphics.framework/CoreGraphics",
+        &[
+            core_graphics::cg_affine_transform::FUNCTIONS,
+            core_graphics::cg_bitmap_context::FUNCTIONS,
+            core_graphics::cg_color::FUNCTIONS,
+            core_graphics::cg_color_space::FUNCTIONS,
+            core_graphics::cg_context::FUNCTIONS,
+            core_graphics::cg_data_provider::FUNCTIONS,
+            core_graphics::cg_geometry::FUNCTIONS,
+            core_graphics::cg_image::FUNCTIONS,
+        ],
+    ),
+    (
+        "/System/Library/Frameworks/Foundation.framework/Foundation",
+        &[
+            foundation::FUNCTIONS,
+            foundation::ns_exception::FUNCTIONS,
+            foundation::ns_file_manager::FUNCTIONS,
+            foundation::ns_log::FUNCTIONS,
+            foundation::ns_objc_runtime::FUNCTIONS,
+        ],
+    ),
+    (
+        "/System/Library/Frameworks/OpenAL.framework/OpenAL",
+        &[openal::FUNCTIONS],
+    ),
+    (
+        "/System/Library/Frameworks/OpenGLES.framework/OpenGLES",
+        &[opengles::FUNCTIONS],
+    ),
+    (
+        "/System/Library/Frameworks/SystemConfiguration.framework/SystemConfiguration",
+        &[system_configuration::sc_network_reachability::FUNCTIONS],
+    ),
+    (
+        "/System/Library/Frameworks/UIKit.framework/UIKit",
+        &[
+            uikit::ui_application::FUNCTIONS,
+            uikit::ui_geometry::FUNCTIONS,
+            uikit::ui_graphics::FUNCTIONS,
+        ],
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dyld::FunctionExports;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_libraries_cover_function_lists() {
+        let symbols = |lists: &[FunctionExports]| -> Vec<&str> {
+            lists
+                .iter()
+                .flat_map(|&list| list.iter().map(|&(symbol, _)| symbol))
+                .collect()
+        };
+        let all = symbols(FUNCTION_LISTS);
+        let in_libraries: Vec<&str> = LIBRARIES
+            .iter()
+            .flat_map(|&(_, lists)| symbols(lists))
+            .collect();
+        assert_eq!(
+            all.iter().collect::<HashSet<_>>(),
+            in_libraries.iter().collect::<HashSet<_>>()
+        );
+        // No list is in two libraries.
+        assert_eq!(all.len(), in_libraries.len());
+    }
+}
@@ -8,7 +8,12 @@
 //! Unlike its siblings, this module should be considered private and only used
 //! via the re-exports one level up.
 
+mod input;
 mod mutex;
+mod pacing;
+mod panic_report;
+mod replay;
+mod thread_dump;
 
 use crate::abi::{CallFromHost, GuestRet};
 use crate::libc::semaphore::sem_t;
@@ -23,10 +28,18 @@ use std::time::{Duration, Instant};
 
 use crate::libc::pthread::cond::pthread_cond_t;
 pub use mutex::{MutexId, MutexType, PTHREAD_MUTEX_DEFAULT};
+pub use panic_report::{locate_address, AddressLocation};
 
 /// Index into the [Vec] of threads. Thread 0 is always the main thread.
 pub type ThreadId = usize;
 
+/// The first whole page in a secondary thread's stack allocation, which is
+/// used as the guard page.
+fn stack_guard_page(stack_alloc: MutVoidPtr) -> GuestUSize {
+    let page_size = mem::Mem::PAGE_SIZE;
+    (stack_alloc.to_bits() + page_size - 1) & !(page_size - 1)
+}
+
 /// Bookkeeping for a thread.
 pub struct Thread {
     /// Once a thread finishes, this is set to false.
@@ -56,6 +69,10 @@ pub struct Thread {
     /// frame" of the thread is a host function, not whether there are any host
     /// functions at all.
     in_host_function: bool,
+    /// Number of host-to-guest calls ([Environment::run_call]) this thread is
+    /// in the middle of. A thread with pending calls can't be terminated
+    /// without breaking the host call stack.
+    pending_host_to_guest_calls: u32,
     /// Context object containing the CPU state for this thread.
     ///
     /// There should always be `(threads.len() - 1)` contexts in existence.
@@ -66,6 +83,9 @@ pub struct Thread {
     /// Address range of this thread's stack, used to check if addresses are in
     /// range while producing a stack trace.
     stack: Option<std::ops::RangeInclusive<u32>>,
+    /// Allocation containing the stack and the guard page below it, for
+    /// secondary threads.
+    stack_alloc: Option<MutVoidPtr>,
 }
 
 impl Thread {
@@ -79,6 +99,8 @@ impl Thread {
 pub struct Environment {
     /// Reference point for various timing functions.
     pub startup_time: Instant,
+    /// Source of clock readings and random numbers for the app.
+    pub replay: replay::Replay,
     pub bundle: bundle::Bundle,
     pub fs: fs::Fs,
     /// The window is only absent when running in headless mode.
@@ -97,6 +119,12 @@ pub struct Environment {
     pub mutex_state: mutex::MutexState,
     pub options: options::Options,
     gdb_server: Option<gdb::GdbServer>,
+    /// See [Environment::set_frame_pacing].
+    frame_pacing: Option<pacing::FramePacing>,
+    /// See [Environment::push_input_event].
+    input_queue: input::InputQueue,
+    /// See [Environment::install_panic_reporter].
+    panic_reporter_installed: bool,
     pub env_vars: HashMap<Vec<u8>, MutPtr<u8>>,
 }
 
@@ -140,6 +168,12 @@ impl Environment {
     /// old environment, but their states will be reset, so the result should be
     /// "like new". This option exists because touchHLE on Android would crash
     /// when allocating a second [mem::Mem] instance.
+    ///
+    /// The app receives each of the `launch_options` in `options` in three
+    /// ways: as the command-line arguments `-KEY VALUE` (the same syntax
+    /// `NSUserDefaults` understands on a real device), as an environment
+    /// variable, and in the launch options dictionary passed to the app
+    /// delegate.
     pub fn new(
         bundle: bundle::Bundle,
         fs: fs::Fs,
@@ -147,6 +181,14 @@ impl Environment {
         env_for_salvage: Option<Environment>,
     ) -> Result<Environment, String> {
         let startup_time = Instant::now();
+        let replay = match (&options.record_inputs, &options.replay_inputs) {
+            (Some(_), Some(_)) => {
+                return Err("--record-inputs= and --replay-inputs= can't be used together".into())
+            }
+            (Some(path), None) => replay::Replay::record(path, startup_time)?,
+            (None, Some(path)) => replay::Replay::replay(path, startup_time)?,
+            (None, None) => replay::Replay::live(startup_time),
+        };
 
         // Extract things to salvage from the old environment, and then drop it.
         // This needs to be done before creating a new window, because SDL2 only
@@ -214,6 +256,12 @@ impl Environment {
         if is_spore {
             log!("Applying game-specific hack for Spore Origins: zeroing memory on alloc instead of free.");
         }
+        if options.heap_canaries {
+            mem.enable_heap_canaries();
+        }
+        if options.strict_alignment {
+            mem.enable_strict_alignment();
+        }
 
         let executable = mach_o::MachO::load_from_file(bundle.executable_path(), &fs, &mut mem)
             .map_err(|e| format!("Could not load executable: {}", e))?;
@@ -244,9 +292,12 @@ impl Environment {
             };
         }
 
+        // The loader only checks this for binaries marked as executables.
         let entry_point_addr = executable.entry_point_pc.ok_or_else(|| {
-            "Mach-O file does not specify an entry point PC, perhaps it is not an executable?"
-                .to_string()
+            format!(
+                "Could not load executable: {}",
+                mach_o::MachOError::MissingEntryPoint
+            )
         })?;
         let entry_point_addr = abi::GuestFunction::from_addr_with_thumb_bit(entry_point_addr);
 
@@ -256,14 +307,35 @@ impl Environment {
         bins.insert(0, executable);
 
         let mut objc = objc::ObjC::new();
+        if options.zombies {
+            objc.enable_zombies();
+        }
+        if options.track_objects {
+            objc.enable_object_tracking();
+        }
+        for name in &options.trace_classes {
+            objc.trace_class(name);
+        }
 
         let mut dyld = dyld::Dyld::new();
         dyld.do_initial_linking(&bins, &mut mem, &mut objc);
+        if let Some(include_metaclasses) = options.dump_class_hierarchy {
+            objc.dump_class_hierarchy(&mem, include_metaclasses);
+        }
+        if options.dump_imports {
+            dyld::Dyld::dump_import_report(&bins);
+        }
 
-        let cpu = cpu::Cpu::new(match options.direct_memory_access {
-            true => Some(&mut mem),
-            false => None,
-        });
+        // Direct memory access would bypass the alignment check in Mem.
+        let mut cpu = cpu::Cpu::new(
+            match options.direct_memory_access && !options.strict_alignment {
+                true => Some(&mut mem),
+                false => None,
+            },
+        );
+        if options.eager_linking {
+            dyld.eager_link_all(&bins, &mut mem, &mut cpu);
+        }
 
         let main_thread = Thread {
             active: true,
@@ -271,12 +343,15 @@ impl Environment {
             return_value: None,
             in_start_routine: false, // main thread never terminates
             in_host_function: false,
+            pending_host_to_guest_calls: 0,
             context: None,
             stack: Some(mem::Mem::MAIN_THREAD_STACK_LOW_END..=0u32.wrapping_sub(1)),
+            stack_alloc: None, // the stack and its guard page are reserved
         };
 
         let mut env = Environment {
             startup_time,
+            replay,
             bundle,
             fs,
             window,
@@ -292,11 +367,29 @@ impl Environment {
             framework_state: Default::default(),
             options,
             gdb_server: None,
+            frame_pacing: None,
+            input_queue: Default::default(),
+            panic_reporter_installed: false,
             env_vars: Default::default(),
         };
 
         env.set_up_initial_env_vars();
 
+        if let Some((frame_rate, tick_rate)) = env.options.frame_pacing {
+            let frame_interval = Duration::from_secs_f64(1.0 / frame_rate);
+            env.set_frame_pacing(
+                frame_interval,
+                tick_rate,
+                pacing::keep_in_step_with_wall_clock(frame_interval),
+            );
+        }
+
+        for coords in env.options.injected_taps.clone() {
+            let touches = HashMap::from([(window::FingerId::Touch(0), coords)]);
+            env.push_input_event(input::InputEvent::TouchesBegan(touches.clone()));
+            env.push_input_event(input::InputEvent::TouchesEnded(touches));
+        }
+
         dyld::Dyld::do_late_linking(&mut env);
 
         {
@@ -320,7 +413,11 @@ impl Environment {
 
             let bin_path_apple_key = format!("executable_path={}", bin_path.as_str());
 
-            let argv = &[bin_path.as_str()];
+            let launch_args: Vec<String> = launch_arguments(&env.options);
+            let argv: Vec<&str> = std::iter::once(bin_path.as_str())
+                .chain(launch_args.iter().map(String::as_str))
+                .collect();
+            let argv = argv.as_slice();
             let envp = envp_ref_list.as_slice();
             let apple = &[bin_path_apple_key.as_str()];
             stack::prep_stack_for_start(&mut env.mem, &mut env.cpu, argv, envp, apple);
@@ -394,6 +491,7 @@ impl Environment {
         let fs = fs::Fs::new_fake_fs();
 
         let startup_time = Instant::now();
+        let replay = replay::Replay::live(startup_time);
 
         let launch_image = None;
 
@@ -434,12 +532,15 @@ impl Environment {
             return_value: None,
             in_start_routine: false, // main thread never terminates
             in_host_function: false,
+            pending_host_to_guest_calls: 0,
             context: None,
             stack: Some(mem::Mem::MAIN_THREAD_STACK_LOW_END..=0u32.wrapping_sub(1)),
+            stack_alloc: None, // the stack and its guard page are reserved
         };
 
         let mut env = Environment {
             startup_time,
+            replay,
             bundle,
             fs,
             window,
@@ -455,6 +556,9 @@ impl Environment {
             framework_state: Default::default(),
             options,
             gdb_server: None,
+            frame_pacing: None,
+            input_queue: Default::default(),
+            panic_reporter_installed: false,
             env_vars: Default::default(),
         };
 
@@ -539,6 +643,9 @@ impl Environment {
         } else {
             echo!(" 1. {:#x} (LR)", lr);
         }
+        // A stack overflow caused by unbounded recursion can have many
+        // thousands of frames, so give up at some point.
+        const MAX_FRAMES: usize = 200;
         let mut i = 2;
         let mut fp: mem::ConstPtr<u8> = mem::Ptr::from_bits(regs[abi::FRAME_POINTER]);
         loop {
@@ -546,8 +653,20 @@ impl Environment {
                 echo!("Next FP ({:?}) is outside the stack.", fp);
                 break;
             }
-            lr = self.mem.read((fp + 4).cast());
-            fp = self.mem.read(fp.cast());
+            if i == MAX_FRAMES {
+                echo!("Stopping after {} frames.", MAX_FRAMES);
+                break;
+            }
+            // The frame pointer could be bogus if the stack is corrupted.
+            let (Some(next_lr), Some(next_fp)) = (
+                self.mem.try_read((fp + 4).cast()),
+                self.mem.try_read(fp.cast()),
+            ) else {
+                echo!("Frame at FP ({:?}) is unreadable.", fp);
+                break;
+            };
+            lr = next_lr;
+            fp = next_fp;
             if lr == return_to_host_routine_addr {
                 echo!("{:2}. [host function]", i);
             } else if lr == thread_exit_routine_addr {
@@ -560,6 +679,48 @@ impl Environment {
         }
     }
 
+    /// Address range of the current thread's stack.
+    pub fn current_thread_stack(&self) -> Option<std::ops::RangeInclusive<u32>> {
+        self.threads[self.current_thread].stack.clone()
+    }
+
+    /// Returns the address range of a thread's stack, or [None] if the thread
+    /// has finished and its stack was freed.
+    pub fn thread_stack(&self, thread: ThreadId) -> Option<std::ops::RangeInclusive<u32>> {
+        self.threads[thread].stack.clone()
+    }
+
+    /// Returns the return addresses of the guest functions on the current
+    /// thread's call stack, innermost first. Host functions are skipped.
+    pub fn guest_backtrace(&self) -> Vec<u32> {
+        let return_to_host_routine_addr = self.dyld.return_to_host_routine().addr_with_thumb_bit();
+        let thread_exit_routine_addr = self.dyld.thread_exit_routine().addr_with_thumb_bit();
+        let Some(stack_range) = self.threads[self.current_thread].stack.clone() else {
+            return Vec::new();
+        };
+
+        let regs = self.cpu.regs();
+        let mut addrs = vec![regs[cpu::Cpu::LR]];
+        let mut fp: mem::ConstPtr<u8> = mem::Ptr::from_bits(regs[abi::FRAME_POINTER]);
+        while stack_range.contains(&fp.to_bits()) && addrs.last() != Some(&thread_exit_routine_addr)
+        {
+            // This is used for diagnostics, so a bogus frame pointer shouldn't
+            // cause a panic.
+            let (Some(lr), Some(next_fp)) = (
+                self.mem.try_read((fp + 4).cast()),
+                self.mem.try_read(fp.cast()),
+            ) else {
+                break;
+            };
+            addrs.push(lr);
+            fp = next_fp;
+        }
+        addrs.retain(|&addr| {
+            addr != return_to_host_routine_addr && addr != thread_exit_routine_addr
+        });
+        addrs
+    }
+
     /// Create a new thread and return its ID. The `start_routine` and
     /// `user_data` arguments have the same meaning as the last two arguments to
     /// `pthread_create`.
@@ -569,9 +730,15 @@ impl Environment {
         user_data: mem::MutVoidPtr,
         stack_size: GuestUSize,
     ) -> ThreadId {
-        let stack_alloc = self.mem.alloc(stack_size);
-        let stack_high_addr = stack_alloc.to_bits() + stack_size;
+        // The stack is preceded by a guard page, so that overflowing it causes
+        // a fault rather than corrupting whatever is below it.
+        let stack_alloc = self.mem.alloc(stack_size + 2 * mem::Mem::PAGE_SIZE);
+        let guard_page = stack_guard_page(stack_alloc);
+        let stack_low_addr = guard_page + mem::Mem::PAGE_SIZE;
+        let stack_high_addr = stack_low_addr + stack_size;
         assert!(stack_high_addr % 4 == 0);
+        self.mem.add_guard_page(guard_page);
+        self.cpu.set_page_trapped(guard_page, true);
 
         self.threads.push(Thread {
             active: true,
@@ -579,12 +746,20 @@ impl Environment {
             return_value: None,
             in_start_routine: true,
             in_host_function: false,
+            pending_host_to_guest_calls: 0,
             context: Some(cpu::CpuContext::new()),
-            stack: Some(stack_alloc.to_bits()..=(stack_high_addr - 1)),
+            stack: Some(stack_low_addr..=(stack_high_addr - 1)),
+            stack_alloc: Some(stack_alloc),
         });
         let new_thread_id = self.threads.len() - 1;
+        self.mem.add_region(
+            stack_low_addr,
+            stack_size,
+            mem::Protection::READ_WRITE,
+            format!("stack of thread {}", new_thread_id),
+        );
 
-        log_dbg!("Created new thread {} with stack {:#x}–{:#x}, will execute function {:?} with data {:?}", new_thread_id, stack_alloc.to_bits(), (stack_high_addr - 1), start_routine, user_data);
+        log_dbg!("Created new thread {} with stack {:#x}–{:#x}, will execute function {:?} with data {:?}", new_thread_id, stack_low_addr, (stack_high_addr - 1), start_routine, user_data);
 
         let old_thread = self.current_thread;
 
@@ -603,9 +778,18 @@ impl Environment {
     }
 
     /// Put the current thread to sleep for some duration, running other threads
-    /// in the meantime as appropriate. Functions that call sleep right before
-    /// they return back to the main run loop ([Environment::run]) should set
-    /// `tail_call`.
+    /// in the meantime as appropriate.
+    ///
+    /// With `tail_call`, the sleep only takes effect once the calling host
+    /// function returns to the main run loop ([Environment::run]): this returns
+    /// immediately, and the guest code after the call runs once the thread
+    /// wakes up. The caller must return straight away, without touching guest
+    /// state or blocking again. This is cheap, so host functions the app calls
+    /// directly, like `usleep`, should use it.
+    ///
+    /// Without `tail_call`, this runs other threads until the current one wakes
+    /// up and only then returns, so the caller can carry on afterwards, e.g. to
+    /// poll something in a loop. This nests a call to [Environment::run_call].
     pub fn sleep(&mut self, duration: Duration, tail_call: bool) {
         assert!(matches!(
             self.threads[self.current_thread].blocked_by,
@@ -743,6 +927,21 @@ impl Environment {
         self.threads[self.current_thread].blocked_by = ThreadBlock::Joining(joinee_thread, ptr);
     }
 
+    /// Send a message to an Objective-C object from host code, for use when
+    /// the selector is only known at runtime (otherwise, prefer
+    /// [objc::msg]). The arguments are passed as a tuple, e.g. `(a, b)` for a
+    /// two-argument selector. See [objc::MsgSendArgs] for the supported
+    /// argument and return types.
+    ///
+    /// Like [objc::msg], the types are inferred from the call-site and they may
+    /// not be checked, so be very sure you get them correct!
+    pub fn call_selector<R, A>(&mut self, receiver: objc::id, selector: objc::SEL, args: A) -> R
+    where
+        A: objc::MsgSendArgs<R>,
+    {
+        args.send(self, receiver, selector)
+    }
+
     /// Run the emulator. This is the main loop and won't return until app exit.
     /// Only `main.rs` should call this.
     pub fn run(&mut self) {
@@ -752,7 +951,11 @@ impl Environment {
         if let Err(e) = res {
             echo!("Register state immediately after panic:");
             self.cpu.dump_regs();
-            self.stack_trace();
+            if self.panic_reporter_installed {
+                self.print_panic_report();
+            } else {
+                self.stack_trace();
+            }
             std::panic::resume_unwind(e);
         }
     }
@@ -760,17 +963,117 @@ impl Environment {
     /// Run the emulator until the app returns control to the host. This is for
     /// host-to-guest function calls (see [abi::CallFromHost::call_from_host]).
     ///
+    /// The app returns control by branching to
+    /// [dyld::Dyld::return_to_host_routine] (see
+    /// [Self::handle_return_to_host]).
+    ///
     /// Note that this might execute code from other threads while waiting for
     /// the app to return control on the original thread!
+    ///
+    /// In debug builds, this checks that the stack pointer is the same on
+    /// return as it was on entry, as the calling convention requires. A guest
+    /// function that doesn't balance its stack would otherwise only cause
+    /// problems much later, far away from the actual bug.
     pub fn run_call(&mut self) {
         let was_in_host_function = self.threads[self.current_thread].in_host_function;
         let old_thread = self.current_thread;
+        let old_sp = self.cpu.regs()[cpu::Cpu::SP];
         self.threads[self.current_thread].in_host_function = false;
+        self.threads[self.current_thread].pending_host_to_guest_calls += 1;
         self.run_inner(false);
         assert!(self.current_thread == old_thread);
+        let new_sp = self.cpu.regs()[cpu::Cpu::SP];
+        debug_assert!(
+            new_sp == old_sp,
+            "Guest code returned to the host with an unbalanced stack: SP was {:#x} on entry \
+             but is {:#x} on return ({} bytes). This is probably an ABI mismatch.",
+            old_sp,
+            new_sp,
+            new_sp.wrapping_sub(old_sp) as i32,
+        );
+        self.threads[self.current_thread].pending_host_to_guest_calls -= 1;
         self.threads[self.current_thread].in_host_function = was_in_host_function;
     }
 
+    /// Enable frame pacing: the main loop will run the guest for a budget of
+    /// `frame_interval * ticks_per_second` CPU ticks (roughly, instructions)
+    /// per frame, and call `on_frame` at the end of each frame. See
+    /// [pacing] for the details. This is used for `--frame-pacing=`.
+    pub fn set_frame_pacing(
+        &mut self,
+        frame_interval: Duration,
+        ticks_per_second: u64,
+        on_frame: impl FnMut(&mut Environment) + 'static,
+    ) {
+        self.frame_pacing = Some(pacing::FramePacing::new(
+            frame_interval,
+            ticks_per_second,
+            Box::new(on_frame),
+        ));
+    }
+
+    /// Queue an input event, to be dispatched to the app the next time the
+    /// main thread's run loop handles events. See [input] for the details.
+    /// This is used for `--inject-tap=`.
+    pub fn push_input_event(&mut self, event: input::InputEvent) {
+        log_dbg!("Queueing input event {:?}", event);
+        self.input_queue.push(event);
+    }
+
+    /// For use by UIKit: take the oldest queued input event, if any.
+    pub fn pop_input_event(&mut self) -> Option<input::InputEvent> {
+        self.input_queue.pop()
+    }
+
+    /// The number of the current frame (counting from app startup) and the
+    /// frame interval. With frame pacing (see [Environment::set_frame_pacing])
+    /// this counts paced frames, otherwise it counts 60ths of a second of
+    /// wall-clock time. This is the clock used for `CADisplayLink`.
+    pub fn current_frame(&mut self) -> (u64, Duration) {
+        if let Some(ref frame_pacing) = self.frame_pacing {
+            return frame_pacing.current_frame();
+        }
+        let frame_interval = Duration::from_secs(1) / 60;
+        let since_startup = self.replay.instant().duration_since(self.startup_time);
+        let frame = since_startup.as_nanos() / frame_interval.as_nanos();
+        (frame.try_into().unwrap(), frame_interval)
+    }
+
+    /// When the frame after the current one (see [Environment::current_frame])
+    /// is expected to start, if known. A run loop waiting for the next frame
+    /// can sleep until then.
+    pub fn next_frame_start(&mut self) -> Option<Instant> {
+        if let Some(ref frame_pacing) = self.frame_pacing {
+            return frame_pacing.next_frame_start();
+        }
+        let (current_frame, frame_interval) = self.current_frame();
+        u32::try_from(current_frame + 1)
+            .ok()
+            .and_then(|next_frame| frame_interval.checked_mul(next_frame))
+            .and_then(|since_startup| self.startup_time.checked_add(since_startup))
+    }
+
+    /// Charge the ticks spent in the last slice to the current frame, and call
+    /// the frame callback if the frame is over.
+    fn account_frame_ticks(&mut self, ticks: u64) {
+        let Some(ref mut frame_pacing) = self.frame_pacing else {
+            return;
+        };
+        if !frame_pacing.account_ticks(ticks) {
+            return;
+        }
+        // The callback is taken out while it runs, so that it can have a
+        // mutable reference to the environment. If the callback calls into
+        // guest code, any frame boundaries during that call are skipped.
+        let Some(mut on_frame) = frame_pacing.take_callback() else {
+            return;
+        };
+        on_frame(self);
+        if let Some(ref mut frame_pacing) = self.frame_pacing {
+            frame_pacing.restore_callback(on_frame);
+        }
+    }
+
     fn switch_thread(&mut self, new_thread: ThreadId) {
         assert!(new_thread != self.current_thread);
 
@@ -792,9 +1095,12 @@ impl Environment {
     /// connected. Returns [true] if the CPU should step and then resume
     /// debugging, or [false] if it should resume normal execution.
     fn debug_cpu_error(&mut self, error: cpu::CpuError) -> bool {
-        if matches!(error, cpu::CpuError::UndefinedInstruction)
-            || matches!(error, cpu::CpuError::Breakpoint)
-        {
+        if matches!(
+            error,
+            cpu::CpuError::UndefinedInstruction
+                | cpu::CpuError::PrivilegedInstruction(_)
+                | cpu::CpuError::Breakpoint
+        ) {
             // Rewind the PC so that it's at the instruction where the error
             // occurred, rather than the next instruction. This is necessary for
             // GDB to detect its software breakpoints. For some reason this
@@ -808,6 +1114,23 @@ impl Environment {
         }
 
         if self.gdb_server.is_none() {
+            if let (cpu::CpuError::UndefinedInstruction, Some((pc, encoding))) =
+                (&error, self.cpu.undefined_instruction())
+            {
+                panic!(
+                    "Error during CPU execution: unsupported instruction {:#010x} at {:#x}",
+                    encoding, pc
+                );
+            }
+            if let (cpu::CpuError::PrivilegedInstruction(name), Some((pc, encoding))) =
+                (&error, self.cpu.undefined_instruction())
+            {
+                panic!(
+                    "Error during CPU execution: privileged instruction {} ({:#010x}) at {:#x}. \
+                     Apps run in user mode, so they can't use it.",
+                    name, encoding, pc
+                );
+            }
             panic!("Error during CPU execution: {:?}", error);
         }
 
@@ -836,6 +1159,16 @@ impl Environment {
             .wait_for_debugger(reason, &mut self.cpu, &mut self.mem)
     }
 
+    /// Whether the SVC instruction at `svc_pc`, which was just executed, is one
+    /// the linker wrote, rather than one that's part of the app.
+    fn is_linker_svc(&self, svc_pc: u32) -> bool {
+        // The linker only writes Arm (A32) instructions.
+        if (self.cpu.cpsr() & cpu::Cpu::CPSR_THUMB) != 0 {
+            return false;
+        }
+        self.dyld.is_linker_svc(svc_pc)
+    }
+
     #[inline(always)]
     /// Respond to the new CPU state (do nothing, execute an SVC or enter
     /// debugging) and decide what to do next.
@@ -845,13 +1178,28 @@ impl Environment {
         initial_thread: ThreadId,
         root: bool,
     ) -> ThreadNextAction {
+        // If this is an SVC, the program counter is pointing at the instruction
+        // after it, but we want the address of the SVC itself.
+        let svc_pc = if (self.cpu.cpsr() & cpu::Cpu::CPSR_THUMB) != 0 {
+            self.cpu.regs()[cpu::Cpu::PC] - 2
+        } else {
+            self.cpu.regs()[cpu::Cpu::PC] - 4
+        };
+        let state = match state {
+            cpu::CpuState::Svc(svc) if !self.is_linker_svc(svc_pc) => {
+                log!(
+                    "SVC #{:#x} at {:#x} was not written by the linker, so it's probably a \
+                     system call made directly by the app. These are not supported.",
+                    svc,
+                    svc_pc
+                );
+                cpu::CpuState::Error(cpu::CpuError::UnexpectedSvc(svc))
+            }
+            state => state,
+        };
         match state {
             cpu::CpuState::Normal => ThreadNextAction::Continue,
             cpu::CpuState::Svc(svc) => {
-                // the program counter is pointing at the
-                // instruction after the SVC, but we want the
-                // address of the SVC itself
-                let svc_pc = self.cpu.regs()[cpu::Cpu::PC] - 4;
                 match svc {
                     dyld::Dyld::SVC_THREAD_EXIT => {
                         assert!(svc_pc == self.dyld.thread_exit_routine().addr_without_thumb_bit());
@@ -864,77 +1212,190 @@ impl Environment {
                                 self.current_thread,
                                 initial_thread
                             );
-                            let curr_thread = &mut self.threads[self.current_thread];
-                            curr_thread.return_value = Some(GuestRet::from_regs(self.cpu.regs()));
-                            curr_thread.active = false;
-                            let stack = curr_thread.stack.take().unwrap();
-                            let stack: mem::MutVoidPtr = mem::Ptr::from_bits(*stack.start());
-                            log_dbg!("Freeing thread {} stack {:?}", self.current_thread, stack);
-                            self.mem.free(stack);
+                            let return_value = GuestRet::from_regs(self.cpu.regs());
+                            self.finish_current_thread(return_value);
                             ThreadNextAction::Yield
                         }
                     }
                     dyld::Dyld::SVC_RETURN_TO_HOST => {
-                        assert!(
-                            svc_pc == self.dyld.return_to_host_routine().addr_without_thumb_bit()
-                        );
-                        assert!(!root);
-                        if self.current_thread == initial_thread {
-                            log_dbg!(
-                                "Thread {} returned from host-to-guest call",
-                                self.current_thread
-                            );
-                            // Normal return from host-to-guest call.
-                            ThreadNextAction::ReturnToHost
-                        } else {
-                            // FIXME?: A drawback of the current thread model is
-                            // that host-to-guest calls affect the host call
-                            // stack. This is a problem because it means that
-                            // threads have to return in the order they were
-                            // called, which means that threads that return
-                            // while they aren't at the top of the call stack
-                            // have to wait until they can.
-                            log_dbg!("Thread {} returned from host-to-guest call but thread {} is top of call stack, deferring!",
-                                     self.current_thread,
-                                     initial_thread
-                            );
-                            self.threads[self.current_thread].blocked_by =
-                                ThreadBlock::DeferredReturn;
-                            ThreadNextAction::Yield
-                        }
+                        self.handle_return_to_host(svc_pc, initial_thread, root)
                     }
                     dyld::Dyld::SVC_LAZY_LINK | dyld::Dyld::SVC_LINKED_FUNCTIONS_BASE.. => {
-                        if let Some(f) = self.dyld.get_svc_handler(
+                        let handler = self.dyld.get_svc_handler(
                             &self.bins,
                             &mut self.mem,
                             &mut self.cpu,
                             svc_pc,
                             svc,
-                        ) {
-                            let was_in_host_function =
-                                self.threads[self.current_thread].in_host_function;
-                            self.threads[self.current_thread].in_host_function = true;
-                            f.call_from_guest(self);
-                            self.threads[self.current_thread].in_host_function =
-                                was_in_host_function;
-                            // Host function might have put the thread to sleep.
-                            if let ThreadBlock::NotBlocked =
-                                self.threads[self.current_thread].blocked_by
-                            {
+                        );
+                        match handler {
+                            dyld::SvcHandler::HostFunction(f) => {
+                                let was_in_host_function =
+                                    self.threads[self.current_thread].in_host_function;
+                                self.threads[self.current_thread].in_host_function = true;
+                                f.call_from_guest(self);
+                                self.threads[self.current_thread].in_host_function =
+                                    was_in_host_function;
+                                // Host function might have put the thread to
+                                // sleep.
+                                if let ThreadBlock::NotBlocked =
+                                    self.threads[self.current_thread].blocked_by
+                                {
+                                    ThreadNextAction::Continue
+                                } else {
+                                    log_dbg!(
+                                        "Yielding: thread {} is blocked.",
+                                        self.current_thread
+                                    );
+                                    ThreadNextAction::Yield
+                                }
+                            }
+                            dyld::SvcHandler::Restart => {
+                                self.cpu.regs_mut()[cpu::Cpu::PC] = svc_pc;
                                 ThreadNextAction::Continue
-                            } else {
-                                log_dbg!("Yielding: thread {} is blocked.", self.current_thread);
-                                ThreadNextAction::Yield
                             }
-                        } else {
-                            self.cpu.regs_mut()[cpu::Cpu::PC] = svc_pc;
-                            ThreadNextAction::Continue
+                            dyld::SvcHandler::Unexpected => self.handle_cpu_state(
+                                cpu::CpuState::Error(cpu::CpuError::UnexpectedSvc(svc)),
+                                initial_thread,
+                                root,
+                            ),
                         }
                     }
                 }
             }
-            cpu::CpuState::Error(e) => ThreadNextAction::DebugCpuError(e),
+            cpu::CpuState::Error(e) => {
+                if self.gdb_server.is_none()
+                    && matches!(
+                        e,
+                        cpu::CpuError::MemoryError | cpu::CpuError::MisalignedAccess
+                    )
+                    && libc::signal::deliver_memory_fault(
+                        self,
+                        matches!(e, cpu::CpuError::MisalignedAccess),
+                    )
+                {
+                    ThreadNextAction::Continue
+                } else if self.gdb_server.is_none()
+                    && self.options.terminate_faulting_threads
+                    && self.can_terminate_current_thread(initial_thread)
+                {
+                    self.terminate_faulting_thread(e);
+                    ThreadNextAction::Yield
+                } else {
+                    ThreadNextAction::DebugCpuError(e)
+                }
+            }
+        }
+    }
+
+    /// Handle the SVC in [dyld::Dyld::return_to_host_routine], which is the
+    /// only way guest code returns control to [Self::run_call]. Every
+    /// host-to-guest call sets the link register to that routine, so it is
+    /// reached when the called guest function returns.
+    ///
+    /// If the current thread is `initial_thread`, the thread that called
+    /// [Self::run_call], the call is over and the emulator returns to the
+    /// host. Otherwise, the return belongs to a host-to-guest call further up
+    /// the host call stack, so it has to wait until that call is on top again
+    /// (see [ThreadBlock::DeferredReturn]).
+    ///
+    /// There is no host caller to return to when running the app's main
+    /// function (`root`), so a return-to-host then is treated as a CPU error.
+    fn handle_return_to_host(
+        &mut self,
+        svc_pc: u32,
+        initial_thread: ThreadId,
+        root: bool,
+    ) -> ThreadNextAction {
+        assert!(svc_pc == self.dyld.return_to_host_routine().addr_without_thumb_bit());
+        if root {
+            log!(
+                "Thread {} tried to return to the host at {:#x}, but there is no host-to-guest \
+                 call in progress.",
+                self.current_thread,
+                svc_pc
+            );
+            let error = cpu::CpuError::UnexpectedSvc(dyld::Dyld::SVC_RETURN_TO_HOST);
+            return self.handle_cpu_state(cpu::CpuState::Error(error), initial_thread, root);
         }
+        if self.current_thread == initial_thread {
+            log_dbg!(
+                "Thread {} returned from host-to-guest call",
+                self.current_thread
+            );
+            // Normal return from host-to-guest call.
+            ThreadNextAction::ReturnToHost
+        } else {
+            // FIXME?: A drawback of the current thread model is that
+            // host-to-guest calls affect the host call stack. This is a problem
+            // because it means that threads have to return in the order they
+            // were called, which means that threads that return while they
+            // aren't at the top of the call stack have to wait until they can.
+            log_dbg!(
+                "Thread {} returned from host-to-guest call but thread {} is top of call stack, \
+                 deferring!",
+                self.current_thread,
+                initial_thread
+            );
+            self.threads[self.current_thread].blocked_by = ThreadBlock::DeferredReturn;
+            ThreadNextAction::Yield
+        }
+    }
+
+    /// Mark the current thread as finished and free its stack. Another thread
+    /// must be switched to afterwards.
+    fn finish_current_thread(&mut self, return_value: mem::MutVoidPtr) {
+        let thread_id = self.current_thread;
+        frameworks::foundation::ns_thread::thread_did_exit(self, thread_id);
+        let curr_thread = &mut self.threads[self.current_thread];
+        curr_thread.return_value = Some(return_value);
+        curr_thread.active = false;
+        let stack = curr_thread.stack.take().unwrap();
+        let stack_alloc = curr_thread.stack_alloc.take().unwrap();
+        log_dbg!(
+            "Freeing thread {} stack {:?}",
+            self.current_thread,
+            stack_alloc
+        );
+        let guard_page = stack_guard_page(stack_alloc);
+        self.cpu.set_page_trapped(guard_page, false);
+        self.mem.remove_guard_page(guard_page);
+        self.mem.remove_region(*stack.start());
+        self.mem.free(stack_alloc);
+    }
+
+    /// Whether the current thread could be terminated after a CPU error
+    /// without affecting other threads (see `--terminate-faulting-threads`).
+    /// The main thread is never terminated, since the app can't continue
+    /// without it, and neither is a thread the host is waiting on.
+    fn can_terminate_current_thread(&self, initial_thread: ThreadId) -> bool {
+        let thread = &self.threads[self.current_thread];
+        self.current_thread != 0
+            && self.current_thread != initial_thread
+            && thread.in_start_routine
+            && !thread.in_host_function
+            && thread.pending_host_to_guest_calls == 0
+    }
+
+    #[cold]
+    /// Terminate the current (secondary) thread after a CPU error, leaving the
+    /// other threads running. Threads joining with it will receive a null
+    /// return value.
+    ///
+    /// Any mutexes held by the thread remain locked, so this can still cause a
+    /// deadlock later on.
+    fn terminate_faulting_thread(&mut self, error: cpu::CpuError) {
+        echo!(
+            "Error during CPU execution on thread {}: {:?}. Terminating the thread.",
+            self.current_thread,
+            error
+        );
+        echo!("Register state immediately after error:");
+        self.cpu.dump_regs();
+        self.stack_trace();
+        echo!("Guest memory regions:");
+        self.mem.dump_regions();
+        self.finish_current_thread(mem::Ptr::null());
     }
 
     fn run_inner(&mut self, root: bool) {
@@ -952,9 +1413,12 @@ impl Environment {
                 // immediately switch to another thread. This only happens when
                 // called from Self::sleep().
                 0
+            } else if let Some(ref frame_pacing) = self.frame_pacing {
+                frame_pacing.slice_ticks(100_000)
             } else {
                 100_000
             };
+            let slice_ticks = ticks;
             let mut step_and_debug = false;
             while ticks > 0 {
                 let state = self.cpu.run_or_step(
@@ -976,13 +1440,18 @@ impl Environment {
                         }
                     }
                     ThreadNextAction::Yield => break,
-                    ThreadNextAction::ReturnToHost => return,
+                    ThreadNextAction::ReturnToHost => {
+                        self.account_frame_ticks(slice_ticks - ticks);
+                        return;
+                    }
                     ThreadNextAction::DebugCpuError(e) => {
                         step_and_debug = self.debug_cpu_error(e);
                     }
                 }
             }
 
+            self.account_frame_ticks(slice_ticks - ticks);
+
             // To maintain responsiveness when moving the window and so on, we
             // need to poll for events occasionally, even if the app isn't
             // actively processing them.
@@ -1121,7 +1590,10 @@ impl Environment {
                 // All suitable threads are blocked and at least one is asleep.
                 // Sleep until one of them wakes up.
                 } else if let Some(next_awakening) = next_awakening {
-                    let duration = next_awakening.duration_since(Instant::now());
+                    // The guest is idle, so it's done with the current frame.
+                    // The frame callback might sleep, so this comes first.
+                    self.account_frame_ticks(u64::MAX);
+                    let duration = next_awakening.saturating_duration_since(Instant::now());
                     log_dbg!("All threads blocked/asleep, sleeping for {:?}.", duration);
                     std::thread::sleep(duration);
                     // Try again, there should be some thread awake now (or
@@ -1131,6 +1603,10 @@ impl Environment {
                     // This should hopefully not happen, but if a thread is
                     // blocked on another thread waiting for a deferred return,
                     // it could.
+                    if !self.panic_reporter_installed {
+                        // Otherwise this is part of the panic report.
+                        self.dump_threads();
+                    }
                     panic!("No active threads, program has deadlocked!");
                 }
             }
@@ -1146,5 +1622,22 @@ impl Environment {
             .mem
             .alloc_and_write_cstr(self.fs.home_directory().as_str().as_bytes());
         self.env_vars.insert(b"HOME".to_vec(), home_value_cstr);
+
+        for (key, value) in self.options.launch_options.clone() {
+            let value_cstr = self.mem.alloc_and_write_cstr(value.as_bytes());
+            if let Some(old) = self.env_vars.insert(key.into_bytes(), value_cstr) {
+                self.mem.free(old.cast());
+            }
+        }
     }
 }
+
+/// The command-line arguments for the app, not including the executable path.
+/// See [Environment::new].
+pub fn launch_arguments(options: &options::Options) -> Vec<String> {
+    options
+        .launch_options
+        .iter()
+        .flat_map(|(key, value)| [format!("-{}", key), value.clone()])
+        .collect()
+}
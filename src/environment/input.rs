@@ -0,0 +1,85 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Input events pushed by the embedder or by `--inject-tap=` (see
+//! [super::Environment::push_input_event]).
+//!
+//! Events from the [crate::window] are handled directly by UIKit. This queue
+//! is for events that come from somewhere else, and it works in headless mode
+//! too. Pushing an event only queues it: it is dispatched the next time the
+//! main thread's run loop handles events, in the order the events were pushed.
+
+use crate::window::{Coords, Event, FingerId};
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    TouchesBegan(HashMap<FingerId, Coords>),
+    TouchesMoved(HashMap<FingerId, Coords>),
+    TouchesEnded(HashMap<FingerId, Coords>),
+}
+
+impl InputEvent {
+    /// Convert to the equivalent window event, which is what UIKit consumes.
+    pub fn into_window_event(self) -> Event {
+        match self {
+            InputEvent::TouchesBegan(touches) => Event::TouchesDown(touches),
+            InputEvent::TouchesMoved(touches) => Event::TouchesMove(touches),
+            InputEvent::TouchesEnded(touches) => Event::TouchesUp(touches),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InputQueue {
+    events: VecDeque<InputEvent>,
+}
+
+impl InputQueue {
+    pub fn push(&mut self, event: InputEvent) {
+        self.events.push_back(event);
+    }
+
+    /// Pop the oldest event, if any.
+    pub fn pop(&mut self) -> Option<InputEvent> {
+        self.events.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_queue_order() {
+        let finger = FingerId::Touch(0);
+        let events = [
+            InputEvent::TouchesBegan(HashMap::from([(finger, (10.0, 20.0))])),
+            InputEvent::TouchesMoved(HashMap::from([(finger, (15.0, 25.0))])),
+            InputEvent::TouchesMoved(HashMap::from([(finger, (20.0, 30.0))])),
+            InputEvent::TouchesEnded(HashMap::from([(finger, (20.0, 30.0))])),
+        ];
+
+        let mut queue = InputQueue::default();
+        for event in events.iter().cloned() {
+            queue.push(event);
+        }
+        // Events pushed while draining come after the existing ones.
+        let mut drained = Vec::new();
+        while let Some(event) = queue.pop() {
+            if drained.is_empty() {
+                queue.push(events[0].clone());
+            }
+            drained.push(event);
+        }
+        assert_eq!(drained[..events.len()], events);
+        assert_eq!(drained[events.len()..], events[..1]);
+
+        let Event::TouchesMove(touches) = drained[1].clone().into_window_event() else {
+            panic!();
+        };
+        assert_eq!(touches[&finger], (15.0, 25.0));
+    }
+}
@@ -0,0 +1,146 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Frame pacing for the main loop (see [super::Environment::set_frame_pacing]).
+//!
+//! The main loop runs the guest in slices of CPU ticks (roughly one tick per
+//! guest instruction), switching threads and polling for events in between.
+//! Without frame pacing, nothing ties those slices to wall-clock time. With
+//! frame pacing, the embedder picks a target frame interval and a tick rate,
+//! and touchHLE runs the guest for `frame_interval * ticks_per_second` ticks
+//! per frame, calling back into the embedder at each frame boundary.
+//!
+//! The budget only counts ticks actually spent executing guest code: time
+//! spent in host functions doesn't count. If all threads are asleep, the guest
+//! is idle (e.g. waiting in a run loop), so the current frame ends early.
+//!
+//! The frame count is also the clock that drives `CADisplayLink`.
+//!
+//! `--frame-pacing=` uses [keep_in_step_with_wall_clock] as the callback.
+
+use super::Environment;
+use std::time::{Duration, Instant};
+
+/// Called at each frame boundary. This can block (e.g. to wait for vsync
+/// while presenting) if the embedder wants to keep frames in step with
+/// wall-clock time.
+///
+/// Note that a frame boundary can happen while the guest is in the middle of
+/// a host-to-guest call, so the callback must not assume anything about the
+/// guest's state.
+pub type FrameCallback = Box<dyn FnMut(&mut Environment)>;
+
+/// Make a frame callback that sleeps until each frame is due, so that frames
+/// last at least `frame_interval`. If the guest falls behind, it doesn't get
+/// to catch up by running the next frames without a pause.
+pub fn keep_in_step_with_wall_clock(frame_interval: Duration) -> impl FnMut(&mut Environment) {
+    let mut frame_due: Option<Instant> = None;
+    move |_| {
+        let now = Instant::now();
+        let next_due = frame_due
+            .and_then(|due| due.checked_add(frame_interval))
+            .filter(|&due| due > now);
+        if let Some(next_due) = next_due {
+            std::thread::sleep(next_due - now);
+        }
+        frame_due = Some(next_due.unwrap_or(now));
+    }
+}
+
+pub struct FramePacing {
+    frame_interval: Duration,
+    /// Ticks to run the guest for in each frame.
+    tick_budget: u64,
+    /// Ticks already spent in the current frame.
+    ticks_into_frame: u64,
+    /// Number of frames that have ended so far.
+    frames_completed: u64,
+    /// When the current frame started, in wall-clock time.
+    frame_started: Instant,
+    /// This is [None] only while the callback is running.
+    on_frame: Option<FrameCallback>,
+}
+
+impl FramePacing {
+    pub fn new(frame_interval: Duration, ticks_per_second: u64, on_frame: FrameCallback) -> Self {
+        FramePacing {
+            frame_interval,
+            tick_budget: Self::tick_budget_for(frame_interval, ticks_per_second),
+            ticks_into_frame: 0,
+            frames_completed: 0,
+            frame_started: Instant::now(),
+            on_frame: Some(on_frame),
+        }
+    }
+
+    /// Computes the number of ticks per frame for a frame interval and tick
+    /// rate. This is never zero, so that the guest always makes progress.
+    pub fn tick_budget_for(frame_interval: Duration, ticks_per_second: u64) -> u64 {
+        let ticks = frame_interval.as_nanos() * u128::from(ticks_per_second) / 1_000_000_000;
+        u64::try_from(ticks).unwrap_or(u64::MAX).max(1)
+    }
+
+    /// The size of the next slice to run the guest for: `max_slice`, but not
+    /// going past the end of the current frame.
+    pub fn slice_ticks(&self, max_slice: u64) -> u64 {
+        max_slice.min(self.tick_budget - self.ticks_into_frame)
+    }
+
+    /// Records that `ticks` ticks were spent running the guest. Returns [true]
+    /// if this reached the end of the frame, in which case the next frame is
+    /// started.
+    pub fn account_ticks(&mut self, ticks: u64) -> bool {
+        self.ticks_into_frame = self.ticks_into_frame.saturating_add(ticks);
+        if self.ticks_into_frame >= self.tick_budget {
+            self.ticks_into_frame = 0;
+            self.frames_completed += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The number of the current frame (counting from zero) and the frame
+    /// interval.
+    pub fn current_frame(&self) -> (u64, Duration) {
+        (self.frames_completed, self.frame_interval)
+    }
+
+    /// When the next frame is expected to start, in wall-clock time. This is
+    /// only accurate if the frame callback keeps frames in step with
+    /// wall-clock time, like [keep_in_step_with_wall_clock] does.
+    pub fn next_frame_start(&self) -> Option<Instant> {
+        self.frame_started.checked_add(self.frame_interval)
+    }
+
+    pub(super) fn take_callback(&mut self) -> Option<FrameCallback> {
+        self.on_frame.take()
+    }
+
+    /// Put the callback back after it has run at the start of a new frame.
+    pub(super) fn restore_callback(&mut self, on_frame: FrameCallback) {
+        assert!(self.on_frame.is_none());
+        self.on_frame = Some(on_frame);
+        self.frame_started = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_budget_for() {
+        assert_eq!(
+            FramePacing::tick_budget_for(Duration::from_millis(20), 1_000_000),
+            20_000
+        );
+        assert_eq!(
+            FramePacing::tick_budget_for(Duration::from_secs(1) / 60, 6_000_000),
+            99_999
+        );
+        assert_eq!(FramePacing::tick_budget_for(Duration::ZERO, 1_000_000), 1);
+    }
+}
@@ -0,0 +1,193 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Reports describing the guest's state when the emulator panics (see
+//! [Environment::install_panic_reporter]).
+//!
+//! Rust's panic hook can't borrow the [Environment], so the hook only records
+//! the panic message. The report itself is printed by [Environment::run] once
+//! the panic has unwound that far, but before it unwinds any further. The CPU
+//! and thread state are unaffected by unwinding, so nothing is lost.
+
+use super::Environment;
+use crate::libc::pthread::thread::get_thread_name_by_id;
+use crate::mach_o::MachO;
+use std::cell::RefCell;
+use std::ops::Range;
+
+thread_local! {
+    /// Message of the most recent panic on this thread, set by the hook.
+    static PANIC_MESSAGE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Find the nearest symbol at or before `addr` among `symbols`, ignoring any
+/// that are outside `section` (so that an address in some unnamed code isn't
+/// attributed to an unrelated function). Returns the symbol name and the
+/// offset of `addr` from it. Thumb bits are ignored.
+fn nearest_symbol<'a>(
+    symbols: impl Iterator<Item = (&'a str, u32)>,
+    section: Range<u32>,
+    addr: u32,
+) -> Option<(&'a str, u32)> {
+    let addr = addr & !1;
+    symbols
+        .map(|(name, symbol_addr)| (name, symbol_addr & !1))
+        .filter(|&(_, symbol_addr)| section.contains(&symbol_addr) && symbol_addr <= addr)
+        // Break ties by name, so aliases are reported consistently.
+        .max_by_key(|&(name, symbol_addr)| (symbol_addr, std::cmp::Reverse(name)))
+        .map(|(name, symbol_addr)| (name, addr - symbol_addr))
+}
+
+/// Where an address is in the loaded binaries, see [locate_address].
+#[derive(Debug, PartialEq, Eq)]
+pub struct AddressLocation<'a> {
+    /// Name of the binary containing the address.
+    pub bin_name: &'a str,
+    /// Name of the section containing the address.
+    pub section_name: &'a str,
+    /// The nearest symbol at or before the address, and the offset from it.
+    pub symbol: Option<(&'a str, u32)>,
+}
+
+/// Find which binary, section and symbol `addr` is in, if any.
+pub fn locate_address(bins: &[MachO], addr: u32) -> Option<AddressLocation<'_>> {
+    let code_addr = addr & !1;
+    bins.iter().find_map(|bin| {
+        let section = bin
+            .sections
+            .iter()
+            .find(|section| (section.addr..section.addr + section.size).contains(&code_addr))?;
+        let symbols = bin
+            .exported_symbols
+            .iter()
+            .map(|(name, &symbol_addr)| (name.as_str(), symbol_addr));
+        let range = section.addr..section.addr + section.size;
+        Some(AddressLocation {
+            bin_name: &bin.name,
+            section_name: &section.name,
+            symbol: nearest_symbol(symbols, range, addr),
+        })
+    })
+}
+
+/// Describe where in the loaded binaries `addr` is, e.g. `_main+0x1c in Foo`.
+pub(super) fn symbolicate(bins: &[MachO], addr: u32) -> String {
+    match locate_address(bins, addr) {
+        Some(AddressLocation {
+            bin_name,
+            symbol: Some((name, offset)),
+            ..
+        }) => format!("{}+{:#x} in {}", name, offset, bin_name),
+        Some(AddressLocation {
+            bin_name,
+            section_name,
+            symbol: None,
+        }) => format!("{} of {}", section_name, bin_name),
+        None => "unknown".to_string(),
+    }
+}
+
+impl Environment {
+    /// Install a panic hook so that panics in the emulator are followed by a
+    /// report of the guest's state: the current thread's ID and name, the
+    /// symbolicated PC, a guest backtrace, a list of all the threads (see
+    /// [Self::dump_threads]) and a map of the guest's memory (see
+    /// [crate::mem::Mem::dump_regions]). This is process-wide, and only
+    /// needs to be done once. The previously installed hook is still run
+    /// first, so the usual panic message is not lost.
+    pub fn install_panic_reporter(&mut self) {
+        let previous_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            previous_hook(info);
+            let payload = if let Some(s) = info.payload().downcast_ref::<&str>() {
+                s
+            } else if let Some(s) = info.payload().downcast_ref::<String>() {
+                s
+            } else {
+                "(non-string payload)"
+            };
+            let message = match info.location() {
+                Some(location) => format!("{} at {}", payload, location),
+                None => payload.to_string(),
+            };
+            PANIC_MESSAGE.with(|m| *m.borrow_mut() = Some(message));
+        }));
+        self.panic_reporter_installed = true;
+    }
+
+    /// Print the report described in [Self::install_panic_reporter].
+    pub(super) fn print_panic_report(&mut self) {
+        let message = PANIC_MESSAGE.with(|m| m.borrow_mut().take());
+        echo!("Guest state at the time of the panic:");
+        if let Some(message) = message {
+            echo!("Panic: {}", message);
+        }
+
+        let thread_id = self.current_thread;
+        let thread_name = get_thread_name_by_id(self, thread_id)
+            .map(|name| format!(" {:?}", String::from_utf8_lossy(name)));
+        echo!(
+            "Guest thread: {}{}{}",
+            thread_id,
+            if thread_id == 0 { " (main thread)" } else { "" },
+            thread_name.unwrap_or_default()
+        );
+
+        let pc = self.cpu.pc_with_thumb_bit().addr_with_thumb_bit();
+        echo!("Guest PC: {:#x} ({})", pc, symbolicate(&self.bins, pc));
+
+        echo!("Guest backtrace:");
+        for (i, addr) in self.guest_backtrace().into_iter().enumerate() {
+            echo!("{:2}. {:#x} ({})", i, addr, symbolicate(&self.bins, addr));
+        }
+
+        echo!("Guest threads:");
+        self.dump_threads();
+
+        echo!("Guest memory regions:");
+        self.mem.dump_regions();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearest_symbol() {
+        let symbols = [
+            ("_a", 0x1000),
+            ("_b", 0x1011),
+            ("_c", 0x1100),
+            ("_d", 0x2000),
+        ];
+        let symbols = || symbols.iter().copied();
+        let section = 0x1000..0x1200;
+        assert_eq!(
+            nearest_symbol(symbols(), section.clone(), 0x1000),
+            Some(("_a", 0))
+        );
+        assert_eq!(
+            nearest_symbol(symbols(), section.clone(), 0x100f),
+            Some(("_a", 0xe))
+        );
+        // Thumb bits don't matter
+        assert_eq!(
+            nearest_symbol(symbols(), section.clone(), 0x1015),
+            Some(("_b", 4))
+        );
+        assert_eq!(
+            nearest_symbol(symbols(), section.clone(), 0x11fe),
+            Some(("_c", 0xfe))
+        );
+        assert_eq!(nearest_symbol(symbols(), section.clone(), 0xfff), None);
+        // Symbols outside the section are ignored
+        assert_eq!(nearest_symbol(symbols(), 0x1800..0x3000, 0x1804), None);
+        assert_eq!(
+            nearest_symbol(symbols(), 0x1800..0x3000, 0x2004),
+            Some(("_d", 4))
+        );
+    }
+}
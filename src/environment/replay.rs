@@ -0,0 +1,236 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Recording and replaying of nondeterministic inputs observed by the app, to
+//! make bugs reproducible (see `--record-inputs=` and `--replay-inputs=`).
+//!
+//! Host code that gives the app a clock reading or a random number should get
+//! it from [Replay] rather than directly from the host. The log is a text file
+//! with one input per line, in the order the app observed them.
+//!
+//! Not everything is covered. File contents are not recorded: the app bundle
+//! can't change, and the app's sandbox directory can be shipped along with the
+//! log. Input events, audio timing and thread scheduling are not recorded
+//! either, so apps that depend on these may still diverge.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+const HEADER: &str = "# touchHLE input log";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Input {
+    /// Time since the UNIX epoch.
+    WallClock(Duration),
+    /// Time since [Replay::startup_time].
+    Monotonic(Duration),
+    Random(u32),
+}
+
+impl Input {
+    fn kind(&self) -> &'static str {
+        match self {
+            Input::WallClock(_) => "wall",
+            Input::Monotonic(_) => "monotonic",
+            Input::Random(_) => "random",
+        }
+    }
+
+    fn to_line(self) -> String {
+        match self {
+            Input::WallClock(d) | Input::Monotonic(d) => {
+                format!("{} {}", self.kind(), d.as_nanos())
+            }
+            Input::Random(value) => format!("{} {}", self.kind(), value),
+        }
+    }
+
+    fn from_line(line: &str) -> Option<Input> {
+        let (kind, value) = line.split_once(' ')?;
+        let nanos = || value.parse::<u64>().ok().map(Duration::from_nanos);
+        match kind {
+            "wall" => nanos().map(Input::WallClock),
+            "monotonic" => nanos().map(Input::Monotonic),
+            "random" => value.parse().ok().map(Input::Random),
+            _ => None,
+        }
+    }
+}
+
+enum Mode {
+    Live,
+    Recording(LineWriter<File>),
+    Replaying {
+        /// Inputs with their line numbers, for error messages.
+        inputs: VecDeque<(usize, Input)>,
+        warned_exhausted: bool,
+    },
+}
+
+pub struct Replay {
+    mode: Mode,
+    startup_time: Instant,
+}
+
+impl Replay {
+    /// Neither records nor replays.
+    pub fn live(startup_time: Instant) -> Replay {
+        Replay {
+            mode: Mode::Live,
+            startup_time,
+        }
+    }
+
+    pub fn record(path: &Path, startup_time: Instant) -> Result<Replay, String> {
+        let mut file = File::create(path)
+            .map(LineWriter::new)
+            .map_err(|e| format!("Could not create input log {}: {}", path.display(), e))?;
+        writeln!(file, "{}", HEADER).unwrap();
+        log!("Recording inputs to {}.", path.display());
+        Ok(Replay {
+            mode: Mode::Recording(file),
+            startup_time,
+        })
+    }
+
+    pub fn replay(path: &Path, startup_time: Instant) -> Result<Replay, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read input log {}: {}", path.display(), e))?;
+        Ok(Replay {
+            mode: Mode::Replaying {
+                inputs: parse_log(&text)?,
+                warned_exhausted: false,
+            },
+            startup_time,
+        })
+    }
+
+    /// Record the live value, or replace it with the one from the log.
+    fn observe(&mut self, live: Input) -> Input {
+        match &mut self.mode {
+            Mode::Live => live,
+            Mode::Recording(file) => {
+                writeln!(file, "{}", live.to_line()).unwrap();
+                live
+            }
+            Mode::Replaying {
+                inputs,
+                warned_exhausted,
+            } => match inputs.pop_front() {
+                Some((_, input)) if input.kind() == live.kind() => input,
+                Some((line_no, input)) => panic!(
+                    "Replay diverged from the input log: line {} has a {} input, but the app requested a {} input.",
+                    line_no,
+                    input.kind(),
+                    live.kind()
+                ),
+                None => {
+                    if !*warned_exhausted {
+                        *warned_exhausted = true;
+                        log!("Warning: reached the end of the input log, using live inputs from now on.");
+                    }
+                    live
+                }
+            },
+        }
+    }
+
+    /// Replacement for [SystemTime::now].
+    pub fn system_time(&mut self) -> SystemTime {
+        let live = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        let Input::WallClock(since_epoch) = self.observe(Input::WallClock(live)) else {
+            unreachable!();
+        };
+        SystemTime::UNIX_EPOCH + since_epoch
+    }
+
+    /// Replacement for [Instant::now].
+    pub fn instant(&mut self) -> Instant {
+        let live = Instant::now().duration_since(self.startup_time);
+        let Input::Monotonic(since_startup) = self.observe(Input::Monotonic(live)) else {
+            unreachable!();
+        };
+        self.startup_time + since_startup
+    }
+
+    /// Pass a freshly generated random number through the log.
+    pub fn random(&mut self, live: u32) -> u32 {
+        let Input::Random(value) = self.observe(Input::Random(live)) else {
+            unreachable!();
+        };
+        value
+    }
+}
+
+fn parse_log(text: &str) -> Result<VecDeque<(usize, Input)>, String> {
+    let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+    if lines.next().map(|(_, line)| line) != Some(HEADER) {
+        return Err("Input log is missing its header line".to_string());
+    }
+    lines
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_no, line)| {
+            Input::from_line(line)
+                .map(|input| (line_no, input))
+                .ok_or_else(|| format!("Input log line {} is invalid: {:?}", line_no, line))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_round_trip() {
+        let inputs = [
+            Input::WallClock(Duration::new(1_234_567_890, 123)),
+            Input::Monotonic(Duration::from_millis(1500)),
+            Input::Random(0xdeadbeef),
+        ];
+        let mut text = format!("{}\n", HEADER);
+        for input in inputs {
+            text.push_str(&input.to_line());
+            text.push('\n');
+        }
+        assert_eq!(text.lines().nth(2), Some("monotonic 1500000000"));
+
+        let parsed: Vec<Input> = parse_log(&text)
+            .unwrap()
+            .into_iter()
+            .map(|(_, input)| input)
+            .collect();
+        assert_eq!(parsed, inputs);
+
+        assert!(parse_log("wall 1\n").is_err());
+        assert!(parse_log(&format!("{}\nwall x\n", HEADER)).is_err());
+    }
+
+    #[test]
+    fn test_replay() {
+        let startup_time = Instant::now();
+        let text = format!("{}\nwall 5000000000\nmonotonic 7\nrandom 42\n", HEADER);
+        let mut replay = Replay {
+            mode: Mode::Replaying {
+                inputs: parse_log(&text).unwrap(),
+                warned_exhausted: false,
+            },
+            startup_time,
+        };
+        assert_eq!(
+            replay.system_time(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(5)
+        );
+        assert_eq!(replay.instant(), startup_time + Duration::from_nanos(7));
+        assert_eq!(replay.random(1), 42);
+        // Once the log is used up, live values are passed through.
+        assert_eq!(replay.random(1), 1);
+    }
+}
@@ -0,0 +1,164 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Listing of all the guest threads, for diagnosing deadlocks and other
+//! multithreading problems (see [Environment::dump_threads]).
+
+use super::panic_report::symbolicate;
+use super::{Environment, ThreadBlock, ThreadId};
+use crate::frameworks::foundation::ns_thread::ns_thread_for_thread_id;
+use crate::libc::pthread::thread::get_thread_name_by_id;
+use crate::objc::id;
+
+/// What [Environment::describe_threads] reports about a thread.
+struct ThreadSummary {
+    id: ThreadId,
+    name: Option<String>,
+    /// The `NSThread` object and its lifecycle state, if there is one.
+    ns_thread: Option<(id, &'static str)>,
+    /// The PC and where it is (see [symbolicate]), if the thread's registers
+    /// could be read.
+    pc: Option<(u32, String)>,
+    /// What the scheduler thinks the thread is doing.
+    state: String,
+}
+
+/// Formats a thread as a line of [Environment::dump_threads]'s output, e.g.
+/// `thread 1 "loader": ready, PC 0x2f04 (_load+0x4 in Foo), NSThread 0x5010
+/// (executing)`.
+fn describe_thread(summary: &ThreadSummary) -> String {
+    let mut line = format!("thread {}", summary.id);
+    if summary.id == 0 {
+        line.push_str(" (main thread)");
+    }
+    if let Some(ref name) = summary.name {
+        line.push_str(&format!(" {:?}", name));
+    }
+    line.push_str(&format!(": {}, ", summary.state));
+    match summary.pc {
+        Some((pc, ref location)) => line.push_str(&format!("PC {:#x} ({})", pc, location)),
+        None => line.push_str("registers unavailable"),
+    }
+    if let Some((ns_thread, state)) = summary.ns_thread {
+        line.push_str(&format!(", NSThread {:?} ({})", ns_thread, state));
+    }
+    line
+}
+
+fn describe_thread_block(block: &ThreadBlock) -> String {
+    match block {
+        ThreadBlock::NotBlocked => "ready".to_string(),
+        ThreadBlock::Sleeping(_) => "sleeping".to_string(),
+        ThreadBlock::Mutex(mutex_id) => format!("waiting for mutex {}", mutex_id),
+        ThreadBlock::Semaphore(sem) => format!("waiting for semaphore {:?}", sem),
+        ThreadBlock::Condition(cond) => format!("waiting for condition variable {:?}", cond),
+        ThreadBlock::Joining(thread_id, _) => format!("joining thread {}", thread_id),
+        ThreadBlock::DeferredReturn => "waiting to return to the host".to_string(),
+    }
+}
+
+impl Environment {
+    /// Get the lines printed by [Self::dump_threads].
+    pub fn describe_threads(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for thread_id in 0..self.threads.len() {
+            if !self.threads[thread_id].active {
+                continue;
+            }
+
+            let pc = if thread_id == self.current_thread {
+                Some(self.cpu.pc_with_thumb_bit())
+            } else {
+                // The registers of other threads can only be read by swapping
+                // their context into the CPU, so swap it straight back. This is
+                // also used by the panic report, so a thread without a saved
+                // context mustn't cause another panic.
+                self.threads[thread_id].context.as_mut().map(|context| {
+                    self.cpu.swap_context(context);
+                    let pc = self.cpu.pc_with_thumb_bit();
+                    self.cpu.swap_context(context);
+                    pc
+                })
+            };
+            let pc = pc.map(|pc| {
+                let pc = pc.addr_with_thumb_bit();
+                (pc, symbolicate(&self.bins, pc))
+            });
+
+            let state = if thread_id == self.current_thread {
+                "running".to_string()
+            } else {
+                describe_thread_block(&self.threads[thread_id].blocked_by)
+            };
+            let summary = ThreadSummary {
+                id: thread_id,
+                name: get_thread_name_by_id(self, thread_id)
+                    .map(|name| String::from_utf8_lossy(name).into_owned()),
+                ns_thread: ns_thread_for_thread_id(self, thread_id),
+                pc,
+                state,
+            };
+            lines.push(describe_thread(&summary));
+        }
+        lines
+    }
+
+    /// Print a list of the live guest threads: their IDs and names, what they
+    /// are waiting for if they're blocked, their symbolicated PCs, and their
+    /// `NSThread` objects, if they have one.
+    pub fn dump_threads(&mut self) {
+        for line in self.describe_threads() {
+            echo!("{}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::Ptr;
+
+    #[test]
+    fn test_describe_thread() {
+        let main_thread = ThreadSummary {
+            id: 0,
+            name: None,
+            ns_thread: None,
+            pc: Some((0x2f04, "_main+0x4 in Foo".to_string())),
+            state: "running".to_string(),
+        };
+        let ns_thread: id = Ptr::from_bits(0x5010);
+        let worker = ThreadSummary {
+            id: 1,
+            name: Some("loader".to_string()),
+            ns_thread: Some((ns_thread, "executing")),
+            pc: Some((0x3001, "unknown".to_string())),
+            state: describe_thread_block(&ThreadBlock::Mutex(3)),
+        };
+        assert_eq!(
+            describe_thread(&main_thread),
+            "thread 0 (main thread): running, PC 0x2f04 (_main+0x4 in Foo)"
+        );
+        assert_eq!(
+            describe_thread(&worker),
+            format!(
+                "thread 1 \"loader\": waiting for mutex 3, PC 0x3001 (unknown), NSThread {:?} \
+                 (executing)",
+                ns_thread
+            )
+        );
+        let unavailable = ThreadSummary {
+            id: 2,
+            name: None,
+            ns_thread: None,
+            pc: None,
+            state: "ready".to_string(),
+        };
+        assert_eq!(
+            describe_thread(&unavailable),
+            "thread 2: ready, registers unavailable"
+        );
+    }
+}
@@ -34,6 +34,7 @@ pub mod game_kit;
 pub mod media_player;
 pub mod openal;
 pub mod opengles;
+mod shared_objects;
 pub mod store_kit;
 pub mod system_configuration;
 pub mod uikit;
@@ -47,5 +48,6 @@ pub struct State {
     media_player: media_player::State,
     openal: openal::State,
     opengles: opengles::State,
+    shared_objects: shared_objects::State,
     uikit: uikit::State,
 }
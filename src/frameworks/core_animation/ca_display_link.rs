@@ -0,0 +1,201 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CADisplayLink`.
+//!
+//! There's no real display to synchronize with, so display links follow the
+//! emulated frame clock instead (see [Environment::current_frame]). A display
+//! link fires at most once per frame. Like on a real device, frames that are
+//! missed because the app was busy are skipped rather than caught up on.
+
+use crate::frameworks::core_foundation::time::CFTimeInterval;
+use crate::frameworks::foundation::{ns_run_loop, NSInteger};
+use crate::objc::{
+    autorelease, id, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
+    HostObject, SEL,
+};
+use crate::Environment;
+use std::time::Instant;
+
+struct CADisplayLinkHostObject {
+    /// Strong reference
+    target: id,
+    selector: SEL,
+    /// Number of frames between each firing.
+    frame_interval: NSInteger,
+    paused: bool,
+    /// The frame the display link last fired on, or was added to the run loop
+    /// on.
+    last_frame: u64,
+    timestamp: CFTimeInterval,
+    duration: CFTimeInterval,
+    /// Weak reference
+    run_loop: id,
+}
+impl HostObject for CADisplayLinkHostObject {}
+
+/// Whether a display link that last fired on `last_frame` should fire on
+/// `current_frame`.
+fn frame_is_due(last_frame: u64, current_frame: u64, frame_interval: NSInteger) -> bool {
+    current_frame >= last_frame + u64::try_from(frame_interval.max(1)).unwrap()
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CADisplayLink: NSObject
+
++ (id)displayLinkWithTarget:(id)target
+                   selector:(SEL)selector {
+    retain(env, target);
+    let host_object = Box::new(CADisplayLinkHostObject {
+        target,
+        selector,
+        frame_interval: 1,
+        paused: false,
+        last_frame: 0,
+        timestamp: 0.0,
+        duration: 0.0,
+        run_loop: nil,
+    });
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    log_dbg!(
+        "New display link {:?}, target [{:?} {}]",
+        new,
+        target,
+        selector.as_str(&env.mem),
+    );
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let target = env.objc.borrow::<CADisplayLinkHostObject>(this).target;
+    release(env, target);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (())addToRunLoop:(id)run_loop // NSRunLoop*
+           forMode:(id)_mode { // NSRunLoopMode
+    // TODO: handle run loop modes
+    let (current_frame, _) = env.current_frame();
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(this);
+    assert!(host_object.run_loop == nil); // TODO: multiple run loops
+    host_object.run_loop = run_loop;
+    host_object.last_frame = current_frame;
+    ns_run_loop::add_display_link(env, run_loop, this);
+}
+
+- (())removeFromRunLoop:(id)run_loop // NSRunLoop*
+               forMode:(id)_mode { // NSRunLoopMode
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(this);
+    if host_object.run_loop != run_loop {
+        return;
+    }
+    host_object.run_loop = nil;
+    ns_run_loop::remove_display_link(env, run_loop, this);
+}
+
+- (())invalidate {
+    let run_loop = std::mem::replace(
+        &mut env.objc.borrow_mut::<CADisplayLinkHostObject>(this).run_loop,
+        nil,
+    );
+    if run_loop != nil {
+        ns_run_loop::remove_display_link(env, run_loop, this);
+    }
+}
+
+- (CFTimeInterval)timestamp {
+    env.objc.borrow::<CADisplayLinkHostObject>(this).timestamp
+}
+- (CFTimeInterval)duration {
+    env.objc.borrow::<CADisplayLinkHostObject>(this).duration
+}
+
+- (NSInteger)frameInterval {
+    env.objc.borrow::<CADisplayLinkHostObject>(this).frame_interval
+}
+- (())setFrameInterval:(NSInteger)frame_interval {
+    // Values less than 1 are documented to have undefined behavior.
+    env.objc.borrow_mut::<CADisplayLinkHostObject>(this).frame_interval = frame_interval.max(1);
+}
+
+- (bool)isPaused {
+    env.objc.borrow::<CADisplayLinkHostObject>(this).paused
+}
+- (())setPaused:(bool)paused {
+    env.objc.borrow_mut::<CADisplayLinkHostObject>(this).paused = paused;
+}
+
+@end
+
+};
+
+/// For use by `NSRunLoop`: check if a display link is due to fire and fire it
+/// if necessary.
+///
+/// Returns the time the next frame starts, if known.
+pub fn handle_display_link(env: &mut Environment, display_link: id) -> Option<Instant> {
+    let &CADisplayLinkHostObject {
+        target,
+        selector,
+        frame_interval,
+        paused,
+        last_frame,
+        ..
+    } = env.objc.borrow(display_link);
+
+    let (current_frame, interval) = env.current_frame();
+    let next_frame_start = env.next_frame_start();
+
+    if paused || !frame_is_due(last_frame, current_frame, frame_interval) {
+        return next_frame_start;
+    }
+
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(display_link);
+    host_object.last_frame = current_frame;
+    host_object.timestamp = interval.as_secs_f64() * current_frame as f64;
+    host_object.duration = interval.as_secs_f64();
+
+    log_dbg!(
+        "Display link {:?} fired on frame {}, sending {:?} message to {:?}",
+        display_link,
+        current_frame,
+        selector.as_str(&env.mem),
+        target
+    );
+
+    // The display link may be invalidated by the target.
+    retain(env, display_link);
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+
+    // Signature should be `- (void)displayLinkDidFire:(CADisplayLink *)link`.
+    let _: () = msg_send(env, (target, selector, display_link));
+
+    release(env, pool);
+    release(env, display_link);
+
+    next_frame_start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_is_due() {
+        // Fires once per frame by default.
+        assert!(!frame_is_due(10, 10, 1));
+        assert!(frame_is_due(10, 11, 1));
+        // Missed frames aren't caught up on.
+        assert!(frame_is_due(10, 15, 1));
+        // Frame intervals greater than one skip frames.
+        assert!(!frame_is_due(10, 11, 2));
+        assert!(frame_is_due(10, 12, 2));
+        // Invalid frame intervals are treated as one.
+        assert!(frame_is_due(10, 11, 0));
+    }
+}
@@ -3,14 +3,115 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! `CFAllocator`. Currently there is no actual support for multiple allocators.
+//! `CFAllocator`. Currently there is no support for custom allocators: all the
+//! standard allocators except `kCFAllocatorNull` use the guest heap, i.e. the
+//! same memory as `malloc()`.
 
-use super::CFTypeRef;
-use crate::dyld::{ConstantExports, HostConstant};
-use crate::mem::Ptr;
+use super::{CFIndex, CFOptionFlags, CFTypeRef};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::mem::{ConstVoidPtr, MutVoidPtr, Ptr};
+use crate::Environment;
 
 pub type CFAllocatorRef = CFTypeRef;
 
 pub const kCFAllocatorDefault: CFAllocatorRef = Ptr::null();
+// These aren't real objects. Apple's kCFAllocatorUseContext is a similarly
+// fake pointer (0x03ab), and all of these are within the null page.
+pub const kCFAllocatorSystemDefault: CFAllocatorRef = Ptr::from_bits(0x03a0);
+pub const kCFAllocatorMalloc: CFAllocatorRef = Ptr::from_bits(0x03a4);
+pub const kCFAllocatorMallocZone: CFAllocatorRef = Ptr::from_bits(0x03a8);
+/// Doesn't allocate anything, and freeing with it does nothing. This is used
+/// to give a CF object a buffer that it shouldn't free.
+pub const kCFAllocatorNull: CFAllocatorRef = Ptr::from_bits(0x03ac);
 
-pub const CONSTANTS: ConstantExports = &[("_kCFAllocatorDefault", HostConstant::NullPtr)];
+fn CFAllocatorGetDefault(_env: &mut Environment) -> CFAllocatorRef {
+    // Apple's implementation would return kCFAllocatorSystemDefault, but NULL
+    // also means the default allocator, and it's the only allocator the other
+    // CF functions accept so far.
+    kCFAllocatorDefault
+}
+
+fn CFAllocatorAllocate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    size: CFIndex,
+    _hint: CFOptionFlags,
+) -> MutVoidPtr {
+    if allocator == kCFAllocatorNull || size <= 0 {
+        return Ptr::null();
+    }
+    env.mem.alloc(size.try_into().unwrap())
+}
+
+fn CFAllocatorReallocate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    ptr: MutVoidPtr,
+    new_size: CFIndex,
+    hint: CFOptionFlags,
+) -> MutVoidPtr {
+    if ptr.is_null() {
+        return CFAllocatorAllocate(env, allocator, new_size, hint);
+    }
+    if new_size <= 0 {
+        CFAllocatorDeallocate(env, allocator, ptr.cast_const());
+        return Ptr::null();
+    }
+    if allocator == kCFAllocatorNull {
+        return Ptr::null();
+    }
+    env.mem.realloc(ptr, new_size.try_into().unwrap())
+}
+
+fn CFAllocatorDeallocate(env: &mut Environment, allocator: CFAllocatorRef, ptr: ConstVoidPtr) {
+    if allocator == kCFAllocatorNull || ptr.is_null() {
+        return;
+    }
+    env.mem.free(ptr.cast_mut());
+}
+
+fn CFAllocatorGetPreferredSizeForSize(
+    _env: &mut Environment,
+    _allocator: CFAllocatorRef,
+    size: CFIndex,
+    _hint: CFOptionFlags,
+) -> CFIndex {
+    // touchHLE's allocator rounds up allocations to multiples of 16 bytes.
+    (size.max(1) + 15) & !15
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFAllocatorGetDefault()),
+    export_c_func!(CFAllocatorAllocate(_, _, _)),
+    export_c_func!(CFAllocatorReallocate(_, _, _, _)),
+    export_c_func!(CFAllocatorDeallocate(_, _)),
+    export_c_func!(CFAllocatorGetPreferredSizeForSize(_, _, _)),
+];
+
+pub const CONSTANTS: ConstantExports = &[
+    ("_kCFAllocatorDefault", HostConstant::NullPtr),
+    (
+        "_kCFAllocatorSystemDefault",
+        HostConstant::Custom(|mem, _| {
+            mem.alloc_and_write(kCFAllocatorSystemDefault)
+                .cast()
+                .cast_const()
+        }),
+    ),
+    (
+        "_kCFAllocatorMalloc",
+        HostConstant::Custom(|mem, _| mem.alloc_and_write(kCFAllocatorMalloc).cast().cast_const()),
+    ),
+    (
+        "_kCFAllocatorMallocZone",
+        HostConstant::Custom(|mem, _| {
+            mem.alloc_and_write(kCFAllocatorMallocZone)
+                .cast()
+                .cast_const()
+        }),
+    ),
+    (
+        "_kCFAllocatorNull",
+        HostConstant::Custom(|mem, _| mem.alloc_and_write(kCFAllocatorNull).cast().cast_const()),
+    ),
+];
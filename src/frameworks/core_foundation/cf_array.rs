@@ -7,30 +7,80 @@
 //!
 //! These are toll-free bridged to `NSArray` and `NSMutableArray` in Apple's
 //! implementation. Here they are the same types.
+//!
+//! Only two kinds of `CFArrayCallBacks` are supported: `NULL`, meaning the
+//! values are arbitrary pointers that aren't retained, and anything with a
+//! `retain` callback, which is assumed to behave like `kCFTypeArrayCallBacks`,
+//! i.e. the values are objects retained and released the Objective-C way.
+//! Custom callbacks for non-object values are not called.
 
 use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::cf_dictionary::create_default_callback_functions;
 use super::CFIndex;
-use crate::dyld::{export_c_func, FunctionExports};
+use crate::abi::GuestFunction;
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
 use crate::frameworks::foundation::NSUInteger;
-use crate::mem::ConstVoidPtr;
+use crate::mem::{ConstPtr, ConstVoidPtr, SafeRead};
 use crate::objc::{id, msg, msg_class};
 use crate::Environment;
 
-#[allow(dead_code)]
 pub type CFArrayRef = super::CFTypeRef;
 pub type CFMutableArrayRef = super::CFTypeRef;
 
+#[repr(C, packed)]
+pub struct CFArrayCallBacks {
+    pub version: CFIndex,         // version
+    pub retain: GuestFunction,    // const void *(*retain)(CFAllocatorRef, const void *value)
+    pub release: GuestFunction,   // void (*release)(CFAllocatorRef alloc, const void *val)
+    pub copy_desc: GuestFunction, // CFStringRef (*copy_desc)(const void *val)
+    pub equal: GuestFunction,     // Boolean (*equal)(const void *val1, const void *val2)
+}
+unsafe impl SafeRead for CFArrayCallBacks {}
+
+/// Whether an array with these callbacks should retain its values, see the
+/// module documentation.
+fn retains_values(env: &mut Environment, callbacks: ConstPtr<CFArrayCallBacks>) -> bool {
+    if callbacks.is_null() {
+        return false;
+    }
+    let callbacks = env.mem.read(callbacks);
+    !callbacks.retain.to_ptr().is_null()
+}
+
+fn CFArrayCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    values: ConstPtr<ConstVoidPtr>,
+    num_values: CFIndex,
+    callbacks: ConstPtr<CFArrayCallBacks>,
+) -> CFArrayRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+
+    let values: ConstPtr<id> = values.cast();
+    let count: NSUInteger = num_values.try_into().unwrap();
+    let new: id = if retains_values(env, callbacks) {
+        msg_class![env; NSArray alloc]
+    } else {
+        msg_class![env; _touchHLE_NSArray_non_retaining alloc]
+    };
+    msg![env; new initWithObjects:values count:count]
+}
+
 fn CFArrayCreateMutable(
     env: &mut Environment,
     allocator: CFAllocatorRef,
-    capacity: CFIndex,
-    callbacks: ConstVoidPtr, // TODO, should be `const CFArrayCallBacks*`
+    // The capacity is a limit, not a hint, but exceeding it is undefined
+    // behavior anyway, so it can be ignored.
+    _capacity: CFIndex,
+    callbacks: ConstPtr<CFArrayCallBacks>,
 ) -> CFMutableArrayRef {
     assert!(allocator == kCFAllocatorDefault); // unimplemented
-    assert!(capacity == 0); // TODO: fixed capacity support
-    assert!(callbacks.is_null()); // TODO: support retaining etc
 
-    msg_class![env; _touchHLE_NSMutableArray_non_retaining new]
+    if retains_values(env, callbacks) {
+        msg_class![env; NSMutableArray new]
+    } else {
+        msg_class![env; _touchHLE_NSMutableArray_non_retaining new]
+    }
 }
 
 fn CFArrayGetCount(env: &mut Environment, array: CFArrayRef) -> CFIndex {
@@ -49,15 +99,66 @@ fn CFArrayAppendValue(env: &mut Environment, array: CFMutableArrayRef, value: Co
     msg![env; array addObject:value]
 }
 
+fn CFArrayInsertValueAtIndex(
+    env: &mut Environment,
+    array: CFMutableArrayRef,
+    idx: CFIndex,
+    value: ConstVoidPtr,
+) {
+    let idx: NSUInteger = idx.try_into().unwrap();
+    let value: id = value.cast().cast_mut();
+    msg![env; array insertObject:value atIndex:idx]
+}
+
+fn CFArraySetValueAtIndex(
+    env: &mut Environment,
+    array: CFMutableArrayRef,
+    idx: CFIndex,
+    value: ConstVoidPtr,
+) {
+    let idx: NSUInteger = idx.try_into().unwrap();
+    let value: id = value.cast().cast_mut();
+    let count: NSUInteger = msg![env; array count];
+    // Setting the value one past the end appends it.
+    if idx == count {
+        msg![env; array addObject:value]
+    } else {
+        msg![env; array replaceObjectAtIndex:idx withObject:value]
+    }
+}
+
 fn CFArrayRemoveValueAtIndex(env: &mut Environment, array: CFMutableArrayRef, idx: CFIndex) {
     let idx: NSUInteger = idx.try_into().unwrap();
     msg![env; array removeObjectAtIndex:idx]
 }
 
+fn CFArrayRemoveAllValues(env: &mut Environment, array: CFMutableArrayRef) {
+    msg![env; array removeAllObjects]
+}
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_kCFTypeArrayCallBacks",
+    HostConstant::Custom(|mem, dyld| {
+        let common = create_default_callback_functions(mem, dyld);
+        let callbacks = CFArrayCallBacks {
+            version: 0, // always 0
+            retain: common.retain,
+            release: common.release,
+            copy_desc: common.copy_desc,
+            equal: common.equal,
+        };
+        mem.alloc_and_write(callbacks).cast_void().cast_const()
+    }),
+)];
+
 pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFArrayCreate(_, _, _, _)),
     export_c_func!(CFArrayCreateMutable(_, _, _)),
     export_c_func!(CFArrayGetCount(_)),
     export_c_func!(CFArrayGetValueAtIndex(_, _)),
     export_c_func!(CFArrayAppendValue(_, _)),
+    export_c_func!(CFArrayInsertValueAtIndex(_, _, _)),
+    export_c_func!(CFArraySetValueAtIndex(_, _, _)),
     export_c_func!(CFArrayRemoveValueAtIndex(_, _)),
+    export_c_func!(CFArrayRemoveAllValues(_)),
 ];
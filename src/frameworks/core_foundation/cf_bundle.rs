@@ -9,6 +9,7 @@
 //! but here it is the same type.
 
 use super::cf_array::CFArrayRef;
+use super::cf_dictionary::CFDictionaryRef;
 use super::cf_string::CFStringRef;
 use super::cf_url::CFURLRef;
 use super::CFTypeRef;
@@ -36,8 +37,15 @@ fn CFBundleGetValueForInfoDictionaryKey(
     bundle: CFBundleRef,
     key: CFStringRef,
 ) -> CFTypeRef {
-    let dict: id = msg![env; bundle infoDictionary];
-    msg![env; dict objectForKey:key]
+    msg![env; bundle objectForInfoDictionaryKey:key]
+}
+
+fn CFBundleGetInfoDictionary(env: &mut Environment, bundle: CFBundleRef) -> CFDictionaryRef {
+    msg![env; bundle infoDictionary]
+}
+
+fn CFBundleGetIdentifier(env: &mut Environment, bundle: CFBundleRef) -> CFStringRef {
+    msg![env; bundle bundleIdentifier]
 }
 
 fn CFBundleGetVersionNumber(env: &mut Environment, bundle: CFBundleRef) -> u32 {
@@ -87,6 +95,8 @@ fn CFBundleCopyResourceURL(
     let url: CFURLRef = msg![env; bundle URLForResource:resource_name
                                           withExtension:resource_type
                                            subdirectory:sub_dir_name];
+    // URLForResource: returns nil if the resource doesn't exist, and so does
+    // this function.
     msg![env; url copy]
 }
 
@@ -157,6 +167,8 @@ pub fn CFBundleCopyPreferredLocalizationsFromArray(
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CFBundleGetMainBundle()),
     export_c_func!(CFBundleGetValueForInfoDictionaryKey(_, _)),
+    export_c_func!(CFBundleGetInfoDictionary(_)),
+    export_c_func!(CFBundleGetIdentifier(_)),
     export_c_func!(CFBundleGetVersionNumber(_)),
     export_c_func!(CFBundleCopyBundleURL(_)),
     export_c_func!(CFBundleCopyResourcesDirectoryURL(_)),
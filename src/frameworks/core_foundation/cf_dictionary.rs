@@ -20,22 +20,43 @@ use crate::frameworks::foundation::ns_dictionary::{
     CFDictionaryKeyCallBacks, CFDictionaryValueCallBacks,
 };
 use crate::frameworks::foundation::NSUInteger;
-use crate::mem::{ConstPtr, ConstVoidPtr, Mem, MutVoidPtr};
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, Mem, MutVoidPtr};
 use crate::objc::{id, msg, msg_class, nil};
 use crate::Environment;
 
 pub type CFDictionaryRef = super::CFTypeRef;
 pub type CFMutableDictionaryRef = super::CFTypeRef;
 
+fn CFDictionaryCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    keys: ConstPtr<ConstVoidPtr>,
+    values: ConstPtr<ConstVoidPtr>,
+    num_values: CFIndex,
+    key_callbacks: ConstPtr<CFDictionaryKeyCallBacks>,
+    value_callbacks: ConstPtr<CFDictionaryValueCallBacks>,
+) -> CFDictionaryRef {
+    // TODO: return an immutable dictionary
+    let dict = CFDictionaryCreateMutable(env, allocator, 0, key_callbacks, value_callbacks);
+    let num_values: GuestUSize = num_values.try_into().unwrap();
+    for i in 0..num_values {
+        let key = env.mem.read(keys + i);
+        let value = env.mem.read(values + i);
+        CFDictionarySetValue(env, dict, key, value);
+    }
+    dict
+}
+
 fn CFDictionaryCreateMutable(
     env: &mut Environment,
     allocator: CFAllocatorRef,
-    capacity: CFIndex,
+    // The capacity is a limit, not a hint, but exceeding it is undefined
+    // behavior anyway, so it can be ignored.
+    _capacity: CFIndex,
     key_callbacks: ConstPtr<CFDictionaryKeyCallBacks>,
     value_callbacks: ConstPtr<CFDictionaryValueCallBacks>,
 ) -> CFMutableDictionaryRef {
     assert_eq!(allocator, kCFAllocatorDefault); // unimplemented
-    assert_eq!(capacity, 0); // TODO: fixed capacity support
 
     let new = msg_class![env; _touchHLE_NSMutableDictionary_non_retaining alloc];
     msg![env; new initWithKeyCallbacks:key_callbacks andValueCallbacks:value_callbacks]
@@ -104,6 +125,16 @@ fn CFDictionaryGetValue(
     res.cast().cast_const()
 }
 
+fn CFDictionaryContainsKey(
+    env: &mut Environment,
+    dict: CFDictionaryRef,
+    key: ConstVoidPtr,
+) -> bool {
+    // Values can't be NULL for dictionaries with object callbacks, and NULL
+    // values for other dictionaries aren't distinguished from absent ones.
+    !CFDictionaryGetValue(env, dict, key).is_null()
+}
+
 fn CFDictionaryGetCount(env: &mut Environment, dict: CFDictionaryRef) -> CFIndex {
     let count: NSUInteger = msg![env; dict count];
     log_dbg!("CFDictionaryGetCount dict {:?} {}", dict, count);
@@ -180,14 +211,18 @@ fn _touchHLE_CFDictionary_hash(env: &mut Environment, value: ConstVoidPtr) -> CF
     CFHash(env, value.cast_mut().cast())
 }
 
-struct DefaultCallbackFunctions {
-    retain: GuestFunction,
-    release: GuestFunction,
-    copy_desc: GuestFunction,
-    equal: GuestFunction,
-    hash: GuestFunction,
+/// Also used for `kCFTypeArrayCallBacks`.
+pub(super) struct DefaultCallbackFunctions {
+    pub(super) retain: GuestFunction,
+    pub(super) release: GuestFunction,
+    pub(super) copy_desc: GuestFunction,
+    pub(super) equal: GuestFunction,
+    pub(super) hash: GuestFunction,
 }
-fn create_default_callback_functions(mem: &mut Mem, dyld: &mut Dyld) -> DefaultCallbackFunctions {
+pub(super) fn create_default_callback_functions(
+    mem: &mut Mem,
+    dyld: &mut Dyld,
+) -> DefaultCallbackFunctions {
     let retain_sym = "__touchHLE_CFDictionary_retain";
     let retain_hf: HostFunction =
         &(_touchHLE_CFDictionary_retain as fn(&mut Environment, _, _) -> _);
@@ -257,12 +292,14 @@ pub const CONSTANTS: ConstantExports = &[
 ];
 
 pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFDictionaryCreate(_, _, _, _, _, _)),
     export_c_func!(CFDictionaryCreateMutable(_, _, _, _)),
     export_c_func!(CFDictionaryAddValue(_, _, _)),
     export_c_func!(CFDictionarySetValue(_, _, _)),
     export_c_func!(CFDictionaryRemoveValue(_, _)),
     export_c_func!(CFDictionaryRemoveAllValues(_)),
     export_c_func!(CFDictionaryGetValue(_, _)),
+    export_c_func!(CFDictionaryContainsKey(_, _)),
     export_c_func!(CFDictionaryGetCount(_)),
     export_c_func!(CFDictionaryGetKeysAndValues(_, _, _)),
 ];
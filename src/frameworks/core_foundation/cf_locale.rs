@@ -20,11 +20,23 @@ type CFLocaleRef = CFTypeRef;
 type CFLocaleKey = CFStringRef;
 
 pub const kCFLocaleCountryCode: &str = "kCFLocaleCountryCodeKey";
+pub const kCFLocaleLanguageCode: &str = "kCFLocaleLanguageCodeKey";
+pub const kCFLocaleDecimalSeparator: &str = "kCFLocaleDecimalSeparatorKey";
 
-pub const CONSTANTS: ConstantExports = &[(
-    "_kCFLocaleCountryCode",
-    HostConstant::NSString(kCFLocaleCountryCode),
-)];
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCFLocaleCountryCode",
+        HostConstant::NSString(kCFLocaleCountryCode),
+    ),
+    (
+        "_kCFLocaleLanguageCode",
+        HostConstant::NSString(kCFLocaleLanguageCode),
+    ),
+    (
+        "_kCFLocaleDecimalSeparator",
+        HostConstant::NSString(kCFLocaleDecimalSeparator),
+    ),
+];
 
 fn CFLocaleCopyCurrent(env: &mut Environment) -> CFLocaleRef {
     let locale: id = msg_class![env; NSLocale currentLocale];
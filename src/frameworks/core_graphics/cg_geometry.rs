@@ -54,6 +54,11 @@ impl std::fmt::Display for CGPoint {
         write!(f, "{{{}, {}}}", x, y)
     }
 }
+// The Make functions are inline in Apple's headers, but some apps still call
+// them.
+fn CGPointMake(_env: &mut Environment, x: CGFloat, y: CGFloat) -> CGPoint {
+    CGPoint { x, y }
+}
 // This function is rare because it is usually inlined.
 fn CGPointEqualToPoint(_env: &mut Environment, a: CGPoint, b: CGPoint) -> bool {
     a == b
@@ -100,6 +105,9 @@ impl std::fmt::Display for CGSize {
         write!(f, "{{{}, {}}}", width, height)
     }
 }
+fn CGSizeMake(_env: &mut Environment, width: CGFloat, height: CGFloat) -> CGSize {
+    CGSize { width, height }
+}
 // This function is rare because it is usually inlined.
 fn CGSizeEqualToSize(_env: &mut Environment, a: CGSize, b: CGSize) -> bool {
     a == b
@@ -155,6 +163,119 @@ impl std::fmt::Display for CGRect {
         write!(f, "{{{}, {}}}", origin, size)
     }
 }
+impl CGRect {
+    /// Equivalent of `CGRectStandardize`: the same rectangle, but with a
+    /// non-negative width and height.
+    pub fn standardize(self) -> CGRect {
+        let CGRect { origin, size } = self;
+        let (x, width) = if size.width < 0.0 {
+            (origin.x + size.width, -size.width)
+        } else {
+            (origin.x, size.width)
+        };
+        let (y, height) = if size.height < 0.0 {
+            (origin.y + size.height, -size.height)
+        } else {
+            (origin.y, size.height)
+        };
+        CGRect {
+            origin: CGPoint { x, y },
+            size: CGSize { width, height },
+        }
+    }
+
+    /// Equivalent of `CGRectIsNull`.
+    pub fn is_null(self) -> bool {
+        let CGPoint { x, y } = self.origin;
+        x.is_infinite() || y.is_infinite()
+    }
+
+    /// Equivalent of `CGRectIsEmpty`.
+    pub fn is_empty(self) -> bool {
+        let CGSize { width, height } = self.size;
+        self.is_null() || width == 0.0 || height == 0.0
+    }
+
+    /// The minimum and maximum x and y coordinates of the standardized
+    /// rectangle, in the order `(min_x, min_y, max_x, max_y)`.
+    fn bounds(self) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+        let CGRect { origin, size } = self.standardize();
+        (
+            origin.x,
+            origin.y,
+            origin.x + size.width,
+            origin.y + size.height,
+        )
+    }
+
+    /// Equivalent of `CGRectIntersection`.
+    pub fn intersection(self, other: CGRect) -> CGRect {
+        if self.is_null() || other.is_null() {
+            return CGRectNull;
+        }
+        let (a_min_x, a_min_y, a_max_x, a_max_y) = self.bounds();
+        let (b_min_x, b_min_y, b_max_x, b_max_y) = other.bounds();
+        let min_x = a_min_x.max(b_min_x);
+        let min_y = a_min_y.max(b_min_y);
+        let max_x = a_max_x.min(b_max_x);
+        let max_y = a_max_y.min(b_max_y);
+        // Rectangles that only share an edge don't intersect.
+        if min_x >= max_x || min_y >= max_y {
+            return CGRectNull;
+        }
+        CGRect {
+            origin: CGPoint { x: min_x, y: min_y },
+            size: CGSize {
+                width: max_x - min_x,
+                height: max_y - min_y,
+            },
+        }
+    }
+
+    /// Equivalent of `CGRectUnion`.
+    pub fn union(self, other: CGRect) -> CGRect {
+        if self.is_null() {
+            return other.standardize();
+        } else if other.is_null() {
+            return self.standardize();
+        }
+        let (a_min_x, a_min_y, a_max_x, a_max_y) = self.bounds();
+        let (b_min_x, b_min_y, b_max_x, b_max_y) = other.bounds();
+        let min_x = a_min_x.min(b_min_x);
+        let min_y = a_min_y.min(b_min_y);
+        CGRect {
+            origin: CGPoint { x: min_x, y: min_y },
+            size: CGSize {
+                width: a_max_x.max(b_max_x) - min_x,
+                height: a_max_y.max(b_max_y) - min_y,
+            },
+        }
+    }
+
+    /// Equivalent of `CGRectContainsPoint`. The minimum edges are inside the
+    /// rectangle, but the maximum edges aren't.
+    pub fn contains_point(self, point: CGPoint) -> bool {
+        if self.is_null() {
+            return false;
+        }
+        let (min_x, min_y, max_x, max_y) = self.bounds();
+        let CGPoint { x, y } = point;
+        min_x <= x && x < max_x && min_y <= y && y < max_y
+    }
+}
+
+fn CGRectMake(
+    _env: &mut Environment,
+    x: CGFloat,
+    y: CGFloat,
+    width: CGFloat,
+    height: CGFloat,
+) -> CGRect {
+    CGRect {
+        origin: CGPoint { x, y },
+        size: CGSize { width, height },
+    }
+}
 // This function is rare because it is usually inlined.
 fn CGRectEqualToRect(_env: &mut Environment, a: CGRect, b: CGRect) -> bool {
     a == b
@@ -165,18 +286,132 @@ pub const CGRectZero: CGRect = CGRect {
     size: CGSizeZero,
 };
 
+/// The rectangle returned by e.g. `CGRectIntersection` when there is no
+/// intersection.
+pub const CGRectNull: CGRect = CGRect {
+    origin: CGPoint {
+        x: CGFloat::INFINITY,
+        y: CGFloat::INFINITY,
+    },
+    size: CGSizeZero,
+};
+
+fn CGRectGetMinX(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.bounds().0
+}
+fn CGRectGetMidX(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    let (min_x, _, max_x, _) = rect.bounds();
+    (min_x + max_x) / 2.0
+}
+fn CGRectGetMaxX(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.bounds().2
+}
+fn CGRectGetMinY(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.bounds().1
+}
+fn CGRectGetMidY(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    let (_, min_y, _, max_y) = rect.bounds();
+    (min_y + max_y) / 2.0
+}
+fn CGRectGetMaxY(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.bounds().3
+}
+fn CGRectGetWidth(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.standardize().size.width
+}
+fn CGRectGetHeight(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.standardize().size.height
+}
+
+fn CGRectIsNull(_env: &mut Environment, rect: CGRect) -> bool {
+    rect.is_null()
+}
+fn CGRectIsEmpty(_env: &mut Environment, rect: CGRect) -> bool {
+    rect.is_empty()
+}
+
+fn CGRectStandardize(_env: &mut Environment, rect: CGRect) -> CGRect {
+    rect.standardize()
+}
+
+fn CGRectInset(_env: &mut Environment, rect: CGRect, dx: CGFloat, dy: CGFloat) -> CGRect {
+    if rect.is_null() {
+        return rect;
+    }
+    let CGRect { origin, size } = rect.standardize();
+    let width = size.width - dx * 2.0;
+    let height = size.height - dy * 2.0;
+    // Insetting by more than the size leaves nothing.
+    if width < 0.0 || height < 0.0 {
+        return CGRectNull;
+    }
+    CGRect {
+        origin: CGPoint {
+            x: origin.x + dx,
+            y: origin.y + dy,
+        },
+        size: CGSize { width, height },
+    }
+}
+
+fn CGRectOffset(_env: &mut Environment, rect: CGRect, dx: CGFloat, dy: CGFloat) -> CGRect {
+    if rect.is_null() {
+        return rect;
+    }
+    let CGRect { origin, size } = rect.standardize();
+    CGRect {
+        origin: CGPoint {
+            x: origin.x + dx,
+            y: origin.y + dy,
+        },
+        size,
+    }
+}
+
+fn CGRectIntersection(_env: &mut Environment, a: CGRect, b: CGRect) -> CGRect {
+    a.intersection(b)
+}
+fn CGRectUnion(_env: &mut Environment, a: CGRect, b: CGRect) -> CGRect {
+    a.union(b)
+}
+
+fn CGRectIntersectsRect(_env: &mut Environment, a: CGRect, b: CGRect) -> bool {
+    !a.intersection(b).is_null()
+}
+
 fn CGRectContainsPoint(_env: &mut Environment, rect: CGRect, point: CGPoint) -> bool {
-    rect.origin.x <= point.x
-        && rect.origin.x + rect.size.width > point.x
-        && rect.origin.y <= point.y
-        && rect.origin.y + rect.size.height > point.y
+    rect.contains_point(point)
+}
+
+fn CGRectContainsRect(_env: &mut Environment, a: CGRect, b: CGRect) -> bool {
+    !a.is_null() && !b.is_null() && a.union(b) == a.standardize()
 }
 
 pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGPointMake(_, _)),
     export_c_func!(CGPointEqualToPoint(_, _)),
+    export_c_func!(CGSizeMake(_, _)),
     export_c_func!(CGSizeEqualToSize(_, _)),
+    export_c_func!(CGRectMake(_, _, _, _)),
     export_c_func!(CGRectEqualToRect(_, _)),
+    export_c_func!(CGRectGetMinX(_)),
+    export_c_func!(CGRectGetMidX(_)),
+    export_c_func!(CGRectGetMaxX(_)),
+    export_c_func!(CGRectGetMinY(_)),
+    export_c_func!(CGRectGetMidY(_)),
+    export_c_func!(CGRectGetMaxY(_)),
+    export_c_func!(CGRectGetWidth(_)),
+    export_c_func!(CGRectGetHeight(_)),
+    export_c_func!(CGRectIsNull(_)),
+    export_c_func!(CGRectIsEmpty(_)),
+    export_c_func!(CGRectStandardize(_)),
+    export_c_func!(CGRectInset(_, _, _)),
+    export_c_func!(CGRectOffset(_, _, _)),
+    export_c_func!(CGRectIntersection(_, _)),
+    export_c_func!(CGRectUnion(_, _)),
+    export_c_func!(CGRectIntersectsRect(_, _)),
     export_c_func!(CGRectContainsPoint(_, _)),
+    export_c_func!(CGRectContainsRect(_, _)),
 ];
 
 pub const CONSTANTS: ConstantExports = &[
@@ -192,4 +427,8 @@ pub const CONSTANTS: ConstantExports = &[
         "_CGRectZero",
         HostConstant::Custom(|mem, _| mem.alloc_and_write(CGRectZero).cast().cast_const()),
     ),
+    (
+        "_CGRectNull",
+        HostConstant::Custom(|mem, _| mem.alloc_and_write(CGRectNull).cast().cast_const()),
+    ),
 ];
@@ -18,6 +18,8 @@ use crate::Environment;
 pub mod ns_array;
 pub mod ns_autorelease_pool;
 pub mod ns_bundle;
+pub mod ns_cache;
+pub mod ns_calendar;
 pub mod ns_character_set;
 pub mod ns_coder;
 pub mod ns_data;
@@ -29,13 +31,19 @@ pub mod ns_error;
 pub mod ns_exception;
 pub mod ns_file_handle;
 pub mod ns_file_manager;
+pub mod ns_index_path;
+pub mod ns_index_set;
+pub mod ns_invocation;
+pub mod ns_json_serialization;
 pub mod ns_keyed_unarchiver;
 pub mod ns_locale;
 pub mod ns_lock;
 pub mod ns_log;
+pub mod ns_method_signature;
 pub mod ns_notification;
 pub mod ns_notification_center;
 pub mod ns_null;
+pub mod ns_number_formatter;
 pub mod ns_objc_runtime;
 pub mod ns_object;
 pub mod ns_process_info;
@@ -57,14 +65,14 @@ pub mod ns_xml_parser;
 pub struct State {
     ns_autorelease_pool: ns_autorelease_pool::State,
     ns_bundle: ns_bundle::State,
-    ns_file_manager: ns_file_manager::State,
+    ns_enumerator: ns_enumerator::State,
     ns_locale: ns_locale::State,
-    ns_notification_center: ns_notification_center::State,
     ns_null: ns_null::State,
+    ns_process_info: ns_process_info::State,
     ns_run_loop: ns_run_loop::State,
     ns_string: ns_string::State,
     ns_thread: ns_thread::State,
-    ns_user_defaults: ns_user_defaults::State,
+    ns_time_zone: ns_time_zone::State,
 }
 
 pub type NSInteger = i32;
@@ -5,15 +5,19 @@
  */
 //! The `NSArray` class cluster, including `NSMutableArray`.
 
-use super::ns_enumerator::{fast_enumeration_helper, NSFastEnumerationState};
+use super::ns_enumerator::{
+    fast_enumeration_helper, forget_mutations, mutations_ptr, note_mutation, NSFastEnumerationState,
+};
 use super::ns_property_list_serialization::deserialize_plist_from_file;
-use super::{ns_keyed_unarchiver, ns_string, ns_url, NSInteger, NSNotFound, NSUInteger};
+use super::{
+    ns_keyed_unarchiver, ns_string, ns_url, NSComparisonResult, NSInteger, NSNotFound, NSUInteger,
+};
 use crate::abi::{CallFromHost, GuestFunction};
 use crate::fs::GuestPath;
-use crate::mem::{MutPtr, MutVoidPtr};
+use crate::mem::{ConstPtr, MutPtr, MutVoidPtr};
 use crate::objc::{
-    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
-    NSZonePtr,
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
+    HostObject, NSZonePtr, SEL,
 };
 use crate::Environment;
 
@@ -41,7 +45,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 // - (id)objectAtIndex:(NSUInteger)index;
 // We can pick whichever subclass we want for the various alloc methods.
 // For the time being, that will always be _touchHLE_NSArray.
-@implementation NSArray: NSObject
+@implementation NSArray: NSObject <NSCopying, NSMutableCopying, NSFastEnumeration>
 
 + (id)allocWithZone:(NSZonePtr)zone {
     // NSArray might be subclassed by something which needs allocWithZone:
@@ -151,6 +155,12 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; this objectAtIndex:(size - 1)]
 }
 
+- (id)sortedArrayUsingSelector:(SEL)comparator {
+    let sorted: id = msg_class![env; NSMutableArray arrayWithArray:this];
+    () = msg![env; sorted sortUsingSelector:comparator];
+    msg_class![env; NSArray arrayWithArray:sorted]
+}
+
 - (id)componentsJoinedByString:(id)str { // NSString *
     let res: id = msg_class![env; NSMutableString new];
     let count: NSUInteger = msg![env; this count];
@@ -285,6 +295,16 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (id)initWithObjects:(ConstPtr<id>)objects
+                count:(NSUInteger)count {
+    let objects = read_objects(env, objects, count);
+    for &object in &objects {
+        retain(env, object);
+    }
+    env.objc.borrow_mut::<ArrayHostObject>(this).array = objects;
+    this
+}
+
 - (())dealloc {
     let host_object: &mut ArrayHostObject = env.objc.borrow_mut(this);
     let array = std::mem::take(&mut host_object.array);
@@ -308,7 +328,7 @@ pub const CLASSES: ClassExports = objc_classes! {
                                   objects:(MutPtr<id>)stackbuf
                                     count:(NSUInteger)len {
     let count: NSUInteger = msg![env; this count];
-    fast_enumeration_helper(env, this, |env, idx| {
+    fast_enumeration_helper(env, this.cast(), |env, idx| {
         if idx < count {
             msg![env; this objectAtIndex:idx]
         } else {
@@ -341,6 +361,13 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
+- (id)initWithObjects:(ConstPtr<id>)objects
+                count:(NSUInteger)count {
+    let objects = read_objects(env, objects, count);
+    env.objc.borrow_mut::<ArrayHostObject>(this).array = objects;
+    this
+}
+
 @end
 
 @implementation _touchHLE_NSArray_ObjectEnumerator: NSEnumerator
@@ -402,6 +429,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         release(env, object);
     }
 
+    forget_mutations(env, this);
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
@@ -422,6 +450,19 @@ pub const CLASSES: ClassExports = objc_classes! {
     });
 
     env.objc.borrow_mut::<ArrayHostObject>(this).array = array;
+    note_mutation(env, this);
+}
+
+- (())sortUsingSelector:(SEL)comparator {
+    let host_object: &mut ArrayHostObject = env.objc.borrow_mut(this);
+    let mut array = std::mem::take(&mut host_object.array);
+    array.sort_by(|&a, &b| {
+        let res: NSComparisonResult = msg_send(env, (a, comparator, b));
+        res.cmp(&0)
+    });
+
+    env.objc.borrow_mut::<ArrayHostObject>(this).array = array;
+    note_mutation(env, this);
 }
 
 // NSFastEnumeration implementation
@@ -430,7 +471,8 @@ pub const CLASSES: ClassExports = objc_classes! {
                                     count:(NSUInteger)len {
     // TODO: check that array wasn't mutated!
     let count: NSUInteger = msg![env; this count];
-    fast_enumeration_helper(env, this, |env, idx| {
+    let mutations_ptr = mutations_ptr(env, this);
+    fast_enumeration_helper(env, mutations_ptr, |env, idx| {
         if idx < count {
             msg![env; this objectAtIndex:idx]
         } else {
@@ -456,15 +498,34 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (())addObject:(id)object {
     retain(env, object);
     env.objc.borrow_mut::<ArrayHostObject>(this).array.push(object);
+    note_mutation(env, this);
+}
+
+- (())insertObject:(id)object
+          atIndex:(NSUInteger)index {
+    retain(env, object);
+    env.objc.borrow_mut::<ArrayHostObject>(this).array.insert(index as usize, object);
+    note_mutation(env, this);
+}
+
+- (())replaceObjectAtIndex:(NSUInteger)index
+                withObject:(id)object {
+    retain(env, object);
+    let array = &mut env.objc.borrow_mut::<ArrayHostObject>(this).array;
+    let old = std::mem::replace(&mut array[index as usize], object);
+    note_mutation(env, this);
+    release(env, old)
 }
 
 - (())removeObjectAtIndex:(NSUInteger)index {
     let object = env.objc.borrow_mut::<ArrayHostObject>(this).array.remove(index as usize);
+    note_mutation(env, this);
     release(env, object)
 }
 
 - (())removeLastObject {
     let object = env.objc.borrow_mut::<ArrayHostObject>(this).array.pop().unwrap();
+    note_mutation(env, this);
     release(env, object)
 }
 
@@ -475,7 +536,8 @@ pub const CLASSES: ClassExports = objc_classes! {
         release(env, object);
     }
 
-    env.objc.borrow_mut::<ArrayHostObject>(this).array = Vec::new()
+    env.objc.borrow_mut::<ArrayHostObject>(this).array = Vec::new();
+    note_mutation(env, this);
 }
 
 @end
@@ -485,19 +547,40 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation _touchHLE_NSMutableArray_non_retaining: _touchHLE_NSMutableArray
 
 - (())dealloc {
+    forget_mutations(env, this);
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
 - (())addObject:(id)object {
     env.objc.borrow_mut::<ArrayHostObject>(this).array.push(object);
+    note_mutation(env, this);
+}
+
+- (())insertObject:(id)object
+          atIndex:(NSUInteger)index {
+    env.objc.borrow_mut::<ArrayHostObject>(this).array.insert(index as usize, object);
+    note_mutation(env, this);
+}
+
+- (())replaceObjectAtIndex:(NSUInteger)index
+                withObject:(id)object {
+    env.objc.borrow_mut::<ArrayHostObject>(this).array[index as usize] = object;
+    note_mutation(env, this);
 }
 
 - (())removeObjectAtIndex:(NSUInteger)index {
     env.objc.borrow_mut::<ArrayHostObject>(this).array.remove(index as usize);
+    note_mutation(env, this);
 }
 
 - (())removeLastObject {
     env.objc.borrow_mut::<ArrayHostObject>(this).array.pop().unwrap();
+    note_mutation(env, this);
+}
+
+- (())removeAllObjects {
+    env.objc.borrow_mut::<ArrayHostObject>(this).array.clear();
+    note_mutation(env, this);
 }
 
 @end
@@ -513,6 +596,11 @@ pub fn from_vec(env: &mut Environment, objects: Vec<id>) -> id {
     array
 }
 
+/// Read a C array of `count` objects, as passed to `initWithObjects:count:`.
+fn read_objects(env: &mut Environment, objects: ConstPtr<id>, count: NSUInteger) -> Vec<id> {
+    (0..count).map(|i| env.mem.read(objects + i)).collect()
+}
+
 /// A helper to build a description NSString
 /// for a NSArray or a NSMutableArray.
 fn build_description(env: &mut Environment, arr: id) -> id {
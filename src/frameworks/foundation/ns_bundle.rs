@@ -5,16 +5,22 @@
  */
 //! `NSBundle`.
 
+mod strings_file;
+
+use super::ns_dictionary::dict_from_keys_and_objects;
+use super::ns_property_list_serialization::{deserialize_plist, deserialize_plist_from_file};
 use super::{ns_string, NSUInteger};
 use crate::bundle::Bundle;
 use crate::frameworks::core_foundation::cf_bundle::{
     CFBundleCopyBundleLocalizations, CFBundleCopyPreferredLocalizationsFromArray,
 };
 use crate::frameworks::foundation::ns_string::from_rust_string;
+use crate::fs::GuestPath;
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
 };
 use crate::Environment;
+use plist::Value;
 use std::collections::{HashMap, HashSet};
 
 // Should be ISO 639-1 (or ISO 639-2) compliant
@@ -35,8 +41,9 @@ const LANG_ID_TO_LANG_PROJ: &[(&str, &str)] = &[
 
 #[derive(Default)]
 pub struct State {
-    main_bundle: Option<id>,
-    localization_tables: HashMap<id, id>, // NSString* to NSDictionary*
+    /// `NSString*` table name to `NSDictionary*`, or `nil` if the table
+    /// doesn't exist.
+    localization_tables: HashMap<id, id>,
 }
 
 pub struct NSBundleHostObject {
@@ -61,9 +68,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation NSBundle: NSObject
 
 + (id)mainBundle {
-    if let Some(bundle) = env.framework_state.foundation.ns_bundle.main_bundle {
-        bundle
-    } else {
+    env.shared_object("+[NSBundle mainBundle]", |env| {
         let bundle_path = env.bundle.bundle_path().as_str().to_string();
         let bundle_path = ns_string::from_rust_string(env, bundle_path);
         let bundle_identifier = env.bundle.bundle_identifier().to_string();
@@ -75,14 +80,8 @@ pub const CLASSES: ClassExports = objc_classes! {
             bundle_url: None,
             info_dictionary: None,
         };
-        let new = env.objc.alloc_object(
-            this,
-            Box::new(host_object),
-            &mut env.mem
-        );
-        env.framework_state.foundation.ns_bundle.main_bundle = Some(new);
-        new
-   }
+        env.objc.alloc_object(this, Box::new(host_object), &mut env.mem)
+    })
 }
 
 + (id)preferredLocalizationsFromArray:(id)localizations_array { // NSArray<NSString *> *
@@ -241,22 +240,19 @@ pub const CLASSES: ClassExports = objc_classes! {
         tableName
     };
     // TODO: support arbitrary bundles, not only main one
-    assert_eq!(this, env.framework_state.foundation.ns_bundle.main_bundle.unwrap());
+    assert_eq!(this, msg_class![env; NSBundle mainBundle]);
     let dict = if let Some(&table_dict) = env.framework_state.foundation.ns_bundle.localization_tables.get(&name) {
         table_dict
     } else {
-        let extension = ns_string::get_static_str(env, "strings");
-        let dict_url: id = msg![env; this URLForResource:name withExtension:extension];
-        let dict: id = msg_class![env; NSDictionary dictionaryWithContentsOfURL:dict_url];
-        assert!(dict != nil);
+        let dict = load_strings_table(env, this, name);
         retain(env, name);
-        retain(env, dict);
         env.framework_state.foundation.ns_bundle.localization_tables.insert(name, dict);
         dict
     };
     let res: id = msg![env; dict objectForKey:key];
     if res == nil {
-        if value == nil || value == empty_str {
+        let value_length: NSUInteger = if value == nil { 0 } else { msg![env; value length] };
+        if value_length == 0 {
             return key;
         }
         return value;
@@ -266,19 +262,15 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (id)infoDictionary {
-    let &NSBundleHostObject {
-        bundle_path,
-        info_dictionary,
-        ..
-    } = env.objc.borrow(this);
-    if let Some(dict) = info_dictionary {
+    let host_object = env.objc.borrow::<NSBundleHostObject>(this);
+    if let Some(dict) = host_object.info_dictionary {
         return dict;
     }
 
-    let plist_path = ns_string::get_static_str(env, "Info.plist");
-    let plist_path: id = msg![env; bundle_path stringByAppendingPathComponent:plist_path];
-    let dict: id = msg_class![env; NSDictionary alloc];
-    let dict: id = msg![env; dict initWithContentsOfFile:plist_path];
+    // Info.plist was already parsed when the bundle was loaded.
+    let bundle = host_object.bundle.as_ref().unwrap_or(&env.bundle);
+    let plist = Value::Dictionary(bundle.info_plist().clone());
+    let dict = deserialize_plist(env, &plist);
     env.objc.borrow_mut::<NSBundleHostObject>(this).info_dictionary = Some(dict);
     dict
 }
@@ -307,6 +299,53 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// Load a `.strings` file as an `NSDictionary*`, or return `nil` if there is no
+/// such file or it can't be parsed. The result is not autoreleased.
+fn load_strings_table(env: &mut Environment, bundle: id, name: id) -> id {
+    let extension = ns_string::get_static_str(env, "strings");
+    let path: id = msg![env; bundle pathForResource:name ofType:extension];
+    if path == nil {
+        log!(
+            "Warning: strings table {:?} not found, localized strings will be missing",
+            ns_string::to_rust_string(env, name)
+        );
+        return nil;
+    }
+    let path = ns_string::to_rust_string(env, path);
+    let path = GuestPath::new(&path);
+
+    // Strings files are usually compiled to binary plists.
+    let dict = deserialize_plist_from_file(env, path, /* array_expected: */ false);
+    if dict != nil {
+        return dict;
+    }
+
+    let Ok(bytes) = env.fs.read(path) else {
+        return nil;
+    };
+    let pairs = match strings_file::parse(&strings_file::decode(&bytes)) {
+        Ok(pairs) => pairs,
+        Err(error) => {
+            log!(
+                "Warning: couldn't parse strings file {:?}: {:?}",
+                path,
+                error
+            );
+            return nil;
+        }
+    };
+    let keys_and_objects: Vec<(id, id)> = pairs
+        .into_iter()
+        .map(|(key, value)| (from_rust_string(env, key), from_rust_string(env, value)))
+        .collect();
+    let dict = dict_from_keys_and_objects(env, &keys_and_objects);
+    for (key, value) in keys_and_objects {
+        release(env, key);
+        release(env, value);
+    }
+    dict
+}
+
 fn path_for_resource_helper(
     env: &mut Environment,
     bundle: id,
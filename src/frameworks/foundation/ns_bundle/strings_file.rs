@@ -0,0 +1,237 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Parser for the text format of `.strings` files, as used for localization.
+//!
+//! Apps usually ship these compiled to binary plists, which are handled by the
+//! plist code instead, but some contain the original text files, which look
+//! like:
+//!
+//! ```text
+//! /* A comment */
+//! "Hello" = "Bonjour";
+//! ```
+//!
+//! Resources:
+//! - Apple's [String Resources](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/LoadingResources/Strings/Strings.html)
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    /// Character offset in the decoded input where the problem was found.
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+/// Decode the file's bytes: UTF-16 if there's a byte order mark (the
+/// traditional encoding for these files), otherwise UTF-8.
+pub fn decode(bytes: &[u8]) -> String {
+    let utf16_units = |be: bool| {
+        bytes[2..]
+            .chunks_exact(2)
+            .map(|pair| {
+                let pair = [pair[0], pair[1]];
+                if be {
+                    u16::from_be_bytes(pair)
+                } else {
+                    u16::from_le_bytes(pair)
+                }
+            })
+            .collect::<Vec<u16>>()
+    };
+    if bytes.starts_with(b"\xFF\xFE") {
+        String::from_utf16_lossy(&utf16_units(false))
+    } else if bytes.starts_with(b"\xFE\xFF") {
+        String::from_utf16_lossy(&utf16_units(true))
+    } else {
+        let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+/// Parse the key-value pairs, in the order they appear in the file.
+pub fn parse(text: &str) -> Result<Vec<(String, String)>, ParseError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    let mut pairs = Vec::new();
+    loop {
+        parser.skip_whitespace_and_comments()?;
+        if parser.peek().is_none() {
+            break;
+        }
+        let key = parser.parse_string()?;
+        parser.skip_whitespace_and_comments()?;
+        // A key on its own is its own value.
+        let value = if parser.peek() == Some('=') {
+            parser.pos += 1;
+            parser.skip_whitespace_and_comments()?;
+            parser.parse_string()?
+        } else {
+            key.clone()
+        };
+        parser.skip_whitespace_and_comments()?;
+        if parser.peek() != Some(';') {
+            return Err(parser.error("Expected ';'."));
+        }
+        parser.pos += 1;
+        pairs.push((key, value));
+    }
+    Ok(pairs)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &'static str) -> ParseError {
+        ParseError {
+            offset: self.pos,
+            message,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), ParseError> {
+        loop {
+            match (self.peek(), self.chars.get(self.pos + 1).copied()) {
+                (Some(c), _) if c.is_whitespace() => self.pos += 1,
+                (Some('/'), Some('/')) => while !matches!(self.next(), None | Some('\n')) {},
+                (Some('/'), Some('*')) => {
+                    let start = self.pos;
+                    self.pos += 2;
+                    loop {
+                        match self.next() {
+                            Some('*') if self.peek() == Some('/') => {
+                                self.pos += 1;
+                                break;
+                            }
+                            Some(_) => (),
+                            None => {
+                                self.pos = start;
+                                return Err(self.error("Unterminated comment."));
+                            }
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Parse a quoted string, or an unquoted one made of the characters
+    /// allowed in that case.
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        if self.peek() != Some('"') {
+            let start = self.pos;
+            while self
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphanumeric() || "_.$:/-".contains(c))
+            {
+                self.pos += 1;
+            }
+            if self.pos == start {
+                return Err(self.error("Expected a string."));
+            }
+            return Ok(self.chars[start..self.pos].iter().collect());
+        }
+
+        self.pos += 1;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                None => return Err(self.error("Unterminated string.")),
+                Some('"') => return Ok(string),
+                Some('\\') => match self.next() {
+                    None => return Err(self.error("Unterminated string.")),
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some('r') => string.push('\r'),
+                    Some('U') | Some('u') => {
+                        let digits: String = self.chars.iter().skip(self.pos).take(4).collect();
+                        let unit = u16::from_str_radix(&digits, 16)
+                            .ok()
+                            .filter(|_| digits.len() == 4)
+                            .ok_or_else(|| self.error("Invalid \\U escape."))?;
+                        self.pos += 4;
+                        string.push(char::from_u32(unit.into()).unwrap_or('\u{FFFD}'));
+                    }
+                    // Covers \" and \\, and is harmless for anything else.
+                    Some(c) => string.push(c),
+                },
+                Some(c) => string.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+/* Menu title */
+"Play" = "Jouer";
+// Line comment
+"Quote \"%@\"\n" = "Citation \U00AB%@\U00BB\n";
+UnquotedKey = "value";
+"Same";
+"#;
+        assert_eq!(
+            parse(text),
+            Ok(pairs(&[
+                ("Play", "Jouer"),
+                ("Quote \"%@\"\n", "Citation «%@»\n"),
+                ("UnquotedKey", "value"),
+                ("Same", "Same"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse(r#""a" = "b""#),
+            Err(ParseError {
+                offset: 9,
+                message: "Expected ';'."
+            })
+        );
+        assert!(parse(r#""a" = "b;"#).is_err());
+        assert!(parse("/* \"a\" = \"b\";").is_err());
+    }
+
+    #[test]
+    fn test_decode() {
+        let utf16le: Vec<u8> = [0xFEFF]
+            .into_iter()
+            .chain("\"é\";".encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(decode(&utf16le), "\"é\";");
+        assert_eq!(decode(b"\xEF\xBB\xBF\"a\";"), "\"a\";");
+    }
+}
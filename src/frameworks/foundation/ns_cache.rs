@@ -0,0 +1,159 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSCache`.
+//!
+//! Apple doesn't document which objects get evicted first, only that the
+//! limits are not strict. This implementation evicts the least recently used
+//! objects first and always keeps within the limits. It doesn't respond to
+//! memory warnings.
+
+use super::NSUInteger;
+use crate::msg;
+use crate::objc::{id, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr};
+use crate::Environment;
+
+#[derive(Default)]
+struct NSCacheHostObject {
+    /// Cached key-object pairs and their costs, ordered from least recently
+    /// used to most recently used. Both the keys and the objects are retained.
+    entries: Vec<(id, id, NSUInteger)>,
+    total_cost: NSUInteger,
+    /// Zero means no limit.
+    count_limit: NSUInteger,
+    /// Zero means no limit.
+    total_cost_limit: NSUInteger,
+    name: id,
+}
+impl HostObject for NSCacheHostObject {}
+
+/// Finds the index of the entry with a key equal to `key`, if any.
+fn find_entry(env: &mut Environment, this: id, key: id) -> Option<usize> {
+    let count = env.objc.borrow::<NSCacheHostObject>(this).entries.len();
+    for i in 0..count {
+        let (candidate_key, _, _) = env.objc.borrow::<NSCacheHostObject>(this).entries[i];
+        if candidate_key == key || msg![env; candidate_key isEqual:key] {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// Removes the entry at `index` and releases its key and object.
+fn remove_entry(env: &mut Environment, this: id, index: usize) {
+    let host_object = env.objc.borrow_mut::<NSCacheHostObject>(this);
+    let (key, object, cost) = host_object.entries.remove(index);
+    host_object.total_cost -= cost;
+    release(env, key);
+    release(env, object);
+}
+
+/// Evicts the least recently used entries until the cache is within its limits.
+fn evict_to_limits(env: &mut Environment, this: id) {
+    loop {
+        let &NSCacheHostObject {
+            ref entries,
+            total_cost,
+            count_limit,
+            total_cost_limit,
+            ..
+        } = env.objc.borrow(this);
+        let over_count = count_limit != 0 && entries.len() as NSUInteger > count_limit;
+        let over_cost = total_cost_limit != 0 && total_cost > total_cost_limit;
+        if entries.is_empty() || !(over_count || over_cost) {
+            break;
+        }
+        remove_entry(env, this, 0);
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSCache: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSCacheHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let _: () = msg![env; this removeAllObjects];
+    let name = env.objc.borrow::<NSCacheHostObject>(this).name;
+    release(env, name);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)objectForKey:(id)key {
+    let Some(index) = find_entry(env, this, key) else {
+        return nil;
+    };
+    // Mark the entry as the most recently used.
+    let entries = &mut env.objc.borrow_mut::<NSCacheHostObject>(this).entries;
+    let entry = entries.remove(index);
+    entries.push(entry);
+    entry.1
+}
+
+- (())setObject:(id)object forKey:(id)key {
+    msg![env; this setObject:object forKey:key cost:0u32]
+}
+
+- (())setObject:(id)object forKey:(id)key cost:(NSUInteger)cost {
+    assert!(key != nil);
+    assert!(object != nil);
+    // Unlike NSMutableDictionary, NSCache doesn't copy its keys.
+    retain(env, key);
+    retain(env, object);
+    if let Some(index) = find_entry(env, this, key) {
+        remove_entry(env, this, index);
+    }
+    let host_object = env.objc.borrow_mut::<NSCacheHostObject>(this);
+    host_object.entries.push((key, object, cost));
+    host_object.total_cost += cost;
+    evict_to_limits(env, this);
+}
+
+- (())removeObjectForKey:(id)key {
+    if let Some(index) = find_entry(env, this, key) {
+        remove_entry(env, this, index);
+    }
+}
+
+- (())removeAllObjects {
+    while !env.objc.borrow::<NSCacheHostObject>(this).entries.is_empty() {
+        remove_entry(env, this, 0);
+    }
+}
+
+- (NSUInteger)countLimit {
+    env.objc.borrow::<NSCacheHostObject>(this).count_limit
+}
+- (())setCountLimit:(NSUInteger)limit {
+    env.objc.borrow_mut::<NSCacheHostObject>(this).count_limit = limit;
+    evict_to_limits(env, this);
+}
+
+- (NSUInteger)totalCostLimit {
+    env.objc.borrow::<NSCacheHostObject>(this).total_cost_limit
+}
+- (())setTotalCostLimit:(NSUInteger)limit {
+    env.objc.borrow_mut::<NSCacheHostObject>(this).total_cost_limit = limit;
+    evict_to_limits(env, this);
+}
+
+- (id)name {
+    env.objc.borrow::<NSCacheHostObject>(this).name
+}
+- (())setName:(id)name { // NSString *
+    let name: id = msg![env; name copy];
+    let old = std::mem::replace(&mut env.objc.borrow_mut::<NSCacheHostObject>(this).name, name);
+    release(env, old);
+}
+
+@end
+
+};
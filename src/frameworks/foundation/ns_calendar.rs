@@ -0,0 +1,407 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSCalendar` and `NSDateComponents`.
+//!
+//! Only the Gregorian calendar is supported, and it is proleptic: the Julian
+//! calendar is never used, even for dates before 1582. Weeks always start on
+//! Sunday.
+
+use super::ns_date_formatter::date_pattern::DateComponents;
+use super::{ns_string, ns_time_zone, NSInteger, NSTimeInterval, NSUInteger};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_foundation::time::SECS_FROM_UNIX_TO_APPLE_EPOCHS;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+const NSGregorianCalendar: &str = "gregorian";
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_NSGregorianCalendar",
+    HostConstant::NSString(NSGregorianCalendar),
+)];
+
+pub type NSCalendarUnit = NSUInteger;
+pub const NSEraCalendarUnit: NSCalendarUnit = 1 << 1;
+pub const NSYearCalendarUnit: NSCalendarUnit = 1 << 2;
+pub const NSMonthCalendarUnit: NSCalendarUnit = 1 << 3;
+pub const NSDayCalendarUnit: NSCalendarUnit = 1 << 4;
+pub const NSHourCalendarUnit: NSCalendarUnit = 1 << 5;
+pub const NSMinuteCalendarUnit: NSCalendarUnit = 1 << 6;
+pub const NSSecondCalendarUnit: NSCalendarUnit = 1 << 7;
+pub const NSWeekdayCalendarUnit: NSCalendarUnit = 1 << 9;
+pub const NSWeekdayOrdinalCalendarUnit: NSCalendarUnit = 1 << 10;
+const SUPPORTED_UNITS: NSCalendarUnit = NSEraCalendarUnit
+    | NSYearCalendarUnit
+    | NSMonthCalendarUnit
+    | NSDayCalendarUnit
+    | NSHourCalendarUnit
+    | NSMinuteCalendarUnit
+    | NSSecondCalendarUnit
+    | NSWeekdayCalendarUnit
+    | NSWeekdayOrdinalCalendarUnit;
+
+/// Value of components that weren't requested or set (`NSIntegerMax`).
+pub const NSUndefinedDateComponent: NSInteger = NSInteger::MAX;
+
+struct NSCalendarHostObject {
+    /// `NSString*`
+    identifier: id,
+    /// `None` means the default time zone.
+    time_zone: Option<id>,
+}
+impl HostObject for NSCalendarHostObject {}
+
+/// The fields of an `NSDateComponents`, in the same order as the units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Components {
+    era: NSInteger,
+    year: NSInteger,
+    month: NSInteger,
+    day: NSInteger,
+    hour: NSInteger,
+    minute: NSInteger,
+    second: NSInteger,
+    weekday: NSInteger,
+    weekday_ordinal: NSInteger,
+}
+impl Default for Components {
+    fn default() -> Self {
+        Components {
+            era: NSUndefinedDateComponent,
+            year: NSUndefinedDateComponent,
+            month: NSUndefinedDateComponent,
+            day: NSUndefinedDateComponent,
+            hour: NSUndefinedDateComponent,
+            minute: NSUndefinedDateComponent,
+            second: NSUndefinedDateComponent,
+            weekday: NSUndefinedDateComponent,
+            weekday_ordinal: NSUndefinedDateComponent,
+        }
+    }
+}
+
+#[derive(Default)]
+struct NSDateComponentsHostObject {
+    components: Components,
+}
+impl HostObject for NSDateComponentsHostObject {}
+
+/// Splits seconds since the UNIX epoch (in local time) into the requested
+/// components. Others are left undefined.
+fn components_from_timestamp(timestamp: i64, units: NSCalendarUnit) -> Components {
+    let date = DateComponents::from_timestamp(timestamp);
+    let wanted = |unit: NSCalendarUnit, value: i64| {
+        if units & unit != 0 {
+            value.try_into().unwrap()
+        } else {
+            NSUndefinedDateComponent
+        }
+    };
+    // There is no year 0: 1 BC is followed by 1 AD.
+    let (era, year) = if date.year > 0 {
+        (1, date.year)
+    } else {
+        (0, 1 - date.year)
+    };
+    Components {
+        era: wanted(NSEraCalendarUnit, era),
+        year: wanted(NSYearCalendarUnit, year),
+        month: wanted(NSMonthCalendarUnit, date.month.into()),
+        day: wanted(NSDayCalendarUnit, date.day.into()),
+        hour: wanted(NSHourCalendarUnit, date.hour.into()),
+        minute: wanted(NSMinuteCalendarUnit, date.minute.into()),
+        second: wanted(NSSecondCalendarUnit, date.second.into()),
+        // 1 is Sunday
+        weekday: wanted(NSWeekdayCalendarUnit, (date.weekday() + 1).into()),
+        weekday_ordinal: wanted(
+            NSWeekdayOrdinalCalendarUnit,
+            ((date.day - 1) / 7 + 1).into(),
+        ),
+    }
+}
+
+/// Converts components into seconds since the UNIX epoch (in local time).
+/// Undefined components take their lowest value, and out-of-range ones wrap
+/// around into the next larger unit (e.g. month 13 is January of next year).
+/// The weekday components are ignored.
+fn timestamp_from_components(components: &Components) -> i64 {
+    let get = |value: NSInteger, default: i64| {
+        if value == NSUndefinedDateComponent {
+            default
+        } else {
+            i64::from(value)
+        }
+    };
+    let year = get(components.year, 1);
+    let year = if get(components.era, 1) == 0 {
+        1 - year
+    } else {
+        year
+    };
+    let month = get(components.month, 1) - 1;
+    let date = DateComponents {
+        year: year + month.div_euclid(12),
+        month: (month.rem_euclid(12) + 1) as u32,
+        day: 1,
+        hour: 0,
+        minute: 0,
+        second: 0,
+    };
+    date.to_timestamp()
+        + (get(components.day, 1) - 1) * 86400
+        + get(components.hour, 0) * 3600
+        + get(components.minute, 0) * 60
+        + get(components.second, 0)
+}
+
+/// Returns the offset from GMT in seconds of an `NSCalendar*`'s time zone.
+fn time_zone_offset(env: &mut Environment, calendar: id) -> i64 {
+    let time_zone: id = msg![env; calendar timeZone];
+    ns_time_zone::seconds_from_gmt(env, time_zone).into()
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSCalendar: NSObject <NSCopying>
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSCalendarHostObject {
+        identifier: nil,
+        time_zone: None,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)currentCalendar {
+    // TODO: use the locale's calendar
+    let identifier = ns_string::get_static_str(env, NSGregorianCalendar);
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithCalendarIdentifier:identifier];
+    autorelease(env, new)
+}
+
+- (id)initWithCalendarIdentifier:(id)identifier { // NSString *
+    let name = ns_string::to_rust_string(env, identifier);
+    if name != NSGregorianCalendar {
+        log!("TODO: calendar {:?}, returning nil", name);
+        release(env, this);
+        return nil;
+    }
+    let identifier: id = msg![env; identifier copy];
+    env.objc.borrow_mut::<NSCalendarHostObject>(this).identifier = identifier;
+    this
+}
+
+- (())dealloc {
+    let &NSCalendarHostObject {
+        identifier,
+        time_zone,
+    } = env.objc.borrow(this);
+    release(env, identifier);
+    if let Some(time_zone) = time_zone {
+        release(env, time_zone);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    let identifier = env.objc.borrow::<NSCalendarHostObject>(this).identifier;
+    let time_zone: id = msg![env; this timeZone];
+    let new: id = msg_class![env; NSCalendar alloc];
+    let new: id = msg![env; new initWithCalendarIdentifier:identifier];
+    () = msg![env; new setTimeZone:time_zone];
+    new
+}
+
+- (id)calendarIdentifier {
+    env.objc.borrow::<NSCalendarHostObject>(this).identifier
+}
+
+- (id)timeZone {
+    let time_zone = env.objc.borrow::<NSCalendarHostObject>(this).time_zone;
+    time_zone.unwrap_or_else(|| msg_class![env; NSTimeZone defaultTimeZone])
+}
+
+- (())setTimeZone:(id)time_zone { // NSTimeZone *
+    if time_zone != nil {
+        retain(env, time_zone);
+    }
+    let new = if time_zone == nil { None } else { Some(time_zone) };
+    let old = std::mem::replace(
+        &mut env.objc.borrow_mut::<NSCalendarHostObject>(this).time_zone,
+        new
+    );
+    if let Some(old) = old {
+        release(env, old);
+    }
+}
+
+- (id)components:(NSCalendarUnit)units
+        fromDate:(id)date { // NSDate *
+    if units & !SUPPORTED_UNITS != 0 {
+        log!("TODO: calendar units {:#x} (leaving them undefined)", units & !SUPPORTED_UNITS);
+    }
+    let offset = time_zone_offset(env, this);
+    let ti: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
+    let timestamp = ti.floor() as i64 + SECS_FROM_UNIX_TO_APPLE_EPOCHS as i64 + offset;
+
+    let components: id = msg_class![env; NSDateComponents new];
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(components).components =
+        components_from_timestamp(timestamp, units);
+    autorelease(env, components)
+}
+
+- (id)dateFromComponents:(id)components { // NSDateComponents *
+    let offset = time_zone_offset(env, this);
+    let components = env.objc.borrow::<NSDateComponentsHostObject>(components).components;
+    let timestamp = timestamp_from_components(&components) - offset;
+
+    let ti = (timestamp - SECS_FROM_UNIX_TO_APPLE_EPOCHS as i64) as NSTimeInterval;
+    let date: id = msg_class![env; NSDate alloc];
+    let date: id = msg![env; date initWithTimeIntervalSinceReferenceDate:ti];
+    autorelease(env, date)
+}
+
+@end
+
+@implementation NSDateComponents: NSObject <NSCopying>
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSDateComponentsHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    let components = env.objc.borrow::<NSDateComponentsHostObject>(this).components;
+    let new: id = msg_class![env; NSDateComponents new];
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(new).components = components;
+    new
+}
+
+- (NSInteger)era {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).components.era
+}
+- (())setEra:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).components.era = value;
+}
+
+- (NSInteger)year {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).components.year
+}
+- (())setYear:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).components.year = value;
+}
+
+- (NSInteger)month {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).components.month
+}
+- (())setMonth:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).components.month = value;
+}
+
+- (NSInteger)day {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).components.day
+}
+- (())setDay:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).components.day = value;
+}
+
+- (NSInteger)hour {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).components.hour
+}
+- (())setHour:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).components.hour = value;
+}
+
+- (NSInteger)minute {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).components.minute
+}
+- (())setMinute:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).components.minute = value;
+}
+
+- (NSInteger)second {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).components.second
+}
+- (())setSecond:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).components.second = value;
+}
+
+- (NSInteger)weekday {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).components.weekday
+}
+- (())setWeekday:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).components.weekday = value;
+}
+
+- (NSInteger)weekdayOrdinal {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).components.weekday_ordinal
+}
+- (())setWeekdayOrdinal:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).components.weekday_ordinal = value;
+}
+
+@end
+
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_components_round_trip() {
+        // 2009-02-13 23:31:30, a Friday
+        let components = components_from_timestamp(1234567890, SUPPORTED_UNITS);
+        assert_eq!(
+            components,
+            Components {
+                era: 1,
+                year: 2009,
+                month: 2,
+                day: 13,
+                hour: 23,
+                minute: 31,
+                second: 30,
+                weekday: 6,
+                weekday_ordinal: 2,
+            }
+        );
+        assert_eq!(timestamp_from_components(&components), 1234567890);
+
+        let components = components_from_timestamp(1234567890, NSYearCalendarUnit);
+        assert_eq!(components.year, 2009);
+        assert_eq!(components.month, NSUndefinedDateComponent);
+    }
+
+    #[test]
+    fn test_timestamp_from_components() {
+        let date = |year, month, day| {
+            timestamp_from_components(&Components {
+                year,
+                month,
+                day,
+                ..Default::default()
+            })
+        };
+        // Out-of-range values wrap around
+        assert_eq!(date(2008, 14, 1), date(2009, 2, 1));
+        assert_eq!(date(2009, 3, 0), date(2009, 2, 28));
+        assert_eq!(date(2009, 0, 1), date(2008, 12, 1));
+        // Undefined components take their lowest value
+        assert_eq!(date(1970, NSUndefinedDateComponent, 1), 0);
+        // 1 BC is year 0 of the proleptic Gregorian calendar
+        let bc = components_from_timestamp(date(0, 6, 1), NSEraCalendarUnit | NSYearCalendarUnit);
+        assert_eq!((bc.era, bc.year), (0, 1));
+    }
+}
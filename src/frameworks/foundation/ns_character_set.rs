@@ -18,6 +18,17 @@ struct CharacterSetHostObject {
 }
 impl HostObject for CharacterSetHostObject {}
 
+/// Unicode General Category Zs and CHARACTER TABULATION (U+0009).
+const WHITESPACE: &[char] = &[
+    '\u{0020}', '\u{00A0}', '\u{1680}', '\u{2000}', '\u{2001}', '\u{2002}', '\u{2003}', '\u{2004}',
+    '\u{2005}', '\u{2006}', '\u{2007}', '\u{2008}', '\u{2009}', '\u{200A}', '\u{202F}', '\u{205F}',
+    '\u{3000}', '\u{0009}',
+];
+/// U+000A–U+000D, U+0085, U+2028 and U+2029.
+const NEWLINES: &[char] = &[
+    '\u{000A}', '\u{000B}', '\u{000C}', '\u{000D}', '\u{0085}', '\u{2028}', '\u{2029}',
+];
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -48,13 +59,29 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 + (id)whitespaceCharacterSet {
-    // Unicode General Category Zs and CHARACTER TABULATION (U+0009).
-    let chars = [
-        '\u{0020}', '\u{00A0}', '\u{1680}', '\u{2000}', '\u{2001}', '\u{2002}', '\u{2003}',
-        '\u{2004}', '\u{2005}', '\u{2006}', '\u{2007}', '\u{2008}', '\u{2009}', '\u{200A}',
-        '\u{202F}', '\u{205F}', '\u{3000}', '\u{0009}',
-    ];
-    let set = HashSet::from(chars.map(|c| unichar::try_from(c).unwrap()));
+    let set = WHITESPACE.iter().map(|&c| unichar::try_from(c).unwrap()).collect();
+
+    let new: id = msg![env; this alloc];
+    env.objc.borrow_mut::<CharacterSetHostObject>(new).set = set;
+
+    autorelease(env, new)
+}
+
++ (id)newlineCharacterSet {
+    let set = NEWLINES.iter().map(|&c| unichar::try_from(c).unwrap()).collect();
+
+    let new: id = msg![env; this alloc];
+    env.objc.borrow_mut::<CharacterSetHostObject>(new).set = set;
+
+    autorelease(env, new)
+}
+
++ (id)whitespaceAndNewlineCharacterSet {
+    let set = WHITESPACE
+        .iter()
+        .chain(NEWLINES)
+        .map(|&c| unichar::try_from(c).unwrap())
+        .collect();
 
     let new: id = msg![env; this alloc];
     env.objc.borrow_mut::<CharacterSetHostObject>(new).set = set;
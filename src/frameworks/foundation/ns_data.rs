@@ -5,8 +5,9 @@
  */
 //! `NSData` and `NSMutableData`.
 
-use super::ns_string::to_rust_string;
-use super::{NSRange, NSUInteger};
+use super::ns_error::set_cocoa_error;
+use super::ns_string::{from_rust_string, to_rust_string};
+use super::{NSInteger, NSRange, NSUInteger};
 use crate::frameworks::foundation::ns_keyed_unarchiver::decode_current_data;
 use crate::fs::GuestPath;
 use crate::mem::{ConstPtr, ConstVoidPtr, MutPtr, MutVoidPtr, Ptr};
@@ -22,12 +23,25 @@ pub(super) struct NSDataHostObject {
 }
 impl HostObject for NSDataHostObject {}
 
+type NSDataReadingOptions = NSUInteger;
+
+type NSDataBase64EncodingOptions = NSUInteger;
+const NSDataBase64Encoding64CharacterLineLength: NSDataBase64EncodingOptions = 1 << 0;
+const NSDataBase64Encoding76CharacterLineLength: NSDataBase64EncodingOptions = 1 << 1;
+const NSDataBase64EncodingEndLineWithCarriageReturn: NSDataBase64EncodingOptions = 1 << 4;
+const NSDataBase64EncodingEndLineWithLineFeed: NSDataBase64EncodingOptions = 1 << 5;
+
+type NSDataBase64DecodingOptions = NSUInteger;
+const NSDataBase64DecodingIgnoreUnknownCharacters: NSDataBase64DecodingOptions = 1 << 0;
+
+const NSFileReadNoSuchFileError: NSInteger = 260;
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
 // NSData doesn't seem to be an abstract class?
-@implementation NSData: NSObject
+@implementation NSData: NSObject <NSCopying, NSMutableCopying>
 
 + (id)allocWithZone:(NSZonePtr)_zone {
     let host_object = Box::new(NSDataHostObject {
@@ -66,6 +80,14 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
++ (id)dataWithContentsOfFile:(id)path // NSString*
+                     options:(NSDataReadingOptions)options
+                       error:(MutPtr<id>)error { // NSError**
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithContentsOfFile:path options:options error:error];
+    autorelease(env, new)
+}
+
 + (id)dataWithContentsOfMappedFile:(id)path {
     let new: id = msg![env; this alloc];
     let new: id = msg![env; new initWithContentsOfMappedFile:path];
@@ -121,9 +143,12 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (id)initWithContentsOfURL:(id)url { // NSURL *
+    if msg![env; url isFileURL] {
+        let path: id = msg![env; url path];
+        return msg![env; this initWithContentsOfFile:path];
+    }
     let path: id = msg![env; url absoluteString];
     let path = to_rust_string(env, path);
-    // TODO: file URL case
     assert!(path.starts_with("http"));
     log!("TODO: ignoring [(NSData*){:?} initWithContentsOfURL:{:?}]", this, path);
     // TODO: actually load data once we have proper network support
@@ -140,14 +165,35 @@ pub const CLASSES: ClassExports = objc_classes! {
         release(env, this);
         return nil;
     };
-    let size = bytes.len().try_into().unwrap();
-    let alloc = env.mem.alloc(size);
-    let slice = env.mem.bytes_at_mut(alloc.cast(), size);
-    slice.copy_from_slice(&bytes);
+    set_bytes_from_slice(env, this, &bytes);
+    this
+}
 
-    let host_object = env.objc.borrow_mut::<NSDataHostObject>(this);
-    host_object.bytes = alloc;
-    host_object.length = size;
+- (id)initWithContentsOfFile:(id)path // NSString*
+                     options:(NSDataReadingOptions)_options
+                       error:(MutPtr<id>)error { // NSError**
+    // The options are only hints about caching and memory mapping.
+    let new: id = msg![env; this initWithContentsOfFile:path];
+    if new == nil && path != nil {
+        let path = to_rust_string(env, path);
+        set_cocoa_error(
+            env,
+            error,
+            NSFileReadNoSuchFileError,
+            format!("Couldn't read file {:?}", path),
+        );
+    }
+    new
+}
+
+- (id)initWithBase64EncodedString:(id)string // NSString*
+                          options:(NSDataBase64DecodingOptions)options {
+    let string = to_rust_string(env, string);
+    let Some(bytes) = base64_decode(string.as_bytes(), options) else {
+        release(env, this);
+        return nil;
+    };
+    set_bytes_from_slice(env, this, &bytes);
     this
 }
 
@@ -161,17 +207,16 @@ pub const CLASSES: ClassExports = objc_classes! {
          atomically:(bool)_use_aux_file {
     let file = to_rust_string(env, path);
     log_dbg!("[(NSData*){:?} writeToFile:{:?} atomically:_]", this, file);
-    let host_object = env.objc.borrow::<NSDataHostObject>(this);
-    // Mem::bytes_at() panics when the pointer is NULL, but NSData's pointer can
-    // be NULL if the length is 0.
-    let slice = if host_object.length == 0 {
-        &[]
-    } else {
-        env.mem.bytes_at(host_object.bytes.cast(), host_object.length)
-    };
+    let slice = bytes_or_empty(env, this);
     env.fs.write(GuestPath::new(&file), slice).is_ok()
 }
 
+- (id)base64EncodedStringWithOptions:(NSDataBase64EncodingOptions)options {
+    let encoded = base64_encode(bytes_or_empty(env, this), options);
+    let encoded = from_rust_string(env, encoded);
+    autorelease(env, encoded)
+}
+
 - (())dealloc {
     let &NSDataHostObject { bytes, free_when_done, .. } = env.objc.borrow(this);
     if !bytes.is_null() && free_when_done {
@@ -189,7 +234,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (id)initWithCoder:(id)coder {
     release(env, this);
     // Note: Assuming NSKeyedUnarchiver as coder here
-    decode_current_data(env, coder, /* is_mutable: */ true)
+    decode_current_data(env, coder, /* is_mutable: */ false)
 }
 
 - (id)mutableCopyWithZone:(NSZonePtr)_zone {
@@ -206,6 +251,12 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow::<NSDataHostObject>(this).length
 }
 
+- (id)description {
+    let desc = hex_description(bytes_or_empty(env, this));
+    let desc = from_rust_string(env, desc);
+    autorelease(env, desc)
+}
+
 - (bool)isEqualToData:(id)other {
     // FIXME: Avoid allocation
     let a = to_rust_slice(env, this).to_owned();
@@ -246,6 +297,13 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation NSMutableData: NSData
 
+// NSCoding implementation
+- (id)initWithCoder:(id)coder {
+    release(env, this);
+    // Note: Assuming NSKeyedUnarchiver as coder here
+    decode_current_data(env, coder, /* is_mutable: */ true)
+}
+
 + (id)data {
     msg![env; this dataWithCapacity:0u32]
 }
@@ -325,9 +383,182 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// Like [to_rust_slice], but allows the data to be empty.
+fn bytes_or_empty(env: &mut Environment, data: id) -> &[u8] {
+    let host_object = env.objc.borrow::<NSDataHostObject>(data);
+    // Mem::bytes_at() panics when the pointer is NULL, but NSData's pointer can
+    // be NULL if the length is 0.
+    if host_object.length == 0 {
+        &[]
+    } else {
+        env.mem
+            .bytes_at(host_object.bytes.cast(), host_object.length)
+    }
+}
+
+/// For use by initializers: set the contents of a new `NSData` to a copy of
+/// `bytes`.
+fn set_bytes_from_slice(env: &mut Environment, data: id, bytes: &[u8]) {
+    let size = bytes.len().try_into().unwrap();
+    let alloc = env.mem.alloc(size);
+    env.mem
+        .bytes_at_mut(alloc.cast(), size)
+        .copy_from_slice(bytes);
+
+    let host_object = env.objc.borrow_mut::<NSDataHostObject>(data);
+    assert!(host_object.bytes.is_null() && host_object.length == 0);
+    host_object.bytes = alloc;
+    host_object.length = size;
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8], options: NSDataBase64EncodingOptions) -> String {
+    let line_length = if options & NSDataBase64Encoding64CharacterLineLength != 0 {
+        Some(64)
+    } else if options & NSDataBase64Encoding76CharacterLineLength != 0 {
+        Some(76)
+    } else {
+        None
+    };
+    let cr = options & NSDataBase64EncodingEndLineWithCarriageReturn != 0;
+    let lf = options & NSDataBase64EncodingEndLineWithLineFeed != 0;
+    // CRLF is the default if neither is specified.
+    let line_ending = match (cr, lf) {
+        (true, false) => "\r",
+        (false, true) => "\n",
+        _ => "\r\n",
+    };
+
+    let mut encoded = String::new();
+    let mut chars_on_line = 0;
+    for chunk in bytes.chunks(3) {
+        if line_length == Some(chars_on_line) {
+            encoded.push_str(line_ending);
+            chars_on_line = 0;
+        }
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (u32::from(byte) << (16 - 8 * i))
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(char::from(BASE64_ALPHABET[index as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+        chars_on_line += 4;
+    }
+    encoded
+}
+
+/// Formats bytes the way `-[NSData description]` does, e.g. `<0001feff 10>`.
+fn hex_description(bytes: &[u8]) -> String {
+    let groups: Vec<String> = bytes
+        .chunks(4)
+        .map(|group| group.iter().map(|byte| format!("{:02x}", byte)).collect())
+        .collect();
+    format!("<{}>", groups.join(" "))
+}
+
+/// Returns [None] if the input isn't valid base64.
+fn base64_decode(text: &[u8], options: NSDataBase64DecodingOptions) -> Option<Vec<u8>> {
+    let ignore_unknown = options & NSDataBase64DecodingIgnoreUnknownCharacters != 0;
+
+    let mut decoded = Vec::new();
+    let mut group: u32 = 0;
+    let mut group_len = 0;
+    let mut padding = 0;
+    for &c in text {
+        let value = match c {
+            b'=' => {
+                padding += 1;
+                continue;
+            }
+            _ => BASE64_ALPHABET.iter().position(|&d| d == c),
+        };
+        let Some(value) = value else {
+            if ignore_unknown {
+                continue;
+            }
+            return None;
+        };
+        // Padding is only allowed at the end.
+        if padding != 0 {
+            return None;
+        }
+        group = (group << 6) | value as u32;
+        group_len += 1;
+        if group_len == 4 {
+            decoded.extend_from_slice(&group.to_be_bytes()[1..]);
+            group = 0;
+            group_len = 0;
+        }
+    }
+    // The final group must be padded to four characters, and one character
+    // on its own doesn't encode a whole byte.
+    match (group_len, padding) {
+        (0, 0) => (),
+        (2, 2) => decoded.push((group >> 4) as u8),
+        (3, 1) => decoded.extend_from_slice(&(group >> 2).to_be_bytes()[2..]),
+        _ => return None,
+    }
+    Some(decoded)
+}
+
 pub fn to_rust_slice(env: &mut Environment, data: id) -> &[u8] {
     let borrowed_data = env.objc.borrow::<NSDataHostObject>(data);
     assert!(!borrowed_data.bytes.is_null() && borrowed_data.length != 0);
     env.mem
         .bytes_at(borrowed_data.bytes.cast(), borrowed_data.length)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_description() {
+        assert_eq!(hex_description(b""), "<>");
+        assert_eq!(hex_description(b"\x00\x01\xfe\xff\x10"), "<0001feff 10>");
+    }
+
+    #[test]
+    fn test_base64() {
+        for (bytes, encoded) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\xff\x00\xfe", "/wD+"),
+        ] {
+            assert_eq!(base64_encode(bytes, 0), encoded);
+            assert_eq!(base64_decode(encoded.as_bytes(), 0).as_deref(), Some(bytes));
+        }
+
+        // Line wrapping
+        let bytes = [0u8; 60];
+        let encoded = base64_encode(&bytes, NSDataBase64Encoding64CharacterLineLength);
+        assert_eq!(encoded, format!("{}\r\n{}", "A".repeat(64), "A".repeat(16)));
+        let encoded = base64_encode(
+            &bytes,
+            NSDataBase64Encoding76CharacterLineLength | NSDataBase64EncodingEndLineWithLineFeed,
+        );
+        assert_eq!(encoded, format!("{}\n{}", "A".repeat(76), "A".repeat(4)));
+        assert_eq!(base64_encode(&bytes[..48], 1).find('\r'), None);
+
+        // Invalid input
+        assert_eq!(base64_decode(b"Zg", 0), None);
+        assert_eq!(base64_decode(b"Zg=", 0), None);
+        assert_eq!(base64_decode(b"Z===", 0), None);
+        assert_eq!(base64_decode(b"Zg==Zm8=", 0), None);
+        assert_eq!(base64_decode(b"Zm9v\r\nYg==", 0), None);
+        assert_eq!(
+            base64_decode(b"Zm9v\r\nYg==", NSDataBase64DecodingIgnoreUnknownCharacters).as_deref(),
+            Some(&b"foob"[..])
+        );
+    }
+}
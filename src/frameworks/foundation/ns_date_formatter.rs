@@ -9,12 +9,21 @@
 //! - Apple's [Introduction to Data Formatting Programming Guide For Cocoa](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/DataFormatting/DataFormatting.html)
 //! - [Unicode Technical Standard #35](https://unicode.org/reports/tr35/tr35-10.html#Date_Format_Patterns)
 
-use crate::frameworks::core_foundation::time::CFAbsoluteTimeGetGregorianDate;
-use crate::frameworks::foundation::{ns_string, NSTimeInterval};
-use crate::objc::{autorelease, id, msg, nil, objc_classes, ClassExports, HostObject, NSZonePtr};
+pub(super) mod date_pattern;
+
+use crate::frameworks::core_foundation::time::SECS_FROM_UNIX_TO_APPLE_EPOCHS;
+use crate::frameworks::foundation::{ns_string, ns_time_zone, NSTimeInterval};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+use date_pattern::DateComponents;
 
 struct NSDateFormatterHostObject {
     date_format: Option<id>,
+    /// `None` means the default time zone.
+    time_zone: Option<id>,
 }
 impl HostObject for NSDateFormatterHostObject {}
 
@@ -27,50 +36,110 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (id)allocWithZone:(NSZonePtr)_zone {
     let host_object = Box::new(NSDateFormatterHostObject {
         date_format: None,
+        time_zone: None,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
+- (())dealloc {
+    let &NSDateFormatterHostObject {
+        date_format,
+        time_zone,
+    } = env.objc.borrow(this);
+    if let Some(date_format) = date_format {
+        release(env, date_format);
+    }
+    if let Some(time_zone) = time_zone {
+        release(env, time_zone);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)dateFormat {
+    env.objc.borrow::<NSDateFormatterHostObject>(this).date_format.unwrap_or(nil)
+}
+
 - (())setDateFormat:(id)format { // NSString *
     let date_format: id = msg![env; format copy];
-    env.objc.borrow_mut::<NSDateFormatterHostObject>(this).date_format = Some(date_format);
+    let old = env.objc.borrow_mut::<NSDateFormatterHostObject>(this).date_format.replace(date_format);
+    if let Some(old) = old {
+        release(env, old);
+    }
+}
+
+- (id)timeZone {
+    let time_zone = env.objc.borrow::<NSDateFormatterHostObject>(this).time_zone;
+    time_zone.unwrap_or_else(|| msg_class![env; NSTimeZone defaultTimeZone])
+}
+
+- (())setTimeZone:(id)time_zone { // NSTimeZone *
+    if time_zone != nil {
+        retain(env, time_zone);
+    }
+    let new = if time_zone == nil { None } else { Some(time_zone) };
+    let old = std::mem::replace(
+        &mut env.objc.borrow_mut::<NSDateFormatterHostObject>(this).time_zone,
+        new
+    );
+    if let Some(old) = old {
+        release(env, old);
+    }
 }
 
 - (id)stringFromDate:(id)date {
-    let &NSDateFormatterHostObject {
-        date_format
-    } = env.objc.borrow(this);
-    let mut format = ns_string::to_rust_string(env, date_format.unwrap()).to_string().clone();
-    log_dbg!("date_format before: {:?}", format);
+    let format = date_format_string(env, this);
+    let offset = time_zone_offset(env, this);
 
     let ti: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
-    let greg_date = CFAbsoluteTimeGetGregorianDate(env, ti, nil);
-    let year = greg_date.year;
-    let month = greg_date.month;
-    let day = greg_date.day;
-    let hour = greg_date.hours;
-    let minute = greg_date.minutes;
-    let second = greg_date.seconds;
-
-    format = format.replace("yyyy", format!("{:04}", year).as_str());
-    format = format.replace("YYYY", format!("{:04}", year).as_str());
-    format = format.replace("MM", format!("{:02}", month).as_str());
-    format = format.replace("dd", format!("{}", day).as_str());
-    format = format.replace("HH", format!("{}", hour).as_str());
-    format = format.replace("mm", format!("{:02}", minute).as_str());
-    format = format.replace("ss", format!("{:02}", second).as_str());
-
-    for c in format.chars() {
-        if let pattern @ ('A'..='Z' | 'a'..='z') = c {
-            unimplemented!("date string contains unsubstituted format pattern: {pattern}");
-        }
-    }
-    log_dbg!("date_format after: {:?}", format);
+    let timestamp = ti.floor() as i64 + SECS_FROM_UNIX_TO_APPLE_EPOCHS as i64 + offset;
+    let components = DateComponents::from_timestamp(timestamp);
+
+    let res = match date_pattern::format(&format, &components) {
+        Ok(res) => res,
+        Err(pattern) => unimplemented!("date format {:?} contains unsupported pattern: {}", format, pattern),
+    };
+    log_dbg!("[(NSDateFormatter*){:?} stringFromDate:{:?}] with format {:?} => {:?}", this, date, format, res);
 
-    let res = ns_string::from_rust_string(env, format);
+    let res = ns_string::from_rust_string(env, res);
     autorelease(env, res)
 }
 
+- (id)dateFromString:(id)string { // NSString *
+    let format = date_format_string(env, this);
+    let offset = time_zone_offset(env, this);
+
+    let string = ns_string::to_rust_string(env, string);
+    let components = match date_pattern::parse(&format, &string) {
+        Ok(Some(components)) => components,
+        Ok(None) => {
+            log_dbg!("Couldn't parse {:?} with date format {:?}, returning nil", string, format);
+            return nil;
+        }
+        Err(pattern) => unimplemented!("date format {:?} contains unsupported pattern: {}", format, pattern),
+    };
+
+    let timestamp = components.to_timestamp() - offset;
+    let ti = (timestamp - SECS_FROM_UNIX_TO_APPLE_EPOCHS as i64) as NSTimeInterval;
+    let date: id = msg_class![env; NSDate alloc];
+    let date: id = msg![env; date initWithTimeIntervalSinceReferenceDate:ti];
+    autorelease(env, date)
+}
+
 @end
 
 };
+
+fn date_format_string(env: &mut Environment, formatter: id) -> String {
+    let date_format = env
+        .objc
+        .borrow::<NSDateFormatterHostObject>(formatter)
+        .date_format;
+    // TODO: date and time styles
+    let date_format = date_format.expect("NSDateFormatter without date format");
+    ns_string::to_rust_string(env, date_format).into_owned()
+}
+
+fn time_zone_offset(env: &mut Environment, formatter: id) -> i64 {
+    let time_zone: id = msg![env; formatter timeZone];
+    ns_time_zone::seconds_from_gmt(env, time_zone).into()
+}
@@ -0,0 +1,373 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Formatting and parsing of dates with Unicode date format patterns.
+//!
+//! Only the most common pattern fields are supported, with English names for
+//! months and weekdays. This is not a replacement for ICU.
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// Gregorian calendar date and time of day, without a time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateComponents {
+    pub year: i64,
+    /// 1 to 12
+    pub month: u32,
+    /// 1 to 31
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl Default for DateComponents {
+    fn default() -> Self {
+        DateComponents {
+            year: 1970,
+            month: 1,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        }
+    }
+}
+
+impl DateComponents {
+    /// Converts seconds since the UNIX epoch into a calendar date.
+    pub fn from_timestamp(timestamp: i64) -> DateComponents {
+        let days = timestamp.div_euclid(86400);
+        let second_in_day = timestamp.rem_euclid(86400) as u32;
+
+        // Algorithm from Howard Hinnant's "chrono-Compatible Low-Level Date
+        // Algorithms" (civil_from_days).
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z.rem_euclid(146097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+        DateComponents {
+            year,
+            month,
+            day,
+            hour: second_in_day / 3600,
+            minute: (second_in_day / 60) % 60,
+            second: second_in_day % 60,
+        }
+    }
+
+    /// Converts a calendar date into seconds since the UNIX epoch.
+    pub fn to_timestamp(&self) -> i64 {
+        // Algorithm from Howard Hinnant's "chrono-Compatible Low-Level Date
+        // Algorithms" (days_from_civil).
+        let year = self.year - (self.month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let month = self.month as i64;
+        let day_of_year =
+            (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146097 + day_of_era - 719468;
+
+        days * 86400 + (self.hour * 3600 + self.minute * 60 + self.second) as i64
+    }
+
+    /// Day of the week, where 0 is Sunday.
+    pub fn weekday(&self) -> u32 {
+        // 1970-01-01 was a Thursday.
+        (self.to_timestamp().div_euclid(86400) + 4).rem_euclid(7) as u32
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    /// A pattern field, e.g. `yyyy` is `Field('y', 4)`.
+    Field(char, usize),
+    Literal(String),
+}
+
+/// Splits a pattern into fields and literal text. Returns the offending
+/// pattern character if the pattern uses an unsupported field.
+fn tokenize(pattern: &str) -> Result<Vec<Token>, char> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    let mut literal = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                // '' is a literal quote, otherwise this starts quoted text.
+                if chars.peek() == Some(&'\'') {
+                    chars.next();
+                    literal.push('\'');
+                    continue;
+                }
+                while let Some(c) = chars.next() {
+                    if c == '\'' {
+                        if chars.peek() == Some(&'\'') {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    literal.push(c);
+                }
+            }
+            'y' | 'Y' | 'M' | 'd' | 'H' | 'h' | 'm' | 's' | 'a' | 'E' => {
+                let mut count = 1;
+                while chars.peek() == Some(&c) {
+                    chars.next();
+                    count += 1;
+                }
+                if !literal.is_empty() {
+                    tokens.push(Token::Literal(std::mem::take(&mut literal)));
+                }
+                tokens.push(Token::Field(c, count));
+            }
+            'A'..='Z' | 'a'..='z' => return Err(c),
+            _ => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+    Ok(tokens)
+}
+
+/// Formats a date with a pattern. See [tokenize] for the error value.
+pub fn format(pattern: &str, date: &DateComponents) -> Result<String, char> {
+    let mut res = String::new();
+    for token in tokenize(pattern)? {
+        let (field, count) = match token {
+            Token::Literal(text) => {
+                res.push_str(&text);
+                continue;
+            }
+            Token::Field(field, count) => (field, count),
+        };
+        let numeric = |value: i64| format!("{:01$}", value, count);
+        let text = match field {
+            'y' | 'Y' if count == 2 => format!("{:02}", date.year.rem_euclid(100)),
+            'y' | 'Y' => numeric(date.year),
+            'M' => match count {
+                1 | 2 => numeric(date.month.into()),
+                3 => MONTH_NAMES[date.month as usize - 1][..3].to_string(),
+                _ => MONTH_NAMES[date.month as usize - 1].to_string(),
+            },
+            'd' => numeric(date.day.into()),
+            'H' => numeric(date.hour.into()),
+            'h' => numeric(((date.hour + 11) % 12 + 1).into()),
+            'm' => numeric(date.minute.into()),
+            's' => numeric(date.second.into()),
+            'a' => if date.hour < 12 { "AM" } else { "PM" }.to_string(),
+            'E' => {
+                let name = WEEKDAY_NAMES[date.weekday() as usize];
+                if count <= 3 {
+                    name[..3].to_string()
+                } else {
+                    name.to_string()
+                }
+            }
+            _ => unreachable!(),
+        };
+        res.push_str(&text);
+    }
+    Ok(res)
+}
+
+/// Parses a date string with a pattern. Fields not present in the pattern
+/// take their value from [DateComponents::default]. Returns `Ok(None)` if the
+/// string doesn't match the pattern. See [tokenize] for the error value.
+pub fn parse(pattern: &str, string: &str) -> Result<Option<DateComponents>, char> {
+    let tokens = tokenize(pattern)?;
+    let mut date = DateComponents::default();
+    let mut is_pm = None;
+    let mut hour_12 = None;
+    let mut rest = string;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let (field, count) = match *token {
+            Token::Literal(ref text) => {
+                let Some(new_rest) = rest.strip_prefix(text.as_str()) else {
+                    return Ok(None);
+                };
+                rest = new_rest;
+                continue;
+            }
+            Token::Field(field, count) => (field, count),
+        };
+
+        // Text fields
+        match (field, count) {
+            ('M', 3..) | ('a', _) | ('E', _) => {
+                let (names, count): (&[&str], _) = match field {
+                    'M' => (&MONTH_NAMES, count),
+                    'a' => (&["AM", "PM"], 4),
+                    _ => (&WEEKDAY_NAMES, count),
+                };
+                let found = names.iter().enumerate().find_map(|(idx, name)| {
+                    let name = if count <= 3 { &name[..3] } else { name };
+                    rest.strip_prefix(name).map(|new_rest| (idx, new_rest))
+                });
+                let Some((idx, new_rest)) = found else {
+                    return Ok(None);
+                };
+                rest = new_rest;
+                match field {
+                    'M' => date.month = idx as u32 + 1,
+                    'a' => is_pm = Some(idx == 1),
+                    _ => (), // The weekday is implied by the date.
+                }
+                continue;
+            }
+            _ => (),
+        }
+
+        // Numeric fields. If the next token is another field, the width is
+        // fixed, otherwise as many digits as are present are consumed.
+        let adjacent = matches!(tokens.get(i + 1), Some(Token::Field(..)));
+        let max_digits = if adjacent { count } else { usize::MAX };
+        let digits = rest
+            .bytes()
+            .take(max_digits)
+            .take_while(u8::is_ascii_digit)
+            .count();
+        if digits == 0 {
+            return Ok(None);
+        }
+        let value: i64 = rest[..digits].parse().unwrap();
+        rest = &rest[digits..];
+        match field {
+            'y' | 'Y' if count == 2 => date.year = 2000 + value,
+            'y' | 'Y' => date.year = value,
+            'M' => date.month = value as u32,
+            'd' => date.day = value as u32,
+            'H' => date.hour = value as u32,
+            'h' => hour_12 = Some(value as u32),
+            'm' => date.minute = value as u32,
+            's' => date.second = value as u32,
+            _ => unreachable!(),
+        }
+    }
+
+    if !rest.is_empty() {
+        return Ok(None);
+    }
+    if let Some(hour) = hour_12 {
+        date.hour = hour % 12 + if is_pm == Some(true) { 12 } else { 0 };
+    }
+    if !(1..=12).contains(&date.month)
+        || !(1..=31).contains(&date.day)
+        || date.hour > 23
+        || date.minute > 59
+        || date.second > 59
+    {
+        return Ok(None);
+    }
+    Ok(Some(date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_conversion() {
+        let date = DateComponents::from_timestamp(1117223147);
+        assert_eq!(
+            date,
+            DateComponents {
+                year: 2005,
+                month: 5,
+                day: 27,
+                hour: 19,
+                minute: 45,
+                second: 47,
+            }
+        );
+        assert_eq!(date.weekday(), 5);
+        assert_eq!(date.to_timestamp(), 1117223147);
+
+        for timestamp in [0, -466053135, 951782400, 4102444800] {
+            let date = DateComponents::from_timestamp(timestamp);
+            assert_eq!(date.to_timestamp(), timestamp);
+        }
+        assert_eq!(DateComponents::default().to_timestamp(), 0);
+    }
+
+    #[test]
+    fn test_format() {
+        let date = DateComponents::from_timestamp(1117223147);
+        assert_eq!(
+            format("yyyy-MM-dd HH:mm:ss", &date).unwrap(),
+            "2005-05-27 19:45:47"
+        );
+        assert_eq!(
+            format("EEE, d MMM yy h:mm a", &date).unwrap(),
+            "Fri, 27 May 05 7:45 PM"
+        );
+        assert_eq!(
+            format("EEEE MMMM 'o''clock' ''", &date).unwrap(),
+            "Friday May o'clock '"
+        );
+        assert_eq!(format("yyyy G", &date), Err('G'));
+    }
+
+    #[test]
+    fn test_parse() {
+        let date = DateComponents::from_timestamp(1117223147);
+        assert_eq!(
+            parse("yyyy-MM-dd HH:mm:ss", "2005-05-27 19:45:47").unwrap(),
+            Some(date)
+        );
+        assert_eq!(
+            parse("yyyyMMddHHmmss", "20050527194547").unwrap(),
+            Some(date)
+        );
+        assert_eq!(
+            parse("EEE, d MMM yy h:mm:ss a", "Fri, 27 May 05 7:45:47 PM").unwrap(),
+            Some(date)
+        );
+        assert_eq!(
+            parse("HH:mm", "12:34").unwrap(),
+            Some(DateComponents {
+                hour: 12,
+                minute: 34,
+                ..Default::default()
+            })
+        );
+        assert_eq!(parse("yyyy-MM-dd", "2005-05-27x").unwrap(), None);
+        assert_eq!(parse("yyyy-MM-dd", "2005-13-27").unwrap(), None);
+        assert_eq!(parse("yyyy-MM-dd", "2005/05/27").unwrap(), None);
+    }
+}
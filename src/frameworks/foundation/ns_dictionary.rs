@@ -6,6 +6,9 @@
 //! The `NSDictionary` class cluster, including `NSMutableDictionary`.
 
 use super::ns_array::ArrayHostObject;
+use super::ns_enumerator::{
+    fast_enumeration_helper, forget_mutations, mutations_ptr, note_mutation, NSFastEnumerationState,
+};
 use super::ns_property_list_serialization::{
     deserialize_plist_from_file, NSPropertyListBinaryFormat_v1_0,
 };
@@ -14,7 +17,7 @@ use super::{ns_array, ns_keyed_unarchiver, ns_string, ns_url, NSUInteger};
 use crate::abi::{CallFromHost, GuestFunction, VaList};
 use crate::frameworks::core_foundation::{CFHashCode, CFIndex};
 use crate::fs::GuestPath;
-use crate::mem::{ConstPtr, MutPtr, Ptr, SafeRead};
+use crate::mem::{ConstPtr, MutPtr, MutVoidPtr, Ptr, SafeRead};
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
     NSZonePtr,
@@ -40,17 +43,17 @@ pub(super) struct DictionaryHostObject {
 }
 impl HostObject for DictionaryHostObject {}
 impl DictionaryHostObject {
-    pub(super) fn lookup(&self, env: &mut Environment, key: id) -> id {
+    /// Finds the entry with a key equal to `key`, according to `-hash` and
+    /// `-isEqual:`, and returns the key as stored and the value.
+    pub(super) fn find(&self, env: &mut Environment, key: id) -> Option<(id, id)> {
         let hash: Hash = msg![env; key hash];
-        let Some(collisions) = self.map.get(&hash) else {
-            return nil;
-        };
-        for &(candidate_key, value) in collisions {
-            if candidate_key == key || msg![env; candidate_key isEqual:key] {
-                return value;
-            }
-        }
-        nil
+        let collisions = self.map.get(&hash)?;
+        collisions.iter().copied().find(|&(candidate_key, _)| {
+            candidate_key == key || msg![env; candidate_key isEqual:key]
+        })
+    }
+    pub(super) fn lookup(&self, env: &mut Environment, key: id) -> id {
+        self.find(env, key).map_or(nil, |(_key, value)| value)
     }
     pub(super) fn insert(&mut self, env: &mut Environment, key: id, value: id, copy_key: bool) {
         let key: id = if copy_key {
@@ -69,6 +72,8 @@ impl DictionaryHostObject {
         };
         for &mut (candidate_key, ref mut existing_value) in collisions.iter_mut() {
             if candidate_key == key || msg![env; candidate_key isEqual:key] {
+                // The existing key is kept.
+                release(env, key);
                 release(env, *existing_value);
                 *existing_value = value;
                 return;
@@ -77,6 +82,27 @@ impl DictionaryHostObject {
         collisions.push((key, value));
         self.count += 1;
     }
+    /// Removes the entry with a key equal to `key`, if any. Returns [true] if
+    /// there was one.
+    pub(super) fn remove(&mut self, env: &mut Environment, key: id) -> bool {
+        let hash: Hash = msg![env; key hash];
+        let Some(collisions) = self.map.get_mut(&hash) else {
+            return false;
+        };
+        let Some(idx) = collisions.iter().position(|&(candidate_key, _)| {
+            candidate_key == key || msg![env; candidate_key isEqual:key]
+        }) else {
+            return false;
+        };
+        let (existing_key, existing_value) = collisions.remove(idx);
+        if collisions.is_empty() {
+            self.map.remove(&hash);
+        }
+        self.count -= 1;
+        release(env, existing_key);
+        release(env, existing_value);
+        true
+    }
     pub(super) fn release(&mut self, env: &mut Environment) {
         for collisions in self.map.values() {
             for &(key, value) in collisions {
@@ -343,6 +369,54 @@ fn all_keys_common(env: &mut Environment, this: id) -> id {
     autorelease(env, res)
 }
 
+/// Helper function to share `allValues` implementations
+fn all_values_common(env: &mut Environment, this: id) -> id {
+    let host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
+    let values: Vec<id> = host_obj
+        .map
+        .values()
+        .flatten()
+        .map(|&(_key, value)| value)
+        .collect();
+    *env.objc.borrow_mut(this) = host_obj;
+
+    for &val in &values {
+        retain(env, val);
+    }
+    let res = ns_array::from_vec(env, values);
+    autorelease(env, res)
+}
+
+/// Helper function to share `countByEnumeratingWithState:objects:count:`
+/// implementations, which enumerate the keys.
+fn count_by_enumerating_keys(
+    env: &mut Environment,
+    this: id,
+    mutations_ptr: MutVoidPtr,
+    state: MutPtr<NSFastEnumerationState>,
+    stackbuf: MutPtr<id>,
+    len: NSUInteger,
+) -> NSUInteger {
+    // We assume that order in which keys are reported is consistent between
+    // calls!
+    let keys: id = msg![env; this allKeys];
+    let count: NSUInteger = msg![env; keys count];
+    fast_enumeration_helper(
+        env,
+        mutations_ptr,
+        |env, idx| {
+            if idx < count {
+                msg![env; keys objectAtIndex:idx]
+            } else {
+                nil
+            }
+        },
+        state,
+        stackbuf,
+        len,
+    )
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -354,7 +428,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 // - (NSEnumerator*)keyEnumerator
 // We can pick whichever subclass we want for the various alloc methods.
 // For the time being, that will always be _touchHLE_NSDictionary.
-@implementation NSDictionary: NSObject
+@implementation NSDictionary: NSObject <NSCopying, NSMutableCopying, NSFastEnumeration>
 
 + (id)allocWithZone:(NSZonePtr)zone {
     // NSDictionary might be subclassed by something which needs allocWithZone:
@@ -452,6 +526,23 @@ pub const CLASSES: ClassExports = objc_classes! {
     res
 }
 
+- (id)keyEnumerator { // NSEnumerator*
+    let keys: id = msg![env; this allKeys];
+    msg![env; keys objectEnumerator]
+}
+
+- (id)objectEnumerator { // NSEnumerator*
+    let values: id = msg![env; this allValues];
+    msg![env; values objectEnumerator]
+}
+
+// NSFastEnumeration implementation
+- (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
+                                  objects:(MutPtr<id>)stackbuf
+                                    count:(NSUInteger)len {
+    count_by_enumerating_keys(env, this, this.cast(), state, stackbuf, len)
+}
+
 // TODO
 
 - (id)valueForKey:(id)key { // NSString*
@@ -484,6 +575,14 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
+// NSFastEnumeration implementation
+- (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
+                                  objects:(MutPtr<id>)stackbuf
+                                    count:(NSUInteger)len {
+    let mutations_ptr = mutations_ptr(env, this);
+    count_by_enumerating_keys(env, this, mutations_ptr, state, stackbuf, len)
+}
+
 @end
 
 // Our private subclass that is the single implementation of NSDictionary for
@@ -495,6 +594,15 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
+// NSCoding implementation
+- (id)initWithCoder:(id)coder {
+    // See the comment in [_touchHLE_NSMutableDictionary initWithCoder:].
+    release(env, this);
+    // FIXME: What if it's not an NSKeyedUnarchiver?
+    let tuples = ns_keyed_unarchiver::decode_current_dict(env, coder);
+    dict_from_keys_and_objects(env, &tuples)
+}
+
 - (())dealloc {
     std::mem::take(env.objc.borrow_mut::<DictionaryHostObject>(this)).release(env);
 
@@ -519,7 +627,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     init_with_objects_for_keys_common(env, this, objects, keys)
 }
 
-// TODO: enumeration, more init methods, etc
+// TODO: more init methods, etc
 
 - (NSUInteger)count {
     env.objc.borrow::<DictionaryHostObject>(this).count
@@ -535,6 +643,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     all_keys_common(env, this)
 }
 
+- (id)allValues {
+    all_values_common(env, this)
+}
+
 // NSCopying implementation
 - (id)copyWithZone:(NSZonePtr)_zone {
     retain(env, this)
@@ -568,6 +680,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (())dealloc {
     std::mem::take(env.objc.borrow_mut::<DictionaryHostObject>(this)).release(env);
+    forget_mutations(env, this);
 
     env.objc.dealloc_object(this, &mut env.mem)
 }
@@ -618,7 +731,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     init_with_objects_for_keys_common(env, this, objects, keys)
 }
 
-// TODO: enumeration, more init methods, etc
+// TODO: more init methods, etc
 
 - (NSUInteger)count {
     env.objc.borrow::<DictionaryHostObject>(this).count
@@ -667,6 +780,14 @@ pub const CLASSES: ClassExports = objc_classes! {
     let mut host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
     host_obj.insert(env, key, object, /* copy_key: */ true);
     *env.objc.borrow_mut(this) = host_obj;
+    note_mutation(env, this);
+}
+
+- (())removeObjectForKey:(id)key {
+    let mut host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
+    host_obj.remove(env, key);
+    *env.objc.borrow_mut(this) = host_obj;
+    note_mutation(env, this);
 }
 
 - (())addEntriesFromDictionary:(id)other { // NSDictionary *
@@ -686,15 +807,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (id)allValues {
-    let host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
-    let values: Vec<id> = host_obj.map.values().flatten().map(|&(_key, value)| value).collect();
-    *env.objc.borrow_mut(this) = host_obj;
-
-    for &val in &values {
-        retain(env, val);
-    }
-    let res = ns_array::from_vec(env, values);
-    autorelease(env, res)
+    all_values_common(env, this)
 }
 
 @end
@@ -722,6 +835,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (())dealloc {
+    forget_mutations(env, this);
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
@@ -755,6 +869,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     let mut host_obj: CFDictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
     host_obj.insert(env, key, object);
     *env.objc.borrow_mut(this) = host_obj;
+    note_mutation(env, this);
 }
 
 - (())removeObjectForKey:(id)key {
@@ -762,6 +877,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     let mut host_obj: CFDictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
     host_obj.remove(env, key);
     *env.objc.borrow_mut(this) = host_obj;
+    note_mutation(env, this);
 }
 
 - (id)allKeys {
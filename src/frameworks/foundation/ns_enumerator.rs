@@ -12,15 +12,28 @@
 //!                                     count:(NSUInteger)len;
 //! ```
 //!
+//! The compiler-generated loop checks the value pointed to by `mutationsPtr`
+//! before each object, and calls `objc_enumerationMutation()` if it changes.
+//! Immutable collections can point it at anything that doesn't change, but
+//! mutable collections use a counter that is incremented by [note_mutation].
+//!
 //! Resources:
 //! - The GCC documentation's [Fast Enumeration Protocol section](https://gcc.gnu.org/onlinedocs/gcc/Fast-enumeration-protocol.html)
 
 use crate::mem::{MutPtr, MutVoidPtr, SafeRead};
 use crate::objc::{id, msg, nil, objc_classes, ClassExports};
 use crate::Environment;
+use std::collections::HashMap;
 
 use super::NSUInteger;
 
+#[derive(Default)]
+pub struct State {
+    /// Guest-memory mutation counters for mutable collections that have been
+    /// enumerated.
+    mutation_counters: HashMap<id, MutPtr<u32>>,
+}
+
 #[repr(C, packed)]
 pub struct NSFastEnumerationState {
     pub state: u32,
@@ -42,7 +55,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
                                   objects:(MutPtr<id>)stackbuf
                                     count:(NSUInteger)len {
-    fast_enumeration_helper(env, this, |env, _| {
+    fast_enumeration_helper(env, this.cast(), |env, _| {
         msg![env; this nextObject]
     }, state, stackbuf, len)
 }
@@ -51,9 +64,52 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// Get the `mutationsPtr` to use when enumerating a mutable collection.
+pub fn mutations_ptr(env: &mut Environment, collection: id) -> MutVoidPtr {
+    let counters = &mut env
+        .framework_state
+        .foundation
+        .ns_enumerator
+        .mutation_counters;
+    let counter = *counters
+        .entry(collection)
+        .or_insert_with(|| env.mem.alloc_and_write(0u32));
+    counter.cast()
+}
+
+/// Must be called by mutable collections whenever they are mutated, so that
+/// mutation during enumeration can be detected.
+pub fn note_mutation(env: &mut Environment, collection: id) {
+    let counters = &env
+        .framework_state
+        .foundation
+        .ns_enumerator
+        .mutation_counters;
+    if let Some(&counter) = counters.get(&collection) {
+        let count: u32 = env.mem.read(counter);
+        env.mem.write(counter, count.wrapping_add(1));
+    }
+}
+
+/// Must be called by mutable collections when they are deallocated.
+pub fn forget_mutations(env: &mut Environment, collection: id) {
+    let counters = &mut env
+        .framework_state
+        .foundation
+        .ns_enumerator
+        .mutation_counters;
+    if let Some(counter) = counters.remove(&collection) {
+        env.mem.free(counter.cast());
+    }
+}
+
+/// Implements `countByEnumeratingWithState:objects:count:` given a function
+/// that returns the object at an index, or [nil] past the end.
+/// `mutations_ptr` should be the collection itself if it's immutable, or the
+/// result of [mutations_ptr] otherwise.
 pub fn fast_enumeration_helper<F: FnMut(&mut Environment, u32) -> id>(
     env: &mut Environment,
-    this: id,
+    mutations_ptr: MutVoidPtr,
     mut iter_fn: F,
     state: MutPtr<NSFastEnumerationState>,
     stackbuf: MutPtr<id>,
@@ -78,11 +134,8 @@ pub fn fast_enumeration_helper<F: FnMut(&mut Environment, u32) -> id>(
         NSFastEnumerationState {
             state: start_index + batch_count,
             items_ptr: stackbuf,
-            // can be anything as long as it's dereferenceable and the same
-            // each iteration
-            // Note: stackbuf can be different each time, it's better to return
-            // self pointer
-            mutations_ptr: this.cast(),
+            // This must be the same each time.
+            mutations_ptr,
             extra: Default::default(),
         },
     );
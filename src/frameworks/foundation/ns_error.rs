@@ -4,16 +4,23 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+use super::{ns_dictionary, ns_string};
 use crate::dyld::{ConstantExports, HostConstant};
 use crate::frameworks::foundation::NSInteger;
-use crate::objc::{id, nil, release, retain, ClassExports, HostObject, NSZonePtr};
-use crate::objc_classes;
+use crate::mem::MutPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, release, retain, ClassExports, HostObject, NSZonePtr,
+};
+use crate::{objc_classes, Environment};
 
 /// `NSString*`
 pub type NSErrorDomain = id;
 
+pub const NSCocoaErrorDomain: &str = "NSCocoaErrorDomain";
 pub const NSOSStatusErrorDomain: &str = "NSOSStatusErrorDomain";
 
+pub const NSDebugDescriptionErrorKey: &str = "NSDebugDescription";
+
 struct ErrorHostObject {
     domain: NSErrorDomain,
     code: NSInteger,
@@ -49,6 +56,18 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (NSErrorDomain)domain {
+    env.objc.borrow::<ErrorHostObject>(this).domain
+}
+
+- (NSInteger)code {
+    env.objc.borrow::<ErrorHostObject>(this).code
+}
+
+- (id)userInfo {
+    env.objc.borrow::<ErrorHostObject>(this).user_info
+}
+
 - (())dealloc {
     let &ErrorHostObject{domain, user_info, ..} = env.objc.borrow(this);
     release(env, domain);
@@ -62,7 +81,38 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// If `error` (an `NSError**` out-parameter) isn't null, write a new
+/// (autoreleased) error in the Cocoa domain to it, with a description for
+/// debugging.
+pub fn set_cocoa_error(
+    env: &mut Environment,
+    error: MutPtr<id>,
+    code: NSInteger,
+    description: String,
+) {
+    if error.is_null() {
+        return;
+    }
+    let domain = ns_string::get_static_str(env, NSCocoaErrorDomain);
+    let key = ns_string::get_static_str(env, NSDebugDescriptionErrorKey);
+    let description = ns_string::from_rust_string(env, description);
+    let user_info = ns_dictionary::dict_from_keys_and_objects(env, &[(key, description)]);
+    release(env, description);
+    let new: id = msg_class![env; NSError alloc];
+    let new: id = msg![env; new initWithDomain:domain code:code userInfo:user_info];
+    release(env, user_info);
+    env.mem.write(error, autorelease(env, new));
+}
+
 pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSCocoaErrorDomain",
+        HostConstant::NSString(NSCocoaErrorDomain),
+    ),
+    (
+        "_NSDebugDescriptionErrorKey",
+        HostConstant::NSString(NSDebugDescriptionErrorKey),
+    ),
     (
         "_NSLocalizedDescriptionKey",
         HostConstant::NSString("NSLocalizedDescriptionKey"),
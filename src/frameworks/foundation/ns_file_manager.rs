@@ -81,11 +81,6 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(NSSearchPathForDirectoriesInDomains(_, _, _)),
 ];
 
-#[derive(Default)]
-pub struct State {
-    default_manager: Option<id>,
-}
-
 struct NSDirectoryEnumeratorHostObject {
     iterator: std::vec::IntoIter<GuestPathBuf>,
 }
@@ -98,13 +93,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation NSFileManager: NSObject
 
 + (id)defaultManager {
-    if let Some(existing) = env.framework_state.foundation.ns_file_manager.default_manager {
-        existing
-    } else {
-        let new: id = msg![env; this new];
-        env.framework_state.foundation.ns_file_manager.default_manager = Some(new);
-        new
-    }
+    env.shared_object("+[NSFileManager defaultManager]", |env| msg![env; this new])
 }
 
 - (id)currentDirectoryPath {
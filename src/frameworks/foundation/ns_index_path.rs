@@ -0,0 +1,142 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSIndexPath`.
+
+use super::{
+    NSComparisonResult, NSNotFound, NSOrderedAscending, NSOrderedDescending, NSOrderedSame,
+    NSUInteger,
+};
+use crate::mem::{ConstPtr, MutPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, retain, Class, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+use std::cmp::Ordering;
+
+struct NSIndexPathHostObject {
+    indexes: Vec<NSUInteger>,
+}
+impl HostObject for NSIndexPathHostObject {}
+
+fn new_index_path(env: &mut Environment, indexes: Vec<NSUInteger>) -> id {
+    let new: id = msg_class![env; NSIndexPath alloc];
+    env.objc.borrow_mut::<NSIndexPathHostObject>(new).indexes = indexes;
+    new
+}
+
+fn indexes(env: &Environment, index_path: id) -> &[NSUInteger] {
+    &env.objc.borrow::<NSIndexPathHostObject>(index_path).indexes
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSIndexPath: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSIndexPathHostObject { indexes: Vec::new() });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)indexPathWithIndex:(NSUInteger)index {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithIndex:index];
+    autorelease(env, new)
+}
+
++ (id)indexPathWithIndexes:(ConstPtr<NSUInteger>)indexes
+                    length:(NSUInteger)length {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithIndexes:indexes length:length];
+    autorelease(env, new)
+}
+
+// These come from a category in UIKit (UITableView.h).
++ (id)indexPathForRow:(NSUInteger)row
+            inSection:(NSUInteger)section {
+    let new = new_index_path(env, vec![section, row]);
+    autorelease(env, new)
+}
+- (NSUInteger)section {
+    indexes(env, this).first().copied().unwrap_or(NSNotFound as NSUInteger)
+}
+- (NSUInteger)row {
+    indexes(env, this).get(1).copied().unwrap_or(NSNotFound as NSUInteger)
+}
+
+- (id)initWithIndex:(NSUInteger)index {
+    env.objc.borrow_mut::<NSIndexPathHostObject>(this).indexes = vec![index];
+    this
+}
+
+- (id)initWithIndexes:(ConstPtr<NSUInteger>)indexes
+               length:(NSUInteger)length {
+    let indexes = (0..length).map(|i| env.mem.read(indexes + i)).collect();
+    env.objc.borrow_mut::<NSIndexPathHostObject>(this).indexes = indexes;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+- (NSUInteger)length {
+    indexes(env, this).len() as NSUInteger
+}
+
+- (NSUInteger)indexAtPosition:(NSUInteger)position {
+    let index = indexes(env, this).get(position as usize).copied();
+    index.unwrap_or(NSNotFound as NSUInteger)
+}
+
+- (())getIndexes:(MutPtr<NSUInteger>)buffer {
+    let indexes = indexes(env, this).to_vec();
+    for (i, index) in indexes.into_iter().enumerate() {
+        env.mem.write(buffer + i as NSUInteger, index);
+    }
+}
+
+- (id)indexPathByAddingIndex:(NSUInteger)index {
+    let mut indexes = indexes(env, this).to_vec();
+    indexes.push(index);
+    let new = new_index_path(env, indexes);
+    autorelease(env, new)
+}
+
+- (id)indexPathByRemovingLastIndex {
+    let mut indexes = indexes(env, this).to_vec();
+    indexes.pop();
+    let new = new_index_path(env, indexes);
+    autorelease(env, new)
+}
+
+- (NSComparisonResult)compare:(id)other { // NSIndexPath*
+    match indexes(env, this).cmp(indexes(env, other)) {
+        Ordering::Less => NSOrderedAscending,
+        Ordering::Equal => NSOrderedSame,
+        Ordering::Greater => NSOrderedDescending,
+    }
+}
+
+- (NSUInteger)hash {
+    super::hash_helper(&indexes(env, this))
+}
+- (bool)isEqual:(id)other {
+    if this == other {
+        return true;
+    }
+    let class: Class = msg_class![env; NSIndexPath class];
+    other != nil
+        && msg![env; other isKindOfClass:class]
+        && indexes(env, this) == indexes(env, other)
+}
+
+@end
+
+};
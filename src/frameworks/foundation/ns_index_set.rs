@@ -0,0 +1,366 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSIndexSet` and `NSMutableIndexSet`.
+
+use super::{NSNotFound, NSRange, NSUInteger};
+use crate::abi::CallFromHost;
+use crate::libc::blocks::{block_invoke, Block_literal};
+use crate::mem::{ConstPtr, MutPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, retain, Class, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+use std::ops::Range;
+
+/// A set of indexes, stored as a sorted list of ranges that neither overlap
+/// nor touch, so that large contiguous sets are cheap.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+struct IndexRanges(Vec<Range<NSUInteger>>);
+
+impl IndexRanges {
+    fn count(&self) -> NSUInteger {
+        self.0.iter().map(|range| range.end - range.start).sum()
+    }
+
+    fn first(&self) -> Option<NSUInteger> {
+        self.0.first().map(|range| range.start)
+    }
+
+    fn last(&self) -> Option<NSUInteger> {
+        self.0.last().map(|range| range.end - 1)
+    }
+
+    /// Whether all of `range` is in the set. An empty range is never
+    /// contained.
+    fn contains_range(&self, range: Range<NSUInteger>) -> bool {
+        if range.is_empty() {
+            return false;
+        }
+        let i = self.0.partition_point(|r| r.end <= range.start);
+        self.0
+            .get(i)
+            .is_some_and(|r| r.start <= range.start && range.end <= r.end)
+    }
+
+    fn contains(&self, index: NSUInteger) -> bool {
+        index
+            .checked_add(1)
+            .is_some_and(|end| self.contains_range(index..end))
+    }
+
+    /// The smallest index in the set that is greater than or equal to `index`.
+    fn first_at_or_after(&self, index: NSUInteger) -> Option<NSUInteger> {
+        let i = self.0.partition_point(|r| r.end <= index);
+        self.0.get(i).map(|r| r.start.max(index))
+    }
+
+    /// The largest index in the set that is less than or equal to `index`.
+    fn last_at_or_before(&self, index: NSUInteger) -> Option<NSUInteger> {
+        let i = self.0.partition_point(|r| r.start <= index);
+        let r = self.0.get(i.checked_sub(1)?)?;
+        Some((r.end - 1).min(index))
+    }
+
+    fn add(&mut self, range: Range<NSUInteger>) {
+        if range.is_empty() {
+            return;
+        }
+        // Ranges that overlap or touch the new one get merged into it.
+        let i = self.0.partition_point(|r| r.end < range.start);
+        let j = self.0.partition_point(|r| r.start <= range.end);
+        let mut merged = range;
+        if i < j {
+            merged.start = merged.start.min(self.0[i].start);
+            merged.end = merged.end.max(self.0[j - 1].end);
+        }
+        self.0.splice(i..j, [merged]);
+    }
+
+    fn remove(&mut self, range: Range<NSUInteger>) {
+        if range.is_empty() {
+            return;
+        }
+        let i = self.0.partition_point(|r| r.end <= range.start);
+        let j = self.0.partition_point(|r| r.start < range.end);
+        if i >= j {
+            return;
+        }
+        // Only the ranges at either end can be partially removed.
+        let before = self.0[i].start..range.start;
+        let after = range.end..self.0[j - 1].end;
+        let kept = [before, after].into_iter().filter(|r| !r.is_empty());
+        self.0.splice(i..j, kept);
+    }
+
+    fn iter(&self) -> impl Iterator<Item = NSUInteger> + '_ {
+        self.0.iter().flat_map(|range| range.clone())
+    }
+}
+
+fn range_from_ns_range(range: NSRange) -> Range<NSUInteger> {
+    let NSRange { location, length } = range;
+    location..location.saturating_add(length)
+}
+
+fn index_or_not_found(index: Option<NSUInteger>) -> NSUInteger {
+    index.unwrap_or(NSNotFound as NSUInteger)
+}
+
+struct NSIndexSetHostObject {
+    ranges: IndexRanges,
+}
+impl HostObject for NSIndexSetHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSIndexSet: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSIndexSetHostObject {
+        ranges: IndexRanges::default(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)indexSet {
+    let new: id = msg![env; this new];
+    autorelease(env, new)
+}
+
++ (id)indexSetWithIndex:(NSUInteger)index {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithIndex:index];
+    autorelease(env, new)
+}
+
++ (id)indexSetWithIndexesInRange:(NSRange)range {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithIndexesInRange:range];
+    autorelease(env, new)
+}
+
+- (id)initWithIndex:(NSUInteger)index {
+    msg![env; this initWithIndexesInRange:(NSRange { location: index, length: 1 })]
+}
+
+- (id)initWithIndexesInRange:(NSRange)range {
+    let range = range_from_ns_range(range);
+    env.objc.borrow_mut::<NSIndexSetHostObject>(this).ranges.add(range);
+    this
+}
+
+- (id)initWithIndexSet:(id)other { // NSIndexSet*
+    let ranges = env.objc.borrow::<NSIndexSetHostObject>(other).ranges.clone();
+    env.objc.borrow_mut::<NSIndexSetHostObject>(this).ranges = ranges;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(NSZonePtr)_zone {
+    let new: id = msg_class![env; NSMutableIndexSet alloc];
+    msg![env; new initWithIndexSet:this]
+}
+
+- (NSUInteger)count {
+    env.objc.borrow::<NSIndexSetHostObject>(this).ranges.count()
+}
+
+- (NSUInteger)firstIndex {
+    index_or_not_found(env.objc.borrow::<NSIndexSetHostObject>(this).ranges.first())
+}
+- (NSUInteger)lastIndex {
+    index_or_not_found(env.objc.borrow::<NSIndexSetHostObject>(this).ranges.last())
+}
+
+- (NSUInteger)indexGreaterThanIndex:(NSUInteger)index {
+    let ranges = &env.objc.borrow::<NSIndexSetHostObject>(this).ranges;
+    index_or_not_found(index.checked_add(1).and_then(|i| ranges.first_at_or_after(i)))
+}
+- (NSUInteger)indexGreaterThanOrEqualToIndex:(NSUInteger)index {
+    let ranges = &env.objc.borrow::<NSIndexSetHostObject>(this).ranges;
+    index_or_not_found(ranges.first_at_or_after(index))
+}
+- (NSUInteger)indexLessThanIndex:(NSUInteger)index {
+    let ranges = &env.objc.borrow::<NSIndexSetHostObject>(this).ranges;
+    index_or_not_found(index.checked_sub(1).and_then(|i| ranges.last_at_or_before(i)))
+}
+- (NSUInteger)indexLessThanOrEqualToIndex:(NSUInteger)index {
+    let ranges = &env.objc.borrow::<NSIndexSetHostObject>(this).ranges;
+    index_or_not_found(ranges.last_at_or_before(index))
+}
+
+- (bool)containsIndex:(NSUInteger)index {
+    env.objc.borrow::<NSIndexSetHostObject>(this).ranges.contains(index)
+}
+- (bool)containsIndexesInRange:(NSRange)range {
+    let range = range_from_ns_range(range);
+    env.objc.borrow::<NSIndexSetHostObject>(this).ranges.contains_range(range)
+}
+- (bool)containsIndexes:(id)other { // NSIndexSet*
+    let other = &env.objc.borrow::<NSIndexSetHostObject>(other).ranges;
+    let ranges = &env.objc.borrow::<NSIndexSetHostObject>(this).ranges;
+    other.0.iter().all(|range| ranges.contains_range(range.clone()))
+}
+
+- (NSUInteger)hash {
+    super::hash_helper(&env.objc.borrow::<NSIndexSetHostObject>(this).ranges)
+}
+- (bool)isEqual:(id)other {
+    if this == other {
+        return true;
+    }
+    let class: Class = msg_class![env; NSIndexSet class];
+    other != nil && msg![env; other isKindOfClass:class] && msg![env; this isEqualToIndexSet:other]
+}
+- (bool)isEqualToIndexSet:(id)other { // NSIndexSet*
+    other != nil && env.objc.borrow::<NSIndexSetHostObject>(this).ranges
+        == env.objc.borrow::<NSIndexSetHostObject>(other).ranges
+}
+
+- (NSUInteger)getIndexes:(MutPtr<NSUInteger>)buffer
+                maxCount:(NSUInteger)max_count
+            inIndexRange:(MutPtr<NSRange>)range { // NSRangePointer
+    let limit = if range.is_null() {
+        0..NSUInteger::MAX
+    } else {
+        range_from_ns_range(env.mem.read(range))
+    };
+    let indexes: Vec<NSUInteger> = env.objc.borrow::<NSIndexSetHostObject>(this).ranges
+        .iter()
+        .skip_while(|&i| i < limit.start)
+        .take_while(|&i| i < limit.end)
+        .take(max_count as usize)
+        .collect();
+    for (i, &index) in indexes.iter().enumerate() {
+        env.mem.write(buffer + i as NSUInteger, index);
+    }
+    if !range.is_null() {
+        // The range is updated to cover the indexes not yet copied.
+        let start = indexes.last().map_or(limit.end, |&last| last + 1);
+        env.mem.write(range, NSRange { location: start, length: limit.end - start });
+    }
+    indexes.len() as NSUInteger
+}
+
+- (())enumerateIndexesUsingBlock:(ConstPtr<Block_literal>)block {
+    // Copied so that the block can't invalidate the iterator.
+    let ranges = env.objc.borrow::<NSIndexSetHostObject>(this).ranges.clone();
+    let stop: MutPtr<bool> = env.mem.alloc(1).cast();
+    env.mem.write(stop, false);
+    for index in ranges.iter() {
+        let invoke = block_invoke(env, block);
+        () = invoke.call_from_host(env, (block, index, stop));
+        if env.mem.read(stop) {
+            break;
+        }
+    }
+    env.mem.free(stop.cast());
+}
+
+@end
+
+@implementation NSMutableIndexSet: NSIndexSet
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    let new: id = msg_class![env; NSIndexSet alloc];
+    msg![env; new initWithIndexSet:this]
+}
+
+- (())addIndex:(NSUInteger)index {
+    msg![env; this addIndexesInRange:(NSRange { location: index, length: 1 })]
+}
+- (())addIndexesInRange:(NSRange)range {
+    let range = range_from_ns_range(range);
+    env.objc.borrow_mut::<NSIndexSetHostObject>(this).ranges.add(range);
+}
+- (())addIndexes:(id)other { // NSIndexSet*
+    let other = env.objc.borrow::<NSIndexSetHostObject>(other).ranges.clone();
+    let ranges = &mut env.objc.borrow_mut::<NSIndexSetHostObject>(this).ranges;
+    for range in other.0 {
+        ranges.add(range);
+    }
+}
+
+- (())removeIndex:(NSUInteger)index {
+    msg![env; this removeIndexesInRange:(NSRange { location: index, length: 1 })]
+}
+- (())removeIndexesInRange:(NSRange)range {
+    let range = range_from_ns_range(range);
+    env.objc.borrow_mut::<NSIndexSetHostObject>(this).ranges.remove(range);
+}
+- (())removeIndexes:(id)other { // NSIndexSet*
+    let other = env.objc.borrow::<NSIndexSetHostObject>(other).ranges.clone();
+    let ranges = &mut env.objc.borrow_mut::<NSIndexSetHostObject>(this).ranges;
+    for range in other.0 {
+        ranges.remove(range);
+    }
+}
+- (())removeAllIndexes {
+    env.objc.borrow_mut::<NSIndexSetHostObject>(this).ranges = IndexRanges::default();
+}
+
+@end
+
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_ranges() {
+        let mut set = IndexRanges::default();
+        assert_eq!((set.count(), set.first(), set.last()), (0, None, None));
+
+        set.add(5..8);
+        set.add(1..2);
+        set.add(10..12);
+        assert_eq!(set.0, [1..2, 5..8, 10..12]);
+        // Touching ranges are merged.
+        set.add(8..10);
+        assert_eq!(set.0, [1..2, 5..12]);
+        set.add(0..6);
+        assert_eq!(set.0, [0..12]);
+        set.add(20..21);
+        assert_eq!(set.iter().collect::<Vec<_>>().len(), 13);
+        assert_eq!(
+            (set.count(), set.first(), set.last()),
+            (13, Some(0), Some(20))
+        );
+
+        set.remove(3..5);
+        set.remove(11..25);
+        assert_eq!(set.0, [0..3, 5..11]);
+        set.remove(2..6);
+        assert_eq!(set.0, [0..2, 6..11]);
+        set.remove(100..200);
+        assert_eq!(set.0, [0..2, 6..11]);
+
+        assert!(set.contains(0) && set.contains(1) && set.contains(6));
+        assert!(!set.contains(2) && !set.contains(11) && !set.contains(NSUInteger::MAX));
+        assert!(set.contains_range(7..11));
+        assert!(!set.contains_range(1..7));
+        assert!(!set.contains_range(7..7));
+
+        assert_eq!(set.first_at_or_after(2), Some(6));
+        assert_eq!(set.first_at_or_after(7), Some(7));
+        assert_eq!(set.first_at_or_after(11), None);
+        assert_eq!(set.last_at_or_before(5), Some(1));
+        assert_eq!(set.last_at_or_before(20), Some(10));
+        assert_eq!(set.last_at_or_before(0), Some(0));
+        set.remove(0..1);
+        assert_eq!(set.last_at_or_before(0), None);
+    }
+}
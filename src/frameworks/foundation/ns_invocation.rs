@@ -0,0 +1,223 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSInvocation` and message forwarding.
+//!
+//! Resources:
+//! - Apple's [Objective-C Runtime Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ObjCRuntimeGuide/Articles/ocrtForwarding.html)
+
+use super::ns_method_signature::{argument_layout, return_layout};
+use super::NSInteger;
+use crate::abi::GuestArg;
+use crate::cpu::Cpu;
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutVoidPtr, Ptr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, msg_send, msg_send_with_frame, nil, objc_classes, release,
+    retain, ClassExports, HostObject, ObjC, SEL,
+};
+use crate::Environment;
+
+struct NSInvocationHostObject {
+    /// `NSMethodSignature*`
+    signature: id,
+    /// The arguments, including the target and selector, laid out as they
+    /// would be in registers and on the stack.
+    frame: Vec<u8>,
+    /// Byte offset into `frame` and size of each argument.
+    arguments: Vec<(GuestUSize, GuestUSize)>,
+    return_value: Vec<u8>,
+    return_in_memory: bool,
+}
+impl HostObject for NSInvocationHostObject {}
+
+impl NSInvocationHostObject {
+    fn argument(&mut self, index: NSInteger) -> &mut [u8] {
+        // TODO: Raise NSInvalidArgumentException
+        let &(offset, size) = self
+            .arguments
+            .get(usize::try_from(index).unwrap())
+            .unwrap_or_else(|| panic!("Invocation argument index {} out of range", index));
+        &mut self.frame[offset as usize..][..size as usize]
+    }
+}
+
+/// Called by [crate::objc] when a message is sent to an object that doesn't
+/// respond to it. If the object provides a method signature for the selector
+/// with `-methodSignatureForSelector:`, the message is packaged up as an
+/// `NSInvocation` and passed to `-forwardInvocation:`, and the return value
+/// set on the invocation is returned to the caller.
+///
+/// Returns [false] if the message couldn't be forwarded.
+pub fn forward_message(env: &mut Environment, receiver: id, selector: SEL) -> bool {
+    // The arguments must be captured before any other messages are sent.
+    let regs = env.cpu.regs();
+    let arg_regs: [u32; 4] = regs[0..4].try_into().unwrap();
+    let stack_ptr: ConstPtr<u32> = Ptr::from_bits(regs[Cpu::SP]);
+
+    let (Some(sig_sel), Some(forward_sel)) = (
+        env.objc.lookup_selector("methodSignatureForSelector:"),
+        env.objc.lookup_selector("forwardInvocation:"),
+    ) else {
+        return false;
+    };
+    let class = ObjC::read_isa(receiver, &env.mem);
+    if selector == sig_sel
+        || selector == forward_sel
+        || !env.objc.class_has_method(class, sig_sel)
+        || !env.objc.class_has_method(class, forward_sel)
+    {
+        return false;
+    }
+
+    let signature: id = msg_send(env, (receiver, sig_sel, selector));
+    if signature == nil {
+        return false;
+    }
+    log_dbg!(
+        "Forwarding message {} sent to {:?}",
+        selector.as_str(&env.mem),
+        receiver
+    );
+
+    let invocation: id = msg_class![env; NSInvocation invocationWithMethodSignature:signature];
+    let host_object = env.objc.borrow_mut::<NSInvocationHostObject>(invocation);
+    // When a struct is returned via a pointer, that pointer is passed in r0
+    // and all the other arguments come one word later.
+    let first_word = usize::from(host_object.return_in_memory);
+    for (i, word) in host_object.frame.chunks_exact_mut(4).enumerate() {
+        let idx = first_word + i;
+        let value = if idx < 4 {
+            arg_regs[idx]
+        } else {
+            env.mem.read(stack_ptr + (idx - 4).try_into().unwrap())
+        };
+        word.copy_from_slice(&value.to_le_bytes());
+    }
+
+    () = msg_send(env, (receiver, forward_sel, invocation));
+
+    let host_object = env.objc.borrow::<NSInvocationHostObject>(invocation);
+    let return_value = &host_object.return_value;
+    if host_object.return_in_memory {
+        let ptr = Ptr::from_bits(arg_regs[0]);
+        env.mem
+            .bytes_at_mut(ptr, return_value.len().try_into().unwrap())
+            .copy_from_slice(return_value);
+    } else {
+        let mut bytes = [0u8; 8];
+        bytes[..return_value.len()].copy_from_slice(return_value);
+        let regs = env.cpu.regs_mut();
+        regs[0] = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+        regs[1] = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    }
+    true
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSInvocation: NSObject
+
++ (id)invocationWithMethodSignature:(id)signature { // NSMethodSignature*
+    assert_ne!(signature, nil);
+    let (arguments, frame_size) = argument_layout(env, signature);
+    let (return_size, return_in_memory) = return_layout(env, signature);
+    // TODO: Support returning scalars larger than 8 bytes (long double?)
+    assert!(return_in_memory || return_size <= 8);
+
+    retain(env, signature);
+    let host_object = Box::new(NSInvocationHostObject {
+        signature,
+        frame: vec![0; frame_size as usize],
+        arguments,
+        return_value: vec![0; return_size as usize],
+        return_in_memory,
+    });
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let signature = env.objc.borrow::<NSInvocationHostObject>(this).signature;
+    release(env, signature);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)methodSignature {
+    env.objc.borrow::<NSInvocationHostObject>(this).signature
+}
+
+- (id)target {
+    let host_object = env.objc.borrow_mut::<NSInvocationHostObject>(this);
+    GuestArg::from_regs(&[u32::from_le_bytes(host_object.argument(0).try_into().unwrap())])
+}
+- (())setTarget:(id)target {
+    let host_object = env.objc.borrow_mut::<NSInvocationHostObject>(this);
+    host_object.argument(0).copy_from_slice(&target.to_bits().to_le_bytes());
+}
+
+- (SEL)selector {
+    let host_object = env.objc.borrow_mut::<NSInvocationHostObject>(this);
+    GuestArg::from_regs(&[u32::from_le_bytes(host_object.argument(1).try_into().unwrap())])
+}
+- (())setSelector:(SEL)selector {
+    let mut word = [0];
+    selector.to_regs(&mut word);
+    let host_object = env.objc.borrow_mut::<NSInvocationHostObject>(this);
+    host_object.argument(1).copy_from_slice(&word[0].to_le_bytes());
+}
+
+- (())getArgument:(MutVoidPtr)buffer
+          atIndex:(NSInteger)index {
+    let host_object = env.objc.borrow_mut::<NSInvocationHostObject>(this);
+    let argument = host_object.argument(index);
+    env.mem
+        .bytes_at_mut(buffer.cast(), argument.len().try_into().unwrap())
+        .copy_from_slice(argument);
+}
+- (())setArgument:(ConstVoidPtr)buffer
+          atIndex:(NSInteger)index {
+    let host_object = env.objc.borrow_mut::<NSInvocationHostObject>(this);
+    let argument = host_object.argument(index);
+    argument.copy_from_slice(env.mem.bytes_at(buffer.cast(), argument.len().try_into().unwrap()));
+}
+
+- (())getReturnValue:(MutVoidPtr)buffer {
+    let return_value = &env.objc.borrow::<NSInvocationHostObject>(this).return_value;
+    env.mem
+        .bytes_at_mut(buffer.cast(), return_value.len().try_into().unwrap())
+        .copy_from_slice(return_value);
+}
+- (())setReturnValue:(ConstVoidPtr)buffer {
+    let return_value = &mut env.objc.borrow_mut::<NSInvocationHostObject>(this).return_value;
+    let size = return_value.len().try_into().unwrap();
+    return_value.copy_from_slice(env.mem.bytes_at(buffer.cast(), size));
+}
+
+- (())invoke {
+    let host_object = env.objc.borrow::<NSInvocationHostObject>(this);
+    let frame: Vec<u32> = host_object
+        .frame
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    let stret_size = host_object
+        .return_in_memory
+        .then_some(host_object.return_value.len().try_into().unwrap());
+    let return_size = host_object.return_value.len();
+
+    let result = msg_send_with_frame(env, &frame, stret_size);
+    env.objc.borrow_mut::<NSInvocationHostObject>(this).return_value = result[..return_size].to_vec();
+}
+
+- (())invokeWithTarget:(id)target {
+    () = msg![env; this setTarget:target];
+    () = msg![env; this invoke];
+}
+
+@end
+
+};
@@ -0,0 +1,246 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSJSONSerialization`.
+//!
+//! This is an iOS 5 API, but some apps check for it at runtime, and it's handy
+//! for apps that bundle their own code targeting both old and new iOS versions.
+
+mod json;
+
+use super::ns_error::set_cocoa_error;
+use super::ns_value::NSNumberHostObject;
+use super::{ns_array, ns_data, ns_dictionary, ns_string, NSInteger, NSUInteger};
+use crate::mem::{MutPtr, MutVoidPtr};
+use crate::objc::{autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports};
+use crate::Environment;
+use json::JsonValue;
+
+pub type NSJSONReadingOptions = NSUInteger;
+pub const NSJSONReadingMutableContainers: NSJSONReadingOptions = 1 << 0;
+pub const NSJSONReadingMutableLeaves: NSJSONReadingOptions = 1 << 1;
+pub const NSJSONReadingAllowFragments: NSJSONReadingOptions = 1 << 2;
+
+pub type NSJSONWritingOptions = NSUInteger;
+pub const NSJSONWritingPrettyPrinted: NSJSONWritingOptions = 1 << 0;
+pub const NSJSONWritingFragmentsAllowed: NSJSONWritingOptions = 1 << 2;
+
+/// `NSPropertyListReadCorruptError`, which is also used for JSON.
+const NSPropertyListReadCorruptError: NSInteger = 3840;
+/// `NSPropertyListWriteInvalidError`, which is also used for JSON.
+const NSPropertyListWriteInvalidError: NSInteger = 3852;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSJSONSerialization: NSObject
+
++ (id)JSONObjectWithData:(id)data // NSData *
+                 options:(NSJSONReadingOptions)options
+                   error:(MutPtr<id>)error { // NSError **
+    assert!(data != nil); // TODO: raise NSInvalidArgumentException
+    let value = match json::parse(ns_data::to_rust_slice(env, data)) {
+        Ok(value) => value,
+        Err(e) => {
+            log_dbg!("JSONObjectWithData: parse error {:?}", e);
+            let description = format!(
+                "{} around character {}.",
+                e.message.trim_end_matches('.'),
+                e.offset,
+            );
+            set_cocoa_error(env, error, NSPropertyListReadCorruptError, description);
+            return nil;
+        }
+    };
+
+    if options & NSJSONReadingAllowFragments == 0
+        && !matches!(value, JsonValue::Array(_) | JsonValue::Object(_)) {
+        set_cocoa_error(
+            env,
+            error,
+            NSPropertyListReadCorruptError,
+            "JSON text did not start with array or object and option to allow \
+             fragments not set.".to_string(),
+        );
+        return nil;
+    }
+
+    let object = json_to_object(env, &value, options);
+    autorelease(env, object)
+}
+
++ (id)dataWithJSONObject:(id)object
+                 options:(NSJSONWritingOptions)options
+                   error:(MutPtr<id>)error { // NSError **
+    let top_level_ok = options & NSJSONWritingFragmentsAllowed != 0
+        || is_top_level_container(env, object);
+    let value = if top_level_ok { object_to_json(env, object) } else { None };
+    let text = value.and_then(|value| {
+        json::serialize(&value, options & NSJSONWritingPrettyPrinted != 0)
+    });
+    let Some(text) = text else {
+        set_cocoa_error(
+            env,
+            error,
+            NSPropertyListWriteInvalidError,
+            "Invalid object for JSON serialization.".to_string(),
+        );
+        return nil;
+    };
+
+    let len: NSUInteger = text.len().try_into().unwrap();
+    let ptr: MutVoidPtr = env.mem.alloc(len);
+    env.mem.bytes_at_mut(ptr.cast(), len).copy_from_slice(text.as_bytes());
+    msg_class![env; NSData dataWithBytesNoCopy:ptr length:len]
+}
+
++ (bool)isValidJSONObject:(id)object {
+    is_top_level_container(env, object) && object_to_json(env, object).is_some()
+}
+
+@end
+
+};
+
+fn is_kind_of(env: &mut Environment, object: id, class_name: &str) -> bool {
+    let class = env.objc.get_known_class(class_name, &mut env.mem);
+    msg![env; object isKindOfClass:class]
+}
+
+fn is_top_level_container(env: &mut Environment, object: id) -> bool {
+    object != nil && (is_kind_of(env, object, "NSArray") || is_kind_of(env, object, "NSDictionary"))
+}
+
+/// Create a new guest object tree (retained) from a parsed JSON value.
+fn json_to_object(env: &mut Environment, value: &JsonValue, options: NSJSONReadingOptions) -> id {
+    let mutable_containers = options & NSJSONReadingMutableContainers != 0;
+    match value {
+        JsonValue::Null => msg_class![env; NSNull null],
+        JsonValue::Bool(b) => {
+            let b: bool = *b;
+            let number: id = msg_class![env; NSNumber alloc];
+            msg![env; number initWithBool:b]
+        }
+        JsonValue::Integer(int) => {
+            let number: id = msg_class![env; NSNumber alloc];
+            if let Ok(int) = i32::try_from(*int) {
+                msg![env; number initWithInt:int]
+            } else {
+                let longlong: i64 = *int;
+                msg![env; number initWithLongLong:longlong]
+            }
+        }
+        JsonValue::Real(real) => {
+            let double: f64 = *real;
+            let number: id = msg_class![env; NSNumber alloc];
+            msg![env; number initWithDouble:double]
+        }
+        JsonValue::String(s) => {
+            let string = ns_string::from_rust_string(env, s.clone());
+            if options & NSJSONReadingMutableLeaves != 0 {
+                let mutable: id = msg![env; string mutableCopy];
+                release(env, string);
+                mutable
+            } else {
+                string
+            }
+        }
+        JsonValue::Array(items) => {
+            let objects: Vec<id> = items
+                .iter()
+                .map(|item| json_to_object(env, item, options))
+                .collect();
+            if mutable_containers {
+                let array: id = msg_class![env; NSMutableArray new];
+                for object in objects {
+                    () = msg![env; array addObject:object];
+                    release(env, object);
+                }
+                array
+            } else {
+                // The objects are already retained by the Vec.
+                ns_array::from_vec(env, objects)
+            }
+        }
+        JsonValue::Object(members) => {
+            let pairs: Vec<(id, id)> = members
+                .iter()
+                .map(|(key, value)| {
+                    (
+                        ns_string::from_rust_string(env, key.clone()),
+                        json_to_object(env, value, options),
+                    )
+                })
+                .collect();
+            let dict = if mutable_containers {
+                let dict: id = msg_class![env; NSMutableDictionary new];
+                for &(key, value) in &pairs {
+                    () = msg![env; dict setObject:value forKey:key];
+                }
+                dict
+            } else {
+                ns_dictionary::dict_from_keys_and_objects(env, &pairs)
+            };
+            // Both of the above retain the keys and values.
+            for (key, value) in pairs {
+                release(env, key);
+                release(env, value);
+            }
+            dict
+        }
+    }
+}
+
+/// Convert a guest object tree to a JSON value. Returns [None] if there's an
+/// object that can't be represented in JSON.
+fn object_to_json(env: &mut Environment, object: id) -> Option<JsonValue> {
+    if object == nil {
+        None
+    } else if is_kind_of(env, object, "NSNull") {
+        Some(JsonValue::Null)
+    } else if is_kind_of(env, object, "NSString") {
+        Some(JsonValue::String(
+            ns_string::to_rust_string(env, object).into_owned(),
+        ))
+    } else if is_kind_of(env, object, "NSNumber") {
+        Some(match *env.objc.borrow::<NSNumberHostObject>(object) {
+            NSNumberHostObject::Bool(b) => JsonValue::Bool(b),
+            NSNumberHostObject::Int(i) => JsonValue::Integer(i.into()),
+            NSNumberHostObject::LongLong(ll) => JsonValue::Integer(ll),
+            NSNumberHostObject::UnsignedLongLong(ull) => match i64::try_from(ull) {
+                Ok(ll) => JsonValue::Integer(ll),
+                Err(_) => JsonValue::Real(ull as f64),
+            },
+            NSNumberHostObject::Float(f) => JsonValue::Real(f.into()),
+            NSNumberHostObject::Double(d) => JsonValue::Real(d),
+        })
+    } else if is_kind_of(env, object, "NSArray") {
+        let count: NSUInteger = msg![env; object count];
+        let mut items = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let item: id = msg![env; object objectAtIndex:i];
+            items.push(object_to_json(env, item)?);
+        }
+        Some(JsonValue::Array(items))
+    } else if is_kind_of(env, object, "NSDictionary") {
+        let keys: id = msg![env; object allKeys];
+        let count: NSUInteger = msg![env; keys count];
+        let mut members = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let key: id = msg![env; keys objectAtIndex:i];
+            // Only string keys are allowed in JSON.
+            if !is_kind_of(env, key, "NSString") {
+                return None;
+            }
+            let value: id = msg![env; object objectForKey:key];
+            let key = ns_string::to_rust_string(env, key).into_owned();
+            members.push((key, object_to_json(env, value)?));
+        }
+        Some(JsonValue::Object(members))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,462 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! A small JSON parser and serializer used by `NSJSONSerialization`.
+//!
+//! This works on plain Rust values, so that the guest object conversion in the
+//! parent module can be kept separate from the text format handling.
+//!
+//! Resources:
+//! - [RFC 8259](https://www.rfc-editor.org/rfc/rfc8259)
+
+use std::fmt::Write;
+
+/// Maximum nesting depth, to avoid overflowing the host stack on malicious or
+/// broken input.
+const MAX_DEPTH: usize = 512;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Real(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// Key order is preserved, though `NSDictionary` won't preserve it.
+    Object(Vec<(String, JsonValue)>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    /// Byte offset in the input where the problem was found.
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+/// Parse a complete JSON text (UTF-8). Trailing non-whitespace is an error.
+pub fn parse(input: &[u8]) -> Result<JsonValue, ParseError> {
+    let mut parser = Parser { input, pos: 0 };
+    // Skip UTF-8 BOM, if any.
+    if parser.input.starts_with(b"\xEF\xBB\xBF") {
+        parser.pos = 3;
+    }
+    parser.skip_whitespace();
+    let value = parser.parse_value(0)?;
+    parser.skip_whitespace();
+    if parser.pos != input.len() {
+        return Err(parser.error("Garbage at end."));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &'static str) -> ParseError {
+        ParseError {
+            offset: self.pos,
+            message,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &'static [u8]) -> Result<(), ParseError> {
+        if self.input[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error("Invalid value."))
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<JsonValue, ParseError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("Too many nested arrays or dictionaries."));
+        }
+        match self.peek() {
+            None => Err(self.error("Unexpected end of file.")),
+            Some(b'n') => self.expect_literal(b"null").map(|_| JsonValue::Null),
+            Some(b't') => self.expect_literal(b"true").map(|_| JsonValue::Bool(true)),
+            Some(b'f') => self
+                .expect_literal(b"false")
+                .map(|_| JsonValue::Bool(false)),
+            Some(b'"') => self.parse_string().map(JsonValue::String),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(b'[') => self.parse_array(depth),
+            Some(b'{') => self.parse_object(depth),
+            Some(_) => Err(self.error("Invalid value.")),
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<JsonValue, ParseError> {
+        self.pos += 1; // [
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.parse_value(depth + 1)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                _ => return Err(self.error("Badly formed array.")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<JsonValue, ParseError> {
+        self.pos += 1; // {
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("No string key for value in object."));
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            if self.peek() != Some(b':') {
+                return Err(self.error("No value for key in object."));
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let value = self.parse_value(depth + 1)?;
+            members.push((key, value));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                _ => return Err(self.error("Badly formed object.")),
+            }
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u16, ParseError> {
+        let digits = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u16::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("Invalid escape sequence."))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.pos += 1; // "
+        let mut bytes = Vec::new();
+        loop {
+            let Some(byte) = self.peek() else {
+                return Err(self.error("Unterminated string."));
+            };
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(escape) = self.peek() else {
+                        return Err(self.error("Unterminated string."));
+                    };
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let first = self.parse_hex4()?;
+                            let code_units = if (0xD800..0xDC00).contains(&first) {
+                                // Surrogate pair, the second half must follow.
+                                if !self.input[self.pos..].starts_with(b"\\u") {
+                                    return Err(
+                                        self.error("Missing low code point in surrogate pair.")
+                                    );
+                                }
+                                self.pos += 2;
+                                vec![first, self.parse_hex4()?]
+                            } else {
+                                vec![first]
+                            };
+                            char::decode_utf16(code_units)
+                                .next()
+                                .unwrap()
+                                .map_err(|_| self.error("Invalid unicode escape sequence."))?
+                        }
+                        _ => return Err(self.error("Invalid escape sequence.")),
+                    };
+                    let mut buf = [0u8; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                0x00..=0x1F => return Err(self.error("Unescaped control character.")),
+                _ => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("Invalid UTF-8 in string."))
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, ParseError> {
+        let start = self.pos;
+        let mut is_integer = true;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => {
+                while let Some(b'0'..=b'9') = self.peek() {
+                    self.pos += 1;
+                }
+            }
+            _ => return Err(self.error("Invalid number.")),
+        }
+        if self.peek() == Some(b'.') {
+            is_integer = false;
+            self.pos += 1;
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.error("Invalid number."));
+            }
+            while let Some(b'0'..=b'9') = self.peek() {
+                self.pos += 1;
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            is_integer = false;
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.error("Invalid number."));
+            }
+            while let Some(b'0'..=b'9') = self.peek() {
+                self.pos += 1;
+            }
+        }
+        // The slice only contains ASCII digits and signs at this point.
+        let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+        if is_integer {
+            if let Ok(int) = text.parse() {
+                return Ok(JsonValue::Integer(int));
+            }
+            // Too big for an integer, fall back to a real.
+        }
+        Ok(JsonValue::Real(text.parse().unwrap()))
+    }
+}
+
+/// Serialize a JSON value. `pretty` selects the same layout as
+/// `NSJSONWritingPrettyPrinted`. Returns [None] if the value contains a number
+/// that can't be represented in JSON (infinity or NaN).
+pub fn serialize(value: &JsonValue, pretty: bool) -> Option<String> {
+    let mut out = String::new();
+    serialize_inner(value, pretty, 0, &mut out)?;
+    Some(out)
+}
+
+fn write_indent(out: &mut String, pretty: bool, level: usize) {
+    if pretty {
+        out.push('\n');
+        for _ in 0..level {
+            out.push_str("  ");
+        }
+    }
+}
+
+fn serialize_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            // Apple's implementation escapes this too.
+            '/' => out.push_str("\\/"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\u{0}'..='\u{1f}' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn serialize_inner(value: &JsonValue, pretty: bool, level: usize, out: &mut String) -> Option<()> {
+    match value {
+        JsonValue::Null => out.push_str("null"),
+        JsonValue::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        JsonValue::Integer(i) => write!(out, "{}", i).unwrap(),
+        JsonValue::Real(r) => {
+            if !r.is_finite() {
+                return None;
+            }
+            write!(out, "{}", r).unwrap()
+        }
+        JsonValue::String(s) => serialize_string(s, out),
+        JsonValue::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                write_indent(out, pretty, level + 1);
+                serialize_inner(item, pretty, level + 1, out)?;
+            }
+            if !items.is_empty() {
+                write_indent(out, pretty, level);
+            }
+            out.push(']');
+        }
+        JsonValue::Object(members) => {
+            out.push('{');
+            for (i, (key, value)) in members.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                write_indent(out, pretty, level + 1);
+                serialize_string(key, out);
+                out.push_str(if pretty { " : " } else { ":" });
+                serialize_inner(value, pretty, level + 1, out)?;
+            }
+            if !members.is_empty() {
+                write_indent(out, pretty, level);
+            }
+            out.push('}');
+        }
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nested() {
+        let text = br#" {"a": [1, -2.5, true, null, "x\ny"], "b": {"c": 1e3}} "#;
+        assert_eq!(
+            parse(text),
+            Ok(JsonValue::Object(vec![
+                (
+                    "a".to_string(),
+                    JsonValue::Array(vec![
+                        JsonValue::Integer(1),
+                        JsonValue::Real(-2.5),
+                        JsonValue::Bool(true),
+                        JsonValue::Null,
+                        JsonValue::String("x\ny".to_string()),
+                    ])
+                ),
+                (
+                    "b".to_string(),
+                    JsonValue::Object(vec![("c".to_string(), JsonValue::Real(1000.0))])
+                ),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_strings() {
+        assert_eq!(
+            parse(r#""é\ud83d\ude00😀\/""#.as_bytes()),
+            Ok(JsonValue::String("é😀😀/".to_string()))
+        );
+        assert!(parse(br#""\ud83d""#).is_err());
+        assert!(parse(b"\"a\nb\"").is_err());
+        assert!(parse(br#""abc"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_numbers() {
+        assert_eq!(parse(b"0"), Ok(JsonValue::Integer(0)));
+        assert_eq!(parse(b"-12"), Ok(JsonValue::Integer(-12)));
+        assert_eq!(parse(b"0.5"), Ok(JsonValue::Real(0.5)));
+        assert_eq!(
+            parse(b"18446744073709551616"),
+            Ok(JsonValue::Real(18446744073709551616.0))
+        );
+        assert!(parse(b"01").is_err());
+        assert!(parse(b"1.").is_err());
+        assert!(parse(b"-").is_err());
+        assert!(parse(b"1e").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(b"").is_err());
+        assert!(parse(b"[1,]").is_err());
+        assert!(parse(b"[1 2]").is_err());
+        assert!(parse(b"{\"a\" 1}").is_err());
+        assert!(parse(b"{1: 2}").is_err());
+        assert!(parse(b"nul").is_err());
+        assert_eq!(
+            parse(b"[] x"),
+            Err(ParseError {
+                offset: 3,
+                message: "Garbage at end."
+            })
+        );
+        let deep = "[".repeat(MAX_DEPTH + 2);
+        assert!(parse(deep.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = br#"{"list":[1,2.5,"a\/b",false,null],"empty":{},"none":[]}"#;
+        let value = parse(text).unwrap();
+        assert_eq!(
+            serialize(&value, false).unwrap().as_bytes(),
+            text.as_slice()
+        );
+        assert_eq!(
+            parse(serialize(&value, true).unwrap().as_bytes()),
+            Ok(value)
+        );
+    }
+
+    #[test]
+    fn test_serialize_pretty() {
+        let value = JsonValue::Object(vec![(
+            "a".to_string(),
+            JsonValue::Array(vec![JsonValue::Integer(1), JsonValue::Null]),
+        )]);
+        assert_eq!(
+            serialize(&value, true).unwrap(),
+            "{\n  \"a\" : [\n    1,\n    null\n  ]\n}"
+        );
+        assert_eq!(serialize(&JsonValue::Real(f64::NAN), false), None);
+    }
+}
@@ -259,6 +259,9 @@ fn unarchive_key(env: &mut Environment, unarchiver: id, key: Uid) -> id {
 
     let item = &objects[key.get() as usize];
     let new_object = match item {
+        // The first object in the archive is always this placeholder string,
+        // and references to it represent nil.
+        Value::String(s) if key.get() == 0 && s == "$null" => nil,
         // The most general kind of item: a dictionary that contains the info
         // needed to invoke `initWithCoder:` on a class implementing NSCoding.
         Value::Dictionary(dict) => {
@@ -316,6 +319,20 @@ fn unarchive_key(env: &mut Environment, unarchiver: id, key: Uid) -> id {
                 unreachable!(); // according to plist crate docs
             }
         }
+        Value::Real(real) => {
+            let double: f64 = *real;
+            let number: id = msg_class![env; NSNumber alloc];
+            msg![env; number initWithDouble:double]
+        }
+        Value::Boolean(boolean) => {
+            let boolean: bool = *boolean;
+            let number: id = msg_class![env; NSNumber alloc];
+            msg![env; number initWithBool:boolean]
+        }
+        Value::Data(bytes) => {
+            let bytes = bytes.clone();
+            data_from_bytes(env, bytes, /* is_mutable: */ false)
+        }
         _ => unimplemented!("Unarchive: {:#?}", item),
     };
 
@@ -381,14 +398,39 @@ pub fn decode_current_data(env: &mut Environment, unarchiver: id, is_mutable: bo
         .as_data()
         .unwrap()
         .to_vec();
+    data_from_bytes(env, bytes, is_mutable)
+}
+
+/// Shortcut for use by `[_touchHLE_NSString initWithCoder:]` and
+/// `[_touchHLE_NSMutableString initWithCoder:]`.
+///
+/// Strings that aren't stored inline in `$objects` (most commonly mutable
+/// ones) keep their contents in either `NS.string` or `NS.bytes`.
+pub fn decode_current_string(env: &mut Environment, unarchiver: id) -> String {
+    let key = get_static_str(env, "NS.string");
+    if let Some(value) = get_value_to_decode_for_key(env, unarchiver, key) {
+        return value.as_string().unwrap().to_string();
+    }
+    let key = get_static_str(env, "NS.bytes");
+    let bytes = get_value_to_decode_for_key(env, unarchiver, key)
+        .unwrap()
+        .as_data()
+        .unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+fn data_from_bytes(env: &mut Environment, bytes: Vec<u8>, is_mutable: bool) -> id {
     let len: GuestUSize = bytes.len().try_into().unwrap();
     let guest_bytes: MutVoidPtr = env.mem.alloc(len);
     env.mem
         .bytes_at_mut(guest_bytes.cast(), len)
         .copy_from_slice(bytes.as_slice());
 
-    assert!(is_mutable); // TODO
-    let data: id = msg_class![env; NSMutableData alloc];
+    let data: id = if is_mutable {
+        msg_class![env; NSMutableData alloc]
+    } else {
+        msg_class![env; NSData alloc]
+    };
     msg![env; data initWithBytesNoCopy:guest_bytes length:len freeWhenDone:true]
 }
 
@@ -7,18 +7,44 @@
 
 use super::{ns_array, ns_string};
 use crate::dyld::{ConstantExports, HostConstant};
-use crate::frameworks::core_foundation::cf_locale::kCFLocaleCountryCode;
-use crate::objc::{id, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr};
+use crate::frameworks::core_foundation::cf_locale::{
+    kCFLocaleCountryCode, kCFLocaleDecimalSeparator, kCFLocaleLanguageCode,
+};
+use crate::objc::{
+    autorelease, id, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+};
 use crate::options::Options;
 use crate::Environment;
 use std::ffi::CStr;
 
 const NSLocaleCountryCode: &str = "NSLocaleCountryCode";
+const NSLocaleLanguageCode: &str = "NSLocaleLanguageCode";
+const NSLocaleDecimalSeparator: &str = "NSLocaleDecimalSeparator";
+const NSLocaleGroupingSeparator: &str = "NSLocaleGroupingSeparator";
+const NSLocaleCurrencySymbol: &str = "NSLocaleCurrencySymbol";
 
-pub const CONSTANTS: ConstantExports = &[(
-    "_NSLocaleCountryCode",
-    HostConstant::NSString(NSLocaleCountryCode),
-)];
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSLocaleCountryCode",
+        HostConstant::NSString(NSLocaleCountryCode),
+    ),
+    (
+        "_NSLocaleLanguageCode",
+        HostConstant::NSString(NSLocaleLanguageCode),
+    ),
+    (
+        "_NSLocaleDecimalSeparator",
+        HostConstant::NSString(NSLocaleDecimalSeparator),
+    ),
+    (
+        "_NSLocaleGroupingSeparator",
+        HostConstant::NSString(NSLocaleGroupingSeparator),
+    ),
+    (
+        "_NSLocaleCurrencySymbol",
+        HostConstant::NSString(NSLocaleCurrencySymbol),
+    ),
+];
 
 #[derive(Default)]
 pub struct State {
@@ -39,6 +65,10 @@ fn get_preferred_languages(options: &Options) -> Vec<String> {
         log!("The app requested your preferred languages. {:?} will reported based on your --preferred-languages= option.", preferred_languages);
         return preferred_languages.clone();
     }
+    if let Some((ref language, _)) = options.locale {
+        log!("The app requested your preferred languages. {:?} will be reported based on your --locale= option.", language);
+        return vec![language.clone()];
+    }
 
     // Unfortunately Rust-SDL2 doesn't provide a wrapper for this yet.
     let languages = unsafe {
@@ -109,7 +139,74 @@ fn get_preferred_countries() -> Vec<String> {
     }
 }
 
+/// Language code and optional country code of the current locale.
+fn get_current_locale(options: &Options) -> (String, Option<String>) {
+    if let Some(ref locale) = options.locale {
+        log!("The app requested your current locale. {:?} will be reported based on your --locale= option.", locale);
+        return locale.clone();
+    }
+    let language = get_preferred_languages(options).swap_remove(0);
+    let country = get_preferred_countries().swap_remove(0);
+    (language, Some(country))
+}
+
+/// Regional differences (e.g. Swiss German uses a point, unlike German
+/// elsewhere) are not taken into account.
+pub(super) fn decimal_separator_for_language(language: &str) -> &'static str {
+    match language {
+        "bg" | "ca" | "cs" | "da" | "de" | "el" | "es" | "fi" | "fr" | "hr" | "hu" | "id"
+        | "it" | "nb" | "nl" | "no" | "pl" | "pt" | "ro" | "ru" | "sk" | "sl" | "sv" | "tr"
+        | "uk" | "vi" => ",",
+        _ => ".",
+    }
+}
+
+/// Like [decimal_separator_for_language]. Languages that group digits with a
+/// space use a non-breaking space, like Apple's implementation does.
+pub(super) fn grouping_separator_for_language(language: &str) -> &'static str {
+    match language {
+        "ca" | "da" | "de" | "el" | "es" | "hr" | "id" | "it" | "nl" | "pt" | "ro" | "sl"
+        | "tr" | "vi" => ".",
+        "bg" | "cs" | "fi" | "fr" | "hu" | "nb" | "no" | "pl" | "ru" | "sk" | "sv" | "uk" => {
+            "\u{a0}"
+        }
+        _ => ",",
+    }
+}
+
+/// Only covers common currencies. Others get the generic currency sign.
+pub(super) fn currency_symbol_for_country(country: &str) -> &'static str {
+    match country {
+        "US" | "CA" | "AU" | "NZ" | "MX" => "$",
+        "GB" => "£",
+        "JP" | "CN" => "¥",
+        "KR" => "₩",
+        "IN" => "₹",
+        "RU" => "₽",
+        "AT" | "BE" | "CY" | "DE" | "EE" | "ES" | "FI" | "FR" | "GR" | "HR" | "IE" | "IT"
+        | "LT" | "LU" | "LV" | "MT" | "NL" | "PT" | "SI" | "SK" => "€",
+        _ => "¤",
+    }
+}
+
+/// The language and country codes of an `NSLocale`, if it has them.
+pub(super) fn language_and_country(
+    env: &mut Environment,
+    locale: id,
+) -> (Option<String>, Option<String>) {
+    let &NSLocaleHostObject {
+        language_code,
+        country_code,
+    } = env.objc.borrow(locale);
+    let to_string = |env: &mut Environment, code: id| {
+        (code != nil).then(|| ns_string::to_rust_string(env, code).into_owned())
+    };
+    (to_string(env, language_code), to_string(env, country_code))
+}
+
 struct NSLocaleHostObject {
+    /// `NSString*`
+    language_code: id,
     /// `NSString*`
     country_code: id,
 }
@@ -121,6 +218,14 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation NSLocale: NSObject
 
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = NSLocaleHostObject {
+        language_code: nil,
+        country_code: nil,
+    };
+    env.objc.alloc_object(this, Box::new(host_object), &mut env.mem)
+}
+
 // The documentation isn't clear about what the format of the strings should be,
 // but Super Monkey Ball does `isEqualToString:` against "fr", "es", "de", "it"
 // and "ja", and its locale detection works properly, so presumably they do not
@@ -141,10 +246,15 @@ pub const CLASSES: ClassExports = objc_classes! {
     if let Some(locale) = State::get(env).current_locale {
         locale
     } else {
-        let countries = get_preferred_countries();
-        let country_code = ns_string::from_rust_string(env, countries[0].clone());
+        let (language, country) = get_current_locale(&env.options);
+        let language_code = ns_string::from_rust_string(env, language);
+        let country_code = match country {
+            Some(country) => ns_string::from_rust_string(env, country),
+            None => nil,
+        };
         let host_object = NSLocaleHostObject {
-            country_code
+            language_code,
+            country_code,
         };
         let new_locale = env.objc.alloc_object(
             this,
@@ -162,6 +272,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     } else {
         let host_object = NSLocaleHostObject {
             // Was confirmed on the iOS Simulator
+            language_code: nil,
             country_code: nil,
         };
         let new_locale = env.objc.alloc_object(
@@ -174,11 +285,34 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
-// TODO: constructors, more accessors
+// Only the language and country parts of the identifier are used.
+- (id)initWithLocaleIdentifier:(id)identifier { // NSString*
+    let identifier = ns_string::to_rust_string(env, identifier).into_owned();
+    let mut parts = identifier.split(['_', '-']);
+    let language_code = match parts.next() {
+        Some(language) if !language.is_empty() => {
+            ns_string::from_rust_string(env, language.to_string())
+        }
+        _ => nil,
+    };
+    let country_code = match parts.next() {
+        Some(country) if !country.is_empty() => {
+            ns_string::from_rust_string(env, country.to_string())
+        }
+        _ => nil,
+    };
+    let host_object = env.objc.borrow_mut::<NSLocaleHostObject>(this);
+    host_object.language_code = language_code;
+    host_object.country_code = country_code;
+    this
+}
+
+// TODO: more constructors, more accessors
 
 - (())dealloc {
-    let host_obj = env.objc.borrow::<NSLocaleHostObject>(this);
-    release(env, host_obj.country_code);
+    let &NSLocaleHostObject { language_code, country_code } = env.objc.borrow(this);
+    release(env, language_code);
+    release(env, country_code);
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
@@ -194,11 +328,46 @@ pub const CLASSES: ClassExports = objc_classes! {
         // But it does work on the iOS Simulator
         // TODO: Define NSLocaleCountryCode _as_ kCFLocaleCountryCode
         NSLocaleCountryCode | kCFLocaleCountryCode => {
-            let &NSLocaleHostObject { country_code } = env.objc.borrow(this);
-            country_code
+            env.objc.borrow::<NSLocaleHostObject>(this).country_code
+        },
+        NSLocaleLanguageCode | kCFLocaleLanguageCode => {
+            env.objc.borrow::<NSLocaleHostObject>(this).language_code
+        },
+        NSLocaleDecimalSeparator | kCFLocaleDecimalSeparator => {
+            let language_code = env.objc.borrow::<NSLocaleHostObject>(this).language_code;
+            let separator = if language_code == nil {
+                "."
+            } else {
+                decimal_separator_for_language(&ns_string::to_rust_string(env, language_code))
+            };
+            ns_string::get_static_str(env, separator)
+        },
+        NSLocaleGroupingSeparator => {
+            let (language, _) = language_and_country(env, this);
+            let separator = language.map_or(",", |language| grouping_separator_for_language(&language));
+            ns_string::get_static_str(env, separator)
+        },
+        NSLocaleCurrencySymbol => {
+            let (_, country) = language_and_country(env, this);
+            let symbol = country.map_or("¤", |country| currency_symbol_for_country(&country));
+            ns_string::get_static_str(env, symbol)
         },
-        _ => unimplemented!()
+        _ => unimplemented!("NSLocale key {:?}", key_str)
+    }
+}
+
+- (id)localeIdentifier {
+    let &NSLocaleHostObject { language_code, country_code } = env.objc.borrow(this);
+    let mut identifier = String::new();
+    if language_code != nil {
+        identifier.push_str(&ns_string::to_rust_string(env, language_code));
+    }
+    if country_code != nil {
+        identifier.push('_');
+        identifier.push_str(&ns_string::to_rust_string(env, country_code));
     }
+    let identifier = ns_string::from_rust_string(env, identifier);
+    autorelease(env, identifier)
 }
 
 @end
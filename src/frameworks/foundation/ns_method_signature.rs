@@ -0,0 +1,130 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSMethodSignature`.
+
+mod type_encoding;
+
+use super::NSUInteger;
+use crate::mem::{ConstPtr, GuestUSize, MutPtr};
+use crate::objc::{autorelease, id, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+use type_encoding::{parse_method_types, TypeInfo};
+
+struct NSMethodSignatureHostObject {
+    return_type: TypeInfo,
+    /// Includes the receiver and the selector.
+    arguments: Vec<TypeInfo>,
+    /// Guest copies of the type encodings for the return type and each
+    /// argument, in that order.
+    encoding_cstrs: Vec<MutPtr<u8>>,
+}
+impl HostObject for NSMethodSignatureHostObject {}
+
+/// Rounds a size up to a whole number of 4-byte words, which is how arguments
+/// are passed in registers and on the stack.
+fn frame_size(size: GuestUSize) -> GuestUSize {
+    size.max(1).next_multiple_of(4)
+}
+
+/// Describes where the arguments of a method go in its argument frame: the
+/// byte offset and size of each argument, and the total frame size.
+pub fn argument_layout(
+    env: &Environment,
+    signature: id,
+) -> (Vec<(GuestUSize, GuestUSize)>, GuestUSize) {
+    let host_object = env.objc.borrow::<NSMethodSignatureHostObject>(signature);
+    let mut offset = 0;
+    let layout = host_object
+        .arguments
+        .iter()
+        .map(|arg| {
+            let arg_offset = offset;
+            offset += frame_size(arg.size);
+            (arg_offset, arg.size)
+        })
+        .collect();
+    (layout, offset)
+}
+
+/// Returns the size of the return value, and whether it's returned in memory
+/// via a pointer rather than in registers.
+pub fn return_layout(env: &Environment, signature: id) -> (GuestUSize, bool) {
+    let TypeInfo {
+        size, is_aggregate, ..
+    } = env
+        .objc
+        .borrow::<NSMethodSignatureHostObject>(signature)
+        .return_type;
+    (size, is_aggregate && size > 4)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSMethodSignature: NSObject
+
++ (id)signatureWithObjCTypes:(ConstPtr<u8>)types {
+    let types = env.mem.cstr_at_utf8(types).unwrap().to_string();
+    let Some((return_type, arguments)) = parse_method_types(&types) else {
+        // TODO: Raise NSInvalidArgumentException
+        panic!("Unsupported or invalid method type encoding {:?}", types);
+    };
+    assert!(arguments.len() >= 2, "Signature {:?} lacks self and _cmd", types);
+
+    let encoding_cstrs = std::iter::once(&return_type)
+        .chain(arguments.iter())
+        .map(|info| env.mem.alloc_and_write_cstr(info.encoding.as_bytes()))
+        .collect();
+    let host_object = Box::new(NSMethodSignatureHostObject {
+        return_type,
+        arguments,
+        encoding_cstrs,
+    });
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let cstrs = std::mem::take(
+        &mut env.objc.borrow_mut::<NSMethodSignatureHostObject>(this).encoding_cstrs
+    );
+    for cstr in cstrs {
+        env.mem.free(cstr.cast());
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (NSUInteger)numberOfArguments {
+    env.objc.borrow::<NSMethodSignatureHostObject>(this).arguments.len().try_into().unwrap()
+}
+
+- (ConstPtr<u8>)getArgumentTypeAtIndex:(NSUInteger)index {
+    let host_object = env.objc.borrow::<NSMethodSignatureHostObject>(this);
+    // TODO: Raise NSInvalidArgumentException
+    assert!((index as usize) < host_object.arguments.len());
+    host_object.encoding_cstrs[index as usize + 1].cast_const()
+}
+
+- (ConstPtr<u8>)methodReturnType {
+    env.objc.borrow::<NSMethodSignatureHostObject>(this).encoding_cstrs[0].cast_const()
+}
+
+- (NSUInteger)methodReturnLength {
+    env.objc.borrow::<NSMethodSignatureHostObject>(this).return_type.size
+}
+
+- (NSUInteger)frameLength {
+    argument_layout(env, this).1
+}
+
+- (bool)isOneway {
+    env.objc.borrow::<NSMethodSignatureHostObject>(this).return_type.encoding.starts_with('V')
+}
+
+@end
+
+};
@@ -0,0 +1,167 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Parsing of Objective-C type encodings, as used in method signatures.
+//!
+//! Sizes and alignments follow the iPhone OS ARM ABI, where 64-bit types only
+//! need 4-byte alignment.
+//!
+//! Resources:
+//! - Apple's [Type Encodings](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ObjCRuntimeGuide/Articles/ocrtTypeEncodings.html)
+
+/// Information about one type in a type encoding string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeInfo {
+    /// The encoding of this type, including any qualifiers (e.g. `r*`) but
+    /// excluding any frame offset.
+    pub encoding: String,
+    pub size: u32,
+    pub align: u32,
+    /// Whether this is a struct, union or array.
+    pub is_aggregate: bool,
+}
+
+fn skip_qualifiers(s: &str) -> &str {
+    s.trim_start_matches(['r', 'n', 'N', 'o', 'O', 'R', 'V'])
+}
+
+fn skip_digits(s: &str) -> &str {
+    s.trim_start_matches(|c: char| c.is_ascii_digit())
+}
+
+/// Skips a quoted name, as found in encodings of object types and fields.
+fn skip_quoted_name(s: &str) -> Option<&str> {
+    match s.strip_prefix('"') {
+        Some(rest) => Some(&rest[rest.find('"')? + 1..]),
+        None => Some(s),
+    }
+}
+
+/// Parses the type at the start of `s`, returning its size, alignment,
+/// whether it's an aggregate, and the rest of the string.
+fn parse_type(s: &str) -> Option<(u32, u32, bool, &str)> {
+    let s = skip_qualifiers(s);
+    let rest = s.get(1..)?;
+    let scalar = |size: u32| Some((size, size.min(4), false, rest));
+    match s.as_bytes()[0] {
+        b'c' | b'C' | b'B' => scalar(1),
+        b's' | b'S' => scalar(2),
+        b'i' | b'I' | b'l' | b'L' | b'f' | b'*' | b'#' | b':' | b'?' => scalar(4),
+        b'q' | b'Q' | b'd' => scalar(8),
+        b'v' => Some((0, 1, false, rest)),
+        b'@' => {
+            // Blocks are `@?`, and object types may name their class.
+            let rest = rest.strip_prefix('?').unwrap_or(rest);
+            Some((4, 4, false, skip_quoted_name(rest)?))
+        }
+        b'^' => {
+            let (_, _, _, rest) = parse_type(rest)?;
+            Some((4, 4, false, rest))
+        }
+        b'[' => {
+            let count: u32 = rest[..rest.len() - skip_digits(rest).len()].parse().ok()?;
+            let (size, align, _, rest) = parse_type(skip_digits(rest))?;
+            let rest = rest.strip_prefix(']')?;
+            Some((size * count, align, true, rest))
+        }
+        open @ (b'{' | b'(') => {
+            let close = if open == b'{' { '}' } else { ')' };
+            let is_union = open == b'(';
+            let name_end = rest.find(['=', close])?;
+            let mut rest = &rest[name_end..];
+            let (mut size, mut align) = (0, 1);
+            if let Some(members) = rest.strip_prefix('=') {
+                rest = members;
+                while !rest.starts_with(close) {
+                    let (member_size, member_align, _, new_rest) =
+                        parse_type(skip_quoted_name(rest)?)?;
+                    rest = new_rest;
+                    align = align.max(member_align);
+                    if is_union {
+                        size = size.max(member_size);
+                    } else {
+                        size = size.next_multiple_of(member_align) + member_size;
+                    }
+                }
+            }
+            let rest = rest.strip_prefix(close)?;
+            Some((size.next_multiple_of(align), align, true, rest))
+        }
+        _ => None,
+    }
+}
+
+/// Parses a method's type encoding (e.g. `v12@0:4i8`) into the return type
+/// and the argument types, which include the receiver and selector.
+pub fn parse_method_types(types: &str) -> Option<(TypeInfo, Vec<TypeInfo>)> {
+    let mut infos = Vec::new();
+    let mut rest = types;
+    while !rest.is_empty() {
+        let (size, align, is_aggregate, new_rest) = parse_type(rest)?;
+        let encoding = rest[..rest.len() - new_rest.len()].to_string();
+        // Frame offsets may be negative in some old encodings.
+        let new_rest = new_rest.strip_prefix('-').unwrap_or(new_rest);
+        rest = skip_digits(new_rest);
+        infos.push(TypeInfo {
+            encoding,
+            size,
+            align,
+            is_aggregate,
+        });
+    }
+    if infos.is_empty() {
+        return None;
+    }
+    let return_type = infos.remove(0);
+    Some((return_type, infos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sizes(types: &str) -> Option<(u32, Vec<u32>)> {
+        let (ret, args) = parse_method_types(types)?;
+        Some((ret.size, args.iter().map(|arg| arg.size).collect()))
+    }
+
+    #[test]
+    fn test_scalars_and_objects() {
+        assert_eq!(sizes("v@:"), Some((0, vec![4, 4])));
+        assert_eq!(sizes("v12@0:4i8"), Some((0, vec![4, 4, 4])));
+        assert_eq!(sizes("@@:@\"NSString\"@?"), Some((4, vec![4, 4, 4, 4])));
+        assert_eq!(
+            sizes("d@:qcS^{Foo=ii}r*"),
+            Some((8, vec![4, 4, 8, 1, 2, 4, 4]))
+        );
+
+        let (ret, args) = parse_method_types("Vv16@0:4r*8@-4").unwrap();
+        assert_eq!(ret.encoding, "Vv");
+        assert_eq!(args[2].encoding, "r*");
+        assert_eq!(args[3].encoding, "@");
+        assert!(!args[2].is_aggregate);
+    }
+
+    #[test]
+    fn test_aggregates() {
+        let (ret, args) =
+            parse_method_types("{CGRect={CGPoint=ff}{CGSize=ff}}@:{?=cd}(u=cs)[3s]{Opaque}")
+                .unwrap();
+        assert_eq!((ret.size, ret.align, ret.is_aggregate), (16, 4, true));
+        assert_eq!((args[2].size, args[2].align), (12, 4));
+        assert_eq!((args[3].size, args[3].align), (2, 2));
+        assert_eq!((args[4].size, args[4].align), (6, 2));
+        assert_eq!(args[5].size, 0);
+        assert_eq!(sizes("v@:{Named=\"x\"f\"y\"c}"), Some((0, vec![4, 4, 8])));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(parse_method_types(""), None);
+        assert_eq!(parse_method_types("{Foo=i"), None);
+        assert_eq!(parse_method_types("[3"), None);
+        assert_eq!(parse_method_types("v@:x"), None);
+    }
+}
@@ -15,11 +15,6 @@ use crate::objc::{
 use std::borrow::Cow;
 use std::collections::HashMap;
 
-#[derive(Default)]
-pub struct State {
-    default_center: Option<id>,
-}
-
 #[derive(Clone)]
 struct Observer {
     observer: id,
@@ -46,13 +41,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 + (id)defaultCenter {
-    if let Some(c) = env.framework_state.foundation.ns_notification_center.default_center {
-        c
-    } else {
-        let new: id = msg![env; this new];
-        env.framework_state.foundation.ns_notification_center.default_center = Some(new);
-        new
-    }
+    env.shared_object("+[NSNotificationCenter defaultCenter]", |env| msg![env; this new])
 }
 
 - (())dealloc {
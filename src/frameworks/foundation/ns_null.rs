@@ -5,7 +5,9 @@
  */
 //! `NSNull`.
 
-use crate::objc::{id, objc_classes, ClassExports, TrivialHostObject};
+use super::ns_string::get_static_str;
+use super::NSUInteger;
+use crate::objc::{id, msg, objc_classes, ClassExports, NSZonePtr, TrivialHostObject};
 
 #[derive(Default)]
 pub struct State {
@@ -34,9 +36,29 @@ pub const CLASSES: ClassExports = objc_classes! {
    }
 }
 
+// Allocating a "new" NSNull just gives you the singleton again, and since
+// init is inherited from NSObject, [[NSNull alloc] init] works as expected.
++ (id)allocWithZone:(NSZonePtr)_zone {
+    msg![env; this null]
+}
+
+// The singleton is never deallocated, so reference counting is a no-op.
 - (id)retain { this }
 - (())release {}
 - (id)autorelease { this }
+- (NSUInteger)retainCount { NSUInteger::MAX }
+- (())dealloc {
+    panic!("NSNull singleton should never be deallocated");
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    this
+}
+
+- (id)description {
+    get_static_str(env, "<null>")
+}
 
 @end
 
@@ -0,0 +1,352 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSNumberFormatter`.
+//!
+//! Only the "no style", decimal and currency styles are supported. The only
+//! locale data used is the decimal separator, grouping separator and currency
+//! symbol from [super::ns_locale], so numbers are always grouped in threes
+//! and negative numbers always use a leading minus sign.
+
+use super::ns_locale::{
+    currency_symbol_for_country, decimal_separator_for_language, grouping_separator_for_language,
+    language_and_country,
+};
+use super::{ns_string, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+type NSNumberFormatterStyle = NSUInteger;
+const NSNumberFormatterNoStyle: NSNumberFormatterStyle = 0;
+const NSNumberFormatterDecimalStyle: NSNumberFormatterStyle = 1;
+const NSNumberFormatterCurrencyStyle: NSNumberFormatterStyle = 2;
+
+/// How numbers are formatted, apart from the locale.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct NumberFormat {
+    is_currency: bool,
+    uses_grouping_separator: bool,
+    minimum_fraction_digits: u32,
+    maximum_fraction_digits: u32,
+}
+impl NumberFormat {
+    /// The defaults for a style, which `setNumberStyle:` resets to.
+    fn for_style(style: NSNumberFormatterStyle) -> NumberFormat {
+        match style {
+            NSNumberFormatterNoStyle => NumberFormat {
+                is_currency: false,
+                uses_grouping_separator: false,
+                minimum_fraction_digits: 0,
+                maximum_fraction_digits: 0,
+            },
+            NSNumberFormatterDecimalStyle => NumberFormat {
+                is_currency: false,
+                uses_grouping_separator: true,
+                minimum_fraction_digits: 0,
+                maximum_fraction_digits: 3,
+            },
+            NSNumberFormatterCurrencyStyle => NumberFormat {
+                is_currency: true,
+                uses_grouping_separator: true,
+                minimum_fraction_digits: 2,
+                maximum_fraction_digits: 2,
+            },
+            _ => unimplemented!("NSNumberFormatterStyle {}", style),
+        }
+    }
+}
+
+/// The locale-specific parts of a formatted number.
+#[derive(Debug)]
+struct NumberSymbols {
+    decimal_separator: &'static str,
+    grouping_separator: &'static str,
+    currency_symbol: &'static str,
+    /// Whether the currency symbol goes before the number (`$1.00`) rather
+    /// than after it (`1,00 €`).
+    currency_symbol_is_prefix: bool,
+}
+impl NumberSymbols {
+    fn for_locale(language: Option<&str>, country: Option<&str>) -> NumberSymbols {
+        NumberSymbols {
+            decimal_separator: language.map_or(".", decimal_separator_for_language),
+            grouping_separator: language.map_or(",", grouping_separator_for_language),
+            currency_symbol: country.map_or("¤", currency_symbol_for_country),
+            currency_symbol_is_prefix: matches!(language, None | Some("en" | "ja" | "ko" | "zh")),
+        }
+    }
+}
+
+fn format_number(value: f64, format: NumberFormat, symbols: &NumberSymbols) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+
+    let mut digits = if value.is_infinite() {
+        "∞".to_string()
+    } else {
+        let digits = format!(
+            "{:.*}",
+            format.maximum_fraction_digits as usize,
+            value.abs()
+        );
+        let (integer, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+
+        let mut res = String::new();
+        for (i, digit) in integer.chars().enumerate() {
+            if format.uses_grouping_separator && i != 0 && (integer.len() - i) % 3 == 0 {
+                res.push_str(symbols.grouping_separator);
+            }
+            res.push(digit);
+        }
+
+        // Trailing zeros are dropped, down to the minimum number of digits.
+        let min = format.minimum_fraction_digits as usize;
+        let trimmed = fraction.trim_end_matches('0');
+        let fraction = if trimmed.len() >= min {
+            trimmed
+        } else {
+            &fraction[..min]
+        };
+        if !fraction.is_empty() {
+            res.push_str(symbols.decimal_separator);
+            res.push_str(fraction);
+        }
+        res
+    };
+
+    if format.is_currency {
+        digits = if symbols.currency_symbol_is_prefix {
+            format!("{}{}", symbols.currency_symbol, digits)
+        } else {
+            format!("{}\u{a0}{}", digits, symbols.currency_symbol)
+        };
+    }
+    // Rounding can produce -0, which isn't shown as negative.
+    if value.is_sign_negative() && digits.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        digits.insert(0, '-');
+    }
+    digits
+}
+
+/// The inverse of [format_number]. This is lenient about whitespace, the
+/// currency symbol and the number of fraction digits, but not about anything
+/// else.
+fn parse_number(string: &str, format: NumberFormat, symbols: &NumberSymbols) -> Option<f64> {
+    let is_space = |c: char| c.is_whitespace() || c == '\u{a0}';
+    let mut string = string.trim_matches(is_space);
+    let is_negative = if let Some(rest) = string.strip_prefix('-') {
+        string = rest.trim_start_matches(is_space);
+        true
+    } else {
+        false
+    };
+    if format.is_currency {
+        string = string
+            .strip_prefix(symbols.currency_symbol)
+            .or_else(|| string.strip_suffix(symbols.currency_symbol))
+            .unwrap_or(string)
+            .trim_matches(is_space);
+    }
+
+    let (integer, fraction) = match string.split_once(symbols.decimal_separator) {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (string, None),
+    };
+    let integer = if format.uses_grouping_separator {
+        integer.replace(symbols.grouping_separator, "")
+    } else {
+        integer.to_string()
+    };
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if !is_digits(&integer) || !fraction.is_none_or(is_digits) {
+        return None;
+    }
+    if integer.is_empty() && fraction.is_none_or(str::is_empty) {
+        return None;
+    }
+
+    let number = format!("0{}.{}0", integer, fraction.unwrap_or(""));
+    let value: f64 = number.parse().ok()?;
+    Some(if is_negative { -value } else { value })
+}
+
+struct NSNumberFormatterHostObject {
+    number_style: NSNumberFormatterStyle,
+    format: NumberFormat,
+    /// `NSLocale*`, or [nil] for the current locale.
+    locale: id,
+}
+impl HostObject for NSNumberFormatterHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSNumberFormatter: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSNumberFormatterHostObject {
+        number_style: NSNumberFormatterNoStyle,
+        format: NumberFormat::for_style(NSNumberFormatterNoStyle),
+        locale: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let locale = env.objc.borrow::<NSNumberFormatterHostObject>(this).locale;
+    release(env, locale);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (NSNumberFormatterStyle)numberStyle {
+    env.objc.borrow::<NSNumberFormatterHostObject>(this).number_style
+}
+- (())setNumberStyle:(NSNumberFormatterStyle)style {
+    let format = NumberFormat::for_style(style);
+    let host_object = env.objc.borrow_mut::<NSNumberFormatterHostObject>(this);
+    host_object.number_style = style;
+    host_object.format = format;
+}
+
+- (id)locale {
+    let locale = env.objc.borrow::<NSNumberFormatterHostObject>(this).locale;
+    if locale == nil {
+        msg_class![env; NSLocale currentLocale]
+    } else {
+        locale
+    }
+}
+- (())setLocale:(id)locale { // NSLocale*
+    retain(env, locale);
+    let old = std::mem::replace(
+        &mut env.objc.borrow_mut::<NSNumberFormatterHostObject>(this).locale,
+        locale
+    );
+    release(env, old);
+}
+
+- (bool)usesGroupingSeparator {
+    env.objc.borrow::<NSNumberFormatterHostObject>(this).format.uses_grouping_separator
+}
+- (())setUsesGroupingSeparator:(bool)uses {
+    env.objc.borrow_mut::<NSNumberFormatterHostObject>(this).format.uses_grouping_separator = uses;
+}
+
+- (NSUInteger)minimumFractionDigits {
+    env.objc.borrow::<NSNumberFormatterHostObject>(this).format.minimum_fraction_digits
+}
+- (())setMinimumFractionDigits:(NSUInteger)digits {
+    let format = &mut env.objc.borrow_mut::<NSNumberFormatterHostObject>(this).format;
+    format.minimum_fraction_digits = digits;
+    format.maximum_fraction_digits = format.maximum_fraction_digits.max(digits);
+}
+- (NSUInteger)maximumFractionDigits {
+    env.objc.borrow::<NSNumberFormatterHostObject>(this).format.maximum_fraction_digits
+}
+- (())setMaximumFractionDigits:(NSUInteger)digits {
+    let format = &mut env.objc.borrow_mut::<NSNumberFormatterHostObject>(this).format;
+    format.maximum_fraction_digits = digits;
+    format.minimum_fraction_digits = format.minimum_fraction_digits.min(digits);
+}
+
+- (id)stringFromNumber:(id)number { // NSNumber*
+    if number == nil {
+        return nil;
+    }
+    let value: f64 = msg![env; number doubleValue];
+    let (format, symbols) = format_and_symbols(env, this);
+    let res = format_number(value, format, &symbols);
+    log_dbg!("[(NSNumberFormatter*){:?} stringFromNumber:{}] => {:?}", this, value, res);
+    let res = ns_string::from_rust_string(env, res);
+    autorelease(env, res)
+}
+
+- (id)numberFromString:(id)string { // NSString*
+    let string = ns_string::to_rust_string(env, string).into_owned();
+    let (format, symbols) = format_and_symbols(env, this);
+    let Some(value) = parse_number(&string, format, &symbols) else {
+        log_dbg!("[(NSNumberFormatter*){:?} numberFromString:{:?}] => nil", this, string);
+        return nil;
+    };
+    // Whole numbers come back as integers, like on a real device.
+    let number: id = if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        msg_class![env; NSNumber numberWithLongLong:(value as i64)]
+    } else {
+        msg_class![env; NSNumber numberWithDouble:value]
+    };
+    number
+}
+
+@end
+
+};
+
+fn format_and_symbols(env: &mut Environment, formatter: id) -> (NumberFormat, NumberSymbols) {
+    let format = env
+        .objc
+        .borrow::<NSNumberFormatterHostObject>(formatter)
+        .format;
+    let locale: id = msg![env; formatter locale];
+    let (language, country) = language_and_country(env, locale);
+    let symbols = NumberSymbols::for_locale(language.as_deref(), country.as_deref());
+    (format, symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_number() {
+        let en_us = NumberSymbols::for_locale(Some("en"), Some("US"));
+        let de_de = NumberSymbols::for_locale(Some("de"), Some("DE"));
+        let none = NumberFormat::for_style(NSNumberFormatterNoStyle);
+        let decimal = NumberFormat::for_style(NSNumberFormatterDecimalStyle);
+        let currency = NumberFormat::for_style(NSNumberFormatterCurrencyStyle);
+
+        assert_eq!(format_number(1234567.891, decimal, &en_us), "1,234,567.891");
+        assert_eq!(format_number(1234567.891, decimal, &de_de), "1.234.567,891");
+        assert_eq!(format_number(1234567.891, none, &en_us), "1234568");
+        assert_eq!(format_number(-0.5, decimal, &en_us), "-0.5");
+        assert_eq!(format_number(-0.0001, decimal, &en_us), "0");
+        assert_eq!(format_number(123.0, decimal, &en_us), "123");
+        assert_eq!(format_number(1234.5, currency, &en_us), "$1,234.50");
+        assert_eq!(format_number(-1234.5, currency, &de_de), "-1.234,50\u{a0}€");
+        assert_eq!(format_number(f64::INFINITY, decimal, &en_us), "∞");
+    }
+
+    #[test]
+    fn test_parse_number() {
+        let en_us = NumberSymbols::for_locale(Some("en"), Some("US"));
+        let de_de = NumberSymbols::for_locale(Some("de"), Some("DE"));
+        let none = NumberFormat::for_style(NSNumberFormatterNoStyle);
+        let decimal = NumberFormat::for_style(NSNumberFormatterDecimalStyle);
+        let currency = NumberFormat::for_style(NSNumberFormatterCurrencyStyle);
+
+        assert_eq!(
+            parse_number("1,234,567.891", decimal, &en_us),
+            Some(1234567.891)
+        );
+        assert_eq!(
+            parse_number("1.234.567,891", decimal, &de_de),
+            Some(1234567.891)
+        );
+        assert_eq!(parse_number(" -42 ", none, &en_us), Some(-42.0));
+        assert_eq!(parse_number(".5", decimal, &en_us), Some(0.5));
+        assert_eq!(parse_number("1,234", none, &en_us), None);
+        assert_eq!(parse_number("12a", decimal, &en_us), None);
+        assert_eq!(parse_number("", decimal, &en_us), None);
+        assert_eq!(parse_number("$1,234.50", currency, &en_us), Some(1234.5));
+        assert_eq!(
+            parse_number("1.234,50\u{a0}€", currency, &de_de),
+            Some(1234.5)
+        );
+    }
+}
@@ -18,17 +18,19 @@ use super::ns_dictionary::dict_from_keys_and_objects;
 use super::ns_run_loop::NSDefaultRunLoopMode;
 use super::ns_string::{from_rust_string, get_static_str, to_rust_string};
 use super::{NSTimeInterval, NSUInteger};
-use crate::mem::MutVoidPtr;
+use crate::mem::{ConstVoidPtr, MutVoidPtr};
 use crate::objc::{
-    id, msg, msg_class, msg_send, nil, objc_classes, retain, Class, ClassExports, NSZonePtr, ObjC,
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, retain,
+    unrecognized_selector_message, Class, ClassExports, MsgSendArgs, NSZonePtr, ObjC,
     TrivialHostObject, SEL,
 };
+use crate::Environment;
 
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
-@implementation NSObject
+@implementation NSObject <NSObject>
 
 + (id)alloc {
     msg![env; this allocWithZone:(MutVoidPtr::null())]
@@ -61,14 +63,53 @@ pub const CLASSES: ClassExports = objc_classes! {
     // classes are not refcounted
 }
 
++ (id)description {
+    let class_name = env.objc.get_class_name(this).to_string();
+    let desc = from_rust_string(env, class_name);
+    autorelease(env, desc)
+}
++ (id)debugDescription {
+    msg![env; this description]
+}
+
 + (bool)instancesRespondToSelector:(SEL)selector {
     env.objc.class_has_method(this, selector)
 }
 
++ (bool)conformsToProtocol:(ConstVoidPtr)protocol { // Protocol*
+    env.objc.class_conforms_to_protocol(this, protocol, &env.mem)
+}
+
 + (bool)accessInstanceVariablesDirectly {
     true
 }
 
+// TODO: Raise NSInvalidArgumentException
++ (())doesNotRecognizeSelector:(SEL)selector {
+    let message = unrecognized_selector_message(
+        env.objc.get_class_name(this),
+        true,
+        selector.as_str(&env.mem),
+        this,
+    );
+    echo!("*** Terminating app due to uncaught exception 'NSInvalidArgumentException', reason: '{}'", message);
+    panic!("{}", message);
+}
+
+// Classes can perform selectors too (the NSObject protocol).
++ (id)performSelector:(SEL)sel {
+    perform_selector(env, this, sel, ())
+}
++ (id)performSelector:(SEL)sel
+           withObject:(id)o1 {
+    perform_selector(env, this, sel, (o1,))
+}
++ (id)performSelector:(SEL)sel
+           withObject:(id)o1
+           withObject:(id)o2 {
+    perform_selector(env, this, sel, (o1, o2))
+}
+
 - (id)init {
     this
 }
@@ -121,10 +162,17 @@ pub const CLASSES: ClassExports = objc_classes! {
     this == other
 }
 
-// TODO: description and debugDescription (both the instance and class method).
-// This is not hard to add, but before adding a fallback implementation of it,
-// we should make sure all the Foundation classes' overrides of it are there,
-// to prevent weird behavior.
+// Foundation classes whose description shows their contents must override
+// this, otherwise it falls back to just the class name and address.
+- (id)description {
+    let class: Class = msg![env; this class];
+    let desc = format!("<{}: {:#x}>", env.objc.get_class_name(class), this.to_bits());
+    let desc = from_rust_string(env, desc);
+    autorelease(env, desc)
+}
+- (id)debugDescription {
+    msg![env; this description]
+}
 // TODO: localized description methods also? (not sure if NSObject has them)
 
 // Helper for NSCopying
@@ -215,26 +263,60 @@ forUndefinedKey:(id)key { // NSString*
 }
 
 - (bool)respondsToSelector:(SEL)selector {
-    let class = msg![env; this class];
+    // Not -class: when this is sent to a class (NSObject's metaclass inherits
+    // this method), the class methods are the ones that matter.
+    let class: Class = ObjC::read_isa(this, &env.mem);
     env.objc.class_has_method(class, selector)
 }
 
-- (id)performSelector:(SEL)sel {
-    assert!(!sel.is_null());
-    msg_send(env, (this, sel))
+- (bool)conformsToProtocol:(ConstVoidPtr)protocol { // Protocol*
+    let class: Class = msg![env; this class];
+    env.objc.class_conforms_to_protocol(class, protocol, &env.mem)
+}
+
+- (id)methodSignatureForSelector:(SEL)selector {
+    // TODO: Keep the type encodings of guest methods so this can work for
+    // classes that don't override it.
+    let class: Class = ObjC::read_isa(this, &env.mem);
+    if env.objc.class_has_method(class, selector) {
+        log!(
+            "TODO: [{:?} methodSignatureForSelector:{}] (type encodings unavailable), returning nil",
+            this,
+            selector.as_str(&env.mem),
+        );
+    }
+    nil
+}
+
+- (())forwardInvocation:(id)invocation { // NSInvocation*
+    let selector: SEL = msg![env; invocation selector];
+    () = msg![env; this doesNotRecognizeSelector:selector];
 }
 
+// TODO: Raise NSInvalidArgumentException
+- (())doesNotRecognizeSelector:(SEL)selector {
+    let class: Class = ObjC::read_isa(this, &env.mem);
+    let message = unrecognized_selector_message(
+        env.objc.get_class_name(class),
+        false,
+        selector.as_str(&env.mem),
+        this,
+    );
+    echo!("*** Terminating app due to uncaught exception 'NSInvalidArgumentException', reason: '{}'", message);
+    panic!("{}", message);
+}
+
+- (id)performSelector:(SEL)sel {
+    perform_selector(env, this, sel, ())
+}
 - (id)performSelector:(SEL)sel
            withObject:(id)o1 {
-    assert!(!sel.is_null());
-    msg_send(env, (this, sel, o1))
+    perform_selector(env, this, sel, (o1,))
 }
-
 - (id)performSelector:(SEL)sel
            withObject:(id)o1
            withObject:(id)o2 {
-    assert!(!sel.is_null());
-    msg_send(env, (this, sel, o1, o2))
+    perform_selector(env, this, sel, (o1, o2))
 }
 
 - (())performSelector:(SEL)sel withObject:(id)arg afterDelay:(NSTimeInterval)delay {
@@ -345,3 +427,21 @@ forUndefinedKey:(id)key { // NSString*
 @end
 
 };
+
+/// Shared implementation of `performSelector:` and its variants, for both
+/// classes and instances. The message is sent synchronously, so if the
+/// receiver doesn't respond to it, it gets `doesNotRecognizeSelector:` as
+/// usual.
+fn perform_selector<A: MsgSendArgs<id>>(
+    env: &mut Environment,
+    receiver: id,
+    selector: SEL,
+    args: A,
+) -> id {
+    // TODO: Raise NSInvalidArgumentException
+    assert!(
+        !selector.is_null(),
+        "performSelector: called with a null selector"
+    );
+    env.call_selector(receiver, selector, args)
+}
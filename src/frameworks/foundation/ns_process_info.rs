@@ -5,9 +5,15 @@
  */
 //! `NSProcessInfo`.
 
-use super::NSTimeInterval;
-use crate::objc::{objc_classes, ClassExports};
-use std::time::Instant;
+use super::{ns_array, ns_dictionary, ns_string, NSTimeInterval};
+use crate::environment::launch_arguments;
+use crate::objc::{autorelease, id, objc_classes, release, ClassExports, TrivialHostObject};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    process_info: Option<id>,
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -15,10 +21,86 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation NSProcessInfo: NSObject
 
++ (id)processInfo {
+    if let Some(process_info) = env.framework_state.foundation.ns_process_info.process_info {
+        process_info
+    } else {
+        let new = env.objc.alloc_static_object(
+            this,
+            Box::new(TrivialHostObject),
+            &mut env.mem
+        );
+        env.framework_state.foundation.ns_process_info.process_info = Some(new);
+        new
+    }
+}
+
 + (NSTimeInterval)systemUptime {
-    Instant::now().duration_since(env.startup_time).as_secs_f64()
+    env.replay.instant().duration_since(env.startup_time).as_secs_f64()
+}
+
+- (NSTimeInterval)systemUptime {
+    env.replay.instant().duration_since(env.startup_time).as_secs_f64()
+}
+
+- (id)environment {
+    // This is a snapshot of the same variables getenv() and setenv() use.
+    let mut vars: Vec<(Vec<u8>, Vec<u8>)> = env
+        .env_vars
+        .iter()
+        .map(|(name, &value)| (name.clone(), env.mem.cstr_at(value).to_vec()))
+        .collect();
+    vars.sort();
+    let vars = vars
+        .into_iter()
+        .map(|(name, value)| {
+            (
+                String::from_utf8_lossy(&name).into_owned(),
+                String::from_utf8_lossy(&value).into_owned(),
+            )
+        })
+        .collect();
+    dict_from_strings(env, vars)
+}
+
+- (id)arguments {
+    // The same arguments main() receives.
+    let mut arguments = vec![env.bundle.executable_path().as_str().to_owned()];
+    arguments.extend(launch_arguments(&env.options));
+    let arguments = arguments
+        .into_iter()
+        .map(|argument| ns_string::from_rust_string(env, argument))
+        .collect();
+    let array = ns_array::from_vec(env, arguments);
+    autorelease(env, array)
 }
 
 @end
 
 };
+
+/// For use by `UIApplication`: the launch options dictionary for the app
+/// delegate (see [Environment::new]). Returns an autoreleased object.
+pub fn launch_options_dict(env: &mut Environment) -> id {
+    let launch_options = env.options.launch_options.clone();
+    dict_from_strings(env, launch_options)
+}
+
+/// Makes an autoreleased dictionary of strings.
+fn dict_from_strings(env: &mut Environment, pairs: Vec<(String, String)>) -> id {
+    let pairs: Vec<(id, id)> = pairs
+        .into_iter()
+        .map(|(key, value)| {
+            (
+                ns_string::from_rust_string(env, key),
+                ns_string::from_rust_string(env, value),
+            )
+        })
+        .collect();
+    let dict = ns_dictionary::dict_from_keys_and_objects(env, &pairs);
+    for (key, value) in pairs {
+        release(env, key);
+        release(env, value);
+    }
+    autorelease(env, dict)
+}
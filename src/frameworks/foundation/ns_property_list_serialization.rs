@@ -117,7 +117,9 @@ pub(super) fn deserialize_plist_from_file(
     deserialize_plist(env, &root)
 }
 
-fn deserialize_plist(env: &mut Environment, value: &Value) -> id {
+/// Create the Objective-C equivalent of a property list value. The result is
+/// not autoreleased.
+pub(super) fn deserialize_plist(env: &mut Environment, value: &Value) -> id {
     match value {
         Value::Array(array) => {
             let array = array
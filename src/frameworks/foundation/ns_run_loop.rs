@@ -13,6 +13,7 @@ use crate::dyld::{ConstantExports, HostConstant};
 use crate::environment::ThreadId;
 use crate::frameworks::audio_toolbox::audio_queue::{handle_audio_queue, AudioQueueRef};
 use crate::frameworks::audio_toolbox::audio_unit::{render_audio_unit, AudioUnit};
+use crate::frameworks::core_animation::ca_display_link;
 use crate::frameworks::core_foundation::cf_run_loop::{
     kCFRunLoopCommonModes, kCFRunLoopDefaultMode, CFRunLoopRef,
 };
@@ -53,6 +54,9 @@ struct NSRunLoopHostObject {
     /// Strong references to `NSTimer*` in no particular order. Timers are owned
     /// by the run loop. The timer must remove itself when invalidated.
     timers: Vec<id>,
+    /// Strong references to `CADisplayLink*`, in the order they were added.
+    /// The display link must remove itself when invalidated.
+    display_links: Vec<id>,
     /// A bool flag to indicate if the run loop is running.
     /// It is needed to deal with re-entrance issues.
     is_running: bool,
@@ -115,6 +119,11 @@ pub const CLASSES: ClassExports = objc_classes! {
     run_run_loop(env, this, /* single_iteration: */ false, Some(time_limit));
 }
 
+// Private method, see cancel_all_work. Apps don't call this, but tests do.
+- (())_touchHLE_cancelAllWork {
+    cancel_run_loop_work(env, this);
+}
+
 // TODO: other run methods
 
 @end
@@ -160,6 +169,29 @@ pub fn remove_audio_queue(env: &mut Environment, run_loop: id, queue: AudioQueue
     queues.remove(queue_idx);
 }
 
+/// For use by Core Animation.
+pub fn add_display_link(env: &mut Environment, run_loop: id, display_link: id) {
+    retain(env, display_link);
+    env.objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .display_links
+        .push(display_link);
+}
+
+/// For use by Core Animation.
+pub fn remove_display_link(env: &mut Environment, run_loop: id, display_link: id) {
+    let display_links = &mut env
+        .objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .display_links;
+    let link_idx = display_links
+        .iter()
+        .position(|&item| item == display_link)
+        .unwrap();
+    display_links.remove(link_idx);
+    release(env, display_link);
+}
+
 /// For use by NSTimer so it can remove itself once it's invalidated.
 pub(super) fn remove_timer(env: &mut Environment, run_loop: id, timer: id) {
     let NSRunLoopHostObject { timers, .. } = env.objc.borrow_mut(run_loop);
@@ -180,6 +212,48 @@ pub(super) fn remove_timer(env: &mut Environment, run_loop: id, timer: id) {
     }
 }
 
+/// Invalidate all the timers (including pending `performSelector:` calls) and
+/// display links on every thread's run loop, so that the objects they retain
+/// are released. This is done when the app quits, so that what
+/// [crate::objc::ObjC::dump_live_objects] reports is only what the app leaked.
+///
+/// A run loop that is running must not continue afterwards, since it might
+/// still try to handle a timer that has been freed. This is fine when quitting.
+pub fn cancel_all_work(env: &mut Environment) {
+    let mut run_loops: Vec<(ThreadId, id)> = env
+        .framework_state
+        .foundation
+        .ns_run_loop
+        .run_loops
+        .iter()
+        .map(|(&thread, &run_loop)| (thread, run_loop))
+        .collect();
+    // For deterministic output
+    run_loops.sort_by_key(|&(thread, _)| thread);
+    for (_, run_loop) in run_loops {
+        cancel_run_loop_work(env, run_loop);
+    }
+}
+
+fn cancel_run_loop_work(env: &mut Environment, run_loop: id) {
+    let host_object = env.objc.borrow::<NSRunLoopHostObject>(run_loop);
+    let timers = host_object.timers.clone();
+    let display_links = host_object.display_links.clone();
+    log_dbg!(
+        "Cancelling {} timers and {} display links on run loop {:?}",
+        timers.len(),
+        display_links.len(),
+        run_loop
+    );
+    // Each of these removes itself from the run loop and releases itself.
+    for timer in timers {
+        () = msg![env; timer invalidate];
+    }
+    for display_link in display_links {
+        () = msg![env; display_link invalidate];
+    }
+}
+
 /// Run the run loop for just a single iteration. This is a special mode just
 /// for the app picker, since we don't have `runMode:beforeDate:` yet.
 /// (TODO: implement those to replace this.)
@@ -237,6 +311,7 @@ pub fn run_run_loop(
     // Temporary vectors used to track things without needing a reference to the
     // environment or to lock the object. Re-used each iteration for efficiency.
     let mut timers_tmp = Vec::new();
+    let mut display_links_tmp = Vec::new();
     let mut audio_queues_tmp = Vec::new();
     let mut audio_units_tmp = Vec::new();
 
@@ -253,10 +328,9 @@ pub fn run_run_loop(
 
         // We want to process those only on the main run loop
         if is_main_run_loop {
-            env.window
-                .as_mut()
-                .expect("NSRunLoop not supported in headless mode")
-                .poll_for_events(&env.options);
+            if let Some(ref mut window) = env.window {
+                window.poll_for_events(&env.options);
+            }
 
             let next_due = uikit::handle_events(env);
             limit_sleep_time(&mut sleep_until, next_due);
@@ -273,6 +347,18 @@ pub fn run_run_loop(
             limit_sleep_time(&mut sleep_until, next_due);
         }
 
+        assert!(display_links_tmp.is_empty());
+        display_links_tmp.extend_from_slice(
+            &env.objc
+                .borrow::<NSRunLoopHostObject>(run_loop)
+                .display_links,
+        );
+
+        for display_link in display_links_tmp.drain(..) {
+            let next_due = ca_display_link::handle_display_link(env, display_link);
+            limit_sleep_time(&mut sleep_until, next_due);
+        }
+
         assert!(audio_queues_tmp.is_empty());
         audio_queues_tmp.extend_from_slice(
             &env.objc
@@ -355,6 +441,7 @@ fn run_loop_for_thread(env: &mut Environment, this: Class, thread_id: ThreadId)
             audio_units: Vec::new(),
             audio_queues: Vec::new(),
             timers: Vec::new(),
+            display_links: Vec::new(),
             is_running: false,
         });
         // TODO: is it OK to allocate static object for all threads,
@@ -5,10 +5,12 @@
  */
 //! The `NSSet` class cluster, including `NSMutableSet` and `NSCountedSet`.
 
-use super::ns_array;
 use super::ns_dictionary::DictionaryHostObject;
-use super::ns_enumerator::{fast_enumeration_helper, NSFastEnumerationState};
+use super::ns_enumerator::{
+    fast_enumeration_helper, forget_mutations, mutations_ptr, note_mutation, NSFastEnumerationState,
+};
 use super::NSUInteger;
+use super::{ns_array, ns_string};
 use crate::abi::DotDotDot;
 use crate::environment::Environment;
 use crate::mem::MutPtr;
@@ -33,7 +35,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 // - (NSEnumerator*)objectEnumerator;
 // We can pick whichever subclass we want for the various alloc methods.
 // For the time being, that will always be _touchHLE_NSSet.
-@implementation NSSet: NSObject
+@implementation NSSet: NSObject <NSCopying, NSFastEnumeration>
 
 + (id)allocWithZone:(NSZonePtr)zone {
     // NSSet might be subclassed by something which needs allocWithZone:
@@ -49,11 +51,25 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
+- (bool)containsObject:(id)object {
+    let member: id = msg![env; this member:object];
+    member != nil
+}
+
 // NSCopying implementation
 - (id)copyWithZone:(NSZonePtr)_zone {
     retain(env, this)
 }
 
+- (id)description {
+    // Like an array's description, but in braces.
+    let objects: id = msg![env; this allObjects];
+    let objects_desc: id = msg![env; objects description];
+    let desc = format!("{{{}}}", ns_string::to_rust_string(env, objects_desc));
+    let desc = ns_string::from_rust_string(env, desc);
+    autorelease(env, desc)
+}
+
 @end
 
 // NSMutableSet is an abstract class. A subclass must provide everything
@@ -117,6 +133,13 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow_mut::<SetHostObject>(this).dict.count
 }
 
+- (id)member:(id)object {
+    let host_obj: SetHostObject = std::mem::take(env.objc.borrow_mut(this));
+    let member = host_obj.dict.find(env, object).map_or(nil, |(key, _)| key);
+    *env.objc.borrow_mut(this) = host_obj;
+    member
+}
+
 - (id)anyObject {
     let object_or_none = env.objc.borrow_mut::<SetHostObject>(this).dict.iter_keys().next();
     match object_or_none {
@@ -143,7 +166,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     // between calls!
     let objects: id = msg![env; this allObjects];
     let count: NSUInteger = msg![env; objects count];
-    fast_enumeration_helper(env, this, |env, idx| {
+    fast_enumeration_helper(env, this.cast(), |env, idx| {
         if idx < count {
             msg![env; objects objectAtIndex:idx]
         } else {
@@ -183,6 +206,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (())dealloc {
     std::mem::take(&mut env.objc.borrow_mut::<SetHostObject>(this).dict).release(env);
+    forget_mutations(env, this);
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
@@ -192,6 +216,13 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow_mut::<SetHostObject>(this).dict.count
 }
 
+- (id)member:(id)object {
+    let host_obj: SetHostObject = std::mem::take(env.objc.borrow_mut(this));
+    let member = host_obj.dict.find(env, object).map_or(nil, |(key, _)| key);
+    *env.objc.borrow_mut(this) = host_obj;
+    member
+}
+
 - (id)anyObject {
     let object_or_none = env.objc.borrow_mut::<SetHostObject>(this).dict.iter_keys().next();
     match object_or_none {
@@ -214,12 +245,12 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
                                   objects:(MutPtr<id>)stackbuf
                                     count:(NSUInteger)len {
-    // TODO: check that set wasn't mutated!
     // We assume that order in which objects are reported is consistent
     // between calls!
     let objects: id = msg![env; this allObjects];
     let count: NSUInteger = msg![env; objects count];
-    fast_enumeration_helper(env, this, |env, idx| {
+    let mutations_ptr = mutations_ptr(env, this);
+    fast_enumeration_helper(env, mutations_ptr, |env, idx| {
         if idx < count {
             msg![env; objects objectAtIndex:idx]
         } else {
@@ -235,6 +266,14 @@ pub const CLASSES: ClassExports = objc_classes! {
     let mut host_obj: SetHostObject = std::mem::take(env.objc.borrow_mut(this));
     host_obj.dict.insert(env, object, null, /* copy_key: */ false);
     *env.objc.borrow_mut(this) = host_obj;
+    note_mutation(env, this);
+}
+
+- (())removeObject:(id)object {
+    let mut host_obj: SetHostObject = std::mem::take(env.objc.borrow_mut(this));
+    host_obj.dict.remove(env, object);
+    *env.objc.borrow_mut(this) = host_obj;
+    note_mutation(env, this);
 }
 
 - (())removeAllObjects {
@@ -245,6 +284,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         },
     );
     old_host_obj.dict.release(env);
+    note_mutation(env, this);
 }
 
 - (())unionSet:(id)other { // NSSet *
@@ -10,10 +10,11 @@
 
 mod path_algorithms;
 
-use super::{ns_array, unichar};
+use super::ns_url::url_parsing;
+use super::{ns_array, ns_keyed_unarchiver, unichar};
 use super::{
-    NSComparisonResult, NSNotFound, NSOrderedAscending, NSOrderedDescending, NSOrderedSame,
-    NSRange, NSUInteger,
+    NSComparisonResult, NSInteger, NSNotFound, NSOrderedAscending, NSOrderedDescending,
+    NSOrderedSame, NSRange, NSUInteger,
 };
 use crate::abi::VaList;
 use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
@@ -27,12 +28,11 @@ use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, retain, Class, ClassExports, HostObject,
     NSZonePtr, ObjC,
 };
-use crate::{fs, Environment};
+use crate::Environment;
 use encoding_rs::SHIFT_JIS;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::io::Write;
-use std::iter::Peekable;
 use std::string::FromUtf16Error;
 use yore::code_pages::CP1252;
 
@@ -273,7 +273,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 // - (unichar)characterAtIndex:(NSUInteger)index;
 // We can pick whichever subclass we want for the various alloc methods.
 // For the time being, that will always be _touchHLE_NSString.
-@implementation NSString: NSObject
+@implementation NSString: NSObject <NSCopying, NSMutableCopying>
 
 + (id)allocWithZone:(NSZonePtr)zone {
     // NSString might be subclassed by something which needs allocWithZone:
@@ -499,9 +499,14 @@ pub const CLASSES: ClassExports = objc_classes! {
     // TODO: use current locale
     // TODO: support `compatibility equivalence` in the Unicode standard
     // More info: https://www.objc.io/issues/9-strings/unicode/
-    assert!(to_rust_string(env, this).is_ascii());
-    assert!(to_rust_string(env, other).is_ascii());
-    msg![env; this compare:other]
+    // Case is only a tie-breaker, with lowercase first, which approximates
+    // the default collation order.
+    let insensitive: NSComparisonResult = msg![env; this caseInsensitiveCompare:other];
+    if insensitive != NSOrderedSame {
+        return insensitive;
+    }
+    let literal: NSComparisonResult = msg![env; this compare:other];
+    -literal
 }
 
 - (NSComparisonResult)compare:(id)other { // NSString*
@@ -513,81 +518,23 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (NSComparisonResult)compare:(id)other options:(NSStringCompareOptions)mask { // NSString*
-    fn ascii_number(iter: &mut Peekable<CodeUnitIterator>, leftmost_digit: char) -> u32 {
-        let mut num = leftmost_digit.to_digit(10).unwrap();
-        while let Some(a_digit_char) = iter.next_if(
-            |&x| char::from_u32(x as u32).is_some_and(|y| y.is_ascii_digit())
-        ) {
-            num = num * 10 + char::from_u32(a_digit_char as u32).unwrap().to_digit(10).unwrap();
-        }
-        num
-    }
-
     assert_ne!(other, nil);
 
     // TODO: support foreign subclasses (perhaps via a helper function that
     // copies the string first)
-    let mut a_iter = env.objc.borrow::<StringHostObject>(this).iter_code_units().peekable();
-    let mut b_iter = env.objc.borrow::<StringHostObject>(other).iter_code_units().peekable();
-
-    // By default, no mask is a literal search
-    let mask = if mask == 0 {
-        NSLiteralSearch
-    } else {
-        mask
-    };
-
-    // TODO: OR'ing of compare options
-    match mask {
-        NSCaseInsensitiveSearch => {
-            loop {
-                let a_next = a_iter.next();
-                let b_next = b_iter.next();
-                let (Some(a_unit), Some(b_unit)) = (a_next, b_next) else {
-                    return from_rust_ordering(a_next.cmp(&b_next));
-                };
-                let (Some(a_c), Some(b_c)) = (char::from_u32(a_unit as u32), char::from_u32(b_unit as u32)) else {
-                    panic!("Invalid chars in the strings!");
-                };
-
-                let insensitive_order = a_c.to_lowercase().cmp(b_c.to_lowercase());
-                if insensitive_order != std::cmp::Ordering::Equal {
-                    return from_rust_ordering(insensitive_order);
-                }
-            }
-        },
-        NSLiteralSearch => {
-            from_rust_ordering(a_iter.cmp(b_iter))
-        },
-        NSNumericSearch => {
-            loop {
-                let a_next = a_iter.next();
-                let b_next = b_iter.next();
-                let (Some(a_unit), Some(b_unit)) = (a_next, b_next) else {
-                    return from_rust_ordering(a_next.cmp(&b_next));
-                };
-                let (Some(a_c), Some(b_c)) = (char::from_u32(a_unit as u32), char::from_u32(b_unit as u32)) else {
-                    panic!("Invalid chars in the strings!");
-                };
+    let a: Vec<u16> = env.objc.borrow::<StringHostObject>(this).iter_code_units().collect();
+    let b: Vec<u16> = env.objc.borrow::<StringHostObject>(other).iter_code_units().collect();
 
-                if a_c.is_ascii_digit() && b_c.is_ascii_digit() {
-                    let a_int = ascii_number(&mut a_iter, a_c);
-                    let b_int = ascii_number(&mut b_iter, b_c);
-
-                    let numeric_order = a_int.cmp(&b_int);
-                    if numeric_order != std::cmp::Ordering::Equal {
-                        return from_rust_ordering(numeric_order);
-                    }
-                } else {
-                    let char_order = a_c.cmp(&b_c);
-                    if char_order != std::cmp::Ordering::Equal {
-                        return from_rust_ordering(char_order);
-                    }
-                }
-            }
-        },
-        mask => unimplemented!("Other mask: {mask}"),
+    let known_options = NSCaseInsensitiveSearch | NSLiteralSearch | NSNumericSearch;
+    if mask & !known_options != 0 {
+        log!("TODO: compare:options: with unsupported options {:#x}", mask & !known_options);
     }
+    from_rust_ordering(compare_with_options(
+        &a,
+        &b,
+        mask & NSCaseInsensitiveSearch != 0,
+        mask & NSNumericSearch != 0,
+    ))
 }
 
 // NSCopying implementation
@@ -912,37 +859,61 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (id)stringByAddingPercentEscapesUsingEncoding:(NSStringEncoding)encoding {
-    assert_eq!(encoding, NSASCIIStringEncoding); // TODO: other encodings
-    // TODO: implement escaping as per RFC 2396
-    let str = to_rust_string(env, this);
-    // FIXME: figure out why '[' and ']' are escaped on iOS simulator
-    assert!(str.as_bytes().iter().all(|byte| {
-        (byte.is_ascii_alphanumeric() || b"-_.~".contains(byte)) // unreserved
-        || b"!*'();:@&=+$,/?%#".contains(byte) // reserved
-    }));
-    let new: id = msg![env; this copy];
-    autorelease(env, new)
+    assert!(encoding == NSUTF8StringEncoding || encoding == NSASCIIStringEncoding);
+    let string = to_rust_string(env, this); // TODO: avoid copying
+    if encoding == NSASCIIStringEncoding && !string.is_ascii() {
+        return nil;
+    }
+    let encoded = url_parsing::percent_encode_legacy(&string);
+    let new_string = from_rust_string(env, encoded);
+    autorelease(env, new_string)
+}
+
+- (id)stringByAddingPercentEncodingWithAllowedCharacters:(id)set { // NSCharacterSet*
+    let string = to_rust_string(env, this); // TODO: avoid copying
+    // The set can only contain characters from the BMP, so anything outside
+    // it is always escaped.
+    let encoded = url_parsing::percent_encode(&string, |c| {
+        let Ok(c) = unichar::try_from(u32::from(c)) else {
+            return false;
+        };
+        msg![env; set characterIsMember:c]
+    });
+    let new_string = from_rust_string(env, encoded);
+    autorelease(env, new_string)
+}
+
+- (id)stringByReplacingPercentEscapesUsingEncoding:(NSStringEncoding)encoding {
+    assert!(encoding == NSUTF8StringEncoding || encoding == NSASCIIStringEncoding);
+    let string = to_rust_string(env, this); // TODO: avoid copying
+    let decoded = url_parsing::percent_decode(&string)
+        .filter(|decoded| encoding != NSASCIIStringEncoding || decoded.is_ascii());
+    let Some(decoded) = decoded else {
+        log_dbg!("Invalid percent escapes in {:?}, returning nil", string);
+        return nil;
+    };
+    let new_string = from_rust_string(env, decoded);
+    autorelease(env, new_string)
 }
 
 - (id)stringByAppendingPathComponent:(id)component { // NSString*
     // TODO: avoid copying
-    // FIXME: check if Rust join() matches NSString (it probably doesn't)
-    let combined = GuestPath::new(&to_rust_string(env, this))
-        .join(to_rust_string(env, component));
-    let new_string = from_rust_string(env, String::from(combined));
+    let combined = path_algorithms::append_path_component(
+        &to_rust_string(env, this),
+        &to_rust_string(env, component),
+    );
+    let new_string = from_rust_string(env, combined);
     autorelease(env, new_string)
 }
 
 - (id)stringByAppendingPathExtension:(id)extension { // NSString*
-    // FIXME: handle edge cases like trailing '/' (may differ from Rust!)
-    let mut combined = to_rust_string(env, this).into_owned();
     // TODO: avoid copying
-    let extension_string = to_rust_string(env, extension);
-    if extension_string.len() > 0 {
-        combined.push('.');
-        combined.push_str(&extension_string);
-    }
-
+    let path = to_rust_string(env, this);
+    let extension = to_rust_string(env, extension);
+    let Some(combined) = path_algorithms::append_path_extension(&path, &extension) else {
+        log!("Cannot append extension {:?} to path {:?}, returning nil", extension, path);
+        return nil;
+    };
     let new_string = from_rust_string(env, combined);
     autorelease(env, new_string)
 }
@@ -956,23 +927,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     //       "/var/automount”, or "/private” from the path
     assert!(!path.starts_with("/private"));
     assert!(!path.starts_with("/var/automount"));
-    // TODO: Reducing empty components and references to the current directory
-    assert!(!path.contains("//"));
-    assert!(!path.contains("/./"));
-    // Removing a trailing slash from the last component.
-    let path = path_algorithms::trim_trailing_slashes(&path);
-    // For absolute paths only, resolve references to the parent directory
-    let new_path_str = if path.starts_with('/') {
-        assert!(!path.starts_with("/.."));
-        // Note: while we are using fs function, it's just string manipulation
-        // here.
-        let resolved = fs::resolve_path(GuestPath::new(path), None);
-        let new_path = format!("/{}", resolved.join("/"));
-        assert!(!new_path.contains(".."));
-        new_path
-    } else {
-        String::from(path)
-    };
+    // Reducing empty components and references to the current directory,
+    // removing a trailing slash from the last component, and for absolute paths
+    // only, resolving references to the parent directory.
+    let new_path_str = path_algorithms::standardize_path(&path);
     log_dbg!("[(NSString *){:?} stringByStandardizingPath] {} -> {}", this, to_rust_string(env, this), new_path_str);
     let new_string = from_rust_string(env, new_path_str);
     autorelease(env, new_string)
@@ -1068,40 +1026,41 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (f32)floatValue {
-    float_value_common(env, this)
+    parse_double_prefix(&to_rust_string(env, this)) as f32
 }
 - (f64)doubleValue {
-    float_value_common(env, this)
+    parse_double_prefix(&to_rust_string(env, this))
 }
 
 - (i32)intValue {
-    let st = to_rust_string(env, this);
-    let st = st.trim_start();
-    let mut cutoff = st.len();
-    for (i, c) in st.char_indices() {
-        if !c.is_ascii_digit() && c != '+' && c != '-' {
-            cutoff = i;
-            break;
-        }
-    }
-    // TODO: handle over/underflow properly
-    st[..cutoff].parse().unwrap_or(0)
+    parse_integer_prefix(&to_rust_string(env, this))
 }
+- (NSInteger)integerValue {
+    parse_integer_prefix(&to_rust_string(env, this))
+}
+
+// The case mapping methods use Rust's, which follow the Unicode default case
+// mappings like Foundation's non-locale versions do. Titlecase isn't
+// distinguished from uppercase, which only matters for a few digraphs.
 
 - (id)lowercaseString {
-    // TODO: check if rust methods are consistent with ObjC one
     let str = to_rust_string(env, this).to_lowercase();
     let res = from_rust_string(env, str);
     autorelease(env, res)
 }
 
 - (id)uppercaseString {
-    // TODO: check if rust methods are consistent with ObjC one
     let str = to_rust_string(env, this).to_uppercase();
     let res = from_rust_string(env, str);
     autorelease(env, res)
 }
 
+- (id)capitalizedString {
+    let str = capitalize(&to_rust_string(env, this));
+    let res = from_rust_string(env, str);
+    autorelease(env, res)
+}
+
 @end
 
 // NSMutableString is an abstract class. A subclass must everything
@@ -1165,6 +1124,14 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 // TODO: more init methods
 
+// NSCoding implementation
+- (id)initWithCoder:(id)coder {
+    // Note: Assuming NSKeyedUnarchiver as coder here
+    let string = ns_keyed_unarchiver::decode_current_string(env, coder);
+    *env.objc.borrow_mut(this) = StringHostObject::Utf8(string.into());
+    this
+}
+
 - (id)initWithData:(id)data // NSData *
           encoding:(NSStringEncoding)encoding {
     let bytes: ConstVoidPtr = msg![env; data bytes];
@@ -1283,16 +1250,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 
 - (bool)boolValue {
-    let string = to_rust_string(env, this);
-    let string = string.trim_start_matches(|c: char| {
-        c.is_ascii_whitespace() || c == '-' || c == '+' || c == '0'
-    });
-
-    let matching_values = "YyTt123456789";
-    string.chars()
-        .next()
-        .map(|c| matching_values.contains(c))
-        .unwrap_or(false)
+    parse_bool_prefix(&to_rust_string(env, this))
 }
 
 - (id)dataUsingEncoding:(NSStringEncoding)encoding
@@ -1369,6 +1327,14 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; this init]
 }
 
+// NSCoding implementation
+- (id)initWithCoder:(id)coder {
+    // Note: Assuming NSKeyedUnarchiver as coder here
+    let string = ns_keyed_unarchiver::decode_current_string(env, coder);
+    *env.objc.borrow_mut(this) = StringHostObject::Utf8(string.into());
+    this
+}
+
 @end
 
 };
@@ -1488,6 +1454,81 @@ where
         });
 }
 
+/// Implementation of `compare:options:`. Without options, strings are ordered
+/// by their UTF-16 code units. With `numeric`, runs of ASCII digits are
+/// compared by their numeric value, so "file2" comes before "file10".
+fn compare_with_options(
+    a: &[u16],
+    b: &[u16],
+    case_insensitive: bool,
+    numeric: bool,
+) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    if !case_insensitive && !numeric {
+        return a.cmp(b);
+    }
+
+    let decode = |s: &[u16]| -> Vec<char> {
+        char::decode_utf16(s.iter().copied())
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect()
+    };
+    let (a, b) = (decode(a), decode(b));
+    let digits_end = |s: &[char], start: usize| {
+        start + s[start..].iter().take_while(|c| c.is_ascii_digit()).count()
+    };
+    let trim_zeros = |digits: &[char]| -> Vec<char> {
+        digits.iter().copied().skip_while(|&c| c == '0').collect()
+    };
+
+    let (mut i, mut j) = (0, 0);
+    loop {
+        let (a_c, b_c) = match (a.get(i), b.get(j)) {
+            (Some(&a_c), Some(&b_c)) => (a_c, b_c),
+            (a_c, b_c) => return a_c.is_some().cmp(&b_c.is_some()),
+        };
+        let order = if numeric && a_c.is_ascii_digit() && b_c.is_ascii_digit() {
+            let (a_end, b_end) = (digits_end(&a, i), digits_end(&b, j));
+            // Comparing the digits avoids overflow for long numbers.
+            let a_digits = trim_zeros(&a[i..a_end]);
+            let b_digits = trim_zeros(&b[j..b_end]);
+            (i, j) = (a_end, b_end);
+            a_digits
+                .len()
+                .cmp(&b_digits.len())
+                .then_with(|| a_digits.cmp(&b_digits))
+        } else {
+            (i, j) = (i + 1, j + 1);
+            if case_insensitive {
+                a_c.to_lowercase().cmp(b_c.to_lowercase())
+            } else {
+                a_c.cmp(&b_c)
+            }
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+}
+
+/// Implementation of `capitalizedString`: the first character of each word is
+/// uppercased and the rest are lowercased. Words are delimited by whitespace,
+/// which includes line terminators.
+fn capitalize(string: &str) -> String {
+    let mut res = String::with_capacity(string.len());
+    let mut at_word_start = true;
+    for c in string.chars() {
+        if at_word_start {
+            res.extend(c.to_uppercase());
+        } else {
+            res.extend(c.to_lowercase());
+        }
+        at_word_start = c.is_whitespace();
+    }
+    res
+}
+
 /// Helper function for `rangeOfString:options:` method
 /// Note: this implementation is linear
 fn is_match_at_position<F: Fn(u16, u16) -> bool>(
@@ -1511,17 +1552,167 @@ fn is_match_at_position<F: Fn(u16, u16) -> bool>(
     })
 }
 
-/// Helper function for shared `doubleValue` and `floatValue` implementations.
-fn float_value_common<F: std::str::FromStr + Default>(env: &mut Environment, string: id) -> F {
-    let st = to_rust_string(env, string);
-    let st = st.trim_start();
-    let mut cutoff = st.len();
-    for (i, c) in st.char_indices() {
-        if !c.is_ascii_digit() && c != '.' && c != '+' && c != '-' {
-            cutoff = i;
-            break;
+/// Splits off the leading whitespace and optional sign that the numeric value
+/// methods skip. Returns whether the sign was negative and the rest of the
+/// string.
+fn skip_whitespace_and_sign(string: &str) -> (bool, &str) {
+    let string = string.trim_start();
+    if let Some(rest) = string.strip_prefix('-') {
+        (true, rest)
+    } else {
+        (false, string.strip_prefix('+').unwrap_or(string))
+    }
+}
+
+/// Implementation of `intValue` and `integerValue`: parses the leading decimal
+/// integer, ignoring anything after it. Out-of-range values are clamped, and
+/// a string without a leading integer gives 0.
+fn parse_integer_prefix(string: &str) -> i32 {
+    let (negative, rest) = skip_whitespace_and_sign(string);
+    let mut value: i64 = 0;
+    for digit in rest.chars().map_while(|c| c.to_digit(10)) {
+        value = (value * 10 + i64::from(digit)).min(i64::from(i32::MAX) + 1);
+    }
+    let value = if negative { -value } else { value };
+    value.clamp(i32::MIN.into(), i32::MAX.into()) as i32
+}
+
+/// Implementation of `doubleValue` and `floatValue`: parses the leading
+/// decimal floating-point number (with optional fraction and exponent),
+/// ignoring anything after it. A string without a leading number gives 0.
+fn parse_double_prefix(string: &str) -> f64 {
+    let (negative, rest) = skip_whitespace_and_sign(string);
+    let bytes = rest.as_bytes();
+    let count_digits = |from: usize| {
+        bytes[from..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+
+    let mut end = count_digits(0);
+    let mut have_digits = end > 0;
+    if bytes.get(end) == Some(&b'.') {
+        let fraction_digits = count_digits(end + 1);
+        have_digits |= fraction_digits > 0;
+        end += 1 + fraction_digits;
+    }
+    if !have_digits {
+        return 0.0;
+    }
+    // The exponent is only included if it has at least one digit.
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let sign_len = matches!(bytes.get(end + 1), Some(b'+' | b'-')) as usize;
+        let exponent_digits = count_digits(end + 1 + sign_len);
+        if exponent_digits > 0 {
+            end += 1 + sign_len + exponent_digits;
         }
     }
-    // TODO: handle over/underflow properly
-    st[..cutoff].parse().unwrap_or(Default::default())
+
+    let value: f64 = rest[..end].parse().unwrap();
+    if negative {
+        -value
+    } else {
+        value
+    }
+}
+
+/// Implementation of `boolValue`: true if the string starts with "Y", "y",
+/// "T", "t" or a non-zero digit, after skipping whitespace, an optional sign
+/// and leading zeros.
+fn parse_bool_prefix(string: &str) -> bool {
+    let (_, rest) = skip_whitespace_and_sign(string);
+    let rest = rest.trim_start_matches('0');
+    rest.chars()
+        .next()
+        .is_some_and(|c| matches!(c, 'Y' | 'y' | 'T' | 't' | '1'..='9'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capitalize() {
+        assert_eq!(capitalize("hello wORLD"), "Hello World");
+        assert_eq!(
+            capitalize("  two\tspaces\nand-dash"),
+            "  Two\tSpaces\nAnd-dash"
+        );
+        assert_eq!(capitalize("élan ßtraße"), "Élan SStraße");
+        assert_eq!(capitalize(""), "");
+    }
+
+    #[test]
+    fn test_parse_integer_prefix() {
+        assert_eq!(parse_integer_prefix("42"), 42);
+        assert_eq!(parse_integer_prefix(" \t\n-17"), -17);
+        assert_eq!(parse_integer_prefix("+8"), 8);
+        assert_eq!(parse_integer_prefix("12-3"), 12);
+        assert_eq!(parse_integer_prefix("99 bottles"), 99);
+        assert_eq!(parse_integer_prefix("3.9"), 3);
+        assert_eq!(parse_integer_prefix("99999999999"), i32::MAX);
+        assert_eq!(parse_integer_prefix("-99999999999"), i32::MIN);
+        assert_eq!(parse_integer_prefix(""), 0);
+        assert_eq!(parse_integer_prefix("abc"), 0);
+        assert_eq!(parse_integer_prefix("--1"), 0);
+        assert_eq!(parse_integer_prefix("- 1"), 0);
+    }
+
+    #[test]
+    fn test_parse_double_prefix() {
+        assert_eq!(parse_double_prefix("1.5"), 1.5);
+        assert_eq!(parse_double_prefix("  -0.25"), -0.25);
+        assert_eq!(parse_double_prefix(".5"), 0.5);
+        assert_eq!(parse_double_prefix("5."), 5.0);
+        assert_eq!(parse_double_prefix("1.5.3"), 1.5);
+        assert_eq!(parse_double_prefix("2.5kg"), 2.5);
+        assert_eq!(parse_double_prefix("1e3"), 1000.0);
+        assert_eq!(parse_double_prefix("1E-2x"), 0.01);
+        assert_eq!(parse_double_prefix("7e"), 7.0);
+        assert_eq!(parse_double_prefix("7e+"), 7.0);
+        assert_eq!(parse_double_prefix(""), 0.0);
+        assert_eq!(parse_double_prefix("."), 0.0);
+        assert_eq!(parse_double_prefix("-"), 0.0);
+        assert_eq!(parse_double_prefix("e5"), 0.0);
+    }
+
+    #[test]
+    fn test_parse_bool_prefix() {
+        for s in ["YES", "yes", "true", "T", "1", "  +5", "-1", "007", "Yikes"] {
+            assert!(parse_bool_prefix(s), "{:?}", s);
+        }
+        for s in [
+            "NO", "no", "false", "0", "000", "", "  ", "nope", "--1", "0.5",
+        ] {
+            assert!(!parse_bool_prefix(s), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_compare_with_options() {
+        use std::cmp::Ordering::*;
+        let cmp = |a: &str, b: &str, case_insensitive, numeric| {
+            let a: Vec<u16> = a.encode_utf16().collect();
+            let b: Vec<u16> = b.encode_utf16().collect();
+            compare_with_options(&a, &b, case_insensitive, numeric)
+        };
+        assert_eq!(cmp("B", "a", false, false), Less);
+        assert_eq!(cmp("B", "a", true, false), Greater);
+        assert_eq!(cmp("HeLLo", "hello", true, false), Equal);
+        assert_eq!(cmp("abc", "ABCD", true, false), Less);
+        assert_eq!(cmp("file10", "file2", false, false), Less);
+        assert_eq!(cmp("file10", "file2", false, true), Greater);
+        assert_eq!(cmp("file002", "file2", false, true), Equal);
+        assert_eq!(cmp("File10", "file9", true, true), Greater);
+        assert_eq!(
+            cmp(
+                "v99999999999999999999",
+                "v100000000000000000000",
+                false,
+                true
+            ),
+            Less
+        );
+    }
 }
@@ -23,6 +23,70 @@ pub fn trim_trailing_slashes(path: &str) -> &str {
     }
 }
 
+/// Replaces each run of slashes with a single slash and removes any trailing
+/// slash, other than for the root path.
+fn collapse_slashes(path: &str) -> String {
+    let mut res = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c == '/' && res.ends_with('/') {
+            continue;
+        }
+        res.push(c);
+    }
+    if res.len() > 1 && res.ends_with('/') {
+        res.pop();
+    }
+    res
+}
+
+/// Returns the `stringByAppendingPathComponent:` value for a string.
+pub fn append_path_component(path: &str, component: &str) -> String {
+    if path.is_empty() {
+        collapse_slashes(component)
+    } else if component.is_empty() {
+        collapse_slashes(path)
+    } else {
+        collapse_slashes(&format!("{}/{}", path, component))
+    }
+}
+
+/// Returns the `stringByAppendingPathExtension:` value for a string, or [None]
+/// if there's no last path component to append the extension to.
+pub fn append_path_extension(path: &str, extension: &str) -> Option<String> {
+    let path = collapse_slashes(path);
+    if path.is_empty() || path == "/" || extension.contains('/') {
+        return None;
+    }
+    if extension.is_empty() {
+        Some(path)
+    } else {
+        Some(format!("{}.{}", path, extension))
+    }
+}
+
+/// Returns the `stringByStandardizingPath` value for a string, except that
+/// tildes and `/private` are not handled. Empty and `.` components are
+/// removed, and for absolute paths only, `..` removes the previous component.
+pub fn standardize_path(path: &str) -> String {
+    let is_absolute = path.starts_with('/');
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." if is_absolute => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    let joined = components.join("/");
+    if is_absolute {
+        format!("/{}", joined)
+    } else {
+        joined
+    }
+}
+
 /// Returns a tuple with the `stringByDeletingLastPathComponent` and
 /// `lastPathComponent` values for a string, in that order.
 pub fn split_last_path_component(path: &str) -> (&str, &str) {
@@ -162,4 +226,63 @@ mod tests {
         assert_eq!(path_extension("/a/"), "");
         assert_eq!(path_extension("/a/a..png"), "png");
     }
+
+    #[test]
+    fn test_append_path_component() {
+        use super::append_path_component;
+
+        assert_eq!(
+            append_path_component("/tmp", "scratch.tiff"),
+            "/tmp/scratch.tiff"
+        );
+        assert_eq!(
+            append_path_component("/tmp/", "scratch.tiff"),
+            "/tmp/scratch.tiff"
+        );
+        assert_eq!(append_path_component("/", "scratch.tiff"), "/scratch.tiff");
+        assert_eq!(append_path_component("", "scratch.tiff"), "scratch.tiff");
+        assert_eq!(append_path_component("/tmp//a/", "/b//c/"), "/tmp/a/b/c");
+        assert_eq!(append_path_component("/tmp/", ""), "/tmp");
+        assert_eq!(append_path_component("/", ""), "/");
+        assert_eq!(append_path_component("", ""), "");
+    }
+
+    #[test]
+    fn test_append_path_extension() {
+        use super::append_path_extension;
+
+        assert_eq!(
+            append_path_extension("/tmp/scratch.old", "tiff").as_deref(),
+            Some("/tmp/scratch.old.tiff")
+        );
+        assert_eq!(
+            append_path_extension("/tmp/scratch.", "tiff").as_deref(),
+            Some("/tmp/scratch..tiff")
+        );
+        assert_eq!(
+            append_path_extension("/tmp/", "tiff").as_deref(),
+            Some("/tmp.tiff")
+        );
+        assert_eq!(
+            append_path_extension("scratch", "tiff").as_deref(),
+            Some("scratch.tiff")
+        );
+        assert_eq!(append_path_extension("a//b", "").as_deref(), Some("a/b"));
+        assert_eq!(append_path_extension("/", "tiff"), None);
+        assert_eq!(append_path_extension("", "tiff"), None);
+        assert_eq!(append_path_extension("a", "b/c"), None);
+    }
+
+    #[test]
+    fn test_standardize_path() {
+        use super::standardize_path;
+
+        assert_eq!(standardize_path("/a//b/./c/"), "/a/b/c");
+        assert_eq!(standardize_path("/a/b/../c"), "/a/c");
+        assert_eq!(standardize_path("/a/../../b"), "/b");
+        assert_eq!(standardize_path("/.."), "/");
+        assert_eq!(standardize_path("/"), "/");
+        assert_eq!(standardize_path("a/./b/../c/"), "a/b/../c");
+        assert_eq!(standardize_path(""), "");
+    }
 }
@@ -5,40 +5,160 @@
  */
 //! `NSThread`.
 
-use super::NSTimeInterval;
-use crate::dyld::HostFunction;
+use super::{ns_string, NSTimeInterval};
+use crate::abi::CallFromHost;
+use crate::dyld::{ConstantExports, HostConstant, HostFunction};
 use crate::frameworks::core_foundation::CFTypeRef;
+use crate::libc::blocks::{block_copy, block_invoke, block_release, Block_literal};
 use crate::libc::pthread::thread::{
-    pthread_attr_init, pthread_attr_setdetachstate, pthread_attr_t, pthread_create, pthread_self,
-    pthread_t, PTHREAD_CREATE_DETACHED,
+    get_thread_id, get_thread_name, pthread_attr_init, pthread_attr_setdetachstate, pthread_attr_t,
+    pthread_create, pthread_self, pthread_t, set_thread_name, PTHREAD_CREATE_DETACHED,
 };
-use crate::mem::{guest_size_of, MutPtr};
+use crate::mem::{guest_size_of, ConstPtr, MutPtr};
 use crate::objc::{
-    id, msg_send, nil, objc_classes, release, retain, Class, ClassExports, HostObject, NSZonePtr,
-    SEL,
+    autorelease, id, msg_send, nil, objc_classes, release, retain, Class, ClassExports, HostObject,
+    NSZonePtr, SEL,
 };
-use crate::Environment;
 use crate::{msg, msg_class};
+use crate::{Environment, ThreadId};
 use std::collections::HashMap;
 use std::time::Duration;
 
 #[derive(Default)]
 pub struct State {
-    ns_threads: HashMap<pthread_t, id>,
+    /// The `NSThread` for each POSIX thread that has one, along with the
+    /// [ThreadId] it was created for. A `pthread_t` could be reused once its
+    /// thread has finished, so the [ThreadId] is what identifies the thread.
+    ns_threads: HashMap<pthread_t, (ThreadId, id)>,
+    /// Set once the first `NSThread` is started.
+    is_multi_threaded: bool,
 }
 impl State {
     fn get(env: &mut Environment) -> &mut Self {
         &mut env.framework_state.foundation.ns_thread
     }
+    /// The POSIX thread an `NSThread` runs on, if it has been started.
+    fn pthread_for(env: &mut Environment, ns_thread: id) -> Option<pthread_t> {
+        Self::get(env)
+            .ns_threads
+            .iter()
+            .find(|&(_, &(_, candidate))| candidate == ns_thread)
+            .map(|(&pthread, _)| pthread)
+    }
+}
+
+/// The `NSThread` for `pthread`, unless there is none or the entry is stale,
+/// i.e. it was left by a finished thread with the same `pthread_t`.
+fn live_ns_thread(
+    ns_threads: &HashMap<pthread_t, (ThreadId, id)>,
+    pthread: pthread_t,
+    thread_id: ThreadId,
+) -> Option<id> {
+    match ns_threads.get(&pthread) {
+        Some(&(owner, ns_thread)) if owner == thread_id => Some(ns_thread),
+        _ => None,
+    }
+}
+
+/// Forget the `NSThread` of a thread that has finished, so that it can't be
+/// returned for a later thread. Called by the thread scheduler.
+pub fn thread_did_exit(env: &mut Environment, thread_id: ThreadId) {
+    // The object is not released, since that could run guest code on a thread
+    // that no longer exists. NSThreads started by the app have already been
+    // removed by the invocation helper, so at most this leaks one lazily
+    // instantiated object per thread.
+    let exited: Vec<id> = State::get(env)
+        .ns_threads
+        .values()
+        .filter(|&&(owner, _)| owner == thread_id)
+        .map(|&(_, ns_thread)| ns_thread)
+        .collect();
+    for ns_thread in exited {
+        env.objc.borrow_mut::<NSThreadHostObject>(ns_thread).state = ThreadState::Finished;
+    }
+    State::get(env)
+        .ns_threads
+        .retain(|_, &mut (owner, _)| owner != thread_id);
+}
+
+/// The `NSThread` of a live thread, if it has one, along with a description of
+/// its lifecycle state. For [Environment::dump_threads].
+pub fn ns_thread_for_thread_id(
+    env: &mut Environment,
+    thread_id: ThreadId,
+) -> Option<(id, &'static str)> {
+    let ns_thread = State::get(env)
+        .ns_threads
+        .values()
+        .find(|&&(owner, _)| owner == thread_id)
+        .map(|&(_, ns_thread)| ns_thread)?;
+    let state = match env.objc.borrow::<NSThreadHostObject>(ns_thread).state {
+        ThreadState::NotStarted => "not started",
+        ThreadState::Executing => "executing",
+        ThreadState::Finished => "finished",
+    };
+    Some((ns_thread, state))
+}
+
+/// The implementation of `+[NSThread sleepForTimeInterval:]`, which does
+/// nothing if `ti` isn't positive. The sleep is a tail call (see
+/// [Environment::sleep]), so the caller must return right away.
+fn sleep_for_time_interval(env: &mut Environment, ti: NSTimeInterval) {
+    // Duration can't represent these.
+    if ti.is_nan() || ti <= 0.0 {
+        return;
+    }
+    env.sleep(Duration::from_secs_f64(ti), /* tail_call: */ true);
+}
+
+pub const NSWillBecomeMultiThreadedNotification: &str = "NSWillBecomeMultiThreadedNotification";
+pub const NSThreadWillExitNotification: &str = "NSThreadWillExitNotification";
+
+/// `NSNotificationName` values.
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSWillBecomeMultiThreadedNotification",
+        HostConstant::NSString(NSWillBecomeMultiThreadedNotification),
+    ),
+    (
+        "_NSThreadWillExitNotification",
+        HostConstant::NSString(NSThreadWillExitNotification),
+    ),
+];
+
+/// Post a notification with a host-side name to the default center.
+fn post_notification(env: &mut Environment, name: &'static str, object: id) {
+    let name = ns_string::get_static_str(env, name);
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    let _: () = msg![env; center postNotificationName:name object:object];
+}
+
+/// Where an `NSThread` is in its life, for `isExecuting` and `isFinished`.
+///
+/// Guest threads are scheduled on a single host thread and only switch at
+/// well-defined points, so a thread polling this from another thread always
+/// sees a consistent value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ThreadState {
+    NotStarted,
+    Executing,
+    Finished,
 }
 
 struct NSThreadHostObject {
+    state: ThreadState,
     target: id,
     selector: Option<SEL>,
     object: id,
+    /// Copy of the block to run instead of sending the selector, if any.
+    block: ConstPtr<Block_literal>,
     /// `NSMutableDictionary*`
     thread_dictionary: id,
     owned: bool,
+    /// `NSString*`, only used until the thread is started. After that, the
+    /// name is stored with the POSIX thread, so `pthread_getname_np()` sees
+    /// it too.
+    name: id,
 }
 impl HostObject for NSThreadHostObject {}
 
@@ -50,15 +170,22 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 + (id)allocWithZone:(NSZonePtr)_zone {
     let host_object = Box::new(NSThreadHostObject {
+        state: ThreadState::NotStarted,
         target: nil,
         selector: None,
         object: nil,
+        block: ConstPtr::null(),
         thread_dictionary: nil,
         owned: false,
+        name: nil,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
++ (bool)isMultiThreaded {
+    State::get(env).is_multi_threaded
+}
+
 + (f64)threadPriority {
     let thread: id = msg![env; this currentThread];
     msg![env; thread threadPriority]
@@ -72,15 +199,17 @@ pub const CLASSES: ClassExports = objc_classes! {
     // TODO: use ThreadId as key for lookup
     // `pthread_self` internally is O(num of threads) time
     let pthread = pthread_self(env);
-    // Clippy suggestion for this warning will not build!
-    #[allow(clippy::map_entry)]
-    if !State::get(env).ns_threads.contains_key(&pthread) {
-        // We lazily instantiate NSThreads for POSIX threads
-        let ns_thread: id = msg_class![env; NSThread alloc];
-        let ns_thread: id = msg![env; ns_thread init];
-        State::get(env).ns_threads.insert(pthread, ns_thread);
+    let thread_id = env.current_thread;
+    if let Some(ns_thread) = live_ns_thread(&State::get(env).ns_threads, pthread, thread_id) {
+        return ns_thread;
     }
-    *State::get(env).ns_threads.get(&pthread).unwrap()
+    // We lazily instantiate NSThreads for POSIX threads. This also replaces a
+    // stale entry for a reused pthread_t.
+    let ns_thread: id = msg_class![env; NSThread alloc];
+    let ns_thread: id = msg![env; ns_thread init];
+    env.objc.borrow_mut::<NSThreadHostObject>(ns_thread).state = ThreadState::Executing;
+    State::get(env).ns_threads.insert(pthread, (thread_id, ns_thread));
+    ns_thread
 }
 
 + (id)callStackReturnAddresses {
@@ -90,7 +219,12 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 + (())sleepForTimeInterval:(NSTimeInterval)ti {
     log_dbg!("[NSThread sleepForTimeInterval:{:?}]", ti);
-    env.sleep(Duration::from_secs_f64(ti), /* tail_call: */ true);
+    sleep_for_time_interval(env, ti);
+}
++ (())sleepUntilDate:(id)date { // NSDate*
+    let ti: NSTimeInterval = msg![env; date timeIntervalSinceNow];
+    log_dbg!("[NSThread sleepUntilDate:{:?}] ({:?}s from now)", date, ti);
+    sleep_for_time_interval(env, ti);
 }
 
 + (())detachNewThreadSelector:(SEL)selector
@@ -107,6 +241,16 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; new start]
 }
 
++ (())detachNewThreadWithBlock:(ConstPtr<Block_literal>)block {
+    let new: id = msg_class![env; NSThread alloc];
+    let new: id = msg![env; new initWithBlock:block];
+
+    // We own this thread and need to release it after it's finished
+    env.objc.borrow_mut::<NSThreadHostObject>(new).owned = true;
+
+    msg![env; new start]
+}
+
 - (id)initWithTarget:(id)target
             selector:(SEL)selector
               object:(id)object {
@@ -119,7 +263,22 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (id)initWithBlock:(ConstPtr<Block_literal>)block {
+    // The block is probably on the caller's stack, so it has to be copied.
+    let block = block_copy(env, block);
+    env.objc.borrow_mut::<NSThreadHostObject>(this).block = block;
+
+    this
+}
+
 - (())start {
+    // The notification is only posted for the first thread, and before it
+    // starts running.
+    if !State::get(env).is_multi_threaded {
+        State::get(env).is_multi_threaded = true;
+        post_notification(env, NSWillBecomeMultiThreadedNotification, nil);
+    }
+
     let symb = "__touchHLE_NSThreadInvocationHelper";
     let hf: HostFunction = &(_touchHLE_NSThreadInvocationHelper as fn(&mut Environment, _) -> _);
     let gf = env
@@ -134,11 +293,21 @@ pub const CLASSES: ClassExports = objc_classes! {
 
     pthread_create(env, thread_ptr, attr.cast_const(), gf, this.cast());
 
-    let pthread = env.mem.read(thread_ptr);
-    assert!(!State::get(env).ns_threads.contains_key(&pthread));
-    State::get(env).ns_threads.insert(pthread, this);
+    // The new thread doesn't run until this one yields, but it's executing
+    // as far as observers are concerned.
+    env.objc.borrow_mut::<NSThreadHostObject>(this).state = ThreadState::Executing;
 
-    // TODO: post NSWillBecomeMultiThreadedNotification
+    let pthread = env.mem.read(thread_ptr);
+    let thread_id = get_thread_id(env, pthread);
+    assert!(live_ns_thread(&State::get(env).ns_threads, pthread, thread_id).is_none());
+    State::get(env).ns_threads.insert(pthread, (thread_id, this));
+
+    let name = std::mem::take(&mut env.objc.borrow_mut::<NSThreadHostObject>(this).name);
+    if name != nil {
+        let name_bytes = ns_string::to_rust_string(env, name).into_owned().into_bytes();
+        set_thread_name(env, pthread, name_bytes);
+        release(env, name);
+    }
 }
 
 - (())main {
@@ -148,9 +317,22 @@ pub const CLASSES: ClassExports = objc_classes! {
         target,
         selector,
         object,
+        block,
         ..
     } = env.objc.borrow(this);
-    () = msg_send(env, (target, selector.unwrap(), object));
+    if let Some(selector) = selector {
+        () = msg_send(env, (target, selector, object));
+    } else if !block.is_null() {
+        let invoke = block_invoke(env, block);
+        () = invoke.call_from_host(env, (block,));
+    }
+}
+
+- (bool)isExecuting {
+    env.objc.borrow::<NSThreadHostObject>(this).state == ThreadState::Executing
+}
+- (bool)isFinished {
+    env.objc.borrow::<NSThreadHostObject>(this).state == ThreadState::Finished
 }
 
 - (id)threadDictionary {
@@ -168,6 +350,37 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
+- (id)name {
+    if let Some(pthread) = State::pthread_for(env, this) {
+        let name = get_thread_name(env, pthread);
+        if name.is_empty() {
+            return nil;
+        }
+        let name = String::from_utf8_lossy(name).into_owned();
+        let name = ns_string::from_rust_string(env, name);
+        autorelease(env, name)
+    } else {
+        env.objc.borrow::<NSThreadHostObject>(this).name
+    }
+}
+- (())setName:(id)name { // NSString*
+    if let Some(pthread) = State::pthread_for(env, this) {
+        let name_bytes = if name == nil {
+            Vec::new()
+        } else {
+            ns_string::to_rust_string(env, name).into_owned().into_bytes()
+        };
+        set_thread_name(env, pthread, name_bytes);
+    } else {
+        let name: id = msg![env; name copy];
+        let old_name = std::mem::replace(
+            &mut env.objc.borrow_mut::<NSThreadHostObject>(this).name,
+            name,
+        );
+        release(env, old_name);
+    }
+}
+
 - (f64)threadPriority {
     log!("TODO: [(NSThread *){:?} threadPriority] (not implemented yet)", this);
     1.0
@@ -180,7 +393,12 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (())dealloc {
     log_dbg!("[(NSThread*){:?} dealloc]", this);
     let host_object = env.objc.borrow::<NSThreadHostObject>(this);
-    release(env, host_object.thread_dictionary);
+    let (thread_dictionary, name) = (host_object.thread_dictionary, host_object.name);
+    // Only set if the thread was never started.
+    let block = host_object.block;
+    release(env, thread_dictionary);
+    release(env, name);
+    block_release(env, block);
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
@@ -201,21 +419,34 @@ pub fn _touchHLE_NSThreadInvocationHelper(env: &mut Environment, ns_thread_obj:
 
     () = msg![env; ns_thread_obj main];
 
+    // This is delivered on the exiting thread, before anything is released.
+    post_notification(env, NSThreadWillExitNotification, ns_thread_obj);
+
     let &NSThreadHostObject {
         target,
         object,
+        block,
         owned,
         ..
     } = env.objc.borrow(ns_thread_obj);
-    // The objects target and argument are retained during the execution
-    // of the detached thread. They are released when the thread finally exits.
+    // The objects target and argument, or the block, are retained during the
+    // execution of the detached thread. They are released when the thread
+    // finally exits.
     release(env, object);
     release(env, target);
+    block_release(env, block);
+    env.objc
+        .borrow_mut::<NSThreadHostObject>(ns_thread_obj)
+        .block = ConstPtr::null();
 
     let pthread = pthread_self(env);
     let res = State::get(env).ns_threads.remove(&pthread);
     assert!(res.is_some());
 
+    env.objc
+        .borrow_mut::<NSThreadHostObject>(ns_thread_obj)
+        .state = ThreadState::Finished;
+
     if owned {
         // Releasing only if the object was owned
         // e.g. created with `detachNewThreadSelector:toTarget:withObject:`
@@ -224,3 +455,23 @@ pub fn _touchHLE_NSThreadInvocationHelper(env: &mut Environment, ns_thread_obj:
 
     // TODO: NSThread exit
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::Ptr;
+
+    #[test]
+    fn test_live_ns_thread() {
+        let pthread: pthread_t = Ptr::from_bits(0x1000);
+        let other_pthread: pthread_t = Ptr::from_bits(0x2000);
+        let ns_thread: id = Ptr::from_bits(0x3000);
+
+        let mut ns_threads = HashMap::new();
+        ns_threads.insert(pthread, (1, ns_thread));
+        assert_eq!(live_ns_thread(&ns_threads, pthread, 1), Some(ns_thread));
+        assert_eq!(live_ns_thread(&ns_threads, other_pthread, 1), None);
+        // Thread 1 finished and its pthread_t was reused for thread 2.
+        assert_eq!(live_ns_thread(&ns_threads, pthread, 2), None);
+    }
+}
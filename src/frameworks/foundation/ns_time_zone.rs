@@ -4,17 +4,65 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `NSTimeZone`.
+//!
+//! There's no time zone database, so named time zones other than GMT/UTC are
+//! treated as having no offset from GMT.
 
 use crate::frameworks::foundation::{ns_string, NSInteger};
 use crate::objc::{autorelease, id, nil, release, retain, ClassExports, HostObject, NSZonePtr};
-use crate::{msg, objc_classes};
+use crate::{msg, msg_class, objc_classes, Environment};
+
+#[derive(Default)]
+pub struct State {
+    system_time_zone: Option<id>,
+    default_time_zone: Option<id>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut State {
+        &mut env.framework_state.foundation.ns_time_zone
+    }
+}
 
 struct NSTimeZoneHostObject {
     // NSString*
     time_zone: id,
+    seconds_from_gmt: NSInteger,
 }
 impl HostObject for NSTimeZoneHostObject {}
 
+/// Parses names like `GMT`, `UTC+2` or `GMT-0130` into an offset in seconds.
+fn parse_gmt_offset(name: &str) -> Option<NSInteger> {
+    let rest = name
+        .strip_prefix("GMT")
+        .or_else(|| name.strip_prefix("UTC"))?;
+    if rest.is_empty() {
+        return Some(0);
+    }
+    let (sign, rest) = match rest.as_bytes()[0] {
+        b'+' => (1, &rest[1..]),
+        b'-' => (-1, &rest[1..]),
+        _ => return None,
+    };
+    let rest = rest.replace(':', "");
+    if rest.is_empty() || rest.len() > 4 || !rest.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = if rest.len() <= 2 {
+        (rest.parse::<NSInteger>().unwrap(), 0)
+    } else {
+        let (hours, minutes) = rest.split_at(rest.len() - 2);
+        (hours.parse().unwrap(), minutes.parse().unwrap())
+    };
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Returns the offset from GMT in seconds of an `NSTimeZone*`.
+pub fn seconds_from_gmt(env: &mut Environment, time_zone: id) -> NSInteger {
+    env.objc
+        .borrow::<NSTimeZoneHostObject>(time_zone)
+        .seconds_from_gmt
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -24,6 +72,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (id)allocWithZone:(NSZonePtr)_zone {
     let host_object = Box::new(NSTimeZoneHostObject {
         time_zone: nil,
+        seconds_from_gmt: 0,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
@@ -34,10 +83,58 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
-+ (id)localTimeZone {
++ (id)timeZoneWithAbbreviation:(id)abbreviation { // NSString *
+    // TODO: abbreviations other than GMT/UTC
+    let name = ns_string::to_rust_string(env, abbreviation);
+    if parse_gmt_offset(&name).is_none() {
+        log!("TODO: [NSTimeZone timeZoneWithAbbreviation:{:?}], returning nil", name);
+        return nil;
+    }
+    msg![env; this timeZoneWithName:abbreviation]
+}
+
++ (id)timeZoneForSecondsFromGMT:(NSInteger)seconds {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    let name = format!("GMT{}{:02}{:02}", sign, minutes / 60, minutes % 60);
+    let tz_name = ns_string::from_rust_string(env, name);
+    let new: id = msg![env; this timeZoneWithName:tz_name];
+    release(env, tz_name);
+    new
+}
+
++ (id)systemTimeZone {
+    if let Some(tz) = State::get(env).system_time_zone {
+        return tz;
+    }
     // As reported by the Aspen Simulator
     let tz_name: id = ns_string::get_static_str(env, "Canada/Eastern");
-    msg![env; this timeZoneWithName:tz_name]
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithName:tz_name];
+    State::get(env).system_time_zone = Some(new);
+    new
+}
+
++ (id)defaultTimeZone {
+    if let Some(tz) = State::get(env).default_time_zone {
+        return tz;
+    }
+    let tz: id = msg_class![env; NSTimeZone systemTimeZone];
+    State::get(env).default_time_zone = Some(retain(env, tz));
+    tz
+}
+
++ (())setDefaultTimeZone:(id)tz { // NSTimeZone *
+    assert_ne!(tz, nil);
+    retain(env, tz);
+    if let Some(old) = State::get(env).default_time_zone.replace(tz) {
+        release(env, old);
+    }
+}
+
++ (id)localTimeZone {
+    // TODO: this should be a proxy that follows changes to the default
+    msg![env; this defaultTimeZone]
 }
 
 - (())dealloc {
@@ -46,18 +143,67 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
 - (id)initWithName:(id)tz_name { // NSString *
     assert_ne!(tz_name, nil);
-    retain(env, tz_name);
-    env.objc.borrow_mut::<NSTimeZoneHostObject>(this).time_zone = tz_name;
+    let name = ns_string::to_rust_string(env, tz_name);
+    let seconds_from_gmt = parse_gmt_offset(&name).unwrap_or_else(|| {
+        log_dbg!("TODO: time zone {:?} is assumed to be GMT", name);
+        0
+    });
+    let tz_name: id = msg![env; tz_name copy];
+    let host_object = env.objc.borrow_mut::<NSTimeZoneHostObject>(this);
+    host_object.time_zone = tz_name;
+    host_object.seconds_from_gmt = seconds_from_gmt;
     this
 }
 
+- (id)name {
+    env.objc.borrow::<NSTimeZoneHostObject>(this).time_zone
+}
+
+- (id)description {
+    env.objc.borrow::<NSTimeZoneHostObject>(this).time_zone
+}
+
 - (NSInteger)secondsFromGMT {
-    // TODO: respect timezone
-    0
+    // TODO: daylight saving time
+    seconds_from_gmt(env, this)
+}
+
+- (NSInteger)secondsFromGMTForDate:(id)_date { // NSDate *
+    seconds_from_gmt(env, this)
+}
+
+- (bool)isEqualToTimeZone:(id)other { // NSTimeZone *
+    if other == nil {
+        return false;
+    }
+    let a = env.objc.borrow::<NSTimeZoneHostObject>(this).time_zone;
+    let b = env.objc.borrow::<NSTimeZoneHostObject>(other).time_zone;
+    msg![env; a isEqualToString:b]
 }
 
 @end
 
 };
+
+#[cfg(test)]
+mod tests {
+    use super::parse_gmt_offset;
+
+    #[test]
+    fn test_parse_gmt_offset() {
+        assert_eq!(parse_gmt_offset("GMT"), Some(0));
+        assert_eq!(parse_gmt_offset("UTC"), Some(0));
+        assert_eq!(parse_gmt_offset("GMT+2"), Some(7200));
+        assert_eq!(parse_gmt_offset("GMT-0130"), Some(-5400));
+        assert_eq!(parse_gmt_offset("UTC+05:45"), Some(20700));
+        assert_eq!(parse_gmt_offset("Europe/London"), None);
+        assert_eq!(parse_gmt_offset("GMT+"), None);
+    }
+}
@@ -119,9 +119,11 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (())invalidate {
-    // Timer might already be invalid, don't try to remove it twice.
-    if env.objc.borrow_mut::<NSTimerHostObject>(this).due_by.take().is_some() {
-        let run_loop: id = msg_class![env; NSRunLoop currentRunLoop];
+    // Timer might already be invalid, don't try to remove it twice. It might
+    // also never have been added to a run loop.
+    let host_object = env.objc.borrow_mut::<NSTimerHostObject>(this);
+    let run_loop = host_object.run_loop;
+    if host_object.due_by.take().is_some() && run_loop != nil {
         ns_run_loop::remove_timer(env, run_loop, this);
     }
 }
@@ -5,7 +5,9 @@
  */
 //! `NSURL`.
 
-use super::ns_string::{from_rust_string, to_rust_string, NSUTF8StringEncoding};
+pub mod url_parsing;
+
+use super::ns_string::{from_rust_string, get_static_str, to_rust_string, NSUTF8StringEncoding};
 use super::NSUInteger;
 use crate::fs::{GuestPath, GuestPathBuf};
 use crate::mem::MutPtr;
@@ -83,8 +85,8 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (id)initFileURLWithPath:(id)path // NSString*
               isDirectory:(bool)_is_dir {
-    // FIXME: this does not resolve relative paths to be absolute!
-    // TODO: this does not strip the file:/// prefix!
+    // Relative paths are kept as-is, but are resolved against the working
+    // directory where an absolute path is needed.
     assert!(!to_rust_string(env, path).starts_with("file:"));
     let path: id = msg![env; path copy];
     *env.objc.borrow_mut(this) = NSURLHostObject::FileURL { ns_string: path, working_directory: env.fs.working_directory().into() };
@@ -96,8 +98,13 @@ pub const CLASSES: ClassExports = objc_classes! {
         return nil;
     }
 
-    // FIXME: this should parse the URL
-    assert!(!to_rust_string(env, url).starts_with("file:")); // TODO
+    // TODO: Return nil for malformed URLs.
+    let url_string = to_rust_string(env, url);
+    if let Some(path) = url_parsing::file_url_to_path(&url_string) {
+        let path = from_rust_string(env, path);
+        *env.objc.borrow_mut(this) = NSURLHostObject::FileURL { ns_string: path, working_directory: env.fs.working_directory().into() };
+        return this;
+    }
     let url: id = msg![env; url copy];
     *env.objc.borrow_mut(this) = NSURLHostObject::OtherURL { ns_string: url };
     this
@@ -122,22 +129,59 @@ pub const CLASSES: ClassExports = objc_classes! {
     match *env.objc.borrow(this) {
         NSURLHostObject::FileURL { ns_string, .. } => ns_string,
         NSURLHostObject::OtherURL { ns_string } => {
-            // TODO: Support full URLs, not only ones that are just a path.
-            // FIXME: This should do unescaping.
-            // TODO: Avoid copy.
-            assert!(to_rust_string(env, ns_string).starts_with('/'));
-            ns_string
+            let url_string = to_rust_string(env, ns_string);
+            let Some(path) = url_parsing::url_path(&url_string) else {
+                return nil;
+            };
+            let path = from_rust_string(env, path);
+            autorelease(env, path)
+        },
+    }
+}
+
+- (id)lastPathComponent {
+    let path: id = msg![env; this path];
+    msg![env; path lastPathComponent]
+}
+
+- (id)pathExtension {
+    let path: id = msg![env; this path];
+    msg![env; path pathExtension]
+}
+
+- (bool)isFileURL {
+    matches!(env.objc.borrow(this), NSURLHostObject::FileURL { .. })
+}
+
+- (id)scheme {
+    match *env.objc.borrow(this) {
+        NSURLHostObject::FileURL { .. } => get_static_str(env, "file"),
+        NSURLHostObject::OtherURL { ns_string } => {
+            let url_string = to_rust_string(env, ns_string);
+            let Some((scheme, _)) = url_parsing::split_scheme(&url_string) else {
+                return nil;
+            };
+            let scheme = from_rust_string(env, scheme);
+            autorelease(env, scheme)
         },
     }
 }
 
 - (id)absoluteString {
     match *env.objc.borrow(this) {
-        // FIXME: don't assume URL is already absolute
-        NSURLHostObject::FileURL { ns_string, .. } => ns_string,
+        NSURLHostObject::FileURL { .. } => {
+            let path = absolute_file_path(env, this);
+            let url_string = url_parsing::path_to_file_url(path.as_str());
+            let url_string = from_rust_string(env, url_string);
+            autorelease(env, url_string)
+        },
         NSURLHostObject::OtherURL { ns_string } => {
-            // TODO: full RFC 1808 resolution
-            assert!(to_rust_string(env, ns_string).starts_with("http"));
+            // A URL with a scheme is already absolute.
+            // TODO: full RFC 1808 resolution, once base URLs are supported
+            let url_string = to_rust_string(env, ns_string);
+            if url_parsing::split_scheme(&url_string).is_none() {
+                log!("TODO: relative URL {:?} isn't resolved, returning it as-is", url_string);
+            }
             ns_string
         },
     }
@@ -176,6 +220,25 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// Returns the absolute path of a file URL, resolving relative paths against
+/// the working directory at the time the URL was created.
+fn absolute_file_path(env: &mut Environment, url: id) -> GuestPathBuf {
+    let NSURLHostObject::FileURL {
+        ns_string,
+        working_directory,
+    } = env.objc.borrow(url)
+    else {
+        panic!("Not a file URL");
+    };
+    let (ns_string, working_directory) = (*ns_string, working_directory.clone());
+    let path = to_rust_string(env, ns_string);
+    if path.starts_with('/') {
+        GuestPathBuf::from(path.into_owned())
+    } else {
+        working_directory.join(path.trim_start_matches("./"))
+    }
+}
+
 /// Shortcut for host code, provides a view of a URL as a path.
 /// TODO: Try to avoid allocating a new GuestPathBuf in more cases.
 pub fn to_rust_path(env: &mut Environment, url: id) -> Cow<'static, GuestPath> {
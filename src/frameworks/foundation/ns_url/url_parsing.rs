@@ -0,0 +1,194 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Minimal URL parsing and formatting used by `NSURL`.
+//!
+//! This is nowhere near a full RFC 1808 implementation. It covers what's
+//! needed to convert between file URLs and paths, and to extract the path of
+//! simple non-file URLs.
+
+/// Splits off the scheme of a URL, if it has one. The scheme is returned in
+/// lowercase, and the rest of the string is returned without the `:`.
+pub fn split_scheme(url: &str) -> Option<(String, &str)> {
+    let (scheme, rest) = url.split_once(':')?;
+    let mut chars = scheme.chars();
+    if !chars.next()?.is_ascii_alphabetic()
+        || !chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    {
+        return None;
+    }
+    Some((scheme.to_ascii_lowercase(), rest))
+}
+
+/// Splits the part of a URL following the scheme into the host (if any) and
+/// the percent-encoded path. Any query or fragment is discarded.
+fn split_host_and_path(rest: &str) -> (Option<&str>, &str) {
+    let rest = rest.split(['?', '#']).next().unwrap();
+    if let Some(rest) = rest.strip_prefix("//") {
+        match rest.find('/') {
+            Some(idx) => (Some(&rest[..idx]), &rest[idx..]),
+            None => (Some(rest), ""),
+        }
+    } else {
+        (None, rest)
+    }
+}
+
+/// Decodes `%XX` escapes. Returns [None] if an escape is malformed or the
+/// result isn't valid UTF-8.
+pub fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hi = (iter.next()? as char).to_digit(16)?;
+            let lo = (iter.next()? as char).to_digit(16)?;
+            bytes.push((hi * 16 + lo) as u8);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Escapes every character for which `is_allowed` returns [false], as `%XX`
+/// escapes of each byte of its UTF-8 encoding.
+pub fn percent_encode(s: &str, mut is_allowed: impl FnMut(char) -> bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for c in s.chars() {
+        if is_allowed(c) {
+            encoded.push(c);
+        } else {
+            let mut buf = [0u8; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    encoded
+}
+
+/// Escapes everything in a path that can't appear literally in a URL path.
+pub fn percent_encode_path(path: &str) -> String {
+    percent_encode(path, |c| {
+        c.is_ascii_alphanumeric() || "-_.~!$&'()*+,;=:@/".contains(c)
+    })
+}
+
+/// Escapes everything that isn't legal anywhere in a URL, the way the legacy
+/// `-[NSString stringByAddingPercentEscapesUsingEncoding:]` does. Reserved
+/// characters like `/` and `?` are left alone, as is `#`, but `%` is escaped.
+pub fn percent_encode_legacy(s: &str) -> String {
+    percent_encode(s, |c| {
+        c.is_ascii_alphanumeric() || "-_.!~*'();/?:@&=+$,#".contains(c)
+    })
+}
+
+/// Parses a `file:` URL and returns the (unescaped) path it refers to.
+/// Returns [None] if this isn't a file URL, or if it refers to a host other
+/// than the local machine.
+pub fn file_url_to_path(url: &str) -> Option<String> {
+    let (scheme, rest) = split_scheme(url)?;
+    if scheme != "file" {
+        return None;
+    }
+    let (host, path) = split_host_and_path(rest);
+    if !matches!(host, None | Some("") | Some("localhost")) {
+        return None;
+    }
+    let path = percent_decode(path)?;
+    Some(if path.is_empty() {
+        "/".to_string()
+    } else {
+        path
+    })
+}
+
+/// Formats an absolute path as a `file:` URL string.
+pub fn path_to_file_url(path: &str) -> String {
+    assert!(path.starts_with('/'));
+    format!("file://localhost{}", percent_encode_path(path))
+}
+
+/// Returns the (unescaped) path of a non-file URL. A string without a scheme
+/// is treated as being just a path.
+pub fn url_path(url: &str) -> Option<String> {
+    let rest = match split_scheme(url) {
+        Some((_scheme, rest)) => rest,
+        None => url,
+    };
+    let (_host, path) = split_host_and_path(rest);
+    percent_decode(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_scheme() {
+        assert_eq!(
+            split_scheme("HTTP://example.com"),
+            Some(("http".to_string(), "//example.com"))
+        );
+        assert_eq!(
+            split_scheme("file:///a"),
+            Some(("file".to_string(), "///a"))
+        );
+        assert_eq!(split_scheme("/a/b:c"), None);
+        assert_eq!(split_scheme("a/b"), None);
+    }
+
+    #[test]
+    fn test_percent_coding() {
+        assert_eq!(percent_decode("a%20b%2Fc").as_deref(), Some("a b/c"));
+        assert_eq!(percent_decode("%C3%A9").as_deref(), Some("é"));
+        assert_eq!(percent_decode("%2"), None);
+        assert_eq!(percent_decode("%zz"), None);
+
+        assert_eq!(percent_encode_path("/a b/c%d.png"), "/a%20b/c%25d.png");
+        assert_eq!(percent_encode_path("/é"), "/%C3%A9");
+
+        assert_eq!(
+            percent_encode_legacy("a b/é?x=[1]#100%"),
+            "a%20b/%C3%A9?x=%5B1%5D#100%25"
+        );
+        assert_eq!(percent_encode("a-€", |c| c == 'a'), "a%2D%E2%82%AC");
+        for s in ["with spaces", "é, ü & 日本語", "%25 🎉"] {
+            let encoded = percent_encode(s, |c| c.is_ascii_alphanumeric());
+            assert!(encoded.is_ascii());
+            assert_eq!(percent_decode(&encoded).as_deref(), Some(s));
+        }
+    }
+
+    #[test]
+    fn test_file_url_round_trip() {
+        for path in ["/", "/a/b.png", "/with space/100%.txt", "/é"] {
+            let url = path_to_file_url(path);
+            assert_eq!(file_url_to_path(&url).as_deref(), Some(path));
+        }
+
+        assert_eq!(
+            path_to_file_url("/a b/c.png"),
+            "file://localhost/a%20b/c.png"
+        );
+        assert_eq!(file_url_to_path("file:///a/b").as_deref(), Some("/a/b"));
+        assert_eq!(file_url_to_path("file:/a/b").as_deref(), Some("/a/b"));
+        assert_eq!(file_url_to_path("FILE:///a?x#y").as_deref(), Some("/a"));
+        assert_eq!(file_url_to_path("file://localhost").as_deref(), Some("/"));
+        assert_eq!(file_url_to_path("file://example.com/a"), None);
+        assert_eq!(file_url_to_path("http://localhost/a"), None);
+    }
+
+    #[test]
+    fn test_url_path() {
+        assert_eq!(
+            url_path("http://example.com/a%20b/c?d=e#f").as_deref(),
+            Some("/a b/c")
+        );
+        assert_eq!(url_path("http://example.com").as_deref(), Some(""));
+        assert_eq!(url_path("/a/b").as_deref(), Some("/a/b"));
+    }
+}
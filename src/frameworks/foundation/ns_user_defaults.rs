@@ -14,18 +14,6 @@ use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, Class, ClassExports, HostObject,
     NSZonePtr,
 };
-use crate::Environment;
-
-#[derive(Default)]
-pub struct State {
-    /// `NSUserDefaults*`
-    standard_defaults: Option<id>,
-}
-impl State {
-    fn get(env: &mut Environment) -> &mut State {
-        &mut env.framework_state.foundation.ns_user_defaults
-    }
-}
 
 struct NSUserDefaultsHostObject {
     /// Defaults meant to be seen by all applications.
@@ -51,14 +39,10 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation NSUserDefaults: NSObject
 
 + (id)standardUserDefaults {
-    if let Some(existing) = State::get(env).standard_defaults {
-        existing
-    } else {
+    env.shared_object("+[NSUserDefaults standardUserDefaults]", |env| {
         let defaults = msg![env; this alloc];
-        let defaults = msg![env; defaults init];
-        State::get(env).standard_defaults = Some(defaults);
-        defaults
-    }
+        msg![env; defaults init]
+    })
 }
 
 + (id)allocWithZone:(NSZonePtr)_zone {
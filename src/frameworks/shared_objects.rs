@@ -0,0 +1,62 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Lazily-created objects shared by the whole app, like the ones returned by
+//! `+[NSNotificationCenter defaultCenter]` or `+[NSBundle mainBundle]`. This
+//! isn't a framework, it's a helper for all of them.
+
+use crate::environment::ThreadId;
+use crate::objc::id;
+use crate::Environment;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct State {
+    objects: HashMap<&'static str, SharedObject>,
+}
+
+enum SharedObject {
+    /// The object is being created by this thread.
+    Creating(ThreadId),
+    Created(id),
+}
+
+impl Environment {
+    /// Get the shared object identified by `key`, calling `init` to create it
+    /// the first time. By convention, `key` is the method that returns it, e.g.
+    /// `"+[NSFileManager defaultManager]"`.
+    ///
+    /// `init` can call guest code, which can cause other threads to run before
+    /// it returns. If one of those asks for the same object, it waits for it to
+    /// be created rather than creating another one.
+    ///
+    /// The object is never released, so `init` should return an owned
+    /// reference.
+    pub fn shared_object(&mut self, key: &'static str, init: impl FnOnce(&mut Self) -> id) -> id {
+        loop {
+            match self.framework_state.shared_objects.objects.get(key) {
+                Some(&SharedObject::Created(object)) => return object,
+                Some(&SharedObject::Creating(thread)) => {
+                    assert!(
+                        thread != self.current_thread,
+                        "{} was used while creating itself",
+                        key
+                    );
+                    self.sleep(Duration::from_millis(1), /* tail_call: */ false);
+                }
+                None => break,
+            }
+        }
+
+        let objects = &mut self.framework_state.shared_objects.objects;
+        objects.insert(key, SharedObject::Creating(self.current_thread));
+        let object = init(self);
+        log_dbg!("Created shared object {}: {:?}", key, object);
+        let objects = &mut self.framework_state.shared_objects.objects;
+        objects.insert(key, SharedObject::Created(object));
+        object
+    }
+}
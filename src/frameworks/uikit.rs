@@ -24,6 +24,7 @@ pub mod ui_graphics;
 pub mod ui_image;
 pub mod ui_image_picker_controller;
 pub mod ui_nib;
+pub mod ui_pasteboard;
 pub mod ui_responder;
 pub mod ui_screen;
 pub mod ui_touch;
@@ -38,6 +39,7 @@ pub struct State {
     ui_device: ui_device::State,
     ui_font: ui_font::State,
     ui_graphics: ui_graphics::State,
+    ui_pasteboard: ui_pasteboard::State,
     ui_screen: ui_screen::State,
     ui_touch: ui_touch::State,
     pub ui_view: ui_view::State,
@@ -53,8 +55,9 @@ pub fn handle_events(env: &mut Environment) -> Option<Instant> {
     use crate::window::TextInputEvent;
 
     loop {
-        // NSRunLoop will never call this function in headless mode.
-        let Some(event) = env.window.as_mut().unwrap().pop_event() else {
+        // There is no window in headless mode, but there can still be events
+        // from the input queue.
+        let Some(event) = env.window.as_mut().and_then(|window| window.pop_event()) else {
             break;
         };
 
@@ -120,5 +123,12 @@ pub fn handle_events(env: &mut Environment) -> Option<Instant> {
         }
     }
 
+    // Events pushed by the embedder come after the window's events, which
+    // doesn't matter since they come from different sources anyway.
+    while let Some(event) = env.pop_input_event() {
+        log_dbg!("Dispatching queued input event {:?}", event);
+        ui_touch::handle_event(env, event.into_window_event());
+    }
+
     ui_accelerometer::handle_accelerometer(env)
 }
@@ -18,8 +18,6 @@ use std::time::{Duration, Instant};
 
 #[derive(Default)]
 pub struct State {
-    /// [UIAccelerometer sharedAccelerometer]
-    shared_accelerometer: Option<id>,
     /// Something implementing UIAccelerometerDelegate, weak reference
     delegate: Option<id>,
     update_interval: Option<NSTimeInterval>,
@@ -46,18 +44,9 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation UIAccelerometer: NSObject
 
 + (id)sharedAccelerometer {
-    if let Some(accelerometer) =
-        env.framework_state.uikit.ui_accelerometer.shared_accelerometer {
-        accelerometer
-    } else {
-        let new = env.objc.alloc_static_object(
-            this,
-            Box::new(TrivialHostObject),
-            &mut env.mem
-        );
-        env.framework_state.uikit.ui_accelerometer.shared_accelerometer = Some(new);
-        new
-   }
+    env.shared_object("+[UIAccelerometer sharedAccelerometer]", |env| {
+        env.objc.alloc_static_object(this, Box::new(TrivialHostObject), &mut env.mem)
+    })
 }
 - (id)retain { this }
 - (())release {}
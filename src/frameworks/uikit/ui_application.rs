@@ -8,7 +8,10 @@
 use super::ui_device::*;
 use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
 use crate::frameworks::foundation::ns_string::{from_rust_string, get_static_str};
-use crate::frameworks::foundation::{ns_array, ns_string, NSInteger, NSUInteger};
+use crate::frameworks::foundation::{
+    ns_array, ns_process_info, ns_run_loop, ns_string, NSInteger, NSUInteger,
+};
+use crate::libc::stdlib::run_atexit_handlers;
 use crate::mem::MutPtr;
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
@@ -290,8 +293,8 @@ pub(super) fn UIApplicationMain(
             delegate,
             "application:didFinishLaunchingWithOptions:",
         ) {
-            let empty_dict: id = msg_class![env; NSDictionary dictionary];
-            () = msg![env; delegate application:ui_application didFinishLaunchingWithOptions:empty_dict];
+            let launch_options = ns_process_info::launch_options_dict(env);
+            () = msg![env; delegate application:ui_application didFinishLaunchingWithOptions:launch_options];
         } else if env.objc.object_has_method_named(
             &env.mem,
             delegate,
@@ -366,6 +369,10 @@ pub(super) fn exit(env: &mut Environment) {
         let _: () = msg![env; pool drain];
     };
 
+    // The app is terminated with exit() on a real device.
+    run_atexit_handlers(env, None);
+    ns_run_loop::cancel_all_work(env);
+    env.objc.dump_live_objects();
     std::process::exit(0);
 }
 
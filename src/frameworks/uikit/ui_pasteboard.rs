@@ -0,0 +1,166 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIPasteboard`.
+//!
+//! Pasteboards are stored in memory, so their contents don't persist between
+//! runs. Text on the general pasteboard is also copied to and from the host's
+//! clipboard, unless touchHLE is running in headless mode.
+
+use crate::frameworks::foundation::ns_string::{from_rust_string, to_rust_string};
+use crate::frameworks::foundation::{ns_array, ns_string, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, Class, ClassExports, HostObject,
+};
+use crate::Environment;
+
+/// `kUTTypeUTF8PlainText`, the type used for strings.
+const PLAIN_TEXT_TYPE: &str = "public.utf8-plain-text";
+
+#[derive(Default)]
+pub struct State {
+    general_pasteboard: Option<id>,
+}
+
+#[derive(Default)]
+struct UIPasteboardHostObject {
+    /// Pasteboard types and their values (strong references). Setting a value
+    /// replaces all the existing ones, so there's usually only one.
+    items: Vec<(String, id)>,
+}
+impl HostObject for UIPasteboardHostObject {}
+
+fn is_general_pasteboard(env: &Environment, pasteboard: id) -> bool {
+    env.framework_state.uikit.ui_pasteboard.general_pasteboard == Some(pasteboard)
+}
+
+/// Replace the contents of a pasteboard with a single value, which is
+/// retained.
+fn set_item(env: &mut Environment, pasteboard: id, type_: String, value: id) {
+    let value: id = msg![env; value copy];
+    let old = std::mem::replace(
+        &mut env
+            .objc
+            .borrow_mut::<UIPasteboardHostObject>(pasteboard)
+            .items,
+        vec![(type_, value)],
+    );
+    for (_, old_value) in old {
+        release(env, old_value);
+    }
+}
+
+fn get_item(env: &Environment, pasteboard: id, type_: &str) -> Option<id> {
+    let host_object = env.objc.borrow::<UIPasteboardHostObject>(pasteboard);
+    host_object
+        .items
+        .iter()
+        .find(|(item_type, _)| item_type == type_)
+        .map(|&(_, value)| value)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIPasteboard: NSObject
+
++ (id)generalPasteboard {
+    if let Some(pasteboard) = env.framework_state.uikit.ui_pasteboard.general_pasteboard {
+        pasteboard
+    } else {
+        let new = env.objc.alloc_static_object(
+            this,
+            Box::<UIPasteboardHostObject>::default(),
+            &mut env.mem
+        );
+        env.framework_state.uikit.ui_pasteboard.general_pasteboard = Some(new);
+        new
+    }
+}
+
+- (id)string {
+    if is_general_pasteboard(env, this) {
+        if let Some(text) = env.window.as_ref().and_then(|window| window.clipboard_text()) {
+            let text = from_rust_string(env, text);
+            return autorelease(env, text);
+        }
+    }
+    let Some(value) = get_item(env, this, PLAIN_TEXT_TYPE) else {
+        return nil;
+    };
+    let string_class: Class = msg_class![env; NSString class];
+    if msg![env; value isKindOfClass:string_class] {
+        return value;
+    }
+    // The text may have been set as data.
+    let new: id = msg_class![env; NSString alloc];
+    let new: id = msg![env; new initWithData:value encoding:(ns_string::NSUTF8StringEncoding)];
+    autorelease(env, new)
+}
+- (())setString:(id)string { // NSString*
+    if is_general_pasteboard(env, this) && env.window.is_some() {
+        let text = to_rust_string(env, string);
+        if let Err(e) = env.window().set_clipboard_text(&text) {
+            log!("Couldn't copy text to the host clipboard: {}", e);
+        }
+    }
+    set_item(env, this, PLAIN_TEXT_TYPE.to_string(), string);
+}
+
+- (id)pasteboardTypes {
+    let types: Vec<String> = env
+        .objc
+        .borrow::<UIPasteboardHostObject>(this)
+        .items
+        .iter()
+        .map(|(type_, _)| type_.clone())
+        .collect();
+    let types = types
+        .into_iter()
+        .map(|type_| from_rust_string(env, type_))
+        .collect();
+    let types = ns_array::from_vec(env, types);
+    autorelease(env, types)
+}
+
+- (NSUInteger)numberOfItems {
+    // Each value set replaces the item, so there's at most one.
+    let empty = env.objc.borrow::<UIPasteboardHostObject>(this).items.is_empty();
+    (!empty).into()
+}
+
+- (())setData:(id)data // NSData*
+forPasteboardType:(id)type_ { // NSString*
+    let type_ = to_rust_string(env, type_).into_owned();
+    set_item(env, this, type_, data);
+}
+- (id)dataForPasteboardType:(id)type_ { // NSString*
+    let type_ = to_rust_string(env, type_);
+    let Some(value) = get_item(env, this, &type_) else {
+        return nil;
+    };
+    let data_class: Class = msg_class![env; NSData class];
+    if msg![env; value isKindOfClass:data_class] {
+        value
+    } else {
+        // Other values (e.g. strings) are not converted.
+        nil
+    }
+}
+
+- (())setValue:(id)value
+forPasteboardType:(id)type_ { // NSString*
+    let type_ = to_rust_string(env, type_).into_owned();
+    set_item(env, this, type_, value);
+}
+- (id)valueForPasteboardType:(id)type_ { // NSString*
+    let type_ = to_rust_string(env, type_);
+    get_item(env, this, &type_).unwrap_or(nil)
+}
+
+@end
+
+};
@@ -5,7 +5,7 @@
  */
 //! `UIScreen`.
 
-use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
 use crate::objc::{id, msg, objc_classes, ClassExports, TrivialHostObject};
 
 #[derive(Default)]
@@ -42,12 +42,19 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (CGRect)bounds {
     // TODO: once rotation is supported, this must change with the rotation!
+    // This comes from the options rather than the window, since there might
+    // not be one.
+    let (width, height) = env.options.screen_size;
     CGRect {
         origin: CGPoint { x: 0.0, y: 0.0 },
-        size: CGSize { width: 320.0, height: 480.0 },
+        size: CGSize { width: width as CGFloat, height: height as CGFloat },
     }
 }
 
+- (CGFloat)scale {
+    env.options.screen_scale
+}
+
 - (CGRect)applicationFrame {
     let mut bounds: CGRect = msg![env; this bounds];
     const STATUS_BAR_HEIGHT: f32 = 20.0;
@@ -159,9 +159,18 @@ impl GdbServer {
             Some(CpuError::UndefinedInstruction) | Some(CpuError::Breakpoint) => {
                 self.send_packet("S05"); // SIGTRAP
             }
-            Some(CpuError::MemoryError) => {
+            Some(CpuError::MemoryError) | Some(CpuError::StackOverflow) => {
                 self.send_packet("S0b"); // SIGSEGV
             }
+            Some(CpuError::MisalignedAccess) => {
+                self.send_packet("S0a"); // SIGBUS
+            }
+            Some(CpuError::UnexpectedSvc(_)) => {
+                self.send_packet("S0c"); // SIGSYS
+            }
+            Some(CpuError::PrivilegedInstruction(_)) => {
+                self.send_packet("S04"); // SIGILL
+            }
         }
 
         let do_step = loop {
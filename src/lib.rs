@@ -216,7 +216,10 @@ pub fn main<T: Iterator<Item = String>>(mut args: T) -> Result<(), String> {
 
     echo!("App bundle info:");
     echo!("- Display name: {}", bundle.display_name());
-    echo!("- Version: {}", bundle.bundle_version());
+    echo!(
+        "- Version: {}",
+        bundle.bundle_version().unwrap_or("(not specified)")
+    );
     echo!("- Identifier: {}", app_id);
     if let Some(canonical_name) = bundle.canonical_bundle_name() {
         echo!("- Internal name (canonical): {}.app", canonical_name);
@@ -227,15 +230,29 @@ pub fn main<T: Iterator<Item = String>>(mut args: T) -> Result<(), String> {
         "- Minimum OS version: {}",
         minimum_os_version.unwrap_or("(not specified)")
     );
+    let device_families = bundle.device_families();
+    echo!(
+        "- Device families: {}",
+        device_families
+            .iter()
+            .map(|family| match family {
+                1 => "iPhone/iPod touch".to_string(),
+                2 => "iPad".to_string(),
+                _ => format!("unknown ({})", family),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
     echo!();
 
+    if !device_families.contains(&1) {
+        echo!("Warning: app does not support iPhone or iPod touch. touchHLE only emulates those devices.");
+    }
+
     if let Some(version) = minimum_os_version {
-        let (major, minor_etc) = version.split_once('.').unwrap();
-        let minor = minor_etc
-            .split_once('.')
-            .map_or(minor_etc, |(minor, _etc)| minor);
-        let major: u32 = major.parse().unwrap();
-        let minor: u32 = minor.parse().unwrap();
+        let mut parts = version.split('.').map(|part| part.parse::<u32>().ok());
+        let major = parts.next().flatten().unwrap_or(0);
+        let minor = parts.next().flatten().unwrap_or(0);
         if major > 3 || (major == 3 && minor > 0) {
             echo!("Warning: app requires OS version {}. Only iPhone OS 2.x and iPhone OS 3.0 apps are currently supported.", version);
         }
@@ -303,6 +320,7 @@ pub fn main<T: Iterator<Item = String>>(mut args: T) -> Result<(), String> {
     }
 
     let mut env = Environment::new(bundle, fs, options, env_for_salvage)?;
+    env.install_panic_reporter();
     env.run();
     Ok(())
 }
@@ -11,24 +11,33 @@
 
 mod generic_char;
 
+pub mod aeabi;
+pub mod blocks;
 pub mod clocale;
 pub mod crypto;
 pub mod ctype;
+pub mod cxx_new;
 pub mod cxxabi;
 pub mod dirent;
+pub mod dispatch;
 pub mod dlfcn;
 pub mod errno;
+pub mod execinfo;
 pub mod ifaddrs;
 pub mod keymgr;
 pub mod mach_host;
 pub mod mach_init;
 pub mod mach_semaphore;
+pub mod mach_task_info;
 pub mod mach_thread_info;
 pub mod mach_time;
+pub mod mach_vm;
+pub mod malloc;
 pub mod math;
 pub mod mmap;
 pub mod net;
 pub mod netdb;
+pub mod os_atomic;
 pub mod posix_io;
 pub mod pthread;
 pub mod sched;
@@ -48,11 +57,15 @@ pub mod wchar;
 #[derive(Default)]
 pub struct State {
     dirent: dirent::State,
+    dispatch: dispatch::State,
     keymgr: keymgr::State,
     mach_semaphore: mach_semaphore::State,
+    mach_vm: mach_vm::State,
     posix_io: posix_io::State,
     pub pthread: pthread::State,
     pub semaphore: semaphore::State,
+    signal: signal::State,
+    stdio: stdio::State,
     stdlib: stdlib::State,
     string: string::State,
     time: time::State,
@@ -0,0 +1,238 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Helper functions from the ARM EABI run-time (`__aeabi_*`).
+//!
+//! ARMv6 has no division instructions, and code built without VFP does
+//! floating-point arithmetic in software, so compilers emit calls to helper
+//! functions instead. Apple's toolchain uses libgcc's `__divsi3` and friends,
+//! which come from the bundled `libgcc_s.1.dylib`, but that doesn't have the
+//! `__aeabi_` variants that some EABI-targeting compilers emit.
+//!
+//! Like the default `__aeabi_idiv0` handler, division by zero is not trapped
+//! and gives a quotient and remainder of 0. Division of `i32::MIN` by -1
+//! wraps around to `i32::MIN`.
+//!
+//! The floating-point helpers take and return their values in core registers,
+//! which is also how touchHLE passes `float` and `double` to host functions,
+//! so they can use Rust's native arithmetic. Conversions to integers truncate
+//! and saturate, with NaN becoming 0, which is what Rust's `as` does too.
+//!
+//! Resources:
+//! - [Run-time ABI for the Arm Architecture](https://github.com/ARM-software/abi-aa/blob/main/rtabi32/rtabi32.rst#integer-division-functions)
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::Environment;
+
+/// The `divmod` functions return the quotient in r0 and the remainder in r1,
+/// which is how a 64-bit value is returned.
+fn pack_divmod(quotient: u32, remainder: u32) -> u64 {
+    ((remainder as u64) << 32) | (quotient as u64)
+}
+
+fn __aeabi_idiv(_env: &mut Environment, numerator: i32, denominator: i32) -> i32 {
+    if denominator == 0 {
+        0
+    } else {
+        numerator.wrapping_div(denominator)
+    }
+}
+
+fn __aeabi_uidiv(_env: &mut Environment, numerator: u32, denominator: u32) -> u32 {
+    numerator.checked_div(denominator).unwrap_or(0)
+}
+
+fn __aeabi_idivmod(env: &mut Environment, numerator: i32, denominator: i32) -> u64 {
+    let quotient = __aeabi_idiv(env, numerator, denominator);
+    let remainder = if denominator == 0 {
+        0
+    } else {
+        numerator.wrapping_rem(denominator)
+    };
+    pack_divmod(quotient as u32, remainder as u32)
+}
+
+fn __aeabi_uidivmod(env: &mut Environment, numerator: u32, denominator: u32) -> u64 {
+    let quotient = __aeabi_uidiv(env, numerator, denominator);
+    let remainder = numerator.checked_rem(denominator).unwrap_or(0);
+    pack_divmod(quotient, remainder)
+}
+
+// Floating-point arithmetic
+
+fn __aeabi_dadd(_env: &mut Environment, a: f64, b: f64) -> f64 {
+    a + b
+}
+fn __aeabi_dsub(_env: &mut Environment, a: f64, b: f64) -> f64 {
+    a - b
+}
+fn __aeabi_drsub(_env: &mut Environment, a: f64, b: f64) -> f64 {
+    b - a
+}
+fn __aeabi_dmul(_env: &mut Environment, a: f64, b: f64) -> f64 {
+    a * b
+}
+fn __aeabi_ddiv(_env: &mut Environment, a: f64, b: f64) -> f64 {
+    a / b
+}
+fn __aeabi_fadd(_env: &mut Environment, a: f32, b: f32) -> f32 {
+    a + b
+}
+fn __aeabi_fsub(_env: &mut Environment, a: f32, b: f32) -> f32 {
+    a - b
+}
+fn __aeabi_frsub(_env: &mut Environment, a: f32, b: f32) -> f32 {
+    b - a
+}
+fn __aeabi_fmul(_env: &mut Environment, a: f32, b: f32) -> f32 {
+    a * b
+}
+fn __aeabi_fdiv(_env: &mut Environment, a: f32, b: f32) -> f32 {
+    a / b
+}
+
+// Floating-point comparisons: these return 1 if the comparison is true and 0
+// otherwise, so all but `un` return 0 if either argument is NaN.
+
+fn __aeabi_dcmpeq(_env: &mut Environment, a: f64, b: f64) -> i32 {
+    (a == b).into()
+}
+fn __aeabi_dcmplt(_env: &mut Environment, a: f64, b: f64) -> i32 {
+    (a < b).into()
+}
+fn __aeabi_dcmple(_env: &mut Environment, a: f64, b: f64) -> i32 {
+    (a <= b).into()
+}
+fn __aeabi_dcmpge(_env: &mut Environment, a: f64, b: f64) -> i32 {
+    (a >= b).into()
+}
+fn __aeabi_dcmpgt(_env: &mut Environment, a: f64, b: f64) -> i32 {
+    (a > b).into()
+}
+fn __aeabi_dcmpun(_env: &mut Environment, a: f64, b: f64) -> i32 {
+    (a.is_nan() || b.is_nan()).into()
+}
+fn __aeabi_fcmpeq(_env: &mut Environment, a: f32, b: f32) -> i32 {
+    (a == b).into()
+}
+fn __aeabi_fcmplt(_env: &mut Environment, a: f32, b: f32) -> i32 {
+    (a < b).into()
+}
+fn __aeabi_fcmple(_env: &mut Environment, a: f32, b: f32) -> i32 {
+    (a <= b).into()
+}
+fn __aeabi_fcmpge(_env: &mut Environment, a: f32, b: f32) -> i32 {
+    (a >= b).into()
+}
+fn __aeabi_fcmpgt(_env: &mut Environment, a: f32, b: f32) -> i32 {
+    (a > b).into()
+}
+fn __aeabi_fcmpun(_env: &mut Environment, a: f32, b: f32) -> i32 {
+    (a.is_nan() || b.is_nan()).into()
+}
+
+// Floating-point conversions
+
+fn __aeabi_d2iz(_env: &mut Environment, a: f64) -> i32 {
+    a as i32
+}
+fn __aeabi_d2uiz(_env: &mut Environment, a: f64) -> u32 {
+    a as u32
+}
+fn __aeabi_d2lz(_env: &mut Environment, a: f64) -> i64 {
+    a as i64
+}
+fn __aeabi_d2ulz(_env: &mut Environment, a: f64) -> u64 {
+    a as u64
+}
+fn __aeabi_f2iz(_env: &mut Environment, a: f32) -> i32 {
+    a as i32
+}
+fn __aeabi_f2uiz(_env: &mut Environment, a: f32) -> u32 {
+    a as u32
+}
+fn __aeabi_f2lz(_env: &mut Environment, a: f32) -> i64 {
+    a as i64
+}
+fn __aeabi_f2ulz(_env: &mut Environment, a: f32) -> u64 {
+    a as u64
+}
+fn __aeabi_i2d(_env: &mut Environment, a: i32) -> f64 {
+    a.into()
+}
+fn __aeabi_ui2d(_env: &mut Environment, a: u32) -> f64 {
+    a.into()
+}
+fn __aeabi_l2d(_env: &mut Environment, a: i64) -> f64 {
+    a as f64
+}
+fn __aeabi_ul2d(_env: &mut Environment, a: u64) -> f64 {
+    a as f64
+}
+fn __aeabi_i2f(_env: &mut Environment, a: i32) -> f32 {
+    a as f32
+}
+fn __aeabi_ui2f(_env: &mut Environment, a: u32) -> f32 {
+    a as f32
+}
+fn __aeabi_l2f(_env: &mut Environment, a: i64) -> f32 {
+    a as f32
+}
+fn __aeabi_ul2f(_env: &mut Environment, a: u64) -> f32 {
+    a as f32
+}
+fn __aeabi_f2d(_env: &mut Environment, a: f32) -> f64 {
+    a.into()
+}
+fn __aeabi_d2f(_env: &mut Environment, a: f64) -> f32 {
+    a as f32
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(__aeabi_idiv(_, _)),
+    export_c_func!(__aeabi_uidiv(_, _)),
+    export_c_func!(__aeabi_idivmod(_, _)),
+    export_c_func!(__aeabi_uidivmod(_, _)),
+    export_c_func!(__aeabi_dadd(_, _)),
+    export_c_func!(__aeabi_dsub(_, _)),
+    export_c_func!(__aeabi_drsub(_, _)),
+    export_c_func!(__aeabi_dmul(_, _)),
+    export_c_func!(__aeabi_ddiv(_, _)),
+    export_c_func!(__aeabi_fadd(_, _)),
+    export_c_func!(__aeabi_fsub(_, _)),
+    export_c_func!(__aeabi_frsub(_, _)),
+    export_c_func!(__aeabi_fmul(_, _)),
+    export_c_func!(__aeabi_fdiv(_, _)),
+    export_c_func!(__aeabi_dcmpeq(_, _)),
+    export_c_func!(__aeabi_dcmplt(_, _)),
+    export_c_func!(__aeabi_dcmple(_, _)),
+    export_c_func!(__aeabi_dcmpge(_, _)),
+    export_c_func!(__aeabi_dcmpgt(_, _)),
+    export_c_func!(__aeabi_dcmpun(_, _)),
+    export_c_func!(__aeabi_fcmpeq(_, _)),
+    export_c_func!(__aeabi_fcmplt(_, _)),
+    export_c_func!(__aeabi_fcmple(_, _)),
+    export_c_func!(__aeabi_fcmpge(_, _)),
+    export_c_func!(__aeabi_fcmpgt(_, _)),
+    export_c_func!(__aeabi_fcmpun(_, _)),
+    export_c_func!(__aeabi_d2iz(_)),
+    export_c_func!(__aeabi_d2uiz(_)),
+    export_c_func!(__aeabi_d2lz(_)),
+    export_c_func!(__aeabi_d2ulz(_)),
+    export_c_func!(__aeabi_f2iz(_)),
+    export_c_func!(__aeabi_f2uiz(_)),
+    export_c_func!(__aeabi_f2lz(_)),
+    export_c_func!(__aeabi_f2ulz(_)),
+    export_c_func!(__aeabi_i2d(_)),
+    export_c_func!(__aeabi_ui2d(_)),
+    export_c_func!(__aeabi_l2d(_)),
+    export_c_func!(__aeabi_ul2d(_)),
+    export_c_func!(__aeabi_i2f(_)),
+    export_c_func!(__aeabi_ui2f(_)),
+    export_c_func!(__aeabi_l2f(_)),
+    export_c_func!(__aeabi_ul2f(_)),
+    export_c_func!(__aeabi_f2d(_)),
+    export_c_func!(__aeabi_d2f(_)),
+];
@@ -0,0 +1,310 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Blocks runtime (`Block.h`).
+//!
+//! Only what's needed to call a block from host code, to copy and release it,
+//! and to copy and dispose of the variables it captures, is implemented for
+//! now.
+//!
+//! Resources:
+//! - Clang's [Block Implementation Specification](https://clang.llvm.org/docs/Block-ABI-Apple.html)
+
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::mem::{guest_size_of, ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, Ptr, SafeRead};
+use crate::objc::{release, retain};
+use crate::Environment;
+
+const BLOCK_REFCOUNT_MASK: i32 = 0xfffe;
+const BLOCK_NEEDS_FREE: i32 = 1 << 24;
+const BLOCK_HAS_COPY_DISPOSE: i32 = 1 << 25;
+const BLOCK_IS_GLOBAL: i32 = 1 << 28;
+
+// Flags passed to _Block_object_assign() and _Block_object_dispose() by the
+// copy and dispose helpers, saying what kind of variable is being copied.
+const BLOCK_FIELD_IS_OBJECT: i32 = 3;
+const BLOCK_FIELD_IS_BLOCK: i32 = 7;
+const BLOCK_FIELD_IS_BYREF: i32 = 8;
+const BLOCK_FIELD_IS_WEAK: i32 = 16;
+/// Set when the helpers of a `__block` variable, rather than of a block, are
+/// copying the variable's value. The value then isn't retained or copied.
+const BLOCK_BYREF_CALLER: i32 = 128;
+
+/// The layout of a block in memory. The captured variables, if any, follow
+/// the struct.
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+pub struct Block_literal {
+    /// One of the `_NSConcrete*Block` classes
+    isa: ConstVoidPtr,
+    flags: i32,
+    reserved: i32,
+    /// The block's function. Its first argument is a pointer to the block,
+    /// followed by the block's own arguments.
+    invoke: GuestFunction,
+    descriptor: ConstPtr<Block_descriptor>,
+}
+unsafe impl SafeRead for Block_literal {}
+
+/// The start of the descriptor a block points to. If the block has
+/// `BLOCK_HAS_COPY_DISPOSE` set, this is followed by the copy and dispose
+/// helpers.
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+struct Block_descriptor {
+    reserved: GuestUSize,
+    /// Size of the block, including captured variables.
+    size: GuestUSize,
+}
+unsafe impl SafeRead for Block_descriptor {}
+
+/// The copy and dispose helpers of a block, if it has them. They're needed
+/// when the captured variables include objects or `__block` variables.
+fn copy_dispose_helpers(
+    env: &Environment,
+    block: ConstPtr<Block_literal>,
+) -> Option<(GuestFunction, GuestFunction)> {
+    let Block_literal {
+        flags, descriptor, ..
+    } = env.mem.read(block);
+    if flags & BLOCK_HAS_COPY_DISPOSE == 0 {
+        return None;
+    }
+    let helpers: ConstPtr<GuestFunction> = (descriptor + 1).cast();
+    Some((env.mem.read(helpers), env.mem.read(helpers + 1)))
+}
+
+/// The header of a `__block` variable. The variable itself follows the struct,
+/// after the keep and destroy helpers if the variable has them.
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+struct Block_byref {
+    isa: ConstVoidPtr,
+    /// Points to the copy on the heap once the variable has been copied there,
+    /// otherwise to this struct.
+    forwarding: MutPtr<Block_byref>,
+    flags: i32,
+    /// Size of the variable, including this header.
+    size: GuestUSize,
+}
+unsafe impl SafeRead for Block_byref {}
+
+fn write_flags(env: &mut Environment, block: ConstPtr<Block_literal>, flags: i32) {
+    // The flags follow the isa.
+    let flags_ptr: MutPtr<i32> = (block.cast::<u8>() + 4).cast_mut().cast();
+    env.mem.write(flags_ptr, flags);
+}
+
+/// Get the function to call for a block. Remember to pass the block itself as
+/// the first argument!
+pub fn block_invoke(env: &Environment, block: ConstPtr<Block_literal>) -> GuestFunction {
+    env.mem.read(block).invoke
+}
+
+/// `_Block_copy()`: copy a block to the heap if it's on the stack, or retain
+/// it if it's already on the heap. Global blocks are returned as-is.
+pub fn block_copy(
+    env: &mut Environment,
+    block: ConstPtr<Block_literal>,
+) -> ConstPtr<Block_literal> {
+    if block.is_null() {
+        return block;
+    }
+    let Block_literal {
+        flags, descriptor, ..
+    } = env.mem.read(block);
+    if flags & BLOCK_NEEDS_FREE != 0 {
+        // The refcount is stored doubled, in the bits above the lowest one.
+        assert!(flags & BLOCK_REFCOUNT_MASK != BLOCK_REFCOUNT_MASK);
+        write_flags(env, block, flags + 2);
+        return block;
+    }
+    if flags & BLOCK_IS_GLOBAL != 0 {
+        return block;
+    }
+
+    let size = env.mem.read(descriptor).size;
+    let copy = env.mem.alloc(size);
+    env.mem.memmove(copy, block.cast(), size);
+    let copy: ConstPtr<Block_literal> = copy.cast_const().cast();
+    // TODO: set the isa to _NSConcreteMallocBlock, if blocks are ever treated
+    // as Objective-C objects.
+    write_flags(
+        env,
+        copy,
+        (flags & !BLOCK_REFCOUNT_MASK) | BLOCK_NEEDS_FREE | 2,
+    );
+    if let Some((copy_helper, _)) = copy_dispose_helpers(env, block) {
+        () = copy_helper.call_from_host(env, (copy, block));
+    }
+    log_dbg!("_Block_copy({:?}) => {:?}", block, copy);
+    copy
+}
+
+/// `_Block_release()`: release a block copied with [block_copy], freeing it
+/// once nothing references it any more. Other blocks are left alone.
+pub fn block_release(env: &mut Environment, block: ConstPtr<Block_literal>) {
+    if block.is_null() {
+        return;
+    }
+    let flags = env.mem.read(block).flags;
+    if flags & BLOCK_NEEDS_FREE == 0 {
+        return;
+    }
+    assert!(flags & BLOCK_REFCOUNT_MASK != 0);
+    if flags & BLOCK_REFCOUNT_MASK != 2 {
+        write_flags(env, block, flags - 2);
+        return;
+    }
+    if let Some((_, dispose_helper)) = copy_dispose_helpers(env, block) {
+        () = dispose_helper.call_from_host(env, (block,));
+    }
+    log_dbg!("_Block_release({:?}) freed the block", block);
+    env.mem.free(block.cast_mut().cast());
+}
+
+/// Copy a `__block` variable to the heap, so that it can outlive the stack
+/// frame it was declared in, or retain it if it's already been copied.
+fn byref_copy(env: &mut Environment, byref: MutPtr<Block_byref>) -> MutPtr<Block_byref> {
+    let original = env.mem.read(byref).forwarding;
+    let header = env.mem.read(original);
+    if header.flags & BLOCK_NEEDS_FREE != 0 {
+        assert!(header.flags & BLOCK_REFCOUNT_MASK != BLOCK_REFCOUNT_MASK);
+        let flags = header.flags + 2;
+        env.mem.write(original, Block_byref { flags, ..header });
+        return original;
+    }
+
+    let size = header.size;
+    let copy: MutPtr<Block_byref> = env.mem.alloc(size).cast();
+    // There's one reference for the stack frame, which is dropped when the
+    // variable goes out of scope, and one for the caller.
+    env.mem.write(
+        copy,
+        Block_byref {
+            isa: Ptr::null(),
+            forwarding: copy,
+            flags: (header.flags & !BLOCK_REFCOUNT_MASK) | BLOCK_NEEDS_FREE | 4,
+            size,
+        },
+    );
+    env.mem.write(
+        byref,
+        Block_byref {
+            forwarding: copy,
+            ..header
+        },
+    );
+    if header.flags & BLOCK_HAS_COPY_DISPOSE != 0 {
+        // The keep helper copies the variable itself.
+        let helpers: ConstPtr<GuestFunction> = (byref + 1).cast_const().cast();
+        let copy_helpers: MutPtr<GuestFunction> = (copy + 1).cast();
+        let keep = env.mem.read(helpers);
+        env.mem.write(copy_helpers, keep);
+        env.mem.write(copy_helpers + 1, env.mem.read(helpers + 1));
+        () = keep.call_from_host(env, (copy, byref));
+    } else {
+        env.mem.memmove(
+            (copy + 1).cast_void(),
+            (byref + 1).cast_void().cast_const(),
+            size - guest_size_of::<Block_byref>(),
+        );
+    }
+    log_dbg!("Copied __block variable {:?} to {:?}", byref, copy);
+    copy
+}
+
+/// Release a `__block` variable copied with [byref_copy], freeing it once
+/// nothing references it any more. Variables that weren't copied are left
+/// alone.
+fn byref_release(env: &mut Environment, byref: MutPtr<Block_byref>) {
+    let byref = env.mem.read(byref).forwarding;
+    let header = env.mem.read(byref);
+    if header.flags & BLOCK_NEEDS_FREE == 0 {
+        return;
+    }
+    assert!(header.flags & BLOCK_REFCOUNT_MASK != 0);
+    if header.flags & BLOCK_REFCOUNT_MASK != 2 {
+        let flags = header.flags - 2;
+        env.mem.write(byref, Block_byref { flags, ..header });
+        return;
+    }
+    if header.flags & BLOCK_HAS_COPY_DISPOSE != 0 {
+        let helpers: ConstPtr<GuestFunction> = (byref + 1).cast_const().cast();
+        let destroy = env.mem.read(helpers + 1);
+        () = destroy.call_from_host(env, (byref,));
+    }
+    log_dbg!("Freed __block variable {:?}", byref);
+    env.mem.free(byref.cast_void());
+}
+
+#[allow(non_snake_case)]
+fn _Block_copy(env: &mut Environment, block: ConstPtr<Block_literal>) -> ConstPtr<Block_literal> {
+    block_copy(env, block)
+}
+
+#[allow(non_snake_case)]
+fn _Block_release(env: &mut Environment, block: ConstPtr<Block_literal>) {
+    block_release(env, block)
+}
+
+/// Called by the copy helpers of blocks and `__block` variables to copy a
+/// captured variable into `dest`, see [BLOCK_FIELD_IS_OBJECT] etc.
+#[allow(non_snake_case)]
+fn _Block_object_assign(
+    env: &mut Environment,
+    dest: MutPtr<ConstVoidPtr>,
+    object: ConstVoidPtr,
+    flags: i32,
+) {
+    // Weak references are only relevant with garbage collection, which iPhone
+    // OS doesn't have.
+    let copied = match flags & !BLOCK_FIELD_IS_WEAK {
+        _ if flags & BLOCK_BYREF_CALLER != 0 => object,
+        BLOCK_FIELD_IS_OBJECT => retain(env, object.cast_mut().cast())
+            .cast_void()
+            .cast_const(),
+        BLOCK_FIELD_IS_BLOCK => block_copy(env, object.cast()).cast_void(),
+        BLOCK_FIELD_IS_BYREF => byref_copy(env, object.cast_mut().cast())
+            .cast_void()
+            .cast_const(),
+        _ => unimplemented!("_Block_object_assign() with flags {:#x}", flags),
+    };
+    env.mem.write(dest, copied);
+}
+
+/// Called by the dispose helpers of blocks and `__block` variables to dispose
+/// of a captured variable, undoing [_Block_object_assign].
+#[allow(non_snake_case)]
+fn _Block_object_dispose(env: &mut Environment, object: ConstVoidPtr, flags: i32) {
+    match flags & !BLOCK_FIELD_IS_WEAK {
+        _ if flags & BLOCK_BYREF_CALLER != 0 => (),
+        BLOCK_FIELD_IS_OBJECT => release(env, object.cast_mut().cast()),
+        BLOCK_FIELD_IS_BLOCK => block_release(env, object.cast()),
+        BLOCK_FIELD_IS_BYREF => byref_release(env, object.cast_mut().cast()),
+        _ => unimplemented!("_Block_object_dispose() with flags {:#x}", flags),
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(_Block_copy(_)),
+    export_c_func!(_Block_release(_)),
+    export_c_func!(_Block_object_assign(_, _, _)),
+    export_c_func!(_Block_object_dispose(_, _)),
+];
+
+pub const CONSTANTS: ConstantExports = &[
+    // These are the classes of block objects. touchHLE doesn't treat blocks as
+    // Objective-C objects yet, so they only need to exist.
+    (
+        "__NSConcreteGlobalBlock",
+        HostConstant::Custom(|mem, _| mem.alloc(guest_size_of::<ConstVoidPtr>()).cast_const()),
+    ),
+    (
+        "__NSConcreteStackBlock",
+        HostConstant::Custom(|mem, _| mem.alloc(guest_size_of::<ConstVoidPtr>()).cast_const()),
+    ),
+];
@@ -0,0 +1,65 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! C++ `operator new` and `operator delete` (`<new>`).
+//!
+//! These would normally come from libstdc++, but providing them on the host
+//! (which takes priority over the bundled libstdc++) means C++ allocations
+//! come from the same heap as `malloc()` and show up in its statistics.
+//!
+//! The guest allocator panics if it runs out of memory, so the throwing
+//! variants never throw `std::bad_alloc` and the `nothrow` variants never
+//! return `NULL`.
+
+use crate::dyld::{export_c_func_aliased, FunctionExports};
+use crate::libc::stdlib::set_allocation_call_site;
+use crate::mem::{ConstVoidPtr, GuestUSize, MutVoidPtr};
+use crate::Environment;
+
+/// `operator new(size_t)` and `operator new[](size_t)`
+fn operator_new(env: &mut Environment, size: GuestUSize) -> MutVoidPtr {
+    // A zero-sized allocation still gets a unique non-null pointer, as C++
+    // requires.
+    let res = env.mem.alloc(size);
+    set_allocation_call_site(env, res);
+    res
+}
+
+/// `operator new(size_t, const std::nothrow_t&)` and the array equivalent
+fn operator_new_nothrow(
+    env: &mut Environment,
+    size: GuestUSize,
+    _nothrow: ConstVoidPtr, // const std::nothrow_t&
+) -> MutVoidPtr {
+    operator_new(env, size)
+}
+
+/// `operator delete(void*)` and `operator delete[](void*)`
+fn operator_delete(env: &mut Environment, ptr: MutVoidPtr) {
+    if ptr.is_null() {
+        return;
+    }
+    env.mem.free(ptr);
+}
+
+/// `operator delete(void*, const std::nothrow_t&)` and the array equivalent
+fn operator_delete_nothrow(
+    env: &mut Environment,
+    ptr: MutVoidPtr,
+    _nothrow: ConstVoidPtr, // const std::nothrow_t&
+) {
+    operator_delete(env, ptr)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func_aliased!("_Znwm", operator_new(_)),
+    export_c_func_aliased!("_Znam", operator_new(_)),
+    export_c_func_aliased!("_ZnwmRKSt9nothrow_t", operator_new_nothrow(_, _)),
+    export_c_func_aliased!("_ZnamRKSt9nothrow_t", operator_new_nothrow(_, _)),
+    export_c_func_aliased!("_ZdlPv", operator_delete(_)),
+    export_c_func_aliased!("_ZdaPv", operator_delete(_)),
+    export_c_func_aliased!("_ZdlPvRKSt9nothrow_t", operator_delete_nothrow(_, _)),
+    export_c_func_aliased!("_ZdaPvRKSt9nothrow_t", operator_delete_nothrow(_, _)),
+];
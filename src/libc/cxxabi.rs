@@ -5,36 +5,160 @@
  */
 //! `cxxabi.h`
 //!
+//! C++ exceptions (`__cxa_throw()`, `__cxa_begin_catch()` and friends) aren't
+//! implemented here: apps that use them link to the bundled libstdc++, which
+//! does the throwing, unwinding and catching in the guest. What the host does
+//! provide is [current_exception_type_name], so that when an uncaught
+//! exception ends in `std::terminate()` and `abort()`, the diagnostic can say
+//! what was thrown.
+//!
 //! Resources:
 //! - [Itanium C++ ABI specification](https://itanium-cxx-abi.github.io/cxx-abi/abi.html#dso-dtor-runtime-api)
 
-use crate::abi::GuestFunction;
+use crate::abi::{CallFromHost, GuestFunction};
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::mem::MutVoidPtr;
+use crate::libc::stdlib::{register_atexit_handler, run_atexit_handlers};
+use crate::mem::{ConstPtr, ConstVoidPtr, MutVoidPtr};
 use crate::Environment;
 
 fn __cxa_atexit(
-    _env: &mut Environment,
+    env: &mut Environment,
     func: GuestFunction, // void (*func)(void *)
     p: MutVoidPtr,
     d: MutVoidPtr,
 ) -> i32 {
-    // TODO: when this is implemented, make sure it's properly compatible with
-    // C atexit.
-    log!(
-        "TODO: __cxa_atexit({:?}, {:?}, {:?}) (unimplemented)",
-        func,
-        p,
-        d
-    );
+    register_atexit_handler(env, func, p, d);
     0 // success
 }
 
-fn __cxa_finalize(_env: &mut Environment, d: MutVoidPtr) {
-    log!("TODO: __cxa_finalize({:?}) (unimplemented)", d);
+fn __cxa_finalize(env: &mut Environment, d: MutVoidPtr) {
+    // A null DSO handle means all handlers should be called.
+    run_atexit_handlers(env, (!d.is_null()).then_some(d));
+}
+
+/// If the guest libstdc++ is loaded and an exception is currently being
+/// handled, get the (demangled, if possible) name of its type. This is the
+/// case while `std::terminate()` is running because of an uncaught exception.
+pub fn current_exception_type_name(env: &mut Environment) -> Option<String> {
+    let addr = env
+        .bins
+        .iter()
+        .find_map(|bin| bin.exported_symbols.get("___cxa_current_exception_type"))
+        .copied()?;
+    let type_info: ConstVoidPtr =
+        GuestFunction::from_addr_with_thumb_bit(addr).call_from_host(env, ());
+    if type_info.is_null() {
+        return None;
+    }
+    // std::type_info is a vtable pointer followed by the mangled name.
+    let name: ConstPtr<u8> = env.mem.read(type_info.cast::<ConstPtr<u8>>() + 1);
+    let mangled = env.mem.cstr_at_utf8(name).ok()?;
+    Some(demangle_type_name(mangled).unwrap_or_else(|| mangled.to_string()))
+}
+
+/// Demangle the kinds of type name that are commonly thrown: builtin types,
+/// (possibly nested) class names, and pointers to those. Returns [None] for
+/// anything else.
+fn demangle_type_name(mangled: &str) -> Option<String> {
+    let (name, rest) = demangle_type(mangled.as_bytes())?;
+    rest.is_empty().then_some(name)
+}
+
+fn demangle_type(mangled: &[u8]) -> Option<(String, &[u8])> {
+    let (&first, rest) = mangled.split_first()?;
+    let builtin = match first {
+        b'v' => "void",
+        b'b' => "bool",
+        b'c' => "char",
+        b'a' => "signed char",
+        b'h' => "unsigned char",
+        b's' => "short",
+        b't' => "unsigned short",
+        b'i' => "int",
+        b'j' => "unsigned int",
+        b'l' => "long",
+        b'm' => "unsigned long",
+        b'x' => "long long",
+        b'y' => "unsigned long long",
+        b'f' => "float",
+        b'd' => "double",
+        b'P' => {
+            let (pointee, rest) = demangle_type(rest)?;
+            return Some((format!("{}*", pointee), rest));
+        }
+        b'K' => {
+            let (inner, rest) = demangle_type(rest)?;
+            return Some((format!("{} const", inner), rest));
+        }
+        b'S' if rest.first() == Some(&b't') => {
+            let (name, rest) = demangle_source_name(&rest[1..])?;
+            return Some((format!("std::{}", name), rest));
+        }
+        b'N' => {
+            let mut rest = rest;
+            let mut components = Vec::new();
+            if let Some(after_std) = rest.strip_prefix(b"St") {
+                components.push("std".to_string());
+                rest = after_std;
+            }
+            while rest.first() != Some(&b'E') {
+                let (name, after_name) = demangle_source_name(rest)?;
+                components.push(name);
+                rest = after_name;
+            }
+            return Some((components.join("::"), &rest[1..]));
+        }
+        b'0'..=b'9' => return demangle_source_name(mangled),
+        _ => return None,
+    };
+    Some((builtin.to_string(), rest))
+}
+
+/// Demangle a `<source-name>`, i.e. a length followed by an identifier.
+fn demangle_source_name(mangled: &[u8]) -> Option<(String, &[u8])> {
+    let digits = mangled.iter().take_while(|c| c.is_ascii_digit()).count();
+    let len: usize = std::str::from_utf8(&mangled[..digits]).ok()?.parse().ok()?;
+    let rest = &mangled[digits..];
+    if len == 0 || rest.len() < len {
+        return None;
+    }
+    let name = std::str::from_utf8(&rest[..len]).ok()?;
+    Some((name.to_string(), &rest[len..]))
 }
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(__cxa_atexit(_, _, _)),
     export_c_func!(__cxa_finalize(_)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demangle_type_name() {
+        assert_eq!(demangle_type_name("i").as_deref(), Some("int"));
+        assert_eq!(demangle_type_name("PKc").as_deref(), Some("char const*"));
+        assert_eq!(
+            demangle_type_name("9Exception").as_deref(),
+            Some("Exception")
+        );
+        assert_eq!(
+            demangle_type_name("St13runtime_error").as_deref(),
+            Some("std::runtime_error")
+        );
+        assert_eq!(
+            demangle_type_name("N3foo3bar5ErrorE").as_deref(),
+            Some("foo::bar::Error")
+        );
+        assert_eq!(
+            demangle_type_name("NSt3foo5ErrorE").as_deref(),
+            Some("std::foo::Error")
+        );
+        // Unsupported or malformed
+        assert_eq!(demangle_type_name("St6vectorIiSaIiEE"), None);
+        assert_eq!(demangle_type_name("5Err"), None);
+        assert_eq!(demangle_type_name("ii"), None);
+        assert_eq!(demangle_type_name(""), None);
+    }
+}
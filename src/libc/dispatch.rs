@@ -0,0 +1,234 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Grand Central Dispatch (`dispatch/*.h`).
+//!
+//! Only `dispatch_once` and dispatch semaphores are implemented so far.
+//!
+//! Waiting is done by polling: the waiting thread sleeps for short intervals,
+//! letting other threads run, until the condition it's waiting for is met.
+//!
+//! Resources:
+//! - Apple's [Dispatch documentation](https://developer.apple.com/documentation/dispatch)
+
+use super::blocks::{block_invoke, Block_literal};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::mem::{guest_size_of, ConstPtr, GuestISize, MutPtr, MutVoidPtr};
+use crate::Environment;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[allow(non_camel_case_types)]
+type dispatch_once_t = GuestISize;
+/// Value of a [dispatch_once_t] while the block is running.
+const ONCE_RUNNING: dispatch_once_t = 1;
+/// Value of a [dispatch_once_t] once the block has run.
+const ONCE_DONE: dispatch_once_t = !0;
+
+/// Nanoseconds since an arbitrary starting point (the same one as
+/// `mach_absolute_time()`), or one of the special values below.
+#[allow(non_camel_case_types)]
+type dispatch_time_t = u64;
+const DISPATCH_TIME_NOW: dispatch_time_t = 0;
+const DISPATCH_TIME_FOREVER: dispatch_time_t = !0;
+
+/// Opaque handle, the host state is in [State].
+#[allow(non_camel_case_types)]
+type dispatch_semaphore_t = MutVoidPtr;
+
+/// Returned by `dispatch_semaphore_wait` on timeout.
+const KERN_OPERATION_TIMED_OUT: GuestISize = 49;
+
+/// How long to sleep between checks when waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(Default)]
+pub struct State {
+    semaphores: HashMap<dispatch_semaphore_t, DispatchSemaphore>,
+}
+
+struct DispatchSemaphore {
+    value: GuestISize,
+    /// Number of threads currently in `dispatch_semaphore_wait`.
+    waiters: u32,
+    retain_count: u32,
+}
+
+/// Polls `ready` until it returns [true], letting other threads run in the
+/// meantime. Returns [false] if `deadline` passes first.
+fn wait_until<F>(env: &mut Environment, deadline: Option<Instant>, mut ready: F) -> bool
+where
+    F: FnMut(&mut Environment) -> bool,
+{
+    loop {
+        if ready(env) {
+            return true;
+        }
+        let now = Instant::now();
+        let sleep_for = match deadline {
+            Some(deadline) if deadline <= now => return false,
+            Some(deadline) => deadline.duration_since(now).min(POLL_INTERVAL),
+            None => POLL_INTERVAL,
+        };
+        env.sleep(sleep_for, /* tail_call: */ false);
+    }
+}
+
+/// The current guest time as a [dispatch_time_t].
+fn guest_now(env: &mut Environment) -> dispatch_time_t {
+    let now = env.replay.instant();
+    now.duration_since(env.startup_time)
+        .as_nanos()
+        .try_into()
+        .unwrap()
+}
+
+/// Convert a [dispatch_time_t] to a host deadline, or [None] for "forever".
+fn deadline_for(env: &mut Environment, when: dispatch_time_t) -> Option<Instant> {
+    if when == DISPATCH_TIME_FOREVER {
+        return None;
+    }
+    let remaining = when.saturating_sub(guest_now(env));
+    Some(Instant::now() + Duration::from_nanos(remaining))
+}
+
+fn dispatch_time(env: &mut Environment, when: dispatch_time_t, delta: i64) -> dispatch_time_t {
+    let when = match when {
+        DISPATCH_TIME_FOREVER => return DISPATCH_TIME_FOREVER,
+        DISPATCH_TIME_NOW => guest_now(env),
+        _ => when,
+    };
+    when.saturating_add_signed(delta)
+        .clamp(1, DISPATCH_TIME_FOREVER - 1)
+}
+
+/// Shared implementation of `dispatch_once` and `dispatch_once_f`.
+fn run_once<F>(env: &mut Environment, predicate: MutPtr<dispatch_once_t>, f: F)
+where
+    F: FnOnce(&mut Environment),
+{
+    match env.mem.read(predicate) {
+        ONCE_DONE => (),
+        0 => {
+            log_dbg!("dispatch_once_t at {:?} running", predicate);
+            env.mem.write(predicate, ONCE_RUNNING);
+            f(env);
+            env.mem.write(predicate, ONCE_DONE);
+        }
+        ONCE_RUNNING => {
+            log_dbg!(
+                "dispatch_once_t at {:?} is running on another thread, waiting",
+                predicate
+            );
+            wait_until(env, None, |env| env.mem.read(predicate) == ONCE_DONE);
+        }
+        other => panic!(
+            "Invalid dispatch_once_t value {:#x} at {:?}",
+            other, predicate
+        ),
+    }
+}
+
+fn dispatch_once(
+    env: &mut Environment,
+    predicate: MutPtr<dispatch_once_t>,
+    block: ConstPtr<Block_literal>,
+) {
+    run_once(env, predicate, |env| {
+        let invoke = block_invoke(env, block);
+        () = invoke.call_from_host(env, (block,));
+    })
+}
+
+fn dispatch_once_f(
+    env: &mut Environment,
+    predicate: MutPtr<dispatch_once_t>,
+    context: MutVoidPtr,
+    function: GuestFunction, // void (*function)(void *)
+) {
+    run_once(env, predicate, |env| {
+        () = function.call_from_host(env, (context,));
+    })
+}
+
+fn dispatch_semaphore_create(env: &mut Environment, value: GuestISize) -> dispatch_semaphore_t {
+    if value < 0 {
+        return MutVoidPtr::null();
+    }
+    let sema = env.mem.alloc(guest_size_of::<GuestISize>());
+    env.libc_state.dispatch.semaphores.insert(
+        sema,
+        DispatchSemaphore {
+            value,
+            waiters: 0,
+            retain_count: 1,
+        },
+    );
+    sema
+}
+
+fn dispatch_semaphore_wait(
+    env: &mut Environment,
+    sema: dispatch_semaphore_t,
+    timeout: dispatch_time_t,
+) -> GuestISize {
+    let deadline = deadline_for(env, timeout);
+    env.libc_state
+        .dispatch
+        .semaphores
+        .get_mut(&sema)
+        .unwrap()
+        .waiters += 1;
+    let available = wait_until(env, deadline, |env| {
+        env.libc_state.dispatch.semaphores[&sema].value > 0
+    });
+    let host_sema = env.libc_state.dispatch.semaphores.get_mut(&sema).unwrap();
+    host_sema.waiters -= 1;
+    if available {
+        host_sema.value -= 1;
+        0
+    } else {
+        KERN_OPERATION_TIMED_OUT
+    }
+}
+
+/// Returns non-zero if a waiting thread will be woken.
+fn dispatch_semaphore_signal(env: &mut Environment, sema: dispatch_semaphore_t) -> GuestISize {
+    let host_sema = env.libc_state.dispatch.semaphores.get_mut(&sema).unwrap();
+    host_sema.value += 1;
+    (host_sema.waiters > 0).into()
+}
+
+fn dispatch_retain(env: &mut Environment, object: MutVoidPtr) {
+    let Some(host_sema) = env.libc_state.dispatch.semaphores.get_mut(&object) else {
+        log!("TODO: dispatch_retain({:?}) for non-semaphore", object);
+        return;
+    };
+    host_sema.retain_count += 1;
+}
+
+fn dispatch_release(env: &mut Environment, object: MutVoidPtr) {
+    let Some(host_sema) = env.libc_state.dispatch.semaphores.get_mut(&object) else {
+        log!("TODO: dispatch_release({:?}) for non-semaphore", object);
+        return;
+    };
+    host_sema.retain_count -= 1;
+    if host_sema.retain_count == 0 {
+        env.libc_state.dispatch.semaphores.remove(&object);
+        env.mem.free(object);
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(dispatch_time(_, _)),
+    export_c_func!(dispatch_once(_, _)),
+    export_c_func!(dispatch_once_f(_, _, _)),
+    export_c_func!(dispatch_semaphore_create(_)),
+    export_c_func!(dispatch_semaphore_wait(_, _)),
+    export_c_func!(dispatch_semaphore_signal(_)),
+    export_c_func!(dispatch_retain(_)),
+    export_c_func!(dispatch_release(_)),
+];
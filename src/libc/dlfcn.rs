@@ -9,32 +9,49 @@ use crate::dyld::{export_c_func, FunctionExports};
 use crate::mem::{ConstPtr, MutVoidPtr, Ptr};
 use crate::Environment;
 
-const ALLOWED_LIBRARIES: [Result<&str, &[u8]>; 2] = [
-    Ok("/usr/lib/libSystem.B.dylib"),
-    Ok("/System/Library/Frameworks/OpenAL.framework/OpenAL"),
-];
-
 const RTLD_DEFAULT: MutVoidPtr = Ptr::from_bits(-2 as _);
 
 fn dlopen(env: &mut Environment, path: ConstPtr<u8>, _mode: i32) -> MutVoidPtr {
     if path.is_null() {
         return RTLD_DEFAULT;
     }
-    // TODO: dlopen() support for real dynamic libraries, and support for all
-    // libraries with host implementations.
-    assert!(ALLOWED_LIBRARIES.contains(&env.mem.cstr_at_utf8(path)));
-    // For convenience, use the path as the handle.
-    // TODO: Find out whether the handle is truly opaque on iPhone OS, and if
-    // not, where it points.
-    path.cast_mut().cast()
+    // TODO: dlopen() support for real dynamic libraries.
+    let path_str = env.mem.cstr_at_utf8(path).ok().map(str::to_string);
+    if let Some(handle) = path_str
+        .as_deref()
+        .and_then(|path| env.dyld.library_handle(&mut env.mem, path))
+    {
+        handle
+    } else {
+        log!(
+            "Warning: dlopen() of unknown library {:?}, returning NULL",
+            env.mem.cstr_at_utf8(path)
+        );
+        Ptr::null()
+    }
 }
 
 fn dlsym(env: &mut Environment, handle: MutVoidPtr, symbol: ConstPtr<u8>) -> MutVoidPtr {
-    assert!(
-        handle == RTLD_DEFAULT || ALLOWED_LIBRARIES.contains(&env.mem.cstr_at_utf8(handle.cast()))
-    );
     // For some reason, the symbols passed to dlsym() don't have the leading _.
     let symbol = format!("_{}", env.mem.cstr_at_utf8(symbol).unwrap());
+    if handle != RTLD_DEFAULT {
+        let path = env
+            .dyld
+            .library_for_handle(handle)
+            .unwrap_or_else(|| panic!("dlsym() with unknown handle {:?}", handle));
+        let Ok(addr) =
+            env.dyld
+                .create_proc_address_in_library(&mut env.mem, &mut env.cpu, path, &symbol)
+        else {
+            log!(
+                "Warning: dlsym() for {} not in {}, returning NULL",
+                symbol,
+                path
+            );
+            return Ptr::null();
+        };
+        return Ptr::from_bits(addr.addr_with_thumb_bit());
+    }
     // TODO: error handling. dlsym() should just return NULL in this case, but
     // currently it's probably more useful to have the emulator crash if there's
     // no symbol found, since it most likely indicates a missing host function.
@@ -46,9 +63,7 @@ fn dlsym(env: &mut Environment, handle: MutVoidPtr, symbol: ConstPtr<u8>) -> Mut
 }
 
 fn dlclose(env: &mut Environment, handle: MutVoidPtr) -> i32 {
-    assert!(
-        handle == RTLD_DEFAULT || ALLOWED_LIBRARIES.contains(&env.mem.cstr_at_utf8(handle.cast()))
-    );
+    assert!(handle == RTLD_DEFAULT || env.dyld.library_for_handle(handle).is_some());
     0 // success
 }
 
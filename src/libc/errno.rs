@@ -12,12 +12,16 @@ use crate::Environment;
 use std::io::Write;
 
 pub const EPERM: i32 = 1;
+pub const ENOENT: i32 = 2;
 pub const ESRCH: i32 = 3;
 pub const EBADF: i32 = 9;
 pub const EDEADLK: i32 = 11;
 pub const EBUSY: i32 = 16;
 pub const EEXIST: i32 = 17;
+pub const ENOTDIR: i32 = 20;
 pub const EINVAL: i32 = 22;
+pub const ERANGE: i32 = 34;
+pub const ENAMETOOLONG: i32 = 63;
 
 #[derive(Default)]
 pub struct State {
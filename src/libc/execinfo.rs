@@ -0,0 +1,111 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `execinfo.h` (`backtrace()` and friends)
+//!
+//! Crash reporters use these. Only exported symbols can be found, so static
+//! functions are attributed to the nearest exported symbol before them.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::environment::{locate_address, AddressLocation};
+use crate::mem::{guest_size_of, ConstPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr};
+use crate::Environment;
+
+/// Formats a line of `backtrace_symbols()`'s output the way Apple's libc does,
+/// e.g. `1   TestApp                             0x00002f05 main + 28`.
+fn format_frame(index: usize, addr: u32, location: Option<AddressLocation>) -> String {
+    let (bin_name, symbol, offset) = match location {
+        Some(AddressLocation {
+            bin_name,
+            symbol: Some((symbol, offset)),
+            ..
+        }) => {
+            // C symbols have a leading underscore that isn't shown.
+            let symbol = symbol.strip_prefix('_').unwrap_or(symbol).to_string();
+            (bin_name, symbol, offset)
+        }
+        Some(AddressLocation { bin_name, .. }) => (bin_name, format!("{:#x}", addr), 0),
+        None => ("???", format!("{:#x}", addr), 0),
+    };
+    format!(
+        "{:<4}{:<35} {:#010x} {} + {}",
+        index, bin_name, addr, symbol, offset
+    )
+}
+
+fn backtrace(env: &mut Environment, buffer: MutPtr<MutVoidPtr>, size: i32) -> i32 {
+    let addrs = env.guest_backtrace();
+    let count = addrs.len().min(size.max(0) as usize);
+    for (i, &addr) in addrs[..count].iter().enumerate() {
+        env.mem
+            .write(buffer + i as GuestUSize, Ptr::from_bits(addr));
+    }
+    count as i32
+}
+
+fn backtrace_symbols(
+    env: &mut Environment,
+    buffer: ConstPtr<MutVoidPtr>,
+    size: i32,
+) -> MutPtr<MutPtr<u8>> {
+    let lines: Vec<String> = (0..size.max(0) as GuestUSize)
+        .map(|i| {
+            let addr = env.mem.read(buffer + i).to_bits();
+            format_frame(i as usize, addr, locate_address(&env.bins, addr))
+        })
+        .collect();
+
+    // The caller frees the result with a single free(), so the strings have to
+    // be in the same allocation as the array of pointers to them.
+    let array_size = lines.len() as GuestUSize * guest_size_of::<MutPtr<u8>>();
+    let strings_size: GuestUSize = lines.iter().map(|line| line.len() as GuestUSize + 1).sum();
+    let array: MutPtr<MutPtr<u8>> = env.mem.alloc(array_size + strings_size).cast();
+    let mut string_ptr: MutPtr<u8> = array.cast::<u8>() + array_size;
+    for (i, line) in lines.iter().enumerate() {
+        env.mem.write(array + i as GuestUSize, string_ptr);
+        env.mem
+            .bytes_at_mut(string_ptr, line.len() as GuestUSize)
+            .copy_from_slice(line.as_bytes());
+        env.mem.write(string_ptr + line.len() as GuestUSize, b'\0');
+        string_ptr += line.len() as GuestUSize + 1;
+    }
+    array
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(backtrace(_, _)),
+    export_c_func!(backtrace_symbols(_, _)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_frame() {
+        let location = AddressLocation {
+            bin_name: "TestApp",
+            section_name: "__text",
+            symbol: Some(("_main", 28)),
+        };
+        assert_eq!(
+            format_frame(1, 0x2f05, Some(location)),
+            "1   TestApp                             0x00002f05 main + 28"
+        );
+        let location = AddressLocation {
+            bin_name: "TestApp",
+            section_name: "__text",
+            symbol: None,
+        };
+        assert_eq!(
+            format_frame(12, 0x1000, Some(location)),
+            "12  TestApp                             0x00001000 0x1000 + 0"
+        );
+        assert_eq!(
+            format_frame(0, 0xdead, None),
+            "0   ???                                 0x0000dead 0xdead + 0"
+        );
+    }
+}
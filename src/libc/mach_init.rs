@@ -7,7 +7,8 @@
 //!
 //! There's not much documentation available for these.
 
-use crate::dyld::{ConstantExports, HostConstant};
+use crate::dyld::{ConstantExports, FunctionExports, HostConstant};
+use crate::{export_c_func, Environment};
 
 use crate::libc::mach_thread_info::mach_port_t;
 
@@ -18,3 +19,11 @@ pub const CONSTANTS: ConstantExports = &[(
     "_mach_task_self_",
     HostConstant::Custom(|mem, _| mem.alloc_and_write(MACH_TASK_SELF).cast_void().cast_const()),
 )];
+
+/// Function form of `mach_task_self_`, which the `mach_task_self()` macro
+/// normally uses instead.
+fn mach_task_self(_env: &mut Environment) -> mach_port_t {
+    MACH_TASK_SELF
+}
+
+pub const FUNCTIONS: FunctionExports = &[export_c_func!(mach_task_self())];
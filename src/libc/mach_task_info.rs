@@ -0,0 +1,112 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `mach/task_info.h`
+//!
+//! Apps mostly use this to find out how much memory they're using.
+
+#![allow(non_camel_case_types)]
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::libc::mach_init::MACH_TASK_SELF;
+use crate::libc::mach_thread_info::{
+    kern_return_t, mach_msg_type_number_t, mach_port_t, natural_t, KERN_SUCCESS,
+};
+use crate::mem::{guest_size_of, HeapStats, MutPtr, SafeRead};
+use crate::Environment;
+
+const KERN_INVALID_ARGUMENT: kern_return_t = 4;
+
+type integer_t = i32;
+type policy_t = i32;
+const POLICY_TIMESHARE: policy_t = 1;
+
+type task_name_t = mach_port_t;
+type task_flavor_t = natural_t;
+type task_info_t = MutPtr<integer_t>;
+
+/// This is `TASK_BASIC_INFO_32`, which is what `TASK_BASIC_INFO` means on a
+/// 32-bit platform.
+const TASK_BASIC_INFO: task_flavor_t = 4;
+
+#[repr(C, packed)]
+struct time_value_t {
+    seconds: integer_t,
+    microseconds: integer_t,
+}
+unsafe impl SafeRead for time_value_t {}
+
+#[repr(C, packed)]
+struct task_basic_info {
+    suspend_count: integer_t,
+    virtual_size: natural_t,
+    resident_size: natural_t,
+    user_time: time_value_t,
+    system_time: time_value_t,
+    policy: policy_t,
+}
+unsafe impl SafeRead for task_basic_info {}
+
+/// Get information about a task. Only the current task can be inspected.
+fn task_info(
+    env: &mut Environment,
+    target_task: task_name_t,
+    flavor: task_flavor_t,
+    task_info_out: task_info_t,
+    task_info_out_count: MutPtr<mach_msg_type_number_t>,
+) -> kern_return_t {
+    assert_eq!(target_task, MACH_TASK_SELF);
+
+    let out_size_available = env.mem.read(task_info_out_count);
+
+    match flavor {
+        TASK_BASIC_INFO => {
+            let out_size_expected =
+                guest_size_of::<task_basic_info>() / guest_size_of::<integer_t>();
+            if out_size_available < out_size_expected {
+                return KERN_INVALID_ARGUMENT;
+            }
+            // There's no paging, so everything allocated counts as resident.
+            // Memory that isn't on the heap, e.g. the binaries and the main
+            // thread's stack, isn't included. The virtual size is all of the
+            // address space that isn't free.
+            let HeapStats {
+                bytes_used,
+                bytes_free,
+                ..
+            } = env.mem.heap_stats();
+            let virtual_size = ((1u64 << 32) - u64::from(bytes_free)).min(u32::MAX.into());
+            env.mem.write(
+                task_info_out.cast(),
+                task_basic_info {
+                    suspend_count: 0,
+                    virtual_size: virtual_size as natural_t,
+                    resident_size: bytes_used,
+                    user_time: time_value_t {
+                        seconds: 0,
+                        microseconds: 0,
+                    },
+                    system_time: time_value_t {
+                        seconds: 0,
+                        microseconds: 0,
+                    },
+                    policy: POLICY_TIMESHARE,
+                },
+            );
+            env.mem.write(task_info_out_count, out_size_expected);
+        }
+        _ => {
+            log!(
+                "TODO: task_info() flavor {}, returning KERN_INVALID_ARGUMENT",
+                flavor
+            );
+            return KERN_INVALID_ARGUMENT;
+        }
+    }
+
+    KERN_SUCCESS
+}
+
+pub const FUNCTIONS: FunctionExports = &[export_c_func!(task_info(_, _, _, _))];
@@ -8,7 +8,6 @@
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::mem::{MutPtr, SafeRead};
 use crate::Environment;
-use std::time::Instant;
 
 #[repr(C, packed)]
 struct struct_mach_timebase_info {
@@ -39,7 +38,7 @@ fn mach_timebase_info(
 /// [mach_timebase_info], should be the absolute time in nanoseconds.
 /// The absolute time is a monotonic clock with an arbitrary starting point.
 fn mach_absolute_time(env: &mut Environment) -> u64 {
-    let now = Instant::now();
+    let now = env.replay.instant();
     now.duration_since(env.startup_time)
         .as_nanos()
         .try_into()
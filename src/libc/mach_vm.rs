@@ -0,0 +1,144 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `mach/vm_map.h`
+//!
+//! Regions are carved out of the same guest address space as the heap, so they
+//! show up in [crate::mem::Mem::dump_regions] like mmap'd files do.
+
+#![allow(non_camel_case_types)]
+
+use std::collections::HashMap;
+
+use crate::dyld::FunctionExports;
+use crate::environment::Environment;
+use crate::export_c_func;
+use crate::libc::mach_init::MACH_TASK_SELF;
+use crate::libc::mach_thread_info::{kern_return_t, mach_port_t, natural_t, KERN_SUCCESS};
+use crate::mem::{GuestUSize, Mem, MutPtr, MutVoidPtr, Protection, Ptr, VAddr};
+
+type vm_map_t = mach_port_t;
+type vm_address_t = natural_t;
+type vm_size_t = natural_t;
+
+const KERN_INVALID_ADDRESS: kern_return_t = 1;
+const KERN_NO_SPACE: kern_return_t = 3;
+const KERN_INVALID_ARGUMENT: kern_return_t = 4;
+
+const VM_FLAGS_ANYWHERE: i32 = 0x1;
+
+#[derive(Default)]
+pub struct State {
+    /// Regions allocated with `vm_allocate`, keyed by their page-aligned base
+    /// address. The value is the heap allocation containing the region, if it
+    /// isn't the region itself, and the region's size.
+    regions: HashMap<VAddr, (Option<MutVoidPtr>, GuestUSize)>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.libc_state.mach_vm
+    }
+}
+
+fn round_to_page(size: GuestUSize) -> Option<GuestUSize> {
+    size.checked_next_multiple_of(Mem::PAGE_SIZE)
+}
+
+fn vm_allocate(
+    env: &mut Environment,
+    target_task: vm_map_t,
+    address: MutPtr<vm_address_t>,
+    size: vm_size_t,
+    flags: i32,
+) -> kern_return_t {
+    assert_eq!(target_task, MACH_TASK_SELF);
+    if flags & !VM_FLAGS_ANYWHERE != 0 {
+        log!("TODO: vm_allocate() flags {:#x} (ignored)", flags);
+    }
+    let anywhere = flags & VM_FLAGS_ANYWHERE != 0;
+
+    let Some(size) = round_to_page(size) else {
+        return KERN_INVALID_ARGUMENT;
+    };
+    if size == 0 {
+        if anywhere {
+            env.mem.write(address, 0);
+        }
+        return KERN_SUCCESS;
+    }
+
+    let (base, alloc) = if anywhere {
+        // The heap only guarantees 16-byte alignment, so over-allocate.
+        let Some(padded_size) = size.checked_add(Mem::PAGE_SIZE) else {
+            return KERN_NO_SPACE;
+        };
+        let alloc = env.mem.alloc(padded_size);
+        let base = alloc.to_bits().next_multiple_of(Mem::PAGE_SIZE);
+        (base, Some(alloc))
+    } else {
+        let base = env.mem.read(address) & !(Mem::PAGE_SIZE - 1);
+        if base < env.mem.null_segment_size() || !env.mem.try_reserve(base, size) {
+            return KERN_NO_SPACE;
+        }
+        // Memory given out by vm_allocate is always zeroed.
+        env.mem.bytes_at_mut(Ptr::from_bits(base), size).fill(0);
+        (base, None)
+    };
+
+    env.mem.add_region(
+        base,
+        size,
+        Protection::READ_WRITE,
+        "vm_allocate'd memory".to_string(),
+    );
+    State::get(env).regions.insert(base, (alloc, size));
+    env.mem.write(address, base);
+    log_dbg!(
+        "vm_allocate({:#x} bytes, flags {:#x}) => {:#x}",
+        size,
+        flags,
+        base
+    );
+    KERN_SUCCESS
+}
+
+fn vm_deallocate(
+    env: &mut Environment,
+    target_task: vm_map_t,
+    address: vm_address_t,
+    size: vm_size_t,
+) -> kern_return_t {
+    assert_eq!(target_task, MACH_TASK_SELF);
+    if size == 0 {
+        return KERN_SUCCESS;
+    }
+
+    let Some((alloc, region_size)) = State::get(env).regions.remove(&address) else {
+        log!(
+            "Warning: vm_deallocate({:#x}, {:#x}) of memory not from vm_allocate() (ignored)",
+            address,
+            size
+        );
+        return KERN_INVALID_ADDRESS;
+    };
+    if round_to_page(size) != Some(region_size) {
+        log!(
+            "TODO: vm_deallocate({:#x}, {:#x}) of part of a {:#x}-byte region, freeing all of it",
+            address,
+            size,
+            region_size
+        );
+    }
+
+    env.mem.remove_region(address);
+    env.mem
+        .free(alloc.unwrap_or_else(|| Ptr::from_bits(address)));
+    KERN_SUCCESS
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(vm_allocate(_, _, _, _)),
+    export_c_func!(vm_deallocate(_, _, _)),
+];
@@ -0,0 +1,94 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `malloc/malloc.h`. The allocation functions themselves are in `stdlib.h`.
+//!
+//! There is only one heap, so every malloc zone is the same zone. Sizes are
+//! those of the underlying chunks, which are rounded up to a multiple of 16
+//! bytes (see [Mem::good_alloc_size]), except with `--heap-canaries`, where
+//! they are the requested sizes.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::mem::{ConstVoidPtr, GuestUSize, HeapStats, Mem, MutPtr, MutVoidPtr, SafeRead};
+use crate::{impl_GuestRet_for_large_struct, Environment};
+
+#[allow(non_camel_case_types)]
+#[derive(Debug)]
+#[repr(C, packed)]
+struct mstats {
+    bytes_total: GuestUSize,
+    chunks_used: GuestUSize,
+    bytes_used: GuestUSize,
+    chunks_free: GuestUSize,
+    bytes_free: GuestUSize,
+}
+unsafe impl SafeRead for mstats {}
+impl_GuestRet_for_large_struct!(mstats);
+
+#[allow(non_camel_case_types)]
+#[derive(Debug)]
+#[repr(C, packed)]
+struct malloc_statistics_t {
+    blocks_in_use: u32,
+    size_in_use: GuestUSize,
+    max_size_in_use: GuestUSize,
+    size_allocated: GuestUSize,
+}
+unsafe impl SafeRead for malloc_statistics_t {}
+
+/// Returns 0 if `ptr` isn't the start of an allocation.
+fn malloc_size(env: &mut Environment, ptr: ConstVoidPtr) -> GuestUSize {
+    env.mem.allocated_size(ptr).unwrap_or(0)
+}
+
+fn malloc_good_size(_env: &mut Environment, size: GuestUSize) -> GuestUSize {
+    Mem::good_alloc_size(size)
+}
+
+fn malloc_zone_statistics(
+    env: &mut Environment,
+    _zone: MutVoidPtr, // malloc_zone_t *, NULL means all zones
+    stats: MutPtr<malloc_statistics_t>,
+) {
+    let HeapStats {
+        chunks_used,
+        bytes_used,
+        ..
+    } = env.mem.heap_stats();
+    env.mem.write(
+        stats,
+        malloc_statistics_t {
+            blocks_in_use: chunks_used,
+            size_in_use: bytes_used,
+            max_size_in_use: env.mem.peak_bytes_used(),
+            // The heap isn't obtained from the system in pieces, so the
+            // closest equivalent is what's in use.
+            size_allocated: bytes_used,
+        },
+    );
+}
+
+fn mstats(env: &mut Environment) -> mstats {
+    let HeapStats {
+        chunks_used,
+        bytes_used,
+        chunks_free,
+        bytes_free,
+    } = env.mem.heap_stats();
+    mstats {
+        bytes_total: bytes_used.saturating_add(bytes_free),
+        chunks_used,
+        bytes_used,
+        chunks_free,
+        bytes_free,
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(malloc_size(_)),
+    export_c_func!(malloc_good_size(_)),
+    export_c_func!(malloc_zone_statistics(_, _)),
+    export_c_func!(mstats()),
+];
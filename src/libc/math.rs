@@ -445,8 +445,8 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(acosf(_)),
     export_c_func!(atan(_)),
     export_c_func!(atanf(_)),
-    export_c_func!(atan2(_, _)),
-    export_c_func!(atan2f(_, _)),
+    export_c_func!(atan2(_, _), arg_words = 4),
+    export_c_func!(atan2f(_, _), arg_words = 2),
     // Hyperbolic functions
     export_c_func!(sinh(_)),
     export_c_func!(sinhf(_)),
@@ -479,8 +479,8 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(ldexpf(_, _)),
     export_c_func!(frexpf(_, _)),
     // Power functions
-    export_c_func!(pow(_, _)),
-    export_c_func!(powf(_, _)),
+    export_c_func!(pow(_, _), arg_words = 4),
+    export_c_func!(powf(_, _), arg_words = 2),
     export_c_func!(sqrt(_)),
     export_c_func!(sqrtf(_)),
     // Nearest integer functions
@@ -496,8 +496,8 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(lrint(_)),
     export_c_func!(lrintf(_)),
     // Remainder functions
-    export_c_func!(fmod(_, _)),
-    export_c_func!(fmodf(_, _)),
+    export_c_func!(fmod(_, _), arg_words = 4),
+    export_c_func!(fmodf(_, _), arg_words = 2),
     // Maximum, minimum and positive difference functions
     export_c_func!(fmax(_, _)),
     export_c_func!(fmaxf(_, _)),
@@ -7,22 +7,28 @@
 use crate::dyld::FunctionExports;
 use crate::environment::Environment;
 use crate::export_c_func;
-use crate::libc::errno::set_errno;
+use crate::libc::errno::{set_errno, EINVAL};
 use crate::libc::posix_io;
 use crate::libc::posix_io::{off_t, FileDescriptor, SEEK_SET};
-use crate::mem::{GuestUSize, MutVoidPtr};
+use crate::mem::{GuestUSize, MutVoidPtr, Protection};
+
+const PROT_READ: i32 = 0x1;
+const PROT_WRITE: i32 = 0x2;
+const PROT_EXEC: i32 = 0x4;
 
 #[allow(dead_code)]
 const MAP_FILE: i32 = 0x0000;
 const MAP_ANON: i32 = 0x1000;
 
+const MAP_FAILED: MutVoidPtr = MutVoidPtr::from_bits(u32::MAX);
+
 /// Our implementation of mmap is really simple: it's just load entirety of
 /// file in memory!
 fn mmap(
     env: &mut Environment,
     addr: MutVoidPtr,
     len: GuestUSize,
-    _prot: i32,
+    prot: i32,
     flags: i32,
     fd: FileDescriptor,
     offset: off_t,
@@ -30,6 +36,11 @@ fn mmap(
     // TODO: handle errno properly
     set_errno(env, 0);
 
+    if len == 0 {
+        set_errno(env, EINVAL);
+        return MAP_FAILED;
+    }
+
     assert!(addr.is_null());
     assert_eq!(offset, 0);
     assert_eq!((flags & MAP_ANON), 0);
@@ -38,6 +49,14 @@ fn mmap(
     let ptr = env.mem.alloc(len);
     let read = posix_io::read(env, fd, ptr, len);
     assert_eq!(read as u32, len);
+    // The protection isn't enforced, but it's useful to know what was asked.
+    let protection = Protection {
+        read: (prot & PROT_READ) != 0,
+        write: (prot & PROT_WRITE) != 0,
+        execute: (prot & PROT_EXEC) != 0,
+    };
+    let name = format!("mmap'd file (fd {})", fd);
+    env.mem.add_region(ptr.to_bits(), len, protection, name);
     ptr
 }
 
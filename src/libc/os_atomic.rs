@@ -0,0 +1,15 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `libkern/OSAtomic.h`.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::Environment;
+
+/// Guest memory accesses are already sequentially consistent (see the memory
+/// model notes in [crate::cpu]), so there's nothing to do here.
+fn OSMemoryBarrier(_env: &mut Environment) {}
+
+pub const FUNCTIONS: FunctionExports = &[export_c_func!(OSMemoryBarrier())];
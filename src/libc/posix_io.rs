@@ -9,8 +9,8 @@ pub mod stat;
 
 use crate::abi::DotDotDot;
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::fs::{GuestFile, GuestOpenOptions, GuestPath};
-use crate::libc::errno::{set_errno, EBADF};
+use crate::fs::{GuestFile, GuestOpenOptions, GuestPath, GuestPathBuf};
+use crate::libc::errno::{set_errno, EBADF, EEXIST, EINVAL, ENOENT, ENOTDIR, ERANGE};
 use crate::mem::{ConstPtr, ConstVoidPtr, GuestISize, GuestUSize, MutPtr, MutVoidPtr, Ptr};
 use crate::Environment;
 use std::io::{Read, Seek, SeekFrom, Write};
@@ -32,6 +32,9 @@ struct PosixFileHostObject {
     file: GuestFile,
     needs_flush: bool,
     reached_eof: bool,
+    /// Path of the file, if it should be deleted when closed. This is used for
+    /// `tmpfile()`, see [remove_on_close].
+    remove_on_close: Option<GuestPathBuf>,
 }
 
 // TODO: stdin/stdout/stderr handling somehow
@@ -106,9 +109,6 @@ pub fn open_direct(env: &mut Environment, path: ConstPtr<u8>, flags: i32) -> Fil
                 | O_EXCL)
             == 0
     );
-    // TODO: exclusive mode not implemented yet
-    assert!(flags & O_EXCL == 0);
-
     if path.is_null() {
         log_dbg!("open({:?}, {:#x}) => -1", path, flags);
         return -1; // TODO: set errno to EFAULT
@@ -158,6 +158,21 @@ pub fn open_direct(env: &mut Environment, path: ConstPtr<u8>, flags: i32) -> Fil
     if flags & O_NOFOLLOW != 0 {
         log!("Ignoring O_NOFOLLOW when opening {:?}", path_string);
     }
+    // Guest threads can't run during a host function, so there's no race
+    // between this check and creating the file.
+    if (flags & O_CREAT) != 0
+        && (flags & O_EXCL) != 0
+        && env.fs.exists(GuestPath::new(&path_string))
+    {
+        log_dbg!(
+            "open({:?} {:?}, {:#x}) => -1, file already exists",
+            path,
+            path_string,
+            flags
+        );
+        set_errno(env, EEXIST);
+        return -1;
+    }
     let res = match env
         .fs
         .open_with_options(GuestPath::new(&path_string), options)
@@ -167,6 +182,7 @@ pub fn open_direct(env: &mut Environment, path: ConstPtr<u8>, flags: i32) -> Fil
                 file,
                 needs_flush,
                 reached_eof: false,
+                remove_on_close: None,
             };
 
             let idx = if let Some(free_idx) = env
@@ -399,7 +415,9 @@ pub fn close(env: &mut Environment, fd: FileDescriptor) -> i32 {
         return 0;
     }
 
-    let result = match env.libc_state.posix_io.files[fd_to_file_idx(fd)].take() {
+    let file = env.libc_state.posix_io.files[fd_to_file_idx(fd)].take();
+    let remove_on_close = file.as_ref().and_then(|file| file.remove_on_close.clone());
+    let result = match file {
         Some(file) => {
             // The actual closing of the file happens implicitly when `file`
             // falls out of scope. The return value is about whether actions
@@ -429,6 +447,13 @@ pub fn close(env: &mut Environment, fd: FileDescriptor) -> i32 {
         }
     };
 
+    // The file has been closed by now, which matters on Windows.
+    if let Some(path) = remove_on_close {
+        if env.fs.remove(&path).is_err() {
+            log!("Warning: close({:?}) couldn't remove {:?}", fd, path);
+        }
+    }
+
     if result == 0 {
         log_dbg!("close({:?}) => 0", fd);
     } else {
@@ -437,6 +462,16 @@ pub fn close(env: &mut Environment, fd: FileDescriptor) -> i32 {
     result
 }
 
+/// Make closing `fd` delete the file at `path`, which should be the path the
+/// file was opened with. This is used for `tmpfile()`.
+pub fn remove_on_close(env: &mut Environment, fd: FileDescriptor, path: GuestPathBuf) {
+    env.libc_state
+        .posix_io
+        .file_for_fd(fd)
+        .unwrap()
+        .remove_on_close = Some(path);
+}
+
 fn rename(env: &mut Environment, old: ConstPtr<u8>, new: ConstPtr<u8>) -> i32 {
     // TODO: handle errno properly
     set_errno(env, 0);
@@ -452,9 +487,11 @@ fn rename(env: &mut Environment, old: ConstPtr<u8>, new: ConstPtr<u8>) -> i32 {
 }
 
 pub fn getcwd(env: &mut Environment, buf_ptr: MutPtr<u8>, buf_size: GuestUSize) -> MutPtr<u8> {
+    set_errno(env, 0);
+
     let working_directory = env.fs.working_directory();
     if !env.fs.is_dir(working_directory) {
-        // TODO: set errno to ENOENT
+        set_errno(env, ENOENT);
         log!(
             "Warning: getcwd({:?}, {:#x}) failed, returning NULL",
             buf_ptr,
@@ -477,7 +514,7 @@ pub fn getcwd(env: &mut Environment, buf_ptr: MutPtr<u8>, buf_size: GuestUSize)
     let res_size: GuestUSize = u32::try_from(working_directory.len()).unwrap() + 1;
 
     if buf_size < res_size {
-        // TODO: set errno to EINVAL or ERANGE as appropriate
+        set_errno(env, if buf_size == 0 { EINVAL } else { ERANGE });
         log!(
             "Warning: getcwd({:?}, {:#x}) failed, returning NULL",
             buf_ptr,
@@ -502,7 +539,6 @@ pub fn getcwd(env: &mut Environment, buf_ptr: MutPtr<u8>, buf_size: GuestUSize)
 }
 
 fn chdir(env: &mut Environment, path_ptr: ConstPtr<u8>) -> i32 {
-    // TODO: handle errno properly
     set_errno(env, 0);
 
     let path = GuestPath::new(env.mem.cstr_at_utf8(path_ptr).unwrap());
@@ -517,7 +553,8 @@ fn chdir(env: &mut Environment, path_ptr: ConstPtr<u8>) -> i32 {
         }
         Err(()) => {
             log!("Warning: chdir({:?}) failed, could not change working directory to {:?}, returning -1", path_ptr, path);
-            // TODO: set errno
+            let errno = if env.fs.exists(path) { ENOTDIR } else { ENOENT };
+            set_errno(env, errno);
             -1
         }
     }
@@ -9,7 +9,7 @@ use crate::abi::GuestFunction;
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::libc::errno::{EDEADLK, EINVAL, ESRCH};
 use crate::libc::mach_host::PAGE_SIZE;
-use crate::mem::{self, ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, SafeRead};
+use crate::mem::{self, ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr, SafeRead};
 use crate::{Environment, ThreadId};
 use std::collections::HashMap;
 
@@ -60,6 +60,9 @@ struct ThreadHostObject {
     thread_id: ThreadId,
     joined_by: Option<ThreadId>,
     attr: pthread_attr_t,
+    /// Set with `pthread_setname_np()` or `-[NSThread setName:]`. Empty if
+    /// there is none.
+    name: Vec<u8>,
 }
 
 /// Arbitrarily-chosen magic number for `pthread_attr_t` (not Apple's).
@@ -160,6 +163,7 @@ pub fn pthread_create(
             thread_id,
             joined_by: None,
             attr,
+            name: Vec::new(),
         },
     );
 
@@ -197,6 +201,7 @@ pub fn pthread_self(env: &mut Environment) -> pthread_t {
                 thread_id: 0,
                 joined_by: None,
                 attr: DEFAULT_ATTR,
+                name: Vec::new(),
             },
         );
         log_dbg!(
@@ -297,6 +302,96 @@ fn pthread_mach_thread_np(env: &mut Environment, thread: pthread_t) -> mach_port
     host_object.thread_id.try_into().unwrap()
 }
 
+/// Get the top (the address just past the highest byte, since stacks grow
+/// downwards) and size of a thread's stack, or zeroes if it has finished.
+///
+/// The main thread's stack ends at the top of the address space, so its top
+/// can't be represented. The highest 16 bytes, which only contain the end of
+/// the argument strings (see [crate::stack::prep_stack_for_start]), are left
+/// out instead.
+fn stack_top_and_size(env: &mut Environment, thread: pthread_t) -> (GuestUSize, GuestUSize) {
+    let thread_id = get_thread_id(env, thread);
+    let Some(stack) = env.thread_stack(thread_id) else {
+        return (0, 0);
+    };
+    let (low, high) = (*stack.start(), *stack.end());
+    let top = high.checked_add(1).unwrap_or(high - 15);
+    (top, top - low)
+}
+
+/// Undocumented Darwin function that returns the top of a thread's stack.
+fn pthread_get_stackaddr_np(env: &mut Environment, thread: pthread_t) -> MutVoidPtr {
+    let (top, _) = stack_top_and_size(env, thread);
+    Ptr::from_bits(top)
+}
+
+/// Undocumented Darwin function that returns the size of a thread's stack.
+fn pthread_get_stacksize_np(env: &mut Environment, thread: pthread_t) -> GuestUSize {
+    let (_, size) = stack_top_and_size(env, thread);
+    size
+}
+
+/// Get the [ThreadId] of the thread a `pthread_t` refers to.
+pub fn get_thread_id(env: &mut Environment, thread: pthread_t) -> ThreadId {
+    State::get(env).threads.get(&thread).unwrap().thread_id
+}
+
+/// Get a thread's name, shared with `NSThread`. Empty if there is none.
+pub fn get_thread_name(env: &mut Environment, thread: pthread_t) -> &[u8] {
+    &State::get(env).threads.get(&thread).unwrap().name
+}
+
+/// Get the name of the thread with a particular [ThreadId], if it has a
+/// `pthread_t` and a name. Unlike [pthread_self], this never allocates.
+pub fn get_thread_name_by_id(env: &mut Environment, thread_id: ThreadId) -> Option<&[u8]> {
+    State::get(env)
+        .threads
+        .values()
+        .find(|host_object| host_object.thread_id == thread_id)
+        .map(|host_object| host_object.name.as_slice())
+        .filter(|name| !name.is_empty())
+}
+
+/// Set a thread's name, shared with `NSThread`.
+pub fn set_thread_name(env: &mut Environment, thread: pthread_t, name: Vec<u8>) {
+    log_dbg!(
+        "Thread {:?} is now named {:?}",
+        thread,
+        String::from_utf8_lossy(&name)
+    );
+    State::get(env).threads.get_mut(&thread).unwrap().name = name;
+}
+
+/// Darwin's version can only name the current thread.
+fn pthread_setname_np(env: &mut Environment, name: ConstPtr<u8>) -> i32 {
+    let name = env.mem.cstr_at(name).to_vec();
+    let thread = pthread_self(env);
+    set_thread_name(env, thread, name);
+    0 // success
+}
+
+fn pthread_getname_np(
+    env: &mut Environment,
+    thread: pthread_t,
+    buf: MutPtr<u8>,
+    len: GuestUSize,
+) -> i32 {
+    if !State::get(env).threads.contains_key(&thread) {
+        return ESRCH;
+    }
+    if len == 0 {
+        return EINVAL;
+    }
+    // Like strlcpy(), this truncates if the buffer is too small.
+    let mut name = get_thread_name(env, thread).to_vec();
+    name.truncate((len - 1) as usize);
+    name.push(b'\0');
+    env.mem
+        .bytes_at_mut(buf, name.len() as GuestUSize)
+        .copy_from_slice(&name);
+    0 // success
+}
+
 fn pthread_getschedparam(
     _env: &mut Environment,
     thread: pthread_t,
@@ -341,6 +436,10 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(pthread_setcanceltype(_, _)),
     export_c_func!(pthread_testcancel()),
     export_c_func!(pthread_mach_thread_np(_)),
+    export_c_func!(pthread_get_stackaddr_np(_)),
+    export_c_func!(pthread_get_stacksize_np(_)),
+    export_c_func!(pthread_setname_np(_)),
+    export_c_func!(pthread_getname_np(_, _, _)),
     export_c_func!(pthread_getschedparam(_, _, _)),
     export_c_func!(pthread_setschedparam(_, _, _)),
 ];
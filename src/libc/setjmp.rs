@@ -75,6 +75,7 @@ fn longjmp(env: &mut Environment, jmp_buf: MutPtr<JmpBuf>, status: u32) {
     regs[crate::cpu::Cpu::LR] = buf.lr;
     env.cpu
         .branch(GuestFunction::from_addr_with_thumb_bit(buf.lr));
+    super::signal::longjmp_happened(env);
 }
 
 pub const FUNCTIONS: FunctionExports = &[export_c_func!(setjmp(_)), export_c_func!(longjmp(_, _))];
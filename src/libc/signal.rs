@@ -3,30 +3,250 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
+//! `signal.h`
+//!
+//! Signals are never sent to the app, with one exception: if the app installs
+//! a handler for `SIGSEGV` or `SIGBUS`, a guest memory fault is delivered to
+//! it, because some apps install such handlers to report their crashes. The
+//! handler is expected to `longjmp()` out or to end the app; if it returns, the
+//! faulting instruction is retried, and if it faults again in the same way, the
+//! fault is treated as though there were no handler. Stack overflows are not
+//! delivered, since there is no alternate signal stack to run the handler on.
 
-use crate::dyld::FunctionExports;
-use crate::environment::Environment;
+use crate::abi::GuestFunction;
+use crate::cpu::Cpu;
+use crate::dyld::{FunctionExports, HostFunction};
+use crate::environment::{Environment, ThreadId};
 use crate::export_c_func;
-use crate::libc::errno::set_errno;
-use crate::mem::{ConstVoidPtr, MutVoidPtr, Ptr};
+use crate::libc::errno::{set_errno, EINVAL};
+use crate::mem::{ConstPtr, GuestUSize, MutPtr, Ptr, SafeRead};
+use std::collections::HashMap;
 
-fn sigaction(env: &mut Environment, signum: i32, act: ConstVoidPtr, oldact: MutVoidPtr) -> i32 {
-    // TODO: handle errno properly
-    set_errno(env, 0);
+const SIGBUS: i32 = 10;
+const SIGSEGV: i32 = 11;
+/// Highest signal number, plus one.
+const NSIG: i32 = 32;
 
-    log!("TODO: sigaction({:?}, {:?}, {:?})", signum, act, oldact);
+const SIG_DFL: u32 = 0;
+const SIG_ERR: u32 = -1i32 as u32;
+
+const SA_SIGINFO: i32 = 0x40;
+
+/// `si_code` value for an access to an unmapped address.
+const SEGV_MAPERR: i32 = 1;
+/// `si_code` value for a misaligned access.
+const BUS_ADRALN: i32 = 1;
+/// `si_code` value for an access to a nonexistent address.
+const BUS_ADRERR: i32 = 2;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, Default)]
+#[repr(C, packed)]
+struct sigaction {
+    /// `sa_handler` or `sa_sigaction`, depending on [SA_SIGINFO].
+    handler: u32,
+    sa_mask: u32,
+    sa_flags: i32,
+}
+unsafe impl SafeRead for sigaction {}
+
+#[allow(non_camel_case_types)]
+#[derive(Default)]
+#[repr(C, packed)]
+struct siginfo_t {
+    si_signo: i32,
+    si_errno: i32,
+    si_code: i32,
+    si_pid: i32,
+    si_uid: u32,
+    si_status: i32,
+    si_addr: u32,
+    si_value: u32,
+    si_band: i32,
+    __pad: [u32; 7],
+}
+unsafe impl SafeRead for siginfo_t {}
+
+/// Registers and CPSR of a thread at the time of a fault.
+type FaultContext = ([u32; 16], u32);
+
+#[derive(Default)]
+pub struct State {
+    /// Actions set with `signal()` or `sigaction()`. Absent means `SIG_DFL`.
+    actions: HashMap<i32, sigaction>,
+    /// Context of the fault each thread's handler is currently handling.
+    delivering: HashMap<ThreadId, FaultContext>,
+    /// Context of the fault each thread's handler returned from most recently,
+    /// so that the same fault is not delivered again when it is retried.
+    returned: HashMap<ThreadId, FaultContext>,
+    /// Function the handler returns to, see [handler_returned].
+    handler_return: Option<GuestFunction>,
+}
+
+fn sigaction(
+    env: &mut Environment,
+    signum: i32,
+    act: ConstPtr<sigaction>,
+    oldact: MutPtr<sigaction>,
+) -> i32 {
+    if !(1..NSIG).contains(&signum) {
+        set_errno(env, EINVAL);
+        return -1;
+    }
+
+    let old = env
+        .libc_state
+        .signal
+        .actions
+        .get(&signum)
+        .copied()
+        .unwrap_or_default();
+    if !act.is_null() {
+        let new = env.mem.read(act);
+        log_dbg!("sigaction({}, {:?}, {:?})", signum, new, oldact);
+        env.libc_state.signal.actions.insert(signum, new);
+    }
+    if !oldact.is_null() {
+        env.mem.write(oldact, old);
+    }
     0
 }
 
-fn signal(env: &mut Environment, signum: i32, handler: MutVoidPtr) -> MutVoidPtr {
-    // TODO: handle errno properly
-    set_errno(env, 0);
+fn signal(env: &mut Environment, signum: i32, handler: u32) -> u32 {
+    if !(1..NSIG).contains(&signum) {
+        set_errno(env, EINVAL);
+        return SIG_ERR;
+    }
 
-    log!("TODO: signal({:?}, {:?})", signum, handler);
-    Ptr::null()
+    log_dbg!("signal({}, {:#x})", signum, handler);
+    let new = sigaction {
+        handler,
+        sa_mask: 0,
+        sa_flags: 0,
+    };
+    let old = env.libc_state.signal.actions.insert(signum, new);
+    old.map_or(SIG_DFL, |old| old.handler)
+}
+
+/// `si_code` value for a memory fault delivered as `signum`.
+fn memory_fault_code(signum: i32, misaligned: bool) -> i32 {
+    match signum {
+        SIGBUS if misaligned => BUS_ADRALN,
+        SIGBUS => BUS_ADRERR,
+        _ => SEGV_MAPERR,
+    }
+}
+
+/// Called when the current thread hits a memory fault. If the app has a
+/// handler for it, this sets up the CPU to run the handler and returns [true].
+/// `misaligned` should be [true] if the access only failed because of strict
+/// alignment checking, in which case `SIGBUS` is preferred.
+pub fn deliver_memory_fault(env: &mut Environment, misaligned: bool) -> bool {
+    let thread = env.current_thread;
+    let context = (*env.cpu.regs(), env.cpu.cpsr());
+    let state = &mut env.libc_state.signal;
+    // A fault inside the handler itself, or the retry of a fault the handler
+    // returned from, can't usefully be delivered.
+    if state.delivering.contains_key(&thread) {
+        return false;
+    }
+    if state.returned.remove(&thread) == Some(context) {
+        return false;
+    }
+    let signums = if misaligned {
+        [SIGBUS, SIGSEGV]
+    } else {
+        [SIGSEGV, SIGBUS]
+    };
+    let Some((signum, action)) = signums.into_iter().find_map(|signum| {
+        state
+            .actions
+            .get(&signum)
+            .filter(|action| action.handler > 1) // not SIG_DFL or SIG_IGN
+            .map(|&action| (signum, action))
+    }) else {
+        return false;
+    };
+
+    let fault_address = env.cpu.fault_address().unwrap_or(0);
+    let handler = GuestFunction::from_addr_with_thumb_bit(action.handler);
+    log!(
+        "Memory fault at {:#x}, delivering signal {} to handler {:?}",
+        fault_address,
+        signum,
+        handler
+    );
+
+    let regs = context.0;
+    env.libc_state.signal.delivering.insert(thread, context);
+
+    // The siginfo is put on the stack of the faulting thread, as the kernel
+    // would do.
+    let size = std::mem::size_of::<siginfo_t>() as GuestUSize;
+    let sp = (regs[Cpu::SP] - size) & !7;
+    let info: MutPtr<siginfo_t> = Ptr::from_bits(sp);
+    env.mem.write(
+        info,
+        siginfo_t {
+            si_signo: signum,
+            si_code: memory_fault_code(signum, misaligned),
+            si_addr: fault_address,
+            ..Default::default()
+        },
+    );
+    if action.sa_flags & SA_SIGINFO == 0 {
+        log_dbg!("Handler for signal {} doesn't use SA_SIGINFO", signum);
+    }
+
+    let handler_return = *env.libc_state.signal.handler_return.get_or_insert_with(|| {
+        let f: HostFunction = &(handler_returned as fn(&mut Environment));
+        env.dyld
+            .create_guest_function(&mut env.mem, "__touchHLE_sigreturn", f)
+    });
+
+    let regs = env.cpu.regs_mut();
+    regs[0] = signum as u32;
+    regs[1] = info.to_bits();
+    regs[2] = 0; // TODO: ucontext_t
+    regs[Cpu::SP] = sp;
+    env.cpu.branch_with_link(handler, handler_return);
+    true
+}
+
+/// Called by [longjmp](super::setjmp), which is how a handler would normally
+/// finish.
+pub fn longjmp_happened(env: &mut Environment) {
+    let thread = env.current_thread;
+    env.libc_state.signal.delivering.remove(&thread);
+}
+
+/// Called if a signal handler returns. This resumes at the faulting
+/// instruction. If that faults again in the same way, the fault won't be
+/// delivered, but later faults will be.
+fn handler_returned(env: &mut Environment) {
+    log!("Signal handler returned, the fault will not be handled again");
+    let thread = env.current_thread;
+    let state = &mut env.libc_state.signal;
+    let (regs, cpsr) = state.delivering.remove(&thread).unwrap();
+    state.returned.insert(thread, (regs, cpsr));
+    *env.cpu.regs_mut() = regs;
+    env.cpu.set_cpsr(cpsr);
 }
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(sigaction(_, _, _)),
     export_c_func!(signal(_, _)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_fault_code() {
+        assert_eq!(memory_fault_code(SIGSEGV, false), SEGV_MAPERR);
+        assert_eq!(memory_fault_code(SIGSEGV, true), SEGV_MAPERR);
+        assert_eq!(memory_fault_code(SIGBUS, false), BUS_ADRERR);
+        assert_eq!(memory_fault_code(SIGBUS, true), BUS_ADRALN);
+    }
+}
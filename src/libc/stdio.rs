@@ -10,8 +10,9 @@ use super::posix_io::{
     STDIN_FILENO, STDOUT_FILENO,
 };
 use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
-use crate::fs::GuestPath;
+use crate::fs::{GuestPath, GuestPathBuf};
 use crate::libc::errno::set_errno;
+use crate::libc::stdlib::{mkstemp, random_temp_name_chars};
 use crate::libc::string::strlen;
 use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr, SafeRead};
 use crate::Environment;
@@ -23,6 +24,16 @@ pub mod printf;
 
 const EOF: i32 = -1;
 
+/// Size of the buffer `tmpnam()` expects, on Apple platforms.
+#[allow(non_upper_case_globals)]
+const L_tmpnam: GuestUSize = 1024;
+
+#[derive(Default)]
+pub struct State {
+    /// Buffer for `tmpnam()` to use when not given one.
+    tmpnam_buffer: Option<MutPtr<u8>>,
+}
+
 #[allow(clippy::upper_case_acronyms)]
 /// C `FILE` struct. This is an opaque type in C, so the definition here is our
 /// own.
@@ -386,6 +397,66 @@ fn remove(env: &mut Environment, path: ConstPtr<u8>) -> i32 {
     }
 }
 
+/// Path in the app's sandbox that `tmpnam()` and `tmpfile()` use, followed by
+/// `random_chars` (or `X`s, for a template).
+fn temp_file_path(env: &Environment, random_chars: &[u8]) -> String {
+    let dir = env.fs.home_directory().join("tmp");
+    format!(
+        "{}/tmp.{}",
+        dir.as_str(),
+        std::str::from_utf8(random_chars).unwrap()
+    )
+}
+
+fn tmpnam(env: &mut Environment, s: MutPtr<u8>) -> MutPtr<u8> {
+    set_errno(env, 0);
+
+    let path = loop {
+        let chars = random_temp_name_chars(env, 10);
+        let path = temp_file_path(env, &chars);
+        if !env.fs.exists(GuestPath::new(&path)) {
+            break path;
+        }
+    };
+    let len: GuestUSize = path.len().try_into().unwrap();
+    assert!(len < L_tmpnam);
+
+    let s = if !s.is_null() {
+        s
+    } else if let Some(buffer) = env.libc_state.stdio.tmpnam_buffer {
+        buffer
+    } else {
+        let buffer = env.mem.alloc(L_tmpnam).cast();
+        env.libc_state.stdio.tmpnam_buffer = Some(buffer);
+        buffer
+    };
+    env.mem
+        .bytes_at_mut(s, len)
+        .copy_from_slice(path.as_bytes());
+    env.mem.write(s + len, b'\0');
+    log_dbg!("tmpnam() => {:?} ({:?})", s, path);
+    s
+}
+
+fn tmpfile(env: &mut Environment) -> MutPtr<FILE> {
+    let template = temp_file_path(env, b"XXXXXXXXXX");
+    let template = env.mem.alloc_and_write_cstr(template.as_bytes());
+    let fd = mkstemp(env, template);
+    let path = env.mem.cstr_at_utf8(template).unwrap().to_string();
+    env.mem.free(template.cast());
+    if fd == -1 {
+        log!("Warning: tmpfile() failed, returning NULL");
+        return Ptr::null();
+    }
+
+    // Real implementations unlink the file right away, but the host might not
+    // allow that while it's open, so it's removed when closed instead.
+    posix_io::remove_on_close(env, fd, GuestPathBuf::from(path));
+    let file = env.mem.alloc_and_write(FILE { fd });
+    log_dbg!("tmpfile() => {:?} (fd {})", file, fd);
+    file
+}
+
 fn setbuf(env: &mut Environment, stream: MutPtr<FILE>, buf: ConstPtr<u8>) {
     // TODO: handle errno properly
     set_errno(env, 0);
@@ -451,6 +522,8 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(puts(_)),
     export_c_func!(putchar(_)),
     export_c_func!(remove(_)),
+    export_c_func!(tmpnam(_)),
+    export_c_func!(tmpfile()),
     export_c_func!(setbuf(_, _)),
     // POSIX-specific functions
     export_c_func!(fileno(_)),
@@ -12,13 +12,10 @@ use crate::libc::clocale::{setlocale, LC_CTYPE};
 use crate::libc::errno::set_errno;
 use crate::libc::posix_io::{STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO};
 use crate::libc::stdio::{fwrite, FILE};
-use crate::libc::stdlib::{atof_inner, strtol_inner, strtoul};
-use crate::libc::string::strlen;
 use crate::libc::wchar::wchar_t;
 use crate::mem::{ConstPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr};
 use crate::objc::{id, msg, nil};
 use crate::Environment;
-use std::collections::HashSet;
 use std::io::Write;
 
 const INTEGER_SPECIFIERS: [u8; 6] = [b'd', b'i', b'o', b'u', b'x', b'X'];
@@ -464,17 +461,36 @@ fn vsnprintf(
     );
 
     let res = printf_inner::<false, _>(env, |mem, idx| mem.read(format + idx), arg);
-    let middle = if ((n - 1) as usize) < res.len() {
-        &res[..(n - 1) as usize]
-    } else {
-        &res[..]
-    };
+    write_truncated(env, dest, n, &res)
+}
 
-    let dest_slice = env.mem.bytes_at_mut(dest, n);
-    for (i, &byte) in middle.iter().chain(b"\0".iter()).enumerate() {
-        dest_slice[i] = byte;
+/// Shared implementation of the `snprintf()` family: writes as much of `res`
+/// as fits in `n` bytes, always followed by a null terminator unless `n` is 0
+/// (in which case `dest` may be null). Returns the length of `res`, i.e. the
+/// length that would have been written if `n` were big enough, not counting
+/// the null terminator.
+fn write_truncated(env: &mut Environment, dest: MutPtr<u8>, n: GuestUSize, res: &[u8]) -> i32 {
+    if n > 0 {
+        let len = res.len().min((n - 1) as usize);
+        let dest_slice = env.mem.bytes_at_mut(dest, len as GuestUSize + 1);
+        dest_slice[..len].copy_from_slice(&res[..len]);
+        dest_slice[len] = b'\0';
     }
+    res.len().try_into().unwrap()
+}
 
+/// Shared implementation of the `sprintf()` family: writes all of `res` and
+/// a null terminator. Returns the length of `res`.
+///
+/// Like the real `sprintf()`, this can't know how big the destination buffer
+/// is, so an app that gets this wrong will have memory after the buffer
+/// overwritten. Only `__sprintf_chk()` can catch this.
+fn write_untruncated(env: &mut Environment, dest: MutPtr<u8>, res: &[u8]) -> i32 {
+    let dest_slice = env
+        .mem
+        .bytes_at_mut(dest, (res.len() + 1).try_into().unwrap());
+    dest_slice[..res.len()].copy_from_slice(res);
+    dest_slice[res.len()] = b'\0';
     res.len().try_into().unwrap()
 }
 
@@ -490,33 +506,38 @@ fn vsprintf(env: &mut Environment, dest: MutPtr<u8>, format: ConstPtr<u8>, arg:
     );
 
     let res = printf_inner::<false, _>(env, |mem, idx| mem.read(format + idx), arg);
-
-    let dest_slice = env
-        .mem
-        .bytes_at_mut(dest, (res.len() + 1).try_into().unwrap());
-    for (i, &byte) in res.iter().chain(b"\0".iter()).enumerate() {
-        dest_slice[i] = byte;
-    }
-
-    res.len().try_into().unwrap()
+    write_untruncated(env, dest, &res)
 }
 
+/// Version of `sprintf()` used when `_FORTIFY_SOURCE` is enabled. `dest_len`
+/// is the size of the destination buffer if the compiler knows it, or
+/// `(size_t)-1` otherwise.
 fn __sprintf_chk(
     env: &mut Environment,
     dest: MutPtr<u8>,
     _flags: i32,
-    strlen: GuestUSize,
+    dest_len: GuestUSize,
     format: ConstPtr<u8>,
     args: DotDotDot,
 ) -> i32 {
-    if strlen == 0 {
-        panic!();
-    }
+    // TODO: handle errno properly
+    set_errno(env, 0);
+
     // TODO: respect flags level
-    // TODO: full overflow check
-    sprintf(env, dest, format, args)
+    let res = printf_inner::<false, _>(env, |mem, idx| mem.read(format + idx), args.start());
+    if res.len() >= dest_len as usize {
+        // The real implementation aborts here too.
+        panic!(
+            "__sprintf_chk(): buffer overflow detected, {} bytes don't fit in {:?} (size {})",
+            res.len() + 1,
+            dest,
+            dest_len
+        );
+    }
+    write_untruncated(env, dest, &res)
 }
 
+/// Beware of buffer overruns, see [write_untruncated].
 fn sprintf(env: &mut Environment, dest: MutPtr<u8>, format: ConstPtr<u8>, args: DotDotDot) -> i32 {
     // TODO: handle errno properly
     set_errno(env, 0);
@@ -529,15 +550,7 @@ fn sprintf(env: &mut Environment, dest: MutPtr<u8>, format: ConstPtr<u8>, args:
     );
 
     let res = printf_inner::<false, _>(env, |mem, idx| mem.read(format + idx), args.start());
-
-    let dest_slice = env
-        .mem
-        .bytes_at_mut(dest, (res.len() + 1).try_into().unwrap());
-    for (i, &byte) in res.iter().chain(b"\0".iter()).enumerate() {
-        dest_slice[i] = byte;
-    }
-
-    res.len().try_into().unwrap()
+    write_untruncated(env, dest, &res)
 }
 
 fn swprintf(
@@ -622,174 +635,366 @@ fn printf(env: &mut Environment, format: ConstPtr<u8>, args: DotDotDot) -> i32 {
 
 // TODO: more printf variants
 
-fn sscanf_common(
-    env: &mut Environment,
-    src: ConstPtr<u8>,
-    format: ConstPtr<u8>,
-    mut args: VaList,
-) -> i32 {
-    let mut src_ptr = src.cast_mut();
-    let mut format_char_idx = 0;
+/// Size of the integer a `scanf` conversion writes, from its length modifier.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScanIntSize {
+    Char,
+    Short,
+    Int,
+    LongLong,
+}
 
-    let mut matched_args = 0;
+/// A value parsed by [scan], to be written to the next pointer argument.
+#[derive(Debug, PartialEq)]
+enum ScanValue {
+    Int(i64, ScanIntSize),
+    /// `%n`, which doesn't count as an assignment.
+    CharsConsumed(i64, ScanIntSize),
+    /// The [bool] is [true] for `double` (`%lf`), [false] for `float`.
+    Float(f64, bool),
+    /// The [bool] is [true] if a null terminator should be written, which is
+    /// the case for everything except `%c`.
+    Bytes(Vec<u8>, bool),
+}
 
-    loop {
-        let c = env.mem.read(format + format_char_idx);
-        format_char_idx += 1;
+// Rust's definition of whitespace excludes vertical tab, unlike C's
+fn is_c_space(c: u8) -> bool {
+    c.is_ascii_whitespace() || c == b'\x0b'
+}
 
-        if c == b'\0' {
-            break;
+/// Parses an integer like `strtol` would, but without skipping whitespace and
+/// only looking at `field`. A `base` of 0 means automatic base detection.
+/// Returns the value and the number of bytes consumed.
+fn scan_int(field: &[u8], mut base: u32) -> Option<(i64, usize)> {
+    let mut len = 0;
+    let negative = match field.first() {
+        Some(b'-') => {
+            len += 1;
+            true
         }
-        if c != b'%' {
-            if isspace(env, format + format_char_idx - 1) && isspace(env, src_ptr.cast_const()) {
-                while isspace(env, src_ptr.cast_const()) {
-                    src_ptr += 1;
-                }
-                continue;
+        Some(b'+') => {
+            len += 1;
+            false
+        }
+        _ => false,
+    };
+    let leading_zero = field.get(len) == Some(&b'0');
+    let has_0x = leading_zero && matches!(field.get(len + 1), Some(b'x' | b'X'));
+    if base == 0 {
+        base = if has_0x {
+            16
+        } else if leading_zero {
+            8
+        } else {
+            10
+        };
+    }
+    if base == 16 && has_0x {
+        if !field.get(len + 2).is_some_and(u8::is_ascii_hexdigit) {
+            // Only the "0" can be consumed.
+            return Some((0, len + 1));
+        }
+        len += 2;
+    }
+    let digits_start = len;
+    let mut value: i64 = 0;
+    while let Some(digit) = field.get(len).and_then(|&c| (c as char).to_digit(base)) {
+        value = value
+            .saturating_mul(base.into())
+            .saturating_add(digit.into());
+        len += 1;
+    }
+    if len == digits_start {
+        return None;
+    }
+    Some((if negative { -value } else { value }, len))
+}
+
+/// Parses a floating-point number, only looking at `field`. Returns the value
+/// and the number of bytes consumed.
+fn scan_float(field: &[u8]) -> Option<(f64, usize)> {
+    let digits = |from: usize| {
+        field[from..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count()
+    };
+
+    let mut len = 0;
+    if matches!(field.first(), Some(b'-' | b'+')) {
+        len += 1;
+    }
+    let int_digits = digits(len);
+    len += int_digits;
+    let mut frac_digits = 0;
+    if field.get(len) == Some(&b'.') {
+        frac_digits = digits(len + 1);
+        len += 1 + frac_digits;
+    }
+    if int_digits == 0 && frac_digits == 0 {
+        // TODO: infinity, NaN and hexadecimal floats
+        return None;
+    }
+    if matches!(field.get(len), Some(b'e' | b'E')) {
+        let sign_len = usize::from(matches!(field.get(len + 1), Some(b'-' | b'+')));
+        let exponent_digits = digits(len + 1 + sign_len);
+        if exponent_digits > 0 {
+            len += 1 + sign_len + exponent_digits;
+        }
+    }
+    let value = std::str::from_utf8(&field[..len]).unwrap().parse().unwrap();
+    Some((value, len))
+}
+
+/// Parses a `%[` scanset, starting just after the `[`, and advances
+/// `format_idx` past the closing `]`. Returns a function that checks whether a
+/// byte is in the set.
+fn scan_set(format: &[u8], format_idx: &mut usize) -> impl Fn(u8) -> bool {
+    let negated = format.get(*format_idx) == Some(&b'^');
+    if negated {
+        *format_idx += 1;
+    }
+    let start = *format_idx;
+    // A `]` right at the start is part of the set rather than the end of it.
+    if format.get(*format_idx) == Some(&b']') {
+        *format_idx += 1;
+    }
+    while format.get(*format_idx).is_some_and(|&c| c != b']') {
+        *format_idx += 1;
+    }
+    let set = &format[start..*format_idx];
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < set.len() {
+        // A `-` at the start or end of the set is a literal `-`.
+        if i + 2 < set.len() && set[i + 1] == b'-' {
+            ranges.push((set[i], set[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((set[i], set[i]));
+            i += 1;
+        }
+    }
+    // Skip the closing `]`.
+    *format_idx += 1;
+    move |c| ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != negated
+}
+
+/// Parsing implementation for the `scanf` function family, working on host
+/// copies of the input and format strings.
+///
+/// Returns the values to assign, and whether the input ended before the first
+/// conversion (in which case `EOF` should be returned).
+fn scan(input: &[u8], format: &[u8]) -> (Vec<ScanValue>, bool) {
+    let mut values = Vec::new();
+    let mut converted = false;
+    let mut input_idx = 0;
+    let mut format_idx = 0;
+
+    let skip_space = |input_idx: &mut usize| {
+        while input.get(*input_idx).copied().is_some_and(is_c_space) {
+            *input_idx += 1;
+        }
+    };
+
+    while let Some(&c) = format.get(format_idx) {
+        format_idx += 1;
+
+        // Whitespace in the format matches any amount of whitespace, including
+        // none.
+        if is_c_space(c) {
+            skip_space(&mut input_idx);
+            continue;
+        }
+        if c != b'%' || format.get(format_idx) == Some(&b'%') {
+            if c == b'%' {
+                format_idx += 1;
+                skip_space(&mut input_idx);
             }
-            let cc = env.mem.read(src_ptr);
-            if c != cc {
-                return matched_args;
+            match input.get(input_idx) {
+                Some(&cc) if cc == c => input_idx += 1,
+                Some(_) => return (values, false),
+                None => return (values, !converted),
             }
-            src_ptr += 1;
             continue;
         }
 
-        let mut max_width: i32 = 0;
-        while let c @ b'0'..=b'9' = env.mem.read(format + format_char_idx) {
-            max_width = max_width * 10 + (c - b'0') as i32;
-            format_char_idx += 1;
+        let suppress = format.get(format_idx) == Some(&b'*');
+        if suppress {
+            format_idx += 1;
         }
 
-        let length_modifier = if env.mem.read(format + format_char_idx) == b'h' {
-            format_char_idx += 1;
-            Some(b'h')
-        } else {
-            None
+        let mut max_width: usize = 0;
+        while let Some(&c @ b'0'..=b'9') = format.get(format_idx) {
+            max_width = max_width * 10 + usize::from(c - b'0');
+            format_idx += 1;
+        }
+
+        let mut int_size = ScanIntSize::Int;
+        let mut is_double = false;
+        match format.get(format_idx) {
+            Some(b'h') if format.get(format_idx + 1) == Some(&b'h') => {
+                format_idx += 2;
+                int_size = ScanIntSize::Char;
+            }
+            Some(b'h') => {
+                format_idx += 1;
+                int_size = ScanIntSize::Short;
+            }
+            Some(b'l') if format.get(format_idx + 1) == Some(&b'l') => {
+                format_idx += 2;
+                int_size = ScanIntSize::LongLong;
+            }
+            Some(b'l') => {
+                // `long` is the same size as `int` on 32-bit iPhone OS, but
+                // `%lf` means `double`.
+                format_idx += 1;
+                is_double = true;
+            }
+            Some(b'q') => {
+                format_idx += 1;
+                int_size = ScanIntSize::LongLong;
+            }
+            Some(b'L') => {
+                // `long double` is the same as `double` on ARM.
+                format_idx += 1;
+                is_double = true;
+            }
+            // These are all 32-bit on 32-bit iPhone OS.
+            Some(b'j' | b'z' | b't') => format_idx += 1,
+            _ => (),
+        }
+
+        let Some(&specifier) = format.get(format_idx) else {
+            break;
         };
+        format_idx += 1;
 
-        let specifier = env.mem.read(format + format_char_idx);
-        format_char_idx += 1;
+        if specifier == b'n' {
+            if !suppress {
+                values.push(ScanValue::CharsConsumed(input_idx as i64, int_size));
+            }
+            continue;
+        }
 
-        match specifier {
-            b'd' | b'i' => {
-                let base: u32 = if specifier == b'd' {
-                    10
-                } else {
-                    // automatic base detection in strtol
-                    0
-                };
+        if !matches!(specifier, b'c' | b'[') {
+            skip_space(&mut input_idx);
+        }
+        if input_idx == input.len() {
+            return (values, !converted);
+        }
+        let field_end = if max_width == 0 {
+            input.len()
+        } else {
+            input.len().min(input_idx + max_width)
+        };
+        let field = &input[input_idx..field_end];
 
-                match length_modifier {
-                    Some(lm) => {
-                        match lm {
-                            b'h' => {
-                                // signed short* or unsigned short*
-                                match strtol_inner(env, src_ptr.cast_const(), base) {
-                                    Ok((val, len)) => {
-                                        if max_width > 0 {
-                                            assert_eq!(max_width, len as i32);
-                                        }
-                                        src_ptr += len;
-                                        let c_int_ptr: ConstPtr<i16> = args.next(env);
-                                        env.mem
-                                            .write(c_int_ptr.cast_mut(), val.try_into().unwrap());
-                                    }
-                                    Err(_) => break,
-                                }
-                            }
-                            _ => unimplemented!(),
-                        }
-                    }
-                    _ => match strtol_inner(env, src_ptr.cast_const(), base) {
-                        Ok((val, len)) => {
-                            src_ptr += len;
-                            let c_int_ptr: ConstPtr<i32> = args.next(env);
-                            env.mem.write(c_int_ptr.cast_mut(), val);
-                        }
-                        Err(_) => break,
-                    },
-                }
+        let signed_int = |(value, len): (i64, usize)| {
+            let value = if int_size == ScanIntSize::Int {
+                // Out-of-range values are clamped like strtol() does.
+                value.clamp(i32::MIN.into(), i32::MAX.into())
+            } else {
+                value
+            };
+            (ScanValue::Int(value, int_size), len)
+        };
+        let unsigned_int = |(value, len): (i64, usize)| (ScanValue::Int(value, int_size), len);
+
+        let parsed = match specifier {
+            b'd' => scan_int(field, 10).map(signed_int),
+            b'i' => scan_int(field, 0).map(signed_int),
+            b'u' => scan_int(field, 10).map(unsigned_int),
+            b'o' => scan_int(field, 8).map(unsigned_int),
+            b'x' | b'X' | b'p' => scan_int(field, 16).map(unsigned_int),
+            b'f' | b'F' | b'e' | b'E' | b'g' | b'G' => {
+                scan_float(field).map(|(value, len)| (ScanValue::Float(value, is_double), len))
             }
-            b'f' => {
-                assert_eq!(max_width, 0);
-                assert!(length_modifier.is_none());
-                match atof_inner(env, src_ptr.cast_const()) {
-                    Ok((val, len)) => {
-                        src_ptr += len;
-                        let c_int_ptr: ConstPtr<f32> = args.next(env);
-                        env.mem.write(c_int_ptr.cast_mut(), val as f32);
-                    }
-                    Err(_) => break,
-                }
+            b's' => {
+                let len = field.iter().take_while(|&&c| !is_c_space(c)).count();
+                Some((ScanValue::Bytes(field[..len].to_vec(), true), len))
             }
-            b'x' | b'X' => {
-                let c_len: GuestUSize = strlen(env, src_ptr.cast_const());
-                if max_width != 0 {
-                    assert_eq!(c_len, max_width.try_into().unwrap());
+            b'c' => {
+                let len = if max_width == 0 { 1 } else { max_width };
+                if field.len() < len {
+                    return (values, !converted);
                 }
-                let val: u32 = strtoul(env, src_ptr.cast_const(), Ptr::null(), 16);
-                src_ptr += c_len;
-                let c_u32_ptr: ConstPtr<u32> = args.next(env);
-                env.mem.write(c_u32_ptr.cast_mut(), val);
+                Some((ScanValue::Bytes(field[..len].to_vec(), false), len))
             }
             b'[' => {
-                assert_eq!(max_width, 0);
-                assert!(length_modifier.is_none());
-                // TODO: support ranges like [0-9]
-                // [set] case
-                let mut c = env.mem.read(format + format_char_idx);
-                format_char_idx += 1;
-                // TODO: only `not in the set` for a moment
-                assert_eq!(c, b'^');
-                // Build set
-                let mut set: HashSet<u8> = HashSet::new();
-                // TODO: set can contain ']' as well
-                c = env.mem.read(format + format_char_idx);
-                format_char_idx += 1;
-                while c != b']' {
-                    set.insert(c);
-                    c = env.mem.read(format + format_char_idx);
-                    format_char_idx += 1;
-                }
-                let mut dst_ptr: MutPtr<u8> = args.next(env);
-                // Consume `src` while chars are not in the set
-                let mut cc = env.mem.read(src_ptr);
-                src_ptr += 1;
-                // TODO: handle end of src string
-                while !set.contains(&cc) {
-                    env.mem.write(dst_ptr, cc);
-                    dst_ptr += 1;
-                    cc = env.mem.read(src_ptr);
-                    src_ptr += 1;
-                }
-                // we need to backtrack one position
-                src_ptr -= 1;
-                env.mem.write(dst_ptr, b'\0');
-            }
-            b's' => {
-                assert_eq!(max_width, 0);
-                assert!(length_modifier.is_none());
-                let mut dst_ptr: MutPtr<u8> = args.next(env);
-                loop {
-                    if !isspace(env, src_ptr.cast_const()) {
-                        env.mem.write(dst_ptr, env.mem.read(src_ptr));
-                        src_ptr += 1;
-                        dst_ptr += 1;
-                    } else {
-                        break;
-                    }
-                }
-                env.mem.write(dst_ptr, b'\0');
+                let in_set = scan_set(format, &mut format_idx);
+                let len = field.iter().take_while(|&&c| in_set(c)).count();
+                (len > 0).then(|| (ScanValue::Bytes(field[..len].to_vec(), true), len))
             }
             // TODO: more specifiers
             _ => unimplemented!("Format character '{}'", specifier as char),
+        };
+
+        let Some((value, len)) = parsed else {
+            // Matching failure
+            return (values, false);
+        };
+        input_idx += len;
+        converted = true;
+        if !suppress {
+            values.push(value);
         }
+    }
+
+    (values, false)
+}
+
+fn write_scanned_int(env: &mut Environment, ptr: MutVoidPtr, value: i64, size: ScanIntSize) {
+    // Values are truncated to the size of the destination, like a C cast.
+    match size {
+        ScanIntSize::Char => env.mem.write(ptr.cast(), value as u8),
+        ScanIntSize::Short => env.mem.write(ptr.cast(), value as u16),
+        ScanIntSize::Int => env.mem.write(ptr.cast(), value as u32),
+        ScanIntSize::LongLong => env.mem.write(ptr.cast(), value as u64),
+    }
+}
 
+fn sscanf_common(
+    env: &mut Environment,
+    src: ConstPtr<u8>,
+    format: ConstPtr<u8>,
+    mut args: VaList,
+) -> i32 {
+    let input = env.mem.cstr_at(src).to_vec();
+    let format = env.mem.cstr_at(format).to_vec();
+
+    let (values, input_failure) = scan(&input, &format);
+
+    let mut matched_args = 0;
+    for value in values {
+        let ptr: MutVoidPtr = args.next(env);
+        match value {
+            ScanValue::Int(value, size) => write_scanned_int(env, ptr, value, size),
+            ScanValue::CharsConsumed(value, size) => {
+                write_scanned_int(env, ptr, value, size);
+                continue;
+            }
+            ScanValue::Float(value, true) => env.mem.write(ptr.cast(), value),
+            ScanValue::Float(value, false) => env.mem.write(ptr.cast(), value as f32),
+            ScanValue::Bytes(bytes, null_terminate) => {
+                let ptr: MutPtr<u8> = ptr.cast();
+                env.mem
+                    .bytes_at_mut(ptr, bytes.len() as GuestUSize)
+                    .copy_from_slice(&bytes);
+                if null_terminate {
+                    env.mem.write(ptr + bytes.len() as GuestUSize, b'\0');
+                }
+            }
+        }
         matched_args += 1;
     }
 
-    matched_args
+    if input_failure {
+        // EOF
+        -1
+    } else {
+        matched_args
+    }
 }
 
 fn sscanf(env: &mut Environment, src: ConstPtr<u8>, format: ConstPtr<u8>, args: DotDotDot) -> i32 {
@@ -917,10 +1122,84 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(vfprintf(_, _, _)),
 ];
 
-// Helper function, not a part of printf family
-// TODO: write proper libc's isspace()
-pub fn isspace(env: &mut Environment, src: ConstPtr<u8>) -> bool {
-    let c = env.mem.read(src);
-    // Rust's definition of whitespace excludes vertical tab, unlike C's
-    c.is_ascii_whitespace() || c == b'\x0b'
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        use ScanValue::*;
+
+        assert_eq!(
+            scan(b"12 3.5 hello", b"%d %f %s"),
+            (
+                vec![
+                    Int(12, ScanIntSize::Int),
+                    Float(3.5, false),
+                    Bytes(b"hello".to_vec(), true)
+                ],
+                false
+            )
+        );
+        // Matching failure after a partial match
+        assert_eq!(
+            scan(b"12 abc", b"%d %d"),
+            (vec![Int(12, ScanIntSize::Int)], false)
+        );
+        // Input failure before the first conversion
+        assert_eq!(scan(b"  ", b"%d"), (vec![], true));
+        assert_eq!(
+            scan(b"12", b"%d %d"),
+            (vec![Int(12, ScanIntSize::Int)], false)
+        );
+        // Widths
+        assert_eq!(
+            scan(b"12345abcdef", b"%3d%2u%3s%2c"),
+            (
+                vec![
+                    Int(123, ScanIntSize::Int),
+                    Int(45, ScanIntSize::Int),
+                    Bytes(b"abc".to_vec(), true),
+                    Bytes(b"de".to_vec(), false)
+                ],
+                false
+            )
+        );
+        assert_eq!(
+            scan(b"0x1234", b"%4x%x"),
+            (
+                vec![Int(0x12, ScanIntSize::Int), Int(0x34, ScanIntSize::Int)],
+                false
+            )
+        );
+        // `%c` doesn't skip whitespace
+        assert_eq!(
+            scan(b"a b", b"%c%c"),
+            (
+                vec![Bytes(b"a".to_vec(), false), Bytes(b" ".to_vec(), false)],
+                false
+            )
+        );
+        // Length modifiers, `%n` and suppression
+        assert_eq!(
+            scan(b"-1 2.5e1 7 8", b"%hhd %lf %*d %n%lld"),
+            (
+                vec![
+                    Int(-1, ScanIntSize::Char),
+                    Float(25.0, true),
+                    CharsConsumed(11, ScanIntSize::Int),
+                    Int(8, ScanIntSize::LongLong)
+                ],
+                false
+            )
+        );
+        // Scansets
+        assert_eq!(
+            scan(b"a-b]c,d,e", b"%[]a-z-],%[^,]"),
+            (
+                vec![Bytes(b"a-b]c".to_vec(), true), Bytes(b"d".to_vec(), true)],
+                false
+            )
+        );
+    }
 }
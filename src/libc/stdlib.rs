@@ -6,10 +6,15 @@
 //! `stdlib.h`
 
 use crate::abi::{CallFromHost, GuestFunction};
+use crate::cpu::Cpu;
 use crate::dyld::{export_c_func, export_c_func_aliased, FunctionExports};
+use crate::frameworks::foundation::ns_run_loop;
 use crate::fs::{resolve_path, GuestPath};
 use crate::libc::clocale::{setlocale, LC_CTYPE};
-use crate::libc::errno::set_errno;
+use crate::libc::cxxabi::current_exception_type_name;
+use crate::libc::dirent::MAXPATHLEN;
+use crate::libc::errno::{set_errno, EINVAL, ENAMETOOLONG, ENOENT, ENOTDIR};
+use crate::libc::posix_io::{self, FileDescriptor, O_CREAT, O_EXCL, O_RDWR};
 use crate::libc::string::strlen;
 use crate::libc::wchar::wchar_t;
 use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr};
@@ -23,17 +28,39 @@ pub struct State {
     rand: u32,
     random: u32,
     arc4random: u32,
+    atexit_handlers: Vec<AtexitHandler>,
+}
+
+/// A function registered with `atexit()` or `__cxa_atexit()`.
+struct AtexitHandler {
+    func: GuestFunction,
+    /// The argument for a function registered with `__cxa_atexit()`. Functions
+    /// registered with `atexit()` take no arguments, so this is null for them.
+    arg: MutVoidPtr,
+    /// Identifies the binary that registered the function, for
+    /// `__cxa_finalize()`. This is null for `atexit()`.
+    dso_handle: MutVoidPtr,
 }
 
 // Sizes of zero are implementation-defined. macOS will happily give you back
 // an allocation for any of these, so presumably iPhone OS does too.
 // (touchHLE's allocator will round up allocations to at least 16 bytes.)
 
+/// Attribute an allocation to the guest code that called the current host
+/// function, so it can be reported if the allocation's canaries are
+/// overwritten (see [crate::mem::Mem::enable_heap_canaries]).
+pub fn set_allocation_call_site(env: &mut Environment, ptr: MutVoidPtr) {
+    let call_site = env.cpu.regs()[Cpu::LR];
+    env.mem.set_allocation_call_site(ptr, call_site);
+}
+
 fn malloc(env: &mut Environment, size: GuestUSize) -> MutVoidPtr {
     // TODO: handle errno properly
     set_errno(env, 0);
 
-    env.mem.alloc(size)
+    let res = env.mem.alloc(size);
+    set_allocation_call_site(env, res);
+    res
 }
 
 fn calloc(env: &mut Environment, count: GuestUSize, size: GuestUSize) -> MutVoidPtr {
@@ -42,6 +69,7 @@ fn calloc(env: &mut Environment, count: GuestUSize, size: GuestUSize) -> MutVoid
 
     let total = size.checked_mul(count).unwrap();
     let res = env.mem.alloc(total);
+    set_allocation_call_site(env, res);
     env.mem.bytes_at_mut(res.cast(), total).fill(0);
     res
 }
@@ -53,7 +81,9 @@ fn realloc(env: &mut Environment, ptr: MutVoidPtr, size: GuestUSize) -> MutVoidP
     if ptr.is_null() {
         return malloc(env, size);
     }
-    env.mem.realloc(ptr, size)
+    let res = env.mem.realloc(ptr, size);
+    set_allocation_call_site(env, res);
+    res
 }
 
 fn free(env: &mut Environment, ptr: MutVoidPtr) {
@@ -78,15 +108,54 @@ fn free(env: &mut Environment, ptr: MutVoidPtr) {
 }
 
 fn atexit(
-    _env: &mut Environment,
+    env: &mut Environment,
     func: GuestFunction, // void (*func)(void)
 ) -> i32 {
-    // TODO: when this is implemented, make sure it's properly compatible with
-    // __cxa_atexit.
-    log!("TODO: atexit({:?}) (unimplemented)", func);
+    register_atexit_handler(env, func, Ptr::null(), Ptr::null());
     0 // success
 }
 
+/// Shared implementation of `atexit()` and `__cxa_atexit()`.
+pub fn register_atexit_handler(
+    env: &mut Environment,
+    func: GuestFunction,
+    arg: MutVoidPtr,
+    dso_handle: MutVoidPtr,
+) {
+    log_dbg!(
+        "Registering atexit handler {:?}({:?}) for DSO {:?}",
+        func,
+        arg,
+        dso_handle
+    );
+    env.libc_state.stdlib.atexit_handlers.push(AtexitHandler {
+        func,
+        arg,
+        dso_handle,
+    });
+}
+
+/// Call and unregister the functions registered with `atexit()` and
+/// `__cxa_atexit()`, most recently registered first. If `dso_handle` is
+/// [Some], only the functions registered for that DSO are called.
+pub fn run_atexit_handlers(env: &mut Environment, dso_handle: Option<MutVoidPtr>) {
+    // A handler can register more handlers, so the list has to be checked
+    // again after each call.
+    loop {
+        let handlers = &mut env.libc_state.stdlib.atexit_handlers;
+        let Some(idx) = handlers
+            .iter()
+            .rposition(|handler| dso_handle.is_none_or(|d| handler.dso_handle == d))
+        else {
+            break;
+        };
+        let AtexitHandler { func, arg, .. } = handlers.remove(idx);
+        log_dbg!("Calling atexit handler {:?}({:?})", func, arg);
+        // Passing an argument to a function that takes none is harmless.
+        () = func.call_from_host(env, (arg,));
+    }
+}
+
 fn skip_whitespace(env: &mut Environment, s: ConstPtr<u8>) -> ConstPtr<u8> {
     let mut start = s;
     loop {
@@ -174,14 +243,14 @@ fn random(env: &mut Environment) -> i32 {
 
 fn arc4random(env: &mut Environment) -> u32 {
     env.libc_state.stdlib.arc4random = prng(env.libc_state.stdlib.arc4random);
-    env.libc_state.stdlib.arc4random
+    env.replay.random(env.libc_state.stdlib.arc4random)
 }
 
 fn getenv(env: &mut Environment, name: ConstPtr<u8>) -> MutPtr<u8> {
     let name_cstr = env.mem.cstr_at(name);
     let Some(&value) = env.env_vars.get(name_cstr) else {
-        log!(
-            "Warning: getenv() for {:?} ({:?}) unhandled",
+        log_dbg!(
+            "getenv({:?} ({:?})) => NULL",
             name,
             std::str::from_utf8(name_cstr)
         );
@@ -198,10 +267,28 @@ fn getenv(env: &mut Environment, name: ConstPtr<u8>) -> MutPtr<u8> {
     // Caller should not modify the result
     value
 }
+
+/// Checks an environment variable name passed to `setenv` or `unsetenv`,
+/// setting `errno` if it's invalid.
+fn is_valid_env_var_name(env: &mut Environment, name: ConstPtr<u8>) -> bool {
+    if !name.is_null() {
+        let name_cstr = env.mem.cstr_at(name);
+        if !name_cstr.is_empty() && !name_cstr.contains(&b'=') {
+            return true;
+        }
+    }
+    set_errno(env, EINVAL);
+    false
+}
+
 fn setenv(env: &mut Environment, name: ConstPtr<u8>, value: ConstPtr<u8>, overwrite: i32) -> i32 {
     // TODO: handle errno properly
     set_errno(env, 0);
 
+    if !is_valid_env_var_name(env, name) {
+        return -1;
+    }
+
     let name_cstr = env.mem.cstr_at(name);
     if let Some(&existing) = env.env_vars.get(name_cstr) {
         if overwrite == 0 {
@@ -221,14 +308,55 @@ fn setenv(env: &mut Environment, name: ConstPtr<u8>, value: ConstPtr<u8>, overwr
     0 // success
 }
 
+fn unsetenv(env: &mut Environment, name: ConstPtr<u8>) -> i32 {
+    // TODO: handle errno properly
+    set_errno(env, 0);
+
+    if !is_valid_env_var_name(env, name) {
+        return -1;
+    }
+
+    let name_cstr = env.mem.cstr_at(name);
+    if let Some(existing) = env.env_vars.remove(name_cstr) {
+        env.mem.free(existing.cast());
+    }
+    log_dbg!(
+        "Removed environment variable {:?}",
+        std::str::from_utf8(env.mem.cstr_at(name))
+    );
+    0 // success
+}
+
 fn exit(env: &mut Environment, exit_code: i32) {
     // TODO: handle errno properly
     set_errno(env, 0);
 
     echo!("App called exit(), exiting.");
+    run_atexit_handlers(env, None);
+    ns_run_loop::cancel_all_work(env);
+    env.objc.dump_live_objects();
+    std::process::exit(exit_code);
+}
+
+fn _exit(_env: &mut Environment, exit_code: i32) {
+    // Unlike exit(), this doesn't call the atexit handlers.
+    echo!("App called _exit(), exiting.");
     std::process::exit(exit_code);
 }
 
+fn abort(env: &mut Environment) {
+    // TODO: raise SIGABRT, so a signal handler can run
+    // abort() is what std::terminate() calls by default, so this is usually
+    // how an uncaught C++ exception ends.
+    if let Some(type_name) = current_exception_type_name(env) {
+        panic!(
+            "App called abort() because of an uncaught C++ exception of type {}",
+            type_name
+        );
+    }
+    panic!("App called abort()");
+}
+
 fn bsearch(
     env: &mut Environment,
     key: ConstVoidPtr,
@@ -318,25 +446,76 @@ fn strtol(env: &mut Environment, str: ConstPtr<u8>, endptr: MutPtr<MutPtr<u8>>,
     }
 }
 
+/// Helper for [realpath]. Returns the canonical absolute path, or an `errno`
+/// value.
+fn canonicalize_path(env: &Environment, path: &str) -> Result<String, i32> {
+    let mut components = if path.starts_with('/') {
+        Vec::new()
+    } else {
+        resolve_path(GuestPath::new(""), Some(env.fs.working_directory()))
+    };
+    let to_path = |components: &[&str]| format!("/{}", components.join("/"));
+    for component in path.split('/') {
+        if component.is_empty() {
+            continue;
+        }
+        // Even `.` and `..` require the path so far to be a directory.
+        if !env.fs.is_dir(GuestPath::new(&to_path(&components))) {
+            return Err(ENOTDIR);
+        }
+        match component {
+            "." => (),
+            ".." => {
+                components.pop();
+            }
+            _ => {
+                components.push(component);
+                if !env.fs.exists(GuestPath::new(&to_path(&components))) {
+                    return Err(ENOENT);
+                }
+            }
+        }
+    }
+    Ok(to_path(&components))
+}
+
+/// Canonicalizes a path within the guest filesystem. There are no symbolic
+/// links in the guest filesystem, so this only needs to resolve `.`, `..` and
+/// relative paths, but unlike [resolve_path], each component must exist.
 fn realpath(
     env: &mut Environment,
     file_name: ConstPtr<u8>,
     resolve_name: MutPtr<u8>,
 ) -> MutPtr<u8> {
-    assert!(!resolve_name.is_null());
+    set_errno(env, 0);
 
     let file_name_str = env.mem.cstr_at_utf8(file_name).unwrap();
-    // TOD0: resolve symbolic links
-    let resolved = resolve_path(
-        GuestPath::new(file_name_str),
-        Some(env.fs.working_directory()),
-    );
-    let result = format!("/{}", resolved.join("/"));
-    env.mem
-        .bytes_at_mut(resolve_name, result.len() as GuestUSize)
-        .copy_from_slice(result.as_bytes());
-    env.mem
-        .write(resolve_name + result.len() as GuestUSize, b'\0');
+    let result = canonicalize_path(env, file_name_str);
+    let result = match result {
+        Ok(result) => result,
+        Err(errno) => {
+            log_dbg!("realpath file_name '{}' => NULL", file_name_str);
+            set_errno(env, errno);
+            return Ptr::null();
+        }
+    };
+    // Includes space for null terminator
+    if result.len() + 1 > MAXPATHLEN {
+        set_errno(env, ENAMETOOLONG);
+        return Ptr::null();
+    }
+
+    let resolve_name = if resolve_name.is_null() {
+        // POSIX-2008 extension: the result is allocated with malloc().
+        env.mem.alloc_and_write_cstr(result.as_bytes())
+    } else {
+        env.mem
+            .bytes_at_mut(resolve_name, result.len() as GuestUSize)
+            .copy_from_slice(result.as_bytes());
+        env.mem
+            .write(resolve_name + result.len() as GuestUSize, b'\0');
+        resolve_name
+    };
 
     log_dbg!(
         "realpath file_name '{}', resolve_name '{}'",
@@ -347,6 +526,66 @@ fn realpath(
     resolve_name
 }
 
+/// Characters used for the random part of temporary file names.
+const TEMP_NAME_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Generate `count` random characters for a temporary file name. These come
+/// from `arc4random()`, so that replaying an app's inputs reproduces them.
+pub fn random_temp_name_chars(env: &mut Environment, count: GuestUSize) -> Vec<u8> {
+    (0..count)
+        .map(|_| TEMP_NAME_CHARS[arc4random(env) as usize % TEMP_NAME_CHARS.len()])
+        .collect()
+}
+
+/// Also used by `tmpfile()`.
+pub fn mkstemp(env: &mut Environment, template: MutPtr<u8>) -> FileDescriptor {
+    set_errno(env, 0);
+
+    let len = strlen(env, template.cast_const());
+    let x_count = env
+        .mem
+        .bytes_at(template.cast_const(), len)
+        .iter()
+        .rev()
+        .take_while(|&&c| c == b'X')
+        .count() as GuestUSize;
+    if x_count == 0 {
+        log!(
+            "Warning: mkstemp() template {:?} has no trailing Xs, returning -1",
+            env.mem.cstr_at_utf8(template)
+        );
+        set_errno(env, EINVAL);
+        return -1;
+    }
+
+    loop {
+        let chars = random_temp_name_chars(env, x_count);
+        env.mem
+            .bytes_at_mut(template + (len - x_count), x_count)
+            .copy_from_slice(&chars);
+        let fd = posix_io::open_direct(env, template.cast_const(), O_RDWR | O_CREAT | O_EXCL);
+        if fd != -1 {
+            log_dbg!(
+                "mkstemp() created {:?} => {}",
+                env.mem.cstr_at_utf8(template),
+                fd
+            );
+            return fd;
+        }
+        // Only try another name if this one was already taken. Otherwise, the
+        // directory is probably missing, and no name will work.
+        let path = GuestPath::new(env.mem.cstr_at_utf8(template).unwrap());
+        if !env.fs.exists(path) {
+            log!(
+                "Warning: mkstemp() couldn't create {:?}, returning -1",
+                path
+            );
+            set_errno(env, ENOENT);
+            return -1;
+        }
+    }
+}
+
 fn mbstowcs(
     env: &mut Environment,
     pwcs: MutPtr<wchar_t>,
@@ -404,6 +643,8 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(realloc(_, _)),
     export_c_func!(free(_)),
     export_c_func!(atexit(_)),
+    export_c_func!(_exit(_)),
+    export_c_func!(abort()),
     export_c_func!(atoi(_)),
     export_c_func!(atol(_)),
     export_c_func!(atof(_)),
@@ -415,6 +656,7 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(arc4random()),
     export_c_func!(getenv(_)),
     export_c_func!(setenv(_, _, _)),
+    export_c_func!(unsetenv(_)),
     export_c_func!(exit(_)),
     export_c_func!(bsearch(_, _, _, _, _)),
     export_c_func!(strtof(_, _)),
@@ -422,6 +664,7 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(strtol(_, _, _)),
     export_c_func!(realpath(_, _)),
     export_c_func_aliased!("realpath$DARWIN_EXTSN", realpath(_, _)),
+    export_c_func!(mkstemp(_)),
     export_c_func!(mbstowcs(_, _, _)),
     export_c_func!(wcstombs(_, _, _)),
 ];
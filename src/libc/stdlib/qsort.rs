@@ -3,13 +3,21 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! stdlib's qsort
+//! stdlib's qsort and qsort_r
 
 use crate::abi::{CallFromHost, GuestFunction};
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::mem::MutPtr;
+use crate::mem::{MutPtr, MutVoidPtr};
 use crate::Environment;
 
+/// A guest comparison function, with the context pointer if it's from
+/// `qsort_r`.
+#[derive(Copy, Clone)]
+struct Comparator {
+    func: GuestFunction,
+    thunk: Option<MutVoidPtr>,
+}
+
 fn qsort(
     env: &mut Environment,
     base: MutPtr<u8>,
@@ -20,6 +28,30 @@ fn qsort(
     if nitems < 2 {
         return;
     }
+    let compar = Comparator {
+        func: compar,
+        thunk: None,
+    };
+    qsort_rec(env, base, nitems, size, compar, 0, nitems - 1);
+}
+
+/// Apple's `qsort_r`, which is from BSD. Note that glibc's version has a
+/// different argument order.
+fn qsort_r(
+    env: &mut Environment,
+    base: MutPtr<u8>,
+    nitems: u32,
+    size: u32,
+    thunk: MutVoidPtr,
+    compar: GuestFunction, // int (*compar)(void *, const void *, const void*))
+) {
+    if nitems < 2 {
+        return;
+    }
+    let compar = Comparator {
+        func: compar,
+        thunk: Some(thunk),
+    };
     qsort_rec(env, base, nitems, size, compar, 0, nitems - 1);
 }
 
@@ -28,7 +60,7 @@ fn qsort_rec(
     base: MutPtr<u8>,
     nitems: u32,
     size: u32,
-    compar: GuestFunction,
+    compar: Comparator,
     low: u32,
     hi: u32,
 ) {
@@ -55,13 +87,16 @@ fn compare(
     env: &mut Environment,
     base: MutPtr<u8>,
     size: u32,
-    compar: GuestFunction,
+    compar: Comparator,
     i: u32,
     j: u32,
 ) -> i32 {
-    let i_ptr = base + i * size;
-    let j_ptr = base + j * size;
-    compar.call_from_host(env, (i_ptr.cast_const(), j_ptr.cast_const()))
+    let i_ptr = (base + i * size).cast_const();
+    let j_ptr = (base + j * size).cast_const();
+    match compar.thunk {
+        Some(thunk) => compar.func.call_from_host(env, (thunk, i_ptr, j_ptr)),
+        None => compar.func.call_from_host(env, (i_ptr, j_ptr)),
+    }
 }
 
 fn swap_slices(env: &mut Environment, base: MutPtr<u8>, nitems: u32, size: u32, i: u32, j: u32) {
@@ -79,4 +114,7 @@ fn swap_slices(env: &mut Environment, base: MutPtr<u8>, nitems: u32, size: u32,
     left[..size as usize].swap_with_slice(&mut right[..size as usize]);
 }
 
-pub const FUNCTIONS: FunctionExports = &[export_c_func!(qsort(_, _, _, _))];
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(qsort(_, _, _, _)),
+    export_c_func!(qsort_r(_, _, _, _, _)),
+];
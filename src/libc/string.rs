@@ -64,11 +64,27 @@ fn strtok(env: &mut Environment, s: MutPtr<u8>, sep: ConstPtr<u8>) -> MutPtr<u8>
 
 // Functions shared with wchar.rs
 
+// These are implemented directly on slices of guest memory rather than with
+// GenericChar, because they're hot and the byte-by-byte versions are slow.
+
 fn bzero(env: &mut Environment, dest: MutVoidPtr, count: GuestUSize) {
     memset(env, dest, 0, count);
 }
 fn memset(env: &mut Environment, dest: MutVoidPtr, ch: i32, count: GuestUSize) -> MutVoidPtr {
-    GenericChar::<u8>::memset(env, dest.cast(), ch as u8, count).cast()
+    if count != 0 {
+        env.mem.bytes_at_mut(dest.cast(), count).fill(ch as u8);
+    }
+    dest
+}
+fn __memset_chk(
+    env: &mut Environment,
+    dest: MutVoidPtr,
+    ch: i32,
+    count: GuestUSize,
+    dest_size: GuestUSize,
+) -> MutVoidPtr {
+    assert!(count <= dest_size);
+    memset(env, dest, ch, count)
 }
 fn memcpy(
     env: &mut Environment,
@@ -76,7 +92,19 @@ fn memcpy(
     src: ConstVoidPtr,
     size: GuestUSize,
 ) -> MutVoidPtr {
-    GenericChar::<u8>::memcpy(env, dest.cast(), src.cast(), size).cast()
+    // Overlapping copies are undefined behavior for memcpy(), but some apps
+    // do them anyway, so it behaves like memmove().
+    memmove(env, dest, src, size)
+}
+fn __memcpy_chk(
+    env: &mut Environment,
+    dest: MutVoidPtr,
+    src: ConstVoidPtr,
+    size: GuestUSize,
+    dest_size: GuestUSize,
+) -> MutVoidPtr {
+    assert!(size <= dest_size);
+    memcpy(env, dest, src, size)
 }
 fn memmove(
     env: &mut Environment,
@@ -84,19 +112,60 @@ fn memmove(
     src: ConstVoidPtr,
     size: GuestUSize,
 ) -> MutVoidPtr {
-    GenericChar::<u8>::memmove(env, dest.cast(), src.cast(), size).cast()
+    env.mem.memmove(dest, src, size);
+    dest
+}
+fn __memmove_chk(
+    env: &mut Environment,
+    dest: MutVoidPtr,
+    src: ConstVoidPtr,
+    size: GuestUSize,
+    dest_size: GuestUSize,
+) -> MutVoidPtr {
+    assert!(size <= dest_size);
+    memmove(env, dest, src, size)
 }
 fn memchr(env: &mut Environment, string: ConstVoidPtr, c: i32, size: GuestUSize) -> ConstVoidPtr {
-    GenericChar::<u8>::memchr(env, string.cast(), c as u8, size).cast()
+    if size == 0 {
+        return Ptr::null();
+    }
+    let string: ConstPtr<u8> = string.cast();
+    match env
+        .mem
+        .bytes_at(string, size)
+        .iter()
+        .position(|&b| b == c as u8)
+    {
+        Some(idx) => (string + idx as GuestUSize).cast(),
+        None => Ptr::null(),
+    }
 }
 fn memcmp(env: &mut Environment, a: ConstVoidPtr, b: ConstVoidPtr, size: GuestUSize) -> i32 {
-    GenericChar::<u8>::memcmp(env, a.cast(), b.cast(), size)
+    if size == 0 {
+        return 0;
+    }
+    let a = env.mem.bytes_at(a.cast(), size);
+    let b = env.mem.bytes_at(b.cast(), size);
+    // The bytes are compared as unsigned chars. The iOS man page says the
+    // result is the difference between the first differing bytes, rather than
+    // just having the right sign.
+    a.iter()
+        .zip(b)
+        .find(|(a, b)| a != b)
+        .map_or(0, |(&a, &b)| i32::from(a) - i32::from(b))
 }
+// The str* functions for single-byte strings are likewise implemented on
+// slices, and unlike GenericChar's versions, the comparison functions return
+// the difference between the first differing bytes (as unsigned chars).
+
 pub(super) fn strlen(env: &mut Environment, s: ConstPtr<u8>) -> GuestUSize {
-    GenericChar::<u8>::strlen(env, s)
+    env.mem.strnlen_at(s, GuestUSize::MAX)
+}
+fn strnlen(env: &mut Environment, s: ConstPtr<u8>, max_len: GuestUSize) -> GuestUSize {
+    env.mem.strnlen_at(s, max_len)
 }
 pub(super) fn strcpy(env: &mut Environment, dest: MutPtr<u8>, src: ConstPtr<u8>) -> MutPtr<u8> {
-    GenericChar::<u8>::strcpy(env, dest, src, GuestUSize::MAX)
+    __strcpy_chk(env, dest, src, GuestUSize::MAX)
 }
 fn __strcpy_chk(
     env: &mut Environment,
@@ -104,10 +173,15 @@ fn __strcpy_chk(
     src: ConstPtr<u8>,
     size: GuestUSize,
 ) -> MutPtr<u8> {
-    GenericChar::<u8>::strcpy(env, dest, src, size)
+    let len = strlen(env, src) + 1; // include null terminator
+    if len > size {
+        panic!("Buffer overrun");
+    }
+    env.mem.memmove(dest.cast(), src.cast(), len);
+    dest
 }
 fn strcat(env: &mut Environment, dest: MutPtr<u8>, src: ConstPtr<u8>) -> MutPtr<u8> {
-    GenericChar::<u8>::strcat(env, dest, src, GuestUSize::MAX)
+    __strcat_chk(env, dest, src, GuestUSize::MAX)
 }
 fn __strcat_chk(
     env: &mut Environment,
@@ -115,7 +189,10 @@ fn __strcat_chk(
     src: ConstPtr<u8>,
     size: GuestUSize,
 ) -> MutPtr<u8> {
-    GenericChar::<u8>::strcat(env, dest, src, size)
+    let dest_len = strlen(env, dest.cast_const());
+    let remaining = size.checked_sub(dest_len).expect("strcat overflowed");
+    __strcpy_chk(env, dest + dest_len, src, remaining);
+    dest
 }
 fn strcspn(env: &mut Environment, s: ConstPtr<u8>, charset: ConstPtr<u8>) -> GuestUSize {
     GenericChar::<u8>::strcspn(env, s, charset)
@@ -126,7 +203,14 @@ fn strncpy(
     src: ConstPtr<u8>,
     size: GuestUSize,
 ) -> MutPtr<u8> {
-    GenericChar::<u8>::strncpy(env, dest, src, size)
+    // If src is at least size bytes long, the result is not null-terminated!
+    // Otherwise, the rest of dest is filled with null bytes.
+    let len = env.mem.strnlen_at(src, size);
+    env.mem.memmove(dest.cast(), src.cast(), len);
+    if len < size {
+        env.mem.bytes_at_mut(dest + len, size - len).fill(b'\0');
+    }
+    dest
 }
 fn strsep(env: &mut Environment, stringp: MutPtr<MutPtr<u8>>, delim: ConstPtr<u8>) -> MutPtr<u8> {
     let orig = env.mem.read(stringp);
@@ -159,13 +243,27 @@ fn strsep(env: &mut Environment, stringp: MutPtr<MutPtr<u8>>, delim: ConstPtr<u8
     orig
 }
 pub(super) fn strdup(env: &mut Environment, src: ConstPtr<u8>) -> MutPtr<u8> {
-    GenericChar::<u8>::strdup(env, src)
+    let len = strlen(env, src) + 1; // include null terminator
+    let new: MutPtr<u8> = env.mem.alloc(len).cast();
+    env.mem.memmove(new.cast(), src.cast(), len);
+    new
 }
 pub fn strcmp(env: &mut Environment, a: ConstPtr<u8>, b: ConstPtr<u8>) -> i32 {
-    GenericChar::<u8>::strcmp(env, a, b)
+    strncmp(env, a, b, GuestUSize::MAX)
 }
 fn strncmp(env: &mut Environment, a: ConstPtr<u8>, b: ConstPtr<u8>, n: GuestUSize) -> i32 {
-    GenericChar::<u8>::strncmp(env, a, b, n)
+    if n == 0 {
+        return 0;
+    }
+    // Include the null terminators, so that a prefix compares as smaller.
+    let len_a = env.mem.strnlen_at(a, n).saturating_add(1).min(n);
+    let len_b = env.mem.strnlen_at(b, n).saturating_add(1).min(n);
+    let a = env.mem.bytes_at(a, len_a);
+    let b = env.mem.bytes_at(b, len_b);
+    a.iter()
+        .zip(b)
+        .find(|(a, b)| a != b)
+        .map_or(0, |(&a, &b)| i32::from(a) - i32::from(b))
 }
 fn strcasecmp(env: &mut Environment, a: ConstPtr<u8>, b: ConstPtr<u8>) -> i32 {
     // TODO: generalize to wide chars
@@ -217,10 +315,31 @@ fn strncat(env: &mut Environment, s1: MutPtr<u8>, s2: ConstPtr<u8>, n: GuestUSiz
     GenericChar::<u8>::strncat(env, s1, s2, n)
 }
 fn strstr(env: &mut Environment, string: ConstPtr<u8>, substring: ConstPtr<u8>) -> ConstPtr<u8> {
-    GenericChar::<u8>::strstr(env, string, substring)
+    let haystack = env.mem.cstr_at(string);
+    let needle = env.mem.cstr_at(substring);
+    if needle.is_empty() {
+        return string;
+    }
+    match haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+    {
+        Some(idx) => string + idx as GuestUSize,
+        None => Ptr::null(),
+    }
 }
 fn strchr(env: &mut Environment, path: ConstPtr<u8>, c: u8) -> ConstPtr<u8> {
-    GenericChar::<u8>::strchr(env, path, c)
+    // If c is '\0', this should locate the null terminator.
+    let len = strlen(env, path);
+    match env
+        .mem
+        .bytes_at(path, len + 1)
+        .iter()
+        .position(|&byte| byte == c)
+    {
+        Some(idx) => path + idx as GuestUSize,
+        None => Ptr::null(),
+    }
 }
 fn strrchr(env: &mut Environment, path: ConstPtr<u8>, c: u8) -> ConstPtr<u8> {
     GenericChar::<u8>::strrchr(env, path, c)
@@ -239,11 +358,15 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(bzero(_, _)),
     // Functions shared with wchar.rs
     export_c_func!(memset(_, _, _)),
+    export_c_func!(__memset_chk(_, _, _, _)),
     export_c_func!(memcpy(_, _, _)),
+    export_c_func!(__memcpy_chk(_, _, _, _)),
     export_c_func!(memmove(_, _, _)),
+    export_c_func!(__memmove_chk(_, _, _, _)),
     export_c_func!(memchr(_, _, _)),
     export_c_func!(memcmp(_, _, _)),
     export_c_func!(strlen(_)),
+    export_c_func!(strnlen(_, _)),
     export_c_func!(strcpy(_, _)),
     export_c_func!(__strcpy_chk(_, _, _)),
     export_c_func!(strcat(_, _)),
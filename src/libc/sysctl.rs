@@ -69,8 +69,12 @@ fn sysctlbyname(
     assert!(newp.is_null());
     assert_eq!(newlen, 0);
 
-    // Below values corresponds to the original iPhone.
+    // Below values corresponds to the original iPhone, except for the memory
+    // size, which is configurable.
     // Reference https://www.mail-archive.com/misc@openbsd.org/msg80988.html
+    let memsize = env.options.device_memory_size;
+    // The kernel keeps this much of the original iPhone's memory for itself.
+    let usermem = memsize.saturating_sub(28069888);
     let val: SysInfoType = match name_str {
         // Generic CPU, I/O
         "hw.machine" => String(b"iPhone1,1"),
@@ -78,9 +82,10 @@ fn sysctlbyname(
         "hw.ncpu" => SysInfoType::Int32(1),
         "hw.cpufrequency" => SysInfoType::Int64(412000000),
         "hw.busfrequency" => SysInfoType::Int64(103000000),
-        "hw.physmem" => SysInfoType::Int32(121634816), // not sure about this type
-        "hw.usermem" => SysInfoType::Int32(93564928), // not sure about this type
-        "hw.memsize" => SysInfoType::Int64(121634816),
+        // These two are clamped on real devices with more memory.
+        "hw.physmem" => SysInfoType::Int32(memsize.min(i32::MAX as u64) as i32), // not sure about this type
+        "hw.usermem" => SysInfoType::Int32(usermem.min(i32::MAX as u64) as i32), // not sure about this type
+        "hw.memsize" => SysInfoType::Int64(memsize as i64),
         "hw.pagesize" => SysInfoType::Int64(PAGE_SIZE.into()),
         // High kernel limits
         "kern.ostype" => String(b"Darwin"),
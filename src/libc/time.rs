@@ -6,10 +6,13 @@
 //! `time.h` (C) and `sys/time.h` (POSIX)
 
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::libc::errno::set_errno;
-use crate::mem::{guest_size_of, ConstPtr, MutPtr, Ptr, SafeRead};
+use crate::frameworks::foundation::ns_time_zone;
+use crate::libc::errno::{set_errno, EINVAL};
+use crate::mem::{guest_size_of, ConstPtr, GuestUSize, MutPtr, Ptr, SafeRead};
+use crate::objc::{id, msg_class};
 use crate::Environment;
-use std::time::{Duration, Instant, SystemTime};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 
 #[derive(Default)]
 pub struct State {
@@ -17,6 +20,8 @@ pub struct State {
     /// Temporary static storage for the return value of `gmtime` or
     /// `localtime`. The standard allows calls to either to overwrite it.
     gmtime_tmp: Option<MutPtr<tm>>,
+    /// Guest copies of the time zone abbreviations `tm_zone` points to.
+    zone_names: HashMap<String, ConstPtr<u8>>,
 }
 
 // time.h (C)
@@ -31,7 +36,8 @@ type clock_t = u64;
 const CLOCKS_PER_SEC: clock_t = 1000000;
 
 fn clock(env: &mut Environment) -> clock_t {
-    Instant::now()
+    env.replay
+        .instant()
         .duration_since(env.startup_time)
         .as_secs()
         .wrapping_mul(CLOCKS_PER_SEC)
@@ -41,7 +47,9 @@ fn time(env: &mut Environment, out: MutPtr<time_t>) -> time_t {
     // TODO: handle errno properly
     set_errno(env, 0);
 
-    let time64 = SystemTime::now()
+    let time64 = env
+        .replay
+        .system_time()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_secs();
@@ -60,6 +68,37 @@ fn tzset(_env: &mut Environment) {
     log!("TODO: tzset()");
 }
 
+/// Get the offset of the local time zone from UTC in seconds. This is the
+/// offset of `[NSTimeZone systemTimeZone]`, so it agrees with Foundation.
+pub fn get_time_zone_offset(env: &mut Environment) -> i32 {
+    let time_zone: id = msg_class![env; NSTimeZone systemTimeZone];
+    ns_time_zone::seconds_from_gmt(env, time_zone)
+        .try_into()
+        .unwrap()
+}
+
+/// Abbreviation of a time zone with a given offset from UTC in seconds, in the
+/// style of `[NSTimeZone timeZoneForSecondsFromGMT:]`, e.g. `GMT+0130`.
+fn time_zone_abbreviation(offset: i32) -> String {
+    if offset == 0 {
+        return "GMT".to_string();
+    }
+    let sign = if offset < 0 { '-' } else { '+' };
+    let minutes = offset.abs() / 60;
+    format!("GMT{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// Get a guest C string for `tm_zone`. These are never freed, since a
+/// `struct tm` can outlive any call.
+fn zone_name_ptr(env: &mut Environment, name: String) -> ConstPtr<u8> {
+    if let Some(&ptr) = env.libc_state.time.zone_names.get(&name) {
+        return ptr;
+    }
+    let ptr = env.mem.alloc_and_write_cstr(name.as_bytes()).cast_const();
+    env.libc_state.time.zone_names.insert(name, ptr);
+    ptr
+}
+
 #[allow(non_camel_case_types)]
 #[repr(C, packed)]
 #[derive(Copy, Clone, Debug)]
@@ -410,9 +449,60 @@ fn test_calendar_date_to_timestamp_known_dates() {
     assert_eq!(calendar_date_to_timestamp(tm_before_epoch), -466053135);
 }
 
+#[cfg(test)]
+#[test]
+fn test_format_calendar_date() {
+    // Sat, 1955-03-26T20:47:45
+    let mut calendar_date = timestamp_to_calendar_date(-466053135);
+    let format = |format: &str, calendar_date: &tm| {
+        String::from_utf8(format_calendar_date(
+            format.as_bytes(),
+            calendar_date,
+            b"UTC",
+        ))
+        .unwrap()
+    };
+    assert_eq!(
+        format("%Y-%m-%d %H:%M:%S %a %b", &calendar_date),
+        "1955-03-26 20:47:45 Sat Mar"
+    );
+    assert_eq!(
+        format("%A %B %e %I%p %j %y %u %Z %z %%", &calendar_date),
+        "Saturday March 26 08PM 085 55 6 UTC +0000 %"
+    );
+    assert_eq!(format("%c", &calendar_date), "Sat Mar 26 20:47:45 1955");
+    assert_eq!(
+        format("%D %T %F", &calendar_date),
+        "03/26/55 20:47:45 1955-03-26"
+    );
+
+    calendar_date.tm_gmtoff = -(5 * 3600 + 30 * 60);
+    calendar_date.tm_mday = 1;
+    assert_eq!(format("%z %e %Ey %Q", &calendar_date), "-0530  1 55 %Q");
+}
+
+#[cfg(test)]
+#[test]
+fn test_time_zone_abbreviation() {
+    assert_eq!(time_zone_abbreviation(0), "GMT");
+    assert_eq!(time_zone_abbreviation(2 * 3600), "GMT+0200");
+    assert_eq!(time_zone_abbreviation(-(3 * 3600 + 30 * 60)), "GMT-0330");
+}
+
+/// Like [timestamp_to_calendar_date], but for the local time zone (see
+/// [get_time_zone_offset]).
+fn timestamp_to_local_calendar_date(env: &mut Environment, timestamp: time_t) -> tm {
+    let offset = get_time_zone_offset(env);
+    let mut calendar_date = timestamp_to_calendar_date(timestamp.saturating_add(offset));
+    calendar_date.tm_gmtoff = offset;
+    calendar_date.tm_zone = zone_name_ptr(env, time_zone_abbreviation(offset));
+    calendar_date
+}
+
 fn gmtime_r(env: &mut Environment, timestamp: ConstPtr<time_t>, res: MutPtr<tm>) -> MutPtr<tm> {
     let timestamp = env.mem.read(timestamp);
-    let calendar_date = timestamp_to_calendar_date(timestamp);
+    let mut calendar_date = timestamp_to_calendar_date(timestamp);
+    calendar_date.tm_zone = zone_name_ptr(env, "UTC".to_string());
     env.mem.write(res, calendar_date);
     res
 }
@@ -426,24 +516,180 @@ fn gmtime(env: &mut Environment, timestamp: ConstPtr<time_t>) -> MutPtr<tm> {
 }
 
 fn localtime_r(env: &mut Environment, timestamp: ConstPtr<time_t>, res: MutPtr<tm>) -> MutPtr<tm> {
-    // TODO: don't assume local time is UTC?
-    gmtime_r(env, timestamp, res)
+    let timestamp = env.mem.read(timestamp);
+    let calendar_date = timestamp_to_local_calendar_date(env, timestamp);
+    env.mem.write(res, calendar_date);
+    res
 }
 fn localtime(env: &mut Environment, timestamp: ConstPtr<time_t>) -> MutPtr<tm> {
-    // TODO: don't assume local time is UTC?
     // This doesn't have to be a unique temporary, gmtime and localtime are
     // allowed to share it.
-    gmtime(env, timestamp)
+    let tmp = *env
+        .libc_state
+        .time
+        .gmtime_tmp
+        .get_or_insert_with(|| env.mem.alloc(guest_size_of::<tm>()).cast());
+    localtime_r(env, timestamp, tmp)
 }
 
 fn mktime(env: &mut Environment, tm: MutPtr<tm>) -> time_t {
-    // TODO: respect the current timezone setting
-    let tm_value = env.mem.read(tm);
-    let res = calendar_date_to_timestamp(tm_value);
+    let mut tm_value = env.mem.read(tm);
+    // Out-of-range fields are allowed. Only the month needs normalizing
+    // beforehand, the others are just added up.
+    tm_value.tm_year += tm_value.tm_mon.div_euclid(12);
+    tm_value.tm_mon = tm_value.tm_mon.rem_euclid(12);
+    // TODO: daylight saving time
+    let res = calendar_date_to_timestamp(tm_value) - get_time_zone_offset(env);
     log_dbg!("mktime({:?}) => {}", tm_value, res);
+    // The fields are normalized and tm_wday and tm_yday are filled in.
+    let calendar_date = timestamp_to_local_calendar_date(env, res);
+    env.mem.write(tm, calendar_date);
+    res
+}
+
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Formats a calendar date like `strftime` does in the C locale. `zone` is the
+/// string `tm_zone` points to.
+fn format_calendar_date(format: &[u8], calendar_date: &tm, zone: &[u8]) -> Vec<u8> {
+    let tm {
+        tm_sec,
+        tm_min,
+        tm_hour,
+        tm_mday,
+        tm_mon,
+        tm_year,
+        tm_wday,
+        tm_yday,
+        tm_gmtoff,
+        ..
+    } = *calendar_date;
+    let weekday = WEEKDAY_NAMES[tm_wday.rem_euclid(7) as usize];
+    let month = MONTH_NAMES[tm_mon.rem_euclid(12) as usize];
+    let hour_12 = if tm_hour % 12 == 0 { 12 } else { tm_hour % 12 };
+    // None of the combinations include the time zone name.
+    let composite = |format: &[u8]| {
+        String::from_utf8(format_calendar_date(format, calendar_date, b"")).unwrap()
+    };
+
+    let mut res = Vec::new();
+    let mut iter = format.iter().copied();
+    while let Some(byte) = iter.next() {
+        if byte != b'%' {
+            res.push(byte);
+            continue;
+        }
+        let mut specifier = iter.next();
+        // The E and O modifiers select alternative representations, which
+        // the C locale doesn't have.
+        if let Some(b'E' | b'O') = specifier {
+            specifier = iter.next();
+        }
+        let formatted = match specifier {
+            Some(b'a') => weekday[..3].to_string(),
+            Some(b'A') => weekday.to_string(),
+            Some(b'b' | b'h') => month[..3].to_string(),
+            Some(b'B') => month.to_string(),
+            Some(b'C') => format!("{:02}", (tm_year + 1900).div_euclid(100)),
+            Some(b'd') => format!("{:02}", tm_mday),
+            Some(b'e') => format!("{:2}", tm_mday),
+            Some(b'H') => format!("{:02}", tm_hour),
+            Some(b'I') => format!("{:02}", hour_12),
+            Some(b'j') => format!("{:03}", tm_yday + 1),
+            Some(b'm') => format!("{:02}", tm_mon + 1),
+            Some(b'M') => format!("{:02}", tm_min),
+            Some(b'n') => "\n".to_string(),
+            Some(b'p') => (if tm_hour < 12 { "AM" } else { "PM" }).to_string(),
+            Some(b'S') => format!("{:02}", tm_sec),
+            Some(b't') => "\t".to_string(),
+            Some(b'u') => format!("{}", if tm_wday == 0 { 7 } else { tm_wday }),
+            Some(b'w') => format!("{}", tm_wday),
+            Some(b'y') => format!("{:02}", (tm_year + 1900).rem_euclid(100)),
+            Some(b'Y') => format!("{}", tm_year + 1900),
+            Some(b'z') => {
+                let sign = if tm_gmtoff < 0 { '-' } else { '+' };
+                let minutes = tm_gmtoff.abs() / 60;
+                format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+            }
+            Some(b'Z') => String::from_utf8_lossy(zone).into_owned(),
+            Some(b'%') => "%".to_string(),
+            // Combinations of the above
+            Some(b'c') => composite(b"%a %b %e %H:%M:%S %Y"),
+            Some(b'D' | b'x') => composite(b"%m/%d/%y"),
+            Some(b'F') => composite(b"%Y-%m-%d"),
+            Some(b'r') => composite(b"%I:%M:%S %p"),
+            Some(b'R') => composite(b"%H:%M"),
+            Some(b'T' | b'X') => composite(b"%H:%M:%S"),
+            other => {
+                log!(
+                    "TODO: strftime() specifier {:?}, outputting it as-is",
+                    other.map(char::from)
+                );
+                res.push(b'%');
+                res.extend(other);
+                continue;
+            }
+        };
+        res.extend_from_slice(formatted.as_bytes());
+    }
     res
 }
 
+fn strftime(
+    env: &mut Environment,
+    s: MutPtr<u8>,
+    max_size: GuestUSize,
+    format: ConstPtr<u8>,
+    calendar_date: ConstPtr<tm>,
+) -> GuestUSize {
+    let format_bytes = env.mem.cstr_at(format).to_vec();
+    let calendar_date = env.mem.read(calendar_date);
+    let zone = if calendar_date.tm_zone.is_null() {
+        Vec::new()
+    } else {
+        env.mem.cstr_at(calendar_date.tm_zone).to_vec()
+    };
+    let res = format_calendar_date(&format_bytes, &calendar_date, &zone);
+    log_dbg!(
+        "strftime({:?}, {}, {:?}, {:?}) => {:?}",
+        s,
+        max_size,
+        String::from_utf8_lossy(&format_bytes),
+        calendar_date,
+        String::from_utf8_lossy(&res)
+    );
+    // The null terminator has to fit too, otherwise nothing is written.
+    let len: GuestUSize = res.len().try_into().unwrap();
+    if len >= max_size {
+        return 0;
+    }
+    env.mem.bytes_at_mut(s, len).copy_from_slice(&res);
+    env.mem.write(s + len, b'\0');
+    len
+}
+
 // sys/time.h (POSIX)
 
 #[allow(non_camel_case_types)]
@@ -483,10 +729,11 @@ fn gettimeofday(
     set_errno(env, 0);
 
     if !timezone_ptr.is_null() {
+        let offset = get_time_zone_offset(env);
         env.mem.write(
             timezone_ptr,
             timezone {
-                tz_minuteswest: 0,
+                tz_minuteswest: -offset / 60,
                 tz_dsttime: 0,
             },
         );
@@ -496,7 +743,9 @@ fn gettimeofday(
         return 0; // success
     }
 
-    let time = SystemTime::now()
+    let time = env
+        .replay
+        .system_time()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
 
@@ -527,6 +776,44 @@ fn nanosleep(env: &mut Environment, rqtp: ConstPtr<timespec>, _rmtp: MutPtr<time
     0 // success
 }
 
+#[allow(non_camel_case_types)]
+type clockid_t = u32;
+
+// Values from Apple's `time.h`.
+const CLOCK_REALTIME: clockid_t = 0;
+const CLOCK_MONOTONIC: clockid_t = 6;
+
+fn clock_gettime(env: &mut Environment, clock_id: clockid_t, tp: MutPtr<timespec>) -> i32 {
+    let time = match clock_id {
+        CLOCK_REALTIME => env
+            .replay
+            .system_time()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap(),
+        CLOCK_MONOTONIC => env.replay.instant().duration_since(env.startup_time),
+        _ => {
+            log!(
+                "Warning: clock_gettime() with unsupported clock {}",
+                clock_id
+            );
+            set_errno(env, EINVAL);
+            return -1;
+        }
+    };
+
+    let time_s_64: u64 = time.as_secs();
+    let tv_sec = time_s_64 as time_t;
+    if !env.libc_state.time.y2k38_warned && time_s_64 != tv_sec as u64 {
+        env.libc_state.time.y2k38_warned = true;
+        log!("Warning: system clock is beyond Y2K38 and might confuse the app");
+    }
+    let tv_nsec: i32 = time.subsec_nanos().try_into().unwrap();
+
+    env.mem.write(tp, timespec { tv_sec, tv_nsec });
+
+    0 // success
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(clock()),
     export_c_func!(time(_)),
@@ -536,6 +823,8 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(mktime(_)),
     export_c_func!(localtime_r(_, _)),
     export_c_func!(localtime(_)),
+    export_c_func!(strftime(_, _, _, _)),
     export_c_func!(gettimeofday(_, _)),
     export_c_func!(nanosleep(_, _)),
+    export_c_func!(clock_gettime(_, _)),
 ];
@@ -4,6 +4,18 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! Logging and terminal output macros.
+//!
+//! Which modules [log_dbg] prints messages for can be chosen at compile time
+//! with [ENABLED_MODULES], or at runtime with the `TOUCHHLE_LOG` environment
+//! variable. The variable is a comma-separated list of directives, each of
+//! which is either a level on its own, which applies to every module, or
+//! `module=level`, which applies to a module and its submodules. The levels
+//! are `info` (the default: only [log] messages) and `debug` (also [log_dbg]
+//! messages). Module names can omit the `touchHLE::` prefix, and when several
+//! directives match a module, the most specific one wins. For example,
+//! `TOUCHHLE_LOG=dyld=debug,dyld::function_lists=info`.
+
+use std::sync::OnceLock;
 
 /// Accessing log output on Android is more difficult than on other platforms;
 /// logcat requires a separate device. As an alternative, let's write to a file
@@ -45,7 +57,9 @@ macro_rules! log {
 /// when debugging.
 macro_rules! log_dbg {
     ($($arg:tt)+) => {
-        if $crate::log::ENABLED_MODULES.contains(&module_path!()) {
+        if $crate::log::ENABLED_MODULES.contains(&module_path!())
+            || $crate::log::debug_enabled_at_runtime(module_path!())
+        {
             log!($($arg)*);
         }
     }
@@ -88,3 +102,135 @@ macro_rules! echo {
 /// Put modules to enable [log_dbg] for here, e.g. "touchHLE::mem" to see when
 /// memory is allocated and freed.
 pub const ENABLED_MODULES: &[&str] = &[];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Level {
+    Info,
+    Debug,
+}
+
+/// Parsed form of the `TOUCHHLE_LOG` environment variable.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct LogFilter {
+    default: Option<Level>,
+    /// Module paths (without the crate name) and their levels.
+    modules: Vec<(String, Level)>,
+}
+
+impl LogFilter {
+    fn parse(spec: &str) -> Result<LogFilter, String> {
+        let parse_level = |level: &str| match level.trim() {
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            other => Err(format!("unknown log level {:?}", other)),
+        };
+
+        let mut filter = LogFilter::default();
+        for directive in spec.split(',').filter(|d| !d.trim().is_empty()) {
+            if let Some((module, level)) = directive.split_once('=') {
+                let module = module.trim();
+                let module = module.strip_prefix("touchHLE::").unwrap_or(module);
+                filter
+                    .modules
+                    .push((module.to_string(), parse_level(level)?));
+            } else {
+                filter.default = Some(parse_level(directive)?);
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Whether any module gets debug messages.
+    fn enables_debug(&self) -> bool {
+        self.default == Some(Level::Debug)
+            || self.modules.iter().any(|&(_, level)| level == Level::Debug)
+    }
+
+    fn level_for(&self, module_path: &str) -> Level {
+        let module_path = module_path
+            .strip_prefix("touchHLE")
+            .and_then(|rest| rest.strip_prefix("::").or(rest.is_empty().then_some("")))
+            .unwrap_or(module_path);
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                module_path
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map(|&(_, level)| level)
+            .or(self.default)
+            .unwrap_or(Level::Info)
+    }
+}
+
+/// [None] if `TOUCHHLE_LOG` doesn't enable debug messages anywhere, which is
+/// the usual case, so that [debug_enabled_at_runtime] can return early.
+static LOG_FILTER: OnceLock<Option<LogFilter>> = OnceLock::new();
+
+#[cold]
+fn load_log_filter() -> Option<LogFilter> {
+    let spec = std::env::var("TOUCHHLE_LOG").ok()?;
+    let filter = match LogFilter::parse(&spec) {
+        Ok(filter) => filter,
+        Err(e) => {
+            echo!("Ignoring invalid TOUCHHLE_LOG value {:?}: {}", spec, e);
+            return None;
+        }
+    };
+    filter.enables_debug().then_some(filter)
+}
+
+/// Only for internal use by [log_dbg]. Checks whether `TOUCHHLE_LOG` enables
+/// debug messages for a module. The variable is read the first time this is
+/// called, which is early during startup. When no debug messages are enabled,
+/// this is just a check of an already-initialized static, so [log_dbg] stays
+/// cheap.
+#[inline]
+pub fn debug_enabled_at_runtime(module_path: &str) -> bool {
+    match LOG_FILTER.get_or_init(load_log_filter) {
+        None => false,
+        Some(filter) => filter.level_for(module_path) == Level::Debug,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_filter() {
+        assert_eq!(LogFilter::parse(""), Ok(LogFilter::default()));
+        assert!(LogFilter::parse("verbose").is_err());
+        assert!(LogFilter::parse("dyld=verbose").is_err());
+
+        assert!(!LogFilter::parse("").unwrap().enables_debug());
+        assert!(!LogFilter::parse("info,mem=info").unwrap().enables_debug());
+
+        let filter = LogFilter::parse("debug").unwrap();
+        assert!(filter.enables_debug());
+        assert_eq!(filter.level_for("touchHLE::mem"), Level::Debug);
+
+        let filter = LogFilter::parse("dyld=debug,objc=info").unwrap();
+        assert!(filter.enables_debug());
+        assert_eq!(filter.level_for("touchHLE::dyld"), Level::Debug);
+        assert_eq!(
+            filter.level_for("touchHLE::dyld::function_lists"),
+            Level::Debug
+        );
+        assert_eq!(filter.level_for("touchHLE::objc"), Level::Info);
+        assert_eq!(filter.level_for("touchHLE::mem"), Level::Info);
+        // Module names must match whole path components.
+        assert_eq!(filter.level_for("touchHLE::dyld_extra"), Level::Info);
+
+        // The most specific directive wins, regardless of order.
+        let filter = LogFilter::parse("touchHLE::libc::stdio=info, debug, libc=debug").unwrap();
+        assert_eq!(
+            filter.level_for("touchHLE::libc::stdio::printf"),
+            Level::Info
+        );
+        assert_eq!(filter.level_for("touchHLE::libc::stdlib"), Level::Debug);
+        assert_eq!(filter.level_for("touchHLE"), Level::Debug);
+    }
+}
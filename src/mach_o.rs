@@ -20,20 +20,109 @@
 
 use crate::abi::GuestFunction;
 use crate::fs::{Fs, GuestPath};
-use crate::mem::{Mem, Ptr};
+use crate::mem::{Mem, Protection, Ptr};
 use mach_object::{
     cpu_subtype_t, vm_prot_t, DyLib, LoadCommand, MachCommand, OFile, Symbol, SymbolIter,
     ThreadState, N_ARM_THUMB_DEF, S_LAZY_SYMBOL_POINTERS, S_MOD_INIT_FUNC_POINTERS,
     S_NON_LAZY_SYMBOL_POINTERS, S_SYMBOL_STUBS,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
 use std::io::{Cursor, Seek, SeekFrom};
 
 const VM_PROT_READ: vm_prot_t = 1;
 const VM_PROT_WRITE: vm_prot_t = 2;
-#[allow(dead_code)]
 const VM_PROT_EXECUTE: vm_prot_t = 4;
 
+/// Reasons a Mach-O binary can fail to load. See [MachO::load_from_bytes].
+#[derive(Debug, PartialEq, Eq)]
+pub enum MachOError {
+    /// The file couldn't be read from the guest file system.
+    Unreadable,
+    /// The file doesn't start with the magic number of a Mach-O file, a fat
+    /// binary or an archive. The number is read as little-endian.
+    BadMagic(u32),
+    /// The file ends before the end of a header, load command or segment.
+    Truncated,
+    /// The file is an archive (static library), not something we can load.
+    NotABinary,
+    /// The binary isn't for 32-bit little-endian ARM, or it's a fat binary
+    /// without a slice for it. The string describes what was found instead.
+    UnsupportedCpuType(String),
+    /// The binary is encrypted (App Store DRM).
+    Encrypted,
+    /// The binary is an executable, but doesn't have an entry point.
+    MissingEntryPoint,
+    /// A load command is invalid, contradicts another one, or uses a feature
+    /// we don't support. The string describes the problem.
+    MalformedLoadCommand(String),
+}
+
+impl fmt::Display for MachOError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MachOError::Unreadable => write!(f, "Could not read the file"),
+            MachOError::BadMagic(magic) => {
+                write!(f, "Not a Mach-O file (magic number {:#010x})", magic)
+            }
+            MachOError::Truncated => write!(f, "The file is truncated"),
+            MachOError::NotABinary => {
+                write!(f, "Unexpected Mach-O file kind: not an executable")
+            }
+            MachOError::UnsupportedCpuType(found) => write!(
+                f,
+                "Unsupported CPU type ({}), only 32-bit little-endian ARM is supported",
+                found
+            ),
+            MachOError::Encrypted => write!(
+                f,
+                "The executable is encrypted. touchHLE can't run encrypted apps!"
+            ),
+            MachOError::MissingEntryPoint => write!(
+                f,
+                "Mach-O file does not specify an entry point PC, perhaps it is not an executable?"
+            ),
+            MachOError::MalformedLoadCommand(problem) => {
+                write!(f, "Malformed load command: {}", problem)
+            }
+        }
+    }
+}
+
+/// Get the `len` bytes at `offset` in the file, or [MachOError::Truncated] if
+/// the file isn't long enough.
+fn file_slice(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], MachOError> {
+    bytes
+        .get(offset..)
+        .and_then(|rest| rest.get(..len))
+        .ok_or(MachOError::Truncated)
+}
+
+/// Check the magic number and that the header and load commands are all
+/// there, before handing the file to mach_object, which doesn't tell these
+/// kinds of failure apart.
+fn check_header(bytes: &[u8]) -> Result<(), MachOError> {
+    let magic = u32::from_le_bytes(file_slice(bytes, 0, 4)?.try_into().unwrap());
+    let (header_size, is_bigend) = match magic {
+        0xfeedface => (28, false),
+        0xfeedfacf => (32, false),
+        0xcefaedfe => (28, true),
+        0xcffaedfe => (32, true),
+        // Fat binary header (always big-endian). Each slice is checked when
+        // it's loaded.
+        0xbebafeca => return file_slice(bytes, 0, 8).map(|_| ()),
+        _ if bytes.starts_with(b"!<arch>\n") => return Ok(()),
+        _ => return Err(MachOError::BadMagic(magic)),
+    };
+    let sizeofcmds: [u8; 4] = file_slice(bytes, 20, 4)?.try_into().unwrap();
+    let sizeofcmds = if is_bigend {
+        u32::from_be_bytes(sizeofcmds)
+    } else {
+        u32::from_le_bytes(sizeofcmds)
+    };
+    file_slice(bytes, header_size, sizeofcmds as usize).map(|_| ())
+}
+
 #[derive(Debug)]
 pub struct MachO {
     /// Name (for debugging purposes)
@@ -46,6 +135,8 @@ pub struct MachO {
     /// can look things up quickly. Thumb function symbols always have the Thumb
     /// bit set.
     pub exported_symbols: HashMap<String, u32>,
+    /// Names of undefined (imported) symbols in the symbol table.
+    pub undefined_symbols: Vec<String>,
     /// List of addresses and names of external relocations for the dynamic
     /// linker to resolve.
     pub external_relocations: Vec<(u32, String)>,
@@ -74,11 +165,14 @@ pub enum SectionType {
     /// Normal section, or some section type we don't care about.
     Normal,
     /// Symbol stub section, usually called `__symbol_stub4` or
-    /// `__picsymbolstub4`.
+    /// `__picsymbolstub4`, or `__stubs` with newer toolchains. A binary can
+    /// have more than one of these.
     SymbolStubs,
     /// Lazy symbol pointer section, usually called `__la_symbol_ptr`.
     LazySymbolPointers,
-    /// Non-lazy symbol pointer section, usually called `__nl_symbol_ptr`.
+    /// Non-lazy symbol pointer section, usually called `__nl_symbol_ptr`, or
+    /// `__got` (global offset table) with newer toolchains. A binary can have
+    /// more than one of these.
     NonLazySymbolPointers,
     /// Initialization function pointer section, usually called
     /// `__mod_init_func`.
@@ -225,7 +319,7 @@ fn cpu_subtype_to_str(ty: cpu_subtype_t) -> &'static str {
         mach_object::CPU_SUBTYPE_ARM_V7S => "armv7s",
         mach_object::CPU_SUBTYPE_ARM_V7K => "armv7k",
         mach_object::CPU_SUBTYPE_ARM_V8 => "armv8",
-        _ => panic!("Unexpected cpu subtype: {:?}", ty),
+        _ => "unknown ARM variant",
     }
 }
 
@@ -233,23 +327,31 @@ impl MachO {
     /// Load the all the sections from a Mach-O binary (provided as `bytes`)
     /// into the guest memory (`into_mem`), and return a struct containing
     /// metadata (e.g. symbols).
+    ///
+    /// Invalid or unsupported binaries produce a [MachOError] rather than a
+    /// panic, though memory may have been modified by then.
     pub fn load_from_bytes(
         bytes: &[u8],
         into_mem: &mut Mem,
         name: String,
-    ) -> Result<MachO, &'static str> {
+    ) -> Result<MachO, MachOError> {
         log_dbg!("Reading {:?}", name);
 
+        check_header(bytes)?;
+
         let mut cursor = Cursor::new(bytes);
 
-        let file = OFile::parse(&mut cursor).map_err(|_| "Could not parse Mach-O file")?;
+        let file = OFile::parse(&mut cursor)
+            .map_err(|e| MachOError::MalformedLoadCommand(format!("could not parse: {:?}", e)))?;
 
         let (header, commands) = match file {
             OFile::MachFile { header, commands } => (header, commands),
             OFile::FatFile { files, .. } => {
                 let mut best_subslice = None;
                 let mut best_type = None;
+                let mut cpu_types = Vec::new();
                 for (arch, _) in files {
+                    cpu_types.push(arch.cputype);
                     if arch.cputype != mach_object::CPU_TYPE_ARM {
                         continue;
                     }
@@ -258,25 +360,30 @@ impl MachO {
                             && best_type != Some(mach_object::CPU_SUBTYPE_ARM_V7))
                         || best_type.is_none()
                     {
-                        best_subslice = Some(
-                            &bytes[arch.offset as usize..arch.offset as usize + arch.size as usize],
-                        );
+                        best_subslice =
+                            Some(file_slice(bytes, arch.offset as usize, arch.size as usize)?);
                         best_type = Some(arch.cpusubtype);
                     }
                 }
                 return if let Some(subslice) = best_subslice {
                     MachO::load_from_bytes(subslice, into_mem, name)
                 } else {
-                    Err("No supported architecture in the fat binary")
+                    Err(MachOError::UnsupportedCpuType(format!(
+                        "fat binary with CPU types {:?}",
+                        cpu_types
+                    )))
                 };
             }
             OFile::ArFile { .. } | OFile::SymDef { .. } => {
-                return Err("Unexpected Mach-O file kind: not an executable");
+                return Err(MachOError::NotABinary);
             }
         };
 
         if header.cputype != mach_object::CPU_TYPE_ARM {
-            return Err("Executable is not for an ARM CPU!");
+            return Err(MachOError::UnsupportedCpuType(format!(
+                "CPU type {}",
+                header.cputype
+            )));
         }
         log!(
             "Loading {} slice for {:?}",
@@ -286,11 +393,11 @@ impl MachO {
 
         let is_bigend = header.is_bigend();
         if is_bigend {
-            return Err("Executable is not little-endian!");
+            return Err(MachOError::UnsupportedCpuType("big-endian ARM".to_string()));
         }
         let is_64bit = header.is_64bit();
         if is_64bit {
-            return Err("Executable is not 32-bit!");
+            return Err(MachOError::UnsupportedCpuType("64-bit ARM".to_string()));
         }
         // TODO: Check cpusubtype (should be some flavour of ARMv6/ARMv7)
 
@@ -306,6 +413,7 @@ impl MachO {
         // Info used for the result
         let mut dynamic_libraries = Vec::new();
         let mut exported_symbols = HashMap::new();
+        let mut undefined_symbols = Vec::new();
         let mut indirect_undef_symbols: Vec<Option<String>> = Vec::new();
         let mut external_relocations: Vec<(u32, String)> = Vec::new();
         let mut entry_point_pc: Option<u32> = None;
@@ -341,13 +449,21 @@ impl MachO {
                         "__LINKEDIT" => false,
                         // Zero page needs to be handled seperately.
                         "__PAGEZERO" => {
-                            assert!(vmaddr == 0);
-                            assert!(filesize == 0);
+                            if vmaddr != 0 || filesize != 0 {
+                                return Err(MachOError::MalformedLoadCommand(format!(
+                                    "__PAGEZERO segment at {:#x} has {:#x} bytes of file data",
+                                    vmaddr, filesize
+                                )));
+                            }
                             into_mem.set_null_segment_size(vmsize);
                             false
                         }
                         "__TEXT" => {
-                            assert!(text_segment_base.is_none());
+                            if text_segment_base.is_some() {
+                                return Err(MachOError::MalformedLoadCommand(
+                                    "more than one __TEXT segment".to_string(),
+                                ));
+                            }
                             text_segment_base = Some(vmaddr);
                             true
                         }
@@ -359,15 +475,32 @@ impl MachO {
                     };
 
                     if load_me {
+                        if filesize > vmsize {
+                            return Err(MachOError::MalformedLoadCommand(format!(
+                                "{} segment has more file data ({:#x} bytes) than fits in \
+                                 memory ({:#x} bytes)",
+                                segname, filesize, vmsize
+                            )));
+                        }
+                        let src = if filesize > 0 {
+                            file_slice(bytes, fileoff, filesize as usize)?
+                        } else {
+                            &[]
+                        };
+
                         into_mem.reserve(vmaddr, vmsize);
+                        let protection = Protection {
+                            read: (initprot & VM_PROT_READ) != 0,
+                            write: (initprot & VM_PROT_WRITE) != 0,
+                            execute: (initprot & VM_PROT_EXECUTE) != 0,
+                        };
+                        let region_name = format!("{} of {}", segname, name);
+                        into_mem.add_region(vmaddr, vmsize, protection, region_name);
 
                         // If filesize is less than vmsize, the rest of the
                         // segment should be filled with zeroes. We are assuming
                         // the memory is already zeroed!
                         if filesize > 0 {
-                            assert!(filesize <= vmsize);
-
-                            let src = &bytes[fileoff..][..filesize as usize];
                             let dst = into_mem.bytes_at_mut(Ptr::from_bits(vmaddr), filesize);
                             dst.copy_from_slice(src);
                         }
@@ -412,6 +545,14 @@ impl MachO {
                                     entry
                                 };
                                 exported_symbols.insert(name.to_string(), entry);
+                            } else if let Symbol::Undefined {
+                                name: Some(name), ..
+                            }
+                            | Symbol::Prebound {
+                                name: Some(name), ..
+                            } = symbol
+                            {
+                                undefined_symbols.push(name.to_string());
                             };
                         }
                     }
@@ -423,20 +564,21 @@ impl MachO {
                     nextrel,
                     ..
                 } => {
+                    let sym_tab_info = sym_tab_info.ok_or_else(|| {
+                        MachOError::MalformedLoadCommand(
+                            "dynamic symbol table without a symbol table".to_string(),
+                        )
+                    })?;
+
                     let indirectsyms =
-                        &bytes[indirectsymoff as usize..][..nindirectsyms as usize * 4];
+                        file_slice(bytes, indirectsymoff as usize, nindirectsyms as usize * 4)?;
                     for idx in indirectsyms.chunks(4) {
                         assert!(!is_bigend);
                         let idx = u32::from_le_bytes(idx.try_into().unwrap());
 
                         let mut cursor = cursor.clone();
-                        let sym = get_sym_by_idx(
-                            idx,
-                            sym_tab_info.unwrap(),
-                            is_bigend,
-                            is_64bit,
-                            &mut cursor,
-                        );
+                        let sym =
+                            get_sym_by_idx(idx, sym_tab_info, is_bigend, is_64bit, &mut cursor);
                         indirect_undef_symbols.push(match sym {
                             // apparently used in apps?
                             Some(Symbol::Undefined { name: Some(n), .. }) => Some(String::from(n)),
@@ -446,11 +588,16 @@ impl MachO {
                             // itself, e.g. to "__Znwm". might be a PIC thing
                             Some(Symbol::Defined { name: Some(n), .. }) => Some(String::from(n)),
                             None => None,
-                            _ => panic!("Unexpected symbol kind {:?}", sym),
+                            _ => {
+                                return Err(MachOError::MalformedLoadCommand(format!(
+                                    "unexpected indirect symbol kind {:?}",
+                                    sym
+                                )))
+                            }
                         })
                     }
 
-                    let extrels = &bytes[extreloff as usize..][..nextrel as usize * 8];
+                    let extrels = file_slice(bytes, extreloff as usize, nextrel as usize * 8)?;
                     for entry in extrels.chunks(8) {
                         let reloc = Reloc::parse(is_bigend, entry.try_into().unwrap());
                         let Reloc::External {
@@ -461,22 +608,26 @@ impl MachO {
                             type_: 0, // generic
                         } = reloc
                         else {
-                            panic!("Unhandled extrel: {:?}", reloc)
+                            return Err(MachOError::MalformedLoadCommand(format!(
+                                "unhandled external relocation {:?}",
+                                reloc
+                            )));
                         };
-                        let addr = if split_segs {
-                            addr + first_read_write_segment_base.unwrap()
+                        let base = if split_segs {
+                            first_read_write_segment_base
                         } else {
-                            addr + first_segment_base.unwrap()
+                            first_segment_base
+                        };
+                        let Some(base) = base else {
+                            return Err(MachOError::MalformedLoadCommand(
+                                "external relocations without a segment to relocate".to_string(),
+                            ));
                         };
+                        let addr = addr + base;
 
                         let mut cursor = cursor.clone();
-                        let sym = get_sym_by_idx(
-                            sym_idx,
-                            sym_tab_info.unwrap(),
-                            is_bigend,
-                            is_64bit,
-                            &mut cursor,
-                        );
+                        let sym =
+                            get_sym_by_idx(sym_idx, sym_tab_info, is_bigend, is_64bit, &mut cursor);
                         match sym {
                             Some(Symbol::Undefined { name: Some(n), .. }) => {
                                 external_relocations.push((addr, String::from(n)));
@@ -502,15 +653,18 @@ impl MachO {
                                 into_mem.write(ptr_ptr, 0); // Clear prebinding.
                                 external_relocations.push((addr, String::from(n)));
                             }
-                            _ => panic!("Unexpected symbol kind {:?}", sym),
+                            _ => {
+                                return Err(MachOError::MalformedLoadCommand(format!(
+                                    "unexpected external relocation symbol kind {:?}",
+                                    sym
+                                )))
+                            }
                         };
                     }
                 }
                 LoadCommand::EncryptionInfo { id, .. } => {
                     if id != 0 {
-                        return Err(
-                            "The executable is encrypted. touchHLE can't run encrypted apps!",
-                        );
+                        return Err(MachOError::Encrypted);
                     }
                 }
                 LoadCommand::LoadDyLib(DyLib { name, .. }) => {
@@ -526,10 +680,17 @@ impl MachO {
                         __cpsr: 0,
                     } = state
                     else {
-                        panic!("Unexpected initial thread state in {:?}: {:?}", name, state);
+                        return Err(MachOError::MalformedLoadCommand(format!(
+                            "unexpected initial thread state {:?}",
+                            state
+                        )));
                     };
                     // There should only be a single initial thread state.
-                    assert!(entry_point_pc.is_none());
+                    if entry_point_pc.is_some() {
+                        return Err(MachOError::MalformedLoadCommand(
+                            "more than one entry point".to_string(),
+                        ));
+                    }
                     entry_point_pc = Some(pc);
                 }
                 // New-style entry point PC command
@@ -542,9 +703,18 @@ impl MachO {
                     }
                     // There should only be a single entry point.
                     // (Presumably an executable won't use both commands?)
-                    assert!(entry_point_pc.is_none());
+                    if entry_point_pc.is_some() {
+                        return Err(MachOError::MalformedLoadCommand(
+                            "more than one entry point".to_string(),
+                        ));
+                    }
+                    let Some(text_segment_base) = text_segment_base else {
+                        return Err(MachOError::MalformedLoadCommand(
+                            "entry point without a __TEXT segment".to_string(),
+                        ));
+                    };
                     let entryoff: u32 = entryoff.try_into().unwrap();
-                    entry_point_pc = Some(text_segment_base.unwrap() + entryoff);
+                    entry_point_pc = Some(text_segment_base + entryoff);
                 }
                 // LoadCommand::DyldInfo is apparently a newer thing that 2008
                 // games don't have. Ignore for now? Unsure if/when iOS got it.
@@ -555,6 +725,10 @@ impl MachO {
             }
         }
 
+        if header.filetype == mach_object::MH_EXECUTE && entry_point_pc.is_none() {
+            return Err(MachOError::MissingEntryPoint);
+        }
+
         let sections = all_sections
             .iter()
             .map(|section| {
@@ -584,33 +758,48 @@ impl MachO {
                     S_NON_LAZY_SYMBOL_POINTERS => (ST::NonLazySymbolPointers, Some(4)),
                     _ => (ST::Normal, None),
                 };
-                let dyld_indirect_symbol_info = dyld_entry_size.map(|entry_size| {
-                    let indirect_start = section.reserved1 as usize;
-                    assert!(size % entry_size == 0);
-                    let indirect_count = (size / entry_size) as usize;
-                    let indirects = &mut indirect_undef_symbols[indirect_start..][..indirect_count];
-                    let syms = indirects.iter_mut().map(|sym| sym.take()).collect();
-                    DyldIndirectSymbolInfo {
-                        entry_size,
-                        indirect_undef_symbols: syms,
+                let dyld_indirect_symbol_info = match dyld_entry_size {
+                    Some(entry_size) => {
+                        let malformed = || {
+                            MachOError::MalformedLoadCommand(format!(
+                                "section {:?} doesn't match the indirect symbol table",
+                                name
+                            ))
+                        };
+                        if entry_size == 0 || size % entry_size != 0 {
+                            return Err(malformed());
+                        }
+                        let indirect_start = section.reserved1 as usize;
+                        let indirect_count = (size / entry_size) as usize;
+                        let indirects = indirect_undef_symbols
+                            .get_mut(indirect_start..)
+                            .and_then(|rest| rest.get_mut(..indirect_count))
+                            .ok_or_else(malformed)?;
+                        let syms = indirects.iter_mut().map(|sym| sym.take()).collect();
+                        Some(DyldIndirectSymbolInfo {
+                            entry_size,
+                            indirect_undef_symbols: syms,
+                        })
                     }
-                });
+                    None => None,
+                };
 
-                Section {
+                Ok(Section {
                     name,
                     addr,
                     size,
                     type_,
                     dyld_indirect_symbol_info,
-                }
+                })
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         Ok(MachO {
             name,
             dynamic_libraries,
             sections,
             exported_symbols,
+            undefined_symbols,
             external_relocations,
             entry_point_pc,
         })
@@ -623,18 +812,187 @@ impl MachO {
         path: P,
         fs: &Fs,
         into_mem: &mut Mem,
-    ) -> Result<MachO, &'static str> {
+    ) -> Result<MachO, MachOError> {
         let name = path.as_ref().file_name().unwrap().to_string();
         Self::load_from_bytes(
-            &fs.read(path.as_ref())
-                .map_err(|_| "Could not read executable file")?,
+            &fs.read(path.as_ref()).map_err(|_| MachOError::Unreadable)?,
             into_mem,
             name,
         )
     }
 
+    /// Get the names of all symbols the binary imports from elsewhere: the
+    /// undefined symbols in the symbol table, the targets of external
+    /// relocations, and the symbols referenced by the dynamic linker's
+    /// indirect symbol sections.
+    ///
+    /// The indirect symbols can include symbols the binary itself defines.
+    pub fn imported_symbols(&self) -> BTreeSet<&str> {
+        let mut symbols = BTreeSet::new();
+        symbols.extend(self.undefined_symbols.iter().map(|name| name.as_str()));
+        symbols.extend(
+            self.external_relocations
+                .iter()
+                .map(|(_, name)| name.as_str()),
+        );
+        for section in &self.sections {
+            let Some(ref info) = section.dyld_indirect_symbol_info else {
+                continue;
+            };
+            symbols.extend(
+                info.indirect_undef_symbols
+                    .iter()
+                    .flatten()
+                    .map(|name| name.as_str()),
+            );
+        }
+        symbols
+    }
+
     /// Get a section by its name (`&str`) or type ([SectionType]).
     pub fn get_section<P: SectionPredicate>(&self, by: P) -> Option<&Section> {
         self.sections.iter().find(|section| by.test(section))
     }
+
+    /// Get all sections with a particular name (`&str`) or type
+    /// ([SectionType]).
+    pub fn get_sections<P: SectionPredicate>(&self, by: P) -> impl Iterator<Item = &Section> {
+        self.sections.iter().filter(move |section| by.test(section))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CPU_TYPE_ARM: u32 = 12;
+    const CPU_TYPE_X86: u32 = 7;
+    const MH_EXECUTE: u32 = 2;
+    const MH_DYLIB: u32 = 6;
+    const LC_SEGMENT: u32 = 0x1;
+    const LC_ENCRYPTION_INFO: u32 = 0x21;
+
+    fn words(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    /// Build a 32-bit little-endian Mach-O file with the given load commands.
+    fn build(cputype: u32, filetype: u32, commands: &[Vec<u8>]) -> Vec<u8> {
+        let sizeofcmds: usize = commands.iter().map(|command| command.len()).sum();
+        let cpusubtype = 9; // armv7
+        let mut bytes = words(&[
+            0xfeedface,
+            cputype,
+            cpusubtype,
+            filetype,
+            commands.len() as u32,
+            sizeofcmds as u32,
+            0,
+        ]);
+        for command in commands {
+            bytes.extend_from_slice(command);
+        }
+        bytes
+    }
+
+    fn segment(segname: &str, vmaddr: u32, vmsize: u32, fileoff: u32, filesize: u32) -> Vec<u8> {
+        let mut command = words(&[LC_SEGMENT, 56]);
+        let mut segname = segname.as_bytes().to_vec();
+        segname.resize(16, 0);
+        command.extend_from_slice(&segname);
+        command.extend_from_slice(&words(&[vmaddr, vmsize, fileoff, filesize, 7, 7, 0, 0]));
+        command
+    }
+
+    fn load(bytes: &[u8]) -> Result<MachO, MachOError> {
+        let mut mem = Mem::new();
+        MachO::load_from_bytes(bytes, &mut mem, "Test".to_string())
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let err = load(b"\x7fELF\x01\x01\x01\x00").unwrap_err();
+        assert_eq!(err, MachOError::BadMagic(0x464c457f));
+        assert_eq!(
+            err.to_string(),
+            "Not a Mach-O file (magic number 0x464c457f)"
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert_eq!(load(&[]).unwrap_err(), MachOError::Truncated);
+        assert_eq!(load(&[0xce, 0xfa]).unwrap_err(), MachOError::Truncated);
+
+        // Load commands run past the end of the file
+        let mut bytes = build(
+            CPU_TYPE_ARM,
+            MH_DYLIB,
+            &[segment("__DATA", 0x1000, 0x1000, 0, 0)],
+        );
+        bytes.truncate(bytes.len() - 4);
+        assert_eq!(load(&bytes).unwrap_err(), MachOError::Truncated);
+
+        // Segment data runs past the end of the file
+        let bytes = build(
+            CPU_TYPE_ARM,
+            MH_DYLIB,
+            &[segment("__DATA", 0x1000, 0x1000, 0x800, 0x100)],
+        );
+        assert_eq!(load(&bytes).unwrap_err(), MachOError::Truncated);
+    }
+
+    #[test]
+    fn test_unsupported_cpu_type() {
+        let bytes = build(CPU_TYPE_X86, MH_EXECUTE, &[]);
+        assert_eq!(
+            load(&bytes).unwrap_err(),
+            MachOError::UnsupportedCpuType("CPU type 7".to_string())
+        );
+    }
+
+    #[test]
+    fn test_encrypted() {
+        let encryption_info = words(&[LC_ENCRYPTION_INFO, 20, 0x1000, 0x1000, 1]);
+        let bytes = build(CPU_TYPE_ARM, MH_EXECUTE, &[encryption_info]);
+        assert_eq!(load(&bytes).unwrap_err(), MachOError::Encrypted);
+
+        // cryptid 0 means the binary has been decrypted
+        let encryption_info = words(&[LC_ENCRYPTION_INFO, 20, 0x1000, 0x1000, 0]);
+        let bytes = build(CPU_TYPE_ARM, MH_DYLIB, &[encryption_info]);
+        assert!(load(&bytes).is_ok());
+    }
+
+    #[test]
+    fn test_missing_entry_point() {
+        let bytes = build(CPU_TYPE_ARM, MH_EXECUTE, &[]);
+        assert_eq!(load(&bytes).unwrap_err(), MachOError::MissingEntryPoint);
+
+        // Libraries don't need one
+        let bytes = build(CPU_TYPE_ARM, MH_DYLIB, &[]);
+        assert_eq!(load(&bytes).unwrap().entry_point_pc, None);
+    }
+
+    #[test]
+    fn test_malformed_load_command() {
+        let bytes = build(
+            CPU_TYPE_ARM,
+            MH_DYLIB,
+            &[segment("__PAGEZERO", 0x1000, 0x1000, 0, 0)],
+        );
+        assert!(matches!(
+            load(&bytes).unwrap_err(),
+            MachOError::MalformedLoadCommand(_)
+        ));
+
+        let bytes = build(
+            CPU_TYPE_ARM,
+            MH_DYLIB,
+            &[segment("__DATA", 0x1000, 0x100, 0, 0x200)],
+        );
+        assert!(matches!(
+            load(&bytes).unwrap_err(),
+            MachOError::MalformedLoadCommand(_)
+        ));
+    }
 }
@@ -15,8 +15,16 @@
 //! * [Memory Usage Performance Guidelines](https://developer.apple.com/library/archive/documentation/Performance/Conceptual/ManagingMemory/ManagingMemory.html)
 
 use crate::libc::wchar::wchar_t;
+use std::collections::{BTreeMap, HashSet};
 
 mod allocator;
+mod canaries;
+mod regions;
+#[cfg(test)]
+mod snapshot;
+
+pub use allocator::HeapStats;
+pub use regions::Protection;
 
 /// Equivalent of `usize` for guest memory.
 pub type GuestUSize = u32;
@@ -237,6 +245,15 @@ pub struct Mem {
     /// range.
     null_segment_size: VAddr,
 
+    /// Base addresses of thread stack guard pages. Like the null segment,
+    /// these are only respected for accesses by guest code, which reach them
+    /// through [crate::cpu]'s memory callbacks.
+    guard_pages: HashSet<VAddr>,
+
+    /// What parts of the address space are used for, keyed by base address.
+    /// See [Mem::dump_regions].
+    regions: BTreeMap<VAddr, regions::Region>,
+
     allocator: allocator::Allocator,
 
     /// The flag to control if memory is zeroed out on free (`true`, default)
@@ -245,6 +262,12 @@ pub struct Mem {
     /// Right now only one game, Spore Origin, is setting this value to `false`
     /// via a game-specific hack. See [crate::Environment] for more info.
     pub(super) zero_memory_on_free: bool,
+
+    /// See [Mem::enable_heap_canaries].
+    heap_canaries: Option<canaries::HeapCanaries>,
+
+    /// See [Mem::enable_strict_alignment].
+    strict_alignment: bool,
 }
 
 impl Drop for Mem {
@@ -270,6 +293,14 @@ impl Mem {
     /// iPhone OS secondary thread stack size.
     pub const SECONDARY_THREAD_DEFAULT_STACK_SIZE: GuestUSize = 512 * 1024;
 
+    /// Size of a page, which is the granularity of the null segment and guard
+    /// pages.
+    pub const PAGE_SIZE: GuestUSize = 0x1000;
+
+    /// Address of the guard page below the main thread's stack.
+    pub const MAIN_THREAD_STACK_GUARD_PAGE: VAddr =
+        Self::MAIN_THREAD_STACK_LOW_END - Self::PAGE_SIZE;
+
     /// Create a fresh instance of guest memory.
     pub fn new() -> Mem {
         // This will hopefully get the host OS to lazily allocate the memory.
@@ -281,8 +312,12 @@ impl Mem {
         Mem {
             bytes,
             null_segment_size: 0,
+            guard_pages: HashSet::from([Self::MAIN_THREAD_STACK_GUARD_PAGE]),
+            regions: regions::initial_regions(),
             allocator,
             zero_memory_on_free: true,
+            heap_canaries: None,
+            strict_alignment: false,
         }
     }
 
@@ -303,6 +338,10 @@ impl Mem {
             mem.bytes_mut()[base as usize..][..size.get() as usize].fill(0);
         }
         mem.null_segment_size = 0;
+        mem.guard_pages = HashSet::from([Self::MAIN_THREAD_STACK_GUARD_PAGE]);
+        mem.regions = regions::initial_regions();
+        mem.heap_canaries = None;
+        mem.strict_alignment = false;
         mem
     }
 
@@ -316,7 +355,7 @@ impl Mem {
         //        segments they shouldn't be able to. Adding that would fix
         //        this, along with removing this special case.
         assert!(self.null_segment_size == 0);
-        assert!(new_null_segment_size % 0x1000 == 0);
+        assert!(new_null_segment_size % Self::PAGE_SIZE == 0);
         self.allocator
             .reserve(allocator::Chunk::new(0, new_null_segment_size));
         self.null_segment_size = new_null_segment_size;
@@ -326,6 +365,36 @@ impl Mem {
         self.null_segment_size
     }
 
+    /// Make a page inaccessible to guest code, so that a thread overflowing
+    /// its stack faults rather than corrupting other memory. The page must be
+    /// part of an allocation or reservation. Note that [crate::cpu::Cpu] must
+    /// also be told about it.
+    pub fn add_guard_page(&mut self, page: VAddr) {
+        assert!(page % Self::PAGE_SIZE == 0);
+        assert!(self.guard_pages.insert(page));
+    }
+
+    /// Undo [Self::add_guard_page].
+    pub fn remove_guard_page(&mut self, page: VAddr) {
+        assert!(self.guard_pages.remove(&page));
+    }
+
+    pub fn guard_pages(&self) -> impl Iterator<Item = VAddr> + '_ {
+        self.guard_pages.iter().copied()
+    }
+
+    /// Whether an access of `size` bytes at `addr` touches a guard page.
+    pub fn is_guard_page_access(&self, addr: VAddr, size: GuestUSize) -> bool {
+        // There are only a few guard pages (one per thread), so checking each
+        // of them is cheaper than checking each page of a large access.
+        let start = u64::from(addr);
+        let end = start + u64::from(size.max(1));
+        self.guard_pages.iter().any(|&page| {
+            let page = u64::from(page);
+            page < end && start < page + u64::from(Self::PAGE_SIZE)
+        })
+    }
+
     /// Get a pointer to the full 4GiB of memory. This is only for use when
     /// setting up the CPU, never call this otherwise.
     ///
@@ -343,6 +412,37 @@ impl Mem {
         unsafe { &mut *self.bytes }
     }
 
+    /// Make [Self::read] and [Self::write] panic if the pointer isn't aligned
+    /// for the type, like on an ARM CPU with alignment checking turned on.
+    /// iPhone OS allows most misaligned accesses, so this is off by default,
+    /// but a misaligned access often means that pointer arithmetic has gone
+    /// wrong somewhere. Guest code only goes through [Mem] if direct memory
+    /// access is disabled for the CPU, see [crate::cpu::Cpu::new].
+    pub fn enable_strict_alignment(&mut self) {
+        self.strict_alignment = true;
+    }
+
+    /// Whether `addr` is suitably aligned for a `T`. The guest never needs
+    /// more than 4-byte alignment.
+    fn is_aligned<T>(addr: VAddr) -> bool {
+        let align = std::mem::align_of::<T>().min(4) as VAddr;
+        addr % align == 0
+    }
+
+    /// Whether an access to a `T` at `addr` would fail the check enabled by
+    /// [Self::enable_strict_alignment].
+    pub fn is_misaligned_access<T>(&self, addr: VAddr) -> bool {
+        self.strict_alignment && !Self::is_aligned::<T>(addr)
+    }
+
+    #[cold]
+    fn alignment_check_fail(at: VAddr, size: GuestUSize) {
+        panic!(
+            "Attempted misaligned access at {:#x} ({:#x} bytes)",
+            at, size
+        )
+    }
+
     // the performance characteristics of this hasn't been profiled, but it
     // seems like a good idea to help the compiler optimise for the fast path
     #[cold]
@@ -489,6 +589,9 @@ impl Mem {
     where
         T: SafeRead,
     {
+        if self.is_misaligned_access::<T>(ptr.to_bits()) {
+            Self::alignment_check_fail(ptr.to_bits(), guest_size_of::<T>())
+        }
         // This is unsafe unless we are careful with which types SafeRead is
         // implemented for!
         // This would also be unsafe if the non-unaligned method was used.
@@ -502,6 +605,9 @@ impl Mem {
     {
         let size = guest_size_of::<T>();
         assert!(size > 0);
+        if self.is_misaligned_access::<T>(ptr.to_bits()) {
+            Self::alignment_check_fail(ptr.to_bits(), size)
+        }
         let slice = self.bytes_at_mut(ptr.cast(), size);
         let ptr: *mut T = slice.as_mut_ptr().cast();
         // It's unaligned because what is well-aligned for the guest is not
@@ -510,8 +616,57 @@ impl Mem {
         unsafe { ptr.write_unaligned(value) }
     }
 
-    /// C-style `memmove`.
+    /// Whether `size` bytes at `addr` could be accessed by guest code without
+    /// faulting: they must not be in the null segment or a guard page, and
+    /// they must be allocated or reserved.
+    pub fn is_mapped(&self, addr: VAddr, size: GuestUSize) -> bool {
+        addr >= self.null_segment_size
+            && !self.is_guard_page_access(addr, size)
+            && self.allocator.is_used(addr, size)
+    }
+
+    /// Like [Self::read], but returns [None] rather than panicking if the
+    /// memory is unmapped (see [Self::is_mapped]) or the pointer is
+    /// misaligned. This is for speculative reads, e.g. when following a frame
+    /// pointer that might be bogus.
+    pub fn try_read<T, const MUT: bool>(&self, ptr: Ptr<T, MUT>) -> Option<T>
+    where
+        T: SafeRead,
+    {
+        if !self.is_accessible::<T>(ptr.to_bits()) {
+            return None;
+        }
+        Some(self.read(ptr))
+    }
+    /// Like [Self::write], but returns `false` rather than panicking if the
+    /// memory is unmapped (see [Self::is_mapped]) or the pointer is
+    /// misaligned. The memory is unchanged in that case.
+    pub fn try_write<T>(&mut self, ptr: MutPtr<T>, value: T) -> bool
+    where
+        T: SafeWrite,
+    {
+        if !self.is_accessible::<T>(ptr.to_bits()) {
+            return false;
+        }
+        self.write(ptr, value);
+        true
+    }
+    /// Check for [Self::try_read] and [Self::try_write].
+    fn is_accessible<T>(&self, addr: VAddr) -> bool {
+        Self::is_aligned::<T>(addr) && self.is_mapped(addr, guest_size_of::<T>())
+    }
+
+    /// C-style `memmove`. Like [Self::bytes_at], this panics on null-page
+    /// access, but only if `size` isn't 0.
     pub fn memmove(&mut self, dest: MutVoidPtr, src: ConstVoidPtr, size: GuestUSize) {
+        if size == 0 {
+            return;
+        }
+        for addr in [dest.to_bits(), src.to_bits()] {
+            if addr < self.null_segment_size {
+                Self::null_check_fail(addr, size)
+            }
+        }
         let src = src.to_bits() as usize;
         let dest = dest.to_bits() as usize;
         let size = size as usize;
@@ -521,6 +676,13 @@ impl Mem {
 
     /// Allocate `size` bytes.
     pub fn alloc(&mut self, size: GuestUSize) -> MutVoidPtr {
+        if self.heap_canaries.is_some() {
+            return self.alloc_with_canaries(size);
+        }
+        self.alloc_without_canaries(size)
+    }
+
+    fn alloc_without_canaries(&mut self, size: GuestUSize) -> MutVoidPtr {
         let ptr = Ptr::from_bits(self.allocator.alloc(size));
         if !self.zero_memory_on_free {
             self.bytes_at_mut(ptr.cast(), size).fill(0);
@@ -535,7 +697,9 @@ impl Mem {
         }
         // TODO: for a moment we always assume that we do not have enough size
         //       to realloc inplace
-        let old_size = self.allocator.find_allocated_size(old_ptr.to_bits());
+        let Some(old_size) = self.allocated_size(old_ptr.cast_const()) else {
+            panic!("Can't find {:?}, unknown allocation!", old_ptr);
+        };
         if old_size >= size {
             return old_ptr;
         }
@@ -547,6 +711,7 @@ impl Mem {
 
     /// Free an allocation made with one of the `alloc` methods on this type.
     pub fn free(&mut self, ptr: MutVoidPtr) {
+        let ptr = self.remove_canaries(ptr);
         let size = self.allocator.free(ptr.to_bits());
         if self.zero_memory_on_free {
             self.bytes_at_mut(ptr.cast(), size).fill(0);
@@ -554,6 +719,31 @@ impl Mem {
         log_dbg!("Freed {:?} ({:#x} bytes)", ptr, size);
     }
 
+    /// Get the size of an allocation made with one of the `alloc` methods on
+    /// this type, which may be larger than the requested size. Returns [None]
+    /// if `ptr` isn't the start of an allocation.
+    pub fn allocated_size(&self, ptr: ConstVoidPtr) -> Option<GuestUSize> {
+        self.canary_allocation_size(ptr.to_bits())
+            .or_else(|| self.allocator.allocated_size(ptr.to_bits()))
+    }
+
+    /// Get statistics about allocations made with the `alloc` methods on this
+    /// type.
+    pub fn heap_stats(&self) -> HeapStats {
+        self.allocator.heap_stats()
+    }
+
+    /// Get the highest number of bytes that were allocated at once with the
+    /// `alloc` methods on this type.
+    pub fn peak_bytes_used(&self) -> GuestUSize {
+        self.allocator.peak_bytes_used()
+    }
+
+    /// Get the size [Self::alloc] would actually allocate for `size` bytes.
+    pub fn good_alloc_size(size: GuestUSize) -> GuestUSize {
+        allocator::Allocator::good_size(size)
+    }
+
     /// Allocate memory large enough for a value of type `T` and write the value
     /// to it. Equivalent to [Self::alloc] + [Self::write].
     pub fn alloc_and_write<T>(&mut self, value: T) -> MutPtr<T>
@@ -578,13 +768,27 @@ impl Mem {
     /// Get a C string (null-terminated) as a slice. The null terminator is not
     /// included in the slice.
     pub fn cstr_at<const MUT: bool>(&self, ptr: Ptr<u8, MUT>) -> &[u8] {
-        let mut len = 0;
-        while self.read(ptr + len) != b'\0' {
-            len += 1;
-        }
+        let len = self.strnlen_at(ptr, GuestUSize::MAX);
         self.bytes_at(ptr, len)
     }
 
+    /// Get the length of a C string (null-terminated), looking at no more than
+    /// `max_len` bytes. This is the equivalent of `strnlen`.
+    pub fn strnlen_at<const MUT: bool>(
+        &self,
+        ptr: Ptr<u8, MUT>,
+        max_len: GuestUSize,
+    ) -> GuestUSize {
+        // Searching a slice is much faster than reading one byte at a time.
+        let available = 0u32.wrapping_sub(ptr.to_bits());
+        let bytes = self.bytes_at(ptr, max_len.min(available));
+        match bytes.iter().position(|&byte| byte == b'\0') {
+            Some(len) => len as GuestUSize,
+            None if max_len > available => panic!("Unterminated C string at {:?}", ptr),
+            None => max_len,
+        }
+    }
+
     /// Get a C string (null-terminated) as a string slice, if it is valid
     /// UTF-8, otherwise returning a byte slice. The null terminator is not
     /// included in the slice.
@@ -610,4 +814,62 @@ impl Mem {
     pub fn reserve(&mut self, base: VAddr, size: GuestUSize) {
         self.allocator.reserve(allocator::Chunk::new(base, size));
     }
+
+    /// Mark a region of address space as being in use, if it isn't already
+    /// (otherwise returns [false]). Unlike [Self::reserve], this can be undone
+    /// with [Self::free].
+    pub fn try_reserve(&mut self, base: VAddr, size: GuestUSize) -> bool {
+        self.allocator
+            .try_reserve(allocator::Chunk::new(base, size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_read_write() {
+        let mut mem = Mem::new();
+        mem.set_null_segment_size(0x1000);
+        let base = 0x4000_0000;
+        assert!(mem.try_reserve(base, 0x1000));
+        let ptr: MutPtr<u32> = Ptr::from_bits(base + 0xffc);
+        assert!(mem.try_write(ptr, 0x1234));
+        assert_eq!(mem.try_read(ptr), Some(0x1234));
+        // Misaligned
+        let misaligned: ConstPtr<u32> = Ptr::from_bits(base + 2);
+        assert_eq!(mem.try_read(misaligned), None);
+        // Partly past the end of the reservation
+        let straddling: ConstPtr<f64> = ptr.cast_const().cast();
+        assert_eq!(mem.try_read(straddling), None);
+        // Null segment
+        assert_eq!(mem.try_read(Ptr::<u32, false>::from_bits(0x100)), None);
+        // Unmapped
+        let unmapped: MutPtr<u32> = Ptr::from_bits(base + 0x1000);
+        assert_eq!(mem.try_read(unmapped), None);
+        let snapshot = mem.snapshot_region(Ptr::from_bits(base), 0x2000);
+        assert!(!mem.try_write(unmapped, 1));
+        assert!(!mem.try_write(misaligned.cast_mut(), 1));
+        assert!(mem.diff_region(&snapshot).is_empty());
+
+        mem.free(Ptr::from_bits(base));
+        assert_eq!(mem.try_read(ptr), None);
+    }
+
+    #[test]
+    fn test_is_guard_page_access() {
+        let mut mem = Mem::new();
+        let guard_page = 0x4000_1000;
+        mem.add_guard_page(guard_page);
+        assert!(mem.is_guard_page_access(guard_page, 1));
+        assert!(mem.is_guard_page_access(guard_page + 0xfff, 1));
+        assert!(!mem.is_guard_page_access(guard_page - 4, 4));
+        assert!(!mem.is_guard_page_access(guard_page + 0x1000, 4));
+        // Straddling the start or the end
+        assert!(mem.is_guard_page_access(guard_page - 2, 4));
+        assert!(mem.is_guard_page_access(guard_page + 0xffe, 4));
+        // Starting and ending outside, but covering it
+        assert!(mem.is_guard_page_access(guard_page - 0x1000, 0x3000));
+    }
 }
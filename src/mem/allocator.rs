@@ -134,6 +134,11 @@ mod collections {
         pub fn get_size_with_base(&self, base: VAddr) -> Option<NonZeroU32> {
             self.chunks.get(&base).copied()
         }
+        pub fn get_containing(&self, addr: VAddr) -> Option<Chunk> {
+            let (&base, &size) = self.chunks.range(..=addr).next_back()?;
+            let chunk = Chunk { base, size };
+            chunk.contains(addr).then_some(chunk)
+        }
     }
 
     #[derive(Default, Debug)]
@@ -255,16 +260,33 @@ use collections::{ChunkMap, SizeBucketedChunkMap};
 pub struct Allocator {
     used_chunks: ChunkMap,
     unused_chunks: SizeBucketedChunkMap,
+    /// Allocations made with [Allocator::alloc]. Reserved regions are not
+    /// counted.
+    chunks_used: GuestUSize,
+    bytes_used: GuestUSize,
+    /// Highest value [Self::bytes_used] has had.
+    peak_bytes_used: GuestUSize,
+}
+
+/// Statistics about the heap, as reported by `mstats()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HeapStats {
+    pub chunks_used: GuestUSize,
+    pub bytes_used: GuestUSize,
+    pub chunks_free: GuestUSize,
+    pub bytes_free: GuestUSize,
 }
 
 impl Allocator {
     pub fn new() -> Allocator {
         let main_thread_stack =
             Chunk::new(Mem::MAIN_THREAD_STACK_LOW_END, Mem::MAIN_THREAD_STACK_SIZE);
-        let rest = Chunk::new(0, Mem::MAIN_THREAD_STACK_LOW_END);
+        let main_thread_stack_guard = Chunk::new(Mem::MAIN_THREAD_STACK_GUARD_PAGE, Mem::PAGE_SIZE);
+        let rest = Chunk::new(0, Mem::MAIN_THREAD_STACK_GUARD_PAGE);
 
         let mut used_chunks: ChunkMap = Default::default();
         used_chunks.insert(main_thread_stack);
+        used_chunks.insert(main_thread_stack_guard);
 
         let mut unused_chunks: SizeBucketedChunkMap = Default::default();
         unused_chunks.insert(rest);
@@ -272,10 +294,21 @@ impl Allocator {
         Allocator {
             used_chunks,
             unused_chunks,
+            chunks_used: 0,
+            bytes_used: 0,
+            peak_bytes_used: 0,
         }
     }
 
     pub fn reserve(&mut self, chunk: Chunk) {
+        if !self.try_reserve(chunk) {
+            panic!("Could not reserve chunk {:?}!", chunk);
+        }
+    }
+
+    /// Like [Self::reserve], but returns [false] rather than panicking if the
+    /// chunk isn't entirely unused.
+    pub fn try_reserve(&mut self, chunk: Chunk) -> bool {
         let mut to_trisect = None;
         for unused_chunk in self.unused_chunks.iter() {
             if unused_chunk.trisect_by(chunk).is_some() {
@@ -285,7 +318,7 @@ impl Allocator {
         }
 
         let Some(to_trisect) = to_trisect else {
-            panic!("Could not reserve chunk {:?}!", chunk);
+            return false;
         };
 
         let (before, after) = to_trisect.trisect_by(chunk).unwrap();
@@ -297,15 +330,22 @@ impl Allocator {
             self.unused_chunks.insert(after);
         }
         self.used_chunks.insert(chunk);
+        true
     }
 
-    pub fn alloc(&mut self, size: GuestUSize) -> VAddr {
+    /// The size of the chunk [Self::alloc] would use for an allocation of
+    /// `size` bytes.
+    pub fn good_size(size: GuestUSize) -> GuestUSize {
         let size = size.max(MIN_CHUNK_SIZE);
-        let size = if size % MIN_CHUNK_SIZE != 0 {
+        if size % MIN_CHUNK_SIZE != 0 {
             size + MIN_CHUNK_SIZE - (size % MIN_CHUNK_SIZE)
         } else {
             size
-        };
+        }
+    }
+
+    pub fn alloc(&mut self, size: GuestUSize) -> VAddr {
+        let size = Self::good_size(size);
 
         let Some(alloc) = self.unused_chunks.allocate(size) else {
             panic!(
@@ -314,16 +354,55 @@ impl Allocator {
             );
         };
         self.used_chunks.insert(alloc);
+        self.chunks_used += 1;
+        self.bytes_used += size;
+        self.peak_bytes_used = self.peak_bytes_used.max(self.bytes_used);
 
         alloc.base
     }
 
-    /// This is used for realloc
-    pub fn find_allocated_size(&mut self, base: VAddr) -> GuestUSize {
-        let Some(size) = self.used_chunks.get_size_with_base(base) else {
-            panic!("Can't find {:#x}, unknown allocation!", base);
+    pub fn heap_stats(&self) -> HeapStats {
+        let (chunks_free, bytes_free) = self.unused_chunks.iter().fold(
+            (0, 0),
+            |(count, bytes): (GuestUSize, GuestUSize), chunk| {
+                (count + 1, bytes.saturating_add(chunk.size.get()))
+            },
+        );
+        HeapStats {
+            chunks_used: self.chunks_used,
+            bytes_used: self.bytes_used,
+            chunks_free,
+            bytes_free,
+        }
+    }
+
+    /// Highest number of bytes that were in use at once.
+    pub fn peak_bytes_used(&self) -> GuestUSize {
+        self.peak_bytes_used
+    }
+
+    pub fn allocated_size(&self, base: VAddr) -> Option<GuestUSize> {
+        self.used_chunks
+            .get_size_with_base(base)
+            .map(|size| size.get())
+    }
+
+    /// Whether all of the `size` bytes at `base` are allocated or reserved.
+    /// They don't have to be part of the same chunk.
+    pub fn is_used(&self, base: VAddr, size: GuestUSize) -> bool {
+        let Some(last_byte) = base.checked_add(size.max(1) - 1) else {
+            return false;
         };
-        size.get()
+        let mut addr = base;
+        loop {
+            let Some(chunk) = self.used_chunks.get_containing(addr) else {
+                return false;
+            };
+            if chunk.last_byte() >= last_byte {
+                return true;
+            }
+            addr = chunk.last_byte() + 1;
+        }
     }
 
     /// Returns the size of the freed chunk so it can be zeroed if desired
@@ -333,20 +412,21 @@ impl Allocator {
             log!("Can't free {:#x}, unknown allocation!", base);
             return 0;
         };
-
-        if let Some(adjacent) = self
-            .unused_chunks
-            .remove_with_base(freed.last_byte() + 1)
-            .or_else(|| self.unused_chunks.remove_with_end(freed.base))
-        {
-            let combined = Chunk::new(
-                freed.base.min(adjacent.base),
-                freed.size.get() + adjacent.size.get(),
-            );
-            self.unused_chunks.insert(combined);
-        } else {
-            self.unused_chunks.insert(freed);
+        // Reserved chunks were never counted, so don't underflow if one is
+        // freed.
+        self.chunks_used = self.chunks_used.saturating_sub(1);
+        self.bytes_used = self.bytes_used.saturating_sub(freed.size.get());
+
+        // Merge with the unused chunks on either side, if any, so that the
+        // heap doesn't get fragmented into ever smaller pieces.
+        let mut combined = freed;
+        if let Some(after) = self.unused_chunks.remove_with_base(freed.last_byte() + 1) {
+            combined = Chunk::new(combined.base, combined.size.get() + after.size.get());
+        }
+        if let Some(before) = self.unused_chunks.remove_with_end(freed.base) {
+            combined = Chunk::new(before.base, before.size.get() + combined.size.get());
         }
+        self.unused_chunks.insert(combined);
 
         freed.size.get()
     }
@@ -357,3 +437,59 @@ impl Allocator {
         chunks.drain()
     }
 }
+
+#[cfg(test)]
+mod allocator_tests {
+    use super::*;
+    #[test]
+    fn test_heap_stats() {
+        let mut allocator = Allocator::new();
+        let initial = allocator.heap_stats();
+        assert_eq!((initial.chunks_used, initial.bytes_used), (0, 0));
+
+        let a = allocator.alloc(1);
+        let b = allocator.alloc(100);
+        let stats = allocator.heap_stats();
+        assert_eq!((stats.chunks_used, stats.bytes_used), (2, 16 + 112));
+        assert_eq!(stats.bytes_free, initial.bytes_free - 128);
+        assert_eq!(allocator.allocated_size(b), Some(112));
+        assert_eq!(allocator.allocated_size(b + 16), None);
+        assert_eq!(Allocator::good_size(100), 112);
+        assert_eq!(Allocator::good_size(0), 16);
+
+        assert_eq!(allocator.free(a), 16);
+        assert_eq!(allocator.free(b), 112);
+        assert_eq!(allocator.heap_stats(), initial);
+        assert_eq!(allocator.peak_bytes_used(), 128);
+    }
+
+    #[test]
+    fn test_try_reserve() {
+        let mut allocator = Allocator::new();
+        let a = allocator.alloc(0x100);
+        assert!(!allocator.try_reserve(Chunk::new(a, 0x10)));
+        assert!(allocator.try_reserve(Chunk::new(a + 0x1000, 0x1000)));
+        assert!(!allocator.try_reserve(Chunk::new(a + 0x1800, 0x1000)));
+        assert_eq!(allocator.allocated_size(a + 0x1000), Some(0x1000));
+        assert_eq!(allocator.free(a + 0x1000), 0x1000);
+        assert!(allocator.try_reserve(Chunk::new(a + 0x1800, 0x1000)));
+    }
+
+    #[test]
+    fn test_is_used() {
+        let mut allocator = Allocator::new();
+        assert!(allocator.try_reserve(Chunk::new(0x10000, 0x10)));
+        assert!(allocator.try_reserve(Chunk::new(0x10010, 0x10)));
+        assert!(allocator.is_used(0x10000, 0x10));
+        // Adjacent chunks count as one.
+        assert!(allocator.is_used(0x10008, 0x10));
+        assert!(!allocator.is_used(0x10018, 0x10));
+        assert!(!allocator.is_used(0xffff, 2));
+        // The main thread's stack is reserved.
+        assert!(allocator.is_used(0xfffffffc, 4));
+        assert!(!allocator.is_used(0xfffffffc, 8));
+        assert_eq!(allocator.free(0x10000), 0x10);
+        assert!(!allocator.is_used(0x10000, 1));
+        assert!(allocator.is_used(0x10010, 1));
+    }
+}
@@ -0,0 +1,227 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Optional detection of heap buffer overruns and underruns. See
+//! [Mem::enable_heap_canaries].
+//!
+//! Each allocation is surrounded by canaries: bytes with a known value that
+//! the app has no business writing to. They are checked when the allocation is
+//! freed, and every so often for all live allocations, so an overrun is caught
+//! even if the allocation is never freed.
+
+use super::{allocator, ConstPtr, GuestUSize, Mem, MutVoidPtr, Ptr, VAddr};
+use std::collections::HashMap;
+
+/// Size of each of the canaries before and after an allocation. Using the
+/// allocator's alignment keeps allocations aligned.
+const CANARY_SIZE: GuestUSize = allocator::MIN_CHUNK_SIZE;
+
+/// The value of every byte of a canary. It's unlikely to be written by
+/// accident, and easy to recognize in a memory dump.
+const CANARY_BYTE: u8 = 0xcb;
+
+/// How many allocations are made between checks of all live canaries.
+const SWEEP_INTERVAL: u32 = 1024;
+
+#[derive(Debug, Copy, Clone)]
+struct CanaryAllocation {
+    /// The requested size, which the trailing canary immediately follows.
+    size: GuestUSize,
+    /// The address in guest code that made the allocation, if known (see
+    /// [Mem::set_allocation_call_site]).
+    call_site: Option<VAddr>,
+}
+
+#[derive(Default)]
+pub(super) struct HeapCanaries {
+    /// Allocations with canaries, keyed by the address given out by
+    /// [Mem::alloc]. The leading canary is just before this.
+    allocations: HashMap<VAddr, CanaryAllocation>,
+    allocs_since_sweep: u32,
+}
+
+/// Check the canaries of an allocation, returning a description of the damage
+/// if any were overwritten, e.g. `canary after the 10-byte allocation at 0x1230
+/// was overwritten (allocated by guest code at 0x2f04)`.
+fn check_canaries(mem: &Mem, addr: VAddr, allocation: CanaryAllocation) -> Option<String> {
+    let is_intact = |base: VAddr| {
+        let canary: ConstPtr<u8> = Ptr::from_bits(base);
+        mem.bytes_at(canary, CANARY_SIZE)
+            .iter()
+            .all(|&byte| byte == CANARY_BYTE)
+    };
+    let before = !is_intact(addr - CANARY_SIZE);
+    let after = !is_intact(addr + allocation.size);
+    let which = match (before, after) {
+        (false, false) => return None,
+        (true, false) => "canary before",
+        (false, true) => "canary after",
+        (true, true) => "canaries before and after",
+    };
+    let call_site = match allocation.call_site {
+        Some(call_site) => format!("guest code at {:#x}", call_site),
+        None => "the host".to_string(),
+    };
+    Some(format!(
+        "{} the {}-byte allocation at {:#x} {} overwritten (allocated by {})",
+        which,
+        allocation.size,
+        addr,
+        if before && after { "were" } else { "was" },
+        call_site
+    ))
+}
+
+#[cold]
+fn report_heap_corruption(damage: &str) -> ! {
+    let message = format!("Heap corruption detected: {}", damage);
+    echo!("{}", message);
+    panic!("{}", message);
+}
+
+impl Mem {
+    /// Surround every allocation made from now on with canaries, which are
+    /// checked when it's freed and periodically while it's alive. If one was
+    /// overwritten, the emulator stops with a report that includes where the
+    /// allocation was made. This uses more memory and slows down allocation.
+    pub fn enable_heap_canaries(&mut self) {
+        self.heap_canaries.get_or_insert_with(Default::default);
+    }
+
+    /// Record that the allocation at `ptr` was made by guest code at
+    /// `call_site`, for reporting if its canaries are overwritten. This does
+    /// nothing if heap canaries aren't enabled.
+    pub fn set_allocation_call_site(&mut self, ptr: MutVoidPtr, call_site: VAddr) {
+        let Some(ref mut canaries) = self.heap_canaries else {
+            return;
+        };
+        if let Some(allocation) = canaries.allocations.get_mut(&ptr.to_bits()) {
+            allocation.call_site = Some(call_site);
+        }
+    }
+
+    /// [Self::alloc] for when heap canaries are enabled.
+    pub(super) fn alloc_with_canaries(&mut self, size: GuestUSize) -> MutVoidPtr {
+        let padded_size = size.checked_add(CANARY_SIZE * 2).unwrap();
+        let base = self.alloc_without_canaries(padded_size).to_bits();
+        let addr = base + CANARY_SIZE;
+        self.bytes_at_mut(Ptr::from_bits(base), CANARY_SIZE)
+            .fill(CANARY_BYTE);
+        self.bytes_at_mut(Ptr::from_bits(addr + size), CANARY_SIZE)
+            .fill(CANARY_BYTE);
+
+        let canaries = self.heap_canaries.as_mut().unwrap();
+        let allocation = CanaryAllocation {
+            size,
+            call_site: None,
+        };
+        assert!(canaries.allocations.insert(addr, allocation).is_none());
+        canaries.allocs_since_sweep += 1;
+        if canaries.allocs_since_sweep >= SWEEP_INTERVAL {
+            canaries.allocs_since_sweep = 0;
+            self.check_heap_canaries();
+        }
+
+        Ptr::from_bits(addr)
+    }
+
+    /// For use by [Self::free]: if `ptr` is an allocation with canaries, check
+    /// them and stop tracking it, returning the address of the underlying
+    /// allocation that should actually be freed.
+    pub(super) fn remove_canaries(&mut self, ptr: MutVoidPtr) -> MutVoidPtr {
+        let Some(ref mut canaries) = self.heap_canaries else {
+            return ptr;
+        };
+        let Some(allocation) = canaries.allocations.remove(&ptr.to_bits()) else {
+            // Allocated before canaries were enabled.
+            return ptr;
+        };
+        if let Some(damage) = check_canaries(self, ptr.to_bits(), allocation) {
+            report_heap_corruption(&damage);
+        }
+        Ptr::from_bits(ptr.to_bits() - CANARY_SIZE)
+    }
+
+    /// For use by [Self::allocated_size]: the requested size of an allocation
+    /// with canaries.
+    pub(super) fn canary_allocation_size(&self, ptr: VAddr) -> Option<GuestUSize> {
+        let canaries = self.heap_canaries.as_ref()?;
+        canaries
+            .allocations
+            .get(&ptr)
+            .map(|allocation| allocation.size)
+    }
+
+    /// Check the canaries of every live allocation. This happens automatically
+    /// every so often when heap canaries are enabled.
+    pub fn check_heap_canaries(&self) {
+        let Some(ref canaries) = self.heap_canaries else {
+            return;
+        };
+        // Report the lowest address, so the result is deterministic.
+        let mut addrs: Vec<_> = canaries.allocations.keys().copied().collect();
+        addrs.sort();
+        for addr in addrs {
+            if let Some(damage) = check_canaries(self, addr, canaries.allocations[&addr]) {
+                report_heap_corruption(&damage);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intact_canaries() {
+        let mut mem = Mem::new();
+        mem.enable_heap_canaries();
+        let ptr = mem.alloc(10);
+        assert_eq!(ptr.to_bits() % allocator::MIN_CHUNK_SIZE, 0);
+        assert_eq!(mem.allocated_size(ptr.cast_const()), Some(10));
+        mem.bytes_at_mut(ptr.cast(), 10).fill(0xff);
+        mem.check_heap_canaries();
+        let ptr = mem.realloc(ptr, 100);
+        assert_eq!(mem.bytes_at(ptr.cast(), 10), [0xff; 10]);
+        mem.free(ptr);
+        assert!(mem.heap_canaries.as_ref().unwrap().allocations.is_empty());
+    }
+
+    #[test]
+    fn test_check_canaries() {
+        let mut mem = Mem::new();
+        mem.enable_heap_canaries();
+        let ptr = mem.alloc(10);
+        mem.set_allocation_call_site(ptr, 0x2f04);
+        let addr = ptr.to_bits();
+        let allocation = mem.heap_canaries.as_ref().unwrap().allocations[&addr];
+
+        mem.write(Ptr::from_bits(addr - 1), 0u8);
+        assert_eq!(
+            check_canaries(&mem, addr, allocation).unwrap(),
+            format!(
+                "canary before the 10-byte allocation at {:#x} was overwritten (allocated by \
+                 guest code at 0x2f04)",
+                addr
+            )
+        );
+        mem.write(Ptr::from_bits(addr + 10), 0u8);
+        assert!(check_canaries(&mem, addr, allocation)
+            .unwrap()
+            .starts_with("canaries before and after the 10-byte allocation"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Heap corruption detected: canary after the 10-byte allocation")]
+    fn test_overrun_detected_on_free() {
+        let mut mem = Mem::new();
+        mem.enable_heap_canaries();
+        let ptr = mem.alloc(10);
+        // One byte past the end
+        mem.write(ptr.cast::<u8>() + 10, 0x42);
+        mem.free(ptr);
+    }
+}
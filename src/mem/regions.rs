@@ -0,0 +1,231 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Map of what the guest address space is used for, for diagnostics (see
+//! [Mem::dump_regions]).
+//!
+//! This is purely informational. The protections recorded here are not
+//! enforced: the only memory protection is still the null segment and the
+//! guard pages.
+
+use super::{GuestUSize, Mem, VAddr};
+use std::collections::BTreeMap;
+
+/// Access permissions of a region of guest memory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Protection {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Protection {
+    pub const NONE: Protection = Protection {
+        read: false,
+        write: false,
+        execute: false,
+    };
+    pub const READ_WRITE: Protection = Protection {
+        read: true,
+        write: true,
+        execute: false,
+    };
+}
+
+/// Formats like `/proc/self/maps` does, e.g. `r-x`.
+impl std::fmt::Display for Protection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flag = |set, c| if set { c } else { '-' };
+        write!(
+            f,
+            "{}{}{}",
+            flag(self.read, 'r'),
+            flag(self.write, 'w'),
+            flag(self.execute, 'x')
+        )
+    }
+}
+
+/// A region recorded with [Mem::add_region].
+#[derive(Debug, Clone)]
+pub(super) struct Region {
+    size: GuestUSize,
+    protection: Protection,
+    name: String,
+}
+
+/// The regions that exist before anything is loaded, keyed by base address.
+pub(super) fn initial_regions() -> BTreeMap<VAddr, Region> {
+    BTreeMap::from([(
+        Mem::MAIN_THREAD_STACK_LOW_END,
+        Region {
+            size: Mem::MAIN_THREAD_STACK_SIZE,
+            protection: Protection::READ_WRITE,
+            name: "stack of thread 0 (main thread)".to_string(),
+        },
+    )])
+}
+
+/// Formats a region as a line of [Mem::dump_regions]'s output, e.g.
+/// `0x00001000-0x00004fff    16 KiB r-x __TEXT of Foo`.
+fn describe_region(base: VAddr, size: GuestUSize, protection: Protection, name: &str) -> String {
+    let size_string = if size % 1024 == 0 {
+        format!("{} KiB", size / 1024)
+    } else {
+        format!("{} B", size)
+    };
+    format!(
+        "{:#010x}-{:#010x} {:>9} {} {}",
+        base,
+        base + (size - 1),
+        size_string,
+        protection,
+        name
+    )
+}
+
+impl Mem {
+    /// Record what a region of guest memory is used for, so that it appears in
+    /// [Self::dump_regions]. This doesn't allocate or reserve anything. There
+    /// must not already be a region with the same base address.
+    pub fn add_region(
+        &mut self,
+        base: VAddr,
+        size: GuestUSize,
+        protection: Protection,
+        name: String,
+    ) {
+        assert!(size != 0);
+        let region = Region {
+            size,
+            protection,
+            name,
+        };
+        assert!(self.regions.insert(base, region).is_none());
+    }
+
+    /// Undo [Self::add_region].
+    pub fn remove_region(&mut self, base: VAddr) {
+        assert!(self.regions.remove(&base).is_some());
+    }
+
+    /// Get the lines printed by [Self::dump_regions].
+    pub fn describe_regions(&self) -> Vec<String> {
+        let null_segment = (self.null_segment_size != 0).then(|| {
+            (
+                0,
+                self.null_segment_size,
+                Protection::NONE,
+                "null segment (__PAGEZERO)",
+            )
+        });
+        let regions = self
+            .regions
+            .iter()
+            .map(|(&base, region)| (base, region.size, region.protection, region.name.as_str()));
+        let guard_pages = self
+            .guard_pages
+            .iter()
+            .map(|&page| (page, Self::PAGE_SIZE, Protection::NONE, "guard page"));
+
+        let mut all: Vec<_> = null_segment
+            .into_iter()
+            .chain(regions)
+            .chain(guard_pages)
+            .collect();
+        all.sort_by_key(|&(base, ..)| base);
+
+        let mut lines: Vec<String> = all
+            .into_iter()
+            .map(|(base, size, protection, name)| describe_region(base, size, protection, name))
+            .collect();
+        let heap_stats = self.heap_stats();
+        lines.push(format!(
+            "heap: {} bytes used in {} allocations, {} bytes free",
+            heap_stats.bytes_used, heap_stats.chunks_used, heap_stats.bytes_free
+        ));
+        lines
+    }
+
+    /// Print a map of the guest address space: the null segment, the segments
+    /// of each loaded binary, thread stacks and their guard pages, mmap'd files
+    /// and a summary of the heap. Regions in the heap, like secondary thread
+    /// stacks, are listed both individually and as part of the summary.
+    pub fn dump_regions(&self) {
+        for line in self.describe_regions() {
+            echo!("{}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_region() {
+        let r_x = Protection {
+            read: true,
+            write: false,
+            execute: true,
+        };
+        assert_eq!(
+            describe_region(0x1000, 0x4000, r_x, "__TEXT of Foo"),
+            "0x00001000-0x00004fff    16 KiB r-x __TEXT of Foo"
+        );
+        assert_eq!(
+            describe_region(0xfff00000, 0x100000, Protection::READ_WRITE, "stack"),
+            "0xfff00000-0xffffffff  1024 KiB rw- stack"
+        );
+        assert_eq!(
+            describe_region(0x5000, 0x123, Protection::NONE, "odd"),
+            "0x00005000-0x00005122     291 B --- odd"
+        );
+    }
+
+    #[test]
+    fn test_describe_regions() {
+        let mut mem = Mem::new();
+
+        // What loading a binary does (see crate::mach_o)
+        mem.set_null_segment_size(0x1000);
+        let r_x = Protection {
+            read: true,
+            write: false,
+            execute: true,
+        };
+        mem.reserve(0x1000, 0x4000);
+        mem.add_region(0x1000, 0x4000, r_x, "__TEXT of Foo".to_string());
+        mem.reserve(0x5000, 0x1000);
+        let data = Protection::READ_WRITE;
+        mem.add_region(0x5000, 0x1000, data, "__DATA of Foo".to_string());
+
+        // What spawning a thread does (see crate::Environment::new_thread)
+        let stack_alloc = mem.alloc(0x10000 + 2 * Mem::PAGE_SIZE).to_bits();
+        let guard_page = stack_alloc.next_multiple_of(Mem::PAGE_SIZE);
+        mem.add_guard_page(guard_page);
+        let stack = guard_page + Mem::PAGE_SIZE;
+        mem.add_region(stack, 0x10000, data, "stack of thread 1".to_string());
+
+        let lines = mem.describe_regions();
+        let expected = [
+            "0x00000000-0x00000fff     4 KiB --- null segment (__PAGEZERO)".to_string(),
+            "0x00001000-0x00004fff    16 KiB r-x __TEXT of Foo".to_string(),
+            "0x00005000-0x00005fff     4 KiB rw- __DATA of Foo".to_string(),
+            describe_region(guard_page, Mem::PAGE_SIZE, Protection::NONE, "guard page"),
+            describe_region(stack, 0x10000, data, "stack of thread 1"),
+            "0xffeff000-0xffefffff     4 KiB --- guard page".to_string(),
+            "0xfff00000-0xffffffff  1024 KiB rw- stack of thread 0 (main thread)".to_string(),
+        ];
+        assert_eq!(lines[..7], expected);
+        assert!(lines[7].starts_with("heap: "));
+        assert_eq!(lines.len(), 8);
+
+        // What a thread exiting does
+        mem.remove_region(stack);
+        mem.remove_guard_page(guard_page);
+        assert_eq!(mem.describe_regions().len(), 6);
+    }
+}
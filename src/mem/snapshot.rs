@@ -0,0 +1,134 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Checksums and snapshots of regions of guest memory, for regression testing.
+//!
+//! These make it possible to check that some code leaves a region of memory in
+//! a known state, or that it only changes the bytes it is expected to change.
+
+use super::{ConstVoidPtr, GuestUSize, Mem};
+use std::ops::Range;
+
+/// A copy of a region of guest memory, see [Mem::snapshot_region].
+#[derive(Clone, Debug)]
+pub struct MemSnapshot {
+    ptr: ConstVoidPtr,
+    bytes: Vec<u8>,
+}
+
+impl MemSnapshot {
+    /// The checksum of the region at the time the snapshot was taken, the same
+    /// as [Mem::checksum_region] would have returned.
+    pub fn checksum(&self) -> u64 {
+        checksum(&self.bytes)
+    }
+}
+
+/// 64-bit FNV-1a. This is used rather than Rust's default hasher because the
+/// result must not change between runs or Rust versions.
+fn checksum(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// Finds the ranges of offsets at which `old` and `new` differ, merging
+/// adjacent offsets into a single range.
+fn diff(old: &[u8], new: &[u8]) -> Vec<Range<GuestUSize>> {
+    // Comparing whole chunks is much faster than comparing individual bytes,
+    // and most of a large region is usually unchanged.
+    const CHUNK_SIZE: usize = 4096;
+
+    assert_eq!(old.len(), new.len());
+    let mut ranges: Vec<Range<GuestUSize>> = Vec::new();
+    let chunks = old.chunks(CHUNK_SIZE).zip(new.chunks(CHUNK_SIZE));
+    for (chunk_idx, (old_chunk, new_chunk)) in chunks.enumerate() {
+        if old_chunk == new_chunk {
+            continue;
+        }
+        for (i, (old_byte, new_byte)) in old_chunk.iter().zip(new_chunk).enumerate() {
+            if old_byte == new_byte {
+                continue;
+            }
+            let offset = (chunk_idx * CHUNK_SIZE + i) as GuestUSize;
+            match ranges.last_mut() {
+                Some(range) if range.end == offset => range.end += 1,
+                _ => ranges.push(offset..offset + 1),
+            }
+        }
+    }
+    ranges
+}
+
+impl Mem {
+    /// Compute a checksum of `len` bytes of guest memory starting at `ptr`.
+    /// The checksum is the same between runs, so it can be compared with a
+    /// known-good value.
+    pub fn checksum_region(&self, ptr: ConstVoidPtr, len: GuestUSize) -> u64 {
+        checksum(self.bytes_at(ptr.cast(), len))
+    }
+
+    /// Copy `len` bytes of guest memory starting at `ptr`, so they can be
+    /// compared later with [Self::diff_region].
+    pub fn snapshot_region(&self, ptr: ConstVoidPtr, len: GuestUSize) -> MemSnapshot {
+        MemSnapshot {
+            ptr,
+            bytes: self.bytes_at(ptr.cast(), len).to_vec(),
+        }
+    }
+
+    /// Compare the current contents of a region of guest memory with a
+    /// snapshot of it. Returns the ranges of bytes that have changed, as
+    /// offsets from the start of the region.
+    pub fn diff_region(&self, snapshot: &MemSnapshot) -> Vec<Range<GuestUSize>> {
+        let len = snapshot.bytes.len() as GuestUSize;
+        diff(&snapshot.bytes, self.bytes_at(snapshot.ptr.cast(), len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::MutPtr;
+
+    #[test]
+    fn test_checksum() {
+        // Test vectors from the FNV reference
+        assert_eq!(checksum(b""), 0xcbf29ce484222325);
+        assert_eq!(checksum(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(checksum(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn test_diff() {
+        assert!(diff(b"abcdef", b"abcdef").is_empty());
+        assert_eq!(diff(b"abcdef", b"aXYdeZ"), [1..3, 5..6]);
+    }
+
+    #[test]
+    fn test_snapshot_region() {
+        let mut mem = Mem::new();
+        let len = 0x10000;
+        let region = mem.alloc(len);
+        for (i, byte) in mem.bytes_at_mut(region.cast(), len).iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let snapshot = mem.snapshot_region(region.cast_const(), len);
+        let checksum = mem.checksum_region(region.cast_const(), len);
+        assert_eq!(snapshot.checksum(), checksum);
+
+        let changed: MutPtr<u8> = region.cast() + 0x1234;
+        mem.write(changed, !mem.read(changed));
+        assert_ne!(mem.checksum_region(region.cast_const(), len), checksum);
+        assert_eq!(mem.diff_region(&snapshot), [0x1234..0x1235]);
+
+        mem.write(changed, !mem.read(changed));
+        assert_eq!(mem.checksum_region(region.cast_const(), len), checksum);
+        assert!(mem.diff_region(&snapshot).is_empty());
+    }
+}
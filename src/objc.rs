@@ -18,21 +18,27 @@
 //! classes that are both (considering Objective-C's support for inheritance,
 //! categories and dynamic class editing).
 
+use crate::abi::{CallFromHost, GuestFunction};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::MutexId;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+mod arc;
+mod class_hierarchy;
 mod classes;
 mod messages;
 mod methods;
 mod objects;
 mod properties;
+mod protocols;
 mod selectors;
 mod synchronization;
+mod tracking;
 
 pub use classes::{objc_classes, Class, ClassExports, ClassTemplate};
 pub use messages::{
-    autorelease, msg, msg_class, msg_send, msg_send_super2, msg_super, objc_super, release, retain,
+    autorelease, msg, msg_class, msg_send, msg_send_super2, msg_send_with_frame, msg_super,
+    objc_super, release, retain, unrecognized_selector_message, MsgSendArgs,
 };
 pub use methods::{HostIMP, IMP};
 pub use objects::{
@@ -40,15 +46,27 @@ pub use objects::{
 };
 pub use selectors::{selector, SEL};
 
-use crate::mem::ConstVoidPtr;
+use crate::mem::ConstPtr;
 use crate::Environment;
-use classes::{ClassHostObject, FakeClass, UnimplementedClass, CLASS_LISTS};
+use arc::{objc_autorelease, objc_release, objc_retain, objc_retainAutorelease};
+use classes::{
+    objc_allocateClassPair, objc_getClass, objc_registerClassPair, ClassHostObject, FakeClass,
+    UnimplementedClass, CLASS_LISTS,
+};
 use messages::{
     objc_msgSend, objc_msgSendSuper2, objc_msgSend_stret, MsgSendSignature, MsgSendSuperSignature,
 };
-use methods::method_list_t;
+use methods::{class_addMethod, method_list_t};
 use objects::{objc_object, HostObjectEntry};
-use properties::{ivar_list_t, objc_copyStruct, objc_getProperty, objc_setProperty};
+use properties::{
+    class_addIvar, class_addProperty, class_copyPropertyList, class_getProperty, ivar_list_t,
+    objc_copyStruct, objc_getProperty, objc_setProperty, object_getInstanceVariable,
+    property_copyAttributeValue, property_getAttributes, property_getName, property_list_t,
+    property_t,
+};
+use protocols::{
+    class_conformsToProtocol, objc_getProtocol, protocol_getName, protocol_list_t, protocol_t,
+};
 use selectors::sel_registerName;
 use synchronization::{objc_sync_enter, objc_sync_exit};
 
@@ -73,13 +91,34 @@ pub struct ObjC {
     /// Look at the `isa` to get the metaclass for a class.
     classes: HashMap<String, Class>,
 
+    /// Known protocols, see [protocols].
+    protocols: HashMap<String, ConstPtr<protocol_t>>,
+
     /// Mutexes used in @synchronized blocks (objc_sync_enter/exit).
     sync_mutexes: HashMap<id, MutexId>,
 
+    /// Deallocated objects kept around to detect messages sent to them, if
+    /// enabled with [ObjC::enable_zombies]. The value is the object's class.
+    /// The memory of these objects is never freed.
+    zombies: Option<HashMap<id, Class>>,
+
+    /// Live objects and where they were allocated, if enabled with
+    /// [ObjC::enable_object_tracking].
+    tracker: Option<tracking::ObjectTracker>,
+
+    /// Names of classes whose messages are logged, see [ObjC::trace_class].
+    traced_classes: HashSet<String>,
+
     /// Temporary storage for optional type information when sending a message.
     /// Type information isn't part of the `objc_msgSend` ABI, so an alternative
     /// channel is needed.
     message_type_info: Option<(std::any::TypeId, &'static str)>,
+
+    /// Guest function set with `objc_setEnumerationMutationHandler()`.
+    enumeration_mutation_handler: Option<GuestFunction>,
+
+    /// Selectors used by `objc_retain()` and friends, see [arc].
+    arc_selectors: Option<arc::ArcSelectors>,
 }
 
 impl ObjC {
@@ -88,36 +127,139 @@ impl ObjC {
             selectors: HashMap::new(),
             objects: HashMap::new(),
             classes: HashMap::new(),
+            protocols: HashMap::new(),
             sync_mutexes: HashMap::new(),
+            zombies: None,
+            tracker: None,
+            traced_classes: HashSet::new(),
             message_type_info: None,
+            enumeration_mutation_handler: None,
+            arc_selectors: None,
+        }
+    }
+
+    /// Turn on detection of over-released objects (like `NSZombieEnabled`):
+    /// deallocated objects are never freed, and sending a message to one is a
+    /// fatal error.
+    pub fn enable_zombies(&mut self) {
+        self.zombies.get_or_insert_with(HashMap::new);
+    }
+
+    /// Turn on tracking of live objects, so [ObjC::dump_live_objects] can be
+    /// used to find leaks.
+    pub fn enable_object_tracking(&mut self) {
+        self.tracker.get_or_insert_with(Default::default);
+    }
+
+    /// Log every message sent to an instance of the class named `name`, or to
+    /// the class object itself. Instances of subclasses aren't included, so
+    /// that the output is limited to one class.
+    pub fn trace_class(&mut self, name: &str) {
+        self.traced_classes.insert(name.to_string());
+    }
+
+    fn is_traced_class(&self, class: Class) -> bool {
+        !self.traced_classes.is_empty() && self.traced_classes.contains(self.get_class_name(class))
+    }
+
+    fn has_untraced_objects(&self) -> bool {
+        self.tracker
+            .as_ref()
+            .is_some_and(|tracker| tracker.has_pending())
+    }
+
+    fn set_allocation_backtrace(&mut self, backtrace: tracking::Backtrace) {
+        self.tracker
+            .as_mut()
+            .unwrap()
+            .set_pending_backtraces(backtrace);
+    }
+
+    /// Print how many objects of each class are still alive, and where most of
+    /// them were allocated, if object tracking is enabled.
+    pub fn dump_live_objects(&self) {
+        let Some(ref tracker) = self.tracker else {
+            return;
+        };
+        let counts = tracker.live_counts();
+        echo!(
+            "{} live Objective-C objects:",
+            counts.iter().map(|&(_, count)| count).sum::<usize>()
+        );
+        for (class, count) in counts {
+            echo!("{:6} {}", count, self.get_class_name(class));
+            if let Some((backtrace, shared)) = tracker.most_common_backtrace(class) {
+                let backtrace: Vec<String> = backtrace
+                    .iter()
+                    .map(|addr| format!("{:#x}", addr))
+                    .collect();
+                echo!(
+                    "       {} allocated from: {}",
+                    shared,
+                    backtrace.join(" <- ")
+                );
+            }
         }
     }
 }
 
-/// Block support is iOS 4+, but it seems like Block Runtime Helpers
-/// could still be called on even if minimal iOS version is set to 3.x?
-///
-/// ref. <https://clang.llvm.org/docs/Block-ABI-Apple.html#runtime-helper-functions>
-fn _Block_object_dispose(_env: &mut Environment, object: ConstVoidPtr, flags: i32) {
-    // `BLOCK_FIELD_IS_BYREF` flag defines an on stack structure holding
-    // the __block variable. It is _probably_ safe to ignore.
-    // TODO: properly implement for block support
-    assert!(flags == 8); // BLOCK_FIELD_IS_BYREF
-    log!(
-        "Warning: Ignoring _Block_object_dispose({:?}, BLOCK_FIELD_IS_BYREF)",
-        object
+/// Standard Objective-C runtime function, called by compiler-generated fast
+/// enumeration loops (`for (id x in collection)`) when the collection is
+/// mutated during enumeration.
+fn objc_enumerationMutation(env: &mut Environment, collection: id) {
+    if let Some(handler) = env.objc.enumeration_mutation_handler {
+        () = handler.call_from_host(env, (collection,));
+        return;
+    }
+    let class = ObjC::read_isa(collection, &env.mem);
+    let message = format!(
+        "*** Collection <{}: {:?}> was mutated while being enumerated.",
+        env.objc.get_class_name(class),
+        collection
     );
+    // TODO: Raise a real NSGenericException
+    echo!(
+        "*** Terminating app due to uncaught exception 'NSGenericException', reason: '{}'",
+        message
+    );
+    panic!("{}", message);
+}
+
+/// Standard Objective-C runtime function for replacing the default behaviour
+/// of [objc_enumerationMutation].
+fn objc_setEnumerationMutationHandler(env: &mut Environment, handler: GuestFunction) {
+    env.objc.enumeration_mutation_handler = (!handler.to_ptr().is_null()).then_some(handler);
 }
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(objc_msgSend(_, _)),
     export_c_func!(objc_msgSend_stret(_, _, _)),
     export_c_func!(objc_msgSendSuper2(_, _)),
+    export_c_func!(objc_retain(_)),
+    export_c_func!(objc_release(_)),
+    export_c_func!(objc_autorelease(_)),
+    export_c_func!(objc_retainAutorelease(_)),
     export_c_func!(objc_getProperty(_, _, _, _)),
     export_c_func!(objc_setProperty(_, _, _, _, _, _)),
     export_c_func!(objc_copyStruct(_, _, _, _, _)),
     export_c_func!(objc_sync_enter(_)),
     export_c_func!(objc_sync_exit(_)),
     export_c_func!(sel_registerName(_)),
-    export_c_func!(_Block_object_dispose(_, _)),
+    export_c_func!(objc_getClass(_)),
+    export_c_func!(objc_allocateClassPair(_, _, _)),
+    export_c_func!(objc_registerClassPair(_)),
+    export_c_func!(class_addMethod(_, _, _, _)),
+    export_c_func!(class_addIvar(_, _, _, _, _)),
+    export_c_func!(object_getInstanceVariable(_, _, _)),
+    export_c_func!(class_getProperty(_, _)),
+    export_c_func!(class_copyPropertyList(_, _)),
+    export_c_func!(class_addProperty(_, _, _, _)),
+    export_c_func!(property_getName(_)),
+    export_c_func!(property_getAttributes(_)),
+    export_c_func!(property_copyAttributeValue(_, _)),
+    export_c_func!(objc_getProtocol(_)),
+    export_c_func!(class_conformsToProtocol(_, _)),
+    export_c_func!(protocol_getName(_)),
+    export_c_func!(objc_enumerationMutation(_)),
+    export_c_func!(objc_setEnumerationMutationHandler(_)),
 ];
@@ -0,0 +1,119 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Runtime functions used by code compiled with Automatic Reference Counting.
+//!
+//! ARC code calls `objc_retain()`, `objc_release()` etc instead of sending
+//! `retain`, `release` etc messages, and it does this constantly, so these
+//! functions are among the hottest in the runtime. When the receiver's class
+//! uses `NSObject`'s implementation of the method, the refcount is changed
+//! directly, skipping message dispatch. Overridden methods are still called.
+//!
+//! Resources:
+//! - [Clang's ARC documentation](https://clang.llvm.org/docs/AutomaticReferenceCounting.html#runtime-support)
+
+use super::{id, msg, msg_class, nil, ClassHostObject, ObjC, IMP, SEL};
+use crate::Environment;
+
+/// The selectors the fast paths check for, cached in [ObjC] so they don't
+/// have to be looked up by name on every call.
+#[derive(Copy, Clone)]
+pub(super) struct ArcSelectors {
+    retain: SEL,
+    release: SEL,
+    autorelease: SEL,
+}
+
+fn arc_selectors(env: &mut Environment) -> ArcSelectors {
+    if let Some(selectors) = env.objc.arc_selectors {
+        return selectors;
+    }
+    // These are all NSObject methods, so they're always registered.
+    let selectors = ArcSelectors {
+        retain: env.objc.lookup_selector("retain").unwrap(),
+        release: env.objc.lookup_selector("release").unwrap(),
+        autorelease: env.objc.lookup_selector("autorelease").unwrap(),
+    };
+    env.objc.arc_selectors = Some(selectors);
+    selectors
+}
+
+/// Checks whether sending a message with the selector `sel` to `object` would
+/// call `NSObject`'s host implementation of it. This is conservative: zombies,
+/// objects of traced classes and anything else unusual get `false`, so that
+/// sending the message handles them properly.
+fn uses_ns_object_method(env: &mut Environment, object: id, sel: SEL) -> bool {
+    if env.objc.zombie_class(object).is_some() {
+        return false;
+    }
+    let ns_object = env.objc.get_known_class("NSObject", &mut env.mem);
+
+    let mut class = ObjC::read_isa(object, &env.mem);
+    if env.objc.is_traced_class(class) {
+        return false;
+    }
+    while class != nil {
+        let Some(&ClassHostObject {
+            superclass,
+            ref methods,
+            ..
+        }) = env
+            .objc
+            .get_host_object(class)
+            .and_then(|host_object| host_object.as_any().downcast_ref())
+        else {
+            return false;
+        };
+        if let Some(imp) = methods.get(&sel) {
+            // A category on NSObject could have replaced the method.
+            return class == ns_object && matches!(imp, IMP::Host(_));
+        }
+        class = superclass;
+    }
+    false
+}
+
+pub(super) fn objc_retain(env: &mut Environment, object: id) -> id {
+    if object == nil {
+        return nil;
+    }
+    if uses_ns_object_method(env, object, arc_selectors(env).retain) {
+        env.objc.increment_refcount(object);
+        object
+    } else {
+        msg![env; object retain]
+    }
+}
+
+pub(super) fn objc_release(env: &mut Environment, object: id) {
+    if object == nil {
+        return;
+    }
+    if uses_ns_object_method(env, object, arc_selectors(env).release) {
+        if env.objc.decrement_refcount(object) {
+            () = msg![env; object dealloc];
+        }
+    } else {
+        msg![env; object release]
+    }
+}
+
+pub(super) fn objc_autorelease(env: &mut Environment, object: id) -> id {
+    if object == nil {
+        return nil;
+    }
+    if uses_ns_object_method(env, object, arc_selectors(env).autorelease) {
+        () = msg_class![env; NSAutoreleasePool addObject:object];
+        object
+    } else {
+        msg![env; object autorelease]
+    }
+}
+
+#[allow(non_snake_case)]
+pub(super) fn objc_retainAutorelease(env: &mut Environment, object: id) -> id {
+    let object = objc_retain(env, object);
+    objc_autorelease(env, object)
+}
@@ -0,0 +1,170 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Printing the class hierarchy, to help with reverse-engineering apps. See
+//! [super::ObjC::dump_class_hierarchy].
+
+use super::classes::{ClassHostObject, FakeClass, UnimplementedClass};
+use super::{nil, Class, ObjC};
+use crate::mem::Mem;
+use std::collections::HashMap;
+
+/// What's known about a class or metaclass for the purposes of printing it.
+struct ClassInfo<'a> {
+    class: Class,
+    name: &'a str,
+    is_metaclass: bool,
+    superclass: Class,
+    kind: &'static str,
+}
+
+/// Formats classes as an indented tree where each class is followed by its
+/// subclasses. Classes whose superclass isn't in the list are roots.
+fn format_hierarchy(classes: &[ClassInfo]) -> String {
+    let mut children = HashMap::<Class, Vec<&ClassInfo>>::new();
+    let mut roots = Vec::new();
+    for info in classes {
+        if info.superclass != nil && classes.iter().any(|other| other.class == info.superclass) {
+            children.entry(info.superclass).or_default().push(info);
+        } else {
+            roots.push(info);
+        }
+    }
+
+    fn write_tree(
+        out: &mut String,
+        mut infos: Vec<&ClassInfo>,
+        children: &HashMap<Class, Vec<&ClassInfo>>,
+        depth: usize,
+    ) {
+        infos.sort_by_key(|info| (info.is_metaclass, info.name));
+        for info in infos {
+            out.push_str(&"  ".repeat(depth));
+            out.push_str(info.name);
+            if info.is_metaclass {
+                out.push_str(" (metaclass, ");
+            } else {
+                out.push_str(" (");
+            }
+            out.push_str(info.kind);
+            out.push_str(")\n");
+            if let Some(subclasses) = children.get(&info.class) {
+                write_tree(out, subclasses.clone(), children, depth + 1);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    write_tree(&mut out, roots, &children, 0);
+    out
+}
+
+impl ObjC {
+    /// Print all the classes known so far as a tree, marking which are
+    /// implemented by touchHLE ("host") and which by the app ("guest"). After
+    /// [crate::dyld::Dyld::do_initial_linking], this is all the app's own
+    /// classes plus the framework classes it references.
+    pub fn dump_class_hierarchy(&self, mem: &Mem, include_metaclasses: bool) {
+        let mut classes = Vec::new();
+        for &class in self.classes.values() {
+            classes.push(class);
+            if include_metaclasses {
+                classes.push(Self::read_isa(class, mem));
+            }
+        }
+        let infos: Vec<ClassInfo> = classes
+            .into_iter()
+            .map(|class| {
+                let any = self.get_host_object(class).unwrap().as_any();
+                if let Some(host_object) = any.downcast_ref::<ClassHostObject>() {
+                    ClassInfo {
+                        class,
+                        name: &host_object.name,
+                        is_metaclass: host_object.is_metaclass,
+                        superclass: host_object.superclass,
+                        kind: if host_object.is_host { "host" } else { "guest" },
+                    }
+                } else if let Some(host_object) = any.downcast_ref::<UnimplementedClass>() {
+                    ClassInfo {
+                        class,
+                        name: &host_object.name,
+                        is_metaclass: host_object.is_metaclass,
+                        superclass: nil,
+                        kind: "unimplemented",
+                    }
+                } else if let Some(host_object) = any.downcast_ref::<FakeClass>() {
+                    ClassInfo {
+                        class,
+                        name: &host_object.name,
+                        is_metaclass: host_object.is_metaclass,
+                        superclass: nil,
+                        kind: "fake",
+                    }
+                } else {
+                    panic!();
+                }
+            })
+            .collect();
+
+        echo!("Objective-C class hierarchy:");
+        for line in format_hierarchy(&infos).lines() {
+            echo!("{}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::Ptr;
+
+    #[test]
+    fn test_two_level_subclass() {
+        let [ns_object, ns_object_meta, view, view_meta, sub, sub_meta, missing]: [Class; 7] =
+            [0x1000, 0x1010, 0x2000, 0x2010, 0x3000, 0x3010, 0x4000].map(Ptr::from_bits);
+        let info = |class, name, is_metaclass, superclass, kind| ClassInfo {
+            class,
+            name,
+            is_metaclass,
+            superclass,
+            kind,
+        };
+        let classes = [
+            info(sub, "MyView", false, view, "guest"),
+            info(missing, "ADBannerView", false, nil, "unimplemented"),
+            info(view, "UIView", false, ns_object, "host"),
+            info(ns_object, "NSObject", false, nil, "host"),
+        ];
+        assert_eq!(
+            format_hierarchy(&classes),
+            "\
+ADBannerView (unimplemented)
+NSObject (host)
+  UIView (host)
+    MyView (guest)
+"
+        );
+
+        let with_metaclasses = [
+            info(sub_meta, "MyView", true, view_meta, "guest"),
+            info(view_meta, "UIView", true, ns_object_meta, "host"),
+            info(ns_object_meta, "NSObject", true, ns_object, "host"),
+            info(ns_object, "NSObject", false, nil, "host"),
+            info(view, "UIView", false, ns_object, "host"),
+            info(sub, "MyView", false, view, "guest"),
+        ];
+        assert_eq!(
+            format_hierarchy(&with_metaclasses),
+            "\
+NSObject (host)
+  UIView (host)
+    MyView (guest)
+  NSObject (metaclass, host)
+    UIView (metaclass, host)
+      MyView (metaclass, guest)
+"
+        );
+    }
+}
@@ -14,11 +14,12 @@ mod class_lists;
 pub(super) use class_lists::CLASS_LISTS;
 
 use super::{
-    id, ivar_list_t, method_list_t, nil, objc_object, AnyHostObject, HostIMP, HostObject, ObjC,
-    IMP, SEL,
+    id, ivar_list_t, method_list_t, nil, objc_object, property_list_t, property_t, protocol_list_t,
+    AnyHostObject, HostIMP, HostObject, ObjC, IMP, SEL,
 };
 use crate::mach_o::MachO;
 use crate::mem::{guest_size_of, ConstPtr, ConstVoidPtr, GuestUSize, Mem, Ptr, SafeRead};
+use crate::Environment;
 use std::collections::HashMap;
 
 /// Generic pointer to an Objective-C class or metaclass.
@@ -37,9 +38,17 @@ pub type Class = id;
 pub(super) struct ClassHostObject {
     pub(super) name: String,
     pub(super) is_metaclass: bool,
+    /// Whether this class is implemented by touchHLE rather than the app.
+    pub(super) is_host: bool,
     pub(super) superclass: Class,
     pub(super) methods: HashMap<SEL, IMP>,
     pub(super) ivars: HashMap<String, ConstPtr<GuestUSize>>,
+    /// Declared properties, in declaration order. The pointers can be given to
+    /// the app as `objc_property_t` values.
+    pub(super) properties: Vec<(String, ConstPtr<property_t>)>,
+    /// Names of the protocols this class adopts, including the ones those
+    /// inherit from. This is always empty for metaclasses.
+    pub(super) protocols: Vec<String>,
     /// Offset into the allocated memory for the object where the ivars of
     /// instances of this class or metaclass (respectively: normal objects or
     /// classes) should live. This is always >= the value in the superclass.
@@ -97,10 +106,10 @@ struct class_rw_t {
     _reserved: u32,
     name: ConstPtr<u8>,
     base_methods: ConstPtr<method_list_t>,
-    _base_protocols: ConstVoidPtr, // protocol list (TODO)
+    base_protocols: ConstPtr<protocol_list_t>,
     ivars: ConstPtr<ivar_list_t>,
     _weak_ivar_layout: u32,
-    _base_properties: ConstVoidPtr, // property list (TODO)
+    base_properties: ConstPtr<property_list_t>,
 }
 unsafe impl SafeRead for class_rw_t {}
 
@@ -113,7 +122,7 @@ struct category_t {
     class: Class,
     instance_methods: ConstPtr<method_list_t>,
     class_methods: ConstPtr<method_list_t>,
-    _protocols: ConstVoidPtr,     // protocol list (TODO)
+    protocols: ConstPtr<protocol_list_t>,
     _property_list: ConstVoidPtr, // property list (TODO)
 }
 unsafe impl SafeRead for category_t {}
@@ -128,6 +137,8 @@ pub struct ClassTemplate {
     pub superclass: Option<&'static str>,
     pub class_methods: &'static [(&'static str, &'static dyn HostIMP)],
     pub instance_methods: &'static [(&'static str, &'static dyn HostIMP)],
+    /// Names of the protocols the class adopts.
+    pub protocols: &'static [&'static str],
 }
 
 /// Type for lists of classes exported by host implementations of frameworks.
@@ -194,7 +205,7 @@ macro_rules! _objc_method {
 ///                    // The second one should be `self` to match Objective-C,
 ///                    // but that's reserved in Rust, hence `this`.
 ///
-/// @implementation MyClass: NSObject
+/// @implementation MyClass: NSObject <NSCopying>
 ///
 /// + (id)foo {
 ///     // ...
@@ -219,6 +230,7 @@ macro_rules! _objc_method {
 ///     ("MyClass", ClassTemplate {
 ///         name: "MyClass",
 ///         superclass: Some("NSObject"),
+///         protocols: &["NSCopying"],
 ///         class_methods: &[
 ///             ("foo", &(|env: &mut Environment, this: id, _cmd: SEL| -> id {
 ///                 // ...
@@ -247,7 +259,8 @@ macro_rules! _objc_method {
 /// ];
 /// ```
 ///
-/// Note that the instance methods must be preceded by the class methods.
+/// Note that the instance methods must be preceded by the class methods. The
+/// protocol list is optional, as is the superclass.
 #[macro_export] // documentation comment links are annoying without this
 macro_rules! objc_classes {
     {
@@ -257,6 +270,7 @@ macro_rules! objc_classes {
         ($env:ident, $this:ident, $_cmd:ident);
         $(
             @implementation $class_name:ident $(: $superclass_name:ident)?
+                $(<$($protocol_name:ident),+>)?
 
             $( + ($cm_type:ty) $cm_name:ident $(:($cm_type1:ty) $cm_arg1:ident)?
                               $($cm_namen:ident:($cm_typen:ty) $cm_argn:ident)*
@@ -283,6 +297,7 @@ macro_rules! objc_classes {
                 (_OBJC_CURRENT_CLASS, $crate::objc::ClassTemplate {
                     name: _OBJC_CURRENT_CLASS,
                     superclass: $crate::_objc_superclass!($(: $superclass_name)?),
+                    protocols: &[$($(stringify!($protocol_name)),+)?],
                     class_methods: &[
                         $(
                             (
@@ -346,6 +361,7 @@ impl ClassHostObject {
         ClassHostObject {
             name: template.name.to_string(),
             is_metaclass,
+            is_host: true,
             superclass,
             methods: HashMap::from_iter(
                 (if is_metaclass {
@@ -365,6 +381,16 @@ impl ClassHostObject {
             instance_start: size,
             instance_size: size,
             ivars: HashMap::default(),
+            properties: Vec::new(),
+            protocols: if is_metaclass {
+                Vec::new()
+            } else {
+                template
+                    .protocols
+                    .iter()
+                    .map(|&name| name.to_string())
+                    .collect()
+            },
         }
     }
 
@@ -377,7 +403,9 @@ impl ClassHostObject {
             instance_size,
             name,
             base_methods,
+            base_protocols,
             ivars,
+            base_properties,
             ..
         } = mem.read(data);
 
@@ -386,11 +414,14 @@ impl ClassHostObject {
         let mut host_object = ClassHostObject {
             name,
             is_metaclass,
+            is_host: false,
             superclass,
             methods: HashMap::new(),
             instance_start,
             instance_size,
             ivars: HashMap::new(),
+            properties: Vec::new(),
+            protocols: Vec::new(),
         };
 
         if !base_methods.is_null() {
@@ -401,6 +432,16 @@ impl ClassHostObject {
             host_object.add_ivars_from_bin(ivars, mem);
         }
 
+        if !base_properties.is_null() {
+            host_object.add_properties_from_bin(base_properties, mem);
+        }
+
+        // The metaclass has the same protocol list, but it's the class that
+        // conforms.
+        if !base_protocols.is_null() && !is_metaclass {
+            host_object.add_protocols_from_bin(base_protocols, mem);
+        }
+
         host_object
     }
 
@@ -463,6 +504,78 @@ fn substitute_classes(
     Some((class_host_object, metaclass_host_object))
 }
 
+/// Standard Objective-C runtime function for looking up a class by name.
+pub(super) fn objc_getClass(env: &mut Environment, name: ConstPtr<u8>) -> Class {
+    let name = env.mem.cstr_at_utf8(name).unwrap().to_string();
+    if env
+        .objc
+        .get_class(&name, /* is_metaclass: */ false, &env.mem)
+        .is_none()
+        && ObjC::find_template(&name).is_none()
+    {
+        return nil;
+    }
+    env.objc.get_known_class(&name, &mut env.mem)
+}
+
+/// Standard Objective-C runtime function for creating a class at runtime.
+/// The class can't be used until it's registered with
+/// [objc_registerClassPair].
+pub(super) fn objc_allocateClassPair(
+    env: &mut Environment,
+    superclass: Class,
+    name: ConstPtr<u8>,
+    extra_bytes: GuestUSize,
+) -> Class {
+    let name = env.mem.cstr_at_utf8(name).unwrap().to_string();
+    if env
+        .objc
+        .get_class(&name, /* is_metaclass: */ false, &env.mem)
+        .is_some()
+        || ObjC::find_template(&name).is_some()
+    {
+        return nil;
+    }
+    // TODO: new root classes, indexed ivars in class objects
+    assert!(superclass != nil);
+    assert!(extra_bytes == 0);
+
+    let super_metaclass = ObjC::read_isa(superclass, &env.mem);
+    let [class_host_object, metaclass_host_object] = [(superclass, false), (super_metaclass, true)]
+        .map(|(superclass, is_metaclass)| {
+            let &ClassHostObject { instance_size, .. } = env.objc.borrow(superclass);
+            Box::new(ClassHostObject {
+                name: name.clone(),
+                is_metaclass,
+                is_host: false,
+                superclass,
+                methods: HashMap::new(),
+                ivars: HashMap::new(),
+                properties: Vec::new(),
+                protocols: Vec::new(),
+                instance_start: instance_size,
+                instance_size,
+            })
+        });
+
+    let root_metaclass =
+        env.objc
+            .link_class("NSObject", /* is_metaclass: */ true, &mut env.mem);
+    let metaclass =
+        env.objc
+            .alloc_static_object(root_metaclass, metaclass_host_object, &mut env.mem);
+    env.objc
+        .alloc_static_object(metaclass, class_host_object, &mut env.mem)
+}
+
+/// Standard Objective-C runtime function for registering a class created with
+/// [objc_allocateClassPair].
+pub(super) fn objc_registerClassPair(env: &mut Environment, class: Class) {
+    let name = env.objc.get_class_name(class).to_string();
+    assert!(!env.objc.classes.contains_key(&name));
+    env.objc.classes.insert(name, class);
+}
+
 impl ObjC {
     fn get_class(&self, name: &str, is_metaclass: bool, mem: &Mem) -> Option<Class> {
         let class = self.classes.get(name).copied()?;
@@ -473,6 +586,12 @@ impl ObjC {
         })
     }
 
+    /// Checks whether touchHLE has an implementation of a class, without
+    /// creating it.
+    pub fn is_host_class(name: &str) -> bool {
+        Self::find_template(name).is_some()
+    }
+
     fn find_template(name: &str) -> Option<&'static ClassTemplate> {
         crate::dyld::search_lists(CLASS_LISTS, name).map(|&(_name, ref template)| template)
     }
@@ -716,11 +835,14 @@ impl ObjC {
                     ClassHostObject {
                         name: Default::default(),
                         is_metaclass: Default::default(),
+                        is_host: Default::default(),
                         superclass: nil,
                         methods: Default::default(),
                         instance_start: Default::default(),
                         instance_size: Default::default(),
                         ivars: Default::default(),
+                        properties: Default::default(),
+                        protocols: Default::default(),
                     },
                 );
                 log_dbg!(
@@ -743,6 +865,14 @@ impl ObjC {
                 host_obj.add_methods_from_bin(methods, mem, self);
                 *self.borrow_mut::<ClassHostObject>(class) = host_obj;
             }
+
+            if !data.protocols.is_null() {
+                let any = self.get_host_object(class).unwrap().as_any();
+                if any.is::<ClassHostObject>() {
+                    self.borrow_mut::<ClassHostObject>(class)
+                        .add_protocols_from_bin(data.protocols, mem);
+                }
+            }
         }
     }
 
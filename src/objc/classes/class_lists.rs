@@ -15,6 +15,7 @@ use crate::frameworks::{
 pub const CLASS_LISTS: &[super::ClassExports] = &[
     crate::app_picker::CLASSES, // Not a framework! Special internal classes.
     core_animation::ca_animation::CLASSES,
+    core_animation::ca_display_link::CLASSES,
     core_animation::ca_eagl_layer::CLASSES,
     core_animation::ca_layer::CLASSES,
     core_animation::ca_media_timing_function::CLASSES,
@@ -30,6 +31,8 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     foundation::ns_array::CLASSES,
     foundation::ns_autorelease_pool::CLASSES,
     foundation::ns_bundle::CLASSES,
+    foundation::ns_cache::CLASSES,
+    foundation::ns_calendar::CLASSES,
     foundation::ns_character_set::CLASSES,
     foundation::ns_coder::CLASSES,
     foundation::ns_data::CLASSES,
@@ -40,12 +43,18 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     foundation::ns_error::CLASSES,
     foundation::ns_file_handle::CLASSES,
     foundation::ns_file_manager::CLASSES,
+    foundation::ns_index_path::CLASSES,
+    foundation::ns_index_set::CLASSES,
+    foundation::ns_invocation::CLASSES,
+    foundation::ns_json_serialization::CLASSES,
     foundation::ns_keyed_unarchiver::CLASSES,
     foundation::ns_locale::CLASSES,
+    foundation::ns_method_signature::CLASSES,
     foundation::ns_lock::CLASSES,
     foundation::ns_notification::CLASSES,
     foundation::ns_notification_center::CLASSES,
     foundation::ns_null::CLASSES,
+    foundation::ns_number_formatter::CLASSES,
     foundation::ns_object::CLASSES,
     foundation::ns_process_info::CLASSES,
     foundation::ns_property_list_serialization::CLASSES,
@@ -80,6 +89,7 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     uikit::ui_image::CLASSES,
     uikit::ui_image_picker_controller::CLASSES,
     uikit::ui_nib::CLASSES,
+    uikit::ui_pasteboard::CLASSES,
     uikit::ui_responder::CLASSES,
     uikit::ui_screen::CLASSES,
     uikit::ui_touch::CLASSES,
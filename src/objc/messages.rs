@@ -12,11 +12,66 @@
 //! - Peter Steinberger's [Calling Super at Runtime in Swift](https://steipete.com/posts/calling-super-at-runtime/) explains `objc_msgSendSuper2`
 
 use super::{id, nil, Class, ObjC, IMP, SEL};
-use crate::abi::{CallFromHost, GuestRet};
-use crate::mem::{ConstPtr, MutVoidPtr, SafeRead};
+use crate::abi::{extend_stack_for_args, CallFromHost, GuestArg, GuestRet};
+use crate::cpu::Cpu;
+use crate::frameworks::foundation::ns_invocation::forward_message;
+use crate::mem::{ConstPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr, SafeRead};
 use crate::Environment;
 use std::any::TypeId;
 
+/// Formats the diagnostic Cocoa uses when a message is sent to an object that
+/// was already deallocated (see [ObjC::enable_zombies]), e.g.
+/// `*** -[NSObject release]: message sent to deallocated instance 0x1234`.
+fn zombie_message(class_name: &str, selector_name: &str, receiver: id) -> String {
+    format!(
+        "*** -[{} {}]: message sent to deallocated instance {:#x}",
+        class_name,
+        selector_name,
+        receiver.to_bits(),
+    )
+}
+
+/// Formats the diagnostic Cocoa uses when an object doesn't respond to a
+/// message, e.g. `-[NSObject foo:]: unrecognized selector sent to instance
+/// 0x1234`.
+pub fn unrecognized_selector_message(
+    class_name: &str,
+    is_metaclass: bool,
+    selector_name: &str,
+    receiver: id,
+) -> String {
+    format!(
+        "{}[{} {}]: unrecognized selector sent to {} {:#x}",
+        if is_metaclass { '+' } else { '-' },
+        class_name,
+        selector_name,
+        if is_metaclass { "class" } else { "instance" },
+        receiver.to_bits(),
+    )
+}
+
+/// Formats the log line for a message sent to an object of a traced class
+/// (see [ObjC::trace_class]), for example:
+/// `-[UIView frob:] sent to 0x1234 (r2: 0x5, r3: 0x0)`.
+/// Registers r2 and r3 hold the first two arguments, if there are any.
+fn trace_message(
+    class_name: &str,
+    is_metaclass: bool,
+    selector_name: &str,
+    receiver: id,
+    regs: [u32; 2],
+) -> String {
+    format!(
+        "{}[{} {}] sent to {:#x} (r2: {:#x}, r3: {:#x})",
+        if is_metaclass { '+' } else { '-' },
+        class_name,
+        selector_name,
+        receiver.to_bits(),
+        regs[0],
+        regs[1],
+    )
+}
+
 /// The core implementation of `objc_msgSend`, the main function of Objective-C.
 ///
 /// Note that while only two parameters (usually receiver and selector) are
@@ -40,7 +95,39 @@ fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2
         return;
     }
 
-    let orig_class = super2.unwrap_or_else(|| ObjC::read_isa(receiver, &env.mem));
+    if let Some(class) = env.objc.zombie_class(receiver) {
+        let message = zombie_message(
+            env.objc.get_class_name(class),
+            selector.as_str(&env.mem),
+            receiver,
+        );
+        echo!("{}", message);
+        panic!("{}", message);
+    }
+
+    let receiver_class = ObjC::read_isa(receiver, &env.mem);
+    if env.objc.is_traced_class(receiver_class) {
+        let is_metaclass = env
+            .objc
+            .get_host_object(receiver_class)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<super::ClassHostObject>()
+            .is_some_and(|class| class.is_metaclass);
+        let regs = env.cpu.regs();
+        log!(
+            "{}",
+            trace_message(
+                env.objc.get_class_name(receiver_class),
+                is_metaclass,
+                selector.as_str(&env.mem),
+                receiver,
+                [regs[2], regs[3]],
+            )
+        );
+    }
+
+    let orig_class = super2.unwrap_or(receiver_class);
     assert!(orig_class != nil);
 
     // Traverse the chain of superclasses to find the method implementation.
@@ -56,20 +143,37 @@ fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2
                 is_metaclass,
                 ..
             } = class_host_object.as_any().downcast_ref().unwrap();
+            let message = unrecognized_selector_message(
+                name,
+                is_metaclass,
+                selector.as_str(&env.mem),
+                receiver,
+            );
+
+            // The object might want to forward the message to another object.
+            if super2.is_none() && forward_message(env, receiver, selector) {
+                return;
+            }
+
+            // Like in Cocoa, give the object a chance to handle this with
+            // -doesNotRecognizeSelector:. NSObject's implementation will
+            // terminate the app, but app-defined ones may have useful logging.
+            if super2.is_none() {
+                if let Some(dnrs) = env.objc.lookup_selector("doesNotRecognizeSelector:") {
+                    if selector != dnrs && env.objc.class_has_method(orig_class, dnrs) {
+                        () = msg_send(env, (receiver, dnrs, selector));
+                    }
+                }
+            }
 
             panic!(
-                "{} {:?} ({}class \"{}\", {:?}){} does not respond to selector \"{}\"!",
-                if is_metaclass { "Class" } else { "Object" },
-                receiver,
-                if is_metaclass { "meta" } else { "" },
-                name,
-                orig_class,
+                "{}{}",
+                message,
                 if super2.is_some() {
-                    "'s superclass"
+                    " (sent to superclass)"
                 } else {
                     ""
                 },
-                selector.as_str(&env.mem),
             );
         }
 
@@ -118,6 +222,12 @@ Type mismatch when sending message {} to {:?}!
                     // interfere with pass-through of stack arguments.
                     IMP::Guest(guest_imp) => guest_imp.call_without_pushing_stack_frame(env),
                 }
+                // Objects allocated by this method are attributed to the guest
+                // code that sent the message.
+                if env.objc.has_untraced_objects() {
+                    let backtrace = env.guest_backtrace();
+                    env.objc.set_allocation_backtrace(backtrace);
+                }
                 return;
             } else {
                 class = superclass;
@@ -255,6 +365,100 @@ where
     }
 }
 
+/// Sends a message whose arguments are only known at runtime, for
+/// `NSInvocation`. `frame` contains the receiver, the selector and the other
+/// arguments, laid out as they would be in registers and on the stack.
+///
+/// If `stret_size` is given, the method returns a struct of that size via a
+/// pointer and its bytes are returned. Otherwise the return value is the
+/// contents of r0 and r1.
+pub fn msg_send_with_frame(
+    env: &mut Environment,
+    frame: &[u32],
+    stret_size: Option<GuestUSize>,
+) -> Vec<u8> {
+    let receiver = id::from_regs(&frame[0..1]);
+    let selector = SEL::from_regs(&frame[1..2]);
+
+    let regs = env.cpu.regs_mut();
+    let old_sp = regs[Cpu::SP];
+    let retval_ptr: Option<MutVoidPtr> = stret_size.map(|size| {
+        regs[Cpu::SP] -= size;
+        Ptr::from_bits(regs[Cpu::SP])
+    });
+    let words: Vec<u32> = retval_ptr
+        .map(|ptr| ptr.to_bits())
+        .into_iter()
+        .chain(frame.iter().copied())
+        .collect();
+    extend_stack_for_args(words.len(), regs);
+    let stack_ptr: MutPtr<u32> = Ptr::from_bits(regs[Cpu::SP]);
+    for (i, &word) in words.iter().enumerate() {
+        if i < 4 {
+            regs[i] = word;
+        } else {
+            env.mem.write(stack_ptr + (i - 4).try_into().unwrap(), word);
+        }
+    }
+
+    env.objc.message_type_info = None;
+    objc_msgSend_inner(env, receiver, selector, /* super2: */ None);
+
+    let result = if let Some(retval_ptr) = retval_ptr {
+        env.mem
+            .bytes_at(retval_ptr.cast(), stret_size.unwrap())
+            .to_vec()
+    } else {
+        let regs = env.cpu.regs();
+        [regs[0], regs[1]]
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    };
+    env.cpu.regs_mut()[Cpu::SP] = old_sp;
+    result
+}
+
+/// Trait for the argument tuples accepted by [Environment::call_selector]:
+/// `()`, `(a,)`, `(a, b)` and so on, up to five arguments. Each argument can be
+/// of any type that implements [GuestArg], and the return type can be any type
+/// that implements [GuestRet]. In practice this means:
+///
+/// - integers up to 64 bits wide, [bool], [f32] and [f64],
+/// - pointers, including [id] and [Class],
+/// - [SEL],
+/// - structs with a [GuestArg] implementation, e.g. `CGRect`.
+///
+/// Variadic methods are not supported.
+pub trait MsgSendArgs<R> {
+    fn send(self, env: &mut Environment, receiver: id, selector: SEL) -> R;
+}
+
+macro_rules! impl_MsgSendArgs {
+    ( $($p:ident: $P:ident),* ) => {
+        impl<R, $($P,)*> MsgSendArgs<R> for ($($P,)*)
+        where
+            fn(&mut Environment, id, SEL): CallFromHost<R, (id, SEL, $($P,)*)>,
+            fn(&mut Environment, MutVoidPtr, id, SEL): CallFromHost<R, (id, SEL, $($P,)*)>,
+            (R, (id, SEL, $($P,)*)): MsgSendSignature,
+            R: GuestRet,
+            $($P: GuestArg,)*
+        {
+            fn send(self, env: &mut Environment, receiver: id, selector: SEL) -> R {
+                let ($($p,)*) = self;
+                msg_send(env, (receiver, selector, $($p,)*))
+            }
+        }
+    }
+}
+
+impl_MsgSendArgs!();
+impl_MsgSendArgs!(p1: P1);
+impl_MsgSendArgs!(p1: P1, p2: P2);
+impl_MsgSendArgs!(p1: P1, p2: P2, p3: P3);
+impl_MsgSendArgs!(p1: P1, p2: P2, p3: P3, p4: P4);
+impl_MsgSendArgs!(p1: P1, p2: P2, p3: P3, p4: P4, p5: P5);
+
 /// Counterpart of [MsgSendSignature] for [msg_send_super2].
 pub trait MsgSendSuperSignature: 'static {
     /// Signature with the [objc_super] pointer replaced by [id].
@@ -428,3 +632,41 @@ pub fn autorelease(env: &mut Environment, object: id) -> id {
     }
     msg![env; object autorelease]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{trace_message, unrecognized_selector_message, zombie_message};
+    use crate::mem::Ptr;
+
+    #[test]
+    fn test_zombie_message() {
+        assert_eq!(
+            zombie_message("NSObject", "release", Ptr::from_bits(0x1234)),
+            "*** -[NSObject release]: message sent to deallocated instance 0x1234"
+        );
+    }
+
+    #[test]
+    fn test_unrecognized_selector_message() {
+        assert_eq!(
+            unrecognized_selector_message("NSObject", false, "foo:bar:", Ptr::from_bits(0x1234)),
+            "-[NSObject foo:bar:]: unrecognized selector sent to instance 0x1234"
+        );
+        assert_eq!(
+            unrecognized_selector_message("UIView", true, "frob", Ptr::from_bits(0xabc0)),
+            "+[UIView frob]: unrecognized selector sent to class 0xabc0"
+        );
+    }
+
+    #[test]
+    fn test_trace_message() {
+        assert_eq!(
+            trace_message("UIView", false, "frob:", Ptr::from_bits(0x1234), [5, 0]),
+            "-[UIView frob:] sent to 0x1234 (r2: 0x5, r3: 0x0)"
+        );
+        assert_eq!(
+            trace_message("UIView", true, "new", Ptr::from_bits(0xabc0), [0, 0]),
+            "+[UIView new] sent to 0xabc0 (r2: 0x0, r3: 0x0)"
+        );
+    }
+}
@@ -141,6 +141,23 @@ impl ClassHostObject {
     }
 }
 
+/// Standard Objective-C runtime function for adding a method to a class.
+/// Returns `false` if the class already has its own method with that name.
+pub(super) fn class_addMethod(
+    env: &mut Environment,
+    class: Class,
+    name: SEL,
+    imp: GuestIMP,
+    _types: ConstPtr<u8>, // TODO: support type strings
+) -> bool {
+    let host_object = env.objc.borrow_mut::<ClassHostObject>(class);
+    if host_object.methods.contains_key(&name) {
+        return false;
+    }
+    host_object.methods.insert(name, IMP::Guest(imp));
+    true
+}
+
 impl ObjC {
     /// Checks if the provided class has a method in its class chain (that is
     /// to say, objects of the given class respond to a selector).
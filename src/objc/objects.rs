@@ -167,6 +167,10 @@ impl super::ObjC {
         let ptr: MutPtr<objc_object> = mem.alloc(instance_size).cast();
         mem.write(ptr, guest_object);
         assert!(!self.objects.contains_key(&ptr));
+        // Objects without a reference count live forever, so they can't leak.
+        if let (Some(tracker), Some(_)) = (&mut self.tracker, refcount) {
+            tracker.track(ptr, isa);
+        }
         self.objects.insert(
             ptr,
             HostObjectEntry {
@@ -366,6 +370,23 @@ impl super::ObjC {
 
         std::mem::drop(host_object);
 
+        if let Some(ref mut tracker) = self.tracker {
+            tracker.untrack(object);
+        }
+
+        if let Some(ref mut zombies) = self.zombies {
+            let class = Self::read_isa(object, mem);
+            log_dbg!("{:?} (class {:?}) is now a zombie", object, class);
+            zombies.insert(object, class);
+            return;
+        }
+
         mem.free(object.cast());
     }
+
+    /// If zombies are enabled (see [ObjC::enable_zombies]) and this object
+    /// has been deallocated, returns the class it had.
+    pub fn zombie_class(&self, object: id) -> Option<Class> {
+        self.zombies.as_ref()?.get(&object).copied()
+    }
 }
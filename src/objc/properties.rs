@@ -3,21 +3,18 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! Handling of Objective-C properties.
-//!
-//! Note that these are not the same as instance variables (ivars), though
-//! they're closely related, so maybe this file will end up being used for those
-//! too.
+//! Handling of Objective-C properties and instance variables (ivars).
 //!
 //! Resources:
 //! - `objc_setProperty` and friends are not documented, so [reading the source code](https://opensource.apple.com/source/objc4/objc4-551.1/runtime/Accessors.subproj/objc-accessors.mm.auto.html) is useful.
+//! - Apple's [Declared Properties](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ObjCRuntimeGuide/Articles/ocrtPropertyIntrospection.html) documentation describes the attribute strings.
 //!
 //! See also: [crate::frameworks::foundation::ns_object].
 
 use super::{id, msg, nil, release, retain, Class, ClassHostObject, ObjC, SEL};
 use crate::mem::{
     guest_size_of, ConstPtr, ConstVoidPtr, GuestISize, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr,
-    SafeRead,
+    SafeRead, SafeWrite,
 };
 use crate::Environment;
 
@@ -45,7 +42,81 @@ struct ivar_t {
 }
 unsafe impl SafeRead for ivar_t {}
 
+/// The layout of a property list in an app binary.
+///
+/// The name, field names and field layout are based on what Ghidra outputs.
+#[repr(C, packed)]
+pub(super) struct property_list_t {
+    entsize: GuestUSize,
+    count: GuestUSize,
+    // entries follow the struct
+}
+unsafe impl SafeRead for property_list_t {}
+
+/// The layout of a declared property in an app binary. Pointers to this are
+/// `objc_property_t` values.
+///
+/// The name, field names and field layout are based on what Ghidra outputs.
+#[repr(C, packed)]
+pub(super) struct property_t {
+    name: ConstPtr<u8>,
+    attributes: ConstPtr<u8>,
+}
+unsafe impl SafeRead for property_t {}
+unsafe impl SafeWrite for property_t {}
+
+/// `objc_property_attribute_t`, used by [class_addProperty].
+#[repr(C, packed)]
+pub(super) struct objc_property_attribute_t {
+    name: ConstPtr<u8>,
+    value: ConstPtr<u8>,
+}
+unsafe impl SafeRead for objc_property_attribute_t {}
+
+/// Splits a property attribute string like `T@"NSString",&,N,V_name` into
+/// its attributes, each of which is a one-character code and a (possibly
+/// empty) value, e.g. `[("T", "@\"NSString\""), ("&", ""), ("N", ""),
+/// ("V", "_name")]`.
+fn parse_property_attributes(attributes: &str) -> Vec<(&str, &str)> {
+    attributes
+        .split(',')
+        .filter(|attribute| !attribute.is_empty())
+        .map(|attribute| {
+            let code_len = attribute.chars().next().unwrap().len_utf8();
+            attribute.split_at(code_len)
+        })
+        .collect()
+}
+
+/// The inverse of [parse_property_attributes].
+fn format_property_attributes(attributes: &[(&str, &str)]) -> String {
+    attributes
+        .iter()
+        .map(|&(code, value)| format!("{}{}", code, value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
 impl ClassHostObject {
+    pub(super) fn add_properties_from_bin(
+        &mut self,
+        property_list_ptr: ConstPtr<property_list_t>,
+        mem: &Mem,
+    ) {
+        let property_list_t { entsize, count } = mem.read(property_list_ptr);
+        assert!(entsize >= guest_size_of::<property_t>());
+
+        let properties_base_ptr: ConstPtr<property_t> = (property_list_ptr + 1).cast();
+
+        for i in 0..count {
+            let property_ptr: ConstPtr<property_t> =
+                Ptr::from_bits(properties_base_ptr.to_bits() + i * entsize);
+            let property_t { name, .. } = mem.read(property_ptr);
+            let name_string = mem.cstr_at_utf8(name).unwrap().to_string();
+            self.properties.push((name_string, property_ptr));
+        }
+    }
+
     pub(super) fn add_ivars_from_bin(&mut self, ivar_list_ptr: ConstPtr<ivar_list_t>, mem: &Mem) {
         let ivar_list_t { entsize, count } = mem.read(ivar_list_ptr);
         assert!(entsize >= guest_size_of::<ivar_t>());
@@ -72,15 +143,13 @@ impl ClassHostObject {
 }
 
 impl ObjC {
-    /// Checks if the object's class has an ivar in its class chain with the
-    /// provided name and returns the pointer to the object's ivar, if any,
-    /// or None if the object's class doesn't have an ivar with that name.
-    pub fn object_lookup_ivar(
-        &self,
-        mem: &Mem,
-        obj: id,
-        name: &String,
-    ) -> Option<MutPtr<GuestUSize>> {
+    /// Resolves the ivar with the provided name by walking the object's class
+    /// chain (so inherited ivars are found too) and returns a pointer to its
+    /// storage within the object, or [None] if there's no such ivar.
+    ///
+    /// The pointer is untyped: the caller is responsible for knowing the type
+    /// of the ivar and casting the pointer appropriately.
+    pub fn ivar_ptr(&self, mem: &Mem, obj: id, name: &str) -> Option<MutPtr<u8>> {
         let mut class = ObjC::read_isa(obj, mem);
         loop {
             let &ClassHostObject {
@@ -90,8 +159,7 @@ impl ObjC {
             } = self.borrow(class);
             if let Some(ivar_offset_ptr) = ivars.get(name) {
                 let ivar_offset = mem.read(*ivar_offset_ptr);
-                let ivar_ptr = MutVoidPtr::from_bits(obj.to_bits() + ivar_offset);
-                return Some(ivar_ptr.cast());
+                return Some(Ptr::from_bits(obj.to_bits() + ivar_offset));
             } else if superclass == nil {
                 return None;
             } else {
@@ -100,6 +168,18 @@ impl ObjC {
         }
     }
 
+    /// Checks if the object's class has an ivar in its class chain with the
+    /// provided name and returns the pointer to the object's ivar, if any,
+    /// or None if the object's class doesn't have an ivar with that name.
+    pub fn object_lookup_ivar(
+        &self,
+        mem: &Mem,
+        obj: id,
+        name: &String,
+    ) -> Option<MutPtr<GuestUSize>> {
+        self.ivar_ptr(mem, obj, name).map(|ptr| ptr.cast())
+    }
+
     pub fn debug_all_class_ivars_as_strings(&self, class: Class) -> Vec<String> {
         let mut class = class;
         let mut ivars_strings = Vec::new();
@@ -208,3 +288,235 @@ pub(super) fn objc_copyStruct(
     // TODO: implement atomic support
     env.mem.memmove(dest, src, size);
 }
+
+/// Standard Objective-C runtime function for adding an ivar to a class created
+/// with [super::objc_allocateClassPair], before it is registered.
+pub(super) fn class_addIvar(
+    env: &mut Environment,
+    class: Class,
+    name: ConstPtr<u8>,
+    size: GuestUSize,
+    alignment: u8,        // log2 of the alignment
+    _types: ConstPtr<u8>, // TODO: support type strings
+) -> bool {
+    let name = env.mem.cstr_at_utf8(name).unwrap().to_string();
+    let &ClassHostObject {
+        name: ref class_name,
+        is_metaclass,
+        ref ivars,
+        instance_size,
+        ..
+    } = env.objc.borrow(class);
+    // Ivars can't be added to metaclasses or to already registered classes,
+    // since instances with the old layout might exist.
+    if is_metaclass || env.objc.classes.get(class_name) == Some(&class) || ivars.contains_key(&name)
+    {
+        return false;
+    }
+
+    let alignment = 1 << alignment;
+    let offset = instance_size.next_multiple_of(alignment);
+    let offset_ptr = env.mem.alloc(guest_size_of::<GuestUSize>()).cast();
+    env.mem.write(offset_ptr, offset);
+
+    let host_object = env.objc.borrow_mut::<ClassHostObject>(class);
+    host_object.ivars.insert(name, offset_ptr.cast_const());
+    host_object.instance_size = offset + size;
+    true
+}
+
+/// Standard Objective-C runtime function for reading an ivar by name.
+///
+/// The returned `Ivar` is an opaque handle: it's only meaningful as a null or
+/// non-null value.
+pub(super) fn object_getInstanceVariable(
+    env: &mut Environment,
+    obj: id,
+    name: ConstPtr<u8>,
+    out_value: MutPtr<MutVoidPtr>,
+) -> ConstVoidPtr {
+    if obj == nil {
+        return Ptr::null();
+    }
+    let name = env.mem.cstr_at_utf8(name).unwrap();
+    let Some(ivar_ptr) = env.objc.ivar_ptr(&env.mem, obj, name) else {
+        return Ptr::null();
+    };
+    if !out_value.is_null() {
+        let value = env.mem.read(ivar_ptr.cast::<MutVoidPtr>().cast_const());
+        env.mem.write(out_value, value);
+    }
+    ivar_ptr.cast_void().cast_const()
+}
+
+/// Standard Objective-C runtime function for looking up a declared property by
+/// name. Superclasses are searched too.
+pub(super) fn class_getProperty(
+    env: &mut Environment,
+    class: Class,
+    name: ConstPtr<u8>,
+) -> ConstPtr<property_t> {
+    if class == nil || name.is_null() {
+        return Ptr::null();
+    }
+    let name = env.mem.cstr_at_utf8(name).unwrap();
+    let mut class = class;
+    loop {
+        let &ClassHostObject {
+            superclass,
+            ref properties,
+            ..
+        } = env.objc.borrow(class);
+        if let Some(&(_, property)) = properties.iter().find(|(n, _)| n == name) {
+            return property;
+        } else if superclass == nil {
+            return Ptr::null();
+        } else {
+            class = superclass;
+        }
+    }
+}
+
+/// Standard Objective-C runtime function for listing the properties declared
+/// by a class (but not its superclasses). The result is a null-terminated
+/// array, allocated with `malloc()`, or null if there are no properties.
+pub(super) fn class_copyPropertyList(
+    env: &mut Environment,
+    class: Class,
+    out_count: MutPtr<u32>,
+) -> MutPtr<ConstPtr<property_t>> {
+    let properties: Vec<ConstPtr<property_t>> = if class == nil {
+        Vec::new()
+    } else {
+        let host_object = env.objc.borrow::<ClassHostObject>(class);
+        host_object.properties.iter().map(|&(_, p)| p).collect()
+    };
+    if !out_count.is_null() {
+        env.mem.write(out_count, properties.len() as u32);
+    }
+    if properties.is_empty() {
+        return Ptr::null();
+    }
+    let size = guest_size_of::<ConstPtr<property_t>>() * (properties.len() as GuestUSize + 1);
+    let list: MutPtr<ConstPtr<property_t>> = env.mem.alloc(size).cast();
+    for (i, &property) in properties.iter().enumerate() {
+        env.mem.write(list + i as GuestUSize, property);
+    }
+    env.mem
+        .write(list + properties.len() as GuestUSize, Ptr::null());
+    list
+}
+
+/// Standard Objective-C runtime function for adding a declared property to a
+/// class at runtime. The attribute string is built from the attribute list.
+pub(super) fn class_addProperty(
+    env: &mut Environment,
+    class: Class,
+    name: ConstPtr<u8>,
+    attributes: ConstPtr<objc_property_attribute_t>,
+    attribute_count: u32,
+) -> bool {
+    let name_string = env.mem.cstr_at_utf8(name).unwrap().to_string();
+    if env
+        .objc
+        .borrow::<ClassHostObject>(class)
+        .properties
+        .iter()
+        .any(|(n, _)| *n == name_string)
+    {
+        return false;
+    }
+
+    let mut attribute_strings = Vec::new();
+    for i in 0..attribute_count {
+        let objc_property_attribute_t { name, value } = env.mem.read(attributes + i);
+        let name = env.mem.cstr_at_utf8(name).unwrap().to_string();
+        let value = if value.is_null() {
+            String::new()
+        } else {
+            env.mem.cstr_at_utf8(value).unwrap().to_string()
+        };
+        attribute_strings.push((name, value));
+    }
+    let attribute_strs: Vec<(&str, &str)> = attribute_strings
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    let attributes_string = format_property_attributes(&attribute_strs);
+
+    let property = property_t {
+        name: env
+            .mem
+            .alloc_and_write_cstr(name_string.as_bytes())
+            .cast_const(),
+        attributes: env
+            .mem
+            .alloc_and_write_cstr(attributes_string.as_bytes())
+            .cast_const(),
+    };
+    let property_ptr = env.mem.alloc_and_write(property).cast_const();
+    env.objc
+        .borrow_mut::<ClassHostObject>(class)
+        .properties
+        .push((name_string, property_ptr));
+    true
+}
+
+pub(super) fn property_getName(
+    env: &mut Environment,
+    property: ConstPtr<property_t>,
+) -> ConstPtr<u8> {
+    env.mem.read(property).name
+}
+
+pub(super) fn property_getAttributes(
+    env: &mut Environment,
+    property: ConstPtr<property_t>,
+) -> ConstPtr<u8> {
+    env.mem.read(property).attributes
+}
+
+/// Standard Objective-C runtime function for getting the value of a single
+/// attribute (e.g. `"_name"` for `"V"`), allocated with `malloc()`. Returns
+/// null if the property doesn't have the attribute.
+pub(super) fn property_copyAttributeValue(
+    env: &mut Environment,
+    property: ConstPtr<property_t>,
+    attribute_name: ConstPtr<u8>,
+) -> MutPtr<u8> {
+    let attributes = env.mem.read(property).attributes;
+    let attributes = env.mem.cstr_at_utf8(attributes).unwrap();
+    let attribute_name = env.mem.cstr_at_utf8(attribute_name).unwrap();
+    let value = parse_property_attributes(attributes)
+        .into_iter()
+        .find(|&(code, _)| code == attribute_name)
+        .map(|(_, value)| value.to_string());
+    match value {
+        Some(value) => env.mem.alloc_and_write_cstr(value.as_bytes()),
+        None => Ptr::null(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_property_attributes() {
+        let attributes = r#"T@"NSString",&,N,V_name"#;
+        let parsed = parse_property_attributes(attributes);
+        assert_eq!(
+            parsed,
+            [
+                ("T", r#"@"NSString""#),
+                ("&", ""),
+                ("N", ""),
+                ("V", "_name")
+            ]
+        );
+        assert_eq!(format_property_attributes(&parsed), attributes);
+
+        assert_eq!(parse_property_attributes("Ti,R"), [("T", "i"), ("R", "")]);
+        assert!(parse_property_attributes("").is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Handling of Objective-C protocols.
+//!
+//! Conformance is tracked by protocol name: each class knows the names of the
+//! protocols it adopts, including the ones those inherit from. Like in Apple's
+//! runtime, two `Protocol *` values with the same name are interchangeable.
+//!
+//! Host classes declare their protocols in [super::objc_classes]. There is no
+//! other metadata for protocols only the host knows about, so they can't
+//! inherit from other protocols.
+
+use super::{id, nil, Class, ClassHostObject, ObjC, CLASS_LISTS};
+use crate::mach_o::MachO;
+use crate::mem::{
+    guest_size_of, ConstPtr, ConstVoidPtr, GuestUSize, Mem, Ptr, SafeRead, SafeWrite,
+};
+use crate::Environment;
+
+/// The layout of a protocol in an app binary. Pointers to this are
+/// `Protocol *` values.
+///
+/// The name, field names and field layout are based on what Ghidra outputs.
+#[repr(C, packed)]
+#[allow(dead_code)]
+pub(super) struct protocol_t {
+    isa: id,
+    name: ConstPtr<u8>,
+    protocols: ConstPtr<protocol_list_t>,
+    _instance_methods: ConstVoidPtr,
+    _class_methods: ConstVoidPtr,
+    _optional_instance_methods: ConstVoidPtr,
+    _optional_class_methods: ConstVoidPtr,
+    _instance_properties: ConstVoidPtr,
+    size: GuestUSize,
+    _flags: u32,
+}
+unsafe impl SafeRead for protocol_t {}
+unsafe impl SafeWrite for protocol_t {}
+
+/// The layout of a protocol list in an app binary.
+///
+/// The name, field names and field layout are based on what Ghidra outputs.
+#[repr(C, packed)]
+pub(super) struct protocol_list_t {
+    count: GuestUSize,
+    // pointers to protocol_t follow the struct
+}
+unsafe impl SafeRead for protocol_list_t {}
+
+/// Get the names of the protocols in a list and of the protocols they inherit
+/// from, recursively, skipping any already in `names`.
+fn add_protocol_names(list: ConstPtr<protocol_list_t>, mem: &Mem, names: &mut Vec<String>) {
+    let protocol_list_t { count } = mem.read(list);
+    let protocols: ConstPtr<ConstPtr<protocol_t>> = (list + 1).cast();
+    for i in 0..count {
+        let protocol_t {
+            name, protocols, ..
+        } = mem.read(mem.read(protocols + i));
+        let name = mem.cstr_at_utf8(name).unwrap();
+        if names.iter().any(|n| n == name) {
+            continue;
+        }
+        names.push(name.to_string());
+        if !protocols.is_null() {
+            add_protocol_names(protocols, mem, names);
+        }
+    }
+}
+
+impl ClassHostObject {
+    pub(super) fn add_protocols_from_bin(
+        &mut self,
+        protocol_list_ptr: ConstPtr<protocol_list_t>,
+        mem: &Mem,
+    ) {
+        add_protocol_names(protocol_list_ptr, mem, &mut self.protocols);
+    }
+}
+
+impl ObjC {
+    /// For use by [crate::dyld]: register all the protocols from the
+    /// application binary, so [objc_getProtocol] can find them.
+    pub fn register_bin_protocols(&mut self, bin: &MachO, mem: &Mem) {
+        let Some(list) = bin.get_section("__objc_protolist") else {
+            return;
+        };
+
+        assert!(list.size % 4 == 0);
+        let base: ConstPtr<ConstPtr<protocol_t>> = Ptr::from_bits(list.addr);
+        for i in 0..(list.size / 4) {
+            let protocol = mem.read(base + i);
+            let name = Self::protocol_name(protocol, mem).to_string();
+            // The first definition wins, like in Apple's runtime.
+            self.protocols.entry(name).or_insert(protocol);
+        }
+    }
+
+    fn protocol_name(protocol: ConstPtr<protocol_t>, mem: &Mem) -> &str {
+        let protocol_t { name, .. } = mem.read(protocol);
+        mem.cstr_at_utf8(name).unwrap()
+    }
+
+    /// Checks if a class or one of its superclasses adopts a protocol.
+    pub fn class_conforms_to_protocol_named(&self, class: Class, name: &str) -> bool {
+        let mut class = class;
+        loop {
+            let &ClassHostObject {
+                superclass,
+                ref protocols,
+                ..
+            } = self.borrow(class);
+            if protocols.iter().any(|n| n == name) {
+                return true;
+            } else if superclass == nil {
+                return false;
+            } else {
+                class = superclass;
+            }
+        }
+    }
+
+    /// Same as [Self::class_conforms_to_protocol_named], but using a
+    /// `Protocol *`.
+    pub fn class_conforms_to_protocol(
+        &self,
+        class: Class,
+        protocol: ConstVoidPtr,
+        mem: &Mem,
+    ) -> bool {
+        if protocol.is_null() {
+            return false;
+        }
+        let name = Self::protocol_name(protocol.cast(), mem);
+        self.class_conforms_to_protocol_named(class, name)
+    }
+}
+
+/// Standard Objective-C runtime function for looking up a protocol by name.
+pub(super) fn objc_getProtocol(env: &mut Environment, name: ConstPtr<u8>) -> ConstPtr<protocol_t> {
+    let name = env.mem.cstr_at_utf8(name).unwrap().to_string();
+    if let Some(&protocol) = env.objc.protocols.get(&name) {
+        return protocol;
+    }
+
+    // Protocols adopted by host classes don't exist in guest memory until
+    // they're asked for.
+    let is_host_protocol = CLASS_LISTS
+        .iter()
+        .flat_map(|&list| list)
+        .any(|(_, template)| template.protocols.contains(&name.as_str()));
+    if !is_host_protocol {
+        log_dbg!("objc_getProtocol({:?}) => NULL", name);
+        return Ptr::null();
+    }
+    let name_ptr = env.mem.alloc_and_write_cstr(name.as_bytes()).cast_const();
+    let protocol = env.mem.alloc_and_write(protocol_t {
+        isa: nil,
+        name: name_ptr,
+        protocols: Ptr::null(),
+        _instance_methods: Ptr::null(),
+        _class_methods: Ptr::null(),
+        _optional_instance_methods: Ptr::null(),
+        _optional_class_methods: Ptr::null(),
+        _instance_properties: Ptr::null(),
+        size: guest_size_of::<protocol_t>(),
+        _flags: 0,
+    });
+    let protocol = protocol.cast_const();
+    env.objc.protocols.insert(name, protocol);
+    protocol
+}
+
+/// Standard Objective-C runtime function for checking if a class (but not its
+/// superclasses) adopts a protocol.
+pub(super) fn class_conformsToProtocol(
+    env: &mut Environment,
+    class: Class,
+    protocol: ConstPtr<protocol_t>,
+) -> bool {
+    if class == nil || protocol.is_null() {
+        return false;
+    }
+    let name = ObjC::protocol_name(protocol, &env.mem);
+    let &ClassHostObject { ref protocols, .. } = env.objc.borrow(class);
+    protocols.iter().any(|n| n == name)
+}
+
+pub(super) fn protocol_getName(
+    env: &mut Environment,
+    protocol: ConstPtr<protocol_t>,
+) -> ConstPtr<u8> {
+    if protocol.is_null() {
+        return Ptr::null();
+    }
+    let protocol_t { name, .. } = env.mem.read(protocol);
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::MutPtr;
+
+    #[test]
+    fn test_add_protocol_names() {
+        let mut mem = Mem::new();
+        let write_protocol = |mem: &mut Mem, name: &str, list| {
+            let name = mem.alloc_and_write_cstr(name.as_bytes()).cast_const();
+            let protocol = mem.alloc_and_write(protocol_t {
+                isa: nil,
+                name,
+                protocols: list,
+                _instance_methods: Ptr::null(),
+                _class_methods: Ptr::null(),
+                _optional_instance_methods: Ptr::null(),
+                _optional_class_methods: Ptr::null(),
+                _instance_properties: Ptr::null(),
+                size: guest_size_of::<protocol_t>(),
+                _flags: 0,
+            });
+            protocol.cast_const()
+        };
+        let write_list = |mem: &mut Mem, protocols: &[ConstPtr<protocol_t>]| {
+            let count = protocols.len() as GuestUSize;
+            let list: MutPtr<GuestUSize> = mem.alloc(4 * (count + 1)).cast();
+            mem.write(list, count);
+            for (i, &protocol) in protocols.iter().enumerate() {
+                let entry = list + 1 + i as GuestUSize;
+                mem.write(entry.cast::<ConstPtr<protocol_t>>(), protocol);
+            }
+            list.cast::<protocol_list_t>().cast_const()
+        };
+
+        // @protocol Base @end
+        // @protocol Derived <Base> @end
+        // @interface Foo : NSObject <Derived, Base, Other> @end
+        let base = write_protocol(&mut mem, "Base", Ptr::null());
+        let derived_list = write_list(&mut mem, &[base]);
+        let derived = write_protocol(&mut mem, "Derived", derived_list);
+        let other = write_protocol(&mut mem, "Other", Ptr::null());
+        let list = write_list(&mut mem, &[derived, base, other]);
+
+        let mut names = Vec::new();
+        add_protocol_names(list, &mem, &mut names);
+        assert_eq!(names, ["Derived", "Base", "Other"]);
+    }
+}
@@ -0,0 +1,114 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Optional tracking of live objects, to help find leaks. See
+//! [super::ObjC::enable_object_tracking].
+
+use super::{id, Class};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Return addresses in the guest code that was running when an object was
+/// allocated, innermost first.
+pub type Backtrace = Vec<u32>;
+
+#[derive(Default)]
+pub struct ObjectTracker {
+    live: HashMap<id, (Class, Option<Rc<Backtrace>>)>,
+    /// Objects that were allocated but don't have a backtrace yet.
+    pending: Vec<id>,
+}
+
+impl ObjectTracker {
+    pub fn track(&mut self, object: id, class: Class) {
+        self.live.insert(object, (class, None));
+        self.pending.push(object);
+    }
+
+    pub fn untrack(&mut self, object: id) {
+        self.live.remove(&object);
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Set the backtrace for all objects allocated since the last call.
+    pub fn set_pending_backtraces(&mut self, backtrace: Backtrace) {
+        let backtrace = Rc::new(backtrace);
+        for object in self.pending.drain(..) {
+            if let Some(entry) = self.live.get_mut(&object) {
+                entry.1 = Some(Rc::clone(&backtrace));
+            }
+        }
+    }
+
+    /// Number of live objects of each class, most common classes first.
+    pub fn live_counts(&self) -> Vec<(Class, usize)> {
+        let mut counts = HashMap::<Class, usize>::new();
+        for &(class, _) in self.live.values() {
+            *counts.entry(class).or_default() += 1;
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by_key(|&(class, count)| (std::cmp::Reverse(count), class.to_bits()));
+        counts
+    }
+
+    /// The backtrace shared by the most live objects of a class, and how many
+    /// objects share it.
+    pub fn most_common_backtrace(&self, class: Class) -> Option<(&Backtrace, usize)> {
+        let mut counts = HashMap::<&Backtrace, usize>::new();
+        for (_, backtrace) in self.live.values().filter(|&&(c, _)| c == class) {
+            if let Some(backtrace) = backtrace {
+                *counts.entry(&**backtrace).or_default() += 1;
+            }
+        }
+        // Ties are broken by the backtrace so the result is deterministic.
+        counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::Ptr;
+
+    #[test]
+    fn test_live_counts() {
+        let class_a: Class = Ptr::from_bits(0x1000);
+        let class_b: Class = Ptr::from_bits(0x2000);
+        let mut tracker = ObjectTracker::default();
+
+        let objects: Vec<id> = (0..10).map(|i| Ptr::from_bits(0x10000 + i * 16)).collect();
+        for &object in &objects {
+            tracker.track(object, class_a);
+        }
+        tracker.set_pending_backtraces(vec![0x4000, 0x5000]);
+        tracker.track(Ptr::from_bits(0x20000), class_b);
+        tracker.set_pending_backtraces(vec![0x6000]);
+        assert!(!tracker.has_pending());
+
+        for &object in &objects[..4] {
+            tracker.untrack(object);
+        }
+        assert_eq!(tracker.live_counts(), vec![(class_a, 6), (class_b, 1)]);
+
+        tracker.track(Ptr::from_bits(0x30000), class_a);
+        assert!(tracker.has_pending());
+        tracker.set_pending_backtraces(vec![0x7000]);
+        assert_eq!(tracker.live_counts(), vec![(class_a, 7), (class_b, 1)]);
+        assert_eq!(
+            tracker.most_common_backtrace(class_a),
+            Some((&vec![0x4000, 0x5000], 6))
+        );
+
+        for &object in &objects[4..] {
+            tracker.untrack(object);
+        }
+        assert_eq!(tracker.live_counts(), vec![(class_a, 1), (class_b, 1)]);
+    }
+}
@@ -11,6 +11,7 @@ use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Read};
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::num::NonZeroU32;
+use std::path::PathBuf;
 
 pub const OPTIONS_HELP: &str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/OPTIONS_HELP.txt"));
@@ -35,6 +36,12 @@ pub struct Options {
     pub fullscreen: bool,
     pub initial_orientation: DeviceOrientation,
     pub scale_hack: NonZeroU32,
+    /// Size of the emulated device's screen in points, in portrait.
+    pub screen_size: (u32, u32),
+    /// Scale factor reported to the app by `UIScreen`.
+    pub screen_scale: f32,
+    /// Amount of RAM the device has, in bytes, as reported by `sysctl()`.
+    pub device_memory_size: u64,
     pub deadzone: f32,
     pub x_tilt_range: f32,
     pub y_tilt_range: f32,
@@ -45,11 +52,34 @@ pub struct Options {
     pub gles1_implementation: Option<GLESImplementation>,
     pub direct_memory_access: bool,
     pub gdb_listen_addrs: Option<Vec<SocketAddr>>,
+    pub terminate_faulting_threads: bool,
+    pub zombies: bool,
+    pub track_objects: bool,
+    pub heap_canaries: bool,
+    pub strict_alignment: bool,
+    /// Frames per second and CPU ticks per second, see
+    /// [crate::Environment::set_frame_pacing].
+    pub frame_pacing: Option<(f64, u64)>,
+    pub trace_classes: Vec<String>,
+    /// Whether to print the class hierarchy, and whether to include
+    /// metaclasses.
+    pub dump_class_hierarchy: Option<bool>,
+    pub dump_imports: bool,
+    pub eager_linking: bool,
+    /// Points to tap at when the app starts handling events, see
+    /// [crate::Environment::push_input_event].
+    pub injected_taps: Vec<(f32, f32)>,
+    pub record_inputs: Option<PathBuf>,
+    pub replay_inputs: Option<PathBuf>,
     pub preferred_languages: Option<Vec<String>>,
+    /// Language code and, optionally, country code.
+    pub locale: Option<(String, Option<String>)>,
     pub headless: bool,
     pub print_fps: bool,
     pub fps_limit: Option<f64>,
     pub force_composition: bool,
+    /// Key-value pairs passed to the app, see [crate::Environment::new].
+    pub launch_options: Vec<(String, String)>,
 }
 
 impl Default for Options {
@@ -58,6 +88,11 @@ impl Default for Options {
             fullscreen: false,
             initial_orientation: DeviceOrientation::Portrait,
             scale_hack: NonZeroU32::new(1).unwrap(),
+            // Original iPhone
+            screen_size: (320, 480),
+            screen_scale: 1.0,
+            // Original iPhone (128MiB, minus what the GPU reserves)
+            device_memory_size: 116 * 1024 * 1024,
             deadzone: 0.1,
             x_tilt_range: 60.0,
             y_tilt_range: 60.0,
@@ -68,11 +103,26 @@ impl Default for Options {
             gles1_implementation: None,
             direct_memory_access: true,
             gdb_listen_addrs: None,
+            terminate_faulting_threads: false,
+            zombies: false,
+            track_objects: false,
+            heap_canaries: false,
+            strict_alignment: false,
+            frame_pacing: None,
+            trace_classes: Vec::new(),
+            dump_class_hierarchy: None,
+            dump_imports: false,
+            eager_linking: false,
+            injected_taps: Vec::new(),
+            record_inputs: None,
+            replay_inputs: None,
             preferred_languages: None,
+            locale: None,
             headless: false,
             print_fps: false,
             fps_limit: Some(60.0), // Original iPhone is 60Hz and uses v-sync,
             force_composition: false,
+            launch_options: Vec::new(),
         }
     }
 }
@@ -102,6 +152,26 @@ impl Options {
             self.scale_hack = value
                 .parse()
                 .map_err(|_| "Invalid scale hack factor".to_string())?;
+        } else if let Some(value) = arg.strip_prefix("--screen-size=") {
+            let size = value.split_once('x').and_then(|(width, height)| {
+                let width: u32 = width.parse().ok()?;
+                let height: u32 = height.parse().ok()?;
+                (width > 0 && height > 0).then_some((width, height))
+            });
+            self.screen_size = size.ok_or_else(|| "Invalid screen size".to_string())?;
+        } else if let Some(value) = arg.strip_prefix("--screen-scale=") {
+            self.screen_scale = value
+                .parse()
+                .ok()
+                .filter(|&scale: &f32| scale.is_finite() && scale > 0.0)
+                .ok_or_else(|| "Invalid screen scale".to_string())?;
+        } else if let Some(value) = arg.strip_prefix("--device-memory=") {
+            let mebibytes: u32 = value
+                .parse()
+                .ok()
+                .filter(|&mebibytes| mebibytes > 0)
+                .ok_or_else(|| "Invalid device memory size".to_string())?;
+            self.device_memory_size = u64::from(mebibytes) * 1024 * 1024;
         } else if let Some(value) = arg.strip_prefix("--deadzone=") {
             self.deadzone = parse_degrees(value, "deadzone")?;
         } else if let Some(value) = arg.strip_prefix("--x-tilt-range=") {
@@ -171,8 +241,67 @@ impl Options {
                 .map_err(|e| format!("Could not resolve GDB server listen address: {}", e))?
                 .collect();
             self.gdb_listen_addrs = Some(addrs);
+        } else if arg == "--terminate-faulting-threads" {
+            self.terminate_faulting_threads = true;
+        } else if arg == "--zombies" {
+            self.zombies = true;
+        } else if arg == "--track-objects" {
+            self.track_objects = true;
+        } else if arg == "--heap-canaries" {
+            self.heap_canaries = true;
+        } else if arg == "--strict-alignment" {
+            self.strict_alignment = true;
+        } else if let Some(value) = arg.strip_prefix("--frame-pacing=") {
+            let (frame_rate, tick_rate) = value
+                .split_once(',')
+                .ok_or_else(|| "--frame-pacing= requires two values".to_string())?;
+            let frame_rate: f64 = frame_rate
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0 && v.is_finite())
+                .ok_or_else(|| "Invalid frame rate for --frame-pacing=".to_string())?;
+            let tick_rate: u64 = tick_rate
+                .parse()
+                .ok()
+                .filter(|&v| v > 0)
+                .ok_or_else(|| "Invalid tick rate for --frame-pacing=".to_string())?;
+            self.frame_pacing = Some((frame_rate, tick_rate));
+        } else if let Some(name) = arg.strip_prefix("--trace-class=") {
+            self.trace_classes.push(name.to_owned());
+        } else if arg == "--dump-class-hierarchy" {
+            self.dump_class_hierarchy = Some(false);
+        } else if arg == "--dump-class-hierarchy=metaclasses" {
+            self.dump_class_hierarchy = Some(true);
+        } else if arg == "--dump-imports" {
+            self.dump_imports = true;
+        } else if arg == "--eager-linking" {
+            self.eager_linking = true;
+        } else if let Some(value) = arg.strip_prefix("--inject-tap=") {
+            let (x, y) = value
+                .split_once(',')
+                .ok_or_else(|| "--inject-tap= requires two values".to_string())?;
+            let x: f32 = x
+                .parse()
+                .map_err(|_| "Invalid X co-ordinate for --inject-tap=".to_string())?;
+            let y: f32 = y
+                .parse()
+                .map_err(|_| "Invalid Y co-ordinate for --inject-tap=".to_string())?;
+            self.injected_taps.push((x, y));
+        } else if let Some(path) = arg.strip_prefix("--record-inputs=") {
+            self.record_inputs = Some(PathBuf::from(path));
+        } else if let Some(path) = arg.strip_prefix("--replay-inputs=") {
+            self.replay_inputs = Some(PathBuf::from(path));
         } else if let Some(value) = arg.strip_prefix("--preferred-languages=") {
             self.preferred_languages = Some(value.split(',').map(ToOwned::to_owned).collect());
+        } else if let Some(value) = arg.strip_prefix("--locale=") {
+            let (language, country) = match value.split_once(|c| c == '_' || c == '-') {
+                Some((language, country)) => (language, Some(country.to_owned())),
+                None => (value, None),
+            };
+            if language.is_empty() || country.as_deref() == Some("") {
+                return Err(format!("Invalid locale {:?}", value));
+            }
+            self.locale = Some((language.to_owned(), country));
         } else if arg == "--headless" {
             self.headless = true;
         } else if arg == "--print-fps" {
@@ -190,6 +319,12 @@ impl Options {
             }
         } else if arg == "--force-composition" {
             self.force_composition = true;
+        } else if let Some(value) = arg.strip_prefix("--launch-option=") {
+            let (key, value) = value
+                .split_once('=')
+                .filter(|(key, _)| !key.is_empty())
+                .ok_or_else(|| "--launch-option= requires a key and a value".to_string())?;
+            self.launch_options.push((key.to_owned(), value.to_owned()));
         } else {
             return Ok(false);
         };
@@ -32,12 +32,19 @@ pub enum DeviceOrientation {
     LandscapeLeft,
     LandscapeRight,
 }
-fn size_for_orientation(orientation: DeviceOrientation, scale_hack: NonZeroU32) -> (u32, u32) {
+/// `screen_size` is the size of the device's screen in portrait (see
+/// [Options::screen_size]).
+fn size_for_orientation(
+    orientation: DeviceOrientation,
+    screen_size: (u32, u32),
+    scale_hack: NonZeroU32,
+) -> (u32, u32) {
     let scale_hack = scale_hack.get();
+    let (width, height) = screen_size;
     match orientation {
-        DeviceOrientation::Portrait => (320 * scale_hack, 480 * scale_hack),
-        DeviceOrientation::LandscapeLeft => (480 * scale_hack, 320 * scale_hack),
-        DeviceOrientation::LandscapeRight => (480 * scale_hack, 320 * scale_hack),
+        DeviceOrientation::Portrait => (width * scale_hack, height * scale_hack),
+        DeviceOrientation::LandscapeLeft => (height * scale_hack, width * scale_hack),
+        DeviceOrientation::LandscapeRight => (height * scale_hack, width * scale_hack),
     }
 }
 fn rotate_fullscreen_size(orientation: DeviceOrientation, screen_size: (u32, u32)) -> (u32, u32) {
@@ -151,6 +158,8 @@ pub struct Window {
     /// Copy of `fullscreen` on [Options]. Note that this is meaningless when
     /// [Self::rotatable_fullscreen] returns [true].
     fullscreen: bool,
+    /// Copy of `screen_size` on [Options].
+    screen_size: (u32, u32),
     scale_hack: NonZeroU32,
     internal_gl_ctx: Option<Box<dyn GLES>>,
     splash_image: Option<Image>,
@@ -208,6 +217,7 @@ impl Window {
         video_ctx.enable_screen_saver();
 
         let scale_hack = options.scale_hack;
+        let screen_size = options.screen_size;
         // TODO: some apps specify their orientation in Info.plist, we could use
         // that here.
         let device_orientation = options.initial_orientation;
@@ -235,7 +245,7 @@ impl Window {
                 .unwrap();
             window
         } else {
-            let (width, height) = size_for_orientation(device_orientation, scale_hack);
+            let (width, height) = size_for_orientation(device_orientation, screen_size, scale_hack);
             let window = video_ctx
                 .window(title, width, height)
                 .position_centered()
@@ -291,6 +301,7 @@ impl Window {
             #[cfg(target_os = "macos")]
             viewport_y_offset: 0,
             fullscreen,
+            screen_size,
             scale_hack,
             internal_gl_ctx: None,
             splash_image: launch_image,
@@ -342,8 +353,11 @@ impl Window {
             independent_of_viewport: bool,
         ) -> (f32, f32) {
             let (vx, vy, vw, vh) = if independent_of_viewport {
-                let (width, height) =
-                    size_for_orientation(window.device_orientation, NonZeroU32::new(1).unwrap());
+                let (width, height) = size_for_orientation(
+                    window.device_orientation,
+                    window.screen_size,
+                    NonZeroU32::new(1).unwrap(),
+                );
                 (0, 0, width, height)
             } else {
                 window.viewport()
@@ -1071,7 +1085,7 @@ impl Window {
                 set_sdl2_orientation(new_orientation);
                 rotate_fullscreen_size(new_orientation, self.window.size())
             } else {
-                size_for_orientation(new_orientation, self.scale_hack)
+                size_for_orientation(new_orientation, self.screen_size, self.scale_hack)
             };
 
             // macOS quirk: when resizing the window, the new framebuffer's size
@@ -1129,7 +1143,11 @@ impl Window {
     /// The aspect ratio, scale and orientation reflect the guest app's view of
     /// the world.
     pub fn size_unrotated_unscaled(&self) -> (u32, u32) {
-        size_for_orientation(DeviceOrientation::Portrait, NonZeroU32::new(1).unwrap())
+        size_for_orientation(
+            DeviceOrientation::Portrait,
+            self.screen_size,
+            NonZeroU32::new(1).unwrap(),
+        )
     }
 
     /// Get the size in pixels of the window without rotation but with the
@@ -1138,7 +1156,11 @@ impl Window {
     /// Only the aspect ratio and orientation reflect the guest app's view of
     /// the world.
     pub fn size_unrotated_scalehacked(&self) -> (u32, u32) {
-        size_for_orientation(DeviceOrientation::Portrait, self.scale_hack)
+        size_for_orientation(
+            DeviceOrientation::Portrait,
+            self.screen_size,
+            self.scale_hack,
+        )
     }
 
     /// Get the region of the on-screen window (x, y, width, height) used to
@@ -1148,7 +1170,7 @@ impl Window {
     /// the world, but the scale and orientation might not.
     pub fn viewport(&self) -> (u32, u32, u32, u32) {
         let (app_width, app_height) =
-            size_for_orientation(self.device_orientation, self.scale_hack);
+            size_for_orientation(self.device_orientation, self.screen_size, self.scale_hack);
         if !self.fullscreen && !Self::rotatable_fullscreen() {
             return (0, 0, app_width, app_height);
         }
@@ -1202,6 +1224,18 @@ impl Window {
             false => self.video_ctx.disable_screen_saver(),
         }
     }
+
+    /// Get the text on the host's clipboard, if there is any.
+    pub fn clipboard_text(&self) -> Option<String> {
+        let clipboard = self.video_ctx.clipboard();
+        if !clipboard.has_clipboard_text() {
+            return None;
+        }
+        clipboard.clipboard_text().ok()
+    }
+    pub fn set_clipboard_text(&self, text: &str) -> Result<(), String> {
+        self.video_ctx.clipboard().set_clipboard_text(text)
+    }
 }
 
 pub fn open_url(url: &str) -> Result<(), String> {
@@ -0,0 +1,39 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+// This app throws a C++ exception that nothing catches, which makes the
+// bundled libstdc++ call std::terminate() and then abort(). touchHLE should
+// then report the type of the exception. It's written in C, so it uses the
+// C++ ABI directly, and it's linked to the bundled libstdc++.
+// See also tests/README.md and tests/integration.rs.
+
+typedef unsigned long size_t;
+
+// Same layout as std::type_info: a vtable pointer and a mangled name.
+struct type_info {
+  const void *vtable;
+  const char *name;
+};
+
+// The vtable of __cxxabiv1::__class_type_info, which is the type_info class
+// for classes without bases.
+extern const void *_ZTVN10__cxxabiv117__class_type_infoE[];
+void *__cxa_allocate_exception(size_t);
+void __cxa_throw(void *, struct type_info *, void (*)(void *));
+void exit(int);
+
+static struct type_info problem_type_info;
+
+int main() {
+  // Equivalent to `throw Example::Problem();` in C++. The vtable pointer
+  // skips the offset-to-top and RTTI slots at the start of the vtable.
+  problem_type_info.vtable = &_ZTVN10__cxxabiv117__class_type_infoE[2];
+  problem_type_info.name = "N7Example7ProblemE";
+  void *exception = __cxa_allocate_exception(sizeof(int));
+  __cxa_throw(exception, &problem_type_info, 0);
+  exit(0);
+  return 1; // unreachable
+}
@@ -0,0 +1,32 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+// This app runs the main thread's run loop briefly, which is when UIKit
+// handles events. It's meant to be run with some --inject-tap= options, and
+// touchHLE should log the queued touch events being dispatched in order.
+// See also tests/README.md and tests/integration.rs.
+
+typedef struct objc_object *id;
+typedef struct objc_selector *SEL;
+
+void exit(int);
+id objc_getClass(const char *);
+SEL sel_registerName(const char *);
+id objc_msgSend(id, SEL, ...);
+
+int main() {
+  id pool =
+      objc_msgSend(objc_getClass("NSAutoreleasePool"), sel_registerName("new"));
+  id run_loop = objc_msgSend(objc_getClass("NSRunLoop"),
+                             sel_registerName("currentRunLoop"));
+  SEL sel_since_now = sel_registerName("dateWithTimeIntervalSinceNow:");
+  id date = ((id(*)(id, SEL, double))objc_msgSend)(objc_getClass("NSDate"),
+                                                   sel_since_now, 0.1);
+  objc_msgSend(run_loop, sel_registerName("runUntilDate:"), date);
+  objc_msgSend(pool, sel_registerName("release"));
+  exit(0);
+  return 1; // unreachable
+}
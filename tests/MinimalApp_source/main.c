@@ -0,0 +1,24 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+// This is the smallest possible app used for integration testing: it calls a
+// single host function and hands the result back to the host. Unlike TestApp,
+// it doesn't depend on the Objective-C runtime, static initializers or any
+// framework, so it exercises only the linking and CPU round-trip (lazy linking
+// of the stub, the SVC for the host function, passing an argument to the host,
+// and receiving the return value in the guest).
+// See also tests/README.md and tests/integration.rs.
+
+int atoi(const char *);
+void exit(int);
+
+int main() {
+  // The host should parse the guest string and return 42 in r0, which is then
+  // passed straight to another host function. integration.rs checks that the
+  // exit status matches.
+  exit(atoi("42"));
+  return 1; // unreachable
+}
@@ -0,0 +1,118 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+// This app checks things that depend on the emulated frame clock. It's meant
+// to be run with --frame-pacing=50,1000000, in which case each frame is 20,000
+// instructions that last at least 20ms. The exit status is 0 if everything
+// behaved as expected.
+// See also tests/README.md and tests/integration.rs.
+
+typedef unsigned long size_t;
+typedef double CFAbsoluteTime;
+typedef struct objc_object *id;
+typedef struct objc_selector *SEL;
+typedef struct opaque_pthread_t *pthread_t;
+
+int printf(const char *, ...);
+void exit(int);
+CFAbsoluteTime CFAbsoluteTimeGetCurrent(void);
+int pthread_create(pthread_t *, const void *, void *(*)(void *), void *);
+int pthread_join(pthread_t, void **);
+id objc_getClass(const char *);
+SEL sel_registerName(const char *);
+id objc_msgSend(id, SEL, ...);
+id objc_allocateClassPair(id superclass, const char *name, size_t extraBytes);
+void objc_registerClassPair(id cls);
+int class_addMethod(id cls, SEL name, void *imp, const char *types);
+extern id NSDefaultRunLoopMode;
+
+// Returns 0 if a loop of a known number of instructions takes long enough.
+int test_instructions_per_frame() {
+  // Two instructions per iteration, so 200,000 instructions, or 10 frames.
+  // The loop starts partway through a frame, so it crosses at least 9 frame
+  // boundaries, and at least 8 whole frames of 20ms pass between them.
+  unsigned int count = 100000;
+  CFAbsoluteTime start = CFAbsoluteTimeGetCurrent();
+  __asm__ volatile("1:\n"
+                   "subs %0, %0, #1\n"
+                   "bne 1b\n"
+                   : "+r"(count)
+                   :
+                   : "cc");
+  CFAbsoluteTime elapsed = CFAbsoluteTimeGetCurrent() - start;
+  printf("Loop took %d ms\n", (int)(elapsed * 1000));
+  return elapsed >= 0.15 ? 0 : -1;
+}
+
+#define LINK_FIRINGS 10
+static int link_fire_count;
+static int link_frames[LINK_FIRINGS];
+
+void LinkTarget_tick(id self, SEL _cmd, id link) {
+  double (*get_double)(id, SEL) = (void *)objc_msgSend;
+  double timestamp = get_double(link, sel_registerName("timestamp"));
+  double duration = get_double(link, sel_registerName("duration"));
+  link_frames[link_fire_count++] = (int)(timestamp / duration + 0.5);
+  if (link_fire_count == LINK_FIRINGS)
+    objc_msgSend(link, sel_registerName("invalidate"));
+}
+
+void *link_thread_func(void *target) {
+  id pool =
+      objc_msgSend(objc_getClass("NSAutoreleasePool"), sel_registerName("new"));
+  id link = objc_msgSend(objc_getClass("CADisplayLink"),
+                         sel_registerName("displayLinkWithTarget:selector:"),
+                         target, sel_registerName("tick:"));
+  id run_loop = objc_msgSend(objc_getClass("NSRunLoop"),
+                             sel_registerName("currentRunLoop"));
+  objc_msgSend(link, sel_registerName("addToRunLoop:forMode:"), run_loop,
+               NSDefaultRunLoopMode);
+  // LINK_FIRINGS frames take at least 200ms, so this is long enough.
+  SEL sel_since_now = sel_registerName("dateWithTimeIntervalSinceNow:");
+  id date = ((id(*)(id, SEL, double))objc_msgSend)(objc_getClass("NSDate"),
+                                                   sel_since_now, 1.0);
+  objc_msgSend(run_loop, sel_registerName("runUntilDate:"), date);
+  objc_msgSend(pool, sel_registerName("release"));
+  return 0;
+}
+
+// Returns 0 if a display link fires exactly once on each frame, without
+// skipping any. The display link's thread sleeps until the next frame and
+// nothing else is running, so no frame can pass without the link seeing it.
+int test_display_link() {
+  id cls = objc_allocateClassPair(objc_getClass("NSObject"), "LinkTarget", 0);
+  class_addMethod(cls, sel_registerName("tick:"), (void *)&LinkTarget_tick,
+                  "v@:@");
+  objc_registerClassPair(cls);
+  id target = objc_msgSend(cls, sel_registerName("new"));
+
+  pthread_t thread;
+  if (pthread_create(&thread, 0, link_thread_func, target) != 0 ||
+      pthread_join(thread, 0) != 0)
+    return -1;
+  objc_msgSend(target, sel_registerName("release"));
+
+  printf("Display link fired %d times\n", link_fire_count);
+  if (link_fire_count != LINK_FIRINGS)
+    return -2;
+  for (int i = 1; i < LINK_FIRINGS; i++) {
+    if (link_frames[i] != link_frames[i - 1] + 1) {
+      printf("Fired on frame %d, then frame %d\n", link_frames[i - 1],
+             link_frames[i]);
+      return -3;
+    }
+  }
+  return 0;
+}
+
+int main() {
+  if (test_instructions_per_frame() != 0)
+    exit(1);
+  if (test_display_link() != 0)
+    exit(2);
+  exit(0);
+  return 1; // unreachable
+}
@@ -0,0 +1,39 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+// This app calls a function that touchHLE doesn't implement, which makes the
+// emulator panic. touchHLE should then print a report of the guest's state,
+// including the PC, a backtrace that points back into main(), a list of the
+// threads that includes the two this app spawns, and a map of guest memory
+// that lists their stacks.
+// See also tests/README.md and tests/integration.rs.
+
+typedef struct opaque_pthread_t *pthread_t;
+int pthread_create(pthread_t *, const void *, void *(*)(void *), void *);
+int pthread_setname_np(const char *);
+unsigned int sleep(unsigned int);
+void exit(int);
+// Not a real function. It should never be implemented by touchHLE!
+void touchHLE_test_unimplemented_function(void);
+
+// Never returns, so the thread's stack is still there at the time of the panic.
+void *sleepy_thread(void *name) {
+  pthread_setname_np(name);
+  for (;;) {
+    sleep(1);
+  }
+}
+
+int main() {
+  pthread_t thread_a, thread_b;
+  pthread_create(&thread_a, 0, sleepy_thread, "sleepy A");
+  pthread_create(&thread_b, 0, sleepy_thread, "sleepy B");
+  // Give the threads a chance to name themselves.
+  sleep(1);
+  touchHLE_test_unimplemented_function();
+  exit(0);
+  return 1; // unreachable
+}
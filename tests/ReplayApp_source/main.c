@@ -0,0 +1,33 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+// This app prints some clock readings and random numbers. It's run once with
+// --record-inputs= and once with --replay-inputs=, and the output of the two
+// runs should be identical.
+// See also tests/README.md and tests/integration.rs.
+
+typedef int time_t;
+typedef double CFAbsoluteTime;
+
+int printf(const char *, ...);
+void exit(int);
+time_t time(time_t *);
+unsigned long long mach_absolute_time(void);
+unsigned int arc4random(void);
+CFAbsoluteTime CFAbsoluteTimeGetCurrent(void);
+
+int main() {
+  for (int i = 0; i < 3; i++) {
+    unsigned long long ticks = mach_absolute_time();
+    printf("time: %d\n", time(0));
+    printf("mach_absolute_time: %08x%08x\n", (unsigned int)(ticks >> 32),
+           (unsigned int)ticks);
+    printf("CFAbsoluteTimeGetCurrent: %f\n", CFAbsoluteTimeGetCurrent());
+    printf("arc4random: %u\n", arc4random());
+  }
+  exit(0);
+  return 1; // unreachable
+}
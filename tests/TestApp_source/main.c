@@ -27,6 +27,10 @@ typedef int wchar_t;
 // <errno.h>
 int *__error(void);
 #define errno (*__error())
+#define ENOENT 2
+#define ENOTDIR 20
+#define EINVAL 22
+#define ERANGE 34
 
 // <stdarg.h>
 typedef __builtin_va_list va_list;
@@ -41,16 +45,27 @@ int fclose(FILE *);
 int sscanf(const char *, const char *, ...);
 int printf(const char *, ...);
 int vsnprintf(char *, size_t, const char *, va_list);
+int snprintf(char *, size_t, const char *, ...);
+int sprintf(char *, const char *, ...);
 int swprintf(wchar_t *, size_t, const wchar_t *, ...);
 size_t fwrite(const void *, size_t, size_t, FILE *);
+size_t fread(void *, size_t, size_t, FILE *);
+void rewind(FILE *);
+int remove(const char *);
+FILE *tmpfile(void);
 
 // <stdlib.h>
 #define EXIT_SUCCESS 0
 #define EXIT_FAILURE 1
+int atexit(void (*)(void));
 void exit(int);
 void free(void *);
 void *malloc(size_t);
 void qsort(void *, size_t, size_t, int (*)(const void *, const void *));
+void qsort_r(void *, size_t, size_t, void *,
+             int (*)(void *, const void *, const void *));
+void *bsearch(const void *, const void *, size_t, size_t,
+              int (*)(const void *, const void *));
 void *realloc(void *, size_t);
 double atof(const char *);
 float strtof(const char *, char **);
@@ -59,11 +74,27 @@ unsigned long strtoul(const char *, char **, int);
 char *realpath(const char *, char *);
 size_t mbstowcs(wchar_t *, const char *, size_t);
 size_t wcstombs(char *, const wchar_t *, size_t);
+char *getenv(const char *);
+int setenv(const char *, const char *, int);
+int unsetenv(const char *);
+int mkstemp(char *);
+
+// <cxxabi.h>
+int __cxa_atexit(void (*)(void *), void *, void *);
+
+// <new> (mangled names of operator new and operator delete)
+void *_Znwm(size_t);
+void *_Znam(size_t);
+void *_ZnwmRKSt9nothrow_t(size_t, const void *);
+void _ZdlPv(void *);
+void _ZdaPv(void *);
 
 // <string.h>
 void *memset(void *, int, size_t);
 int memcmp(const void *, const void *, size_t);
 void *memmove(void *, const void *, size_t);
+void *memcpy(void *, const void *, size_t);
+void bzero(void *, size_t);
 int strcmp(const char *, const char *);
 char *strncpy(char *, const char *, size_t);
 char *strncat(char *, const char *, size_t);
@@ -74,6 +105,10 @@ size_t strlen(const char *);
 int strncmp(const char *, const char *, size_t);
 size_t strcspn(const char *, const char *);
 char *strdup(const char *);
+char *strstr(const char *, const char *);
+char *strcat(char *, const char *);
+char *strcpy(char *, const char *);
+size_t strnlen(const char *, size_t);
 
 // <unistd.h>
 typedef unsigned int __uint32_t;
@@ -81,6 +116,35 @@ typedef __uint32_t useconds_t;
 int chdir(const char *);
 char *getcwd(char *, size_t);
 int usleep(useconds_t);
+typedef long ssize_t;
+ssize_t write(int, const void *, size_t);
+
+// <time.h>
+struct timespec {
+  long tv_sec;
+  long tv_nsec;
+};
+#define CLOCK_REALTIME 0
+#define CLOCK_MONOTONIC 6
+int clock_gettime(int clock_id, struct timespec *tp);
+typedef long time_t;
+struct tm {
+  int tm_sec;
+  int tm_min;
+  int tm_hour;
+  int tm_mday;
+  int tm_mon;
+  int tm_year;
+  int tm_wday;
+  int tm_yday;
+  int tm_isdst;
+  long tm_gmtoff;
+  char *tm_zone;
+};
+struct tm *gmtime(const time_t *);
+struct tm *localtime(const time_t *);
+time_t mktime(struct tm *);
+size_t strftime(char *, size_t, const char *, const struct tm *);
 
 // <fcntl.h>
 #define O_RDONLY 0x00000000
@@ -121,6 +185,14 @@ typedef __pthread_condattr_t pthread_condattr_t;
 
 int pthread_create(pthread_t *, const pthread_attr_t *, void *(*)(void *),
                    void *);
+int pthread_join(pthread_t, void **);
+int pthread_attr_init(pthread_attr_t *);
+int pthread_attr_setstacksize(pthread_attr_t *, size_t);
+pthread_t pthread_self(void);
+int pthread_setname_np(const char *);
+int pthread_getname_np(pthread_t, char *, size_t);
+void *pthread_get_stackaddr_np(pthread_t);
+size_t pthread_get_stacksize_np(pthread_t);
 
 int pthread_cond_init(pthread_cond_t *, const pthread_condattr_t *);
 int pthread_cond_signal(pthread_cond_t *);
@@ -162,6 +234,31 @@ typedef _register_t jmp_buf[_JBLEN];
 int setjmp(jmp_buf env);
 void longjmp(jmp_buf env, int val);
 
+// <signal.h>
+#define SIGBUS 10
+#define SIGSEGV 11
+#define SIG_DFL ((void (*)(int))0)
+#define SA_SIGINFO 0x40
+#define SEGV_MAPERR 1
+#define BUS_ADRERR 2
+typedef struct {
+  int si_signo;
+  int si_errno;
+  int si_code;
+  int si_pid;
+  unsigned int si_uid;
+  int si_status;
+  void *si_addr;
+  int __pad[9];
+} siginfo_t;
+struct sigaction {
+  void (*sa_sigaction)(int, siginfo_t *, void *);
+  unsigned int sa_mask;
+  int sa_flags;
+};
+int sigaction(int, const struct sigaction *, struct sigaction *);
+void (*signal(int, void (*)(int)))(int);
+
 // <ctype.h>
 int __maskrune(wchar_t, unsigned long);
 
@@ -179,6 +276,44 @@ int closedir(DIR *);
 int scandir(const char *, struct dirent ***, int (*)(struct dirent *),
             int (*)(const void *, const void *));
 
+// <malloc/malloc.h>
+struct mstats {
+  size_t bytes_total;
+  size_t chunks_used;
+  size_t bytes_used;
+  size_t chunks_free;
+  size_t bytes_free;
+};
+size_t malloc_size(const void *);
+struct mstats mstats(void);
+size_t malloc_good_size(size_t);
+typedef struct _malloc_zone_t malloc_zone_t;
+typedef struct {
+  unsigned blocks_in_use;
+  size_t size_in_use;
+  size_t max_size_in_use;
+  size_t size_allocated;
+} malloc_statistics_t;
+void malloc_zone_statistics(malloc_zone_t *, malloc_statistics_t *);
+
+// <libkern/OSAtomic.h>
+void OSMemoryBarrier(void);
+
+// <dispatch/dispatch.h>
+typedef long dispatch_once_t;
+typedef void (^dispatch_block_t)(void);
+void dispatch_once(dispatch_once_t *, dispatch_block_t);
+typedef unsigned long long dispatch_time_t;
+#define DISPATCH_TIME_NOW 0ull
+#define DISPATCH_TIME_FOREVER (~0ull)
+#define NSEC_PER_MSEC 1000000ll
+dispatch_time_t dispatch_time(dispatch_time_t, long long);
+typedef struct dispatch_semaphore_s *dispatch_semaphore_t;
+dispatch_semaphore_t dispatch_semaphore_create(long);
+long dispatch_semaphore_wait(dispatch_semaphore_t, dispatch_time_t);
+long dispatch_semaphore_signal(dispatch_semaphore_t);
+void dispatch_release(void *);
+
 // <wchar.h>
 int swscanf(const wchar_t *, const wchar_t *, ...);
 
@@ -189,6 +324,77 @@ double ldexp(double, int);
 float ldexpf(float, int);
 float frexpf(float, int *);
 
+// <dlfcn.h>
+void *dlopen(const char *, int);
+void *dlsym(void *, const char *);
+int dlclose(void *);
+#define RTLD_LAZY 0x1
+
+// <execinfo.h>
+int backtrace(void **, int);
+char **backtrace_symbols(void *const *, int);
+
+// <sys/mman.h>
+#define PROT_READ 0x1
+#define MAP_PRIVATE 0x2
+#define MAP_FAILED ((void *)-1)
+void *mmap(void *, size_t, int, int, int, long long);
+
+// <mach/mach.h>
+typedef int kern_return_t;
+typedef unsigned int mach_port_t;
+typedef unsigned int vm_address_t;
+typedef unsigned int vm_size_t;
+#define KERN_SUCCESS 0
+#define VM_FLAGS_FIXED 0
+#define VM_FLAGS_ANYWHERE 1
+mach_port_t mach_task_self(void);
+kern_return_t vm_allocate(mach_port_t, vm_address_t *, vm_size_t, int);
+kern_return_t vm_deallocate(mach_port_t, vm_address_t, vm_size_t);
+typedef struct {
+  int seconds;
+  int microseconds;
+} time_value_t;
+struct task_basic_info {
+  int suspend_count;
+  vm_size_t virtual_size;
+  vm_size_t resident_size;
+  time_value_t user_time;
+  time_value_t system_time;
+  int policy;
+};
+#define TASK_BASIC_INFO 4
+#define TASK_BASIC_INFO_COUNT                                                  \
+  (sizeof(struct task_basic_info) / sizeof(unsigned int))
+kern_return_t task_info(mach_port_t, unsigned int, int *, unsigned int *);
+
+// <sys/sysctl.h>
+int sysctlbyname(const char *, void *, size_t *, void *, size_t);
+
+// ARM EABI run-time helpers. The divmod functions return the quotient in r0
+// and the remainder in r1, so they're declared as returning a 64-bit value.
+int __aeabi_idiv(int, int);
+unsigned int __aeabi_uidiv(unsigned int, unsigned int);
+unsigned long long __aeabi_idivmod(int, int);
+unsigned long long __aeabi_uidivmod(unsigned int, unsigned int);
+double __aeabi_dadd(double, double);
+double __aeabi_drsub(double, double);
+double __aeabi_ddiv(double, double);
+float __aeabi_fmul(float, float);
+int __aeabi_dcmpeq(double, double);
+int __aeabi_dcmplt(double, double);
+int __aeabi_dcmpun(double, double);
+int __aeabi_fcmpge(float, float);
+int __aeabi_d2iz(double);
+unsigned int __aeabi_d2uiz(double);
+long long __aeabi_d2lz(double);
+int __aeabi_f2iz(float);
+double __aeabi_i2d(int);
+float __aeabi_ui2f(unsigned int);
+double __aeabi_l2d(long long);
+double __aeabi_f2d(float);
+float __aeabi_d2f(double);
+
 // `CFBase.h`
 
 typedef unsigned char Boolean;
@@ -211,6 +417,14 @@ void CFRelease(CFTypeRef cf);
 Boolean CFEqual(CFTypeRef cf1, CFTypeRef cf2);
 CFHashCode CFHash(CFTypeRef cf);
 
+// `CFAllocator.h`
+
+extern const CFAllocatorRef kCFAllocatorNull;
+
+void *CFAllocatorAllocate(CFAllocatorRef allocator, CFIndex size,
+                          CFOptionFlags hint);
+void CFAllocatorDeallocate(CFAllocatorRef allocator, void *ptr);
+
 // `CFString.h`
 
 typedef int CFComparisonResult;
@@ -271,6 +485,141 @@ CFIndex CFDictionaryGetCount(CFDictionaryRef dict);
 void CFDictionaryGetKeysAndValues(CFDictionaryRef dict, const void **keys,
                                   const void **values);
 
+extern const CFDictionaryKeyCallBacks kCFTypeDictionaryKeyCallBacks;
+extern const CFDictionaryValueCallBacks kCFTypeDictionaryValueCallBacks;
+
+CFDictionaryRef
+CFDictionaryCreate(CFAllocatorRef allocator, const void **keys,
+                   const void **values, CFIndex numValues,
+                   const CFDictionaryKeyCallBacks *keyCallBacks,
+                   const CFDictionaryValueCallBacks *valueCallBacks);
+Boolean CFDictionaryContainsKey(CFDictionaryRef dict, const void *key);
+
+// `CFArray.h`
+
+typedef const struct _CFArray *CFArrayRef;
+typedef const struct _CFArray *CFMutableArrayRef;
+
+typedef struct {
+  CFIndex version;
+  CFDictionaryRetainCallBack retain;
+  CFDictionaryReleaseCallBack release;
+  CFDictionaryCopyDescriptionCallBack copyDescription;
+  CFDictionaryEqualCallBack equal;
+} CFArrayCallBacks;
+
+extern const CFArrayCallBacks kCFTypeArrayCallBacks;
+
+CFArrayRef CFArrayCreate(CFAllocatorRef allocator, const void **values,
+                         CFIndex numValues, const CFArrayCallBacks *callBacks);
+CFMutableArrayRef CFArrayCreateMutable(CFAllocatorRef allocator,
+                                       CFIndex capacity,
+                                       const CFArrayCallBacks *callBacks);
+CFIndex CFArrayGetCount(CFArrayRef array);
+const void *CFArrayGetValueAtIndex(CFArrayRef array, CFIndex idx);
+void CFArrayAppendValue(CFMutableArrayRef array, const void *value);
+void CFArrayInsertValueAtIndex(CFMutableArrayRef array, CFIndex idx,
+                               const void *value);
+void CFArraySetValueAtIndex(CFMutableArrayRef array, CFIndex idx,
+                            const void *value);
+void CFArrayRemoveAllValues(CFMutableArrayRef array);
+
+// `CFURL.h`
+
+typedef const struct __CFURL *CFURLRef;
+
+Boolean CFURLGetFileSystemRepresentation(CFURLRef url,
+                                         Boolean resolveAgainstBase,
+                                         unsigned char *buffer,
+                                         CFIndex maxBufLen);
+
+// `CFBundle.h`
+
+typedef struct __CFBundle *CFBundleRef;
+
+CFBundleRef CFBundleGetMainBundle(void);
+CFTypeRef CFBundleGetValueForInfoDictionaryKey(CFBundleRef bundle,
+                                               CFStringRef key);
+CFStringRef CFBundleGetIdentifier(CFBundleRef bundle);
+CFURLRef CFBundleCopyResourceURL(CFBundleRef bundle, CFStringRef resourceName,
+                                 CFStringRef resourceType,
+                                 CFStringRef subDirName);
+
+// <objc/runtime.h>
+typedef struct objc_object *id;
+typedef struct objc_selector *SEL;
+#define nil ((id)0)
+id objc_getClass(const char *);
+SEL sel_registerName(const char *);
+id objc_msgSend(id, SEL, ...);
+void objc_msgSend_stret(void *, id, SEL, ...);
+id objc_allocateClassPair(id superclass, const char *name, size_t extraBytes);
+void objc_registerClassPair(id cls);
+char class_addMethod(id cls, SEL name, void *imp, const char *types);
+char class_addIvar(id cls, const char *name, size_t size,
+                   unsigned char alignment, const char *types);
+void *object_getInstanceVariable(id obj, const char *name, void **outValue);
+typedef struct objc_object Protocol;
+Protocol *objc_getProtocol(const char *name);
+char class_conformsToProtocol(id cls, Protocol *protocol);
+typedef struct objc_property *objc_property_t;
+typedef struct {
+  const char *name;
+  const char *value;
+} objc_property_attribute_t;
+char class_addProperty(id cls, const char *name,
+                       const objc_property_attribute_t *attributes,
+                       unsigned int attributeCount);
+objc_property_t class_getProperty(id cls, const char *name);
+objc_property_t *class_copyPropertyList(id cls, unsigned int *outCount);
+const char *property_getName(objc_property_t property);
+const char *property_getAttributes(objc_property_t property);
+char *property_copyAttributeValue(objc_property_t property,
+                                  const char *attributeName);
+void objc_enumerationMutation(id obj);
+void objc_setEnumerationMutationHandler(void (*handler)(id));
+
+// <objc/objc-arc.h> (not a public header)
+id objc_retain(id obj);
+void objc_release(id obj);
+id objc_autorelease(id obj);
+id objc_retainAutorelease(id obj);
+
+// <Foundation/NSPathUtilities.h>
+id NSTemporaryDirectory(void);
+
+// `NSEnumerator.h`
+typedef struct {
+  unsigned long state;
+  id *itemsPtr;
+  unsigned long *mutationsPtr;
+  unsigned long extra[5];
+} NSFastEnumerationState;
+
+// `NSLocale.h`
+extern id NSLocaleLanguageCode;
+extern id NSLocaleCountryCode;
+extern id NSLocaleDecimalSeparator;
+
+// `NSThread.h`
+extern id NSWillBecomeMultiThreadedNotification;
+extern id NSThreadWillExitNotification;
+
+// `NSRunLoop.h`
+extern id NSDefaultRunLoopMode;
+
+// `CGGeometry.h` (the structs are declared in CGAffineTransform.c)
+CGPoint CGPointMake(CGFloat, CGFloat);
+CGSize CGSizeMake(CGFloat, CGFloat);
+CGRect CGRectMake(CGFloat, CGFloat, CGFloat, CGFloat);
+CGFloat CGRectGetMaxX(CGRect);
+CGFloat CGRectGetMidY(CGRect);
+bool CGRectIsNull(CGRect);
+CGRect CGRectInset(CGRect, CGFloat, CGFloat);
+CGRect CGRectIntersection(CGRect, CGRect);
+bool CGRectIntersectsRect(CGRect, CGRect);
+bool CGRectContainsPoint(CGRect, CGPoint);
+
 // === Main code ===
 
 int int_compar(const void *a, const void *b) { return *(int *)a - *(int *)b; }
@@ -301,6 +650,40 @@ int test_qsort() {
   return 0;
 }
 
+// The context is a multiplier applied to both values, so a negative one
+// reverses the order.
+int int_compar_r(void *context, const void *a, const void *b) {
+  int multiplier = *(int *)context;
+  return *(int *)a * multiplier - *(int *)b * multiplier;
+}
+
+int test_qsort_r_bsearch() {
+  int arr[5] = {3, -1, 4, 1, 5};
+  int multiplier = -1;
+  qsort_r(arr, 5, sizeof(int), &multiplier, &int_compar_r);
+  if (memcmp(arr, (int[]){5, 4, 3, 1, -1}, sizeof(arr)))
+    return -1;
+  multiplier = 1;
+  qsort_r(arr, 5, sizeof(int), &multiplier, &int_compar_r);
+  if (memcmp(arr, (int[]){-1, 1, 3, 4, 5}, sizeof(arr)))
+    return -2;
+
+  int key = 4;
+  int *found = bsearch(&key, arr, 5, sizeof(int), &int_compar);
+  if (found != &arr[3])
+    return -3;
+  key = -1;
+  found = bsearch(&key, arr, 5, sizeof(int), &int_compar);
+  if (found != &arr[0])
+    return -4;
+  key = 2;
+  if (bsearch(&key, arr, 5, sizeof(int), &int_compar) != NULL)
+    return -5;
+  if (bsearch(&key, arr, 0, sizeof(int), &int_compar) != NULL)
+    return -6;
+  return 0;
+}
+
 char *str_format(const char *format, ...) {
   char *str = malloc(256);
   if (str == NULL) {
@@ -441,8 +824,10 @@ int test_vsnprintf() {
 int test_sscanf() {
   int a, b;
   short c, d;
+  unsigned int u;
   float f;
-  char str[4];
+  char ch;
+  char str[4], str2[8];
   int matched = sscanf("1.23", "%d.%d", &a, &b);
   if (!(matched == 2 && a == 1 && b == 23))
     return -1;
@@ -488,6 +873,24 @@ int test_sscanf() {
   matched = sscanf("09", "%i", &a);
   if (!(matched == 1 && a == 0))
     return -16;
+  matched = sscanf("12 3.5 hello", "%d %f %s", &a, &f, str2);
+  if (!(matched == 3 && a == 12 && f == 3.5 && strcmp(str2, "hello") == 0))
+    return -17;
+  matched = sscanf("12 abc", "%d %f", &a, &f);
+  if (!(matched == 1 && a == 12))
+    return -18;
+  matched = sscanf("  ", "%d", &a);
+  if (matched != -1)
+    return -19;
+  matched = sscanf("4294967295 x", "%u %c", &u, &ch);
+  if (!(matched == 2 && u == 4294967295u && ch == 'x'))
+    return -20;
+  matched = sscanf("12345ff00", "%3d%2d%2x%c", &a, &b, &u, &ch);
+  if (!(matched == 4 && a == 123 && b == 45 && u == 255 && ch == '0'))
+    return -21;
+  matched = sscanf("hello world", "%3s%n", str2, &a);
+  if (!(matched == 1 && strcmp(str2, "hel") == 0 && a == 3))
+    return -22;
   return 0;
 }
 
@@ -1099,6 +1502,27 @@ int test_realpath() {
   if (!res || strncmp(cwd, res, strlen(cwd)) != 0 ||
       strncmp("/TestApp", res + strlen(cwd), 8) != 0)
     return -4;
+  // `..` can't escape the root
+  res = realpath("../../../../../../../usr/./lib", buf);
+  if (!res || strcmp(res, "/usr/lib") != 0)
+    return -5;
+  // the result is allocated if no buffer is passed
+  res = realpath(".", NULL);
+  if (!res || strcmp(res, cwd) != 0)
+    return -6;
+  free(res);
+  // the path must exist
+  if (realpath("NonExistent/..", buf) != NULL || errno != ENOENT)
+    return -7;
+  // chdir errors
+  if (chdir("NonExistent") != -1 || errno != ENOENT)
+    return -8;
+  if (chdir("TestApp") != -1 || errno != ENOTDIR)
+    return -9;
+  if (getcwd(buf, 1) != NULL || errno != ERANGE)
+    return -10;
+  if (chdir("/"))
+    return -11;
   return 0;
 }
 
@@ -2134,6 +2558,3137 @@ int test_setjmp() {
   return -1;
 }
 
+jmp_buf segv_jmp_buf;
+void *segv_addr;
+int segv_code;
+
+void segv_handler(int sig, siginfo_t *info, void *context) {
+  segv_addr = info->si_addr;
+  segv_code = info->si_code;
+  longjmp(segv_jmp_buf, sig);
+}
+
+int test_sigsegv_handler() {
+  struct sigaction act = {0}, old;
+  act.sa_sigaction = segv_handler;
+  act.sa_flags = SA_SIGINFO;
+  if (sigaction(SIGSEGV, &act, &old) != 0 || old.sa_sigaction != NULL)
+    return -1;
+
+  int res = 0;
+  int sig = setjmp(segv_jmp_buf);
+  if (sig == 0) {
+    *(volatile int *)0x10 = 1;
+    res = -2; // the handler wasn't called
+  } else if (sig != SIGSEGV) {
+    res = -3;
+  } else if (segv_addr != (void *)0x10) {
+    res = -4;
+  } else if (segv_code != SEGV_MAPERR) {
+    res = -7;
+  }
+
+  // Restore the default action, checking signal() returns the old handler.
+  void (*prev)(int) = signal(SIGSEGV, SIG_DFL);
+  if (prev != (void (*)(int))segv_handler)
+    return -5;
+  if (signal(SIGSEGV, SIG_DFL) != SIG_DFL)
+    return -6;
+
+  return res;
+}
+
+// Binary plist produced by NSKeyedArchiver for the object graph:
+// @{@"name": @"touchHLE", @"list": @[@42, @1.5, <010203>]}
+static const unsigned char keyed_archive[] = {
+    0x62, 0x70, 0x6c, 0x69, 0x73, 0x74, 0x30, 0x30, 0xd4, 0x01, 0x02, 0x03,
+    0x04, 0x05, 0x06, 0x28, 0x2b, 0x59, 0x24, 0x61, 0x72, 0x63, 0x68, 0x69,
+    0x76, 0x65, 0x72, 0x58, 0x24, 0x6f, 0x62, 0x6a, 0x65, 0x63, 0x74, 0x73,
+    0x54, 0x24, 0x74, 0x6f, 0x70, 0x58, 0x24, 0x76, 0x65, 0x72, 0x73, 0x69,
+    0x6f, 0x6e, 0x5f, 0x10, 0x0f, 0x4e, 0x53, 0x4b, 0x65, 0x79, 0x65, 0x64,
+    0x41, 0x72, 0x63, 0x68, 0x69, 0x76, 0x65, 0x72, 0xab, 0x07, 0x08, 0x13,
+    0x14, 0x15, 0x16, 0x1c, 0x1d, 0x23, 0x26, 0x27, 0x55, 0x24, 0x6e, 0x75,
+    0x6c, 0x6c, 0xd3, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x10, 0x56, 0x24, 0x63,
+    0x6c, 0x61, 0x73, 0x73, 0x57, 0x4e, 0x53, 0x2e, 0x6b, 0x65, 0x79, 0x73,
+    0x5a, 0x4e, 0x53, 0x2e, 0x6f, 0x62, 0x6a, 0x65, 0x63, 0x74, 0x73, 0x80,
+    0x07, 0xa2, 0x0e, 0x0f, 0x80, 0x02, 0x80, 0x03, 0xa2, 0x11, 0x12, 0x80,
+    0x04, 0x80, 0x05, 0x54, 0x6e, 0x61, 0x6d, 0x65, 0x54, 0x6c, 0x69, 0x73,
+    0x74, 0x58, 0x74, 0x6f, 0x75, 0x63, 0x68, 0x48, 0x4c, 0x45, 0xd2, 0x09,
+    0x0b, 0x17, 0x18, 0x80, 0x08, 0xa3, 0x19, 0x1a, 0x1b, 0x80, 0x06, 0x80,
+    0x09, 0x80, 0x0a, 0x10, 0x2a, 0xd2, 0x1e, 0x1f, 0x20, 0x21, 0x58, 0x24,
+    0x63, 0x6c, 0x61, 0x73, 0x73, 0x65, 0x73, 0x5a, 0x24, 0x63, 0x6c, 0x61,
+    0x73, 0x73, 0x6e, 0x61, 0x6d, 0x65, 0xa2, 0x21, 0x22, 0x5c, 0x4e, 0x53,
+    0x44, 0x69, 0x63, 0x74, 0x69, 0x6f, 0x6e, 0x61, 0x72, 0x79, 0x58, 0x4e,
+    0x53, 0x4f, 0x62, 0x6a, 0x65, 0x63, 0x74, 0xd2, 0x1e, 0x1f, 0x24, 0x25,
+    0xa2, 0x25, 0x22, 0x57, 0x4e, 0x53, 0x41, 0x72, 0x72, 0x61, 0x79, 0x23,
+    0x3f, 0xf8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x43, 0x01, 0x02, 0x03,
+    0xd1, 0x29, 0x2a, 0x54, 0x72, 0x6f, 0x6f, 0x74, 0x80, 0x01, 0x12, 0x00,
+    0x01, 0x86, 0xa0, 0x00, 0x08, 0x00, 0x11, 0x00, 0x1b, 0x00, 0x24, 0x00,
+    0x29, 0x00, 0x32, 0x00, 0x44, 0x00, 0x50, 0x00, 0x56, 0x00, 0x5d, 0x00,
+    0x64, 0x00, 0x6c, 0x00, 0x77, 0x00, 0x79, 0x00, 0x7c, 0x00, 0x7e, 0x00,
+    0x80, 0x00, 0x83, 0x00, 0x85, 0x00, 0x87, 0x00, 0x8c, 0x00, 0x91, 0x00,
+    0x9a, 0x00, 0x9f, 0x00, 0xa1, 0x00, 0xa5, 0x00, 0xa7, 0x00, 0xa9, 0x00,
+    0xab, 0x00, 0xad, 0x00, 0xb2, 0x00, 0xbb, 0x00, 0xc6, 0x00, 0xc9, 0x00,
+    0xd6, 0x00, 0xdf, 0x00, 0xe4, 0x00, 0xe7, 0x00, 0xef, 0x00, 0xf8, 0x00,
+    0xfc, 0x00, 0xff, 0x01, 0x04, 0x01, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x02, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x01, 0x0b,
+};
+
+int test_NSKeyedUnarchiver() {
+  SEL sel_dataWithBytes_length = sel_registerName("dataWithBytes:length:");
+  SEL sel_unarchive = sel_registerName("unarchiveObjectWithData:");
+  SEL sel_objectForKey = sel_registerName("objectForKey:");
+  SEL sel_objectAtIndex = sel_registerName("objectAtIndex:");
+  SEL sel_count = sel_registerName("count");
+  SEL sel_UTF8String = sel_registerName("UTF8String");
+  SEL sel_intValue = sel_registerName("intValue");
+  SEL sel_doubleValue = sel_registerName("doubleValue");
+  SEL sel_length = sel_registerName("length");
+  SEL sel_bytes = sel_registerName("bytes");
+
+  id data = ((id(*)(id, SEL, const void *, unsigned int))objc_msgSend)(
+      objc_getClass("NSData"), sel_dataWithBytes_length, keyed_archive,
+      sizeof(keyed_archive));
+  id root =
+      objc_msgSend(objc_getClass("NSKeyedUnarchiver"), sel_unarchive, data);
+  if (root == nil)
+    return -1;
+
+  id name = objc_msgSend(root, sel_objectForKey,
+                         CFStringCreateWithCString(NULL, "name", 0x0600));
+  if (name == nil)
+    return -2;
+  if (strcmp((const char *)objc_msgSend(name, sel_UTF8String), "touchHLE"))
+    return -3;
+
+  id list = objc_msgSend(root, sel_objectForKey,
+                         CFStringCreateWithCString(NULL, "list", 0x0600));
+  if (list == nil)
+    return -4;
+  if (((unsigned int (*)(id, SEL))objc_msgSend)(list, sel_count) != 3)
+    return -5;
+
+  id item =
+      ((id(*)(id, SEL, unsigned int))objc_msgSend)(list, sel_objectAtIndex, 0);
+  if (((int (*)(id, SEL))objc_msgSend)(item, sel_intValue) != 42)
+    return -6;
+  item =
+      ((id(*)(id, SEL, unsigned int))objc_msgSend)(list, sel_objectAtIndex, 1);
+  if (((double (*)(id, SEL))objc_msgSend)(item, sel_doubleValue) != 1.5)
+    return -7;
+  item =
+      ((id(*)(id, SEL, unsigned int))objc_msgSend)(list, sel_objectAtIndex, 2);
+  if (((unsigned int (*)(id, SEL))objc_msgSend)(item, sel_length) != 3)
+    return -8;
+  const unsigned char *bytes =
+      (const unsigned char *)objc_msgSend(item, sel_bytes);
+  if (bytes[0] != 1 || bytes[1] != 2 || bytes[2] != 3)
+    return -9;
+
+  return 0;
+}
+
+int test_performSelector() {
+  SEL sel_perform1 = sel_registerName("performSelector:withObject:");
+  SEL sel_perform2 = sel_registerName("performSelector:withObject:withObject:");
+  SEL sel_setObject_forKey = sel_registerName("setObject:forKey:");
+  SEL sel_objectForKey = sel_registerName("objectForKey:");
+
+  id dict = objc_msgSend(objc_getClass("NSMutableDictionary"),
+                         sel_registerName("dictionary"));
+  id key = (id)CFStringCreateWithCString(NULL, "key", 0x0600);
+  id value = (id)CFStringCreateWithCString(NULL, "value", 0x0600);
+
+  objc_msgSend(dict, sel_perform2, sel_setObject_forKey, value, key);
+  id result = objc_msgSend(dict, sel_perform1, sel_objectForKey, key);
+  if (result != value)
+    return -1;
+  result = objc_msgSend(dict, sel_perform1, sel_objectForKey, value);
+  if (result != nil)
+    return -2;
+
+  // Two arguments, returning an object
+  id fruit = (id)CFStringCreateWithCString(NULL, "apple pie", 0x0600);
+  id old = (id)CFStringCreateWithCString(NULL, "apple", 0x0600);
+  id new = (id)CFStringCreateWithCString(NULL, "cherry", 0x0600);
+  result = objc_msgSend(
+      fruit, sel_perform2,
+      sel_registerName("stringByReplacingOccurrencesOfString:withString:"), old,
+      new);
+  if (result == nil ||
+      strcmp((const char *)objc_msgSend(result, sel_registerName("UTF8String")),
+             "cherry pie"))
+    return -3;
+
+  // Classes can perform selectors too
+  result = objc_msgSend(objc_getClass("NSDictionary"), sel_perform2,
+                        sel_registerName("dictionaryWithObject:forKey:"), value,
+                        key);
+  if (result == nil || objc_msgSend(result, sel_objectForKey, key) != value)
+    return -4;
+
+  return 0;
+}
+
+int test_NSNull() {
+  SEL sel_null = sel_registerName("null");
+  SEL sel_release = sel_registerName("release");
+  SEL sel_retainCount = sel_registerName("retainCount");
+  SEL sel_isEqual = sel_registerName("isEqual:");
+  id NSNull = objc_getClass("NSNull");
+
+  id null1 = objc_msgSend(NSNull, sel_null);
+  if (null1 == nil)
+    return -1;
+  id null2 = objc_msgSend(NSNull, sel_null);
+  if (null1 != null2)
+    return -2;
+
+  // Over-releasing the singleton must not deallocate it.
+  objc_msgSend(null1, sel_release);
+  objc_msgSend(null1, sel_release);
+  objc_msgSend(null1, sel_release);
+  if (objc_msgSend(NSNull, sel_null) != null1)
+    return -3;
+  if (((unsigned int (*)(id, SEL))objc_msgSend)(null1, sel_retainCount) !=
+      0xFFFFFFFF)
+    return -4;
+
+  id null3 = objc_msgSend(objc_msgSend(NSNull, sel_registerName("alloc")),
+                          sel_registerName("init"));
+  if (null3 != null1)
+    return -5;
+  if (!((char (*)(id, SEL, id))objc_msgSend)(null1, sel_isEqual, null3))
+    return -6;
+
+  return 0;
+}
+
+int test_NSJSONSerialization() {
+  SEL sel_JSONObject = sel_registerName("JSONObjectWithData:options:error:");
+  SEL sel_dataWithJSONObject =
+      sel_registerName("dataWithJSONObject:options:error:");
+  SEL sel_dataWithBytes_length = sel_registerName("dataWithBytes:length:");
+  SEL sel_objectForKey = sel_registerName("objectForKey:");
+  SEL sel_objectAtIndex = sel_registerName("objectAtIndex:");
+  SEL sel_count = sel_registerName("count");
+  SEL sel_intValue = sel_registerName("intValue");
+  SEL sel_code = sel_registerName("code");
+  SEL sel_length = sel_registerName("length");
+  SEL sel_bytes = sel_registerName("bytes");
+  id NSData = objc_getClass("NSData");
+  id NSJSONSerialization = objc_getClass("NSJSONSerialization");
+
+  const char *text = "{\"a\": [1, {\"b\": null}, \"c\"]}";
+  id data = ((id(*)(id, SEL, const void *, unsigned int))objc_msgSend)(
+      NSData, sel_dataWithBytes_length, text, strlen(text));
+  id error = nil;
+  id root = ((id(*)(id, SEL, id, unsigned int, id *))objc_msgSend)(
+      NSJSONSerialization, sel_JSONObject, data, 0, &error);
+  if (root == nil || error != nil)
+    return -1;
+  id a = objc_msgSend(root, sel_objectForKey,
+                      CFStringCreateWithCString(NULL, "a", 0x0600));
+  if (((unsigned int (*)(id, SEL))objc_msgSend)(a, sel_count) != 3)
+    return -2;
+  id one =
+      ((id(*)(id, SEL, unsigned int))objc_msgSend)(a, sel_objectAtIndex, 0);
+  if (((int (*)(id, SEL))objc_msgSend)(one, sel_intValue) != 1)
+    return -3;
+  id inner =
+      ((id(*)(id, SEL, unsigned int))objc_msgSend)(a, sel_objectAtIndex, 1);
+  id b = objc_msgSend(inner, sel_objectForKey,
+                      CFStringCreateWithCString(NULL, "b", 0x0600));
+  if (b != objc_msgSend(objc_getClass("NSNull"), sel_registerName("null")))
+    return -4;
+
+  // Serialize it back. There's only one key per dictionary, so the output
+  // doesn't depend on the key order.
+  id out = ((id(*)(id, SEL, id, unsigned int, id *))objc_msgSend)(
+      NSJSONSerialization, sel_dataWithJSONObject, root, 0, &error);
+  if (out == nil || error != nil)
+    return -5;
+  const char *expected = "{\"a\":[1,{\"b\":null},\"c\"]}";
+  unsigned int len = ((unsigned int (*)(id, SEL))objc_msgSend)(out, sel_length);
+  if (len != strlen(expected) ||
+      memcmp(objc_msgSend(out, sel_bytes), expected, len))
+    return -6;
+
+  // Malformed input must produce an error.
+  const char *bad = "[1, 2";
+  data = ((id(*)(id, SEL, const void *, unsigned int))objc_msgSend)(
+      NSData, sel_dataWithBytes_length, bad, strlen(bad));
+  root = ((id(*)(id, SEL, id, unsigned int, id *))objc_msgSend)(
+      NSJSONSerialization, sel_JSONObject, data, 0, &error);
+  if (root != nil || error == nil)
+    return -7;
+  if (((int (*)(id, SEL))objc_msgSend)(error, sel_code) != 3840)
+    return -8;
+
+  return 0;
+}
+
+int test_NSURL() {
+  SEL sel_fileURLWithPath = sel_registerName("fileURLWithPath:");
+  SEL sel_URLWithString = sel_registerName("URLWithString:");
+  SEL sel_path = sel_registerName("path");
+  SEL sel_absoluteString = sel_registerName("absoluteString");
+  SEL sel_lastPathComponent = sel_registerName("lastPathComponent");
+  SEL sel_isFileURL = sel_registerName("isFileURL");
+  SEL sel_isEqualToString = sel_registerName("isEqualToString:");
+  id NSURL = objc_getClass("NSURL");
+
+  id path = CFStringCreateWithCString(NULL, "/tmp/some dir/file.txt", 0x0600);
+  id url = objc_msgSend(NSURL, sel_fileURLWithPath, path);
+  if (!((char (*)(id, SEL))objc_msgSend)(url, sel_isFileURL))
+    return -1;
+  if (!((char (*)(id, SEL, id))objc_msgSend)(objc_msgSend(url, sel_path),
+                                             sel_isEqualToString, path))
+    return -2;
+  id last = CFStringCreateWithCString(NULL, "file.txt", 0x0600);
+  if (!((char (*)(id, SEL, id))objc_msgSend)(
+          objc_msgSend(url, sel_lastPathComponent), sel_isEqualToString, last))
+    return -3;
+
+  // Round trip through the URL string form.
+  id string = objc_msgSend(url, sel_absoluteString);
+  id expected = CFStringCreateWithCString(
+      NULL, "file://localhost/tmp/some%20dir/file.txt", 0x0600);
+  if (!((char (*)(id, SEL, id))objc_msgSend)(string, sel_isEqualToString,
+                                             expected))
+    return -4;
+  id url2 = objc_msgSend(NSURL, sel_URLWithString, string);
+  if (!((char (*)(id, SEL))objc_msgSend)(url2, sel_isFileURL))
+    return -5;
+  if (!((char (*)(id, SEL, id))objc_msgSend)(objc_msgSend(url2, sel_path),
+                                             sel_isEqualToString, path))
+    return -6;
+
+  id http_string = CFStringCreateWithCString(
+      NULL, "http://example.com/a%20b/c.png?d=e", 0x0600);
+  id url3 = objc_msgSend(NSURL, sel_URLWithString, http_string);
+  if (((char (*)(id, SEL))objc_msgSend)(url3, sel_isFileURL))
+    return -7;
+  id http_path = CFStringCreateWithCString(NULL, "/a b/c.png", 0x0600);
+  if (!((char (*)(id, SEL, id))objc_msgSend)(objc_msgSend(url3, sel_path),
+                                             sel_isEqualToString, http_path))
+    return -8;
+  id http_last = CFStringCreateWithCString(NULL, "c.png", 0x0600);
+  if (!((char (*)(id, SEL, id))objc_msgSend)(
+          objc_msgSend(url3, sel_lastPathComponent), sel_isEqualToString,
+          http_last))
+    return -9;
+
+  // Other schemes, e.g. the ones apps register for themselves, work too.
+  id app_string = CFStringCreateWithCString(NULL, "myapp://x", 0x0600);
+  id url4 = objc_msgSend(NSURL, sel_URLWithString, app_string);
+  if (!((char (*)(id, SEL, id))objc_msgSend)(
+          objc_msgSend(url4, sel_absoluteString), sel_isEqualToString,
+          app_string))
+    return -10;
+
+  return 0;
+}
+
+int test_NSDateFormatter() {
+  SEL sel_alloc = sel_registerName("alloc");
+  SEL sel_init = sel_registerName("init");
+  SEL sel_setDateFormat = sel_registerName("setDateFormat:");
+  SEL sel_setTimeZone = sel_registerName("setTimeZone:");
+  SEL sel_stringFromDate = sel_registerName("stringFromDate:");
+  SEL sel_dateFromString = sel_registerName("dateFromString:");
+  SEL sel_isEqualToString = sel_registerName("isEqualToString:");
+  SEL sel_secondsFromGMT = sel_registerName("secondsFromGMT");
+  SEL sel_timeIntervalSinceReferenceDate =
+      sel_registerName("timeIntervalSinceReferenceDate");
+  id NSTimeZone = objc_getClass("NSTimeZone");
+
+  if (objc_msgSend(NSTimeZone, sel_registerName("defaultTimeZone")) == nil)
+    return -1;
+  id gmt = objc_msgSend(NSTimeZone, sel_registerName("timeZoneWithName:"),
+                        CFStringCreateWithCString(NULL, "GMT", 0x0600));
+  if (gmt == nil ||
+      ((int (*)(id, SEL))objc_msgSend)(gmt, sel_secondsFromGMT) != 0)
+    return -2;
+
+  // 2005-05-27 19:45:47 UTC
+  id date = ((id(*)(id, SEL, double))objc_msgSend)(
+      objc_msgSend(objc_getClass("NSDate"), sel_alloc),
+      sel_registerName("initWithTimeIntervalSinceReferenceDate:"), 138915947.0);
+  id formatter = objc_msgSend(
+      objc_msgSend(objc_getClass("NSDateFormatter"), sel_alloc), sel_init);
+  id format = CFStringCreateWithCString(NULL, "yyyy-MM-dd HH:mm:ss", 0x0600);
+  objc_msgSend(formatter, sel_setDateFormat, format);
+  objc_msgSend(formatter, sel_setTimeZone, gmt);
+
+  id string = objc_msgSend(formatter, sel_stringFromDate, date);
+  id expected = CFStringCreateWithCString(NULL, "2005-05-27 19:45:47", 0x0600);
+  if (!((char (*)(id, SEL, id))objc_msgSend)(string, sel_isEqualToString,
+                                             expected))
+    return -3;
+
+  id parsed = objc_msgSend(formatter, sel_dateFromString, string);
+  if (parsed == nil ||
+      ((double (*)(id, SEL))objc_msgSend)(
+          parsed, sel_timeIntervalSinceReferenceDate) != 138915947.0)
+    return -4;
+
+  id plus_one = ((id(*)(id, SEL, int))objc_msgSend)(
+      NSTimeZone, sel_registerName("timeZoneForSecondsFromGMT:"), 3600);
+  objc_msgSend(formatter, sel_setTimeZone, plus_one);
+  string = objc_msgSend(formatter, sel_stringFromDate, date);
+  expected = CFStringCreateWithCString(NULL, "2005-05-27 20:45:47", 0x0600);
+  if (!((char (*)(id, SEL, id))objc_msgSend)(string, sel_isEqualToString,
+                                             expected))
+    return -5;
+
+  id garbage = CFStringCreateWithCString(NULL, "27/05/2005", 0x0600);
+  if (objc_msgSend(formatter, sel_dateFromString, garbage) != nil)
+    return -6;
+
+  return 0;
+}
+
+int test_getenv_setenv() {
+  if (getenv("TOUCHHLE_TEST_VAR") != NULL)
+    return -1;
+  if (getenv("HOME") == NULL)
+    return -2;
+
+  if (setenv("TOUCHHLE_TEST_VAR", "a", 0) != 0)
+    return -3;
+  char *value = getenv("TOUCHHLE_TEST_VAR");
+  if (value == NULL || strcmp(value, "a") != 0)
+    return -4;
+
+  // Without the overwrite flag, the existing value is kept.
+  if (setenv("TOUCHHLE_TEST_VAR", "b", 0) != 0)
+    return -5;
+  if (strcmp(getenv("TOUCHHLE_TEST_VAR"), "a") != 0)
+    return -6;
+  if (setenv("TOUCHHLE_TEST_VAR", "b", 1) != 0)
+    return -7;
+  if (strcmp(getenv("TOUCHHLE_TEST_VAR"), "b") != 0)
+    return -8;
+
+  // The same variables are visible through NSProcessInfo.
+  id process_info = objc_msgSend(objc_getClass("NSProcessInfo"),
+                                 sel_registerName("processInfo"));
+  id environment = objc_msgSend(process_info, sel_registerName("environment"));
+  id name = CFStringCreateWithCString(NULL, "TOUCHHLE_TEST_VAR", 0x0600);
+  id ns_value =
+      objc_msgSend(environment, sel_registerName("objectForKey:"), name);
+  if (ns_value == nil || !((char (*)(id, SEL, id))objc_msgSend)(
+                             ns_value, sel_registerName("isEqualToString:"),
+                             CFStringCreateWithCString(NULL, "b", 0x0600)))
+    return -9;
+
+  if (unsetenv("TOUCHHLE_TEST_VAR") != 0)
+    return -10;
+  if (getenv("TOUCHHLE_TEST_VAR") != NULL)
+    return -11;
+  // Unsetting a variable that doesn't exist is not an error.
+  if (unsetenv("TOUCHHLE_TEST_VAR") != 0)
+    return -12;
+
+  if (setenv("A=B", "c", 1) != -1 || errno != EINVAL)
+    return -13;
+  if (unsetenv("") != -1 || errno != EINVAL)
+    return -14;
+
+  return 0;
+}
+
+int faulting_thread_progress;
+void *faulting_thread_func(void *arg) {
+  faulting_thread_progress = 1;
+  // integration.rs runs this app with --terminate-faulting-threads, so this
+  // should only terminate this thread.
+  volatile int *null_ptr = arg;
+  *null_ptr = 1;
+  faulting_thread_progress = 2;
+  return (void *)1;
+}
+
+int test_faulting_thread() {
+  pthread_t thread;
+  faulting_thread_progress = 0;
+  if (pthread_create(&thread, NULL, faulting_thread_func, NULL) != 0)
+    return -1;
+  void *ret = (void *)0xdead;
+  if (pthread_join(thread, &ret) != 0)
+    return -2;
+  if (faulting_thread_progress != 1)
+    return -3;
+  if (ret != NULL)
+    return -4;
+  // The main thread keeps working afterwards.
+  return strcmp(getenv("HOME"), "") != 0 ? 0 : -5;
+}
+
+int returning_handler_calls;
+int returning_handler_code;
+void returning_handler(int sig, siginfo_t *info, void *context) {
+  returning_handler_calls++;
+  returning_handler_code = sig == SIGBUS ? info->si_code : -1;
+}
+
+int test_returning_signal_handler() {
+  struct sigaction act = {0};
+  act.sa_sigaction = returning_handler;
+  act.sa_flags = SA_SIGINFO;
+  if (sigaction(SIGBUS, &act, NULL) != 0)
+    return -1;
+
+  int res = 0;
+  returning_handler_calls = 0;
+  // Each thread's fault is delivered once. When the handler returns, the
+  // fault happens again and terminates the thread, like in
+  // test_faulting_thread.
+  for (int i = 1; i <= 2 && res == 0; i++) {
+    pthread_t thread;
+    faulting_thread_progress = 0;
+    returning_handler_code = 0;
+    void *ret = (void *)0xdead;
+    if (pthread_create(&thread, NULL, faulting_thread_func, (void *)0x10) !=
+            0 ||
+        pthread_join(thread, &ret) != 0)
+      res = -2;
+    else if (faulting_thread_progress != 1 || ret != NULL)
+      res = -3;
+    else if (returning_handler_calls != i)
+      res = -4;
+    else if (returning_handler_code != BUS_ADRERR)
+      res = -5;
+  }
+
+  if (signal(SIGBUS, SIG_DFL) != (void (*)(int))returning_handler)
+    return -6;
+  return res;
+}
+
+int svc_thread_progress;
+// Defined by the label in svc_thread_func.
+extern char svc_instruction[];
+void *svc_thread_func(void *arg) {
+  svc_thread_progress = 1;
+  // This is how iPhone OS system calls are made. touchHLE doesn't support
+  // them, and must not confuse this with the SVCs it uses for host functions.
+  // Like in test_faulting_thread, this should only terminate this thread.
+  __asm__ volatile("mov r12, #26\n" // SYS_ptrace
+                   "_svc_instruction:\n"
+                   "svc #0x80\n" ::
+                       : "r12");
+  svc_thread_progress = 2;
+  return (void *)1;
+}
+
+int test_unexpected_svc() {
+  pthread_t thread;
+  svc_thread_progress = 0;
+  if (pthread_create(&thread, NULL, svc_thread_func, NULL) != 0)
+    return -1;
+  void *ret = (void *)0xdead;
+  if (pthread_join(thread, &ret) != 0)
+    return -2;
+  if (svc_thread_progress != 1 || ret != NULL)
+    return -3;
+  // touchHLE's diagnostic should name this address, see integration.rs.
+  printf("Unexpected SVC is at %#x\n", (unsigned int)svc_instruction);
+  return 0;
+}
+
+char *stack_overflow_top;
+char *stack_overflow_lowest;
+int stack_overflow_recurse(int depth) {
+  volatile char frame[256];
+  frame[0] = depth;
+  stack_overflow_lowest = (char *)frame;
+  // Not a tail call, so the compiler can't turn this into a loop.
+  return stack_overflow_recurse(depth + 1) + frame[0];
+}
+void *stack_overflow_thread_func(void *arg) {
+  char top;
+  stack_overflow_top = &top;
+  return (void *)(long)stack_overflow_recurse(0);
+}
+
+int test_stack_overflow() {
+  // pthread_attr_t is opaque, this matches the size of Apple's.
+  long attr[10];
+  size_t stack_size = 64 * 1024;
+  if (pthread_attr_init((pthread_attr_t *)attr) != 0 ||
+      pthread_attr_setstacksize((pthread_attr_t *)attr, stack_size) != 0)
+    return -1;
+  pthread_t thread;
+  if (pthread_create(&thread, (pthread_attr_t *)attr,
+                     stack_overflow_thread_func, NULL) != 0)
+    return -2;
+  // The overflow hits the guard page and, because of
+  // --terminate-faulting-threads, only terminates that thread.
+  void *ret = (void *)0xdead;
+  if (pthread_join(thread, &ret) != 0 || ret != NULL)
+    return -3;
+  // The whole stack should have been usable, and no more.
+  size_t used = stack_overflow_top - stack_overflow_lowest;
+  if (used > stack_size || used < stack_size - 2048)
+    return -4;
+  return 0;
+}
+
+int test_memmove_memcmp() {
+  char buf[11] = "0123456789";
+
+  // Overlapping, destination after source
+  memmove(buf + 2, buf, 6);
+  if (memcmp(buf, "0101234589", 10) != 0)
+    return -1;
+  // Overlapping, destination before source
+  memmove(buf, buf + 4, 6);
+  if (memcmp(buf, "2345894589", 10) != 0)
+    return -2;
+  if (memcpy(buf, "abc", 3) != buf || memcmp(buf, "abc589", 6) != 0)
+    return -3;
+  if (memmove(buf, buf, 0) != buf)
+    return -4;
+
+  // Bytes are compared as unsigned chars.
+  if (memcmp("\x80", "\x01", 1) <= 0 || memcmp("\x01", "\x80", 1) >= 0)
+    return -5;
+  if (memcmp("abcd", "abce", 4) >= 0 || memcmp("abce", "abcd", 4) <= 0)
+    return -6;
+  if (memcmp("abcd", "abce", 3) != 0 || memcmp("a", "b", 0) != 0)
+    return -7;
+
+  memset(buf, 'x', 4);
+  if (memcmp(buf, "xxxx89", 6) != 0)
+    return -8;
+  bzero(buf + 1, 2);
+  if (memcmp(buf, "x\0\0x89", 6) != 0)
+    return -9;
+
+  return 0;
+}
+
+int test_str_functions() {
+  const char *haystack = "the quick brown fox";
+  if (strstr(haystack, "quick") != haystack + 4)
+    return -1;
+  if (strstr(haystack, "fox") != haystack + 16)
+    return -2;
+  if (strstr(haystack, "foxes") != NULL || strstr(haystack, "cat") != NULL)
+    return -3;
+  if (strstr(haystack, "") != haystack)
+    return -4;
+
+  // strncpy doesn't null-terminate when truncating.
+  char buf[8];
+  memset(buf, 'z', 8);
+  if (strncpy(buf, "abcdef", 3) != buf || memcmp(buf, "abczzzzz", 8) != 0)
+    return -5;
+  if (strncpy(buf, "ab", 4) != buf || memcmp(buf, "ab\0\0zzzz", 8) != 0)
+    return -6;
+
+  if (strcpy(buf, "ab") != buf || strcat(buf, "cd") != buf ||
+      strcmp(buf, "abcd") != 0 || strlen(buf) != 4)
+    return -7;
+  if (strnlen(buf, 2) != 2 || strnlen(buf, 8) != 4)
+    return -8;
+  if (strchr(buf, 'c') != buf + 2 || strchr(buf, '\0') != buf + 4 ||
+      strchr(buf, 'x') != NULL)
+    return -9;
+
+  if (strcmp("abc", "abd") >= 0 || strcmp("abd", "abc") <= 0)
+    return -10;
+  if (strcmp("ab", "abc") >= 0 || strcmp("\x80", "\x01") <= 0)
+    return -11;
+  if (strncmp("abcx", "abcy", 3) != 0 || strncmp("ab", "abc", 3) >= 0)
+    return -12;
+
+  char *dup = strdup(buf);
+  if (dup == buf || strcmp(dup, "abcd") != 0)
+    return -13;
+  free(dup);
+
+  return 0;
+}
+
+static id forward_target;
+
+id TestForwarder_methodSignatureForSelector(id self, SEL _cmd, SEL sel) {
+  const char *types = sel == sel_registerName("length") ? "I@:" : "@@:@";
+  return ((id(*)(id, SEL, const char *))objc_msgSend)(
+      objc_getClass("NSMethodSignature"),
+      sel_registerName("signatureWithObjCTypes:"), types);
+}
+
+void TestForwarder_forwardInvocation(id self, SEL _cmd, id invocation) {
+  objc_msgSend(invocation, sel_registerName("invokeWithTarget:"),
+               forward_target);
+}
+
+int test_message_forwarding() {
+  id cls =
+      objc_allocateClassPair(objc_getClass("NSObject"), "TestForwarder", 0);
+  if (cls == nil)
+    return -1;
+  class_addMethod(cls, sel_registerName("methodSignatureForSelector:"),
+                  (void *)&TestForwarder_methodSignatureForSelector, "@@::");
+  class_addMethod(cls, sel_registerName("forwardInvocation:"),
+                  (void *)&TestForwarder_forwardInvocation, "v@:@");
+  objc_registerClassPair(cls);
+  if (objc_getClass("TestForwarder") != cls)
+    return -2;
+
+  forward_target = CFStringCreateWithCString(NULL, "foo", 0x0600);
+  id forwarder = objc_msgSend(objc_msgSend(cls, sel_registerName("alloc")),
+                              sel_registerName("init"));
+  if (((unsigned int (*)(id, SEL))objc_msgSend)(
+          forwarder, sel_registerName("length")) != 3)
+    return -3;
+  id result =
+      objc_msgSend(forwarder, sel_registerName("stringByAppendingString:"),
+                   CFStringCreateWithCString(NULL, "bar", 0x0600));
+  id expected = CFStringCreateWithCString(NULL, "foobar", 0x0600);
+  if (!((char (*)(id, SEL, id))objc_msgSend)(
+          result, sel_registerName("isEqualToString:"), expected))
+    return -4;
+  return 0;
+}
+
+int test_CFAllocator() {
+  size_t before = mstats().bytes_used;
+  void *ptr = CFAllocatorAllocate(NULL, 100, 0);
+  if (ptr == NULL || malloc_size(ptr) < 100)
+    return -1;
+  if (mstats().bytes_used < before + 100)
+    return -2;
+  // Memory from a CF allocator is interchangeable with malloc() memory.
+  char *str = ptr;
+  strcpy(str, "foo");
+  void *ptr2 = realloc(ptr, 200);
+  if (ptr2 == NULL || strcmp(ptr2, "foo") != 0)
+    return -3;
+  CFAllocatorDeallocate(NULL, ptr2);
+  if (mstats().bytes_used != before)
+    return -4;
+
+  if (CFAllocatorAllocate(kCFAllocatorNull, 100, 0) != NULL)
+    return -5;
+  char buf[4] = "bar";
+  CFAllocatorDeallocate(kCFAllocatorNull, buf);
+  if (mstats().bytes_used != before || strcmp(buf, "bar") != 0)
+    return -6;
+  return 0;
+}
+
+int test_malloc_size() {
+  malloc_statistics_t before, after;
+  malloc_zone_statistics(NULL, &before);
+  // Allocations are rounded up to a multiple of 16 bytes.
+  char *ptr = malloc(100);
+  if (malloc_size(ptr) != 112 || malloc_good_size(100) != 112)
+    return -1;
+  if (malloc_size(ptr + 16) != 0 || malloc_size(&before) != 0)
+    return -2;
+  malloc_zone_statistics(NULL, &after);
+  if (after.blocks_in_use != before.blocks_in_use + 1 ||
+      after.size_in_use != before.size_in_use + 112 ||
+      after.max_size_in_use < after.size_in_use)
+    return -3;
+  free(ptr);
+  malloc_zone_statistics(NULL, &after);
+  if (after.blocks_in_use != before.blocks_in_use)
+    return -4;
+  return 0;
+}
+
+int mutation_count;
+void count_mutation(id collection) { mutation_count++; }
+
+// Does what the compiler generates for `for (id x in collection)`, copying
+// up to `max` objects to `out` and returning the number of objects. If
+// `mutate` isn't nil, it's added to the collection after the first object.
+// The buffer is small so that several calls are needed.
+int fast_enumerate(id collection, id *out, int max, id mutate) {
+  SEL sel = sel_registerName("countByEnumeratingWithState:objects:count:");
+  unsigned int (*count_by_enumerating)(id, SEL, NSFastEnumerationState *, id *,
+                                       unsigned int) = (void *)objc_msgSend;
+  NSFastEnumerationState state = {0};
+  id buf[2];
+  int count = 0;
+  unsigned int batch = count_by_enumerating(collection, sel, &state, buf, 2);
+  if (batch == 0)
+    return 0;
+  unsigned long mutations = *state.mutationsPtr;
+  do {
+    for (unsigned int i = 0; i < batch; i++) {
+      if (*state.mutationsPtr != mutations)
+        objc_enumerationMutation(collection);
+      if (count < max)
+        out[count] = state.itemsPtr[i];
+      count++;
+      if (mutate != nil && count == 1)
+        objc_msgSend(collection, sel_registerName("addObject:"), mutate);
+    }
+    batch = count_by_enumerating(collection, sel, &state, buf, 2);
+  } while (batch != 0);
+  return count;
+}
+
+int test_fast_enumeration() {
+  id a = (id)CFStringCreateWithCString(NULL, "a", 0x0600);
+  id b = (id)CFStringCreateWithCString(NULL, "b", 0x0600);
+  id c = (id)CFStringCreateWithCString(NULL, "c", 0x0600);
+  id out[4];
+
+  mutation_count = 0;
+  objc_setEnumerationMutationHandler(&count_mutation);
+
+  id array = objc_msgSend(objc_getClass("NSArray"),
+                          sel_registerName("arrayWithObjects:"), a, b, c, nil);
+  if (fast_enumerate(array, out, 4, nil) != 3)
+    return -1;
+  if (out[0] != a || out[1] != b || out[2] != c)
+    return -2;
+
+  id mut_array = objc_msgSend(objc_getClass("NSMutableArray"),
+                              sel_registerName("arrayWithArray:"), array);
+  if (fast_enumerate(mut_array, out, 4, nil) != 3 || out[2] != c)
+    return -3;
+
+  id dict = objc_msgSend(objc_getClass("NSDictionary"),
+                         sel_registerName("dictionaryWithObjectsAndKeys:"), a,
+                         b, c, a, nil);
+  if (fast_enumerate(dict, out, 4, nil) != 2)
+    return -4;
+  if (!((out[0] == b && out[1] == a) || (out[0] == a && out[1] == b)))
+    return -5;
+
+  id set = objc_msgSend(objc_getClass("NSSet"),
+                        sel_registerName("setWithObject:"), c);
+  if (fast_enumerate(set, out, 4, nil) != 1 || out[0] != c)
+    return -6;
+
+  // The older NSEnumerator API.
+  id enumerator = objc_msgSend(dict, sel_registerName("keyEnumerator"));
+  int count = 0;
+  while (objc_msgSend(enumerator, sel_registerName("nextObject")) != nil)
+    count++;
+  if (count != 2)
+    return -7;
+
+  objc_setEnumerationMutationHandler(NULL);
+  if (mutation_count != 0)
+    return -8;
+  return 0;
+}
+
+int test_fast_enumeration_mutation() {
+  id a = (id)CFStringCreateWithCString(NULL, "a", 0x0600);
+  id b = (id)CFStringCreateWithCString(NULL, "b", 0x0600);
+  id c = (id)CFStringCreateWithCString(NULL, "c", 0x0600);
+  id out[4];
+
+  mutation_count = 0;
+  objc_setEnumerationMutationHandler(&count_mutation);
+
+  id array = objc_msgSend(objc_getClass("NSMutableArray"),
+                          sel_registerName("arrayWithCapacity:"), 2);
+  objc_msgSend(array, sel_registerName("addObject:"), a);
+  objc_msgSend(array, sel_registerName("addObject:"), b);
+  fast_enumerate(array, out, 4, c);
+  if (mutation_count == 0)
+    return -1;
+
+  mutation_count = 0;
+  id set = objc_msgSend(objc_getClass("NSMutableSet"),
+                        sel_registerName("setWithObject:"), a);
+  fast_enumerate(set, out, 4, b);
+  if (mutation_count == 0)
+    return -2;
+
+  objc_setEnumerationMutationHandler(NULL);
+  return 0;
+}
+
+// This relies on the app being run with --locale=fr_FR, see integration.rs.
+int test_NSLocale() {
+  SEL sel_objectForKey = sel_registerName("objectForKey:");
+  SEL sel_UTF8String = sel_registerName("UTF8String");
+  id locale_class = objc_getClass("NSLocale");
+
+  id locale = objc_msgSend(locale_class, sel_registerName("currentLocale"));
+  id language = objc_msgSend(locale, sel_objectForKey, NSLocaleLanguageCode);
+  if (strcmp((const char *)objc_msgSend(language, sel_UTF8String), "fr"))
+    return -1;
+  id country = objc_msgSend(locale, sel_objectForKey, NSLocaleCountryCode);
+  if (strcmp((const char *)objc_msgSend(country, sel_UTF8String), "FR"))
+    return -2;
+  id separator =
+      objc_msgSend(locale, sel_objectForKey, NSLocaleDecimalSeparator);
+  if (strcmp((const char *)objc_msgSend(separator, sel_UTF8String), ","))
+    return -3;
+  id identifier = objc_msgSend(locale, sel_registerName("localeIdentifier"));
+  if (strcmp((const char *)objc_msgSend(identifier, sel_UTF8String), "fr_FR"))
+    return -4;
+
+  id languages =
+      objc_msgSend(locale_class, sel_registerName("preferredLanguages"));
+  id first = ((id(*)(id, SEL, unsigned int))objc_msgSend)(
+      languages, sel_registerName("objectAtIndex:"), 0);
+  if (strcmp((const char *)objc_msgSend(first, sel_UTF8String), "fr"))
+    return -5;
+
+  id system = objc_msgSend(locale_class, sel_registerName("systemLocale"));
+  if (objc_msgSend(system, sel_objectForKey, NSLocaleLanguageCode) != nil)
+    return -6;
+  separator = objc_msgSend(system, sel_objectForKey, NSLocaleDecimalSeparator);
+  if (strcmp((const char *)objc_msgSend(separator, sel_UTF8String), "."))
+    return -7;
+
+  return 0;
+}
+
+// Uses tests/TestApp.app/English.lproj/Localizable.strings
+int test_NSBundle_localizedString() {
+  SEL sel_localized = sel_registerName("localizedStringForKey:value:table:");
+  SEL sel_UTF8String = sel_registerName("UTF8String");
+  id bundle =
+      objc_msgSend(objc_getClass("NSBundle"), sel_registerName("mainBundle"));
+  id empty = (id)CFStringCreateWithCString(NULL, "", 0x0600);
+
+  // This is what NSLocalizedString() expands to.
+  id key = (id)CFStringCreateWithCString(NULL, "Greeting", 0x0600);
+  id str = objc_msgSend(bundle, sel_localized, key, empty, nil);
+  if (strcmp((const char *)objc_msgSend(str, sel_UTF8String),
+             "Hello from Localizable.strings"))
+    return -1;
+
+  key = (id)CFStringCreateWithCString(NULL, "Escaped", 0x0600);
+  str = objc_msgSend(bundle, sel_localized, key, empty, nil);
+  if (strcmp((const char *)objc_msgSend(str, sel_UTF8String),
+             "Tab:\t Quote:\" Unicode:\xC3\xA9"))
+    return -2;
+
+  // A missing key falls back to the value, or to the key itself if there's no
+  // value.
+  key = (id)CFStringCreateWithCString(NULL, "Missing", 0x0600);
+  id value = (id)CFStringCreateWithCString(NULL, "Fallback", 0x0600);
+  if (objc_msgSend(bundle, sel_localized, key, value, nil) != value)
+    return -3;
+  if (objc_msgSend(bundle, sel_localized, key, empty, nil) != key)
+    return -4;
+
+  // So does a missing table.
+  id table = (id)CFStringCreateWithCString(NULL, "NoSuchTable", 0x0600);
+  if (objc_msgSend(bundle, sel_localized, key, value, table) != value)
+    return -5;
+
+  return 0;
+}
+
+int test_CFMutableDictionary_bridged() {
+  CFMutableDictionaryRef dict =
+      CFDictionaryCreateMutable(NULL, 0, &kCFTypeDictionaryKeyCallBacks,
+                                &kCFTypeDictionaryValueCallBacks);
+  CFStringRef key = CFStringCreateWithCString(NULL, "key", 0x0600);
+  CFStringRef value = CFStringCreateWithCString(NULL, "value", 0x0600);
+  CFDictionarySetValue(dict, key, value);
+  // The dictionary should have retained both.
+  CFRelease(key);
+  CFRelease(value);
+
+  // Read it back as an NSDictionary, with a different but equal key.
+  id ns_dict = (id)dict;
+  id dict_class = objc_getClass("NSDictionary");
+  if (!objc_msgSend(ns_dict, sel_registerName("isKindOfClass:"), dict_class))
+    return -1;
+  if ((size_t)objc_msgSend(ns_dict, sel_registerName("count")) != 1)
+    return -2;
+  CFStringRef key2 = CFStringCreateWithCString(NULL, "key", 0x0600);
+  id res = objc_msgSend(ns_dict, sel_registerName("objectForKey:"), key2);
+  CFStringRef value2 = CFStringCreateWithCString(NULL, "value", 0x0600);
+  if (res == nil || !CFEqual(res, value2))
+    return -3;
+  CFRelease(value2);
+  if (!CFDictionaryContainsKey(dict, key2))
+    return -4;
+  CFRelease(key2);
+  CFRelease(dict);
+
+  const void *keys[] = {CFStringCreateWithCString(NULL, "a", 0x0600)};
+  const void *values[] = {CFStringCreateWithCString(NULL, "b", 0x0600)};
+  CFDictionaryRef dict2 =
+      CFDictionaryCreate(NULL, keys, values, 1, &kCFTypeDictionaryKeyCallBacks,
+                         &kCFTypeDictionaryValueCallBacks);
+  res = objc_msgSend((id)dict2, sel_registerName("objectForKey:"), keys[0]);
+  if (res != values[0])
+    return -5;
+  CFRelease(keys[0]);
+  CFRelease(values[0]);
+  CFRelease(dict2);
+  return 0;
+}
+
+int test_CFArray_bridged() {
+  const void *values[] = {
+      CFStringCreateWithCString(NULL, "a", 0x0600),
+      CFStringCreateWithCString(NULL, "b", 0x0600),
+  };
+  CFArrayRef array = CFArrayCreate(NULL, values, 2, &kCFTypeArrayCallBacks);
+  if ((size_t)objc_msgSend((id)array, sel_registerName("count")) != 2)
+    return -1;
+  id res = objc_msgSend((id)array, sel_registerName("objectAtIndex:"), 1);
+  if (res != values[1])
+    return -2;
+  CFRelease(array);
+
+  CFMutableArrayRef mut_array = CFArrayCreateMutable(NULL, 0, NULL);
+  CFArrayAppendValue(mut_array, "c");
+  CFArrayInsertValueAtIndex(mut_array, 0, "a");
+  CFArraySetValueAtIndex(mut_array, 2, "d");
+  CFArraySetValueAtIndex(mut_array, 1, "b");
+  if (CFArrayGetCount(mut_array) != 3 ||
+      strcmp(CFArrayGetValueAtIndex(mut_array, 0), "a") ||
+      strcmp(CFArrayGetValueAtIndex(mut_array, 1), "b") ||
+      strcmp(CFArrayGetValueAtIndex(mut_array, 2), "d"))
+    return -3;
+  CFArrayRemoveAllValues(mut_array);
+  if (CFArrayGetCount(mut_array) != 0)
+    return -4;
+  CFRelease(mut_array);
+
+  CFRelease(values[0]);
+  CFRelease(values[1]);
+  return 0;
+}
+
+int test_pthread_name() {
+  SEL sel_name = sel_registerName("name");
+  SEL sel_UTF8String = sel_registerName("UTF8String");
+  id thread = objc_msgSend(objc_getClass("NSThread"),
+                           sel_registerName("currentThread"));
+
+  if (pthread_setname_np("touchHLE worker") != 0)
+    return -1;
+  id name = objc_msgSend(thread, sel_name);
+  if (name == nil || strcmp((const char *)objc_msgSend(name, sel_UTF8String),
+                            "touchHLE worker"))
+    return -2;
+
+  // The other way around, and with truncation.
+  objc_msgSend(thread, sel_registerName("setName:"),
+               CFStringCreateWithCString(NULL, "renamed", 0x0600));
+  char buf[4];
+  if (pthread_getname_np(pthread_self(), buf, sizeof(buf)) != 0 ||
+      strcmp(buf, "ren"))
+    return -3;
+
+  pthread_setname_np("");
+  if (objc_msgSend(thread, sel_name) != nil)
+    return -4;
+  return 0;
+}
+
+int test_NSString_case_and_trimming() {
+  SEL sel_UTF8String = sel_registerName("UTF8String");
+  id str = (id)CFStringCreateWithCString(NULL, "\t Hello wORLD \n", 0x0600);
+
+  id lower = objc_msgSend(str, sel_registerName("lowercaseString"));
+  if (strcmp((const char *)objc_msgSend(lower, sel_UTF8String),
+             "\t hello world \n"))
+    return -1;
+  id upper = objc_msgSend(str, sel_registerName("uppercaseString"));
+  if (strcmp((const char *)objc_msgSend(upper, sel_UTF8String),
+             "\t HELLO WORLD \n"))
+    return -2;
+  id capitalized = objc_msgSend(str, sel_registerName("capitalizedString"));
+  if (strcmp((const char *)objc_msgSend(capitalized, sel_UTF8String),
+             "\t Hello World \n"))
+    return -3;
+
+  SEL sel_trim = sel_registerName("stringByTrimmingCharactersInSet:");
+  id NSCharacterSet = objc_getClass("NSCharacterSet");
+  id whitespace =
+      objc_msgSend(NSCharacterSet, sel_registerName("whitespaceCharacterSet"));
+  id trimmed = objc_msgSend(str, sel_trim, whitespace);
+  if (strcmp((const char *)objc_msgSend(trimmed, sel_UTF8String),
+             "Hello wORLD \n"))
+    return -4;
+  id whitespace_and_newlines = objc_msgSend(
+      NSCharacterSet, sel_registerName("whitespaceAndNewlineCharacterSet"));
+  trimmed = objc_msgSend(str, sel_trim, whitespace_and_newlines);
+  if (strcmp((const char *)objc_msgSend(trimmed, sel_UTF8String),
+             "Hello wORLD"))
+    return -5;
+  return 0;
+}
+
+int test_NSString_compare() {
+  SEL sel_compare_options = sel_registerName("compare:options:");
+  id abc = (id)CFStringCreateWithCString(NULL, "abc", 0x0600);
+  id ABD = (id)CFStringCreateWithCString(NULL, "ABD", 0x0600);
+  id file2 = (id)CFStringCreateWithCString(NULL, "file2", 0x0600);
+  id File10 = (id)CFStringCreateWithCString(NULL, "File10", 0x0600);
+
+  // NSOrderedAscending is -1, NSOrderedDescending is 1.
+  long res =
+      (long)objc_msgSend(abc, sel_registerName("caseInsensitiveCompare:"), ABD);
+  if (res != -1)
+    return -1;
+  // Literal comparison puts uppercase first.
+  res = (long)objc_msgSend(file2, sel_compare_options, File10, 0);
+  if (res != 1)
+    return -2;
+  // NSCaseInsensitiveSearch (1) alone compares digits character by
+  // character.
+  res = (long)objc_msgSend(file2, sel_compare_options, File10, 1);
+  if (res != 1)
+    return -3;
+  // NSCaseInsensitiveSearch | NSNumericSearch (64)
+  res = (long)objc_msgSend(file2, sel_compare_options, File10, 1 | 64);
+  if (res != -1)
+    return -4;
+
+  id array =
+      objc_msgSend(objc_getClass("NSMutableArray"), sel_registerName("array"));
+  SEL sel_addObject = sel_registerName("addObject:");
+  objc_msgSend(array, sel_addObject, ABD);
+  objc_msgSend(array, sel_addObject, abc);
+  objc_msgSend(array, sel_registerName("sortUsingSelector:"),
+               sel_registerName("localizedCompare:"));
+  SEL sel_objectAtIndex = sel_registerName("objectAtIndex:");
+  if (objc_msgSend(array, sel_objectAtIndex, 0) != abc ||
+      objc_msgSend(array, sel_objectAtIndex, 1) != ABD)
+    return -5;
+  return 0;
+}
+
+int test_integer_division() {
+  // Division by a non-constant uses the helpers from the bundled libgcc.
+  volatile int a = -7, b = 2;
+  volatile unsigned int ua = 7, ub = 2;
+  if (a / b != -3 || a % b != -1 || ua / ub != 3 || ua % ub != 1)
+    return -1;
+
+  if (__aeabi_idiv(-7, 2) != -3 || __aeabi_uidiv(0xFFFFFFFF, 2) != 0x7FFFFFFF)
+    return -2;
+  // Division by zero gives 0 rather than trapping.
+  if (__aeabi_idiv(5, 0) != 0 || __aeabi_uidiv(5, 0) != 0)
+    return -3;
+  // INT_MIN / -1 overflows and wraps around.
+  int int_min = -2147483647 - 1;
+  if (__aeabi_idiv(int_min, -1) != int_min)
+    return -4;
+
+  unsigned long long res = __aeabi_idivmod(-7, 2);
+  if ((int)res != -3 || (int)(res >> 32) != -1)
+    return -5;
+  res = __aeabi_idivmod(int_min, -1);
+  if ((int)res != int_min || (int)(res >> 32) != 0)
+    return -6;
+  res = __aeabi_idivmod(5, 0);
+  if ((int)res != 0 || (int)(res >> 32) != 0)
+    return -7;
+  res = __aeabi_uidivmod(7, 2);
+  if ((unsigned int)res != 3 || (unsigned int)(res >> 32) != 1)
+    return -8;
+  res = __aeabi_uidivmod(7, 0);
+  if ((unsigned int)res != 0 || (unsigned int)(res >> 32) != 0)
+    return -9;
+  return 0;
+}
+
+int test_soft_float() {
+  volatile double zero = 0.0;
+  double nan = zero / zero;
+
+  if (__aeabi_dadd(1.5, 2.25) != 3.75 || __aeabi_drsub(1.0, 3.0) != 2.0 ||
+      __aeabi_ddiv(1.0, 4.0) != 0.25 || __aeabi_fmul(1.5f, -2.0f) != -3.0f)
+    return -1;
+  double res = __aeabi_dadd(nan, 1.0);
+  if (res == res)
+    return -2;
+  // Comparisons with NaN are false, except for the "unordered" one.
+  if (!__aeabi_dcmplt(1.0, 2.0) || __aeabi_dcmpeq(nan, nan) ||
+      __aeabi_dcmplt(nan, 1.0) || !__aeabi_dcmpun(nan, 1.0) ||
+      __aeabi_dcmpun(1.0, 2.0) || !__aeabi_fcmpge(2.0f, 2.0f))
+    return -3;
+  // Conversions to integers truncate towards zero.
+  if (__aeabi_d2iz(-3.9) != -3 || __aeabi_f2iz(3.9f) != 3 ||
+      __aeabi_d2uiz(3.9) != 3 || __aeabi_d2lz(-1e12) != -1000000000000LL)
+    return -4;
+  // Out-of-range values saturate and NaN becomes 0.
+  if (__aeabi_d2iz(1e10) != 2147483647 ||
+      __aeabi_d2iz(-1e10) != -2147483647 - 1 || __aeabi_d2uiz(-1.0) != 0 ||
+      __aeabi_d2iz(nan) != 0 || __aeabi_d2lz(nan) != 0)
+    return -5;
+  if (__aeabi_i2d(-7) != -7.0 || __aeabi_ui2f(4294967295u) != 4294967296.0f ||
+      __aeabi_l2d(1LL << 53) != 9007199254740992.0 ||
+      __aeabi_f2d(0.5f) != 0.5 || __aeabi_d2f(0.1) != 0.1f)
+    return -6;
+  return 0;
+}
+
+int test_NSString_paths() {
+  SEL sel_UTF8String = sel_registerName("UTF8String");
+  id tmp = (id)CFStringCreateWithCString(NULL, "/tmp//", 0x0600);
+  id component = (id)CFStringCreateWithCString(NULL, "a/b.old/", 0x0600);
+  id tiff = (id)CFStringCreateWithCString(NULL, "tiff", 0x0600);
+
+  id path = objc_msgSend(
+      tmp, sel_registerName("stringByAppendingPathComponent:"), component);
+  if (strcmp((const char *)objc_msgSend(path, sel_UTF8String), "/tmp/a/b.old"))
+    return -1;
+  id ext = objc_msgSend(path, sel_registerName("pathExtension"));
+  if (strcmp((const char *)objc_msgSend(ext, sel_UTF8String), "old"))
+    return -2;
+  path = objc_msgSend(path, sel_registerName("stringByAppendingPathExtension:"),
+                      tiff);
+  if (strcmp((const char *)objc_msgSend(path, sel_UTF8String),
+             "/tmp/a/b.old.tiff"))
+    return -3;
+  id last = objc_msgSend(path, sel_registerName("lastPathComponent"));
+  if (strcmp((const char *)objc_msgSend(last, sel_UTF8String), "b.old.tiff"))
+    return -4;
+
+  id messy = (id)CFStringCreateWithCString(NULL, "/tmp/./a//../b/", 0x0600);
+  path = objc_msgSend(messy, sel_registerName("stringByStandardizingPath"));
+  if (strcmp((const char *)objc_msgSend(path, sel_UTF8String), "/tmp/b"))
+    return -5;
+  return 0;
+}
+
+int test_ivar_lookup() {
+  id base = objc_allocateClassPair(objc_getClass("NSObject"), "IvarBase", 0);
+  if (base == nil)
+    return -1;
+  if (!class_addIvar(base, "baseValue", sizeof(int), 2, "i"))
+    return -2;
+  // Adding the same ivar twice should fail.
+  if (class_addIvar(base, "baseValue", sizeof(int), 2, "i"))
+    return -3;
+  objc_registerClassPair(base);
+  // Ivars can't be added once the class is registered.
+  if (class_addIvar(base, "lateValue", sizeof(int), 2, "i"))
+    return -4;
+
+  id derived = objc_allocateClassPair(base, "IvarDerived", 0);
+  if (derived == nil)
+    return -5;
+  if (!class_addIvar(derived, "derivedValue", sizeof(int), 2, "i"))
+    return -6;
+  objc_registerClassPair(derived);
+
+  id obj = objc_msgSend(objc_msgSend(derived, sel_registerName("alloc")),
+                        sel_registerName("init"));
+  // The isa pointer comes first, then the base class's ivars, then the
+  // derived class's ivars.
+  ((int *)obj)[1] = 42;
+  ((int *)obj)[2] = 7;
+
+  void *value = NULL;
+  if (object_getInstanceVariable(obj, "baseValue", &value) == NULL)
+    return -7;
+  if ((int)value != 42)
+    return -8;
+  if (object_getInstanceVariable(obj, "derivedValue", &value) == NULL)
+    return -9;
+  if ((int)value != 7)
+    return -10;
+  if (object_getInstanceVariable(obj, "missingValue", &value) != NULL)
+    return -11;
+  return 0;
+}
+
+int test_NSCache() {
+  SEL sel_set = sel_registerName("setObject:forKey:");
+  SEL sel_set_cost = sel_registerName("setObject:forKey:cost:");
+  SEL sel_get = sel_registerName("objectForKey:");
+  SEL sel_retain_count = sel_registerName("retainCount");
+  void (*set_cost)(id, SEL, id, id, unsigned int) = (void *)objc_msgSend;
+  void (*set_limit)(id, SEL, unsigned int) = (void *)objc_msgSend;
+  unsigned int (*get_count)(id, SEL) = (void *)objc_msgSend;
+
+  id a = (id)CFStringCreateWithCString(NULL, "a", 0x0600);
+  id b = (id)CFStringCreateWithCString(NULL, "b", 0x0600);
+  id c = (id)CFStringCreateWithCString(NULL, "c", 0x0600);
+  id value = (id)CFStringCreateWithCString(NULL, "value", 0x0600);
+  unsigned int value_count = get_count(value, sel_retain_count);
+
+  // Count limit: the least recently used object is evicted.
+  id cache = objc_msgSend(
+      objc_msgSend(objc_getClass("NSCache"), sel_registerName("alloc")),
+      sel_registerName("init"));
+  set_limit(cache, sel_registerName("setCountLimit:"), 2);
+  objc_msgSend(cache, sel_set, value, a);
+  if (get_count(value, sel_retain_count) != value_count + 1)
+    return -1;
+  objc_msgSend(cache, sel_set, value, b);
+  if (objc_msgSend(cache, sel_get, a) != value)
+    return -2;
+  objc_msgSend(cache, sel_set, value, c);
+  if (objc_msgSend(cache, sel_get, b) != nil)
+    return -3;
+  if (objc_msgSend(cache, sel_get, a) != value ||
+      objc_msgSend(cache, sel_get, c) != value)
+    return -4;
+  if (get_count(value, sel_retain_count) != value_count + 2)
+    return -5;
+  objc_msgSend(cache, sel_registerName("removeObjectForKey:"), a);
+  if (objc_msgSend(cache, sel_get, a) != nil)
+    return -6;
+  objc_msgSend(cache, sel_registerName("removeAllObjects"));
+  if (objc_msgSend(cache, sel_get, c) != nil)
+    return -7;
+  if (get_count(value, sel_retain_count) != value_count)
+    return -8;
+
+  // Cost limit: objects are evicted until the total cost fits.
+  set_limit(cache, sel_registerName("setCountLimit:"), 0);
+  set_limit(cache, sel_registerName("setTotalCostLimit:"), 10);
+  set_cost(cache, sel_set_cost, value, a, 4);
+  set_cost(cache, sel_set_cost, value, b, 4);
+  if (objc_msgSend(cache, sel_get, a) != value)
+    return -9;
+  set_cost(cache, sel_set_cost, value, c, 4);
+  if (objc_msgSend(cache, sel_get, b) != nil)
+    return -10;
+  if (objc_msgSend(cache, sel_get, a) != value ||
+      objc_msgSend(cache, sel_get, c) != value)
+    return -11;
+  // An object costing more than the limit evicts everything.
+  set_cost(cache, sel_set_cost, value, b, 11);
+  if (objc_msgSend(cache, sel_get, a) != nil ||
+      objc_msgSend(cache, sel_get, c) != nil)
+    return -12;
+
+  objc_msgSend(cache, sel_registerName("release"));
+  if (get_count(value, sel_retain_count) != value_count)
+    return -13;
+  return 0;
+}
+
+int test_clock_gettime() {
+  struct timespec a, b;
+  if (clock_gettime(CLOCK_MONOTONIC, &a) != 0)
+    return -1;
+  if (clock_gettime(CLOCK_MONOTONIC, &b) != 0)
+    return -2;
+  if (b.tv_sec < a.tv_sec || (b.tv_sec == a.tv_sec && b.tv_nsec < a.tv_nsec))
+    return -3;
+
+  if (clock_gettime(CLOCK_REALTIME, &a) != 0)
+    return -4;
+  if (a.tv_nsec < 0 || a.tv_nsec >= 1000000000)
+    return -5;
+  usleep(10000);
+  if (clock_gettime(CLOCK_REALTIME, &b) != 0)
+    return -6;
+  if (b.tv_sec < a.tv_sec || (b.tv_sec == a.tv_sec && b.tv_nsec <= a.tv_nsec))
+    return -7;
+
+  if (clock_gettime(12345, &a) != -1 || errno != EINVAL)
+    return -8;
+  return 0;
+}
+
+// A reference pattern for loading a file in the background: an NSThread reads
+// the file and posts a notification once it's done. Notifications are
+// delivered on the posting thread, so the observers run in the background too.
+static volatile int loader_bytes_read;
+static volatile int loader_finished;
+static volatile int loader_thread_exited;
+static volatile int loader_became_multithreaded;
+
+void FileLoader_load(id self, SEL _cmd, id path) {
+  // Secondary threads have to set up their own autorelease pool.
+  id pool =
+      objc_msgSend(objc_getClass("NSAutoreleasePool"), sel_registerName("new"));
+  id data = objc_msgSend(objc_getClass("NSData"),
+                         sel_registerName("dataWithContentsOfFile:"), path);
+  if (data != nil)
+    loader_bytes_read =
+        ((int (*)(id, SEL))objc_msgSend)(data, sel_registerName("length"));
+  id center = objc_msgSend(objc_getClass("NSNotificationCenter"),
+                           sel_registerName("defaultCenter"));
+  objc_msgSend(center, sel_registerName("postNotificationName:object:"),
+               CFStringCreateWithCString(NULL, "FileLoaderDidFinish", 0x0600),
+               self);
+  objc_msgSend(pool, sel_registerName("release"));
+}
+
+void FileLoader_didFinish(id self, SEL _cmd, id notification) {
+  loader_finished = 1;
+}
+void FileLoader_threadWillExit(id self, SEL _cmd, id notification) {
+  // The exit notification must come after the file has been loaded.
+  loader_thread_exited = loader_finished ? 1 : -1;
+}
+void FileLoader_willBecomeMultiThreaded(id self, SEL _cmd, id notification) {
+  loader_became_multithreaded = 1;
+}
+
+int test_NSThread_background_loading() {
+  id cls = objc_allocateClassPair(objc_getClass("NSObject"), "FileLoader", 0);
+  if (cls == nil)
+    return -1;
+  class_addMethod(cls, sel_registerName("load:"), (void *)&FileLoader_load,
+                  "v@:@");
+  class_addMethod(cls, sel_registerName("didFinish:"),
+                  (void *)&FileLoader_didFinish, "v@:@");
+  class_addMethod(cls, sel_registerName("threadWillExit:"),
+                  (void *)&FileLoader_threadWillExit, "v@:@");
+  class_addMethod(cls, sel_registerName("willBecomeMultiThreaded:"),
+                  (void *)&FileLoader_willBecomeMultiThreaded, "v@:@");
+  objc_registerClassPair(cls);
+
+  id loader = objc_msgSend(objc_msgSend(cls, sel_registerName("alloc")),
+                           sel_registerName("init"));
+  id center = objc_msgSend(objc_getClass("NSNotificationCenter"),
+                           sel_registerName("defaultCenter"));
+  SEL add_observer = sel_registerName("addObserver:selector:name:object:");
+  objc_msgSend(center, add_observer, loader, sel_registerName("didFinish:"),
+               CFStringCreateWithCString(NULL, "FileLoaderDidFinish", 0x0600),
+               nil);
+  objc_msgSend(center, add_observer, loader,
+               sel_registerName("threadWillExit:"),
+               NSThreadWillExitNotification, nil);
+  objc_msgSend(center, add_observer, loader,
+               sel_registerName("willBecomeMultiThreaded:"),
+               NSWillBecomeMultiThreadedNotification, nil);
+
+  SEL is_multithreaded = sel_registerName("isMultiThreaded");
+  char (*get_bool)(id, SEL) = (void *)objc_msgSend;
+  char was_multithreaded =
+      get_bool(objc_getClass("NSThread"), is_multithreaded);
+
+  id path = (id)CFStringCreateWithCString(
+      NULL,
+      "/var/mobile/Applications/00000000-0000-0000-0000-000000000000/"
+      "TestApp.app/Info.plist",
+      0x0600);
+  objc_msgSend(objc_getClass("NSThread"),
+               sel_registerName("detachNewThreadSelector:toTarget:withObject:"),
+               sel_registerName("load:"), loader, path);
+
+  // Wait for up to a second.
+  for (int i = 0; i < 100 && !loader_thread_exited; i++)
+    usleep(10000);
+
+  objc_msgSend(center, sel_registerName("removeObserver:"), loader);
+
+  if (!get_bool(objc_getClass("NSThread"), is_multithreaded))
+    return -2;
+  // The notification is only sent for the first thread.
+  if (loader_became_multithreaded == was_multithreaded)
+    return -3;
+  if (!loader_finished)
+    return -4;
+  if (loader_bytes_read <= 0)
+    return -5;
+  if (loader_thread_exited != 1)
+    return -6;
+  return 0;
+}
+
+int test_objc_properties() {
+  id base = objc_allocateClassPair(objc_getClass("NSObject"), "PropBase", 0);
+  if (base == nil)
+    return -1;
+  objc_property_attribute_t attributes[] = {
+      {"T", "@\"NSString\""}, {"&", ""}, {"N", ""}, {"V", "_name"}};
+  if (!class_addProperty(base, "name", attributes, 4))
+    return -2;
+  // Adding the same property twice should fail.
+  if (class_addProperty(base, "name", attributes, 4))
+    return -3;
+  objc_registerClassPair(base);
+  id derived = objc_allocateClassPair(base, "PropDerived", 0);
+  objc_registerClassPair(derived);
+
+  objc_property_t property = class_getProperty(base, "name");
+  if (property == NULL)
+    return -4;
+  if (strcmp(property_getName(property), "name"))
+    return -5;
+  if (strcmp(property_getAttributes(property), "T@\"NSString\",&,N,V_name"))
+    return -6;
+  char *value = property_copyAttributeValue(property, "V");
+  if (value == NULL || strcmp(value, "_name"))
+    return -7;
+  free(value);
+  if (property_copyAttributeValue(property, "R") != NULL)
+    return -8;
+
+  // Superclasses are searched by class_getProperty(), but not by
+  // class_copyPropertyList().
+  if (class_getProperty(derived, "name") != property)
+    return -9;
+  if (class_getProperty(derived, "missing") != NULL)
+    return -10;
+  unsigned int count = 1;
+  objc_property_t *list = class_copyPropertyList(derived, &count);
+  if (list != NULL || count != 0)
+    return -11;
+  list = class_copyPropertyList(base, &count);
+  if (list == NULL || count != 1 || list[0] != property || list[1] != NULL)
+    return -12;
+  free(list);
+  return 0;
+}
+
+int test_memory_barriers() {
+  volatile int shared = 0;
+  shared = 1;
+  OSMemoryBarrier();
+  if (shared != 1)
+    return -1;
+  // The ARMv6 equivalent of DMB, as used by OSMemoryBarrier() itself.
+  __asm__ volatile("mcr p15, 0, %0, c7, c10, 5" : : "r"(0) : "memory");
+  shared = 2;
+  if (shared != 2)
+    return -2;
+  return 0;
+}
+
+// The main thread's run loop needs a window, so the display link test runs
+// on a secondary thread's run loop instead.
+static volatile int link_fire_count;
+static volatile int link_bad_timestamps;
+static volatile int link_done;
+static double link_last_timestamp;
+
+void LinkTarget_tick(id self, SEL _cmd, id link) {
+  double (*get_double)(id, SEL) = (void *)objc_msgSend;
+  double timestamp = get_double(link, sel_registerName("timestamp"));
+  double duration = get_double(link, sel_registerName("duration"));
+  // Each firing must be on a later frame than the one before.
+  if (duration <= 0 || (link_fire_count > 0 &&
+                        timestamp - link_last_timestamp < duration * 0.999))
+    link_bad_timestamps++;
+  link_last_timestamp = timestamp;
+  link_fire_count++;
+}
+
+void LinkTarget_run(id self, SEL _cmd, id unused) {
+  id pool =
+      objc_msgSend(objc_getClass("NSAutoreleasePool"), sel_registerName("new"));
+  id link = objc_msgSend(objc_getClass("CADisplayLink"),
+                         sel_registerName("displayLinkWithTarget:selector:"),
+                         self, sel_registerName("tick:"));
+  id run_loop = objc_msgSend(objc_getClass("NSRunLoop"),
+                             sel_registerName("currentRunLoop"));
+  objc_msgSend(link, sel_registerName("addToRunLoop:forMode:"), run_loop,
+               NSDefaultRunLoopMode);
+  SEL sel_since_now = sel_registerName("dateWithTimeIntervalSinceNow:");
+  id date = ((id(*)(id, SEL, double))objc_msgSend)(objc_getClass("NSDate"),
+                                                   sel_since_now, 0.25);
+  objc_msgSend(run_loop, sel_registerName("runUntilDate:"), date);
+  objc_msgSend(link, sel_registerName("invalidate"));
+  objc_msgSend(pool, sel_registerName("release"));
+  link_done = 1;
+}
+
+int test_CADisplayLink() {
+  id cls = objc_allocateClassPair(objc_getClass("NSObject"), "LinkTarget", 0);
+  if (cls == nil)
+    return -1;
+  class_addMethod(cls, sel_registerName("tick:"), (void *)&LinkTarget_tick,
+                  "v@:@");
+  class_addMethod(cls, sel_registerName("run:"), (void *)&LinkTarget_run,
+                  "v@:@");
+  objc_registerClassPair(cls);
+
+  id target = objc_msgSend(objc_msgSend(cls, sel_registerName("alloc")),
+                           sel_registerName("init"));
+  objc_msgSend(objc_getClass("NSThread"),
+               sel_registerName("detachNewThreadSelector:toTarget:withObject:"),
+               sel_registerName("run:"), target, nil);
+
+  // Wait for up to two seconds.
+  for (int i = 0; i < 200 && !link_done; i++)
+    usleep(10000);
+
+  if (!link_done)
+    return -2;
+  // 0.25s is 15 frames at 60Hz. Allow for some lag, but the display link
+  // can't fire more than once per frame.
+  if (link_fire_count < 2 || link_fire_count > 17)
+    return -3;
+  if (link_bad_timestamps != 0)
+    return -4;
+  return 0;
+}
+
+int test_NSString_numeric_values() {
+  id str = (id)CFStringCreateWithCString(NULL, "  -12.5e1xyz", 0x0600);
+  int (*get_int)(id, SEL) = (void *)objc_msgSend;
+  double (*get_double)(id, SEL) = (void *)objc_msgSend;
+  float (*get_float)(id, SEL) = (void *)objc_msgSend;
+  char (*get_bool)(id, SEL) = (void *)objc_msgSend;
+  if (get_int(str, sel_registerName("intValue")) != -12)
+    return -1;
+  if (get_int(str, sel_registerName("integerValue")) != -12)
+    return -2;
+  if (get_double(str, sel_registerName("doubleValue")) != -125.0)
+    return -3;
+  if (get_float(str, sel_registerName("floatValue")) != -125.0f)
+    return -4;
+  if (!get_bool(str, sel_registerName("boolValue")))
+    return -5;
+  str = (id)CFStringCreateWithCString(NULL, "NO", 0x0600);
+  if (get_bool(str, sel_registerName("boolValue")) ||
+      get_int(str, sel_registerName("intValue")) != 0)
+    return -6;
+  return 0;
+}
+
+static dispatch_once_t once_token;
+static volatile int once_count;
+
+void *once_thread_func(void *unused) {
+  dispatch_once(&once_token, ^{
+    // Sleeping gives the other thread a chance to call dispatch_once() while
+    // this block is still running.
+    usleep(20000);
+    once_count++;
+  });
+  // dispatch_once() must not return before the block has finished.
+  return (void *)once_count;
+}
+
+int test_dispatch_once() {
+  pthread_t thread1, thread2;
+  void *result1, *result2;
+  pthread_create(&thread1, NULL, once_thread_func, NULL);
+  pthread_create(&thread2, NULL, once_thread_func, NULL);
+  pthread_join(thread1, &result1);
+  pthread_join(thread2, &result2);
+  if (once_count != 1)
+    return -1;
+  if ((int)result1 != 1 || (int)result2 != 1)
+    return -2;
+  // Later calls do nothing.
+  once_thread_func(NULL);
+  if (once_count != 1)
+    return -3;
+  return 0;
+}
+
+static dispatch_semaphore_t handoff_sema;
+static volatile int handoff_value;
+
+void *handoff_thread_func(void *unused) {
+  usleep(10000);
+  handoff_value = 42;
+  dispatch_semaphore_signal(handoff_sema);
+  return NULL;
+}
+
+int test_dispatch_semaphore() {
+  handoff_sema = dispatch_semaphore_create(0);
+  if (handoff_sema == NULL)
+    return -1;
+  // Nothing has signalled yet, so this should time out.
+  dispatch_time_t soon = dispatch_time(DISPATCH_TIME_NOW, 5 * NSEC_PER_MSEC);
+  if (dispatch_semaphore_wait(handoff_sema, soon) == 0)
+    return -2;
+  if (dispatch_semaphore_wait(handoff_sema, DISPATCH_TIME_NOW) == 0)
+    return -3;
+
+  pthread_t thread;
+  pthread_create(&thread, NULL, handoff_thread_func, NULL);
+  if (dispatch_semaphore_wait(handoff_sema, DISPATCH_TIME_FOREVER) != 0)
+    return -4;
+  if (handoff_value != 42)
+    return -5;
+  pthread_join(thread, NULL);
+
+  // A signal with no waiter is kept for the next wait.
+  if (dispatch_semaphore_signal(handoff_sema) != 0)
+    return -6;
+  if (dispatch_semaphore_wait(handoff_sema, DISPATCH_TIME_NOW) != 0)
+    return -7;
+  dispatch_release(handoff_sema);
+
+  if (dispatch_semaphore_create(-1) != NULL)
+    return -8;
+  return 0;
+}
+
+int test_snprintf() {
+  char buf[8];
+  memset(buf, 'x', sizeof(buf));
+  // The return value is the length that would have been written.
+  if (snprintf(buf, sizeof(buf), "%d-%s", 12345, "abcdef") != 12)
+    return -1;
+  // The output is truncated and always null-terminated.
+  if (strcmp(buf, "12345-a"))
+    return -2;
+  // Nothing is written if the size is zero, so the buffer can be NULL.
+  if (snprintf(NULL, 0, "%d", 42) != 2)
+    return -3;
+  memset(buf, 'x', sizeof(buf));
+  if (snprintf(buf, 1, "abc") != 3 || buf[0] != '\0' || buf[1] != 'x')
+    return -4;
+  // An exact fit isn't truncated.
+  if (snprintf(buf, 4, "abc") != 3 || strcmp(buf, "abc"))
+    return -5;
+  if (sprintf(buf, "%c%c", 'h', 'i') != 2 || strcmp(buf, "hi"))
+    return -6;
+  return 0;
+}
+
+int test_launch_options() {
+  // The integration test passes --launch-option=TOUCHHLE_LAUNCH_OPTION=hi.
+  char *value = getenv("TOUCHHLE_LAUNCH_OPTION");
+  if (value == NULL || strcmp(value, "hi") != 0)
+    return -1;
+
+  id process_info = objc_msgSend(objc_getClass("NSProcessInfo"),
+                                 sel_registerName("processInfo"));
+  id environment = objc_msgSend(process_info, sel_registerName("environment"));
+  id name = CFStringCreateWithCString(NULL, "TOUCHHLE_LAUNCH_OPTION", 0x0600);
+  id ns_value =
+      objc_msgSend(environment, sel_registerName("objectForKey:"), name);
+  if (ns_value == nil || !((char (*)(id, SEL, id))objc_msgSend)(
+                             ns_value, sel_registerName("isEqualToString:"),
+                             CFStringCreateWithCString(NULL, "hi", 0x0600)))
+    return -2;
+
+  // The option is also passed as the arguments "-KEY VALUE".
+  id arguments = objc_msgSend(process_info, sel_registerName("arguments"));
+  if ((size_t)objc_msgSend(arguments, sel_registerName("count")) != 3)
+    return -3;
+  id flag = objc_msgSend(arguments, sel_registerName("objectAtIndex:"), 1);
+  id expected_flag =
+      CFStringCreateWithCString(NULL, "-TOUCHHLE_LAUNCH_OPTION", 0x0600);
+  if (!((char (*)(id, SEL, id))objc_msgSend)(
+          flag, sel_registerName("isEqualToString:"), expected_flag))
+    return -4;
+
+  return 0;
+}
+
+// A value object whose equality and hash depend on an int ivar.
+unsigned int ValueKey_hash(id self, SEL _cmd) { return ((int *)self)[1]; }
+char ValueKey_isEqual(id self, SEL _cmd, id other) {
+  SEL sel_class = sel_registerName("class");
+  return other != nil &&
+         objc_msgSend(other, sel_class) == objc_msgSend(self, sel_class) &&
+         ((int *)other)[1] == ((int *)self)[1];
+}
+id ValueKey_copyWithZone(id self, SEL _cmd, void *zone) {
+  return objc_msgSend(self, sel_registerName("retain"));
+}
+
+int test_custom_equality_keys() {
+  id cls = objc_allocateClassPair(objc_getClass("NSObject"), "ValueKey", 0);
+  class_addIvar(cls, "value", sizeof(int), 2, "i");
+  class_addMethod(cls, sel_registerName("hash"), &ValueKey_hash, "I@:");
+  class_addMethod(cls, sel_registerName("isEqual:"), &ValueKey_isEqual, "c@:@");
+  class_addMethod(cls, sel_registerName("copyWithZone:"),
+                  &ValueKey_copyWithZone, "@@:^v");
+  objc_registerClassPair(cls);
+
+  SEL sel_new = sel_registerName("new");
+  id k1 = objc_msgSend(cls, sel_new);
+  id k2 = objc_msgSend(cls, sel_new);
+  id k3 = objc_msgSend(cls, sel_new);
+  ((int *)k1)[1] = 5;
+  ((int *)k2)[1] = 5;
+  ((int *)k3)[1] = 6;
+  id v1 = (id)CFStringCreateWithCString(NULL, "v1", 0x0600);
+  id v2 = (id)CFStringCreateWithCString(NULL, "v2", 0x0600);
+
+  SEL sel_set = sel_registerName("setObject:forKey:");
+  SEL sel_get = sel_registerName("objectForKey:");
+  SEL sel_count = sel_registerName("count");
+  id dict = objc_msgSend(objc_getClass("NSMutableDictionary"), sel_new);
+  objc_msgSend(dict, sel_set, v1, k1);
+  // An equal key finds the entry, even though it is a different object.
+  if (objc_msgSend(dict, sel_get, k2) != v1)
+    return -1;
+  if (objc_msgSend(dict, sel_get, k3) != nil)
+    return -2;
+  // Setting an equal key replaces the entry.
+  objc_msgSend(dict, sel_set, v2, k2);
+  if ((size_t)objc_msgSend(dict, sel_count) != 1)
+    return -3;
+  if (objc_msgSend(dict, sel_get, k1) != v2)
+    return -4;
+  objc_msgSend(dict, sel_registerName("removeObjectForKey:"), k2);
+  if ((size_t)objc_msgSend(dict, sel_count) != 0)
+    return -5;
+
+  // Sets use the same equality.
+  SEL sel_contains = sel_registerName("containsObject:");
+  id set = objc_msgSend(objc_getClass("NSMutableSet"), sel_new);
+  objc_msgSend(set, sel_registerName("addObject:"), k1);
+  if (!objc_msgSend(set, sel_contains, k2) ||
+      objc_msgSend(set, sel_contains, k3))
+    return -6;
+  if (objc_msgSend(set, sel_registerName("member:"), k2) != k1)
+    return -7;
+  objc_msgSend(set, sel_registerName("removeObject:"), k2);
+  if ((size_t)objc_msgSend(set, sel_count) != 0)
+    return -8;
+
+  // NSObject's default equality is identity.
+  id a = objc_msgSend(objc_getClass("NSObject"), sel_new);
+  id b = objc_msgSend(objc_getClass("NSObject"), sel_new);
+  objc_msgSend(set, sel_registerName("addObject:"), a);
+  if (!objc_msgSend(set, sel_contains, a) || objc_msgSend(set, sel_contains, b))
+    return -9;
+
+  return 0;
+}
+
+// The handlers registered by test_atexit() run when main() calls exit(). The
+// integration test checks the message printed by the last one.
+char atexit_order[3];
+void atexit_first(void) {
+  atexit_order[1] = '1';
+  printf("atexit handlers ran in order: %s\n", atexit_order);
+}
+void atexit_second(void *arg) { atexit_order[0] = *(char *)arg; }
+
+int test_atexit() {
+  if (atexit(&atexit_first) != 0)
+    return -1;
+  if (__cxa_atexit(&atexit_second, "2", NULL) != 0)
+    return -2;
+  return 0;
+}
+
+int test_operator_new() {
+  size_t before = mstats().bytes_used;
+  char *obj = _Znwm(100);
+  char *array = _Znam(200);
+  if (obj == NULL || array == NULL || malloc_size(obj) < 100 ||
+      malloc_size(array) < 200)
+    return -1;
+  if (mstats().bytes_used < before + 300)
+    return -2;
+  _ZdlPv(obj);
+  _ZdaPv(array);
+  if (mstats().bytes_used != before)
+    return -3;
+  // Zero-sized allocations must still return unique pointers.
+  char nothrow = 0;
+  void *a = _Znwm(0);
+  void *b = _ZnwmRKSt9nothrow_t(0, &nothrow);
+  if (a == NULL || b == NULL || a == b)
+    return -4;
+  _ZdlPv(a);
+  _ZdlPv(b);
+  // Deleting NULL does nothing.
+  _ZdlPv(NULL);
+  _ZdaPv(NULL);
+  if (mstats().bytes_used != before)
+    return -5;
+  return 0;
+}
+
+int test_NSData_base64_and_files() {
+  SEL sel_dataWithBytes_length = sel_registerName("dataWithBytes:length:");
+  SEL sel_base64Encoded = sel_registerName("base64EncodedStringWithOptions:");
+  SEL sel_initWithBase64 =
+      sel_registerName("initWithBase64EncodedString:options:");
+  SEL sel_dataWithContentsOfFile =
+      sel_registerName("dataWithContentsOfFile:options:error:");
+  SEL sel_isEqualToData = sel_registerName("isEqualToData:");
+  SEL sel_UTF8String = sel_registerName("UTF8String");
+  id NSData = objc_getClass("NSData");
+
+  const unsigned char bytes[] = {'t', 'o', 'u', 'c', 'h', 0, 0xff};
+  id data = ((id(*)(id, SEL, const void *, unsigned int))objc_msgSend)(
+      NSData, sel_dataWithBytes_length, bytes, sizeof(bytes));
+  id encoded =
+      ((id(*)(id, SEL, unsigned int))objc_msgSend)(data, sel_base64Encoded, 0);
+  if (strcmp((const char *)objc_msgSend(encoded, sel_UTF8String),
+             "dG91Y2gA/w=="))
+    return -1;
+  id decoded = ((id(*)(id, SEL, id, unsigned int))objc_msgSend)(
+      objc_msgSend(NSData, sel_registerName("alloc")), sel_initWithBase64,
+      encoded, 0);
+  if (decoded == nil || !objc_msgSend(decoded, sel_isEqualToData, data))
+    return -2;
+  objc_msgSend(decoded, sel_registerName("release"));
+  // Line breaks are unknown characters unless they're ignored.
+  id wrapped = (id)CFStringCreateWithCString(NULL, "dG91\r\nY2gA/w==", 0x0600);
+  decoded = ((id(*)(id, SEL, id, unsigned int))objc_msgSend)(
+      objc_msgSend(NSData, sel_registerName("alloc")), sel_initWithBase64,
+      wrapped, 0);
+  if (decoded != nil)
+    return -3;
+  decoded = ((id(*)(id, SEL, id, unsigned int))objc_msgSend)(
+      objc_msgSend(NSData, sel_registerName("alloc")), sel_initWithBase64,
+      wrapped, 1);
+  if (decoded == nil || !objc_msgSend(decoded, sel_isEqualToData, data))
+    return -4;
+  objc_msgSend(decoded, sel_registerName("release"));
+
+  // Write the data to a file and read it back.
+  SEL sel_appendPath = sel_registerName("stringByAppendingPathComponent:");
+  id path = objc_msgSend(
+      NSTemporaryDirectory(), sel_appendPath,
+      (id)CFStringCreateWithCString(NULL, "test_NSData.bin", 0x0600));
+  if (!objc_msgSend(data, sel_registerName("writeToFile:atomically:"), path, 1))
+    return -5;
+  id error = nil;
+  id read = ((id(*)(id, SEL, id, unsigned int, id *))objc_msgSend)(
+      NSData, sel_dataWithContentsOfFile, path, 0, &error);
+  if (read == nil || error != nil ||
+      !objc_msgSend(read, sel_isEqualToData, data))
+    return -6;
+  id missing =
+      objc_msgSend(NSTemporaryDirectory(), sel_appendPath,
+                   (id)CFStringCreateWithCString(NULL, "missing.bin", 0x0600));
+  read = ((id(*)(id, SEL, id, unsigned int, id *))objc_msgSend)(
+      NSData, sel_dataWithContentsOfFile, missing, 0, &error);
+  if (read != nil || error == nil)
+    return -7;
+  if (((int (*)(id, SEL))objc_msgSend)(error, sel_registerName("code")) != 260)
+    return -8;
+  return 0;
+}
+
+typedef struct {
+  unsigned int location;
+  unsigned int length;
+} NSRange;
+
+typedef void (^index_block_t)(unsigned int, char *);
+unsigned int index_block_sum;
+
+int test_NSIndexSet() {
+  SEL sel_containsIndex = sel_registerName("containsIndex:");
+  SEL sel_count = sel_registerName("count");
+  SEL sel_indexGreaterThanIndex = sel_registerName("indexGreaterThanIndex:");
+  id set =
+      objc_msgSend(objc_getClass("NSMutableIndexSet"), sel_registerName("new"));
+  ((void (*)(id, SEL, NSRange))objc_msgSend)(
+      set, sel_registerName("addIndexesInRange:"), (NSRange){10, 5});
+  ((void (*)(id, SEL, unsigned int))objc_msgSend)(
+      set, sel_registerName("addIndex:"), 3);
+  ((void (*)(id, SEL, unsigned int))objc_msgSend)(
+      set, sel_registerName("removeIndex:"), 12);
+  // The set is now {3, 10, 11, 13, 14}.
+  if (((unsigned int (*)(id, SEL))objc_msgSend)(set, sel_count) != 5)
+    return -1;
+  if (!((char (*)(id, SEL, unsigned int))objc_msgSend)(set, sel_containsIndex,
+                                                       11) ||
+      ((char (*)(id, SEL, unsigned int))objc_msgSend)(set, sel_containsIndex,
+                                                      12))
+    return -2;
+  if (((unsigned int (*)(id, SEL))objc_msgSend)(
+          set, sel_registerName("firstIndex")) != 3)
+    return -3;
+
+  // Enumeration the old-fashioned way...
+  unsigned int sum = 0;
+  unsigned int i = ((unsigned int (*)(id, SEL))objc_msgSend)(
+      set, sel_registerName("firstIndex"));
+  while (i != 0x7fffffff) {
+    sum += i;
+    i = ((unsigned int (*)(id, SEL, unsigned int))objc_msgSend)(
+        set, sel_indexGreaterThanIndex, i);
+  }
+  if (sum != 51)
+    return -4;
+  // ...and with a block.
+  index_block_sum = 0;
+  ((void (*)(id, SEL, index_block_t))objc_msgSend)(
+      set, sel_registerName("enumerateIndexesUsingBlock:"),
+      ^(unsigned int idx, char *stop) {
+        index_block_sum += idx;
+        *stop = idx == 11;
+      });
+  if (index_block_sum != 24)
+    return -5;
+
+  id single = ((id(*)(id, SEL, unsigned int))objc_msgSend)(
+      objc_getClass("NSIndexSet"), sel_registerName("indexSetWithIndex:"), 7);
+  if (((unsigned int (*)(id, SEL))objc_msgSend)(single, sel_count) != 1 ||
+      !((char (*)(id, SEL, unsigned int))objc_msgSend)(single,
+                                                       sel_containsIndex, 7))
+    return -6;
+  objc_msgSend(set, sel_registerName("release"));
+  return 0;
+}
+
+int test_NSIndexPath() {
+  SEL sel_row = sel_registerName("row");
+  SEL sel_section = sel_registerName("section");
+  SEL sel_indexPathForRow = sel_registerName("indexPathForRow:inSection:");
+  id path = ((id(*)(id, SEL, unsigned int, unsigned int))objc_msgSend)(
+      objc_getClass("NSIndexPath"), sel_indexPathForRow, 4, 2);
+  if (((unsigned int (*)(id, SEL))objc_msgSend)(path, sel_row) != 4 ||
+      ((unsigned int (*)(id, SEL))objc_msgSend)(path, sel_section) != 2)
+    return -1;
+  if (((unsigned int (*)(id, SEL))objc_msgSend)(
+          path, sel_registerName("length")) != 2)
+    return -2;
+  unsigned int indexes[] = {2, 4};
+  id same = ((id(*)(id, SEL, unsigned int *, unsigned int))objc_msgSend)(
+      objc_getClass("NSIndexPath"),
+      sel_registerName("indexPathWithIndexes:length:"), indexes, 2);
+  if (!objc_msgSend(path, sel_registerName("isEqual:"), same))
+    return -3;
+  id longer = ((id(*)(id, SEL, unsigned int))objc_msgSend)(
+      path, sel_registerName("indexPathByAddingIndex:"), 0);
+  if (((int (*)(id, SEL, id))objc_msgSend)(path, sel_registerName("compare:"),
+                                           longer) != -1)
+    return -4;
+  return 0;
+}
+
+int test_UIPasteboard() {
+  SEL sel_string = sel_registerName("string");
+  SEL sel_UTF8String = sel_registerName("UTF8String");
+  SEL sel_dataForPasteboardType = sel_registerName("dataForPasteboardType:");
+  id pasteboard = objc_msgSend(objc_getClass("UIPasteboard"),
+                               sel_registerName("generalPasteboard"));
+  if (pasteboard == nil ||
+      pasteboard != objc_msgSend(objc_getClass("UIPasteboard"),
+                                 sel_registerName("generalPasteboard")))
+    return -1;
+
+  // The test app runs headless, so this doesn't touch the host clipboard.
+  id text = (id)CFStringCreateWithCString(NULL, "copied", 0x0600);
+  objc_msgSend(pasteboard, sel_registerName("setString:"), text);
+  id pasted = objc_msgSend(pasteboard, sel_string);
+  if (pasted == nil ||
+      strcmp((const char *)objc_msgSend(pasted, sel_UTF8String), "copied"))
+    return -2;
+
+  // Setting data replaces the string.
+  id type = (id)CFStringCreateWithCString(NULL, "com.example.bytes", 0x0600);
+  id data = ((id(*)(id, SEL, const void *, unsigned int))objc_msgSend)(
+      objc_getClass("NSData"), sel_registerName("dataWithBytes:length:"),
+      "\x01\x02", 2);
+  objc_msgSend(pasteboard, sel_registerName("setData:forPasteboardType:"), data,
+               type);
+  id got = objc_msgSend(pasteboard, sel_dataForPasteboardType, type);
+  if (!objc_msgSend(got, sel_registerName("isEqualToData:"), data))
+    return -3;
+  if (objc_msgSend(pasteboard, sel_string) != nil)
+    return -4;
+  return 0;
+}
+
+// Returns the NSThread of the thread it runs on, which must be the same each
+// time it is asked for.
+void *current_NSThread_func(void *unused) {
+  id thread = objc_msgSend(objc_getClass("NSThread"),
+                           sel_registerName("currentThread"));
+  if (objc_msgSend(objc_getClass("NSThread"),
+                   sel_registerName("currentThread")) != thread)
+    return NULL;
+  return thread;
+}
+
+int test_NSThread_currentThread_after_exit() {
+  pthread_t thread1, thread2;
+  id result1, result2;
+  pthread_create(&thread1, NULL, current_NSThread_func, NULL);
+  pthread_join(thread1, (void **)&result1);
+  if (result1 == nil)
+    return -1;
+
+  // A later thread must get a fresh NSThread, not the finished thread's one.
+  pthread_create(&thread2, NULL, current_NSThread_func, NULL);
+  pthread_join(thread2, (void **)&result2);
+  if (result2 == nil)
+    return -2;
+  if (result1 == result2)
+    return -3;
+
+  // The main thread's NSThread is unaffected.
+  id main_thread = objc_msgSend(objc_getClass("NSThread"),
+                                sel_registerName("currentThread"));
+  if (main_thread == result1 || main_thread == result2)
+    return -4;
+  return 0;
+}
+
+// Returns the -description of an object as a C string.
+const char *description_of(id object) {
+  id desc = objc_msgSend(object, sel_registerName("description"));
+  return (const char *)objc_msgSend(desc, sel_registerName("UTF8String"));
+}
+
+int test_description() {
+  char expected[32];
+  id object = objc_msgSend(objc_getClass("NSObject"), sel_registerName("new"));
+  sprintf(expected, "<NSObject: %p>", object);
+  if (strcmp(description_of(object), expected))
+    return -1;
+  id debug_desc = objc_msgSend(object, sel_registerName("debugDescription"));
+  if (strcmp((const char *)objc_msgSend(debug_desc,
+                                        sel_registerName("UTF8String")),
+             expected))
+    return -2;
+  if (strcmp(description_of((id)objc_getClass("NSObject")), "NSObject"))
+    return -3;
+
+  id a = (id)CFStringCreateWithCString(NULL, "apple", 0x0600);
+  id b = (id)CFStringCreateWithCString(NULL, "banana", 0x0600);
+  id array = objc_msgSend(objc_getClass("NSArray"),
+                          sel_registerName("arrayWithObjects:"), a, b, nil);
+  const char *array_desc = description_of(array);
+  if (array_desc[0] != '(' || !strstr(array_desc, "apple") ||
+      strstr(array_desc, "apple") > strstr(array_desc, "banana"))
+    return -4;
+
+  id set = objc_msgSend(objc_getClass("NSSet"), sel_registerName("alloc"));
+  set = objc_msgSend(set, sel_registerName("initWithObjects:"), a, b, nil);
+  const char *set_desc = description_of(set);
+  if (strncmp(set_desc, "{(", 2) || !strstr(set_desc, "apple") ||
+      !strstr(set_desc, "banana"))
+    return -5;
+
+  char bytes[] = {0x00, 0x01, 0xfe, 0xff, 0x10};
+  id data = objc_msgSend(objc_getClass("NSData"),
+                         sel_registerName("dataWithBytes:length:"), bytes, 5);
+  if (strcmp(description_of(data), "<0001feff 10>"))
+    return -6;
+
+  objc_msgSend(set, sel_registerName("release"));
+  objc_msgSend(object, sel_registerName("release"));
+  return 0;
+}
+
+int test_CFBundle() {
+  CFBundleRef bundle = CFBundleGetMainBundle();
+  if (bundle == NULL) {
+    return -1;
+  }
+
+  CFStringRef key =
+      CFStringCreateWithCString(NULL, "CFBundleIdentifier", 0x0600);
+  CFStringRef identifier =
+      CFStringCreateWithCString(NULL, "com.yourcompany.TestApp", 0x0600);
+  CFTypeRef value = CFBundleGetValueForInfoDictionaryKey(bundle, key);
+  if (value == NULL || !CFEqual(value, identifier)) {
+    return -2;
+  }
+  if (!CFEqual(CFBundleGetIdentifier(bundle), identifier)) {
+    return -3;
+  }
+  CFRelease(key);
+  CFRelease(identifier);
+
+  CFStringRef name = CFStringCreateWithCString(NULL, "Localizable", 0x0600);
+  CFStringRef type = CFStringCreateWithCString(NULL, "strings", 0x0600);
+  CFURLRef url = CFBundleCopyResourceURL(bundle, name, type, NULL);
+  if (url == NULL) {
+    return -4;
+  }
+  char path[1024];
+  if (!CFURLGetFileSystemRepresentation(url, 1, (unsigned char *)path,
+                                        sizeof(path))) {
+    return -5;
+  }
+  if (path[0] != '/' ||
+      strstr(path, "TestApp.app/English.lproj/Localizable.strings") == NULL) {
+    return -6;
+  }
+  CFRelease(url);
+  CFRelease(name);
+
+  // Missing resources give NULL
+  CFStringRef missing = CFStringCreateWithCString(NULL, "Missing", 0x0600);
+  if (CFBundleCopyResourceURL(bundle, missing, type, NULL) != NULL) {
+    return -7;
+  }
+  CFRelease(missing);
+  CFRelease(type);
+
+  return 0;
+}
+
+int test_mkstemp() {
+  char template[1024];
+  snprintf(template, sizeof(template), "%s/tmp/test_mkstemp.XXXXXX",
+           getenv("HOME"));
+  size_t len = strlen(template);
+
+  int fd = mkstemp(template);
+  if (fd == -1) {
+    return -1;
+  }
+  // The Xs were replaced with the actual name, and nothing else changed
+  if (strlen(template) != len || strstr(template, "XXX") != NULL ||
+      strstr(template, "/tmp/test_mkstemp.") == NULL) {
+    return -2;
+  }
+  if (write(fd, "hello", 5) != 5) {
+    return -3;
+  }
+  if (close(fd) != 0) {
+    return -4;
+  }
+
+  // The file is where the template says it is
+  FILE *file = fopen(template, "r");
+  if (file == NULL) {
+    return -5;
+  }
+  char buf[8] = {0};
+  if (fread(buf, 1, sizeof(buf), file) != 5 || strcmp(buf, "hello") != 0) {
+    return -6;
+  }
+  fclose(file);
+
+  // Another file gets a different name
+  char template2[1024];
+  snprintf(template2, sizeof(template2), "%s/tmp/test_mkstemp.XXXXXX",
+           getenv("HOME"));
+  int fd2 = mkstemp(template2);
+  if (fd2 == -1 || strcmp(template, template2) == 0) {
+    return -7;
+  }
+  close(fd2);
+  remove(template);
+  remove(template2);
+
+  // tmpfile() gives a FILE* that can be written and read back
+  file = tmpfile();
+  if (file == NULL) {
+    return -8;
+  }
+  if (fwrite("world", 1, 5, file) != 5) {
+    return -9;
+  }
+  rewind(file);
+  memset(buf, 0, sizeof(buf));
+  if (fread(buf, 1, sizeof(buf), file) != 5 || strcmp(buf, "world") != 0) {
+    return -10;
+  }
+  if (fclose(file) != 0) {
+    return -11;
+  }
+
+  return 0;
+}
+
+int test_respondsToSelector_conformsToProtocol() {
+  SEL sel_responds = sel_registerName("respondsToSelector:");
+  SEL sel_conforms = sel_registerName("conformsToProtocol:");
+  SEL sel_stringWithUTF8String = sel_registerName("stringWithUTF8String:");
+  char (*responds)(id, SEL, SEL) = (char (*)(id, SEL, SEL))objc_msgSend;
+  char (*conforms)(id, SEL, Protocol *) =
+      (char (*)(id, SEL, Protocol *))objc_msgSend;
+
+  id cls = objc_getClass("NSString");
+  id str = objc_msgSend(cls, sel_stringWithUTF8String, "foo");
+  SEL sel_length = sel_registerName("length");
+  SEL sel_bogus = sel_registerName("thisSelectorDoesNotExist:");
+
+  // Instances respond to instance methods, including inherited ones
+  if (!responds(str, sel_responds, sel_length) ||
+      !responds(str, sel_responds, sel_responds)) {
+    return -1;
+  }
+  // Classes respond to class methods, but not instance methods
+  if (!responds(cls, sel_responds, sel_stringWithUTF8String) ||
+      responds(cls, sel_responds, sel_length)) {
+    return -2;
+  }
+  if (responds(str, sel_responds, sel_bogus) ||
+      responds(cls, sel_responds, sel_bogus)) {
+    return -3;
+  }
+
+  Protocol *copying = objc_getProtocol("NSCopying");
+  if (copying == NULL || objc_getProtocol("NotARealProtocol") != NULL) {
+    return -4;
+  }
+  if (!conforms(str, sel_conforms, copying) ||
+      !conforms(cls, sel_conforms, copying)) {
+    return -5;
+  }
+  // NSObject doesn't adopt NSCopying, and class_conformsToProtocol doesn't
+  // look at superclasses.
+  id nsobject = objc_getClass("NSObject");
+  if (conforms(nsobject, sel_conforms, copying) ||
+      !class_conformsToProtocol(cls, copying) ||
+      class_conformsToProtocol(objc_getClass("NSMutableString"), copying)) {
+    return -6;
+  }
+  return 0;
+}
+
+// Pointers to host functions in initialized data are linked when the app is
+// loaded, so they don't go through the lazy symbol stubs.
+static size_t (*const strlen_ptr)(const char *) = strlen;
+static const CFArrayCallBacks *const array_callbacks_ptr =
+    &kCFTypeArrayCallBacks;
+
+int test_host_symbol_pointers() {
+  if (strlen_ptr("touchHLE") != 8) {
+    return -1;
+  }
+  if (array_callbacks_ptr != &kCFTypeArrayCallBacks) {
+    return -2;
+  }
+  return 0;
+}
+
+void *shared_object_thread_func(void *arg) {
+  return objc_msgSend(objc_getClass("NSFileManager"),
+                      sel_registerName("defaultManager"));
+}
+
+int test_shared_object() {
+  pthread_t thread;
+  if (pthread_create(&thread, NULL, shared_object_thread_func, NULL) != 0)
+    return -1;
+  id manager = objc_msgSend(objc_getClass("NSFileManager"),
+                            sel_registerName("defaultManager"));
+  void *ret = NULL;
+  if (pthread_join(thread, &ret) != 0)
+    return -2;
+  if (manager == nil || ret != manager)
+    return -3;
+  return 0;
+}
+
+int test_NSCalendar() {
+  SEL sel_date1970 = sel_registerName("dateWithTimeIntervalSince1970:");
+  SEL sel_ti1970 = sel_registerName("timeIntervalSince1970");
+  SEL sel_components = sel_registerName("components:fromDate:");
+  SEL sel_from_components = sel_registerName("dateFromComponents:");
+  int (*get)(id, SEL) = (int (*)(id, SEL))objc_msgSend;
+
+  // 2009-02-13 23:31:30 GMT, a Friday
+  id date = ((id(*)(id, SEL, double))objc_msgSend)(objc_getClass("NSDate"),
+                                                   sel_date1970, 1234567890.0);
+  id calendar = objc_msgSend(objc_getClass("NSCalendar"),
+                             sel_registerName("currentCalendar"));
+  id time_zone = ((id(*)(id, SEL, int))objc_msgSend)(
+      objc_getClass("NSTimeZone"),
+      sel_registerName("timeZoneForSecondsFromGMT:"), 2 * 3600);
+  objc_msgSend(calendar, sel_registerName("setTimeZone:"), time_zone);
+
+  // Year, month, day, hour, minute, second and weekday
+  unsigned int units = 4 | 8 | 16 | 32 | 64 | 128 | 512;
+  id components = ((id(*)(id, SEL, unsigned int, id))objc_msgSend)(
+      calendar, sel_components, units, date);
+  if (get(components, sel_registerName("year")) != 2009 ||
+      get(components, sel_registerName("month")) != 2 ||
+      get(components, sel_registerName("day")) != 14) {
+    return -1;
+  }
+  if (get(components, sel_registerName("hour")) != 1 ||
+      get(components, sel_registerName("minute")) != 31 ||
+      get(components, sel_registerName("second")) != 30) {
+    return -2;
+  }
+  // Saturday
+  if (get(components, sel_registerName("weekday")) != 7) {
+    return -3;
+  }
+
+  id recomposed = objc_msgSend(calendar, sel_from_components, components);
+  if (((double (*)(id, SEL))objc_msgSend)(recomposed, sel_ti1970) !=
+      1234567890.0) {
+    return -4;
+  }
+  return 0;
+}
+
+int test_run_loop_cancel_all_work() {
+  SEL sel_retainCount = sel_registerName("retainCount");
+  SEL sel_description = sel_registerName("description");
+  unsigned int (*retain_count)(id, SEL) =
+      (unsigned int (*)(id, SEL))objc_msgSend;
+
+  id target = objc_msgSend(objc_getClass("NSObject"), sel_registerName("new"));
+  unsigned int count_before = retain_count(target, sel_retainCount);
+
+  // Both of these retain the target until they fire, which they won't. The
+  // timers are autoreleased, so only the run loop owns them once this is
+  // drained.
+  id pool =
+      objc_msgSend(objc_getClass("NSAutoreleasePool"), sel_registerName("new"));
+  ((void (*)(id, SEL, SEL, id, double))objc_msgSend)(
+      target, sel_registerName("performSelector:withObject:afterDelay:"),
+      sel_description, nil, 1000.0);
+  ((id(*)(id, SEL, double, id, SEL, id, char))objc_msgSend)(
+      objc_getClass("NSTimer"),
+      sel_registerName(
+          "scheduledTimerWithTimeInterval:target:selector:userInfo:repeats:"),
+      1000.0, target, sel_description, nil, 1);
+  objc_msgSend(pool, sel_registerName("drain"));
+  if (retain_count(target, sel_retainCount) != count_before + 2) {
+    return -1;
+  }
+
+  // This is what touchHLE does when the app quits. It's not an Apple API.
+  id run_loop =
+      objc_msgSend(objc_getClass("NSRunLoop"), sel_registerName("mainRunLoop"));
+  objc_msgSend(run_loop, sel_registerName("_touchHLE_cancelAllWork"));
+  if (retain_count(target, sel_retainCount) != count_before) {
+    return -2;
+  }
+
+  objc_msgSend(target, sel_registerName("release"));
+  return 0;
+}
+
+// Each call to this from qsort() is a host-to-guest call, which makes another
+// one by calling bsearch(). Every one of them must return to the right place.
+int nested_compar_calls;
+int nested_compar(const void *a, const void *b) {
+  static const int keys[3] = {1, 2, 3};
+  nested_compar_calls++;
+  int *found_a = bsearch(a, keys, 3, sizeof(int), &int_compar);
+  int *found_b = bsearch(b, keys, 3, sizeof(int), &int_compar);
+  if (found_a == NULL || found_b == NULL)
+    return 0;
+  return (int)(found_a - found_b);
+}
+
+int test_nested_host_to_guest_calls() {
+  int arr[4] = {3, 1, 2, 1};
+  nested_compar_calls = 0;
+  qsort(arr, 4, sizeof(int), &nested_compar);
+  if (memcmp(arr, (int[]){1, 1, 2, 3}, sizeof(arr)))
+    return -1;
+  if (nested_compar_calls == 0)
+    return -2;
+  return 0;
+}
+
+// The stack grows downwards, so the reported address is its top.
+int stack_bounds_contain(char *addr) {
+  pthread_t self = pthread_self();
+  char *top = pthread_get_stackaddr_np(self);
+  size_t size = pthread_get_stacksize_np(self);
+  return size != 0 && addr < top && addr >= top - size;
+}
+void *stack_bounds_thread_func(void *arg) {
+  char local;
+  if (!stack_bounds_contain(&local))
+    return (void *)-1;
+  return (void *)pthread_get_stacksize_np(pthread_self());
+}
+
+int test_pthread_stack_bounds() {
+  char local;
+  if (!stack_bounds_contain(&local))
+    return -1;
+
+  // pthread_attr_t is opaque, this matches the size of Apple's.
+  long attr[10];
+  size_t stack_size = 64 * 1024;
+  if (pthread_attr_init((pthread_attr_t *)attr) != 0 ||
+      pthread_attr_setstacksize((pthread_attr_t *)attr, stack_size) != 0)
+    return -2;
+  pthread_t thread;
+  if (pthread_create(&thread, (pthread_attr_t *)attr, stack_bounds_thread_func,
+                     NULL) != 0)
+    return -3;
+  void *ret;
+  if (pthread_join(thread, &ret) != 0)
+    return -4;
+  if ((size_t)ret != stack_size)
+    return -5;
+  return 0;
+}
+
+int test_NSString_percent_escapes() {
+  SEL sel_UTF8String = sel_registerName("UTF8String");
+  SEL sel_add_legacy =
+      sel_registerName("stringByAddingPercentEscapesUsingEncoding:");
+  SEL sel_add_allowed =
+      sel_registerName("stringByAddingPercentEncodingWithAllowedCharacters:");
+  SEL sel_replace =
+      sel_registerName("stringByReplacingPercentEscapesUsingEncoding:");
+  id (*with_encoding)(id, SEL, unsigned int) =
+      (id(*)(id, SEL, unsigned int))objc_msgSend;
+  unsigned int utf8 = 4;
+  int res = 0;
+
+  id pool =
+      objc_msgSend(objc_getClass("NSAutoreleasePool"), sel_registerName("new"));
+  // "a b/é?[1]"
+  id string = objc_msgSend(objc_getClass("NSString"),
+                           sel_registerName("stringWithUTF8String:"),
+                           "a b/\xc3\xa9?[1]");
+  id escaped = with_encoding(string, sel_add_legacy, utf8);
+  if (escaped == nil ||
+      strcmp((const char *)objc_msgSend(escaped, sel_UTF8String),
+             "a%20b/%C3%A9?%5B1%5D")) {
+    res = -1;
+    goto done;
+  }
+  id unescaped = with_encoding(escaped, sel_replace, utf8);
+  if (unescaped == nil ||
+      strcmp((const char *)objc_msgSend(unescaped, sel_UTF8String),
+             "a b/\xc3\xa9?[1]")) {
+    res = -2;
+    goto done;
+  }
+
+  // Only "a" and "b" are allowed, so everything else is escaped.
+  id allowed =
+      objc_msgSend(objc_getClass("NSCharacterSet"),
+                   sel_registerName("characterSetWithCharactersInString:"),
+                   CFStringCreateWithCString(NULL, "ab", 0x0600));
+  escaped = objc_msgSend(string, sel_add_allowed, allowed);
+  if (escaped == nil ||
+      strcmp((const char *)objc_msgSend(escaped, sel_UTF8String),
+             "a%20b%2F%C3%A9%3F%5B%31%5D")) {
+    res = -3;
+    goto done;
+  }
+  unescaped = with_encoding(escaped, sel_replace, utf8);
+  if (unescaped == nil ||
+      strcmp((const char *)objc_msgSend(unescaped, sel_UTF8String),
+             "a b/\xc3\xa9?[1]")) {
+    res = -4;
+    goto done;
+  }
+
+  // Malformed escapes
+  id malformed = (id)CFStringCreateWithCString(NULL, "100%zz", 0x0600);
+  if (with_encoding(malformed, sel_replace, utf8) != nil)
+    res = -5;
+
+done:
+  objc_msgSend(pool, sel_registerName("drain"));
+  return res;
+}
+
+int test_gmtime_strftime() {
+  // 2009-02-13 23:31:30 UTC, a Friday
+  time_t timestamp = 1234567890;
+  struct tm *tm = gmtime(&timestamp);
+  if (tm->tm_year != 109 || tm->tm_mon != 1 || tm->tm_mday != 13 ||
+      tm->tm_hour != 23 || tm->tm_min != 31 || tm->tm_sec != 30)
+    return -1;
+  if (tm->tm_wday != 5 || tm->tm_yday != 43 || tm->tm_gmtoff != 0)
+    return -2;
+
+  char buf[64];
+  size_t len = strftime(buf, sizeof(buf), "%Y-%m-%d %H:%M:%S %a %b", tm);
+  if (len != 27 || strcmp(buf, "2009-02-13 23:31:30 Fri Feb"))
+    return -3;
+  // Too small for the terminator
+  if (strftime(buf, 27, "%Y-%m-%d %H:%M:%S %a %b", tm) != 0)
+    return -4;
+
+  // mktime() undoes localtime(), whatever the local time zone is.
+  struct tm local = *localtime(&timestamp);
+  if (mktime(&local) != timestamp)
+    return -5;
+  // Out-of-range fields are normalized: the 14th month is February.
+  local.tm_mon += 12;
+  if (mktime(&local) != timestamp + 365 * 24 * 3600 || local.tm_year != 110 ||
+      local.tm_mon != 1)
+    return -6;
+  return 0;
+}
+
+int test_CGGeometry() {
+  CGRect rect = CGRectMake(10, 20, 30, 40);
+  if (rect.origin.x != 10 || rect.origin.y != 20 || rect.size.width != 30 ||
+      rect.size.height != 40)
+    return -1;
+  CGSize size = CGSizeMake(30, 40);
+  if (!CGSizeEqualToSize(rect.size, size))
+    return -2;
+  if (CGRectGetMaxX(rect) != 40 || CGRectGetMidY(rect) != 40)
+    return -3;
+
+  // The minimum edges are inside the rectangle, the maximum edges aren't.
+  if (!CGRectContainsPoint(rect, CGPointMake(10, 20)) ||
+      !CGRectContainsPoint(rect, CGPointMake(39.5, 59.5)))
+    return -4;
+  if (CGRectContainsPoint(rect, CGPointMake(40, 30)) ||
+      CGRectContainsPoint(rect, CGPointMake(20, 60)) ||
+      CGRectContainsPoint(rect, CGPointMake(9.5, 30)))
+    return -5;
+  // A negative size extends the other way.
+  if (!CGRectContainsPoint(CGRectMake(40, 60, -30, -40), CGPointMake(10, 20)))
+    return -6;
+
+  CGRect overlapping = CGRectMake(30, 50, 100, 100);
+  CGRect touching = CGRectMake(40, 20, 10, 10);
+  CGRect apart = CGRectMake(100, 100, 10, 10);
+  if (!CGRectIntersectsRect(rect, overlapping) ||
+      !CGRectIntersectsRect(overlapping, rect) ||
+      CGRectIntersectsRect(rect, touching) || CGRectIntersectsRect(rect, apart))
+    return -7;
+  if (!CGRectEqualToRect(CGRectIntersection(rect, overlapping),
+                         CGRectMake(30, 50, 10, 10)) ||
+      !CGRectIsNull(CGRectIntersection(rect, apart)))
+    return -8;
+
+  if (!CGRectEqualToRect(CGRectInset(rect, 5, 10), CGRectMake(15, 30, 20, 20)))
+    return -9;
+  if (!CGRectIsNull(CGRectInset(rect, 20, 0)))
+    return -10;
+  return 0;
+}
+
+int test_UIScreen() {
+  id screen =
+      objc_msgSend(objc_getClass("UIScreen"), sel_registerName("mainScreen"));
+  if (screen == nil)
+    return -1;
+
+  // The test app runs with the default screen options, which match the
+  // original iPhone.
+  CGRect bounds;
+  objc_msgSend_stret(&bounds, screen, sel_registerName("bounds"));
+  if (!CGRectEqualToRect(bounds, CGRectMake(0, 0, 320, 480)))
+    return -2;
+  CGFloat scale =
+      ((CGFloat(*)(id, SEL))objc_msgSend)(screen, sel_registerName("scale"));
+  if (scale != 1)
+    return -3;
+
+  // The application frame is the part not covered by the status bar, if
+  // there is one.
+  CGRect frame;
+  objc_msgSend_stret(&frame, screen, sel_registerName("applicationFrame"));
+  if (frame.size.width != 320 || frame.origin.y + frame.size.height != 480)
+    return -4;
+  return 0;
+}
+
+// An NSThread's isExecuting and isFinished, as seen from the thread itself and
+// from the main thread waiting for it to complete.
+static volatile int lifecycle_executing_in_thread;
+static volatile int lifecycle_returned;
+
+void LifecycleTarget_run(id self, SEL _cmd, id object) {
+  char (*get_bool)(id, SEL) = (void *)objc_msgSend;
+  id thread = objc_msgSend(objc_getClass("NSThread"),
+                           sel_registerName("currentThread"));
+  lifecycle_executing_in_thread =
+      get_bool(thread, sel_registerName("isExecuting")) &&
+      !get_bool(thread, sel_registerName("isFinished"));
+  lifecycle_returned = 1;
+}
+
+int test_NSThread_isExecuting_isFinished() {
+  id cls =
+      objc_allocateClassPair(objc_getClass("NSObject"), "LifecycleTarget", 0);
+  if (cls == nil)
+    return -1;
+  class_addMethod(cls, sel_registerName("run:"), (void *)&LifecycleTarget_run,
+                  "v@:@");
+  objc_registerClassPair(cls);
+
+  id target = objc_msgSend(objc_msgSend(cls, sel_registerName("alloc")),
+                           sel_registerName("init"));
+  id thread =
+      objc_msgSend(objc_getClass("NSThread"), sel_registerName("alloc"));
+  thread =
+      objc_msgSend(thread, sel_registerName("initWithTarget:selector:object:"),
+                   target, sel_registerName("run:"), nil);
+  objc_msgSend(target, sel_registerName("release"));
+
+  char (*get_bool)(id, SEL) = (void *)objc_msgSend;
+  SEL is_executing = sel_registerName("isExecuting");
+  SEL is_finished = sel_registerName("isFinished");
+  if (get_bool(thread, is_executing) || get_bool(thread, is_finished))
+    return -2;
+
+  objc_msgSend(thread, sel_registerName("start"));
+  if (!get_bool(thread, is_executing) || get_bool(thread, is_finished))
+    return -3;
+
+  // Wait for up to a second.
+  for (int i = 0; i < 100 && !get_bool(thread, is_finished); i++)
+    usleep(10000);
+
+  if (!lifecycle_returned)
+    return -4;
+  if (!lifecycle_executing_in_thread)
+    return -5;
+  if (get_bool(thread, is_executing) || !get_bool(thread, is_finished))
+    return -6;
+  objc_msgSend(thread, sel_registerName("release"));
+
+  // Threads that weren't started with NSThread are executing too.
+  id main_thread = objc_msgSend(objc_getClass("NSThread"),
+                                sel_registerName("currentThread"));
+  if (!get_bool(main_thread, is_executing))
+    return -7;
+  return 0;
+}
+
+int test_mmap_zero_length() {
+  errno = 0;
+  if (mmap(NULL, 0, PROT_READ, MAP_PRIVATE, 0, 0) != MAP_FAILED)
+    return -1;
+  if (errno != EINVAL)
+    return -2;
+  return 0;
+}
+
+int test_vm_allocate() {
+  vm_address_t addr = 0;
+  if (vm_allocate(mach_task_self(), &addr, 10000, VM_FLAGS_ANYWHERE) !=
+      KERN_SUCCESS)
+    return -1;
+  if (addr == 0 || addr % 4096 != 0)
+    return -2;
+  // The size is rounded up to whole pages, which start out zeroed.
+  unsigned char *bytes = (unsigned char *)addr;
+  for (int i = 0; i < 3 * 4096; i++) {
+    if (bytes[i] != 0)
+      return -3;
+    bytes[i] = i & 0xff;
+  }
+  for (int i = 0; i < 3 * 4096; i++) {
+    if (bytes[i] != (i & 0xff))
+      return -4;
+  }
+  if (vm_deallocate(mach_task_self(), addr, 10000) != KERN_SUCCESS)
+    return -5;
+  if (vm_deallocate(mach_task_self(), addr, 10000) == KERN_SUCCESS)
+    return -6;
+
+  // Without VM_FLAGS_ANYWHERE, the address asked for is used if it's free.
+  vm_address_t fixed = addr;
+  if (vm_allocate(mach_task_self(), &fixed, 4096, VM_FLAGS_FIXED) !=
+      KERN_SUCCESS)
+    return -7;
+  if (fixed != addr || bytes[1] != 0)
+    return -8;
+  if (vm_deallocate(mach_task_self(), fixed, 4096) != KERN_SUCCESS)
+    return -9;
+  void *used = malloc(8192);
+  fixed = ((vm_address_t)used + 4095) & ~4095u;
+  kern_return_t res = vm_allocate(mach_task_self(), &fixed, 4096, 0);
+  free(used);
+  if (res == KERN_SUCCESS)
+    return -10;
+  return 0;
+}
+
+// A thread sleeping with +[NSThread sleepForTimeInterval:] or
+// +[NSThread sleepUntilDate:] lets other threads run, then carries on where it
+// left off.
+static volatile int sleep_ticks;
+static volatile int sleep_ticker_stop;
+
+void *sleep_ticker_func(void *unused) {
+  while (!sleep_ticker_stop) {
+    sleep_ticks++;
+    usleep(1000);
+  }
+  return NULL;
+}
+
+static double monotonic_seconds() {
+  struct timespec ts;
+  clock_gettime(CLOCK_MONOTONIC, &ts);
+  return ts.tv_sec + ts.tv_nsec / 1e9;
+}
+
+int test_NSThread_sleep() {
+  pthread_t ticker;
+  if (pthread_create(&ticker, NULL, sleep_ticker_func, NULL) != 0)
+    return -1;
+
+  id thread_class = objc_getClass("NSThread");
+  SEL sel_sleep_for = sel_registerName("sleepForTimeInterval:");
+  void (*sleep_for)(id, SEL, double) = (void *)objc_msgSend;
+  int ticks = sleep_ticks;
+  double start = monotonic_seconds();
+  sleep_for(thread_class, sel_sleep_for, 0.05);
+  if (monotonic_seconds() - start < 0.045)
+    return -2;
+  if (sleep_ticks == ticks)
+    return -3;
+
+  // Intervals in the past don't sleep at all.
+  sleep_for(thread_class, sel_sleep_for, -1.0);
+
+  id date = objc_msgSend(objc_getClass("NSDate"), sel_registerName("alloc"));
+  date = ((id(*)(id, SEL, double))objc_msgSend)(
+      date, sel_registerName("initWithTimeIntervalSinceNow:"), 0.05);
+  ticks = sleep_ticks;
+  start = monotonic_seconds();
+  objc_msgSend(thread_class, sel_registerName("sleepUntilDate:"), date);
+  objc_msgSend(date, sel_registerName("release"));
+  if (monotonic_seconds() - start < 0.045)
+    return -4;
+  if (sleep_ticks == ticks)
+    return -5;
+
+  sleep_ticker_stop = 1;
+  if (pthread_join(ticker, NULL) != 0)
+    return -6;
+  return 0;
+}
+
+static id make_number_formatter(const char *locale_id, int style) {
+  id locale =
+      objc_msgSend(objc_getClass("NSLocale"), sel_registerName("alloc"));
+  locale = objc_msgSend(locale, sel_registerName("initWithLocaleIdentifier:"),
+                        CFStringCreateWithCString(NULL, locale_id, 0x0600));
+  id formatter =
+      objc_msgSend(objc_getClass("NSNumberFormatter"), sel_registerName("new"));
+  ((void (*)(id, SEL, unsigned int))objc_msgSend)(
+      formatter, sel_registerName("setNumberStyle:"), style);
+  objc_msgSend(formatter, sel_registerName("setLocale:"), locale);
+  objc_msgSend(locale, sel_registerName("release"));
+  return formatter;
+}
+
+// Formats with a formatter and checks the result, then parses it back.
+static int check_number_formatter(id formatter, double value,
+                                  const char *expected) {
+  id number = ((id(*)(id, SEL, double))objc_msgSend)(
+      objc_getClass("NSNumber"), sel_registerName("numberWithDouble:"), value);
+  id string =
+      objc_msgSend(formatter, sel_registerName("stringFromNumber:"), number);
+  const char *str =
+      (const char *)objc_msgSend(string, sel_registerName("UTF8String"));
+  if (strcmp(str, expected) != 0)
+    return -1;
+  id parsed =
+      objc_msgSend(formatter, sel_registerName("numberFromString:"), string);
+  if (parsed == nil)
+    return -2;
+  double parsed_value = ((double (*)(id, SEL))objc_msgSend)(
+      parsed, sel_registerName("doubleValue"));
+  return parsed_value == value ? 0 : -3;
+}
+
+int test_NSNumberFormatter() {
+  id pool =
+      objc_msgSend(objc_getClass("NSAutoreleasePool"), sel_registerName("new"));
+  int res = 0;
+
+  id en_decimal = make_number_formatter("en_US", 1);
+  id de_decimal = make_number_formatter("de_DE", 1);
+  id en_currency = make_number_formatter("en_US", 2);
+  if (check_number_formatter(en_decimal, 1234567.891, "1,234,567.891") != 0)
+    res = -1;
+  else if (check_number_formatter(de_decimal, 1234567.891, "1.234.567,891"))
+    res = -2;
+  else if (check_number_formatter(en_currency, 1234.5, "$1,234.50") != 0)
+    res = -3;
+  else if (objc_msgSend(en_decimal, sel_registerName("numberFromString:"),
+                        CFStringCreateWithCString(NULL, "12a", 0x0600)) != nil)
+    res = -4;
+
+  objc_msgSend(en_decimal, sel_registerName("release"));
+  objc_msgSend(de_decimal, sel_registerName("release"));
+  objc_msgSend(en_currency, sel_registerName("release"));
+  objc_msgSend(pool, sel_registerName("release"));
+  return res;
+}
+
+static unsigned int retain_count(id obj) {
+  return ((unsigned int (*)(id, SEL))objc_msgSend)(
+      obj, sel_registerName("retainCount"));
+}
+
+// Checks that the refcounts stay balanced, including over many calls.
+int test_objc_retain_release() {
+  id pool =
+      objc_msgSend(objc_getClass("NSAutoreleasePool"), sel_registerName("new"));
+  id obj = objc_msgSend(objc_getClass("NSObject"), sel_registerName("new"));
+  if (objc_retain(nil) != nil || objc_autorelease(nil) != nil)
+    return -1;
+  objc_release(nil);
+
+  for (int i = 0; i < 1000; i++) {
+    if (objc_retain(obj) != obj)
+      return -2;
+    objc_release(obj);
+  }
+  if (retain_count(obj) != 1)
+    return -3;
+
+  objc_retain(obj);
+  objc_retain(obj);
+  if (retain_count(obj) != 3)
+    return -4;
+  if (objc_autorelease(obj) != obj || objc_retainAutorelease(obj) != obj)
+    return -5;
+  // The retain from objc_retainAutorelease is balanced by the pool.
+  if (retain_count(obj) != 4)
+    return -6;
+  objc_msgSend(pool, sel_registerName("release"));
+  if (retain_count(obj) != 2)
+    return -7;
+
+  objc_release(obj);
+  objc_release(obj);
+  return 0;
+}
+
+static int custom_retains;
+static int custom_releases;
+
+id TestCustomRetain_retain(id self, SEL _cmd) {
+  custom_retains++;
+  return self;
+}
+void TestCustomRetain_release(id self, SEL _cmd) { custom_releases++; }
+
+int test_objc_retain_release_override() {
+  id cls =
+      objc_allocateClassPair(objc_getClass("NSObject"), "TestCustomRetain", 0);
+  if (cls == nil)
+    return -1;
+  class_addMethod(cls, sel_registerName("retain"),
+                  (void *)&TestCustomRetain_retain, "@@:");
+  class_addMethod(cls, sel_registerName("release"),
+                  (void *)&TestCustomRetain_release, "v@:");
+  objc_registerClassPair(cls);
+
+  id obj = objc_msgSend(cls, sel_registerName("new"));
+  if (objc_retain(obj) != obj || custom_retains != 1)
+    return -2;
+  objc_release(obj);
+  if (custom_releases != 1)
+    return -3;
+  // The overrides don't touch the refcount.
+  if (retain_count(obj) != 1)
+    return -4;
+  return 0;
+}
+
+static void *backtrace_buffer[64];
+static int backtrace_frames;
+
+// These can't be static, because only exported symbols can be found.
+void test_backtrace_inner() {
+  backtrace_frames = backtrace(backtrace_buffer, 64);
+}
+void test_backtrace_outer() { test_backtrace_inner(); }
+
+int test_backtrace() {
+  test_backtrace_outer();
+  // The inner and outer functions, this one, and main() at least.
+  if (backtrace_frames < 4)
+    return -1;
+
+  char **symbols = backtrace_symbols(backtrace_buffer, backtrace_frames);
+  if (symbols == NULL)
+    return -2;
+  int res = 0;
+  if (strstr(symbols[0], "0   TestApp ") != symbols[0] ||
+      !strstr(symbols[0], " test_backtrace_inner + "))
+    res = -3;
+  else if (!strstr(symbols[1], " test_backtrace_outer + "))
+    res = -4;
+  else if (!strstr(symbols[2], " test_backtrace + "))
+    res = -5;
+  // The strings are part of the same allocation.
+  free(symbols);
+  if (res != 0)
+    return res;
+
+  // A small buffer is filled as far as it goes.
+  void *small_buffer[2];
+  if (backtrace(small_buffer, 2) != 2)
+    return -6;
+  return 0;
+}
+
+int test_dlopen_framework() {
+  const char *path =
+      "/System/Library/Frameworks/Foundation.framework/Foundation";
+  void *foundation = dlopen(path, RTLD_LAZY);
+  if (foundation == NULL)
+    return -1;
+  // The handle is stable.
+  if (dlopen(path, RTLD_LAZY) != foundation)
+    return -2;
+
+  id (*temp_dir_func)(void) = dlsym(foundation, "NSTemporaryDirectory");
+  if (temp_dir_func == NULL)
+    return -3;
+  id temp_dir = temp_dir_func();
+  id expected = NSTemporaryDirectory();
+  if (!((char (*)(id, SEL, id))objc_msgSend)(
+          temp_dir, sel_registerName("isEqualToString:"), expected))
+    return -4;
+  // Only Foundation's functions are found.
+  if (dlsym(foundation, "strlen") != NULL)
+    return -5;
+  if (dlclose(foundation) != 0)
+    return -6;
+
+  if (dlopen("/System/Library/Frameworks/Bogus.framework/Bogus", RTLD_LAZY) !=
+      NULL)
+    return -7;
+  return 0;
+}
+
+// A hand-written block with copy and dispose helpers, like the compiler
+// generates for blocks that capture objects, so that the copy and release of
+// the block can be observed.
+struct counting_block_descriptor {
+  unsigned long reserved;
+  unsigned long size;
+  void (*copy)(void *dst, const void *src);
+  void (*dispose)(const void *);
+};
+struct counting_block {
+  void *isa;
+  int flags;
+  int reserved;
+  void (*invoke)(struct counting_block *);
+  struct counting_block_descriptor *descriptor;
+  int captured;
+};
+#define BLOCK_HAS_COPY_DISPOSE (1 << 25)
+extern void *_NSConcreteStackBlock[];
+
+static volatile int counting_block_copies;
+static volatile int counting_block_disposals;
+static volatile int counting_block_result;
+static volatile int counting_block_disposed_after_run;
+
+void counting_block_invoke(struct counting_block *block) {
+  counting_block_result = block->captured;
+}
+void counting_block_copy(void *dst, const void *src) {
+  counting_block_copies++;
+}
+void counting_block_dispose(const void *block) {
+  counting_block_disposed_after_run = counting_block_result != 0;
+  counting_block_disposals++;
+}
+
+static volatile int simple_block_ran;
+
+int test_NSThread_detachNewThreadWithBlock() {
+  struct counting_block_descriptor descriptor = {
+      0, sizeof(struct counting_block), counting_block_copy,
+      counting_block_dispose};
+  struct counting_block block = {_NSConcreteStackBlock,
+                                 BLOCK_HAS_COPY_DISPOSE,
+                                 0,
+                                 counting_block_invoke,
+                                 &descriptor,
+                                 42};
+  SEL detach = sel_registerName("detachNewThreadWithBlock:");
+  objc_msgSend(objc_getClass("NSThread"), detach, &block);
+  // The block was copied, so changing the original doesn't matter.
+  block.captured = 0;
+  if (counting_block_copies != 1)
+    return -1;
+
+  // Wait for up to a second.
+  for (int i = 0; i < 100 && !counting_block_disposals; i++)
+    usleep(10000);
+  if (counting_block_result != 42)
+    return -2;
+  if (counting_block_disposals != 1 || !counting_block_disposed_after_run)
+    return -3;
+
+  // A block written the normal way.
+  int value = 7;
+  objc_msgSend(objc_getClass("NSThread"), detach, ^{
+    simple_block_ran = value;
+  });
+  for (int i = 0; i < 100 && !simple_block_ran; i++)
+    usleep(10000);
+  if (simple_block_ran != 7)
+    return -4;
+  return 0;
+}
+
+// Blocks capturing a variable of this type retain it, like they would an
+// Objective-C object pointer.
+typedef struct objc_object *retained_id __attribute__((NSObject));
+
+static volatile int capturing_block_done;
+
+int test_block_captures() {
+  SEL retainCount = sel_registerName("retainCount");
+  id object = objc_msgSend(objc_getClass("NSObject"), sel_registerName("new"));
+  retained_id captured = object;
+  __block int counter = 0;
+  void (^increment)(void) = ^{
+    counter++;
+  };
+
+  capturing_block_done = 0;
+  SEL detach = sel_registerName("detachNewThreadWithBlock:");
+  objc_msgSend(objc_getClass("NSThread"), detach, ^{
+    if (objc_msgSend(captured, retainCount) == (id)2)
+      counter += 10;
+    increment();
+    capturing_block_done = 1;
+  });
+  // Copying the block retained the object.
+  if (objc_msgSend(object, retainCount) != (id)2)
+    return -1;
+
+  // Wait for up to a second for the block to run and be released.
+  for (int i = 0; i < 100 && (!capturing_block_done ||
+                              objc_msgSend(object, retainCount) != (id)1);
+       i++)
+    usleep(10000);
+  if (!capturing_block_done)
+    return -2;
+  if (objc_msgSend(object, retainCount) != (id)1)
+    return -3;
+  // Both blocks changed the __block variable on the heap, which this stack
+  // frame sees too.
+  if (counter != 11)
+    return -4;
+
+  objc_msgSend(object, sel_registerName("release"));
+  return 0;
+}
+
+int test_memory_info() {
+  // The test app is run with --device-memory=128.
+  unsigned long long memsize = 0;
+  size_t len = sizeof(memsize);
+  if (sysctlbyname("hw.memsize", &memsize, &len, NULL, 0) != 0 ||
+      len != sizeof(memsize) || memsize != 128 * 1024 * 1024)
+    return -1;
+
+  struct task_basic_info before, after;
+  unsigned int count = TASK_BASIC_INFO_COUNT;
+  if (task_info(mach_task_self(), TASK_BASIC_INFO, (int *)&before, &count) !=
+          KERN_SUCCESS ||
+      count != TASK_BASIC_INFO_COUNT)
+    return -2;
+  if (before.resident_size == 0 || before.virtual_size < before.resident_size)
+    return -3;
+  void *ptr = malloc(1024 * 1024);
+  task_info(mach_task_self(), TASK_BASIC_INFO, (int *)&after, &count);
+  free(ptr);
+  if (after.resident_size < before.resident_size + 1024 * 1024 ||
+      after.virtual_size < after.resident_size)
+    return -4;
+  // Too small a buffer
+  count = 2;
+  if (task_info(mach_task_self(), TASK_BASIC_INFO, (int *)&after, &count) ==
+      KERN_SUCCESS)
+    return -5;
+  // Unsupported flavor (MACH_TASK_BASIC_INFO)
+  count = TASK_BASIC_INFO_COUNT;
+  if (task_info(mach_task_self(), 20, (int *)&after, &count) == KERN_SUCCESS)
+    return -6;
+  return 0;
+}
+
 // clang-format off
 #define FUNC_DEF(func)                                                         \
   { &func, #func }
@@ -2178,6 +5733,82 @@ struct {
     FUNC_DEF(test_maskrune),
     FUNC_DEF(test_frexpf),
     FUNC_DEF(test_setjmp),
+    FUNC_DEF(test_sigsegv_handler),
+    FUNC_DEF(test_NSKeyedUnarchiver),
+    FUNC_DEF(test_performSelector),
+    FUNC_DEF(test_NSNull),
+    FUNC_DEF(test_NSJSONSerialization),
+    FUNC_DEF(test_NSURL),
+    FUNC_DEF(test_NSDateFormatter),
+    FUNC_DEF(test_getenv_setenv),
+    FUNC_DEF(test_faulting_thread),
+    FUNC_DEF(test_returning_signal_handler),
+    FUNC_DEF(test_memmove_memcmp),
+    FUNC_DEF(test_str_functions),
+    FUNC_DEF(test_message_forwarding),
+    FUNC_DEF(test_CFAllocator),
+    FUNC_DEF(test_fast_enumeration),
+    FUNC_DEF(test_fast_enumeration_mutation),
+    FUNC_DEF(test_NSLocale),
+    FUNC_DEF(test_NSBundle_localizedString),
+    FUNC_DEF(test_CFMutableDictionary_bridged),
+    FUNC_DEF(test_CFArray_bridged),
+    FUNC_DEF(test_malloc_size),
+    FUNC_DEF(test_pthread_name),
+    FUNC_DEF(test_stack_overflow),
+    FUNC_DEF(test_NSString_case_and_trimming),
+    FUNC_DEF(test_NSString_compare),
+    FUNC_DEF(test_integer_division),
+    FUNC_DEF(test_soft_float),
+    FUNC_DEF(test_qsort_r_bsearch),
+    FUNC_DEF(test_unexpected_svc),
+    FUNC_DEF(test_NSString_paths),
+    FUNC_DEF(test_ivar_lookup),
+    FUNC_DEF(test_NSCache),
+    FUNC_DEF(test_clock_gettime),
+    FUNC_DEF(test_NSThread_background_loading),
+    FUNC_DEF(test_objc_properties),
+    FUNC_DEF(test_memory_barriers),
+    FUNC_DEF(test_CADisplayLink),
+    FUNC_DEF(test_NSString_numeric_values),
+    FUNC_DEF(test_dispatch_once),
+    FUNC_DEF(test_dispatch_semaphore),
+    FUNC_DEF(test_snprintf),
+    FUNC_DEF(test_launch_options),
+    FUNC_DEF(test_custom_equality_keys),
+    FUNC_DEF(test_atexit),
+    FUNC_DEF(test_operator_new),
+    FUNC_DEF(test_NSData_base64_and_files),
+    FUNC_DEF(test_NSIndexSet),
+    FUNC_DEF(test_NSIndexPath),
+    FUNC_DEF(test_UIPasteboard),
+    FUNC_DEF(test_NSThread_currentThread_after_exit),
+    FUNC_DEF(test_description),
+    FUNC_DEF(test_CFBundle),
+    FUNC_DEF(test_mkstemp),
+    FUNC_DEF(test_respondsToSelector_conformsToProtocol),
+    FUNC_DEF(test_host_symbol_pointers),
+    FUNC_DEF(test_shared_object),
+    FUNC_DEF(test_NSCalendar),
+    FUNC_DEF(test_run_loop_cancel_all_work),
+    FUNC_DEF(test_nested_host_to_guest_calls),
+    FUNC_DEF(test_pthread_stack_bounds),
+    FUNC_DEF(test_NSString_percent_escapes),
+    FUNC_DEF(test_gmtime_strftime),
+    FUNC_DEF(test_CGGeometry),
+    FUNC_DEF(test_UIScreen),
+    FUNC_DEF(test_NSThread_isExecuting_isFinished),
+    FUNC_DEF(test_vm_allocate),
+    FUNC_DEF(test_mmap_zero_length),
+    FUNC_DEF(test_NSThread_sleep),
+    FUNC_DEF(test_NSNumberFormatter),
+    FUNC_DEF(test_objc_retain_release),
+    FUNC_DEF(test_objc_retain_release_override),
+    FUNC_DEF(test_backtrace),
+    FUNC_DEF(test_dlopen_framework),
+    FUNC_DEF(test_NSThread_detachNewThreadWithBlock),
+    FUNC_DEF(test_block_captures),
+    FUNC_DEF(test_memory_info),
 };
 // clang-format on
 
@@ -0,0 +1,39 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+// This app sends the same messages to two classes. It's meant to be run with
+// --trace-class=TracedClass, in which case touchHLE should log the messages
+// sent to TracedClass and its instances, but not those sent to UntracedClass.
+// See also tests/README.md and tests/integration.rs.
+
+typedef unsigned long size_t;
+typedef struct objc_object *id;
+typedef struct objc_selector *SEL;
+id objc_getClass(const char *);
+SEL sel_registerName(const char *);
+id objc_msgSend(id, SEL, ...);
+id objc_allocateClassPair(id superclass, const char *name, size_t extraBytes);
+void objc_registerClassPair(id cls);
+
+void exit(int);
+
+int main() {
+  id traced_class =
+      objc_allocateClassPair(objc_getClass("NSObject"), "TracedClass", 0);
+  objc_registerClassPair(traced_class);
+  id untraced_class =
+      objc_allocateClassPair(objc_getClass("NSObject"), "UntracedClass", 0);
+  objc_registerClassPair(untraced_class);
+
+  id traced = objc_msgSend(traced_class, sel_registerName("new"));
+  id untraced = objc_msgSend(untraced_class, sel_registerName("new"));
+  objc_msgSend(traced, sel_registerName("isEqual:"), untraced);
+  objc_msgSend(untraced, sel_registerName("isEqual:"), traced);
+  objc_msgSend(traced, sel_registerName("release"));
+  objc_msgSend(untraced, sel_registerName("release"));
+  exit(0);
+  return 1; // unreachable
+}
@@ -0,0 +1,30 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+// This app passes a comparison function that doesn't restore the stack pointer
+// to qsort(), so the host-to-guest call returns with an unbalanced stack. In
+// debug builds, touchHLE should stop the app with a diagnostic rather than
+// carry on with a corrupted stack.
+// See also tests/README.md and tests/integration.rs.
+
+typedef unsigned long size_t;
+
+void qsort(void *, size_t, size_t, int (*)(const void *, const void *));
+void exit(int);
+
+// Returns 0 ("equal") but leaves 16 bytes on the stack.
+__attribute__((naked)) int unbalanced_compare(const void *a, const void *b) {
+  __asm__ volatile("sub sp, sp, #16\n"
+                   "mov r0, #0\n"
+                   "bx lr\n");
+}
+
+int main() {
+  int array[2] = {2, 1};
+  qsort(array, 2, sizeof(int), unbalanced_compare);
+  exit(0);
+  return 1; // unreachable
+}
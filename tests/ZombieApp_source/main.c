@@ -0,0 +1,28 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+// This app over-releases an object and then sends it a message. It's meant to
+// be run with --zombies, in which case touchHLE should stop the app with a
+// diagnostic naming the class and selector. Without zombies this is undefined
+// behavior, which is why it isn't part of TestApp.
+// See also tests/README.md and tests/integration.rs.
+
+typedef struct objc_object *id;
+typedef struct objc_selector *SEL;
+id objc_getClass(const char *);
+SEL sel_registerName(const char *);
+id objc_msgSend(id, SEL, ...);
+
+void exit(int);
+
+int main() {
+  id object = objc_msgSend(objc_getClass("NSObject"), sel_registerName("new"));
+  objc_msgSend(object, sel_registerName("release"));
+  // The object is now deallocated, so this should be fatal.
+  objc_msgSend(object, sel_registerName("retain"));
+  exit(0);
+  return 1; // unreachable
+}
@@ -3,7 +3,7 @@ use std::env::current_dir;
 use std::error::Error;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Output};
 
 // adapted from `assert_cmd` crate
 fn target_dir() -> PathBuf {
@@ -26,7 +26,9 @@ fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
         .position(|window| window == needle)
 }
 
-fn build_test_app(tests_dir: &Path, test_app_path: &Path) -> Result<(), Box<dyn Error>> {
+/// Compile a single C source file from `tests/{app_name}_source/main.c` into
+/// the executable of the bundle at `tests/{app_name}.app`.
+fn build_test_app(tests_dir: &Path, app_name: &str) -> Result<PathBuf, Box<dyn Error>> {
     let clang_path = tests_dir
         .join("llvm")
         .join("bin")
@@ -40,12 +42,21 @@ fn build_test_app(tests_dir: &Path, test_app_path: &Path) -> Result<(), Box<dyn
         );
     }
 
-    let test_bin_path = test_app_path.join("TestApp");
+    let test_app_path = tests_dir.join(format!("{}.app", app_name));
+    let test_bin_path = test_app_path.join(app_name);
 
     eprintln!("Building {} for iPhone OS 2...", test_bin_path.display());
 
     let mut cmd = Command::new(clang_path);
 
+    // Apps that use C++ exceptions need to link to the bundled libstdc++ and
+    // libgcc, otherwise touchHLE won't load them.
+    if app_name == "CxxExceptionApp" {
+        let dylibs_dir = tests_dir.parent().unwrap().join("touchHLE_dylibs");
+        cmd.arg(dylibs_dir.join("libstdc++.6.dylib"))
+            .arg(dylibs_dir.join("libgcc_s.1.dylib"));
+    }
+
     let output = cmd
         // Use upstream LLVM linker (not system linker)
         .arg("-fuse-ld=lld")
@@ -70,7 +81,11 @@ fn build_test_app(tests_dir: &Path, test_app_path: &Path) -> Result<(), Box<dyn
         // because we have no system libraries/frameworks for it to link to.
         .arg("-Wl,-e,_main,-undefined,dynamic_lookup")
         // Input
-        .arg(tests_dir.join("TestApp_source").join("main.c"))
+        .arg(
+            tests_dir
+                .join(format!("{}_source", app_name))
+                .join("main.c"),
+        )
         // Write the output to the bundle.
         .arg("-o")
         .arg(&test_bin_path)
@@ -84,16 +99,24 @@ fn build_test_app(tests_dir: &Path, test_app_path: &Path) -> Result<(), Box<dyn
 
     eprintln!("Built successfully.");
 
-    Ok(())
+    Ok(test_app_path)
 }
 
-#[test]
-fn run_test_app() -> Result<(), Box<dyn Error>> {
-    let tests_dir = current_dir()?.join("tests");
+/// Build and run one of the test apps with some extra touchHLE options,
+/// returning the output of touchHLE.
+fn run_app(app_name: &str, options: &[&str]) -> Result<Output, Box<dyn Error>> {
+    run_app_with_env(app_name, options, &[])
+}
 
-    let test_app_path = tests_dir.join("TestApp.app");
+/// Like [run_app], but also sets some environment variables for touchHLE.
+fn run_app_with_env(
+    app_name: &str,
+    options: &[&str],
+    env_vars: &[(&str, &str)],
+) -> Result<Output, Box<dyn Error>> {
+    let tests_dir = current_dir()?.join("tests");
 
-    build_test_app(&tests_dir, &test_app_path)?;
+    let test_app_path = build_test_app(&tests_dir, app_name)?;
 
     let binary_name = "touchHLE";
     let binary_path = target_dir().join(format!("{}{}", binary_name, env::consts::EXE_SUFFIX));
@@ -105,18 +128,288 @@ fn run_test_app() -> Result<(), Box<dyn Error>> {
         // headless mode avoids a distracting window briefly appearing during
         // testing, and works in CI.
         .arg("--headless")
+        .args(options)
+        .envs(env_vars.iter().copied())
         .output()
         .expect("failed to execute touchHLE process");
 
     std::io::stdout().write_all(&output.stdout).unwrap();
     std::io::stderr().write_all(&output.stderr).unwrap();
 
-    assert!(output.status.success());
     // sanity check: check that emulation actually happened
     assert_ne!(
         find_subsequence(output.stderr.as_slice(), b"CPU emulation begins now."),
         None
     );
 
+    Ok(output)
+}
+
+#[test]
+fn run_test_app() -> Result<(), Box<dyn Error>> {
+    // test_faulting_thread, test_returning_signal_handler and
+    // test_unexpected_svc need --terminate-faulting-threads, test_NSLocale
+    // needs --locale=fr_FR, test_launch_options needs
+    // --launch-option=TOUCHHLE_LAUNCH_OPTION=hi, and test_memory_info needs
+    // --device-memory=128.
+    let output = run_app(
+        "TestApp",
+        &[
+            "--terminate-faulting-threads",
+            "--locale=fr_FR",
+            "--launch-option=TOUCHHLE_LAUNCH_OPTION=hi",
+            "--device-memory=128",
+        ],
+    )?;
+
+    assert!(output.status.success());
+    // test_stack_overflow's thread hits the guard page below its stack.
+    assert_ne!(
+        find_subsequence(
+            output.stderr.as_slice(),
+            b": StackOverflow. Terminating the thread."
+        ),
+        None
+    );
+    // The atexit handlers registered by test_atexit run in reverse order.
+    assert_ne!(
+        find_subsequence(
+            output.stdout.as_slice(),
+            b"atexit handlers ran in order: 21"
+        ),
+        None
+    );
+    // test_unexpected_svc prints the address of its SVC instruction, which
+    // touchHLE's diagnostic should name.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let svc_addr = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Unexpected SVC is at "))
+        .expect("test_unexpected_svc didn't print the SVC's address");
+    assert_ne!(
+        find_subsequence(
+            output.stderr.as_slice(),
+            format!("SVC #0x80 at {} was not written by the linker", svc_addr).as_bytes()
+        ),
+        None
+    );
+
+    Ok(())
+}
+
+/// End-to-end check of the linking and CPU integration: the minimal app calls
+/// one host function through a lazily-linked stub and passes the result to
+/// `exit()`, so the exit status tells us both that the host function received
+/// the right argument and that the guest received the right return value.
+#[test]
+fn run_minimal_app() -> Result<(), Box<dyn Error>> {
+    let output = run_app("MinimalApp", &[])?;
+
+    assert_eq!(output.status.code(), Some(42));
+    assert_ne!(
+        find_subsequence(output.stderr.as_slice(), b"App called exit(), exiting."),
+        None
+    );
+
+    Ok(())
+}
+
+/// Checks that with --zombies, a message sent to an over-released object stops
+/// the app with a diagnostic naming the class and the selector.
+#[test]
+fn run_zombie_app() -> Result<(), Box<dyn Error>> {
+    let output = run_app("ZombieApp", &["--zombies"])?;
+
+    assert!(!output.status.success());
+    assert_ne!(
+        find_subsequence(
+            output.stderr.as_slice(),
+            b"*** -[NSObject retain]: message sent to deallocated instance 0x"
+        ),
+        None
+    );
+
+    Ok(())
+}
+
+/// Checks that with --trace-class=, messages sent to that class and its
+/// instances are logged, but messages sent to other classes aren't.
+#[test]
+fn run_trace_app() -> Result<(), Box<dyn Error>> {
+    let output = run_app("TraceApp", &["--trace-class=TracedClass"])?;
+
+    assert!(output.status.success());
+    let stderr = output.stderr.as_slice();
+    assert_ne!(
+        find_subsequence(stderr, b"+[TracedClass new] sent to 0x"),
+        None
+    );
+    assert_ne!(
+        find_subsequence(stderr, b"-[TracedClass isEqual:] sent to 0x"),
+        None
+    );
+    assert_ne!(
+        find_subsequence(stderr, b"-[TracedClass release] sent to 0x"),
+        None
+    );
+    assert_eq!(find_subsequence(stderr, b"UntracedClass"), None);
+
+    Ok(())
+}
+
+/// Checks that `TOUCHHLE_LOG` enables debug messages only for the modules it
+/// names.
+#[test]
+fn run_app_with_log_filter() -> Result<(), Box<dyn Error>> {
+    let output = run_app_with_env("TraceApp", &[], &[("TOUCHHLE_LOG", "dyld=debug")])?;
+
+    assert!(output.status.success());
+    let stderr = output.stderr.as_slice();
+    // Debug messages are printed for the enabled module...
+    assert_ne!(find_subsequence(stderr, b"touchHLE::dyld: Linked "), None);
+    // ...but not for others.
+    assert_eq!(find_subsequence(stderr, b"touchHLE::mem: "), None);
+
+    Ok(())
+}
+
+/// Checks that a guest function returning to the host without restoring the
+/// stack pointer is caught, rather than corrupting the stack. This relies on
+/// the check being enabled in debug builds, which is what `cargo test` uses.
+#[test]
+#[cfg(debug_assertions)]
+fn run_unbalanced_stack_app() -> Result<(), Box<dyn Error>> {
+    let output = run_app("UnbalancedStackApp", &[])?;
+
+    assert!(!output.status.success());
+    assert_ne!(
+        find_subsequence(
+            output.stderr.as_slice(),
+            b"Guest code returned to the host with an unbalanced stack"
+        ),
+        None
+    );
+
+    Ok(())
+}
+
+/// Checks that when the emulator panics, it prints a report of the guest's
+/// state with the PC, a symbolicated backtrace, a list of the threads and a
+/// map of guest memory.
+#[test]
+fn run_panic_app() -> Result<(), Box<dyn Error>> {
+    let output = run_app("PanicApp", &[])?;
+
+    assert!(!output.status.success());
+    let stderr = output.stderr.as_slice();
+    assert_ne!(
+        find_subsequence(
+            stderr,
+            b"Call to unimplemented function _touchHLE_test_unimplemented_function"
+        ),
+        None
+    );
+    assert_ne!(find_subsequence(stderr, b"Guest PC: 0x"), None);
+    assert_ne!(find_subsequence(stderr, b"(_main+0x"), None);
+    assert_ne!(find_subsequence(stderr, b"Guest threads:"), None);
+    assert_ne!(
+        find_subsequence(stderr, b"thread 0 (main thread): running, PC 0x"),
+        None
+    );
+    assert_ne!(
+        find_subsequence(stderr, b"thread 1 \"sleepy A\": sleeping, PC 0x"),
+        None
+    );
+    assert_ne!(
+        find_subsequence(stderr, b"thread 2 \"sleepy B\": sleeping, PC 0x"),
+        None
+    );
+    assert_ne!(find_subsequence(stderr, b"Guest memory regions:"), None);
+    assert_ne!(find_subsequence(stderr, b"r-x __TEXT of PanicApp"), None);
+    assert_ne!(find_subsequence(stderr, b"__DATA of PanicApp"), None);
+    assert_ne!(find_subsequence(stderr, b"stack of thread 0"), None);
+    assert_ne!(find_subsequence(stderr, b"rw- stack of thread 1"), None);
+    assert_ne!(find_subsequence(stderr, b"rw- stack of thread 2"), None);
+
+    Ok(())
+}
+
+/// Checks that an uncaught C++ exception ends in a panic that names the type
+/// of the exception.
+#[test]
+fn run_cxx_exception_app() -> Result<(), Box<dyn Error>> {
+    let output = run_app("CxxExceptionApp", &[])?;
+
+    assert!(!output.status.success());
+    assert_ne!(
+        find_subsequence(
+            output.stderr.as_slice(),
+            b"App called abort() because of an uncaught C++ exception of type Example::Problem"
+        ),
+        None
+    );
+
+    Ok(())
+}
+
+/// Checks that --frame-pacing= slows the guest down to the requested number of
+/// instructions per frame and frames per second, and that a display link fires
+/// on every paced frame.
+#[test]
+fn run_pacing_app() -> Result<(), Box<dyn Error>> {
+    let output = run_app("PacingApp", &["--frame-pacing=50,1000000"])?;
+    assert!(output.status.success());
+    Ok(())
+}
+
+/// Checks that taps injected with --inject-tap= are queued and then dispatched
+/// in order when the main run loop handles events.
+#[test]
+fn run_input_app() -> Result<(), Box<dyn Error>> {
+    let output = run_app_with_env(
+        "InputApp",
+        &["--inject-tap=10,20", "--inject-tap=30,40"],
+        &[("TOUCHHLE_LOG", "frameworks::uikit=debug")],
+    )?;
+
+    assert!(output.status.success());
+    let stderr = output.stderr.as_slice();
+    let expected: [&[u8]; 4] = [
+        b"Dispatching queued input event TouchesBegan({Touch(0): (10.0, 20.0)})",
+        b"Dispatching queued input event TouchesEnded({Touch(0): (10.0, 20.0)})",
+        b"Dispatching queued input event TouchesBegan({Touch(0): (30.0, 40.0)})",
+        b"Dispatching queued input event TouchesEnded({Touch(0): (30.0, 40.0)})",
+    ];
+    let positions: Vec<_> = expected
+        .iter()
+        .map(|event| find_subsequence(stderr, event).expect("event not dispatched"))
+        .collect();
+    assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+
+    Ok(())
+}
+
+/// Records the clock readings and random numbers ReplayApp receives, then
+/// replays them and checks the app's output is the same.
+#[test]
+fn run_replay_app() -> Result<(), Box<dyn Error>> {
+    let log_path = target_dir().join("ReplayApp_inputs.log");
+    let record_option = format!("--record-inputs={}", log_path.display());
+    let replay_option = format!("--replay-inputs={}", log_path.display());
+
+    let recorded = run_app("ReplayApp", &[&record_option])?;
+    assert!(recorded.status.success());
+    // Make sure the live clocks have moved on, so a replay that didn't happen
+    // would be noticed.
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    let replayed = run_app("ReplayApp", &[&replay_option])?;
+    assert!(replayed.status.success());
+
+    assert!(!recorded.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8_lossy(&recorded.stdout),
+        String::from_utf8_lossy(&replayed.stdout)
+    );
+
     Ok(())
 }